@@ -48,3 +48,43 @@ for ua_file in "${UA_FILES[@]}"; do
 done
 
 echo "All $count tests done."
+
+# Parsing and emission throughput on a large program (dead code elimination
+# is off, as it dominates otherwise). Pass a git revision to time it against
+# the compiler built from that revision as well. Unwind information stays on,
+# as by default; it takes about a third of the time here.
+BIG="$OUTDIR/big.ua"
+{
+  echo "section .text"
+  echo "global _start"
+  echo "_start:"
+  for i in $(seq 1 50000); do
+    echo "block_$i:"
+    echo "    mov r0, $i"
+    echo "    add r1, r0"
+    echo "    cmp r1, 1000"
+    echo "    jl block_$i"
+  done
+  echo "    ret"
+} > "$BIG"
+
+COMPILERS=("./target/release/uac")
+if [ $# -ge 1 ]; then
+  BASE=$(mktemp -d)
+  git worktree add --force --detach "$BASE" "$1" >/dev/null
+  (cd "$BASE" && cargo build --release --quiet)
+  COMPILERS+=("$BASE/target/release/uac")
+fi
+
+TIMEFORMAT="    %R s"
+for compiler in "${COMPILERS[@]}"; do
+  for target in amd64_linux arm64_linux; do
+    echo "  $compiler, $target, $(wc -l < "$BIG") lines"
+    time "$compiler" "$BIG" -o "$OUTDIR/big.s" -t "$target" -s --keep-dead
+  done
+done
+rm -f "$BIG" "$OUTDIR/big.s"
+
+if [ $# -ge 1 ]; then
+  git worktree remove --force "$BASE"
+fi
@@ -3,6 +3,8 @@ use std::collections::HashMap;
 
 pub struct AMD32CodeGen {
     register_map: HashMap<String, String>,
+    /// Symbols of the program, once code is position-independent.
+    pic: Option<Pic>,
 }
 
 impl AMD32CodeGen {
@@ -43,7 +45,84 @@ impl AMD32CodeGen {
         register_map.insert("sb".to_string(), "ebp".to_string());
         register_map.insert("ip".to_string(), "eip".to_string());
 
-        AMD32CodeGen { register_map }
+        AMD32CodeGen {
+            register_map,
+            pic: None,
+        }
+    }
+
+    /// The address of the GOT in `dst`. There is no eip-relative addressing,
+    /// so it is found from the return address of a call to the next line.
+    fn got(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
+            "    call 1f\n1:\n    pop {}\n    add {}, offset _GLOBAL_OFFSET_TABLE_ + 1\n",
+            dst,
+            dst
+        );
+    }
+
+    /// The address of `symbol + offset` in `dst`: relative to the GOT, or
+    /// loaded from it if another module may provide the symbol.
+    fn address(&self, out: &mut String, pic: &Pic, dst: &str, symbol: &str, offset: i64) {
+        self.got(out, dst);
+        if pic.is_preemptible(symbol) {
+            emit!(
+                out,
+                "    mov {}, DWORD PTR [{} + {}@GOT]\n",
+                dst,
+                dst,
+                symbol
+            );
+            if offset != 0 {
+                emit!(out, "    lea {}, [{}]\n", dst, Pic::expression(dst, offset));
+            }
+        } else {
+            emit!(
+                out,
+                "    lea {}, [{} + {}]\n",
+                dst,
+                dst,
+                Pic::expression(&format!("{}@GOTOFF", symbol), offset)
+            );
+        }
+    }
+
+    /// `call` or `jmp` to `target`, through the PLT if another module may
+    /// provide it. PLT entries expect the GOT in ebx.
+    fn transfer(&self, out: &mut String, mnemonic: &str, target: &str) {
+        match &self.pic {
+            Some(pic) if pic.is_preemptible(target) => {
+                self.got(out, "ebx");
+                emit!(out, "    {} {}@PLT\n", mnemonic, target);
+            }
+            _ => emit!(out, "    {} {}\n", mnemonic, target),
+        }
+    }
+
+    /// Signed division through edx:eax, leaving the quotient, or the
+    /// `remainder`, in dst. edx is saved around it if `save_edx` is set
+    /// and it is not an operand.
+    fn divide(&self, out: &mut String, dst: &str, src: &str, remainder: bool, save_edx: bool) {
+        let dst_reg = self.map_operand(dst);
+        let src_op = self.map_operand(src);
+
+        let need_save_edx = save_edx && dst_reg != "edx" && src_op != "edx";
+        if need_save_edx {
+            out.push_str("    push edx\n");
+        }
+        if remainder || dst_reg != "eax" {
+            emit!(out, "    mov eax, {}\n", dst_reg);
+        }
+        out.push_str("    cdq\n");
+        emit!(out, "    idiv {}\n", src_op);
+        let source = if remainder { "edx" } else { "eax" };
+        if dst_reg != source {
+            emit!(out, "    mov {}, {}\n", dst_reg, source);
+        }
+        if need_save_edx {
+            out.push_str("    pop edx\n");
+        }
     }
 }
 
@@ -56,206 +135,260 @@ impl ArchCodeGen for AMD32CodeGen {
         ".intel_syntax noprefix\n.text\n\n".to_string()
     }
 
-    fn generate_mov(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_mov(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(pic) = &self.pic
+            && !src.starts_with('[')
+            && let Some((symbol, offset)) = pic.address(src)
+        {
+            self.address(out, pic, &self.map_operand(dst), symbol, offset);
+            return;
+        }
+        emit!(
+            out,
             "    mov {}, {}\n",
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
 
-    fn generate_lea(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_lea(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(src)
+        {
+            self.address(out, pic, &self.map_operand(dst), symbol, offset);
+            return;
+        }
+        emit!(
+            out,
             "    lea {}, {}\n",
             self.map_operand(dst),
             self.map_memory_operand(src)
-        )
+        );
     }
 
-    fn generate_load(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_load(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(src)
+        {
+            let dst = self.map_operand(dst);
+            if !pic.is_preemptible(symbol) {
+                self.got(out, &dst);
+                emit!(
+                    out,
+                    "    mov {}, DWORD PTR [{} + {}]\n",
+                    dst,
+                    dst,
+                    Pic::expression(&format!("{}@GOTOFF", symbol), offset)
+                );
+                return;
+            }
+            self.address(out, pic, &dst, symbol, 0);
+            emit!(
+                out,
+                "    mov {}, DWORD PTR [{}]\n",
+                dst,
+                Pic::expression(&dst, offset)
+            );
+            return;
+        }
+        emit!(
+            out,
             "    mov {}, DWORD PTR {}\n",
             self.map_operand(dst),
             self.map_memory_operand(src)
-        )
+        );
     }
 
-    fn generate_store(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_store(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(dst)
+        {
+            let src = self.map_operand(src);
+            // The address goes in a scratch register saved around the store.
+            let scratch = if src == "eax" { "ecx" } else { "eax" };
+            emit!(out, "    push {}\n", scratch);
+            let target = if pic.is_preemptible(symbol) {
+                self.address(out, pic, scratch, symbol, 0);
+                Pic::expression(scratch, offset)
+            } else {
+                self.got(out, scratch);
+                let target = Pic::expression(&format!("{}@GOTOFF", symbol), offset);
+                format!("{} + {}", scratch, target)
+            };
+            emit!(
+                out,
+                "    mov DWORD PTR [{}], {}\n    pop {}\n",
+                target,
+                src,
+                scratch
+            );
+            return;
+        }
+        emit!(
+            out,
             "    mov DWORD PTR {}, {}\n",
             self.map_memory_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
 
-    fn generate_add(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_add(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    add {}, {}\n",
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
 
-    fn generate_sub(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_sub(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    sub {}, {}\n",
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
 
-    fn generate_mul(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_mul(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    imul {}, {}\n",
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
 
-    fn generate_div(&self, dst: &str, src: &str) -> String {
-        let dst_reg = self.map_operand(dst);
-        let src_op = self.map_operand(src);
-        let mut result = String::new();
-
-        let need_save_edx = dst_reg != "edx" && src_op != "edx";
-        if need_save_edx {
-            result.push_str("    push edx\n");
-        }
-
-        if dst_reg != "eax" {
-            result.push_str(&format!("    mov eax, {}\n", dst_reg));
-        }
-        result.push_str("    cdq\n"); // Sign extend eax to edx:eax
-        result.push_str(&format!("    idiv {}\n", src_op));
-        if dst_reg != "eax" {
-            result.push_str(&format!("    mov {}, eax\n", dst_reg));
-        }
-        if need_save_edx {
-            result.push_str("    pop edx\n");
-        }
-
-        result
+    fn generate_div(&self, out: &mut String, dst: &str, src: &str) {
+        self.divide(out, dst, src, false, true);
     }
 
-    fn generate_inc(&self, dst: &str) -> String {
-        format!("    inc {}\n", self.map_operand(dst))
+    fn generate_inc(&self, out: &mut String, dst: &str) {
+        emit!(out, "    inc {}\n", self.map_operand(dst));
     }
 
-    fn generate_dec(&self, dst: &str) -> String {
-        format!("    dec {}\n", self.map_operand(dst))
+    fn generate_dec(&self, out: &mut String, dst: &str) {
+        emit!(out, "    dec {}\n", self.map_operand(dst));
     }
 
-    fn generate_neg(&self, dst: &str) -> String {
-        format!("    neg {}\n", self.map_operand(dst))
+    fn generate_neg(&self, out: &mut String, dst: &str) {
+        emit!(out, "    neg {}\n", self.map_operand(dst));
     }
 
-    fn generate_and(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_and(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    and {}, {}\n",
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
 
-    fn generate_or(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_or(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    or {}, {}\n",
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
 
-    fn generate_xor(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_xor(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    xor {}, {}\n",
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
 
-    fn generate_not(&self, dst: &str) -> String {
-        format!("    not {}\n", self.map_operand(dst))
+    fn generate_not(&self, out: &mut String, dst: &str) {
+        emit!(out, "    not {}\n", self.map_operand(dst));
     }
 
-    fn generate_shl(&self, dst: &str, src: &str) -> String {
+    fn generate_shl(&self, out: &mut String, dst: &str, src: &str) {
         let src_op = self.map_operand(src);
         if src_op != "cl" && !src_op.chars().all(|c| c.is_ascii_digit()) {
-            format!(
+            emit!(
+                out,
                 "    mov cl, {}\n    shl {}, cl\n",
                 src_op,
                 self.map_operand(dst)
-            )
+            );
         } else {
-            format!("    shl {}, {}\n", self.map_operand(dst), src_op)
+            emit!(out, "    shl {}, {}\n", self.map_operand(dst), src_op);
         }
     }
 
-    fn generate_shr(&self, dst: &str, src: &str) -> String {
+    fn generate_shr(&self, out: &mut String, dst: &str, src: &str) {
         let src_op = self.map_operand(src);
         if src_op != "cl" && !src_op.chars().all(|c| c.is_ascii_digit()) {
-            format!(
+            emit!(
+                out,
                 "    mov cl, {}\n    shr {}, cl\n",
                 src_op,
                 self.map_operand(dst)
-            )
+            );
         } else {
-            format!("    shr {}, {}\n", self.map_operand(dst), src_op)
+            emit!(out, "    shr {}, {}\n", self.map_operand(dst), src_op);
         }
     }
 
-    fn generate_cmp(&self, op1: &str, op2: &str) -> String {
-        format!(
+    fn generate_cmp(&self, out: &mut String, op1: &str, op2: &str) {
+        emit!(
+            out,
             "    cmp {}, {}\n",
             self.map_operand(op1),
             self.map_operand(op2)
-        )
+        );
     }
 
-    fn generate_test(&self, op1: &str, op2: &str) -> String {
-        format!(
+    fn generate_test(&self, out: &mut String, op1: &str, op2: &str) {
+        emit!(
+            out,
             "    test {}, {}\n",
             self.map_operand(op1),
             self.map_operand(op2)
-        )
+        );
     }
 
-    fn generate_jmp(&self, label: &str) -> String {
-        format!("    jmp {}\n", label)
+    fn generate_jmp(&self, out: &mut String, label: &str) {
+        self.transfer(out, "jmp", label);
     }
 
-    fn generate_je(&self, label: &str) -> String {
-        format!("    je {}\n", label)
+    fn generate_je(&self, out: &mut String, label: &str) {
+        emit!(out, "    je {}\n", label);
     }
 
-    fn generate_jne(&self, label: &str) -> String {
-        format!("    jne {}\n", label)
+    fn generate_jne(&self, out: &mut String, label: &str) {
+        emit!(out, "    jne {}\n", label);
     }
 
-    fn generate_jg(&self, label: &str) -> String {
-        format!("    jg {}\n", label)
+    fn generate_jg(&self, out: &mut String, label: &str) {
+        emit!(out, "    jg {}\n", label);
     }
 
-    fn generate_jl(&self, label: &str) -> String {
-        format!("    jl {}\n", label)
+    fn generate_jl(&self, out: &mut String, label: &str) {
+        emit!(out, "    jl {}\n", label);
     }
 
-    fn generate_jge(&self, label: &str) -> String {
-        format!("    jge {}\n", label)
+    fn generate_jge(&self, out: &mut String, label: &str) {
+        emit!(out, "    jge {}\n", label);
     }
 
-    fn generate_jle(&self, label: &str) -> String {
-        format!("    jle {}\n", label)
+    fn generate_jle(&self, out: &mut String, label: &str) {
+        emit!(out, "    jle {}\n", label);
     }
 
-    fn generate_call(&self, func: &str) -> String {
-        format!("    call {}\n", func)
+    fn generate_call(&self, out: &mut String, func: &str) {
+        self.transfer(out, "call", func);
     }
 
-    fn generate_ret(&self) -> String {
-        "    ret\n".to_string()
+    fn generate_ret(&self, out: &mut String) {
+        out.push_str("    ret\n");
     }
 
-    fn generate_syscall(&self, name: &str) -> String {
+    fn generate_syscall(&self, out: &mut String, name: &str) {
         // 32-bit Linux syscalls use int 0x80
         let syscall_num = match name {
             "read" => "3",
@@ -267,673 +400,787 @@ impl ArchCodeGen for AMD32CodeGen {
             "munmap" => "91",
             "brk" => "45",
             _ => {
-                return format!(
+                emit!(
+                    out,
                     "    # Unknown syscall: {}\n    mov eax, 0\n    int 0x80\n",
                     name
                 );
+                return;
             }
         };
-        format!("    mov eax, {}\n    int 0x80\n", syscall_num)
+        emit!(out, "    mov eax, {}\n    int 0x80\n", syscall_num);
     }
 
     // Conditional Moves (Pentium Pro+)
-    fn generate_cmov_eq(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_eq(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len()) % 10000;
-            format!(
+            emit!(
+                out,
                 "    je .Lcmove_set_{}\n    jmp .Lcmove_end_{}\n.Lcmove_set_{}:\n    mov {}, {}\n.Lcmove_end_{}:\n",
-                hash, hash, hash, dst_reg, src_op, hash
-            )
+                hash,
+                hash,
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    cmove {}, {}\n", dst_reg, src_op)
+            emit!(out, "    cmove {}, {}\n", dst_reg, src_op);
         }
     }
-    fn generate_cmov_ne(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_ne(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 1) % 10000;
-            format!(
+            emit!(
+                out,
                 "    jne .Lcmovne_set_{}\n    jmp .Lcmovne_end_{}\n.Lcmovne_set_{}:\n    mov {}, {}\n.Lcmovne_end_{}:\n",
-                hash, hash, hash, dst_reg, src_op, hash
-            )
+                hash,
+                hash,
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    cmovne {}, {}\n", dst_reg, src_op)
+            emit!(out, "    cmovne {}, {}\n", dst_reg, src_op);
         }
     }
-    fn generate_cmov_lt(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_lt(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 2) % 10000;
-            format!(
+            emit!(
+                out,
                 "    jl .Lcmovl_set_{}\n    jmp .Lcmovl_end_{}\n.Lcmovl_set_{}:\n    mov {}, {}\n.Lcmovl_end_{}:\n",
-                hash, hash, hash, dst_reg, src_op, hash
-            )
+                hash,
+                hash,
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    cmovl {}, {}\n", dst_reg, src_op)
+            emit!(out, "    cmovl {}, {}\n", dst_reg, src_op);
         }
     }
-    fn generate_cmov_le(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_le(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 3) % 10000;
-            format!(
+            emit!(
+                out,
                 "    jle .Lcmovle_set_{}\n    jmp .Lcmovle_end_{}\n.Lcmovle_set_{}:\n    mov {}, {}\n.Lcmovle_end_{}:\n",
-                hash, hash, hash, dst_reg, src_op, hash
-            )
+                hash,
+                hash,
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    cmovle {}, {}\n", dst_reg, src_op)
+            emit!(out, "    cmovle {}, {}\n", dst_reg, src_op);
         }
     }
-    fn generate_cmov_gt(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_gt(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 4) % 10000;
-            format!(
+            emit!(
+                out,
                 "    jg .Lcmovg_set_{}\n    jmp .Lcmovg_end_{}\n.Lcmovg_set_{}:\n    mov {}, {}\n.Lcmovg_end_{}:\n",
-                hash, hash, hash, dst_reg, src_op, hash
-            )
+                hash,
+                hash,
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    cmovg {}, {}\n", dst_reg, src_op)
+            emit!(out, "    cmovg {}, {}\n", dst_reg, src_op);
         }
     }
-    fn generate_cmov_ge(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_ge(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 5) % 10000;
-            format!(
+            emit!(
+                out,
                 "    jge .Lcmovge_set_{}\n    jmp .Lcmovge_end_{}\n.Lcmovge_set_{}:\n    mov {}, {}\n.Lcmovge_end_{}:\n",
-                hash, hash, hash, dst_reg, src_op, hash
-            )
+                hash,
+                hash,
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    cmovge {}, {}\n", dst_reg, src_op)
+            emit!(out, "    cmovge {}, {}\n", dst_reg, src_op);
         }
     }
 
     // Stack
-    fn generate_push(&self, src: &str) -> String {
-        format!("    push {}\n", self.map_operand(src))
+    fn generate_push(&self, out: &mut String, src: &str) {
+        emit!(out, "    push {}\n", self.map_operand(src));
     }
-    fn generate_pop(&self, dst: &str) -> String {
-        format!("    pop {}\n", self.map_operand(dst))
+    fn generate_pop(&self, out: &mut String, dst: &str) {
+        emit!(out, "    pop {}\n", self.map_operand(dst));
     }
 
     // Data Section
-    fn generate_global(&self, symbol: &str) -> String {
-        format!(".global {}\n", symbol)
+    fn generate_global(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".global {}\n", symbol);
     }
-    fn generate_extern(&self, symbol: &str) -> String {
-        format!(".extern {}\n", symbol)
+    fn generate_extern(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".extern {}\n", symbol);
     }
-    fn generate_align(&self, n: &str) -> String {
-        format!(".p2align {}\n", n)
+    fn generate_align(&self, out: &mut String, n: &str) {
+        emit!(out, ".p2align {}\n", n);
     }
-    fn generate_data_byte(&self, name: &str, values: &[String]) -> String {
-        format!("{}: .byte {}\n", name, values.join(", "))
+    fn generate_data_byte(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .byte {}\n", name, values.join(", "));
     }
-    fn generate_data_word(&self, name: &str, values: &[String]) -> String {
-        format!("{}: .word {}\n", name, values.join(", "))
+    fn generate_data_word(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .word {}\n", name, values.join(", "));
     }
-    fn generate_data_dword(&self, name: &str, values: &[String]) -> String {
-        format!("{}: .long {}\n", name, values.join(", "))
+    fn generate_data_dword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .long {}\n", name, values.join(", "));
     }
-    fn generate_data_qword(&self, name: &str, values: &[String]) -> String {
+    fn generate_data_qword(&self, out: &mut String, name: &str, values: &[String]) {
         // In 32-bit, qword is still supported but less common
-        format!("{}: .quad {}\n", name, values.join(", "))
+        emit!(out, "{}: .quad {}\n", name, values.join(", "));
     }
-    fn generate_reserve_byte(&self, name: &str, count: &str) -> String {
-        format!("{}: .skip {}, 0\n", name, count)
+    fn generate_reserve_byte(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip {}, 0\n", name, count);
     }
-    fn generate_reserve_word(&self, name: &str, count: &str) -> String {
-        format!("{}: .skip {}, 0\n", name, count)
+    fn generate_reserve_word(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip {}, 0\n", name, count);
     }
-    fn generate_reserve_dword(&self, name: &str, count: &str) -> String {
+    fn generate_reserve_dword(&self, out: &mut String, name: &str, count: &str) {
         // Each dword: 4 bytes
-        format!(
+        emit!(
+            out,
             "{}: .skip {}, 0\n",
             name,
             4 * count.parse::<usize>().unwrap_or(1)
-        )
+        );
     }
-    fn generate_reserve_qword(&self, name: &str, count: &str) -> String {
+    fn generate_reserve_qword(&self, out: &mut String, name: &str, count: &str) {
         // Each qword: 8 bytes
-        format!(
+        emit!(
+            out,
             "{}: .skip {}, 0\n",
             name,
             8 * count.parse::<usize>().unwrap_or(1)
-        )
+        );
     }
-    fn generate_equ(&self, name: &str, value: &str) -> String {
-        format!("{} = {}\n", name, value)
+    fn generate_equ(&self, out: &mut String, name: &str, value: &str) {
+        emit!(out, "{} = {}\n", name, value);
     }
 
-    fn generate_section(&self, section: &Section) -> String {
+    fn generate_section(&self, out: &mut String, section: &Section) {
         match section {
-            Section::Text => ".section .text\n".to_string(),
-            Section::Data => ".section .data\n".to_string(),
-            Section::Bss => ".section .bss\n".to_string(),
-            Section::Rodata => ".section .rodata\n".to_string(),
-            Section::Custom(s) => format!(".section {}\n", s),
+            Section::Text => out.push_str(".section .text\n"),
+            Section::Data => out.push_str(".section .data\n"),
+            Section::Bss => out.push_str(".section .bss\n"),
+            Section::Rodata => out.push_str(".section .rodata\n"),
+            Section::Custom(s) => emit!(out, ".section {}\n", s),
         }
     }
 
-    fn generate_in(&self, dst: &str, port: &str) -> String {
-        format!(
+    fn generate_in(&self, out: &mut String, dst: &str, port: &str) {
+        emit!(
+            out,
             "    in {}, {}\n",
             self.map_operand(dst),
             self.map_operand(port)
-        )
+        );
     }
-    fn generate_out(&self, port: &str, src: &str) -> String {
-        format!(
+    fn generate_out(&self, out: &mut String, port: &str, src: &str) {
+        emit!(
+            out,
             "    out {}, {}\n",
             self.map_operand(port),
             self.map_operand(src)
-        )
+        );
     }
-    fn generate_ins(&self, dst: &str, port: &str) -> String {
-        format!(
+    fn generate_ins(&self, out: &mut String, dst: &str, port: &str) {
+        emit!(
+            out,
             "    insd {}, {}\n",
             self.map_operand(dst),
             self.map_operand(port)
-        )
+        );
     }
-    fn generate_outs(&self, port: &str, src: &str) -> String {
-        format!(
+    fn generate_outs(&self, out: &mut String, port: &str, src: &str) {
+        emit!(
+            out,
             "    outsd {}, {}\n",
             self.map_operand(port),
             self.map_operand(src)
-        )
+        );
     }
 
-    fn generate_sal(&self, dst: &str, src: &str) -> String {
+    fn generate_sal(&self, out: &mut String, dst: &str, src: &str) {
         // synonym for SHL
-        self.generate_shl(dst, src)
+        self.generate_shl(out, dst, src);
     }
-    fn generate_sar(&self, dst: &str, src: &str) -> String {
+    fn generate_sar(&self, out: &mut String, dst: &str, src: &str) {
         let src_op = self.map_operand(src);
         if src_op != "cl" && !src_op.chars().all(|c| c.is_ascii_digit()) {
-            format!(
+            emit!(
+                out,
                 "    mov cl, {}\n    sar {}, cl\n",
                 src_op,
                 self.map_operand(dst)
-            )
+            );
         } else {
-            format!("    sar {}, {}\n", self.map_operand(dst), src_op)
+            emit!(out, "    sar {}, {}\n", self.map_operand(dst), src_op);
         }
     }
-    fn generate_rol(&self, dst: &str, src: &str) -> String {
+    fn generate_rol(&self, out: &mut String, dst: &str, src: &str) {
         let src_op = self.map_operand(src);
         if src_op != "cl" && !src_op.chars().all(|c| c.is_ascii_digit()) {
-            format!(
+            emit!(
+                out,
                 "    mov cl, {}\n    rol {}, cl\n",
                 src_op,
                 self.map_operand(dst)
-            )
+            );
         } else {
-            format!("    rol {}, {}\n", self.map_operand(dst), src_op)
+            emit!(out, "    rol {}, {}\n", self.map_operand(dst), src_op);
         }
     }
-    fn generate_ror(&self, dst: &str, src: &str) -> String {
+    fn generate_ror(&self, out: &mut String, dst: &str, src: &str) {
         let src_op = self.map_operand(src);
         if src_op != "cl" && !src_op.chars().all(|c| c.is_ascii_digit()) {
-            format!(
+            emit!(
+                out,
                 "    mov cl, {}\n    ror {}, cl\n",
                 src_op,
                 self.map_operand(dst)
-            )
+            );
         } else {
-            format!("    ror {}, {}\n", self.map_operand(dst), src_op)
+            emit!(out, "    ror {}, {}\n", self.map_operand(dst), src_op);
         }
     }
-    fn generate_rcl(&self, dst: &str, src: &str) -> String {
+    fn generate_rcl(&self, out: &mut String, dst: &str, src: &str) {
         let src_op = self.map_operand(src);
         if src_op != "cl" && !src_op.chars().all(|c| c.is_ascii_digit()) {
-            format!(
+            emit!(
+                out,
                 "    mov cl, {}\n    rcl {}, cl\n",
                 src_op,
                 self.map_operand(dst)
-            )
+            );
         } else {
-            format!("    rcl {}, {}\n", self.map_operand(dst), src_op)
+            emit!(out, "    rcl {}, {}\n", self.map_operand(dst), src_op);
         }
     }
-    fn generate_rcr(&self, dst: &str, src: &str) -> String {
+    fn generate_rcr(&self, out: &mut String, dst: &str, src: &str) {
         let src_op = self.map_operand(src);
         if src_op != "cl" && !src_op.chars().all(|c| c.is_ascii_digit()) {
-            format!(
+            emit!(
+                out,
                 "    mov cl, {}\n    rcr {}, cl\n",
                 src_op,
                 self.map_operand(dst)
-            )
+            );
         } else {
-            format!("    rcr {}, {}\n", self.map_operand(dst), src_op)
+            emit!(out, "    rcr {}, {}\n", self.map_operand(dst), src_op);
         }
     }
 
-    fn generate_imul(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_imul(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    imul {}, {}\n",
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
-    fn generate_idiv(&self, dst: &str, src: &str) -> String {
-        // Same pattern as generate_div:
+    fn generate_idiv(&self, out: &mut String, dst: &str, src: &str) {
+        self.divide(out, dst, src, false, true);
+    }
+    fn generate_mod(&self, out: &mut String, dst: &str, src: &str) {
+        self.divide(out, dst, src, true, true);
+    }
+
+    fn generate_mulh(&self, out: &mut String, dst: &str, src: &str) {
+        // One-operand imul leaves the high half in edx; eax and edx are
+        // preserved unless one of them is the destination. src must be a register.
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
-        let mut result = String::new();
-        let need_save_edx = dst_reg != "edx" && src_op != "edx";
-        if need_save_edx {
-            result.push_str("    push edx\n");
+
+        if dst_reg != "edx" {
+            out.push_str("    push edx\n");
         }
         if dst_reg != "eax" {
-            result.push_str(&format!("    mov eax, {}\n", dst_reg));
+            out.push_str("    push eax\n");
         }
-        result.push_str("    cdq\n");
-        result.push_str(&format!("    idiv {}\n", src_op));
-        if dst_reg != "eax" {
-            result.push_str(&format!("    mov {}, eax\n", dst_reg));
+        if src_op == "eax" {
+            emit!(out, "    imul {}\n", dst_reg);
+        } else {
+            if dst_reg != "eax" {
+                emit!(out, "    mov eax, {}\n", dst_reg);
+            }
+            emit!(out, "    imul {}\n", src_op);
         }
-        if need_save_edx {
-            result.push_str("    pop edx\n");
+        if dst_reg != "edx" {
+            emit!(out, "    mov {}, edx\n", dst_reg);
         }
-        result
-    }
-    fn generate_mod(&self, dst: &str, src: &str) -> String {
-        // Store result (remainder) in dst, like idiv but copy edx to dst
-        let dst_reg = self.map_operand(dst);
-        let src_op = self.map_operand(src);
-        let mut result = String::new();
-        let need_save_edx = dst_reg != "edx" && src_op != "edx";
-        if need_save_edx {
-            result.push_str("    push edx\n");
+        if dst_reg != "eax" {
+            out.push_str("    pop eax\n");
         }
-        result.push_str(&format!("    mov eax, {}\n", dst_reg));
-        result.push_str("    cdq\n");
-        result.push_str(&format!("    idiv {}\n", src_op));
         if dst_reg != "edx" {
-            result.push_str(&format!("    mov {}, edx\n", dst_reg));
+            out.push_str("    pop edx\n");
         }
-        if need_save_edx {
-            result.push_str("    pop edx\n");
-        }
-        result
     }
 
-    fn generate_cmov_ov(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_ov(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 6) % 10000;
-            format!(
+            emit!(
+                out,
                 "    jo .Lcmovo_set_{}\n    jmp .Lcmovo_end_{}\n.Lcmovo_set_{}:\n    mov {}, {}\n.Lcmovo_end_{}:\n",
-                hash, hash, hash, dst_reg, src_op, hash
-            )
+                hash,
+                hash,
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    cmovo {}, {}\n", dst_reg, src_op)
+            emit!(out, "    cmovo {}, {}\n", dst_reg, src_op);
         }
     }
-    fn generate_cmov_no(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_no(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 7) % 10000;
-            format!(
+            emit!(
+                out,
                 "    jno .Lcmovno_set_{}\n    jmp .Lcmovno_end_{}\n.Lcmovno_set_{}:\n    mov {}, {}\n.Lcmovno_end_{}:\n",
-                hash, hash, hash, dst_reg, src_op, hash
-            )
+                hash,
+                hash,
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    cmovno {}, {}\n", dst_reg, src_op)
+            emit!(out, "    cmovno {}, {}\n", dst_reg, src_op);
         }
     }
-    fn generate_cmov_s(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_s(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 8) % 10000;
-            format!(
+            emit!(
+                out,
                 "    js .Lcmovs_set_{}\n    jmp .Lcmovs_end_{}\n.Lcmovs_set_{}:\n    mov {}, {}\n.Lcmovs_end_{}:\n",
-                hash, hash, hash, dst_reg, src_op, hash
-            )
+                hash,
+                hash,
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    cmovs {}, {}\n", dst_reg, src_op)
+            emit!(out, "    cmovs {}, {}\n", dst_reg, src_op);
         }
     }
-    fn generate_cmov_ns(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_ns(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 9) % 10000;
-            format!(
+            emit!(
+                out,
                 "    jns .Lcmovns_set_{}\n    jmp .Lcmovns_end_{}\n.Lcmovns_set_{}:\n    mov {}, {}\n.Lcmovns_end_{}:\n",
-                hash, hash, hash, dst_reg, src_op, hash
-            )
+                hash,
+                hash,
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    cmovns {}, {}\n", dst_reg, src_op)
+            emit!(out, "    cmovns {}, {}\n", dst_reg, src_op);
         }
     }
-    fn generate_cmov_p(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_p(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 10) % 10000;
-            format!(
+            emit!(
+                out,
                 "    jp .Lcmovp_set_{}\n    jmp .Lcmovp_end_{}\n.Lcmovp_set_{}:\n    mov {}, {}\n.Lcmovp_end_{}:\n",
-                hash, hash, hash, dst_reg, src_op, hash
-            )
+                hash,
+                hash,
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    cmovp {}, {}\n", dst_reg, src_op)
+            emit!(out, "    cmovp {}, {}\n", dst_reg, src_op);
         }
     }
-    fn generate_cmov_np(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_np(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 11) % 10000;
-            format!(
+            emit!(
+                out,
                 "    jnp .Lcmovnp_set_{}\n    jmp .Lcmovnp_end_{}\n.Lcmovnp_set_{}:\n    mov {}, {}\n.Lcmovnp_end_{}:\n",
-                hash, hash, hash, dst_reg, src_op, hash
-            )
+                hash,
+                hash,
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    cmovnp {}, {}\n", dst_reg, src_op)
+            emit!(out, "    cmovnp {}, {}\n", dst_reg, src_op);
         }
     }
-    fn generate_cmov_a(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_a(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 12) % 10000;
-            format!(
+            emit!(
+                out,
                 "    ja .Lcmova_set_{}\n    jmp .Lcmova_end_{}\n.Lcmova_set_{}:\n    mov {}, {}\n.Lcmova_end_{}:\n",
-                hash, hash, hash, dst_reg, src_op, hash
-            )
+                hash,
+                hash,
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    cmova {}, {}\n", dst_reg, src_op)
+            emit!(out, "    cmova {}, {}\n", dst_reg, src_op);
         }
     }
-    fn generate_cmov_ae(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_ae(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 13) % 10000;
-            format!(
+            emit!(
+                out,
                 "    jae .Lcmovae_set_{}\n    jmp .Lcmovae_end_{}\n.Lcmovae_set_{}:\n    mov {}, {}\n.Lcmovae_end_{}:\n",
-                hash, hash, hash, dst_reg, src_op, hash
-            )
+                hash,
+                hash,
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    cmovae {}, {}\n", dst_reg, src_op)
+            emit!(out, "    cmovae {}, {}\n", dst_reg, src_op);
         }
     }
-    fn generate_cmov_b(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_b(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 14) % 10000;
-            format!(
+            emit!(
+                out,
                 "    jb .Lcmovb_set_{}\n    jmp .Lcmovb_end_{}\n.Lcmovb_set_{}:\n    mov {}, {}\n.Lcmovb_end_{}:\n",
-                hash, hash, hash, dst_reg, src_op, hash
-            )
+                hash,
+                hash,
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    cmovb {}, {}\n", dst_reg, src_op)
+            emit!(out, "    cmovb {}, {}\n", dst_reg, src_op);
         }
     }
-    fn generate_cmov_be(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_be(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 15) % 10000;
-            format!(
+            emit!(
+                out,
                 "    jbe .Lcmovbe_set_{}\n    jmp .Lcmovbe_end_{}\n.Lcmovbe_set_{}:\n    mov {}, {}\n.Lcmovbe_end_{}:\n",
-                hash, hash, hash, dst_reg, src_op, hash
-            )
+                hash,
+                hash,
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    cmovbe {}, {}\n", dst_reg, src_op)
+            emit!(out, "    cmovbe {}, {}\n", dst_reg, src_op);
         }
     }
 
-    fn generate_pusha(&self) -> String {
+    fn generate_pusha(&self, out: &mut String) {
         // PUSHA pushes all general-purpose registers
-        "    pusha\n".to_string()
+        out.push_str("    pusha\n");
     }
-    fn generate_popa(&self) -> String {
+    fn generate_popa(&self, out: &mut String) {
         // POPA pops all general-purpose registers
-        "    popa\n".to_string()
+        out.push_str("    popa\n");
     }
 
-    fn generate_enter(&self, frame_size: &str, nesting_level: &str) -> String {
-        format!("    enter {}, {}\n", frame_size, nesting_level)
+    fn generate_enter(&self, out: &mut String, frame_size: &str, nesting_level: &str) {
+        emit!(out, "    enter {}, {}\n", frame_size, nesting_level);
     }
-    fn generate_leave(&self) -> String {
-        "    leave\n".to_string()
+    fn generate_leave(&self, out: &mut String) {
+        out.push_str("    leave\n");
     }
 
     // Most advanced instructions are not available in 32-bit or have limited support
-    fn generate_andn(&self, dst: &str, src: &str) -> String {
+    fn generate_andn(&self, out: &mut String, dst: &str, src: &str) {
         // BMI1 not typically available in 32-bit, simulate with NOT + AND
-        format!(
+        emit!(
+            out,
             "    mov {}, {}\n    not {}\n    and {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst),
             self.map_operand(dst),
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
 
-    fn generate_bextr(&self, dst: &str, src: &str, _imm: &str) -> String {
+    fn generate_bextr(&self, out: &mut String, dst: &str, src: &str, _imm: &str) {
         // Not available in 32-bit, provide comment
-        format!(
+        emit!(
+            out,
             "    # BEXTR not available in 32-bit\n    mov {}, {}\n",
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
-    fn generate_bsf(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_bsf(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    bsf {}, {}\n",
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
-    fn generate_bsr(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_bsr(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    bsr {}, {}\n",
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
-    fn generate_bt(&self, dst: &str, bit: &str) -> String {
-        format!(
+    fn generate_bt(&self, out: &mut String, dst: &str, bit: &str) {
+        emit!(
+            out,
             "    bt {}, {}\n",
             self.map_operand(dst),
             self.map_operand(bit)
-        )
+        );
     }
-    fn generate_btr(&self, dst: &str, bit: &str) -> String {
-        format!(
+    fn generate_btr(&self, out: &mut String, dst: &str, bit: &str) {
+        emit!(
+            out,
             "    btr {}, {}\n",
             self.map_operand(dst),
             self.map_operand(bit)
-        )
+        );
     }
-    fn generate_bts(&self, dst: &str, bit: &str) -> String {
-        format!(
+    fn generate_bts(&self, out: &mut String, dst: &str, bit: &str) {
+        emit!(
+            out,
             "    bts {}, {}\n",
             self.map_operand(dst),
             self.map_operand(bit)
-        )
+        );
     }
-    fn generate_btc(&self, dst: &str, bit: &str) -> String {
-        format!(
+    fn generate_btc(&self, out: &mut String, dst: &str, bit: &str) {
+        emit!(
+            out,
             "    btc {}, {}\n",
             self.map_operand(dst),
             self.map_operand(bit)
-        )
+        );
     }
 
-    fn generate_set_eq(&self, dst: &str) -> String {
-        format!("    setz {}\n", self.map_operand(dst))
+    fn generate_set_eq(&self, out: &mut String, dst: &str) {
+        emit!(out, "    setz {}\n", self.map_operand(dst));
     }
-    fn generate_set_ne(&self, dst: &str) -> String {
-        format!("    setnz {}\n", self.map_operand(dst))
+    fn generate_set_ne(&self, out: &mut String, dst: &str) {
+        emit!(out, "    setnz {}\n", self.map_operand(dst));
     }
-    fn generate_set_lt(&self, dst: &str) -> String {
-        format!("    setl {}\n", self.map_operand(dst))
+    fn generate_set_lt(&self, out: &mut String, dst: &str) {
+        emit!(out, "    setl {}\n", self.map_operand(dst));
     }
-    fn generate_set_le(&self, dst: &str) -> String {
-        format!("    setle {}\n", self.map_operand(dst))
+    fn generate_set_le(&self, out: &mut String, dst: &str) {
+        emit!(out, "    setle {}\n", self.map_operand(dst));
     }
-    fn generate_set_gt(&self, dst: &str) -> String {
-        format!("    setg {}\n", self.map_operand(dst))
+    fn generate_set_gt(&self, out: &mut String, dst: &str) {
+        emit!(out, "    setg {}\n", self.map_operand(dst));
     }
-    fn generate_set_ge(&self, dst: &str) -> String {
-        format!("    setge {}\n", self.map_operand(dst))
+    fn generate_set_ge(&self, out: &mut String, dst: &str) {
+        emit!(out, "    setge {}\n", self.map_operand(dst));
     }
-    fn generate_set_ov(&self, dst: &str) -> String {
-        format!("    seto {}\n", self.map_operand(dst))
+    fn generate_set_ov(&self, out: &mut String, dst: &str) {
+        emit!(out, "    seto {}\n", self.map_operand(dst));
     }
-    fn generate_set_no(&self, dst: &str) -> String {
-        format!("    setno {}\n", self.map_operand(dst))
+    fn generate_set_no(&self, out: &mut String, dst: &str) {
+        emit!(out, "    setno {}\n", self.map_operand(dst));
     }
-    fn generate_set_s(&self, dst: &str) -> String {
-        format!("    sets {}\n", self.map_operand(dst))
+    fn generate_set_s(&self, out: &mut String, dst: &str) {
+        emit!(out, "    sets {}\n", self.map_operand(dst));
     }
-    fn generate_set_ns(&self, dst: &str) -> String {
-        format!("    setns {}\n", self.map_operand(dst))
+    fn generate_set_ns(&self, out: &mut String, dst: &str) {
+        emit!(out, "    setns {}\n", self.map_operand(dst));
     }
-    fn generate_set_p(&self, dst: &str) -> String {
-        format!("    setp {}\n", self.map_operand(dst))
+    fn generate_set_p(&self, out: &mut String, dst: &str) {
+        emit!(out, "    setp {}\n", self.map_operand(dst));
     }
-    fn generate_set_np(&self, dst: &str) -> String {
-        format!("    setnp {}\n", self.map_operand(dst))
+    fn generate_set_np(&self, out: &mut String, dst: &str) {
+        emit!(out, "    setnp {}\n", self.map_operand(dst));
     }
-    fn generate_set_a(&self, dst: &str) -> String {
-        format!("    seta {}\n", self.map_operand(dst))
+    fn generate_set_a(&self, out: &mut String, dst: &str) {
+        emit!(out, "    seta {}\n", self.map_operand(dst));
     }
-    fn generate_set_ae(&self, dst: &str) -> String {
-        format!("    setae {}\n", self.map_operand(dst))
+    fn generate_set_ae(&self, out: &mut String, dst: &str) {
+        emit!(out, "    setae {}\n", self.map_operand(dst));
     }
-    fn generate_set_b(&self, dst: &str) -> String {
-        format!("    setb {}\n", self.map_operand(dst))
+    fn generate_set_b(&self, out: &mut String, dst: &str) {
+        emit!(out, "    setb {}\n", self.map_operand(dst));
     }
-    fn generate_set_be(&self, dst: &str) -> String {
-        format!("    setbe {}\n", self.map_operand(dst))
+    fn generate_set_be(&self, out: &mut String, dst: &str) {
+        emit!(out, "    setbe {}\n", self.map_operand(dst));
     }
 
-    fn generate_cmps(&self, _src1: &str, _src2: &str) -> String {
-        "    cmpsd\n".to_string()
+    fn generate_cmps(&self, out: &mut String, _src1: &str, _src2: &str) {
+        out.push_str("    cmpsd\n");
     }
-    fn generate_scas(&self, _src: &str, _val: &str) -> String {
-        "    scasd\n".to_string()
+    fn generate_scas(&self, out: &mut String, _src: &str, _val: &str) {
+        out.push_str("    scasd\n");
     }
-    fn generate_stos(&self, _dst: &str, _src: &str) -> String {
-        "    stosd\n".to_string()
+    fn generate_stos(&self, out: &mut String, _dst: &str, _src: &str) {
+        out.push_str("    stosd\n");
     }
-    fn generate_lods(&self, _dst: &str, _src: &str) -> String {
-        "    lodsd\n".to_string()
+    fn generate_lods(&self, out: &mut String, _dst: &str, _src: &str) {
+        out.push_str("    lodsd\n");
     }
-    fn generate_movs(&self, _dst: &str, _src: &str) -> String {
-        "    movsd\n".to_string()
+    fn generate_movs(&self, out: &mut String, _dst: &str, _src: &str) {
+        out.push_str("    movsd\n");
     }
 
-    fn generate_cbw(&self, _dst: &str) -> String {
-        "    cbw\n".to_string()
+    fn generate_cbw(&self, out: &mut String, _dst: &str) {
+        out.push_str("    cbw\n");
     }
-    fn generate_cwd(&self, _dst: &str) -> String {
-        "    cwd\n".to_string()
+    fn generate_cwd(&self, out: &mut String, _dst: &str) {
+        out.push_str("    cwd\n");
     }
-    fn generate_cdq(&self, _dst: &str) -> String {
-        "    cdq\n".to_string()
+    fn generate_cdq(&self, out: &mut String, _dst: &str) {
+        out.push_str("    cdq\n");
     }
-    fn generate_cqo(&self, _dst: &str) -> String {
+    fn generate_cqo(&self, out: &mut String, _dst: &str) {
         // CQO not available in 32-bit, use CDQ instead
-        "    cdq\n".to_string()
+        out.push_str("    cdq\n");
     }
-    fn generate_cwde(&self, _dst: &str) -> String {
-        "    cwde\n".to_string()
+    fn generate_cwde(&self, out: &mut String, _dst: &str) {
+        out.push_str("    cwde\n");
     }
-    fn generate_cdqe(&self, _dst: &str) -> String {
+    fn generate_cdqe(&self, out: &mut String, _dst: &str) {
         // CDQE not available in 32-bit, use CWDE instead
-        "    cwde\n".to_string()
+        out.push_str("    cwde\n");
     }
 
-    fn generate_jo(&self, label: &str) -> String {
-        format!("    jo {}\n", label)
+    fn generate_jo(&self, out: &mut String, label: &str) {
+        emit!(out, "    jo {}\n", label);
     }
-    fn generate_jno(&self, label: &str) -> String {
-        format!("    jno {}\n", label)
+    fn generate_jno(&self, out: &mut String, label: &str) {
+        emit!(out, "    jno {}\n", label);
     }
-    fn generate_js(&self, label: &str) -> String {
-        format!("    js {}\n", label)
+    fn generate_js(&self, out: &mut String, label: &str) {
+        emit!(out, "    js {}\n", label);
     }
-    fn generate_jns(&self, label: &str) -> String {
-        format!("    jns {}\n", label)
+    fn generate_jns(&self, out: &mut String, label: &str) {
+        emit!(out, "    jns {}\n", label);
     }
-    fn generate_jp(&self, label: &str) -> String {
-        format!("    jp {}\n", label)
+    fn generate_jp(&self, out: &mut String, label: &str) {
+        emit!(out, "    jp {}\n", label);
     }
-    fn generate_jnp(&self, label: &str) -> String {
-        format!("    jnp {}\n", label)
+    fn generate_jnp(&self, out: &mut String, label: &str) {
+        emit!(out, "    jnp {}\n", label);
     }
-    fn generate_ja(&self, label: &str) -> String {
-        format!("    ja {}\n", label)
+    fn generate_ja(&self, out: &mut String, label: &str) {
+        emit!(out, "    ja {}\n", label);
     }
-    fn generate_jae(&self, label: &str) -> String {
-        format!("    jae {}\n", label)
+    fn generate_jae(&self, out: &mut String, label: &str) {
+        emit!(out, "    jae {}\n", label);
     }
-    fn generate_jb(&self, label: &str) -> String {
-        format!("    jb {}\n", label)
+    fn generate_jb(&self, out: &mut String, label: &str) {
+        emit!(out, "    jb {}\n", label);
     }
-    fn generate_jbe(&self, label: &str) -> String {
-        format!("    jbe {}\n", label)
+    fn generate_jbe(&self, out: &mut String, label: &str) {
+        emit!(out, "    jbe {}\n", label);
     }
 
-    fn generate_loop_eq(&self, label: &str) -> String {
-        format!("    loope {}\n", label)
+    fn generate_loop_eq(&self, out: &mut String, label: &str) {
+        emit!(out, "    loope {}\n", label);
     }
-    fn generate_loop_ne(&self, label: &str) -> String {
-        format!("    loopne {}\n", label)
+    fn generate_loop_ne(&self, out: &mut String, label: &str) {
+        emit!(out, "    loopne {}\n", label);
     }
 
     // Utility
-    fn generate_label(&self, name: &str) -> String {
-        format!("{}:\n", name)
+    fn generate_label(&self, out: &mut String, name: &str) {
+        emit!(out, "{}:\n", name);
     }
-    fn generate_cpuid(&self) -> String {
-        "    cpuid\n".to_string()
+    fn generate_cpuid(&self, out: &mut String) {
+        out.push_str("    cpuid\n");
     }
-    fn generate_lfence(&self) -> String {
+    fn generate_lfence(&self, out: &mut String) {
         // Not available in older 32-bit processors
-        "    # lfence not available in 32-bit\n".to_string()
+        out.push_str("    # lfence not available in 32-bit\n");
     }
-    fn generate_sfence(&self) -> String {
+    fn generate_sfence(&self, out: &mut String) {
         // Not available in older 32-bit processors
-        "    # sfence not available in 32-bit\n".to_string()
+        out.push_str("    # sfence not available in 32-bit\n");
     }
-    fn generate_mfence(&self) -> String {
+    fn generate_mfence(&self, out: &mut String) {
         // Not available in older 32-bit processors
-        "    # mfence not available in 32-bit\n".to_string()
+        out.push_str("    # mfence not available in 32-bit\n");
     }
-    fn generate_prefetch(&self, addr: &str) -> String {
+    fn generate_prefetch(&self, out: &mut String, addr: &str) {
         // Limited prefetch support in 32-bit
-        format!("    # prefetch {}\n", self.map_memory_operand(addr))
+        emit!(out, "    # prefetch {}\n", self.map_memory_operand(addr));
     }
-    fn generate_clflush(&self, addr: &str) -> String {
-        format!("    clflush {}\n", self.map_memory_operand(addr))
+    fn generate_clflush(&self, out: &mut String, addr: &str) {
+        emit!(out, "    clflush {}\n", self.map_memory_operand(addr));
     }
-    fn generate_clwb(&self, addr: &str) -> String {
+    fn generate_clwb(&self, out: &mut String, addr: &str) {
         // Not available in 32-bit
-        format!(
+        emit!(
+            out,
             "    # clwb not available in 32-bit: {}\n",
             self.map_memory_operand(addr)
-        )
+        );
     }
 
     // Memory/Register mapping functions
@@ -965,10 +1212,19 @@ impl ArchCodeGen for AMD32CodeGen {
                 let parts: Vec<&str> = inner.split_whitespace().collect();
                 let mut result = String::new();
                 for (i, part) in parts.iter().enumerate() {
-                    if let Some(mapped) = self.register_map.get(*part) {
+                    // Scaled index, e.g. `r1*4`
+                    let (reg, scale) = match part.split_once('*') {
+                        Some((reg, scale)) => (reg, Some(scale)),
+                        None => (*part, None),
+                    };
+                    if let Some(mapped) = self.register_map.get(reg) {
                         result.push_str(mapped);
                     } else {
-                        result.push_str(part);
+                        result.push_str(reg);
+                    }
+                    if let Some(scale) = scale {
+                        result.push('*');
+                        result.push_str(scale);
                     }
                     if i < parts.len() - 1 {
                         result.push(' ');
@@ -984,4 +1240,97 @@ impl ArchCodeGen for AMD32CodeGen {
             operand.to_string()
         }
     }
+
+    fn get_register_width(&self) -> u32 {
+        32
+    }
+
+    fn get_reserved_registers(&self) -> Vec<String> {
+        ["esp", "ebp", "eip"]
+            .iter()
+            .map(|r| r.to_string())
+            .collect()
+    }
+
+    fn get_callee_saved_registers(&self) -> Vec<String> {
+        ["ebx", "esi", "edi", "ebp"]
+            .iter()
+            .map(|r| r.to_string())
+            .collect()
+    }
+
+    fn get_push_size(&self) -> u32 {
+        4
+    }
+
+    fn get_clobbered_registers(&self, instruction: &Instruction) -> Vec<String> {
+        use Instruction::*;
+        let registers: &[&str] = match instruction {
+            Div(_) | Idiv(_) | Mod(_) | Cbw(_) | Cwd(_) | Cdq(_) | Cqo(_) | Cwde(_) | Cdqe(_) => {
+                &["eax", "edx"]
+            }
+            // Shifts and rotates by a register count go through cl.
+            Shl((_, count)) | Shr((_, count)) | Sal((_, count)) | Sar((_, count))
+            | Rol((_, count)) | Ror((_, count)) | Rcl((_, count)) | Rcr((_, count))
+                if !count.chars().all(|c| c.is_ascii_digit() || c == '-') =>
+            {
+                &["ecx"]
+            }
+            LoopEq(_) | LoopNe(_) => &["ecx"],
+            Cmps(_) | Scas(_) | Stos(_) | Lods(_) | Movs(_) => &["eax", "ecx", "esi", "edi"],
+            In(_) | Out(_) | Ins(_) | Outs(_) => &["eax", "edx", "esi", "edi"],
+            Cpuid => &["eax", "ebx", "ecx", "edx"],
+            Syscall(_) => &["eax", "ebx", "ecx", "edx", "esi", "edi"],
+            // Calls through the PLT load the GOT into ebx first.
+            Call(_) if self.pic.is_some() => &["eax", "ebx", "ecx", "edx"],
+            Call(_) => &["eax", "ecx", "edx"],
+            Pusha | Popa => return self.register_map.values().cloned().collect(),
+            _ => &[],
+        };
+        registers.iter().map(|r| r.to_string()).collect()
+    }
+
+    fn generate_compact(
+        &self,
+        out: &mut String,
+        instruction: &Instruction,
+        dead: &dyn Fn(&str) -> bool,
+    ) -> bool {
+        use Instruction::*;
+        match instruction {
+            Div((dst, src)) | Idiv((dst, src)) if dead("edx") => {
+                self.divide(out, dst, src, false, false);
+                true
+            }
+            Mod((dst, src)) if dead("edx") => {
+                self.divide(out, dst, src, true, false);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn get_frame_layout(&self) -> Option<FrameLayout> {
+        // `enter` pushes ebp and points it at the saved copy.
+        Some(FrameLayout {
+            return_address: 4,
+            frame_pointer: "ebp",
+            record: &["ebp"],
+            slot: 4,
+        })
+    }
+
+    fn set_position_independent(&mut self, pic: Pic) -> Result<(), String> {
+        match pic.format {
+            // Windows relocates images at load time instead.
+            Format::COFF => {}
+            Format::MachO => {
+                return Err(
+                    "position-independent code is not supported for 32-bit x86 Mach-O".to_string(),
+                );
+            }
+            _ => self.pic = Some(pic),
+        }
+        Ok(())
+    }
 }
@@ -4,6 +4,10 @@ use std::collections::HashMap;
 
 pub struct ARM32CodeGen {
     register_map: HashMap<String, String>,
+    /// Emit Thumb-2 instead of ARM code.
+    thumb: bool,
+    /// Symbols of the program, once code is position-independent.
+    pic: Option<Pic>,
 }
 
 impl ARM32CodeGen {
@@ -50,7 +54,89 @@ impl ARM32CodeGen {
         register_map.insert("r27".to_string(), "r3".to_string()); // Reuse r3
         register_map.insert("r28".to_string(), "r4".to_string()); // Reuse r4
 
-        ARM32CodeGen { register_map }
+        ARM32CodeGen {
+            register_map,
+            thumb: false,
+            pic: None,
+        }
+    }
+
+    pub fn thumb() -> Self {
+        ARM32CodeGen {
+            thumb: true,
+            ..Self::new()
+        }
+    }
+
+    /// `code`, a run of conditional `mov`s, inside the IT block Thumb-2
+    /// needs for it. The first instruction's condition is the block's; the
+    /// others either share it or take its inverse.
+    fn predicated(&self, out: &mut String, code: String) {
+        if !self.thumb {
+            out.push_str(&code);
+            return;
+        }
+        let conditions: Vec<&str> = code
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .map(|mnemonic| mnemonic.trim_start_matches("mov"))
+            .collect();
+        let Some(first) = conditions.first() else {
+            out.push_str(&code);
+            return;
+        };
+        let pattern: String = conditions[1..]
+            .iter()
+            .map(|c| if c == first { 't' } else { 'e' })
+            .collect();
+        emit!(out, "    it{} {}\n{}", pattern, first, code);
+    }
+
+    /// The address of `symbol + offset` in `dst`, as a distance from pc, or
+    /// loaded from the GOT if another module may provide the symbol. pc
+    /// reads 8 bytes ahead in ARM code and 4 in Thumb.
+    fn address(&self, out: &mut String, pic: &Pic, dst: &str, symbol: &str, offset: i64) {
+        let ahead = if self.thumb { 4 } else { 8 };
+        if !pic.is_preemptible(symbol) {
+            let distance = format!("{} - (1f + {})", Pic::expression(symbol, offset), ahead);
+            emit!(
+                out,
+                "    movw {}, #:lower16:({})\n    movt {}, #:upper16:({})\n1:\n",
+                dst,
+                distance,
+                dst,
+                distance
+            );
+            if self.thumb {
+                emit!(out, "    add {}, pc\n", dst);
+            } else {
+                emit!(out, "    add {}, pc, {}\n", dst, dst);
+            }
+            return;
+        }
+        // The literal holds the distance from pc to the symbol's GOT entry.
+        let load = if self.thumb {
+            format!("    add {}, pc\n    ldr {}, [{}]\n", dst, dst, dst)
+        } else {
+            format!("    ldr {}, [pc, {}]\n", dst, dst)
+        };
+        emit!(
+            out,
+            "    ldr {}, 2f\n1:\n{}    b 3f\n    .p2align 2\n2:\n    .word {}(GOT_PREL) + (2b - (1b + {}))\n3:\n",
+            dst,
+            load,
+            symbol,
+            ahead
+        );
+        // Each byte of the offset is an immediate both encodings take.
+        let mnemonic = if offset < 0 { "sub" } else { "add" };
+        let magnitude = offset.unsigned_abs();
+        for shift in (0..64).step_by(8) {
+            let part = magnitude & (0xff << shift);
+            if part != 0 {
+                emit!(out, "    {} {}, {}, #{}\n", mnemonic, dst, dst, part);
+            }
+        }
     }
 }
 
@@ -60,92 +146,159 @@ impl ArchCodeGen for ARM32CodeGen {
     }
 
     fn get_syntax_header(&self) -> String {
-        ".syntax unified\n.arch armv7-a\n.text\n\n".to_string()
+        if self.thumb {
+            ".syntax unified\n.arch armv7-a\n.thumb\n.text\n\n".to_string()
+        } else {
+            ".syntax unified\n.arch armv7-a\n.text\n\n".to_string()
+        }
     }
 
-    fn generate_mov(&self, dst: &str, src: &str) -> String {
+    fn generate_mov(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let value: i64 = src_op.parse().unwrap_or(0);
             if value >= 0 && value <= 255 {
-                return format!("    mov {}, #{}\n", dst_reg, src_op);
+                emit!(out, "    mov {}, #{}\n", dst_reg, src_op);
+                return;
             } else if value >= 0 && value <= 65535 {
                 let low = value & 0xFFFF;
-                return format!("    mov {}, #{}\n", dst_reg, low);
+                emit!(out, "    mov {}, #{}\n", dst_reg, low);
+                return;
             } else {
                 let low = value & 0xFFFF;
                 let high = (value >> 16) & 0xFFFF;
                 if high == 0 {
-                    return format!("    mov {}, #{}\n", dst_reg, low);
+                    emit!(out, "    mov {}, #{}\n", dst_reg, low);
+                    return;
                 } else {
-                    return format!(
+                    emit!(
+                        out,
                         "    mov {}, #{}\n    movt {}, #{}\n",
-                        dst_reg, low, dst_reg, high
+                        dst_reg,
+                        low,
+                        dst_reg,
+                        high
                     );
+                    return;
                 }
             }
         }
 
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(src)
+        {
+            self.address(out, pic, &dst_reg, symbol, offset);
+            return;
+        }
+
         if src_op.starts_with('r') || src_op == "sp" || src_op == "lr" || src_op == "pc" {
-            return format!("    mov {}, {}\n", dst_reg, src_op);
+            emit!(out, "    mov {}, {}\n", dst_reg, src_op);
+            return;
         }
 
-        format!("    ldr {}, ={}\n", dst_reg, src_op)
+        emit!(out, "    ldr {}, ={}\n", dst_reg, src_op);
     }
 
-    fn generate_lea(&self, dst: &str, src: &str) -> String {
+    fn generate_lea(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(src)
+        {
+            self.address(out, pic, &self.map_operand(dst), symbol, offset);
+            return;
+        }
         let src_clean = if src.starts_with('[') && src.ends_with(']') {
             &src[1..src.len() - 1]
         } else {
             src
         };
-        format!("    adr {}, {}\n", self.map_operand(dst), src_clean)
+        emit!(out, "    adr {}, {}\n", self.map_operand(dst), src_clean);
     }
 
-    fn generate_load(&self, dst: &str, src: &str) -> String {
+    fn generate_load(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
 
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(src)
+        {
+            self.address(out, pic, "r12", symbol, offset);
+            emit!(out, "    ldr {}, [r12]\n", dst_reg);
+            return;
+        }
+
         if !src.starts_with('[') && !src.ends_with(']') {
-            return format!("    ldr {}, ={}\n", dst_reg, src);
+            emit!(out, "    ldr {}, ={}\n", dst_reg, src);
+            return;
         }
 
         let inner = &src[1..src.len() - 1].trim();
 
         if let Some(mapped_reg) = self.register_map.get(&inner.to_string()) {
-            return format!("    ldr {}, [{}]\n", dst_reg, mapped_reg);
+            emit!(out, "    ldr {}, [{}]\n", dst_reg, mapped_reg);
+            return;
         }
 
         if inner.contains('+') || inner.contains('-') {
-            return format!("    ldr {}, {}\n", dst_reg, self.map_memory_operand(src));
+            emit!(
+                out,
+                "    ldr {}, {}\n",
+                dst_reg,
+                self.map_memory_operand(src)
+            );
+            return;
         }
 
-        format!("    adr r12, {}\n    ldr {}, [r12]\n", inner, dst_reg)
+        emit!(out, "    adr r12, {}\n    ldr {}, [r12]\n", inner, dst_reg);
     }
 
-    fn generate_store(&self, dst: &str, src: &str) -> String {
+    fn generate_store(&self, out: &mut String, dst: &str, src: &str) {
         let src_reg = self.map_operand(src);
 
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(dst)
+        {
+            self.address(out, pic, "r12", symbol, offset);
+            if src_reg.chars().all(|c| c.is_ascii_digit()) {
+                emit!(out, "    mov lr, #{}\n    str lr, [r12]\n", src_reg);
+                return;
+            }
+            emit!(out, "    str {}, [r12]\n", src_reg);
+            return;
+        }
+
         if src_reg.chars().all(|c| c.is_ascii_digit()) {
             if dst.starts_with('[') && dst.ends_with(']') {
                 let inner = &dst[1..dst.len() - 1].trim();
                 if let Some(mapped_reg) = self.register_map.get(&inner.to_string()) {
-                    return format!("    mov r12, #{}\n    str r12, [{}]\n", src_reg, mapped_reg);
+                    emit!(
+                        out,
+                        "    mov r12, #{}\n    str r12, [{}]\n",
+                        src_reg,
+                        mapped_reg
+                    );
+                    return;
                 } else if inner.contains('+') || inner.contains('-') {
                     let dst_mem = self.map_memory_operand(dst);
-                    return format!("    mov r12, #{}\n    str r12, {}\n", src_reg, dst_mem);
+                    emit!(out, "    mov r12, #{}\n    str r12, {}\n", src_reg, dst_mem);
+                    return;
                 } else {
-                    return format!(
+                    emit!(
+                        out,
                         "    adr r12, {}\n    mov lr, #{}\n    str lr, [r12]\n",
-                        inner, src_reg
+                        inner,
+                        src_reg
                     );
+                    return;
                 }
             } else {
-                return format!(
+                emit!(
+                    out,
                     "    adr r12, {}\n    mov lr, #{}\n    str lr, [r12]\n",
-                    dst, src_reg
+                    dst,
+                    src_reg
                 );
+                return;
             }
         }
 
@@ -153,219 +306,235 @@ impl ArchCodeGen for ARM32CodeGen {
             let inner = &dst[1..dst.len() - 1].trim();
 
             if let Some(mapped_reg) = self.register_map.get(&inner.to_string()) {
-                return format!("    str {}, [{}]\n", src_reg, mapped_reg);
+                emit!(out, "    str {}, [{}]\n", src_reg, mapped_reg);
+                return;
             }
 
             if inner.contains('+') || inner.contains('-') {
                 let dst_mem = self.map_memory_operand(dst);
-                return format!("    str {}, {}\n", src_reg, dst_mem);
+                emit!(out, "    str {}, {}\n", src_reg, dst_mem);
+                return;
             }
 
-            return format!("    adr r12, {}\n    str {}, [r12]\n", inner, src_reg);
+            emit!(out, "    adr r12, {}\n    str {}, [r12]\n", inner, src_reg);
+            return;
         }
 
-        format!("    adr r12, {}\n    str {}, [r12]\n", dst, src_reg)
+        emit!(out, "    adr r12, {}\n    str {}, [r12]\n", dst, src_reg);
     }
 
-    fn generate_add(&self, dst: &str, src: &str) -> String {
+    fn generate_add(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    add {}, {}, #{}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    add {}, {}, #{}\n", dst_reg, dst_reg, src_op);
         } else {
-            format!("    add {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    add {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_sub(&self, dst: &str, src: &str) -> String {
+    fn generate_sub(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    sub {}, {}, #{}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    sub {}, {}, #{}\n", dst_reg, dst_reg, src_op);
         } else {
-            format!("    sub {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    sub {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_mul(&self, dst: &str, src: &str) -> String {
+    fn generate_mul(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!(
+            emit!(
+                out,
                 "    mov r12, #{}\n    mul {}, {}, r12\n",
-                src_op, dst_reg, dst_reg
-            )
+                src_op,
+                dst_reg,
+                dst_reg
+            );
         } else {
-            format!("    mul {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    mul {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_div(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_div(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    @ Software division: {} / {}\n    mov r0, {}\n    mov r1, {}\n    bl __aeabi_idiv\n    mov {}, r0\n",
             dst,
             src,
             self.map_operand(dst),
             self.map_operand(src),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_inc(&self, dst: &str) -> String {
-        format!(
+    fn generate_inc(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    add {}, {}, #1\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_dec(&self, dst: &str) -> String {
-        format!(
+    fn generate_dec(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    sub {}, {}, #1\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_neg(&self, dst: &str) -> String {
-        format!(
+    fn generate_neg(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    rsb {}, {}, #0\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_and(&self, dst: &str, src: &str) -> String {
+    fn generate_and(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    and {}, {}, #{}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    and {}, {}, #{}\n", dst_reg, dst_reg, src_op);
         } else {
-            format!("    and {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    and {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_or(&self, dst: &str, src: &str) -> String {
+    fn generate_or(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    orr {}, {}, #{}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    orr {}, {}, #{}\n", dst_reg, dst_reg, src_op);
         } else {
-            format!("    orr {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    orr {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_xor(&self, dst: &str, src: &str) -> String {
+    fn generate_xor(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    eor {}, {}, #{}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    eor {}, {}, #{}\n", dst_reg, dst_reg, src_op);
         } else {
-            format!("    eor {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    eor {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_not(&self, dst: &str) -> String {
-        format!(
+    fn generate_not(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    mvn {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_shl(&self, dst: &str, src: &str) -> String {
+    fn generate_shl(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit()) {
-            format!("    lsl {}, {}, #{}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    lsl {}, {}, #{}\n", dst_reg, dst_reg, src_op);
         } else {
-            format!("    lsl {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    lsl {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_shr(&self, dst: &str, src: &str) -> String {
+    fn generate_shr(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit()) {
-            format!("    lsr {}, {}, #{}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    lsr {}, {}, #{}\n", dst_reg, dst_reg, src_op);
         } else {
-            format!("    lsr {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    lsr {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_cmp(&self, left: &str, right: &str) -> String {
+    fn generate_cmp(&self, out: &mut String, left: &str, right: &str) {
         let left_reg = self.map_operand(left);
         let right_op = self.map_operand(right);
 
         if right_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    cmp {}, #{}\n", left_reg, right_op)
+            emit!(out, "    cmp {}, #{}\n", left_reg, right_op);
         } else if right_op.starts_with('r')
             || right_op == "sp"
             || right_op == "lr"
             || right_op == "pc"
         {
-            format!("    cmp {}, {}\n", left_reg, right_op)
+            emit!(out, "    cmp {}, {}\n", left_reg, right_op);
         } else {
-            format!("    ldr r12, ={}\n    cmp {}, r12\n", right_op, left_reg)
+            emit!(
+                out,
+                "    ldr r12, ={}\n    cmp {}, r12\n",
+                right_op,
+                left_reg
+            );
         }
     }
 
-    fn generate_test(&self, left: &str, right: &str) -> String {
+    fn generate_test(&self, out: &mut String, left: &str, right: &str) {
         let left_reg = self.map_operand(left);
         let right_op = self.map_operand(right);
 
         if right_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    tst {}, #{}\n", left_reg, right_op)
+            emit!(out, "    tst {}, #{}\n", left_reg, right_op);
         } else {
-            format!("    tst {}, {}\n", left_reg, right_op)
+            emit!(out, "    tst {}, {}\n", left_reg, right_op);
         }
     }
 
-    fn generate_jmp(&self, target: &str) -> String {
-        format!("    b {}\n", target)
+    fn generate_jmp(&self, out: &mut String, target: &str) {
+        emit!(out, "    b {}\n", target);
     }
 
-    fn generate_je(&self, target: &str) -> String {
-        format!("    beq {}\n", target)
+    fn generate_je(&self, out: &mut String, target: &str) {
+        emit!(out, "    beq {}\n", target);
     }
 
-    fn generate_jne(&self, target: &str) -> String {
-        format!("    bne {}\n", target)
+    fn generate_jne(&self, out: &mut String, target: &str) {
+        emit!(out, "    bne {}\n", target);
     }
 
-    fn generate_jg(&self, target: &str) -> String {
-        format!("    bgt {}\n", target)
+    fn generate_jg(&self, out: &mut String, target: &str) {
+        emit!(out, "    bgt {}\n", target);
     }
 
-    fn generate_jl(&self, target: &str) -> String {
-        format!("    blt {}\n", target)
+    fn generate_jl(&self, out: &mut String, target: &str) {
+        emit!(out, "    blt {}\n", target);
     }
 
-    fn generate_jge(&self, target: &str) -> String {
-        format!("    bge {}\n", target)
+    fn generate_jge(&self, out: &mut String, target: &str) {
+        emit!(out, "    bge {}\n", target);
     }
 
-    fn generate_jle(&self, target: &str) -> String {
-        format!("    ble {}\n", target)
+    fn generate_jle(&self, out: &mut String, target: &str) {
+        emit!(out, "    ble {}\n", target);
     }
 
-    fn generate_call(&self, target: &str) -> String {
-        format!("    bl {}\n", target)
+    fn generate_call(&self, out: &mut String, target: &str) {
+        emit!(out, "    bl {}\n", target);
     }
 
-    fn generate_ret(&self) -> String {
-        "    mov pc, lr\n".to_string()
+    fn generate_ret(&self, out: &mut String) {
+        out.push_str("    mov pc, lr\n");
     }
 
-    fn generate_syscall(&self, name: &str) -> String {
+    fn generate_syscall(&self, out: &mut String, name: &str) {
         // ARM32 Linux syscall numbers
         let syscall_num = match name {
             "read" => "3",
@@ -378,218 +547,283 @@ impl ArchCodeGen for ARM32CodeGen {
             "brk" => "45",
             "fstat" => "108",
             _ => {
-                return format!(
+                emit!(
+                    out,
                     "    @ Unknown syscall: {}\n    mov r7, #0\n    swi 0\n",
                     name
                 );
+                return;
             }
         };
-        format!("    mov r7, #{}\n    swi 0\n", syscall_num)
+        emit!(out, "    mov r7, #{}\n    swi 0\n", syscall_num);
     }
 
-    fn generate_cmov_eq(&self, dst: &str, src: &str) -> String {
-        format!(
-            "    moveq {}, {}\n",
-            self.map_operand(dst),
-            self.map_operand(src)
-        )
+    fn generate_cmov_eq(&self, out: &mut String, dst: &str, src: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    moveq {}, {}\n",
+                self.map_operand(dst),
+                self.map_operand(src)
+            ),
+        );
     }
 
-    fn generate_cmov_ne(&self, dst: &str, src: &str) -> String {
-        format!(
-            "    movne {}, {}\n",
-            self.map_operand(dst),
-            self.map_operand(src)
-        )
+    fn generate_cmov_ne(&self, out: &mut String, dst: &str, src: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movne {}, {}\n",
+                self.map_operand(dst),
+                self.map_operand(src)
+            ),
+        );
     }
 
-    fn generate_cmov_lt(&self, dst: &str, src: &str) -> String {
-        format!(
-            "    movlt {}, {}\n",
-            self.map_operand(dst),
-            self.map_operand(src)
-        )
+    fn generate_cmov_lt(&self, out: &mut String, dst: &str, src: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movlt {}, {}\n",
+                self.map_operand(dst),
+                self.map_operand(src)
+            ),
+        );
     }
 
-    fn generate_cmov_le(&self, dst: &str, src: &str) -> String {
-        format!(
-            "    movle {}, {}\n",
-            self.map_operand(dst),
-            self.map_operand(src)
-        )
+    fn generate_cmov_le(&self, out: &mut String, dst: &str, src: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movle {}, {}\n",
+                self.map_operand(dst),
+                self.map_operand(src)
+            ),
+        );
     }
 
-    fn generate_cmov_gt(&self, dst: &str, src: &str) -> String {
-        format!(
-            "    movgt {}, {}\n",
-            self.map_operand(dst),
-            self.map_operand(src)
-        )
+    fn generate_cmov_gt(&self, out: &mut String, dst: &str, src: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movgt {}, {}\n",
+                self.map_operand(dst),
+                self.map_operand(src)
+            ),
+        );
     }
 
-    fn generate_cmov_ge(&self, dst: &str, src: &str) -> String {
-        format!(
-            "    movge {}, {}\n",
-            self.map_operand(dst),
-            self.map_operand(src)
-        )
+    fn generate_cmov_ge(&self, out: &mut String, dst: &str, src: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movge {}, {}\n",
+                self.map_operand(dst),
+                self.map_operand(src)
+            ),
+        );
     }
 
-    fn generate_cmov_ov(&self, dst: &str, src: &str) -> String {
-        format!(
-            "    movvs {}, {}\n",
-            self.map_operand(dst),
-            self.map_operand(src)
-        )
+    fn generate_cmov_ov(&self, out: &mut String, dst: &str, src: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movvs {}, {}\n",
+                self.map_operand(dst),
+                self.map_operand(src)
+            ),
+        );
     }
 
-    fn generate_cmov_no(&self, dst: &str, src: &str) -> String {
-        format!(
-            "    movvc {}, {}\n",
-            self.map_operand(dst),
-            self.map_operand(src)
-        )
+    fn generate_cmov_no(&self, out: &mut String, dst: &str, src: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movvc {}, {}\n",
+                self.map_operand(dst),
+                self.map_operand(src)
+            ),
+        );
     }
 
-    fn generate_cmov_s(&self, dst: &str, src: &str) -> String {
-        format!(
-            "    movmi {}, {}\n",
-            self.map_operand(dst),
-            self.map_operand(src)
-        )
+    fn generate_cmov_s(&self, out: &mut String, dst: &str, src: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movmi {}, {}\n",
+                self.map_operand(dst),
+                self.map_operand(src)
+            ),
+        );
     }
 
-    fn generate_cmov_ns(&self, dst: &str, src: &str) -> String {
-        format!(
-            "    movpl {}, {}\n",
-            self.map_operand(dst),
-            self.map_operand(src)
-        )
+    fn generate_cmov_ns(&self, out: &mut String, dst: &str, src: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movpl {}, {}\n",
+                self.map_operand(dst),
+                self.map_operand(src)
+            ),
+        );
     }
 
-    fn generate_cmov_p(&self, _dst: &str, _src: &str) -> String {
-        "    @ Parity flag not available in ARM32\n".to_string()
+    fn generate_cmov_p(&self, out: &mut String, _dst: &str, _src: &str) {
+        out.push_str("    @ Parity flag not available in ARM32\n");
     }
 
-    fn generate_cmov_np(&self, _dst: &str, _src: &str) -> String {
-        "    @ Parity flag not available in ARM32\n".to_string()
+    fn generate_cmov_np(&self, out: &mut String, _dst: &str, _src: &str) {
+        out.push_str("    @ Parity flag not available in ARM32\n");
     }
 
-    fn generate_cmov_a(&self, dst: &str, src: &str) -> String {
-        format!(
-            "    movhi {}, {}\n",
-            self.map_operand(dst),
-            self.map_operand(src)
-        )
+    fn generate_cmov_a(&self, out: &mut String, dst: &str, src: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movhi {}, {}\n",
+                self.map_operand(dst),
+                self.map_operand(src)
+            ),
+        );
     }
 
-    fn generate_cmov_ae(&self, dst: &str, src: &str) -> String {
-        format!(
-            "    movcs {}, {}\n",
-            self.map_operand(dst),
-            self.map_operand(src)
-        )
+    fn generate_cmov_ae(&self, out: &mut String, dst: &str, src: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movcs {}, {}\n",
+                self.map_operand(dst),
+                self.map_operand(src)
+            ),
+        );
     }
 
-    fn generate_cmov_b(&self, dst: &str, src: &str) -> String {
-        format!(
-            "    movcc {}, {}\n",
-            self.map_operand(dst),
-            self.map_operand(src)
-        )
+    fn generate_cmov_b(&self, out: &mut String, dst: &str, src: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movcc {}, {}\n",
+                self.map_operand(dst),
+                self.map_operand(src)
+            ),
+        );
     }
 
-    fn generate_cmov_be(&self, dst: &str, src: &str) -> String {
-        format!(
-            "    movls {}, {}\n",
-            self.map_operand(dst),
-            self.map_operand(src)
-        )
+    fn generate_cmov_be(&self, out: &mut String, dst: &str, src: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movls {}, {}\n",
+                self.map_operand(dst),
+                self.map_operand(src)
+            ),
+        );
     }
 
-    fn generate_push(&self, src: &str) -> String {
+    fn generate_push(&self, out: &mut String, src: &str) {
         let src_reg = self.map_operand(src);
-        format!("    push {{{}}}\n", src_reg)
+        emit!(out, "    push {{{}}}\n", src_reg);
     }
 
-    fn generate_pop(&self, dst: &str) -> String {
-        format!("    pop {{{}}}\n", self.map_operand(dst))
+    fn generate_pop(&self, out: &mut String, dst: &str) {
+        emit!(out, "    pop {{{}}}\n", self.map_operand(dst));
     }
 
-    fn generate_pusha(&self) -> String {
-        "    push {r0-r12, lr}\n".to_string()
+    fn generate_pusha(&self, out: &mut String) {
+        out.push_str("    push {r0-r12, lr}\n");
     }
 
-    fn generate_popa(&self) -> String {
-        "    pop {r0-r12, lr}\n".to_string()
+    fn generate_popa(&self, out: &mut String) {
+        out.push_str("    pop {r0-r12, lr}\n");
     }
 
-    fn generate_enter(&self, frame_size: &str, _nesting_level: &str) -> String {
-        format!(
+    fn generate_enter(&self, out: &mut String, frame_size: &str, _nesting_level: &str) {
+        emit!(
+            out,
             "    push {{fp, lr}}\n    mov fp, sp\n    sub sp, sp, #{}\n",
             frame_size
-        )
+        );
     }
 
-    fn generate_leave(&self) -> String {
-        "    mov sp, fp\n    pop {fp, lr}\n".to_string()
+    fn generate_leave(&self, out: &mut String) {
+        out.push_str("    mov sp, fp\n    pop {fp, lr}\n");
     }
 
-    fn generate_imul(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_imul(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    mul {}, {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
 
-    fn generate_idiv(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_idiv(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    @ Signed division: {} / {}\n    mov r0, {}\n    mov r1, {}\n    bl __aeabi_idiv\n    mov {}, r0\n",
             dst,
             src,
             self.map_operand(dst),
             self.map_operand(src),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_mod(&self, dst: &str, src: &str) -> String {
+    fn generate_mod(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_reg = self.map_operand(src);
-        format!(
+        emit!(
+            out,
             "    @ Modulo operation: {} % {}\n    mov r0, {}\n    mov r1, {}\n    bl __aeabi_idivmod\n    mov {}, r1\n",
-            dst, src, dst_reg, src_reg, dst_reg
-        )
+            dst,
+            src,
+            dst_reg,
+            src_reg,
+            dst_reg
+        );
+    }
+
+    fn generate_mulh(&self, out: &mut String, dst: &str, src: &str) {
+        // smull writes the low half to r12 (scratch), the high half to dst
+        let dst_reg = self.map_operand(dst);
+        emit!(
+            out,
+            "    smull r12, {}, {}, {}\n",
+            dst_reg,
+            dst_reg,
+            self.map_operand(src)
+        );
     }
 
-    fn generate_andn(&self, dst: &str, src: &str) -> String {
+    fn generate_andn(&self, out: &mut String, dst: &str, src: &str) {
         // ARM32 doesn't have andn - emulate with bic (bit clear)
-        format!(
+        emit!(
+            out,
             "    bic {}, {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
 
-    fn generate_sal(&self, dst: &str, src: &str) -> String {
-        self.generate_shl(dst, src)
+    fn generate_sal(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_shl(out, dst, src);
     }
 
-    fn generate_sar(&self, dst: &str, src: &str) -> String {
+    fn generate_sar(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit()) {
-            format!("    asr {}, {}, #{}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    asr {}, {}, #{}\n", dst_reg, dst_reg, src_op);
         } else {
-            format!("    asr {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    asr {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_rol(&self, dst: &str, src: &str) -> String {
+    fn generate_rol(&self, out: &mut String, dst: &str, src: &str) {
         // ARM32 has ROR but not ROL - emulate with ROR
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
@@ -597,35 +831,38 @@ impl ArchCodeGen for ARM32CodeGen {
         if src_op.chars().all(|c| c.is_ascii_digit()) {
             let shift_val: u32 = src_op.parse().unwrap_or(0);
             let ror_val = 32 - (shift_val % 32);
-            format!("    ror {}, {}, #{}\n", dst_reg, dst_reg, ror_val)
+            emit!(out, "    ror {}, {}, #{}\n", dst_reg, dst_reg, ror_val);
         } else {
-            format!(
+            emit!(
+                out,
                 "    rsb r12, {}, #32\n    ror {}, {}, r12\n",
-                src_op, dst_reg, dst_reg
-            )
+                src_op,
+                dst_reg,
+                dst_reg
+            );
         }
     }
 
-    fn generate_ror(&self, dst: &str, src: &str) -> String {
+    fn generate_ror(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit()) {
-            format!("    ror {}, {}, #{}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    ror {}, {}, #{}\n", dst_reg, dst_reg, src_op);
         } else {
-            format!("    ror {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    ror {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_rcl(&self, _dst: &str, _src: &str) -> String {
-        "    @ RCL not available in ARM32 - would need carry flag emulation\n".to_string()
+    fn generate_rcl(&self, out: &mut String, _dst: &str, _src: &str) {
+        out.push_str("    @ RCL not available in ARM32 - would need carry flag emulation\n");
     }
 
-    fn generate_rcr(&self, _dst: &str, _src: &str) -> String {
-        "    @ RCR not available in ARM32 - would need carry flag emulation\n".to_string()
+    fn generate_rcr(&self, out: &mut String, _dst: &str, _src: &str) {
+        out.push_str("    @ RCR not available in ARM32 - would need carry flag emulation\n");
     }
 
-    fn generate_bextr(&self, dst: &str, src: &str, imm: &str) -> String {
+    fn generate_bextr(&self, out: &mut String, dst: &str, src: &str, imm: &str) {
         // ARM32 doesn't have bit field extract - emulate
         // imm format expected: "start,length" or single value
         let dst_reg = self.map_operand(dst);
@@ -634,7 +871,8 @@ impl ArchCodeGen for ARM32CodeGen {
         if let Some((start_str, length_str)) = imm.split_once(',') {
             let start = start_str.trim().parse::<u32>().unwrap_or(0);
             let length = length_str.trim().parse::<u32>().unwrap_or(0);
-            format!(
+            emit!(
+                out,
                 "    @ Bit field extract emulation\n    lsl {}, {}, #{}\n    lsr {}, {}, #{}\n",
                 dst_reg,
                 src_reg,
@@ -642,450 +880,524 @@ impl ArchCodeGen for ARM32CodeGen {
                 dst_reg,
                 dst_reg,
                 32u32.saturating_sub(length)
-            )
+            );
         } else {
-            format!("    @ Invalid bextr immediate format: {}\n", imm)
+            emit!(out, "    @ Invalid bextr immediate format: {}\n", imm);
         }
     }
 
-    fn generate_bsf(&self, dst: &str, _src: &str) -> String {
+    fn generate_bsf(&self, out: &mut String, dst: &str, _src: &str) {
         // ARM32 doesn't have bit scan - would need software implementation
-        format!(
+        emit!(
+            out,
             "    @ Bit scan forward - software implementation needed\n    mov {}, #-1\n",
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_bsr(&self, dst: &str, src: &str) -> String {
+    fn generate_bsr(&self, out: &mut String, dst: &str, src: &str) {
         // ARM32 has CLZ (count leading zeros) which can help
-        format!(
+        emit!(
+            out,
             "    clz {}, {}\n    rsb {}, {}, #31\n",
             self.map_operand(dst),
             self.map_operand(src),
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_bt(&self, dst: &str, src: &str) -> String {
+    fn generate_bt(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
-        format!(
+        emit!(
+            out,
             "    @ Bit test\n    mov r12, #1\n    lsl r12, r12, {}\n    tst {}, r12\n",
-            src_op, dst_reg
-        )
+            src_op,
+            dst_reg
+        );
     }
 
-    fn generate_btr(&self, dst: &str, src: &str) -> String {
+    fn generate_btr(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
-        format!(
+        emit!(
+            out,
             "    @ Bit test and reset\n    mov r12, #1\n    lsl r12, r12, {}\n    tst {}, r12\n    bic {}, {}, r12\n",
-            src_op, dst_reg, dst_reg, dst_reg
-        )
+            src_op,
+            dst_reg,
+            dst_reg,
+            dst_reg
+        );
     }
 
-    fn generate_bts(&self, dst: &str, src: &str) -> String {
+    fn generate_bts(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
-        format!(
+        emit!(
+            out,
             "    @ Bit test and set\n    mov r12, #1\n    lsl r12, r12, {}\n    tst {}, r12\n    orr {}, {}, r12\n",
-            src_op, dst_reg, dst_reg, dst_reg
-        )
+            src_op,
+            dst_reg,
+            dst_reg,
+            dst_reg
+        );
     }
 
-    fn generate_btc(&self, dst: &str, src: &str) -> String {
+    fn generate_btc(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
-        format!(
+        emit!(
+            out,
             "    @ Bit test and complement\n    mov r12, #1\n    lsl r12, r12, {}\n    tst {}, r12\n    eor {}, {}, r12\n",
-            src_op, dst_reg, dst_reg, dst_reg
-        )
+            src_op,
+            dst_reg,
+            dst_reg,
+            dst_reg
+        );
     }
 
-    fn generate_set_eq(&self, dst: &str) -> String {
-        format!(
-            "    moveq {}, #1\n    movne {}, #0\n",
-            self.map_operand(dst),
-            self.map_operand(dst)
-        )
+    fn generate_set_eq(&self, out: &mut String, dst: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    moveq {}, #1\n    movne {}, #0\n",
+                self.map_operand(dst),
+                self.map_operand(dst)
+            ),
+        );
     }
 
-    fn generate_set_ne(&self, dst: &str) -> String {
-        format!(
-            "    movne {}, #1\n    moveq {}, #0\n",
-            self.map_operand(dst),
-            self.map_operand(dst)
-        )
+    fn generate_set_ne(&self, out: &mut String, dst: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movne {}, #1\n    moveq {}, #0\n",
+                self.map_operand(dst),
+                self.map_operand(dst)
+            ),
+        );
     }
 
-    fn generate_set_lt(&self, dst: &str) -> String {
-        format!(
-            "    movlt {}, #1\n    movge {}, #0\n",
-            self.map_operand(dst),
-            self.map_operand(dst)
-        )
+    fn generate_set_lt(&self, out: &mut String, dst: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movlt {}, #1\n    movge {}, #0\n",
+                self.map_operand(dst),
+                self.map_operand(dst)
+            ),
+        );
     }
 
-    fn generate_set_le(&self, dst: &str) -> String {
-        format!(
-            "    movle {}, #1\n    movgt {}, #0\n",
-            self.map_operand(dst),
-            self.map_operand(dst)
-        )
+    fn generate_set_le(&self, out: &mut String, dst: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movle {}, #1\n    movgt {}, #0\n",
+                self.map_operand(dst),
+                self.map_operand(dst)
+            ),
+        );
     }
 
-    fn generate_set_gt(&self, dst: &str) -> String {
-        format!(
-            "    movgt {}, #1\n    movle {}, #0\n",
-            self.map_operand(dst),
-            self.map_operand(dst)
-        )
+    fn generate_set_gt(&self, out: &mut String, dst: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movgt {}, #1\n    movle {}, #0\n",
+                self.map_operand(dst),
+                self.map_operand(dst)
+            ),
+        );
     }
 
-    fn generate_set_ge(&self, dst: &str) -> String {
-        format!(
-            "    movge {}, #1\n    movlt {}, #0\n",
-            self.map_operand(dst),
-            self.map_operand(dst)
-        )
+    fn generate_set_ge(&self, out: &mut String, dst: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movge {}, #1\n    movlt {}, #0\n",
+                self.map_operand(dst),
+                self.map_operand(dst)
+            ),
+        );
     }
 
-    fn generate_set_ov(&self, dst: &str) -> String {
-        format!(
-            "    movvs {}, #1\n    movvc {}, #0\n",
-            self.map_operand(dst),
-            self.map_operand(dst)
-        )
+    fn generate_set_ov(&self, out: &mut String, dst: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movvs {}, #1\n    movvc {}, #0\n",
+                self.map_operand(dst),
+                self.map_operand(dst)
+            ),
+        );
     }
 
-    fn generate_set_no(&self, dst: &str) -> String {
-        format!(
-            "    movvc {}, #1\n    movvs {}, #0\n",
-            self.map_operand(dst),
-            self.map_operand(dst)
-        )
+    fn generate_set_no(&self, out: &mut String, dst: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movvc {}, #1\n    movvs {}, #0\n",
+                self.map_operand(dst),
+                self.map_operand(dst)
+            ),
+        );
     }
 
-    fn generate_set_s(&self, dst: &str) -> String {
-        format!(
-            "    movmi {}, #1\n    movpl {}, #0\n",
-            self.map_operand(dst),
-            self.map_operand(dst)
-        )
+    fn generate_set_s(&self, out: &mut String, dst: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movmi {}, #1\n    movpl {}, #0\n",
+                self.map_operand(dst),
+                self.map_operand(dst)
+            ),
+        );
     }
 
-    fn generate_set_ns(&self, dst: &str) -> String {
-        format!(
-            "    movpl {}, #1\n    movmi {}, #0\n",
-            self.map_operand(dst),
-            self.map_operand(dst)
-        )
+    fn generate_set_ns(&self, out: &mut String, dst: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movpl {}, #1\n    movmi {}, #0\n",
+                self.map_operand(dst),
+                self.map_operand(dst)
+            ),
+        );
     }
 
-    fn generate_set_p(&self, _dst: &str) -> String {
-        "    @ Parity flag not available in ARM32\n".to_string()
+    fn generate_set_p(&self, out: &mut String, _dst: &str) {
+        out.push_str("    @ Parity flag not available in ARM32\n");
     }
 
-    fn generate_set_np(&self, _dst: &str) -> String {
-        "    @ Parity flag not available in ARM32\n".to_string()
+    fn generate_set_np(&self, out: &mut String, _dst: &str) {
+        out.push_str("    @ Parity flag not available in ARM32\n");
     }
 
-    fn generate_set_a(&self, dst: &str) -> String {
-        format!(
-            "    movhi {}, #1\n    movls {}, #0\n",
-            self.map_operand(dst),
-            self.map_operand(dst)
-        )
+    fn generate_set_a(&self, out: &mut String, dst: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movhi {}, #1\n    movls {}, #0\n",
+                self.map_operand(dst),
+                self.map_operand(dst)
+            ),
+        );
     }
 
-    fn generate_set_ae(&self, dst: &str) -> String {
-        format!(
-            "    movcs {}, #1\n    movcc {}, #0\n",
-            self.map_operand(dst),
-            self.map_operand(dst)
-        )
+    fn generate_set_ae(&self, out: &mut String, dst: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movcs {}, #1\n    movcc {}, #0\n",
+                self.map_operand(dst),
+                self.map_operand(dst)
+            ),
+        );
     }
 
-    fn generate_set_b(&self, dst: &str) -> String {
-        format!(
-            "    movcc {}, #1\n    movcs {}, #0\n",
-            self.map_operand(dst),
-            self.map_operand(dst)
-        )
+    fn generate_set_b(&self, out: &mut String, dst: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movcc {}, #1\n    movcs {}, #0\n",
+                self.map_operand(dst),
+                self.map_operand(dst)
+            ),
+        );
     }
 
-    fn generate_set_be(&self, dst: &str) -> String {
-        format!(
-            "    movls {}, #1\n    movhi {}, #0\n",
-            self.map_operand(dst),
-            self.map_operand(dst)
-        )
+    fn generate_set_be(&self, out: &mut String, dst: &str) {
+        self.predicated(
+            out,
+            format!(
+                "    movls {}, #1\n    movhi {}, #0\n",
+                self.map_operand(dst),
+                self.map_operand(dst)
+            ),
+        );
     }
 
-    fn generate_cmps(&self, src1: &str, src2: &str) -> String {
-        format!(
+    fn generate_cmps(&self, out: &mut String, src1: &str, src2: &str) {
+        emit!(
+            out,
             "    ldr r12, {}\n    ldr lr, {}\n    cmp r12, lr\n",
             self.map_memory_operand(src1),
             self.map_memory_operand(src2)
-        )
+        );
     }
 
-    fn generate_scas(&self, src: &str, val: &str) -> String {
-        format!(
+    fn generate_scas(&self, out: &mut String, src: &str, val: &str) {
+        emit!(
+            out,
             "    ldr r12, {}\n    cmp r12, {}\n",
             self.map_memory_operand(src),
             self.map_operand(val)
-        )
+        );
     }
 
-    fn generate_stos(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_stos(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    str {}, {}\n",
             self.map_operand(src),
             self.map_memory_operand(dst)
-        )
+        );
     }
 
-    fn generate_lods(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_lods(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    ldr {}, {}\n",
             self.map_operand(dst),
             self.map_memory_operand(src)
-        )
+        );
     }
 
-    fn generate_movs(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_movs(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    ldr r12, {}\n    str r12, {}\n",
             self.map_memory_operand(src),
             self.map_memory_operand(dst)
-        )
+        );
     }
 
-    fn generate_cbw(&self, dst: &str) -> String {
-        format!(
+    fn generate_cbw(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    sxtb {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_cwd(&self, dst: &str) -> String {
-        format!(
+    fn generate_cwd(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    sxth {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_cdq(&self, dst: &str) -> String {
-        format!(
+    fn generate_cdq(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    @ CDQ: Sign extend 32-bit to 64-bit not directly available\n    asr {}, {}, #31\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_cqo(&self, _dst: &str) -> String {
-        "    @ CQO: 64-bit operations not available in ARM32\n".to_string()
+    fn generate_cqo(&self, out: &mut String, _dst: &str) {
+        out.push_str("    @ CQO: 64-bit operations not available in ARM32\n");
     }
 
-    fn generate_cwde(&self, dst: &str) -> String {
-        format!(
+    fn generate_cwde(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    sxth {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_cdqe(&self, _dst: &str) -> String {
-        "    @ CDQE: 64-bit operations not available in ARM32\n".to_string()
+    fn generate_cdqe(&self, out: &mut String, _dst: &str) {
+        out.push_str("    @ CDQE: 64-bit operations not available in ARM32\n");
     }
 
-    fn generate_jo(&self, target: &str) -> String {
-        format!("    bvs {}\n", target)
+    fn generate_jo(&self, out: &mut String, target: &str) {
+        emit!(out, "    bvs {}\n", target);
     }
 
-    fn generate_jno(&self, target: &str) -> String {
-        format!("    bvc {}\n", target)
+    fn generate_jno(&self, out: &mut String, target: &str) {
+        emit!(out, "    bvc {}\n", target);
     }
 
-    fn generate_js(&self, target: &str) -> String {
-        format!("    bmi {}\n", target)
+    fn generate_js(&self, out: &mut String, target: &str) {
+        emit!(out, "    bmi {}\n", target);
     }
 
-    fn generate_jns(&self, target: &str) -> String {
-        format!("    bpl {}\n", target)
+    fn generate_jns(&self, out: &mut String, target: &str) {
+        emit!(out, "    bpl {}\n", target);
     }
 
-    fn generate_jp(&self, _target: &str) -> String {
-        "    @ Parity flag not available in ARM32\n".to_string()
+    fn generate_jp(&self, out: &mut String, _target: &str) {
+        out.push_str("    @ Parity flag not available in ARM32\n");
     }
 
-    fn generate_jnp(&self, _target: &str) -> String {
-        "    @ Parity flag not available in ARM32\n".to_string()
+    fn generate_jnp(&self, out: &mut String, _target: &str) {
+        out.push_str("    @ Parity flag not available in ARM32\n");
     }
 
-    fn generate_ja(&self, target: &str) -> String {
-        format!("    bhi {}\n", target)
+    fn generate_ja(&self, out: &mut String, target: &str) {
+        emit!(out, "    bhi {}\n", target);
     }
 
-    fn generate_jae(&self, target: &str) -> String {
-        format!("    bcs {}\n", target)
+    fn generate_jae(&self, out: &mut String, target: &str) {
+        emit!(out, "    bcs {}\n", target);
     }
 
-    fn generate_jb(&self, target: &str) -> String {
-        format!("    bcc {}\n", target)
+    fn generate_jb(&self, out: &mut String, target: &str) {
+        emit!(out, "    bcc {}\n", target);
     }
 
-    fn generate_jbe(&self, target: &str) -> String {
-        format!("    bls {}\n", target)
+    fn generate_jbe(&self, out: &mut String, target: &str) {
+        emit!(out, "    bls {}\n", target);
     }
 
-    fn generate_loop_eq(&self, target: &str) -> String {
-        format!(
+    fn generate_loop_eq(&self, out: &mut String, target: &str) {
+        emit!(
+            out,
             "    @ LOOP equivalent: subs r12, r12, #1\n    beq {}\n",
             target
-        )
+        );
     }
 
-    fn generate_loop_ne(&self, target: &str) -> String {
-        format!(
+    fn generate_loop_ne(&self, out: &mut String, target: &str) {
+        emit!(
+            out,
             "    @ LOOP equivalent: subs r12, r12, #1\n    bne {}\n",
             target
-        )
+        );
     }
 
-    fn generate_in(&self, _dst: &str, _port: &str) -> String {
-        "    @ IN instruction not available in ARM32\n".to_string()
+    fn generate_in(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("    @ IN instruction not available in ARM32\n");
     }
 
-    fn generate_out(&self, _port: &str, _src: &str) -> String {
-        "    @ OUT instruction not available in ARM32\n".to_string()
+    fn generate_out(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("    @ OUT instruction not available in ARM32\n");
     }
 
-    fn generate_ins(&self, _dst: &str, _port: &str) -> String {
-        "    @ INS instruction not available in ARM32\n".to_string()
+    fn generate_ins(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("    @ INS instruction not available in ARM32\n");
     }
 
-    fn generate_outs(&self, _port: &str, _src: &str) -> String {
-        "    @ OUTS instruction not available in ARM32\n".to_string()
+    fn generate_outs(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("    @ OUTS instruction not available in ARM32\n");
     }
 
-    fn generate_cpuid(&self) -> String {
-        "    @ CPUID not available in ARM32\n".to_string()
+    fn generate_cpuid(&self, out: &mut String) {
+        out.push_str("    @ CPUID not available in ARM32\n");
     }
 
-    fn generate_lfence(&self) -> String {
-        "    dmb\n".to_string()
+    fn generate_lfence(&self, out: &mut String) {
+        out.push_str("    dmb\n");
     }
 
-    fn generate_sfence(&self) -> String {
-        "    dmb st\n".to_string()
+    fn generate_sfence(&self, out: &mut String) {
+        out.push_str("    dmb st\n");
     }
 
-    fn generate_mfence(&self) -> String {
-        "    dmb sy\n".to_string()
+    fn generate_mfence(&self, out: &mut String) {
+        out.push_str("    dmb sy\n");
     }
 
-    fn generate_prefetch(&self, addr: &str) -> String {
-        format!("    pld {}\n", self.map_memory_operand(addr))
+    fn generate_prefetch(&self, out: &mut String, addr: &str) {
+        emit!(out, "    pld {}\n", self.map_memory_operand(addr));
     }
 
-    fn generate_clflush(&self, _addr: &str) -> String {
-        "    @ Cache flush not available in ARM32\n".to_string()
+    fn generate_clflush(&self, out: &mut String, _addr: &str) {
+        out.push_str("    @ Cache flush not available in ARM32\n");
     }
 
-    fn generate_clwb(&self, _addr: &str) -> String {
-        "    @ Cache writeback not available in ARM32\n".to_string()
+    fn generate_clwb(&self, out: &mut String, _addr: &str) {
+        out.push_str("    @ Cache writeback not available in ARM32\n");
     }
 
-    fn generate_global(&self, symbol: &str) -> String {
-        format!(".global {}\n.type {}, %function\n", symbol, symbol)
+    fn generate_global(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".global {}\n.type {}, %function\n", symbol, symbol);
     }
 
-    fn generate_extern(&self, symbol: &str) -> String {
-        format!(".extern {}\n", symbol)
+    fn generate_extern(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".extern {}\n", symbol);
     }
 
-    fn generate_align(&self, n: &str) -> String {
-        format!(".align {}\n", n)
+    fn generate_align(&self, out: &mut String, n: &str) {
+        emit!(out, ".align {}\n", n);
     }
 
-    fn generate_data_byte(&self, name: &str, values: &[String]) -> String {
-        format!(
+    fn generate_data_byte(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(
+            out,
             ".type {}, %object\n{}: .byte {}\n",
             name,
             name,
             values.join(", ")
-        )
+        );
     }
 
-    fn generate_data_word(&self, name: &str, values: &[String]) -> String {
-        format!(
+    fn generate_data_word(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(
+            out,
             ".type {}, %object\n{}: .hword {}\n",
             name,
             name,
             values.join(", ")
-        )
+        );
     }
 
-    fn generate_data_dword(&self, name: &str, values: &[String]) -> String {
-        format!(
+    fn generate_data_dword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(
+            out,
             ".type {}, %object\n{}: .word {}\n",
             name,
             name,
             values.join(", ")
-        )
+        );
     }
 
-    fn generate_data_qword(&self, _name: &str, _values: &[String]) -> String {
-        "    @ 64-bit data not directly supported in ARM32\n".to_string()
+    fn generate_data_qword(&self, out: &mut String, _name: &str, _values: &[String]) {
+        out.push_str("    @ 64-bit data not directly supported in ARM32\n");
     }
 
-    fn generate_reserve_byte(&self, name: &str, count: &str) -> String {
-        format!(".type {}, %object\n{}: .skip {}\n", name, name, count)
+    fn generate_reserve_byte(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, ".type {}, %object\n{}: .skip {}\n", name, name, count);
     }
 
-    fn generate_reserve_word(&self, name: &str, count: &str) -> String {
-        format!(
+    fn generate_reserve_word(&self, out: &mut String, name: &str, count: &str) {
+        emit!(
+            out,
             ".type {}, %object\n{}: .skip {}\n",
             name,
             name,
             2 * count.parse::<usize>().unwrap_or(1)
-        )
+        );
     }
 
-    fn generate_reserve_dword(&self, name: &str, count: &str) -> String {
-        format!(
+    fn generate_reserve_dword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(
+            out,
             ".type {}, %object\n{}: .skip {}\n",
             name,
             name,
             4 * count.parse::<usize>().unwrap_or(1)
-        )
+        );
     }
 
-    fn generate_reserve_qword(&self, _name: &str, _count: &str) -> String {
-        "    @ 64-bit reservations not directly supported in ARM32\n".to_string()
+    fn generate_reserve_qword(&self, out: &mut String, _name: &str, _count: &str) {
+        out.push_str("    @ 64-bit reservations not directly supported in ARM32\n");
     }
 
-    fn generate_equ(&self, name: &str, value: &str) -> String {
-        format!("{} = {}\n", name, value)
+    fn generate_equ(&self, out: &mut String, name: &str, value: &str) {
+        emit!(out, "{} = {}\n", name, value);
     }
 
-    fn generate_section(&self, section: &Section) -> String {
+    fn generate_section(&self, out: &mut String, section: &Section) {
         match section {
-            Section::Text => ".section .text,\"ax\",%progbits\n".to_string(),
-            Section::Data => ".section .data,\"aw\",%progbits\n".to_string(),
-            Section::Bss => ".section .bss,\"aw\",%nobits\n".to_string(),
-            Section::Rodata => ".section .rodata,\"a\",%progbits\n".to_string(),
-            Section::Custom(s) => format!(".section {}\n", s),
+            Section::Text => out.push_str(".section .text,\"ax\",%progbits\n"),
+            Section::Data => out.push_str(".section .data,\"aw\",%progbits\n"),
+            Section::Bss => out.push_str(".section .bss,\"aw\",%nobits\n"),
+            Section::Rodata => out.push_str(".section .rodata,\"a\",%progbits\n"),
+            Section::Custom(s) => emit!(out, ".section {}\n", s),
         }
     }
 
-    fn generate_label(&self, name: &str) -> String {
-        format!("{}:\n", name)
+    fn generate_label(&self, out: &mut String, name: &str) {
+        emit!(out, "{}:\n", name);
     }
 
     fn map_operand(&self, operand: &str) -> String {
@@ -1151,4 +1463,57 @@ impl ArchCodeGen for ARM32CodeGen {
             operand.to_string()
         }
     }
+
+    fn get_register_width(&self) -> u32 {
+        32
+    }
+
+    fn get_reserved_registers(&self) -> Vec<String> {
+        ["r11", "r12", "r13", "r14", "sp", "lr", "pc", "ip"]
+            .iter()
+            .map(|r| r.to_string())
+            .collect()
+    }
+
+    fn get_callee_saved_registers(&self) -> Vec<String> {
+        ["r4", "r5", "r6", "r7", "r8", "r9", "r10", "r11"]
+            .iter()
+            .map(|r| r.to_string())
+            .collect()
+    }
+
+    fn get_push_size(&self) -> u32 {
+        4
+    }
+
+    fn get_clobbered_registers(&self, instruction: &Instruction) -> Vec<String> {
+        use Instruction::*;
+        let registers: &[&str] = match instruction {
+            // Division calls into the EABI runtime.
+            Div(_) | Idiv(_) | Mod(_) | Call(_) => &["r0", "r1", "r2", "r3", "r12", "lr"],
+            Syscall(_) => &["r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7"],
+            _ => &[],
+        };
+        registers.iter().map(|r| r.to_string()).collect()
+    }
+
+    fn get_frame_layout(&self) -> Option<FrameLayout> {
+        // `enter` pushes fp and lr and points fp at the pair.
+        Some(FrameLayout {
+            return_address: 0,
+            frame_pointer: "r11",
+            record: &["r11", "lr"],
+            slot: 4,
+        })
+    }
+
+    fn set_position_independent(&mut self, pic: Pic) -> Result<(), String> {
+        if pic.format == Format::MachO {
+            return Err(
+                "position-independent code is not supported for 32-bit ARM Mach-O".to_string(),
+            );
+        }
+        self.pic = Some(pic);
+        Ok(())
+    }
 }
@@ -3,6 +3,8 @@ use std::collections::HashMap;
 
 pub struct ARM64CodeGen {
     register_map: HashMap<String, String>,
+    /// Symbols of the program, once code is position-independent.
+    pic: Option<Pic>,
 }
 
 impl ARM64CodeGen {
@@ -49,7 +51,80 @@ impl ARM64CodeGen {
         register_map.insert("sb".to_string(), "x29".to_string()); // frame pointer (FP)
         register_map.insert("ip".to_string(), "x30".to_string()); // link register (LR)
 
-        ARM64CodeGen { register_map }
+        ARM64CodeGen {
+            register_map,
+            pic: None,
+        }
+    }
+
+    /// The address of `symbol + offset` in `dst`, from the 4 KB page `adrp`
+    /// finds relative to pc, or loaded from the GOT if another module may
+    /// provide the symbol.
+    fn address(&self, out: &mut String, pic: &Pic, dst: &str, symbol: &str, offset: i64) {
+        let preemptible = pic.is_preemptible(symbol);
+        let symbol = &pic.name(symbol);
+        if pic.format == Format::MachO {
+            if preemptible {
+                emit!(
+                    out,
+                    "    adrp {}, {}@GOTPAGE\n    ldr {}, [{}, {}@GOTPAGEOFF]\n",
+                    dst,
+                    symbol,
+                    dst,
+                    dst,
+                    symbol
+                );
+            } else {
+                emit!(
+                    out,
+                    "    adrp {}, {}@PAGE\n    add {}, {}, {}@PAGEOFF\n",
+                    dst,
+                    symbol,
+                    dst,
+                    dst,
+                    symbol
+                );
+            }
+            self.offset(out, dst, offset);
+            return;
+        }
+        if preemptible {
+            emit!(
+                out,
+                "    adrp {}, :got:{}\n    ldr {}, [{}, :got_lo12:{}]\n",
+                dst,
+                symbol,
+                dst,
+                dst,
+                symbol
+            );
+            self.offset(out, dst, offset);
+            return;
+        }
+        let expression = Pic::expression(symbol, offset);
+        emit!(
+            out,
+            "    adrp {}, {}\n    add {}, {}, :lo12:{}\n",
+            dst,
+            expression,
+            dst,
+            dst,
+            expression
+        );
+    }
+
+    /// Adds `offset` to the address in `dst`, through x17 if it does not fit
+    /// an immediate.
+    fn offset(&self, out: &mut String, dst: &str, offset: i64) {
+        match offset {
+            0 => {}
+            1..4096 => emit!(out, "    add {}, {}, #{}\n", dst, dst, offset),
+            -4095..0 => emit!(out, "    sub {}, {}, #{}\n", dst, dst, -offset),
+            _ => {
+                self.generate_mov(out, "x17", &offset.to_string());
+                emit!(out, "    add {}, {}, x17\n", dst, dst);
+            }
+        }
     }
 }
 
@@ -62,73 +137,124 @@ impl ArchCodeGen for ARM64CodeGen {
         ".text\n\n".to_string()
     }
 
-    fn generate_mov(&self, dst: &str, src: &str) -> String {
+    fn generate_mov(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
+        if let Some(pic) = &self.pic
+            && !src.starts_with('[')
+            && let Some((symbol, offset)) = pic.address(src)
+        {
+            self.address(out, pic, &dst_reg, symbol, offset);
+            return;
+        }
+
         if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let value: i64 = src_op.parse().unwrap_or(0);
             if value >= 0 && value <= 65535 {
-                return format!("    mov {}, #{}\n", dst_reg, src_op);
+                emit!(out, "    mov {}, #{}\n", dst_reg, src_op);
+                return;
             } else {
                 let low = value & 0xFFFF;
                 let high = (value >> 16) & 0xFFFF;
                 if high == 0 {
-                    return format!("    mov {}, #{}\n", dst_reg, low);
+                    emit!(out, "    mov {}, #{}\n", dst_reg, low);
+                    return;
                 } else {
-                    return format!(
+                    emit!(
+                        out,
                         "    movz {}, #{}\n    movk {}, #{}, lsl #16\n",
-                        dst_reg, low, dst_reg, high
+                        dst_reg,
+                        low,
+                        dst_reg,
+                        high
                     );
+                    return;
                 }
             }
         }
 
         if src_op.starts_with('x') || src_op.starts_with('w') || src_op == "sp" {
-            return format!("    mov {}, {}\n", dst_reg, src_op);
+            emit!(out, "    mov {}, {}\n", dst_reg, src_op);
+            return;
         }
 
         // format!("    mov {}, #{}\n", dst_reg, src_op)
-        format!("    ldr {}, ={}\n", dst_reg, src_op)
+        emit!(out, "    ldr {}, ={}\n", dst_reg, src_op);
     }
 
-    fn generate_lea(&self, dst: &str, src: &str) -> String {
+    fn generate_lea(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(src)
+        {
+            self.address(out, pic, &self.map_operand(dst), symbol, offset);
+            return;
+        }
         let src_clean = if src.starts_with('[') && src.ends_with(']') {
             &src[1..src.len() - 1]
         } else {
             src
         };
-        format!("    adr {}, {}\n", self.map_operand(dst), src_clean)
+        emit!(out, "    adr {}, {}\n", self.map_operand(dst), src_clean);
     }
 
-    fn generate_load(&self, dst: &str, src: &str) -> String {
+    fn generate_load(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
 
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(src)
+        {
+            self.address(out, pic, "x16", symbol, offset);
+            emit!(out, "    ldr {}, [x16]\n", dst_reg);
+            return;
+        }
+
         if !src.starts_with('[') && !src.ends_with(']') {
-            return format!("    ldr {}, ={}\n", dst_reg, src);
+            emit!(out, "    ldr {}, ={}\n", dst_reg, src);
+            return;
         }
 
         let inner = &src[1..src.len() - 1].trim();
 
         // If it's a register reference like [r1], map it properly
         if let Some(mapped_reg) = self.register_map.get(&inner.to_string()) {
-            return format!("    ldr {}, [{}]\n", dst_reg, mapped_reg);
+            emit!(out, "    ldr {}, [{}]\n", dst_reg, mapped_reg);
+            return;
         }
 
         // If it contains arithmetic like [r1 + offset]
         if inner.contains('+') || inner.contains('-') {
-            return format!("    ldr {}, {}\n", dst_reg, self.map_memory_operand(src));
+            emit!(
+                out,
+                "    ldr {}, {}\n",
+                dst_reg,
+                self.map_memory_operand(src)
+            );
+            return;
         }
 
         // If it's a symbol/label, load from that address
-        format!("    adr x16, {}\n    ldr {}, [x16]\n", inner, dst_reg)
+        emit!(out, "    adr x16, {}\n    ldr {}, [x16]\n", inner, dst_reg);
     }
 
-    fn generate_store(&self, dst: &str, src: &str) -> String {
+    fn generate_store(&self, out: &mut String, dst: &str, src: &str) {
         let src_reg = self.map_operand(src);
 
         if src_reg.chars().all(|c| c.is_ascii_digit()) {
-            return format!("    // ERROR: str requires a register, got {}\n", src_reg);
+            emit!(
+                out,
+                "    // ERROR: str requires a register, got {}\n",
+                src_reg
+            );
+            return;
+        }
+
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(dst)
+        {
+            self.address(out, pic, "x16", symbol, offset);
+            emit!(out, "    str {}, [x16]\n", src_reg);
+            return;
         }
 
         // Handle memory operand properly
@@ -137,215 +263,228 @@ impl ArchCodeGen for ARM64CodeGen {
 
             // If it's a register reference like [r1], map it properly
             if let Some(mapped_reg) = self.register_map.get(&inner.to_string()) {
-                return format!("    str {}, [{}]\n", src_reg, mapped_reg);
+                emit!(out, "    str {}, [{}]\n", src_reg, mapped_reg);
+                return;
             }
 
             // If it contains arithmetic like [r1 + offset]
             if inner.contains('+') || inner.contains('-') {
                 let dst_mem = self.map_memory_operand(dst);
-                return format!("    str {}, {}\n", src_reg, dst_mem);
+                emit!(out, "    str {}, {}\n", src_reg, dst_mem);
+                return;
             }
 
             // If it's a symbol/label, load address first then store
-            return format!("    adr x16, {}\n    str {}, [x16]\n", inner, src_reg);
+            {
+                emit!(out, "    adr x16, {}\n    str {}, [x16]\n", inner, src_reg);
+                return;
+            };
         }
 
         // Direct symbol without brackets - load address and store
-        format!("    adr x16, {}\n    str {}, [x16]\n", dst, src_reg)
+        emit!(out, "    adr x16, {}\n    str {}, [x16]\n", dst, src_reg);
     }
 
-    fn generate_add(&self, dst: &str, src: &str) -> String {
+    fn generate_add(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    add {}, {}, #{}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    add {}, {}, #{}\n", dst_reg, dst_reg, src_op);
         } else {
-            format!("    add {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    add {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_sub(&self, dst: &str, src: &str) -> String {
+    fn generate_sub(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    sub {}, {}, #{}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    sub {}, {}, #{}\n", dst_reg, dst_reg, src_op);
         } else {
-            format!("    sub {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    sub {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_mul(&self, dst: &str, src: &str) -> String {
+    fn generate_mul(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         // ARM64 mul doesn't accept immediate values - load into register first
         if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!(
+            emit!(
+                out,
                 "    mov x16, #{}\n    mul {}, {}, x16\n",
-                src_op, dst_reg, dst_reg
-            )
+                src_op,
+                dst_reg,
+                dst_reg
+            );
         } else {
-            format!("    mul {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    mul {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_div(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_div(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    sdiv {}, {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
 
-    fn generate_inc(&self, dst: &str) -> String {
-        format!(
+    fn generate_inc(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    add {}, {}, #1\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_dec(&self, dst: &str) -> String {
-        format!(
+    fn generate_dec(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    sub {}, {}, #1\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_neg(&self, dst: &str) -> String {
-        format!(
+    fn generate_neg(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    neg {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_and(&self, dst: &str, src: &str) -> String {
+    fn generate_and(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    and {}, {}, #{}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    and {}, {}, #{}\n", dst_reg, dst_reg, src_op);
         } else {
-            format!("    and {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    and {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_or(&self, dst: &str, src: &str) -> String {
+    fn generate_or(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    orr {}, {}, #{}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    orr {}, {}, #{}\n", dst_reg, dst_reg, src_op);
         } else {
-            format!("    orr {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    orr {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_xor(&self, dst: &str, src: &str) -> String {
+    fn generate_xor(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    eor {}, {}, #{}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    eor {}, {}, #{}\n", dst_reg, dst_reg, src_op);
         } else {
-            format!("    eor {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    eor {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_not(&self, dst: &str) -> String {
-        format!(
+    fn generate_not(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    mvn {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_shl(&self, dst: &str, src: &str) -> String {
+    fn generate_shl(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    lsl {}, {}, #{}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    lsl {}, {}, #{}\n", dst_reg, dst_reg, src_op);
         } else {
-            format!("    lsl {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    lsl {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_shr(&self, dst: &str, src: &str) -> String {
+    fn generate_shr(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
         if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    lsr {}, {}, #{}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    lsr {}, {}, #{}\n", dst_reg, dst_reg, src_op);
         } else {
-            format!("    lsr {}, {}, {}\n", dst_reg, dst_reg, src_op)
+            emit!(out, "    lsr {}, {}, {}\n", dst_reg, dst_reg, src_op);
         }
     }
 
-    fn generate_cmp(&self, op1: &str, op2: &str) -> String {
+    fn generate_cmp(&self, out: &mut String, op1: &str, op2: &str) {
         let op1_reg = self.map_operand(op1);
         let op2_op = self.map_operand(op2);
 
         if op2_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    cmp {}, #{}\n", op1_reg, op2_op)
+            emit!(out, "    cmp {}, #{}\n", op1_reg, op2_op);
         } else {
-            format!("    cmp {}, {}\n", op1_reg, op2_op)
+            emit!(out, "    cmp {}, {}\n", op1_reg, op2_op);
         }
     }
 
-    fn generate_test(&self, op1: &str, op2: &str) -> String {
+    fn generate_test(&self, out: &mut String, op1: &str, op2: &str) {
         let op1_reg = self.map_operand(op1);
         let op2_op = self.map_operand(op2);
 
         if op2_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    tst {}, #{}\n", op1_reg, op2_op)
+            emit!(out, "    tst {}, #{}\n", op1_reg, op2_op);
         } else {
-            format!("    tst {}, {}\n", op1_reg, op2_op)
+            emit!(out, "    tst {}, {}\n", op1_reg, op2_op);
         }
     }
 
-    fn generate_jmp(&self, label: &str) -> String {
-        format!("    b {}\n", label)
+    fn generate_jmp(&self, out: &mut String, label: &str) {
+        emit!(out, "    b {}\n", label);
     }
 
-    fn generate_je(&self, label: &str) -> String {
-        format!("    b.eq {}\n", label)
+    fn generate_je(&self, out: &mut String, label: &str) {
+        emit!(out, "    b.eq {}\n", label);
     }
 
-    fn generate_jne(&self, label: &str) -> String {
-        format!("    b.ne {}\n", label)
+    fn generate_jne(&self, out: &mut String, label: &str) {
+        emit!(out, "    b.ne {}\n", label);
     }
 
-    fn generate_jg(&self, label: &str) -> String {
-        format!("    b.gt {}\n", label)
+    fn generate_jg(&self, out: &mut String, label: &str) {
+        emit!(out, "    b.gt {}\n", label);
     }
 
-    fn generate_jl(&self, label: &str) -> String {
-        format!("    b.lt {}\n", label)
+    fn generate_jl(&self, out: &mut String, label: &str) {
+        emit!(out, "    b.lt {}\n", label);
     }
 
-    fn generate_jge(&self, label: &str) -> String {
-        format!("    b.ge {}\n", label)
+    fn generate_jge(&self, out: &mut String, label: &str) {
+        emit!(out, "    b.ge {}\n", label);
     }
 
-    fn generate_jle(&self, label: &str) -> String {
-        format!("    b.le {}\n", label)
+    fn generate_jle(&self, out: &mut String, label: &str) {
+        emit!(out, "    b.le {}\n", label);
     }
 
-    fn generate_call(&self, func: &str) -> String {
-        format!("    bl {}\n", func)
+    fn generate_call(&self, out: &mut String, func: &str) {
+        emit!(out, "    bl {}\n", func);
     }
 
-    fn generate_ret(&self) -> String {
-        "    ret\n".to_string()
+    fn generate_ret(&self, out: &mut String) {
+        out.push_str("    ret\n");
     }
 
-    fn generate_syscall(&self, name: &str) -> String {
+    fn generate_syscall(&self, out: &mut String, name: &str) {
         // Linux AArch64: x8 = syscall#, x0..x7 = args, svc 0
         let syscall_num = match name {
             "read" => "63",
@@ -358,639 +497,737 @@ impl ArchCodeGen for ARM64CodeGen {
             "brk" => "214",
             "fstat" => "80",
             _ => {
-                return format!(
+                emit!(
+                    out,
                     "    // Unknown syscall: {}\n    mov x8, #0\n    svc 0\n",
                     name
                 );
+                return;
             }
         };
-        format!("    mov x8, #{}\n    svc 0\n", syscall_num)
+        emit!(out, "    mov x8, #{}\n    svc 0\n", syscall_num);
     }
 
-    fn generate_cmov_eq(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_eq(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len()) % 10000;
-            format!(
+            emit!(
+                out,
                 "    b.ne .Lcmove_end_{}\n    mov {}, {}\n.Lcmove_end_{}:\n",
-                hash, dst_reg, src_op, hash
-            )
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    csel {}, {}, {}, eq\n", dst_reg, src_op, dst_reg)
+            emit!(out, "    csel {}, {}, {}, eq\n", dst_reg, src_op, dst_reg);
         }
     }
-    fn generate_cmov_ne(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_ne(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 1) % 10000;
-            format!(
+            emit!(
+                out,
                 "    b.eq .Lcmovne_end_{}\n    mov {}, {}\n.Lcmovne_end_{}:\n",
-                hash, dst_reg, src_op, hash
-            )
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    csel {}, {}, {}, ne\n", dst_reg, src_op, dst_reg)
+            emit!(out, "    csel {}, {}, {}, ne\n", dst_reg, src_op, dst_reg);
         }
     }
-    fn generate_cmov_lt(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_lt(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 2) % 10000;
-            format!(
+            emit!(
+                out,
                 "    b.ge .Lcmovlt_end_{}\n    mov {}, {}\n.Lcmovlt_end_{}:\n",
-                hash, dst_reg, src_op, hash
-            )
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    csel {}, {}, {}, lt\n", dst_reg, src_op, dst_reg)
+            emit!(out, "    csel {}, {}, {}, lt\n", dst_reg, src_op, dst_reg);
         }
     }
-    fn generate_cmov_le(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_le(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 3) % 10000;
-            format!(
+            emit!(
+                out,
                 "    b.gt .Lcmovle_end_{}\n    mov {}, {}\n.Lcmovle_end_{}:\n",
-                hash, dst_reg, src_op, hash
-            )
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    csel {}, {}, {}, le\n", dst_reg, src_op, dst_reg)
+            emit!(out, "    csel {}, {}, {}, le\n", dst_reg, src_op, dst_reg);
         }
     }
-    fn generate_cmov_gt(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_gt(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 4) % 10000;
-            format!(
+            emit!(
+                out,
                 "    b.le .Lcmovgt_end_{}\n    mov {}, {}\n.Lcmovgt_end_{}:\n",
-                hash, dst_reg, src_op, hash
-            )
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    csel {}, {}, {}, gt\n", dst_reg, src_op, dst_reg)
+            emit!(out, "    csel {}, {}, {}, gt\n", dst_reg, src_op, dst_reg);
         }
     }
-    fn generate_cmov_ge(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_ge(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 5) % 10000;
-            format!(
+            emit!(
+                out,
                 "    b.lt .Lcmovge_end_{}\n    mov {}, {}\n.Lcmovge_end_{}:\n",
-                hash, dst_reg, src_op, hash
-            )
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    csel {}, {}, {}, ge\n", dst_reg, src_op, dst_reg)
+            emit!(out, "    csel {}, {}, {}, ge\n", dst_reg, src_op, dst_reg);
         }
     }
-    fn generate_cmov_ov(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_ov(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 6) % 10000;
-            format!(
+            emit!(
+                out,
                 "    b.vc .Lcmovo_end_{}\n    mov {}, {}\n.Lcmovo_end_{}:\n",
-                hash, dst_reg, src_op, hash
-            )
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    csel {}, {}, {}, vs\n", dst_reg, src_op, dst_reg)
+            emit!(out, "    csel {}, {}, {}, vs\n", dst_reg, src_op, dst_reg);
         }
     }
-    fn generate_cmov_no(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_no(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 7) % 10000;
-            format!(
+            emit!(
+                out,
                 "    b.vs .Lcmovno_end_{}\n    mov {}, {}\n.Lcmovno_end_{}:\n",
-                hash, dst_reg, src_op, hash
-            )
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    csel {}, {}, {}, vc\n", dst_reg, src_op, dst_reg)
+            emit!(out, "    csel {}, {}, {}, vc\n", dst_reg, src_op, dst_reg);
         }
     }
-    fn generate_cmov_s(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_s(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 8) % 10000;
-            format!(
+            emit!(
+                out,
                 "    b.pl .Lcmovs_end_{}\n    mov {}, {}\n.Lcmovs_end_{}:\n",
-                hash, dst_reg, src_op, hash
-            )
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    csel {}, {}, {}, mi\n", dst_reg, src_op, dst_reg)
+            emit!(out, "    csel {}, {}, {}, mi\n", dst_reg, src_op, dst_reg);
         }
     }
-    fn generate_cmov_ns(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_ns(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 9) % 10000;
-            format!(
+            emit!(
+                out,
                 "    b.mi .Lcmovns_end_{}\n    mov {}, {}\n.Lcmovns_end_{}:\n",
-                hash, dst_reg, src_op, hash
-            )
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    csel {}, {}, {}, pl\n", dst_reg, src_op, dst_reg)
+            emit!(out, "    csel {}, {}, {}, pl\n", dst_reg, src_op, dst_reg);
         }
     }
-    fn generate_cmov_p(&self, dst: &str, src: &str) -> String {
-        "// ARM64 has no parity flag, cannot synthesize cmov_p\n".to_string()
+    fn generate_cmov_p(&self, out: &mut String, dst: &str, src: &str) {
+        out.push_str("// ARM64 has no parity flag, cannot synthesize cmov_p\n");
     }
-    fn generate_cmov_np(&self, dst: &str, src: &str) -> String {
-        "// ARM64 has no parity flag, cannot synthesize cmov_np\n".to_string()
+    fn generate_cmov_np(&self, out: &mut String, dst: &str, src: &str) {
+        out.push_str("// ARM64 has no parity flag, cannot synthesize cmov_np\n");
     }
-    fn generate_cmov_a(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_a(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 12) % 10000;
-            format!(
+            emit!(
+                out,
                 "    b.ls .Lcmova_end_{}\n    mov {}, {}\n.Lcmova_end_{}:\n",
-                hash, dst_reg, src_op, hash
-            )
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    csel {}, {}, {}, hi\n", dst_reg, src_op, dst_reg)
+            emit!(out, "    csel {}, {}, {}, hi\n", dst_reg, src_op, dst_reg);
         }
     }
-    fn generate_cmov_ae(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_ae(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 13) % 10000;
-            format!(
+            emit!(
+                out,
                 "    b.lo .Lcmovae_end_{}\n    mov {}, {}\n.Lcmovae_end_{}:\n",
-                hash, dst_reg, src_op, hash
-            )
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    csel {}, {}, {}, hs\n", dst_reg, src_op, dst_reg)
+            emit!(out, "    csel {}, {}, {}, hs\n", dst_reg, src_op, dst_reg);
         }
     }
-    fn generate_cmov_b(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_b(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 14) % 10000;
-            format!(
+            emit!(
+                out,
                 "    b.hs .Lcmovb_end_{}\n    mov {}, {}\n.Lcmovb_end_{}:\n",
-                hash, dst_reg, src_op, hash
-            )
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    csel {}, {}, {}, lo\n", dst_reg, src_op, dst_reg)
+            emit!(out, "    csel {}, {}, {}, lo\n", dst_reg, src_op, dst_reg);
         }
     }
-    fn generate_cmov_be(&self, dst: &str, src: &str) -> String {
+    fn generate_cmov_be(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
         if src.chars().all(|c| c.is_ascii_digit() || c == '-') {
             let hash = (dst.len() + src.len() + 15) % 10000;
-            format!(
+            emit!(
+                out,
                 "    b.hi .Lcmovbe_end_{}\n    mov {}, {}\n.Lcmovbe_end_{}:\n",
-                hash, dst_reg, src_op, hash
-            )
+                hash,
+                dst_reg,
+                src_op,
+                hash
+            );
         } else {
-            format!("    csel {}, {}, {}, ls\n", dst_reg, src_op, dst_reg)
+            emit!(out, "    csel {}, {}, {}, ls\n", dst_reg, src_op, dst_reg);
         }
     }
 
-    fn generate_push(&self, src: &str) -> String {
+    fn generate_push(&self, out: &mut String, src: &str) {
         // ARM64 doesn't have a direct push instruction
-        format!("    str {}, [sp, #-16]!\n", self.map_operand(src))
+        emit!(out, "    str {}, [sp, #-16]!\n", self.map_operand(src));
     }
 
-    fn generate_pop(&self, dst: &str) -> String {
-        format!("    ldr {}, [sp], #16\n", self.map_operand(dst))
+    fn generate_pop(&self, out: &mut String, dst: &str) {
+        emit!(out, "    ldr {}, [sp], #16\n", self.map_operand(dst));
     }
 
-    fn generate_pusha(&self) -> String {
+    fn generate_pusha(&self, out: &mut String) {
         // ARM64 doesn't have pusha, save multiple registers
-        "    stp x0, x1, [sp, #-16]!\n    stp x2, x3, [sp, #-16]!\n    stp x4, x5, [sp, #-16]!\n    stp x6, x7, [sp, #-16]!\n".to_string()
+        out.push_str("    stp x0, x1, [sp, #-16]!\n    stp x2, x3, [sp, #-16]!\n    stp x4, x5, [sp, #-16]!\n    stp x6, x7, [sp, #-16]!\n");
     }
 
-    fn generate_popa(&self) -> String {
-        "    ldp x6, x7, [sp], #16\n    ldp x4, x5, [sp], #16\n    ldp x2, x3, [sp], #16\n    ldp x0, x1, [sp], #16\n".to_string()
+    fn generate_popa(&self, out: &mut String) {
+        out.push_str("    ldp x6, x7, [sp], #16\n    ldp x4, x5, [sp], #16\n    ldp x2, x3, [sp], #16\n    ldp x0, x1, [sp], #16\n");
     }
 
-    fn generate_enter(&self, frame_size: &str, _nesting: &str) -> String {
-        format!(
+    fn generate_enter(&self, out: &mut String, frame_size: &str, _nesting: &str) {
+        emit!(
+            out,
             "    stp x29, x30, [sp, #-16]!\n    mov x29, sp\n    sub sp, sp, #{}\n",
             frame_size
-        )
+        );
     }
 
-    fn generate_leave(&self) -> String {
-        "    mov sp, x29\n    ldp x29, x30, [sp], #16\n".to_string()
+    fn generate_leave(&self, out: &mut String) {
+        out.push_str("    mov sp, x29\n    ldp x29, x30, [sp], #16\n");
     }
 
-    fn generate_imul(&self, dst: &str, src: &str) -> String {
-        self.generate_mul(dst, src)
+    fn generate_imul(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_mul(out, dst, src);
     }
 
-    fn generate_idiv(&self, dst: &str, src: &str) -> String {
-        self.generate_div(dst, src)
+    fn generate_idiv(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_div(out, dst, src);
     }
 
-    fn generate_mod(&self, dst: &str, src: &str) -> String {
+    fn generate_mod(&self, out: &mut String, dst: &str, src: &str) {
         // ARM64: msub after sdiv for modulo
         let t = "x16"; // Use x16 as scratch register
         let dst = self.map_operand(dst);
         let src = self.map_operand(src);
-        format!(
+        emit!(
+            out,
             "    sdiv {t}, {dst}, {src}\n    msub {dst}, {t}, {src}, {dst}\n",
             t = t,
             dst = dst,
             src = src
-        )
+        );
     }
 
-    fn generate_andn(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_mulh(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
+            "    smulh {}, {}, {}\n",
+            self.map_operand(dst),
+            self.map_operand(dst),
+            self.map_operand(src)
+        );
+    }
+
+    fn generate_andn(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    bic {}, {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
 
-    fn generate_sal(&self, dst: &str, src: &str) -> String {
-        self.generate_shl(dst, src)
+    fn generate_sal(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_shl(out, dst, src);
     }
 
-    fn generate_sar(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_sar(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    asr {}, {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
 
-    fn generate_rol(&self, dst: &str, src: &str) -> String {
+    fn generate_rol(&self, out: &mut String, dst: &str, src: &str) {
         // ARM64 has ROR, for ROL we use ROR with (64-src)
         let src_val = src.parse::<u32>().unwrap_or(0);
-        format!(
+        emit!(
+            out,
             "    ror {}, {}, #{}\n",
             self.map_operand(dst),
             self.map_operand(dst),
             64 - src_val
-        )
+        );
     }
 
-    fn generate_ror(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_ror(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    ror {}, {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
 
-    fn generate_rcl(&self, _dst: &str, _src: &str) -> String {
-        "// ARM64 has no direct RCL (rotate through carry)\n".to_string()
+    fn generate_rcl(&self, out: &mut String, _dst: &str, _src: &str) {
+        out.push_str("// ARM64 has no direct RCL (rotate through carry)\n");
     }
 
-    fn generate_rcr(&self, _dst: &str, _src: &str) -> String {
-        "// ARM64 has no direct RCR (rotate through carry)\n".to_string()
+    fn generate_rcr(&self, out: &mut String, _dst: &str, _src: &str) {
+        out.push_str("// ARM64 has no direct RCR (rotate through carry)\n");
     }
 
-    fn generate_bextr(&self, dst: &str, src: &str, imm: &str) -> String {
+    fn generate_bextr(&self, out: &mut String, dst: &str, src: &str, imm: &str) {
         // Bit field extract: UBFX
         let src = self.map_operand(src);
         if let Some((lsb, width)) = imm.split_once(',') {
-            format!(
+            emit!(
+                out,
                 "    ubfx {}, {}, #{}, #{}\n",
                 self.map_operand(dst),
                 src,
                 lsb.trim(),
                 width.trim()
-            )
+            );
         } else {
-            "// ARM64: bextr expects imm as lsb,width\n".to_string()
+            out.push_str("// ARM64: bextr expects imm as lsb,width\n");
         }
     }
 
-    fn generate_bsf(&self, dst: &str, src: &str) -> String {
+    fn generate_bsf(&self, out: &mut String, dst: &str, src: &str) {
         // Count trailing zeros: rbit + clz
-        format!(
+        emit!(
+            out,
             "    rbit {}, {}\n    clz {}, {}\n",
             self.map_operand(dst),
             self.map_operand(src),
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_bsr(&self, dst: &str, src: &str) -> String {
+    fn generate_bsr(&self, out: &mut String, dst: &str, src: &str) {
         // Count leading zeros
-        format!(
+        emit!(
+            out,
             "    clz {}, {}\n",
             self.map_operand(dst),
             self.map_operand(src)
-        )
+        );
     }
 
-    fn generate_bt(&self, dst: &str, bit: &str) -> String {
+    fn generate_bt(&self, out: &mut String, dst: &str, bit: &str) {
         // Test bit: tst dst, #(1 << bit)
-        format!(
+        emit!(
+            out,
             "    tst {}, #{}\n",
             self.map_operand(dst),
             1u64 << bit.parse::<u32>().unwrap_or(0)
-        )
+        );
     }
 
-    fn generate_btr(&self, dst: &str, bit: &str) -> String {
+    fn generate_btr(&self, out: &mut String, dst: &str, bit: &str) {
         // Bit reset: bic dst, dst, #(1 << bit)
-        format!(
+        emit!(
+            out,
             "    bic {}, {}, #{}\n",
             self.map_operand(dst),
             self.map_operand(dst),
             1u64 << bit.parse::<u32>().unwrap_or(0)
-        )
+        );
     }
 
-    fn generate_bts(&self, dst: &str, bit: &str) -> String {
+    fn generate_bts(&self, out: &mut String, dst: &str, bit: &str) {
         // Bit set: orr dst, dst, #(1 << bit)
-        format!(
+        emit!(
+            out,
             "    orr {}, {}, #{}\n",
             self.map_operand(dst),
             self.map_operand(dst),
             1u64 << bit.parse::<u32>().unwrap_or(0)
-        )
+        );
     }
 
-    fn generate_btc(&self, dst: &str, bit: &str) -> String {
+    fn generate_btc(&self, out: &mut String, dst: &str, bit: &str) {
         // Bit toggle: eor dst, dst, #(1 << bit)
-        format!(
+        emit!(
+            out,
             "    eor {}, {}, #{}\n",
             self.map_operand(dst),
             self.map_operand(dst),
             1u64 << bit.parse::<u32>().unwrap_or(0)
-        )
+        );
     }
 
-    fn generate_set_eq(&self, dst: &str) -> String {
-        format!("    cset {}, eq\n", self.map_operand(dst))
+    fn generate_set_eq(&self, out: &mut String, dst: &str) {
+        emit!(out, "    cset {}, eq\n", self.map_operand(dst));
     }
-    fn generate_set_ne(&self, dst: &str) -> String {
-        format!("    cset {}, ne\n", self.map_operand(dst))
+    fn generate_set_ne(&self, out: &mut String, dst: &str) {
+        emit!(out, "    cset {}, ne\n", self.map_operand(dst));
     }
-    fn generate_set_lt(&self, dst: &str) -> String {
-        format!("    cset {}, lt\n", self.map_operand(dst))
+    fn generate_set_lt(&self, out: &mut String, dst: &str) {
+        emit!(out, "    cset {}, lt\n", self.map_operand(dst));
     }
-    fn generate_set_le(&self, dst: &str) -> String {
-        format!("    cset {}, le\n", self.map_operand(dst))
+    fn generate_set_le(&self, out: &mut String, dst: &str) {
+        emit!(out, "    cset {}, le\n", self.map_operand(dst));
     }
-    fn generate_set_gt(&self, dst: &str) -> String {
-        format!("    cset {}, gt\n", self.map_operand(dst))
+    fn generate_set_gt(&self, out: &mut String, dst: &str) {
+        emit!(out, "    cset {}, gt\n", self.map_operand(dst));
     }
-    fn generate_set_ge(&self, dst: &str) -> String {
-        format!("    cset {}, ge\n", self.map_operand(dst))
+    fn generate_set_ge(&self, out: &mut String, dst: &str) {
+        emit!(out, "    cset {}, ge\n", self.map_operand(dst));
     }
-    fn generate_set_ov(&self, dst: &str) -> String {
-        format!("    cset {}, vs\n", self.map_operand(dst))
+    fn generate_set_ov(&self, out: &mut String, dst: &str) {
+        emit!(out, "    cset {}, vs\n", self.map_operand(dst));
     }
-    fn generate_set_no(&self, dst: &str) -> String {
-        format!("    cset {}, vc\n", self.map_operand(dst))
+    fn generate_set_no(&self, out: &mut String, dst: &str) {
+        emit!(out, "    cset {}, vc\n", self.map_operand(dst));
     }
-    fn generate_set_s(&self, dst: &str) -> String {
-        format!("    cset {}, mi\n", self.map_operand(dst))
+    fn generate_set_s(&self, out: &mut String, dst: &str) {
+        emit!(out, "    cset {}, mi\n", self.map_operand(dst));
     }
-    fn generate_set_ns(&self, dst: &str) -> String {
-        format!("    cset {}, pl\n", self.map_operand(dst))
+    fn generate_set_ns(&self, out: &mut String, dst: &str) {
+        emit!(out, "    cset {}, pl\n", self.map_operand(dst));
     }
-    fn generate_set_p(&self, dst: &str) -> String {
-        "// ARM64 has no parity flag, cannot synthesize set_p\n".to_string()
+    fn generate_set_p(&self, out: &mut String, dst: &str) {
+        out.push_str("// ARM64 has no parity flag, cannot synthesize set_p\n");
     }
-    fn generate_set_np(&self, dst: &str) -> String {
-        "// ARM64 has no parity flag, cannot synthesize set_np\n".to_string()
+    fn generate_set_np(&self, out: &mut String, dst: &str) {
+        out.push_str("// ARM64 has no parity flag, cannot synthesize set_np\n");
     }
-    fn generate_set_a(&self, dst: &str) -> String {
-        format!("    cset {}, hi\n", self.map_operand(dst))
+    fn generate_set_a(&self, out: &mut String, dst: &str) {
+        emit!(out, "    cset {}, hi\n", self.map_operand(dst));
     }
-    fn generate_set_ae(&self, dst: &str) -> String {
-        format!("    cset {}, hs\n", self.map_operand(dst))
+    fn generate_set_ae(&self, out: &mut String, dst: &str) {
+        emit!(out, "    cset {}, hs\n", self.map_operand(dst));
     }
-    fn generate_set_b(&self, dst: &str) -> String {
-        format!("    cset {}, lo\n", self.map_operand(dst))
+    fn generate_set_b(&self, out: &mut String, dst: &str) {
+        emit!(out, "    cset {}, lo\n", self.map_operand(dst));
     }
-    fn generate_set_be(&self, dst: &str) -> String {
-        format!("    cset {}, ls\n", self.map_operand(dst))
+    fn generate_set_be(&self, out: &mut String, dst: &str) {
+        emit!(out, "    cset {}, ls\n", self.map_operand(dst));
     }
 
-    fn generate_cmps(&self, src1: &str, src2: &str) -> String {
-        format!(
+    fn generate_cmps(&self, out: &mut String, src1: &str, src2: &str) {
+        emit!(
+            out,
             "    ldr x16, {} \n    ldr x17, {}\n    cmp x16, x17\n",
             self.map_memory_operand(src1),
             self.map_memory_operand(src2)
-        )
+        );
     }
 
-    fn generate_scas(&self, src: &str, val: &str) -> String {
-        format!(
+    fn generate_scas(&self, out: &mut String, src: &str, val: &str) {
+        emit!(
+            out,
             "    ldr x16, {} \n    cmp x16, {}\n",
             self.map_memory_operand(src),
             self.map_operand(val)
-        )
+        );
     }
-    fn generate_stos(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_stos(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    str {}, {}\n",
             self.map_operand(src),
             self.map_memory_operand(dst)
-        )
+        );
     }
-    fn generate_lods(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_lods(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    ldr {}, {}\n",
             self.map_operand(dst),
             self.map_memory_operand(src)
-        )
+        );
     }
-    fn generate_movs(&self, dst: &str, src: &str) -> String {
-        format!(
+    fn generate_movs(&self, out: &mut String, dst: &str, src: &str) {
+        emit!(
+            out,
             "    ldr x16, {}\n    str x16, {}\n",
             self.map_memory_operand(src),
             self.map_memory_operand(dst)
-        )
+        );
     }
 
-    fn generate_cbw(&self, dst: &str) -> String {
+    fn generate_cbw(&self, out: &mut String, dst: &str) {
         // Sign-extend byte to word: sxtb
-        format!(
+        emit!(
+            out,
             "    sxtb {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
-    fn generate_cwd(&self, dst: &str) -> String {
+    fn generate_cwd(&self, out: &mut String, dst: &str) {
         // Sign-extend word to doubleword: sxth
-        format!(
+        emit!(
+            out,
             "    sxth {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
-    fn generate_cdq(&self, dst: &str) -> String {
+    fn generate_cdq(&self, out: &mut String, dst: &str) {
         // Sign-extend 32-bit to 64-bit: sxtw
-        format!(
+        emit!(
+            out,
             "    sxtw {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
-    fn generate_cqo(&self, dst: &str) -> String {
-        "// ARM64: CQO equivalent handled by sxtw\n".to_string()
+    fn generate_cqo(&self, out: &mut String, dst: &str) {
+        out.push_str("// ARM64: CQO equivalent handled by sxtw\n");
     }
-    fn generate_cwde(&self, dst: &str) -> String {
-        format!(
+    fn generate_cwde(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    sxth {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
-    fn generate_cdqe(&self, dst: &str) -> String {
-        format!(
+    fn generate_cdqe(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    sxtw {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_jo(&self, label: &str) -> String {
-        format!("    b.vs {}\n", label)
+    fn generate_jo(&self, out: &mut String, label: &str) {
+        emit!(out, "    b.vs {}\n", label);
     }
-    fn generate_jno(&self, label: &str) -> String {
-        format!("    b.vc {}\n", label)
+    fn generate_jno(&self, out: &mut String, label: &str) {
+        emit!(out, "    b.vc {}\n", label);
     }
-    fn generate_js(&self, label: &str) -> String {
-        format!("    b.mi {}\n", label)
+    fn generate_js(&self, out: &mut String, label: &str) {
+        emit!(out, "    b.mi {}\n", label);
     }
-    fn generate_jns(&self, label: &str) -> String {
-        format!("    b.pl {}\n", label)
+    fn generate_jns(&self, out: &mut String, label: &str) {
+        emit!(out, "    b.pl {}\n", label);
     }
-    fn generate_jp(&self, label: &str) -> String {
-        "// No parity bit on ARM64\n".to_string()
+    fn generate_jp(&self, out: &mut String, label: &str) {
+        out.push_str("// No parity bit on ARM64\n");
     }
-    fn generate_jnp(&self, label: &str) -> String {
-        "// No parity bit on ARM64\n".to_string()
+    fn generate_jnp(&self, out: &mut String, label: &str) {
+        out.push_str("// No parity bit on ARM64\n");
     }
-    fn generate_ja(&self, label: &str) -> String {
-        format!("    b.hi {}\n", label)
+    fn generate_ja(&self, out: &mut String, label: &str) {
+        emit!(out, "    b.hi {}\n", label);
     }
-    fn generate_jae(&self, label: &str) -> String {
-        format!("    b.hs {}\n", label)
+    fn generate_jae(&self, out: &mut String, label: &str) {
+        emit!(out, "    b.hs {}\n", label);
     }
-    fn generate_jb(&self, label: &str) -> String {
-        format!("    b.lo {}\n", label)
+    fn generate_jb(&self, out: &mut String, label: &str) {
+        emit!(out, "    b.lo {}\n", label);
     }
-    fn generate_jbe(&self, label: &str) -> String {
-        format!("    b.ls {}\n", label)
+    fn generate_jbe(&self, out: &mut String, label: &str) {
+        emit!(out, "    b.ls {}\n", label);
     }
-    fn generate_loop_eq(&self, label: &str) -> String {
-        "// No direct LOOPxx on ARM64 -- emulate with sub and cbz\n".to_string()
+    fn generate_loop_eq(&self, out: &mut String, label: &str) {
+        out.push_str("// No direct LOOPxx on ARM64 -- emulate with sub and cbz\n");
     }
-    fn generate_loop_ne(&self, label: &str) -> String {
-        "// No direct LOOPxx on ARM64 -- emulate with sub and cbnz\n".to_string()
+    fn generate_loop_ne(&self, out: &mut String, label: &str) {
+        out.push_str("// No direct LOOPxx on ARM64 -- emulate with sub and cbnz\n");
     }
 
-    fn generate_in(&self, _dst: &str, _port: &str) -> String {
-        "// ARM64 has no IN instruction, not supported.\n".to_string()
+    fn generate_in(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("// ARM64 has no IN instruction, not supported.\n");
     }
-    fn generate_out(&self, _port: &str, _src: &str) -> String {
-        "// ARM64 has no OUT instruction, not supported.\n".to_string()
+    fn generate_out(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("// ARM64 has no OUT instruction, not supported.\n");
     }
-    fn generate_ins(&self, _dst: &str, _port: &str) -> String {
-        "// ARM64 has no INS instruction, not supported.\n".to_string()
+    fn generate_ins(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("// ARM64 has no INS instruction, not supported.\n");
     }
-    fn generate_outs(&self, _port: &str, _src: &str) -> String {
-        "// ARM64 has no OUTS instruction, not supported.\n".to_string()
+    fn generate_outs(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("// ARM64 has no OUTS instruction, not supported.\n");
     }
 
-    fn generate_cpuid(&self) -> String {
-        "// ARM64 does not have CPUID\n".to_string()
+    fn generate_cpuid(&self, out: &mut String) {
+        out.push_str("// ARM64 does not have CPUID\n");
     }
-    fn generate_lfence(&self) -> String {
-        "    dmb ld\n".to_string()
+    fn generate_lfence(&self, out: &mut String) {
+        out.push_str("    dmb ld\n");
     }
-    fn generate_sfence(&self) -> String {
-        "    dmb st\n".to_string()
+    fn generate_sfence(&self, out: &mut String) {
+        out.push_str("    dmb st\n");
     }
-    fn generate_mfence(&self) -> String {
-        "    dmb sy\n".to_string()
+    fn generate_mfence(&self, out: &mut String) {
+        out.push_str("    dmb sy\n");
     }
-    fn generate_prefetch(&self, addr: &str) -> String {
-        format!("    prfm pldl1keep, {}\n", self.map_memory_operand(addr))
+    fn generate_prefetch(&self, out: &mut String, addr: &str) {
+        emit!(
+            out,
+            "    prfm pldl1keep, {}\n",
+            self.map_memory_operand(addr)
+        );
     }
-    fn generate_clflush(&self, addr: &str) -> String {
-        "// ARM64 does not support clflush\n".to_string()
+    fn generate_clflush(&self, out: &mut String, addr: &str) {
+        out.push_str("// ARM64 does not support clflush\n");
     }
-    fn generate_clwb(&self, addr: &str) -> String {
-        "// ARM64 does not support clwb\n".to_string()
+    fn generate_clwb(&self, out: &mut String, addr: &str) {
+        out.push_str("// ARM64 does not support clwb\n");
     }
 
-    fn generate_global(&self, symbol: &str) -> String {
-        format!(".global {}\n", symbol)
+    fn generate_global(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".global {}\n", symbol);
     }
-    fn generate_extern(&self, symbol: &str) -> String {
-        format!(".extern {}\n", symbol)
+    fn generate_extern(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".extern {}\n", symbol);
     }
-    fn generate_align(&self, n: &str) -> String {
-        format!(".align {}\n", n)
+    fn generate_align(&self, out: &mut String, n: &str) {
+        emit!(out, ".align {}\n", n);
     }
 
-    fn generate_data_byte(&self, name: &str, values: &[String]) -> String {
-        format!("{}: .byte {}\n", name, values.join(", "))
+    fn generate_data_byte(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .byte {}\n", name, values.join(", "));
     }
-    fn generate_data_word(&self, name: &str, values: &[String]) -> String {
-        format!("{}: .hword {}\n", name, values.join(", "))
+    fn generate_data_word(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .hword {}\n", name, values.join(", "));
     }
-    fn generate_data_dword(&self, name: &str, values: &[String]) -> String {
-        format!("{}: .word {}\n", name, values.join(", "))
+    fn generate_data_dword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .word {}\n", name, values.join(", "));
     }
-    fn generate_data_qword(&self, name: &str, values: &[String]) -> String {
-        format!("{}: .quad {}\n", name, values.join(", "))
+    fn generate_data_qword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .quad {}\n", name, values.join(", "));
     }
-    fn generate_reserve_byte(&self, name: &str, count: &str) -> String {
-        format!("{}: .skip {}\n", name, count)
+    fn generate_reserve_byte(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip {}\n", name, count);
     }
-    fn generate_reserve_word(&self, name: &str, count: &str) -> String {
-        format!(
+    fn generate_reserve_word(&self, out: &mut String, name: &str, count: &str) {
+        emit!(
+            out,
             "{}: .skip {}\n",
             name,
             2 * count.parse::<usize>().unwrap_or(1)
-        )
+        );
     }
-    fn generate_reserve_dword(&self, name: &str, count: &str) -> String {
-        format!(
+    fn generate_reserve_dword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(
+            out,
             "{}: .skip {}\n",
             name,
             4 * count.parse::<usize>().unwrap_or(1)
-        )
+        );
     }
-    fn generate_reserve_qword(&self, name: &str, count: &str) -> String {
-        format!(
+    fn generate_reserve_qword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(
+            out,
             "{}: .skip {}\n",
             name,
             8 * count.parse::<usize>().unwrap_or(1)
-        )
+        );
     }
-    fn generate_equ(&self, name: &str, value: &str) -> String {
-        format!("{} = {}\n", name, value)
+    fn generate_equ(&self, out: &mut String, name: &str, value: &str) {
+        emit!(out, "{} = {}\n", name, value);
     }
-    fn generate_section(&self, section: &Section) -> String {
+    fn generate_section(&self, out: &mut String, section: &Section) {
         match section {
-            Section::Text => ".section .text\n".to_string(),
-            Section::Data => ".section .data\n".to_string(),
-            Section::Bss => ".section .bss\n".to_string(),
-            Section::Rodata => ".section .rodata\n".to_string(),
-            Section::Custom(s) => format!(".section {}\n", s),
+            Section::Text => out.push_str(".section .text\n"),
+            Section::Data => out.push_str(".section .data\n"),
+            Section::Bss => out.push_str(".section .bss\n"),
+            Section::Rodata => out.push_str(".section .rodata\n"),
+            Section::Custom(s) => emit!(out, ".section {}\n", s),
         }
     }
-    fn generate_label(&self, name: &str) -> String {
-        format!("{}:\n", name)
+    fn generate_label(&self, out: &mut String, name: &str) {
+        emit!(out, "{}:\n", name);
     }
 
     fn map_operand(&self, operand: &str) -> String {
@@ -1058,4 +1295,56 @@ impl ArchCodeGen for ARM64CodeGen {
             operand.to_string()
         }
     }
+
+    fn get_register_width(&self) -> u32 {
+        64
+    }
+
+    fn get_reserved_registers(&self) -> Vec<String> {
+        ["sp", "x16", "x17", "x18", "x29", "x30"]
+            .iter()
+            .map(|r| r.to_string())
+            .collect()
+    }
+
+    fn get_callee_saved_registers(&self) -> Vec<String> {
+        [
+            "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27", "x28", "x29",
+        ]
+        .iter()
+        .map(|r| r.to_string())
+        .collect()
+    }
+
+    fn get_push_size(&self) -> u32 {
+        16
+    }
+
+    fn get_clobbered_registers(&self, instruction: &Instruction) -> Vec<String> {
+        use Instruction::*;
+        let registers: &[&str] = match instruction {
+            Syscall(_) => &["x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8"],
+            Call(_) => &[
+                "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12",
+                "x13", "x14", "x15", "x16", "x17", "x18",
+            ],
+            _ => &[],
+        };
+        registers.iter().map(|r| r.to_string()).collect()
+    }
+
+    fn get_frame_layout(&self) -> Option<FrameLayout> {
+        // `enter` stores x29 and x30 as a pair and points x29 at it.
+        Some(FrameLayout {
+            return_address: 0,
+            frame_pointer: "x29",
+            record: &["x29", "x30"],
+            slot: 8,
+        })
+    }
+
+    fn set_position_independent(&mut self, pic: Pic) -> Result<(), String> {
+        self.pic = Some(pic);
+        Ok(())
+    }
 }
@@ -0,0 +1,1644 @@
+use super::*;
+use std::collections::{HashMap, HashSet};
+
+pub struct AVRCodeGen {
+    register_map: HashMap<String, String>,
+    /// Names the program defines in `.rodata`, which stays in flash where
+    /// loads read it with `lpm`.
+    program_memory: HashSet<String>,
+}
+
+/// One byte of an operand: a register, or a constant the assembler works
+/// out.
+#[derive(Debug, Clone, PartialEq)]
+enum Byte {
+    Register(u32),
+    Constant(String),
+}
+
+/// Where an access finds its operand.
+enum Place {
+    /// `displacement` bytes past the pointer register `Y` or `Z`.
+    Data(&'static str, i64),
+    /// Flash at `Z`, which `lpm` reads a byte at a time.
+    Program,
+}
+
+/// How `shift` moves the bits of a register.
+#[derive(Clone, Copy, PartialEq)]
+enum Shift {
+    Left,
+    Right,
+    Arithmetic,
+    RotateLeft,
+    RotateRight,
+}
+
+/// The scratch registers `ldi` can load: `X` and `Z`.
+const SCRATCH: [u32; 4] = [26, 27, 30, 31];
+
+/// Puts the address of the top of the stack in `Z`: `SP` points at the free
+/// byte below it.
+const READ_SP: &str = "    in r30, __SP_L__\n    in r31, __SP_H__\n    adiw r30, 1\n";
+
+/// Makes `Z` the address of the top of the stack, with interrupts held off
+/// between the two halves of `SP` as avr-gcc does.
+const WRITE_SP: &str = "    sbiw r30, 1\n    in r0, __SREG__\n    cli\n    out __SP_H__, r31\n    out __SREG__, r0\n    out __SP_L__, r30\n";
+
+impl AVRCodeGen {
+    pub fn new() -> Self {
+        let mut register_map = HashMap::with_capacity(8);
+
+        // Quads of byte registers, named highest first as avr-gcc writes
+        // them, in the order avr-gcc passes 32-bit arguments
+        register_map.insert("r0".to_string(), "r25:r22".to_string()); // 1st arg/return value
+        register_map.insert("r1".to_string(), "r21:r18".to_string()); // 2nd arg
+        register_map.insert("r2".to_string(), "r17:r14".to_string()); // 3rd arg
+        register_map.insert("r3".to_string(), "r13:r10".to_string()); // 4th arg
+        register_map.insert("r4".to_string(), "r9:r6".to_string());
+        register_map.insert("r5".to_string(), "r5:r2".to_string());
+
+        // Special purpose registers
+        register_map.insert("sp".to_string(), "SP".to_string()); // Stack pointer, an I/O register
+        register_map.insert("sb".to_string(), "r29:r28".to_string()); // Frame pointer, Y
+
+        AVRCodeGen {
+            register_map,
+            program_memory: HashSet::new(),
+        }
+    }
+
+    /// The byte registers of the register `name`, lowest first.
+    fn registers(name: &str) -> Option<Vec<u32>> {
+        let (high, low) = name.split_once(':')?;
+        let number = |name: &str| {
+            name.strip_prefix('r')?
+                .parse::<u32>()
+                .ok()
+                .filter(|&n| n < 32)
+        };
+        let (high, low) = (number(high)?, number(low)?);
+        (low <= high).then(|| (low..=high).collect())
+    }
+
+    /// Whether `operand`, mapped, can be the base of an address.
+    fn is_base(operand: &str) -> bool {
+        operand == "SP" || Self::registers(operand).is_some()
+    }
+
+    /// The value of the constant `operand`, including the unsigned masks
+    /// `opt::flags` writes for `bt` of the top bit.
+    fn immediate(operand: &str) -> Option<i64> {
+        opt::parse_immediate(operand)
+            .or_else(|| operand.trim().parse::<u64>().ok().map(|value| value as i64))
+    }
+
+    /// The four bytes of `value`, lowest first.
+    fn bytes_of(value: i64) -> Vec<Byte> {
+        (0..4)
+            .map(|i| Byte::Constant(((value >> (8 * i)) & 0xff).to_string()))
+            .collect()
+    }
+
+    /// The four bytes of the assembler expression `expression`.
+    fn constant(expression: &str) -> Vec<Byte> {
+        ["lo8", "hi8", "hlo8", "hhi8"]
+            .iter()
+            .map(|part| Byte::Constant(format!("{}({})", part, expression)))
+            .collect()
+    }
+
+    fn zero() -> Byte {
+        Byte::Constant("0".to_string())
+    }
+
+    /// The bytes of `operand`, negated when `negate`, if it is a constant.
+    fn constant_bytes(&self, operand: &str, negate: bool) -> Option<Vec<Byte>> {
+        let mapped = self.map_operand(operand);
+        if Self::is_base(&mapped) {
+            return None;
+        }
+        Some(match Self::immediate(operand) {
+            Some(value) if negate => Self::bytes_of(value.wrapping_neg()),
+            Some(value) => Self::bytes_of(value),
+            None if negate => Self::constant(&format!("-({})", mapped)),
+            None => Self::constant(&mapped),
+        })
+    }
+
+    /// The four bytes of `operand`, lowest first, once it is read: a
+    /// register's bytes padded with zeros, `Z` for `SP`, or the bytes of a
+    /// constant.
+    fn source(&self, out: &mut String, operand: &str) -> Vec<Byte> {
+        let mapped = self.map_operand(operand);
+        let registers = match Self::registers(&mapped) {
+            Some(registers) => registers,
+            None if mapped == "SP" => {
+                out.push_str(READ_SP);
+                vec![30, 31]
+            }
+            None => return self.constant_bytes(operand, false).unwrap(),
+        };
+        let mut bytes: Vec<Byte> = registers.into_iter().map(Byte::Register).collect();
+        bytes.resize(4, Self::zero());
+        bytes
+    }
+
+    /// `body` applied to the registers of `dst`. `SP` is worked on in `Z`,
+    /// read first if `reads`, and written back after.
+    fn update(
+        &self,
+        out: &mut String,
+        dst: &str,
+        reads: bool,
+        body: impl FnOnce(&mut String, &[u32]),
+    ) {
+        let mapped = self.map_operand(dst);
+        if mapped == "SP" {
+            if reads {
+                out.push_str(READ_SP);
+            }
+            body(out, &[30, 31]);
+            out.push_str(WRITE_SP);
+            return;
+        }
+        match Self::registers(&mapped) {
+            Some(registers) => body(out, &registers),
+            None => emit!(out, "    .error \"{} is not an AVR register\"\n", dst),
+        }
+    }
+
+    /// Puts `constant` in the register `register`, through r26 below r16,
+    /// which `ldi` cannot load. `loaded` is what r26 holds.
+    fn load_byte(out: &mut String, register: u32, constant: &str, loaded: &mut Option<String>) {
+        if register >= 16 {
+            if register == 26 {
+                *loaded = None;
+            }
+            emit!(out, "    ldi r{}, {}\n", register, constant);
+            return;
+        }
+        if loaded.as_deref() != Some(constant) {
+            emit!(out, "    ldi r26, {}\n", constant);
+            *loaded = Some(constant.to_string());
+        }
+        emit!(out, "    mov r{}, r26\n", register);
+    }
+
+    /// Copies `src` into the registers `dst`, a pair at a time where `movw`
+    /// can.
+    fn copy(out: &mut String, dst: &[u32], src: &[Byte]) {
+        let mut loaded = None;
+        let mut i = 0;
+        while i < dst.len() {
+            if i + 1 < dst.len()
+                && dst[i].is_multiple_of(2)
+                && dst[i + 1] == dst[i] + 1
+                && let (Byte::Register(low), Byte::Register(high)) = (&src[i], &src[i + 1])
+                && low.is_multiple_of(2)
+                && *high == low + 1
+            {
+                if *low != dst[i] {
+                    emit!(out, "    movw r{}, r{}\n", dst[i], low);
+                }
+                i += 2;
+                continue;
+            }
+            match &src[i] {
+                Byte::Register(register) if *register != dst[i] => {
+                    emit!(out, "    mov r{}, r{}\n", dst[i], register);
+                }
+                Byte::Register(_) => {}
+                Byte::Constant(constant) => {
+                    Self::load_byte(out, dst[i], constant, &mut loaded);
+                }
+            }
+            i += 1;
+        }
+    }
+
+    /// `op d, s` for each byte of `dst` and `src`, `ops[0]` for the lowest
+    /// byte and `ops[1]` for the carries above it. Constants use the
+    /// immediate form from r16 up and r26 below.
+    fn chain(
+        out: &mut String,
+        ops: [&str; 2],
+        immediates: [Option<&str>; 2],
+        dst: &[u32],
+        src: &[Byte],
+    ) {
+        let mut loaded = None;
+        for (i, (register, byte)) in dst.iter().zip(src).enumerate() {
+            let k = usize::from(i > 0);
+            match byte {
+                Byte::Register(source) => {
+                    emit!(out, "    {} r{}, r{}\n", ops[k], register, source);
+                }
+                Byte::Constant(constant) => match immediates[k] {
+                    Some(immediate) if *register >= 16 => {
+                        emit!(out, "    {} r{}, {}\n", immediate, register, constant);
+                    }
+                    _ => {
+                        if loaded != Some(constant) || *register == 26 {
+                            emit!(out, "    ldi r26, {}\n", constant);
+                            loaded = Some(constant);
+                        }
+                        emit!(out, "    {} r{}, r26\n", ops[k], register);
+                    }
+                },
+            }
+            if *register == 26 {
+                loaded = None;
+            }
+        }
+    }
+
+    /// `dst = dst + src`, or `dst - src` when `subtract`. AVR adds a
+    /// constant by subtracting its negation.
+    fn add(&self, out: &mut String, subtract: bool, dst: &str, src: &str) {
+        if let Some(bytes) = self.constant_bytes(src, !subtract) {
+            self.update(out, dst, true, |out, d| {
+                Self::chain(out, ["sub", "sbc"], [Some("subi"), Some("sbci")], d, &bytes)
+            });
+            return;
+        }
+        let bytes = self.source(out, src);
+        let ops = if subtract {
+            ["sub", "sbc"]
+        } else {
+            ["add", "adc"]
+        };
+        self.update(out, dst, true, |out, d| {
+            Self::chain(out, ops, [None, None], d, &bytes)
+        });
+    }
+
+    /// `op dst, src` byte by byte, leaving out the bytes `identity` keeps.
+    fn logical(
+        &self,
+        out: &mut String,
+        op: &str,
+        immediate: Option<&str>,
+        identity: &str,
+        dst: &str,
+        src: &str,
+    ) {
+        let bytes = self.source(out, src);
+        self.update(out, dst, true, |out, d| {
+            let (d, bytes): (Vec<u32>, Vec<Byte>) = d
+                .iter()
+                .zip(bytes)
+                .filter(|(_, byte)| *byte != Byte::Constant(identity.to_string()))
+                .map(|(register, byte)| (*register, byte))
+                .unzip();
+            Self::chain(out, [op, op], [immediate, immediate], &d, &bytes)
+        });
+    }
+
+    /// `dst = dst op src` through the libgcc routine `routine`, which takes
+    /// its operands in r25:r22 and r21:r18 and leaves the result in the quad
+    /// that starts at `result`. Other values in those registers wait on the
+    /// stack.
+    fn library(&self, out: &mut String, routine: &str, result: u32, dst: &str, src: &str) {
+        let src = self.source(out, src);
+        let mapped = self.map_operand(dst);
+        let Some(dst) = Self::registers(&mapped) else {
+            emit!(out, "    .error \"{} is not an AVR register\"\n", dst);
+            return;
+        };
+        let saved: Vec<u32> = (18..=25).filter(|r| !dst.contains(r)).collect();
+        for register in &saved {
+            emit!(out, "    push r{}\n", register);
+        }
+        let mut operand: Vec<Byte> = dst.iter().copied().map(Byte::Register).collect();
+        operand.resize(4, Self::zero());
+        for byte in src.iter().chain(&operand) {
+            Self::push_byte(out, byte);
+        }
+        for register in (18..=25).rev() {
+            emit!(out, "    pop r{}\n", register);
+        }
+        emit!(out, "    call {}\n", routine);
+        let result: Vec<Byte> = (result..result + 4).map(Byte::Register).collect();
+        Self::copy(out, &dst, &result);
+        for register in saved.iter().rev() {
+            emit!(out, "    pop r{}\n", register);
+        }
+    }
+
+    fn push_byte(out: &mut String, byte: &Byte) {
+        match byte {
+            Byte::Register(register) => emit!(out, "    push r{}\n", register),
+            Byte::Constant(constant) => emit!(out, "    ldi r26, {}\n    push r26\n", constant),
+        }
+    }
+
+    /// One bit of `shift` across the registers `d`.
+    fn step(shift: Shift, d: &[u32]) -> String {
+        let last = d.len() - 1;
+        let mut code = String::new();
+        match shift {
+            Shift::Left | Shift::RotateLeft => {
+                if shift == Shift::RotateLeft {
+                    code.push_str(&format!("    bst r{}, 7\n", d[last]));
+                }
+                code.push_str(&format!("    lsl r{}\n", d[0]));
+                for register in &d[1..] {
+                    code.push_str(&format!("    rol r{}\n", register));
+                }
+                if shift == Shift::RotateLeft {
+                    code.push_str(&format!("    bld r{}, 0\n", d[0]));
+                }
+            }
+            Shift::Right | Shift::Arithmetic | Shift::RotateRight => {
+                if shift == Shift::RotateRight {
+                    code.push_str(&format!("    bst r{}, 0\n", d[0]));
+                }
+                let op = if shift == Shift::Arithmetic {
+                    "asr"
+                } else {
+                    "lsr"
+                };
+                code.push_str(&format!("    {} r{}\n", op, d[last]));
+                for register in d[..last].iter().rev() {
+                    code.push_str(&format!("    ror r{}\n", register));
+                }
+                if shift == Shift::RotateRight {
+                    code.push_str(&format!("    bld r{}, 7\n", d[last]));
+                }
+            }
+        }
+        code
+    }
+
+    /// `shift` of the registers `d` by the constant `count`, whole bytes
+    /// first.
+    fn shift_by(out: &mut String, shift: Shift, d: &[u32], count: i64) {
+        let n = d.len();
+        let count = (count & (8 * n as i64 - 1)) as usize;
+        let (bytes, bits) = (count / 8, count % 8);
+        let mut loaded = None;
+        if bytes > 0 {
+            match shift {
+                Shift::Left => {
+                    for i in (bytes..n).rev() {
+                        emit!(out, "    mov r{}, r{}\n", d[i], d[i - bytes]);
+                    }
+                    for register in &d[..bytes] {
+                        Self::load_byte(out, *register, "0", &mut loaded);
+                    }
+                }
+                Shift::Right | Shift::Arithmetic => {
+                    if shift == Shift::Arithmetic {
+                        emit!(
+                            out,
+                            "    mov r26, r{}\n    lsl r26\n    sbc r26, r26\n",
+                            d[n - 1]
+                        );
+                    }
+                    for i in 0..n - bytes {
+                        emit!(out, "    mov r{}, r{}\n", d[i], d[i + bytes]);
+                    }
+                    for register in &d[n - bytes..] {
+                        if shift == Shift::Arithmetic {
+                            emit!(out, "    mov r{}, r26\n", register);
+                        } else {
+                            Self::load_byte(out, *register, "0", &mut loaded);
+                        }
+                    }
+                }
+                Shift::RotateLeft | Shift::RotateRight => {
+                    for (register, scratch) in d.iter().zip(SCRATCH) {
+                        emit!(out, "    mov r{}, r{}\n", scratch, register);
+                    }
+                    for (i, register) in d.iter().enumerate() {
+                        let from = match shift {
+                            Shift::RotateLeft => (i + n - bytes) % n,
+                            _ => (i + bytes) % n,
+                        };
+                        emit!(out, "    mov r{}, r{}\n", register, SCRATCH[from]);
+                    }
+                }
+            }
+        }
+        for _ in 0..bits {
+            out.push_str(&Self::step(shift, d));
+        }
+    }
+
+    /// `shift` of `dst` by `count`, which counts modulo the register width
+    /// as x86 does.
+    fn shift(&self, out: &mut String, shift: Shift, dst: &str, count: &str) {
+        if let Some(count) = Self::immediate(count) {
+            self.update(out, dst, true, |out, d| {
+                Self::shift_by(out, shift, d, count)
+            });
+            return;
+        }
+        let bytes = self.source(out, count);
+        self.update(out, dst, true, |out, d| {
+            match &bytes[0] {
+                Byte::Register(register) => emit!(out, "    mov r26, r{}\n", register),
+                Byte::Constant(constant) => emit!(out, "    ldi r26, {}\n", constant),
+            }
+            emit!(
+                out,
+                "    andi r26, {}\n    breq 2f\n1:\n{}    dec r26\n    brne 1b\n2:\n",
+                8 * d.len() - 1,
+                Self::step(shift, d)
+            )
+        });
+    }
+
+    /// Puts the single-bit mask `bit` selects in `X` and `Z`.
+    fn mask(&self, out: &mut String, bit: &str) {
+        let bytes = self.source(out, bit);
+        match &bytes[0] {
+            Byte::Register(register) => emit!(out, "    mov r26, r{}\n", register),
+            Byte::Constant(constant) => emit!(out, "    ldi r26, {}\n", constant),
+        }
+        emit!(
+            out,
+            "    andi r26, 31\n    mov r0, r26\n    ldi r26, 1\n    ldi r27, 0\n    ldi r30, 0\n    ldi r31, 0\n    rjmp 2f\n1:\n{}2:\n    dec r0\n    brpl 1b\n",
+            Self::step(Shift::Left, &SCRATCH)
+        );
+    }
+
+    /// `op dst, mask` for the single-bit mask `bit` selects, inverted when
+    /// `clear`.
+    fn bit(
+        &self,
+        out: &mut String,
+        op: &str,
+        immediate: Option<&str>,
+        clear: bool,
+        dst: &str,
+        bit: &str,
+    ) {
+        if let Some(bit) = Self::immediate(bit) {
+            self.update(out, dst, true, |out, d| {
+                let bit = (bit & 31) as usize;
+                let Some(register) = d.get(bit / 8) else {
+                    return;
+                };
+                let mask = 1u8 << (bit % 8);
+                let mask = if clear { !mask } else { mask };
+                match immediate {
+                    Some(immediate) if *register >= 16 => {
+                        emit!(out, "    {} r{}, {}\n", immediate, register, mask)
+                    }
+                    _ => emit!(
+                        out,
+                        "    ldi r26, {}\n    {} r{}, r26\n",
+                        mask,
+                        op,
+                        register
+                    ),
+                }
+            });
+            return;
+        }
+        self.mask(out, bit);
+        if clear {
+            for scratch in SCRATCH {
+                emit!(out, "    com r{}\n", scratch);
+            }
+        }
+        self.update(out, dst, true, |out, d| {
+            for (register, scratch) in d.iter().zip(SCRATCH) {
+                emit!(out, "    {} r{}, r{}\n", op, register, scratch);
+            }
+        });
+    }
+
+    /// Sign-extends `dst` from its lowest `bytes` bytes.
+    fn extend(&self, out: &mut String, dst: &str, bytes: usize) {
+        self.update(out, dst, true, |out, d| {
+            if bytes >= d.len() {
+                return;
+            }
+            emit!(
+                out,
+                "    mov r26, r{}\n    lsl r26\n    sbc r26, r26\n",
+                d[bytes - 1]
+            );
+            for register in &d[bytes..] {
+                emit!(out, "    mov r{}, r26\n", register);
+            }
+        });
+    }
+
+    /// Sets `dst` to the signed byte in r0.
+    fn extend_r0(&self, out: &mut String, dst: &str) {
+        self.update(out, dst, false, |out, d| {
+            emit!(out, "    mov r{}, r0\n", d[0]);
+            if d.len() > 1 {
+                out.push_str("    mov r26, r0\n    lsl r26\n    sbc r26, r26\n");
+                for register in &d[1..] {
+                    emit!(out, "    mov r{}, r26\n", register);
+                }
+            }
+        });
+    }
+
+    /// `operand` as base, index and displacement, where `SP` or any register
+    /// group can be the base.
+    fn split_address(&self, operand: &str) -> Result<Address, String> {
+        super::split_address(operand, |name| self.map_operand(name), Self::is_base)
+    }
+
+    /// Adds `value` to `Z`.
+    fn add_to_z(out: &mut String, value: i64) {
+        match value {
+            0 => {}
+            1..=63 => emit!(out, "    adiw r30, {}\n", value),
+            -63..=-1 => emit!(out, "    sbiw r30, {}\n", -value),
+            _ => emit!(
+                out,
+                "    subi r30, {}\n    sbci r31, {}\n",
+                value.wrapping_neg() & 0xff,
+                (value.wrapping_neg() >> 8) & 0xff
+            ),
+        }
+    }
+
+    /// Finds `operand` in memory: in flash for data in `.rodata` when
+    /// `program`, otherwise in data memory at `Y` or `Z` plus a
+    /// displacement `ldd` and `std` take, or at `Z` alone unless `direct`.
+    fn locate(&self, out: &mut String, operand: &str, program: bool, direct: bool) -> Place {
+        let address = address_or_error(out, self.split_address(operand));
+        let in_flash = program
+            && opt::identifiers(&address.symbolic).any(|name| self.program_memory.contains(name));
+        let mut displacement = address.displacement;
+        if direct
+            && !in_flash
+            && address.symbolic.is_empty()
+            && address.index.is_none()
+            && address.base.as_deref() == Some("r29:r28")
+            && (0..=60).contains(&displacement)
+        {
+            return Place::Data("Y", displacement);
+        }
+
+        let base = address.base.as_deref();
+        if !address.symbolic.is_empty() {
+            let expression = match displacement {
+                0 => address.symbolic.clone(),
+                _ => format!("{} + ({})", address.symbolic, displacement),
+            };
+            displacement = 0;
+            emit!(
+                out,
+                "    ldi r30, lo8({e})\n    ldi r31, hi8({e})\n",
+                e = expression
+            );
+            if base == Some("SP") {
+                out.push_str("    in r26, __SP_L__\n    in r27, __SP_H__\n    adiw r26, 1\n    add r30, r26\n    adc r31, r27\n");
+            } else if let Some(registers) = base.and_then(Self::registers) {
+                emit!(
+                    out,
+                    "    add r30, r{}\n    adc r31, r{}\n",
+                    registers[0],
+                    registers[1]
+                );
+            }
+        } else if base == Some("SP") {
+            out.push_str("    in r30, __SP_L__\n    in r31, __SP_H__\n");
+            displacement += 1;
+        } else if let Some(registers) = base.and_then(Self::registers) {
+            emit!(out, "    movw r30, r{}\n", registers[0]);
+        } else {
+            emit!(
+                out,
+                "    ldi r30, {}\n    ldi r31, {}\n",
+                displacement & 0xff,
+                (displacement >> 8) & 0xff
+            );
+            displacement = 0;
+        }
+        if let Some((index, shift)) = &address.index
+            && let Some(registers) = Self::registers(index)
+        {
+            if *shift == 0 {
+                emit!(
+                    out,
+                    "    add r30, r{}\n    adc r31, r{}\n",
+                    registers[0],
+                    registers[1]
+                );
+            } else {
+                emit!(out, "    movw r26, r{}\n", registers[0]);
+                for _ in 0..*shift {
+                    out.push_str("    lsl r26\n    rol r27\n");
+                }
+                out.push_str("    add r30, r26\n    adc r31, r27\n");
+            }
+        }
+        if in_flash {
+            Self::add_to_z(out, displacement);
+            return Place::Program;
+        }
+        if !direct || !(0..=60).contains(&displacement) {
+            Self::add_to_z(out, displacement);
+            displacement = 0;
+        }
+        Place::Data("Z", displacement)
+    }
+
+    /// Loads the registers `d` from `place`.
+    fn read(out: &mut String, place: &Place, d: &[u32]) {
+        for (i, register) in d.iter().enumerate() {
+            match place {
+                Place::Data(pointer, displacement) => emit!(
+                    out,
+                    "    ldd r{}, {}+{}\n",
+                    register,
+                    pointer,
+                    displacement + i as i64
+                ),
+                Place::Program => emit!(out, "    lpm r{}, Z+\n", register),
+            }
+        }
+    }
+
+    /// Stores `bytes` at `place`, which is in data memory.
+    fn write(out: &mut String, place: &Place, bytes: &[Byte]) {
+        let Place::Data(pointer, displacement) = place else {
+            unreachable!("stores locate data memory")
+        };
+        let mut loaded: Option<&str> = None;
+        for (i, byte) in bytes.iter().enumerate() {
+            let register = match byte {
+                Byte::Register(register) => *register,
+                Byte::Constant(constant) => {
+                    if loaded != Some(constant) {
+                        emit!(out, "    ldi r26, {}\n", constant);
+                        loaded = Some(constant);
+                    }
+                    26
+                }
+            };
+            emit!(
+                out,
+                "    std {}+{}, r{}\n",
+                pointer,
+                displacement + i as i64,
+                register
+            );
+        }
+    }
+
+    /// Reads the I/O register `port` into the byte register `register`.
+    /// Ports past the 64 `in` reaches, and ports in a register, are read in
+    /// data memory, where I/O registers sit 0x20 above their port.
+    fn port_in(&self, out: &mut String, register: u32, port: &str) {
+        let mapped = self.map_operand(port);
+        if let Some(registers) = Self::registers(&mapped) {
+            emit!(
+                out,
+                "    movw r30, r{}\n    adiw r30, 0x20\n    ld r{}, Z\n",
+                registers[0],
+                register
+            );
+            return;
+        }
+        match Self::immediate(port) {
+            Some(port) if port > 63 => emit!(out, "    lds r{}, {}\n", register, port + 0x20),
+            _ => emit!(out, "    in r{}, {}\n", register, mapped),
+        }
+    }
+
+    /// Writes `byte` to the I/O register `port`.
+    fn port_out(&self, out: &mut String, port: &str, byte: &Byte) {
+        let register = match byte {
+            Byte::Register(register) => *register,
+            Byte::Constant(constant) => {
+                emit!(out, "    ldi r26, {}\n", constant);
+                26
+            }
+        };
+        let mapped = self.map_operand(port);
+        if let Some(registers) = Self::registers(&mapped) {
+            emit!(
+                out,
+                "    movw r30, r{}\n    adiw r30, 0x20\n    st Z, r{}\n",
+                registers[0],
+                register
+            );
+            return;
+        }
+        match Self::immediate(port) {
+            Some(port) if port > 63 => emit!(out, "    sts {}, r{}\n", port + 0x20, register),
+            _ => emit!(out, "    out {}, r{}\n", mapped, register),
+        }
+    }
+
+    /// Sets the flags `comparison` tests: `cp` and `cpc` through the bytes, so
+    /// that Z, C and S hold for all 32 bits.
+    fn flags(&self, out: &mut String, comparison: &Comparison) {
+        let a = self.source(out, &comparison.a);
+        let b = self.source(&mut String::new(), &comparison.b);
+        let (a, b) = if comparison.tested == Tested::Mask {
+            // a & b, in X and Z.
+            let mut loaded = None;
+            for ((x, y), scratch) in a.iter().zip(&b).zip(SCRATCH) {
+                match x {
+                    Byte::Register(register) => {
+                        emit!(out, "    mov r{}, r{}\n", scratch, register);
+                    }
+                    Byte::Constant(constant) => {
+                        Self::load_byte(out, scratch, constant, &mut loaded);
+                    }
+                }
+                match y {
+                    Byte::Register(register) => {
+                        emit!(out, "    and r{}, r{}\n", scratch, register);
+                    }
+                    Byte::Constant(constant) => {
+                        emit!(out, "    andi r{}, {}\n", scratch, constant);
+                    }
+                }
+            }
+            (SCRATCH.to_vec(), vec![Self::zero(); 4])
+        } else if a.iter().all(|byte| matches!(byte, Byte::Register(_))) {
+            let registers = a
+                .iter()
+                .map(|byte| match byte {
+                    Byte::Register(register) => *register,
+                    Byte::Constant(_) => unreachable!(),
+                })
+                .collect();
+            (registers, b)
+        } else {
+            let registers = vec![30, 31, 27, 0];
+            Self::copy(out, &registers, &a);
+            (registers, b)
+        };
+
+        if b.iter().all(|byte| *byte == Self::zero()) {
+            match comparison.condition {
+                Condition::Eq | Condition::Ne => {
+                    let last = a.len() - 1;
+                    if a[last] != 0 {
+                        emit!(out, "    mov r0, r{}\n", a[last]);
+                    }
+                    for register in &a[..last] {
+                        emit!(out, "    or r0, r{}\n", register);
+                    }
+                    return;
+                }
+                Condition::Lt | Condition::Ge | Condition::S | Condition::Ns => {
+                    emit!(out, "    tst r{}\n", a[a.len() - 1]);
+                    return;
+                }
+                _ => {}
+            }
+        }
+        Self::chain(out, ["cp", "cpc"], [Some("cpi"), None], &a, &b);
+    }
+
+    /// `body`, skipped unless the flags `flags` set hold `condition`. Jumps
+    /// reach anywhere: a branch only reaches 64 words.
+    fn unless(out: &mut String, condition: Condition, body: impl FnOnce(&mut String)) {
+        let branches = match condition {
+            Condition::Eq => "    brne 1f\n",
+            Condition::Ne => "    breq 1f\n",
+            Condition::Lt => "    brge 1f\n",
+            Condition::Ge => "    brlt 1f\n",
+            Condition::B => "    brsh 1f\n",
+            Condition::Ae => "    brlo 1f\n",
+            Condition::S => "    brpl 1f\n",
+            Condition::Ns => "    brmi 1f\n",
+            Condition::Gt => "    breq 1f\n    brlt 1f\n",
+            Condition::A => "    breq 1f\n    brlo 1f\n",
+            Condition::Le => "    breq 2f\n    brge 1f\n2:\n",
+            Condition::Be => "    breq 2f\n    brsh 1f\n2:\n",
+        };
+        out.push_str(branches);
+        body(out);
+        out.push_str("1:\n");
+    }
+
+    /// The registers `pusha` saves and `popa` restores: every one the
+    /// register map names but `SP`, in a fixed order.
+    fn saved_by_pusha(&self) -> Vec<&String> {
+        let mut registers: Vec<&String> = self
+            .register_map
+            .values()
+            .filter(|r| Self::registers(r).is_some())
+            .collect();
+        registers.sort();
+        registers.dedup();
+        registers
+    }
+}
+
+impl ArchCodeGen for AVRCodeGen {
+    fn get_register_map(&self) -> HashMap<String, String> {
+        self.register_map.clone()
+    }
+
+    fn get_syntax_header(&self) -> String {
+        // avr-gcc defines these in every file it emits.
+        "__SP_H__ = 0x3e\n__SP_L__ = 0x3d\n__SREG__ = 0x3f\n.text\n\n".to_string()
+    }
+
+    fn generate_mov(&self, out: &mut String, dst: &str, src: &str) {
+        let bytes = self.source(out, src);
+        self.update(out, dst, false, |out, d| Self::copy(out, d, &bytes));
+    }
+
+    fn generate_lea(&self, out: &mut String, dst: &str, src: &str) {
+        let address = address_or_error(out, self.split_address(src));
+        if address.base.is_none() && address.index.is_none() {
+            let expression = match (address.symbolic.is_empty(), address.displacement) {
+                (true, displacement) => displacement.to_string(),
+                (false, 0) => address.symbolic,
+                (false, displacement) => format!("{} + ({})", address.symbolic, displacement),
+            };
+            self.generate_mov(out, dst, &expression);
+            return;
+        }
+        self.locate(out, src, false, false);
+        let mut address = vec![Byte::Register(30), Byte::Register(31)];
+        address.resize(4, Self::zero());
+        self.update(out, dst, false, |out, d| Self::copy(out, d, &address));
+    }
+
+    fn generate_load(&self, out: &mut String, dst: &str, src: &str) {
+        // Y cannot address the load that replaces it.
+        let direct = self.map_operand(dst) != "r29:r28";
+        let place = self.locate(out, src, true, direct);
+        self.update(out, dst, false, |out, d| Self::read(out, &place, d));
+    }
+
+    fn generate_store(&self, out: &mut String, dst: &str, src: &str) {
+        let place = self.locate(out, dst, false, true);
+        let bytes = self.source(&mut String::new(), src);
+        Self::write(out, &place, &bytes);
+    }
+
+    fn generate_add(&self, out: &mut String, dst: &str, src: &str) {
+        self.add(out, false, dst, src);
+    }
+
+    fn generate_sub(&self, out: &mut String, dst: &str, src: &str) {
+        self.add(out, true, dst, src);
+    }
+
+    fn generate_mul(&self, out: &mut String, dst: &str, src: &str) {
+        self.library(out, "__mulsi3", 22, dst, src);
+    }
+
+    fn generate_div(&self, out: &mut String, dst: &str, src: &str) {
+        self.library(out, "__divmodsi4", 18, dst, src);
+    }
+
+    fn generate_inc(&self, out: &mut String, dst: &str) {
+        self.generate_add(out, dst, "1");
+    }
+
+    fn generate_dec(&self, out: &mut String, dst: &str) {
+        self.generate_sub(out, dst, "1");
+    }
+
+    fn generate_neg(&self, out: &mut String, dst: &str) {
+        // The complement, less -1.
+        self.update(out, dst, true, |out, d| {
+            for register in d {
+                emit!(out, "    com r{}\n", register);
+            }
+            Self::chain(
+                out,
+                ["sub", "sbc"],
+                [Some("subi"), Some("sbci")],
+                d,
+                &Self::bytes_of(-1),
+            )
+        });
+    }
+
+    fn generate_and(&self, out: &mut String, dst: &str, src: &str) {
+        self.logical(out, "and", Some("andi"), "255", dst, src);
+    }
+
+    fn generate_or(&self, out: &mut String, dst: &str, src: &str) {
+        self.logical(out, "or", Some("ori"), "0", dst, src);
+    }
+
+    fn generate_xor(&self, out: &mut String, dst: &str, src: &str) {
+        self.logical(out, "eor", None, "0", dst, src);
+    }
+
+    fn generate_not(&self, out: &mut String, dst: &str) {
+        self.update(out, dst, true, |out, d| {
+            for register in d {
+                emit!(out, "    com r{}\n", register);
+            }
+        });
+    }
+
+    fn generate_shl(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, Shift::Left, dst, src);
+    }
+
+    fn generate_shr(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, Shift::Right, dst, src);
+    }
+
+    fn generate_cmp(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_test(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_jmp(&self, out: &mut String, label: &str) {
+        // `ijmp` takes a word address, the byte address halved.
+        match Self::registers(&self.map_operand(label)) {
+            Some(registers) => emit!(
+                out,
+                "    movw r30, r{}\n    lsr r31\n    ror r30\n    ijmp\n",
+                registers[0]
+            ),
+            None => emit!(out, "    jmp {}\n", label),
+        }
+    }
+
+    fn generate_je(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_jne(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_jg(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_jl(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_jge(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_jle(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_call(&self, out: &mut String, func: &str) {
+        // `icall` takes a word address, the byte address halved.
+        match Self::registers(&self.map_operand(func)) {
+            Some(registers) => emit!(
+                out,
+                "    movw r30, r{}\n    lsr r31\n    ror r30\n    icall\n",
+                registers[0]
+            ),
+            None => emit!(out, "    call {}\n", func),
+        }
+    }
+
+    fn generate_ret(&self, out: &mut String) {
+        out.push_str("    ret\n");
+    }
+
+    fn generate_syscall(&self, out: &mut String, name: &str) {
+        match name {
+            // avr-libc's `exit` stops the same way.
+            "exit" => out.push_str("    cli\n1:\n    rjmp 1b\n"),
+            _ => emit!(
+                out,
+                "    ; No operating system to handle syscall: {}\n",
+                name
+            ),
+        }
+    }
+
+    fn map_operand(&self, operand: &str) -> String {
+        if operand.chars().all(|c| c.is_ascii_digit() || c == '-') {
+            return operand.to_string();
+        }
+
+        if operand.starts_with('[') && operand.ends_with(']') {
+            return self.map_memory_operand(operand);
+        }
+
+        if let Some(mapped) = self.register_map.get(operand) {
+            mapped.clone()
+        } else {
+            operand.to_string()
+        }
+    }
+
+    fn map_memory_operand(&self, operand: &str) -> String {
+        if !(operand.starts_with('[') && operand.ends_with(']')) {
+            return operand.to_string();
+        }
+        let mut code = String::new();
+        match self.locate(&mut code, operand, false, true) {
+            Place::Data(pointer, displacement) if code.is_empty() => {
+                format!("{}+{}", pointer, displacement)
+            }
+            _ => operand.to_string(),
+        }
+    }
+
+    fn generate_cmov_eq(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_cmov_ne(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_cmov_lt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_cmov_le(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_cmov_gt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_cmov_ge(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_cmov_ov(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_cmov_no(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_cmov_s(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_cmov_ns(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_cmov_p(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_cmov_np(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_cmov_a(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_cmov_ae(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_cmov_b(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_cmov_be(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_push(&self, out: &mut String, src: &str) {
+        // Highest byte first, so that the value is little-endian in memory
+        // like the rest of AVR data.
+        let bytes = self.source(out, src);
+        for byte in bytes.iter().rev() {
+            Self::push_byte(out, byte);
+        }
+    }
+
+    fn generate_pop(&self, out: &mut String, dst: &str) {
+        self.update(out, dst, false, |out, d| {
+            for register in d {
+                emit!(out, "    pop r{}\n", register);
+            }
+            for _ in d.len()..4 {
+                out.push_str("    pop r0\n");
+            }
+        });
+    }
+
+    fn generate_pusha(&self, out: &mut String) {
+        for register in self.saved_by_pusha() {
+            self.generate_push(out, register);
+        }
+    }
+
+    fn generate_popa(&self, out: &mut String) {
+        for register in self.saved_by_pusha().into_iter().rev() {
+            self.generate_pop(out, register);
+        }
+    }
+
+    fn generate_enter(&self, out: &mut String, frame_size: &str, _nesting_level: &str) {
+        self.generate_push(out, "sb");
+        self.generate_mov(out, "sb", "sp");
+        if opt::parse_immediate(frame_size) != Some(0) {
+            self.generate_sub(out, "sp", frame_size);
+        }
+    }
+
+    fn generate_leave(&self, out: &mut String) {
+        self.generate_mov(out, "sp", "sb");
+        self.generate_pop(out, "sb");
+    }
+
+    fn generate_imul(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_mul(out, dst, src);
+    }
+
+    fn generate_idiv(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_div(out, dst, src);
+    }
+
+    fn generate_mod(&self, out: &mut String, dst: &str, src: &str) {
+        self.library(out, "__divmodsi4", 22, dst, src);
+    }
+
+    fn generate_mulh(&self, out: &mut String, dst: &str, src: &str) {
+        // The 64-bit product is in r25:r18.
+        self.library(out, "__mulsidi3", 22, dst, src);
+    }
+
+    fn generate_andn(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(value) = Self::immediate(src) {
+            self.generate_and(out, dst, &(!value).to_string());
+            return;
+        }
+        let bytes = self.source(out, src);
+        self.update(out, dst, true, |out, d| {
+            let mut loaded = None;
+            for (register, byte) in d.iter().zip(&bytes) {
+                match byte {
+                    Byte::Register(source) => emit!(out, "    mov r26, r{}\n", source),
+                    Byte::Constant(constant) => Self::load_byte(out, 26, constant, &mut loaded),
+                }
+                emit!(out, "    com r26\n    and r{}, r26\n", register);
+            }
+        });
+    }
+
+    fn generate_sal(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_shl(out, dst, src);
+    }
+
+    fn generate_sar(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, Shift::Arithmetic, dst, src);
+    }
+
+    fn generate_rol(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, Shift::RotateLeft, dst, src);
+    }
+
+    fn generate_ror(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, Shift::RotateRight, dst, src);
+    }
+
+    fn generate_rcl(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_rcr(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_bextr(&self, out: &mut String, dst: &str, src: &str, imm: &str) {
+        // The field is worked on in X and Z.
+        if let Some(control) = Self::immediate(imm) {
+            let bytes = self.source(out, src);
+            let (start, length) = (control & 0xff, (control >> 8) & 0xff);
+            let mut field = vec![Self::zero(); 4];
+            if start < 32 && length > 0 {
+                Self::copy(out, &SCRATCH, &bytes);
+                Self::shift_by(out, Shift::Right, &SCRATCH, start);
+                for (i, scratch) in SCRATCH.iter().enumerate() {
+                    match length - 8 * i as i64 {
+                        ..=0 => emit!(out, "    ldi r{}, 0\n", scratch),
+                        bits @ 1..=7 => {
+                            emit!(out, "    andi r{}, {}\n", scratch, (1 << bits) - 1)
+                        }
+                        _ => {}
+                    }
+                }
+                field = SCRATCH.iter().copied().map(Byte::Register).collect();
+            }
+            self.update(out, dst, false, |out, d| Self::copy(out, d, &field));
+            return;
+        }
+        // Shift the field down, then, unless the length keeps all 32 bits,
+        // up to the top and back by 32 - length.
+        let control = self.source(&mut String::new(), imm);
+        Self::copy(out, &[0, 1], &control[..2]);
+        let bytes = self.source(out, src);
+        Self::copy(out, &SCRATCH, &bytes);
+        let right = Self::step(Shift::Right, &SCRATCH);
+        emit!(
+            out,
+            "    tst r0\n    breq 2f\n1:\n{}    dec r0\n    brne 1b\n2:\n    clr r0\n    set\n    bld r0, 5\n    cp r1, r0\n    brsh 5f\n    sub r0, r1\n    mov r1, r0\n3:\n{}    dec r0\n    brne 3b\n4:\n{}    dec r1\n    brne 4b\n5:\n    clr r1\n",
+            right,
+            Self::step(Shift::Left, &SCRATCH),
+            right
+        );
+        let field: Vec<Byte> = SCRATCH.iter().copied().map(Byte::Register).collect();
+        self.update(out, dst, false, |out, d| Self::copy(out, d, &field));
+    }
+
+    fn generate_bsf(&self, out: &mut String, dst: &str, src: &str) {
+        // Counts the bits shifted out below the lowest set one, -1 for 0.
+        let bytes = self.source(out, src);
+        Self::copy(out, &SCRATCH, &bytes);
+        out.push_str("    clr r0\n    dec r0\n    mov r1, r26\n    or r1, r27\n    or r1, r30\n    or r1, r31\n    breq 2f\n1:\n    inc r0\n");
+        out.push_str(&Self::step(Shift::Right, &SCRATCH));
+        out.push_str("    brcc 1b\n2:\n    clr r1\n");
+        self.extend_r0(out, dst);
+    }
+
+    fn generate_bsr(&self, out: &mut String, dst: &str, src: &str) {
+        // Counts down from 31 to the highest set bit, -1 for 0.
+        let bytes = self.source(out, src);
+        Self::copy(out, &SCRATCH, &bytes);
+        out.push_str("    clr r0\n    set\n    bld r0, 5\n1:\n    dec r0\n    brmi 2f\n");
+        out.push_str(&Self::step(Shift::Left, &SCRATCH));
+        out.push_str("    brcc 1b\n2:\n");
+        self.extend_r0(out, dst);
+    }
+
+    fn generate_bt(&self, _out: &mut String, _dst: &str, _bit: &str) {}
+
+    fn generate_btr(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "and", Some("andi"), true, dst, bit);
+    }
+
+    fn generate_bts(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "or", Some("ori"), false, dst, bit);
+    }
+
+    fn generate_btc(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "eor", None, false, dst, bit);
+    }
+
+    fn generate_set_eq(&self, _out: &mut String, _dst: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_set_ne(&self, _out: &mut String, _dst: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_set_lt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_set_le(&self, _out: &mut String, _dst: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_set_gt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_set_ge(&self, _out: &mut String, _dst: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_set_ov(&self, _out: &mut String, _dst: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_set_no(&self, _out: &mut String, _dst: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_set_s(&self, _out: &mut String, _dst: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_set_ns(&self, _out: &mut String, _dst: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_set_p(&self, _out: &mut String, _dst: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_set_np(&self, _out: &mut String, _dst: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_set_a(&self, _out: &mut String, _dst: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_set_ae(&self, _out: &mut String, _dst: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_set_b(&self, _out: &mut String, _dst: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_set_be(&self, _out: &mut String, _dst: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_cmps(&self, _out: &mut String, _src1: &str, _src2: &str) {}
+
+    fn generate_scas(&self, _out: &mut String, _src: &str, _val: &str) {}
+
+    fn generate_stos(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_store(out, dst, src);
+    }
+
+    fn generate_lods(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_load(out, dst, src);
+    }
+
+    fn generate_movs(&self, out: &mut String, dst: &str, src: &str) {
+        let place = self.locate(out, src, true, true);
+        let value: Vec<Byte> = SCRATCH[..2]
+            .iter()
+            .chain(&[0, 1])
+            .copied()
+            .map(Byte::Register)
+            .collect();
+        let target = self.locate(out, dst, false, true);
+        Self::read(out, &place, &[26, 27, 0, 1]);
+        Self::write(out, &target, &value);
+        out.push_str("    clr r1\n");
+    }
+
+    fn generate_cbw(&self, out: &mut String, dst: &str) {
+        self.extend(out, dst, 1);
+    }
+
+    fn generate_cwd(&self, out: &mut String, dst: &str) {
+        self.extend(out, dst, 2);
+    }
+
+    fn generate_cdq(&self, _out: &mut String, _dst: &str) {}
+
+    fn generate_cqo(&self, out: &mut String, dst: &str) {
+        // The high half of the 64-bit sign extension: all sign bits.
+        self.update(out, dst, true, |out, d| {
+            emit!(
+                out,
+                "    mov r26, r{}\n    lsl r26\n    sbc r26, r26\n",
+                d[d.len() - 1]
+            );
+            for register in d {
+                emit!(out, "    mov r{}, r26\n", register);
+            }
+        });
+    }
+
+    fn generate_cwde(&self, out: &mut String, dst: &str) {
+        self.extend(out, dst, 2);
+    }
+
+    fn generate_cdqe(&self, _out: &mut String, _dst: &str) {}
+
+    fn generate_jo(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_jno(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_js(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_jns(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_jp(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_jnp(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_ja(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_jae(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_jb(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_jbe(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_loop_eq(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_loop_ne(&self, _out: &mut String, _label: &str) {
+        unreachable!("AVR compares in each reader")
+    }
+
+    fn generate_in(&self, out: &mut String, dst: &str, port: &str) {
+        // I/O registers are a byte wide.
+        self.update(out, dst, false, |out, d| {
+            self.port_in(out, d[0], port);
+            Self::copy(out, &d[1..], &vec![Self::zero(); d.len() - 1]);
+        });
+    }
+
+    fn generate_out(&self, out: &mut String, port: &str, src: &str) {
+        let bytes = self.source(out, src);
+        self.port_out(out, port, &bytes[0]);
+    }
+
+    fn generate_ins(&self, out: &mut String, dst: &str, port: &str) {
+        let place = self.locate(out, dst, false, true);
+        self.port_in(out, 0, port);
+        Self::write(out, &place, &[Byte::Register(0)]);
+    }
+
+    fn generate_outs(&self, out: &mut String, port: &str, src: &str) {
+        let place = self.locate(out, src, true, true);
+        Self::read(out, &place, &[0]);
+        self.port_out(out, port, &Byte::Register(0));
+    }
+
+    fn generate_cpuid(&self, out: &mut String) {
+        out.push_str("    ; AVR does not have CPUID\n");
+    }
+
+    fn generate_lfence(&self, _out: &mut String) {}
+
+    fn generate_sfence(&self, _out: &mut String) {}
+
+    fn generate_mfence(&self, _out: &mut String) {}
+
+    fn generate_prefetch(&self, out: &mut String, _addr: &str) {
+        out.push_str("    ; AVR has no data cache to prefetch into\n");
+    }
+
+    fn generate_clflush(&self, out: &mut String, _addr: &str) {
+        out.push_str("    ; AVR has no data cache to flush\n");
+    }
+
+    fn generate_clwb(&self, out: &mut String, _addr: &str) {
+        out.push_str("    ; AVR has no data cache to write back\n");
+    }
+
+    fn generate_global(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".globl {}\n", symbol);
+    }
+
+    fn generate_extern(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".extern {}\n", symbol);
+    }
+
+    fn generate_align(&self, out: &mut String, n: &str) {
+        emit!(out, ".p2align {}\n", n);
+    }
+
+    fn generate_data_byte(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .byte {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_word(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .2byte {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_dword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .4byte {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_qword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .8byte {}\n", name, values.join(", "));
+    }
+
+    fn generate_reserve_byte(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip {}\n", name, count);
+    }
+
+    fn generate_reserve_word(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 2 * ({})\n", name, count);
+    }
+
+    fn generate_reserve_dword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 4 * ({})\n", name, count);
+    }
+
+    fn generate_reserve_qword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 8 * ({})\n", name, count);
+    }
+
+    fn generate_equ(&self, out: &mut String, name: &str, value: &str) {
+        emit!(out, ".set {}, {}\n", name, value);
+    }
+
+    fn generate_section(&self, out: &mut String, section: &Section) {
+        match section {
+            Section::Text => out.push_str(".section .text\n"),
+            Section::Data => out.push_str(".section .data\n"),
+            Section::Bss => out.push_str(".section .bss\n"),
+            Section::Rodata => out.push_str(".section .progmem.data\n"),
+            Section::Custom(s) => emit!(out, ".section {}\n", s),
+        }
+    }
+
+    fn generate_label(&self, out: &mut String, name: &str) {
+        emit!(out, "{}:\n", name);
+    }
+
+    fn get_register_width(&self) -> u32 {
+        32
+    }
+
+    fn get_reserved_registers(&self) -> Vec<String> {
+        ["r1:r0", "r27:r26", "r31:r30"]
+            .iter()
+            .map(|r| r.to_string())
+            .collect()
+    }
+
+    fn get_callee_saved_registers(&self) -> Vec<String> {
+        ["r5:r2", "r9:r6", "r13:r10", "r17:r14", "r29:r28"]
+            .iter()
+            .map(|r| r.to_string())
+            .collect()
+    }
+
+    fn get_push_size(&self) -> u32 {
+        4
+    }
+
+    fn get_clobbered_registers(&self, instruction: &Instruction) -> Vec<String> {
+        use Instruction::*;
+        let registers: &[&str] = match instruction {
+            Call(_) => &["r25:r22", "r21:r18"],
+            _ => &[],
+        };
+        registers.iter().map(|r| r.to_string()).collect()
+    }
+
+    fn has_program_memory(&self) -> bool {
+        true
+    }
+
+    fn set_program_memory(&mut self, symbols: HashSet<String>) {
+        self.program_memory = symbols;
+    }
+
+    fn has_condition_flags(&self) -> bool {
+        false
+    }
+
+    fn generate_compare_branch(&self, out: &mut String, comparison: &Comparison, label: &str) {
+        self.flags(out, comparison);
+        Self::unless(out, comparison.condition, |out| {
+            emit!(out, "    jmp {}\n", label)
+        });
+    }
+
+    fn generate_compare_set(&self, out: &mut String, comparison: &Comparison, dst: &str) {
+        // Neither `ldi` nor `mov` changes the flags.
+        self.flags(out, comparison);
+        self.update(out, dst, false, |out, d| {
+            let mut loaded = None;
+            Self::copy(out, d, &vec![Self::zero(); d.len()]);
+            Self::unless(out, comparison.condition, |out| {
+                Self::load_byte(out, d[0], "1", &mut loaded)
+            });
+        });
+    }
+
+    fn generate_compare_cmov(
+        &self,
+        out: &mut String,
+        comparison: &Comparison,
+        dst: &str,
+        src: &str,
+    ) {
+        self.flags(out, comparison);
+        Self::unless(out, comparison.condition, |out| {
+            self.generate_mov(out, dst, src)
+        });
+    }
+}
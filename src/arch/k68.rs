@@ -0,0 +1,1459 @@
+use super::*;
+use std::collections::HashMap;
+
+pub struct K68CodeGen {
+    register_map: HashMap<String, String>,
+    /// The processor model, from 68000 to 68060.
+    cpu: u32,
+}
+
+/// The callee-saved data registers, in the order `r2`-`r9` take them.
+const DATA: [&str; 4] = ["%d2", "%d3", "%d4", "%d5"];
+
+/// The callee-saved address registers, in the order `r2`-`r9` take them.
+const ADDRESS: [&str; 4] = ["%a2", "%a3", "%a4", "%a5"];
+
+/// The registers `pusha` saves and `popa` restores: every one the register
+/// map names but `%sp`.
+const SAVED_BY_PUSHA: &str = "%d0-%d5/%a2-%a6";
+
+impl K68CodeGen {
+    pub fn new() -> Self {
+        let mut register_map = HashMap::with_capacity(16);
+
+        // Return value registers (SVR4 m68k ABI)
+        register_map.insert("r0".to_string(), "%d0".to_string()); // return value
+        register_map.insert("r1".to_string(), "%d1".to_string()); // second return word
+
+        // Callee-saved data registers
+        register_map.insert("r2".to_string(), "%d2".to_string());
+        register_map.insert("r3".to_string(), "%d3".to_string());
+        register_map.insert("r4".to_string(), "%d4".to_string());
+        register_map.insert("r5".to_string(), "%d5".to_string());
+
+        // Callee-saved address registers; `set_address_registers` moves the
+        // names a program addresses memory through here.
+        register_map.insert("r6".to_string(), "%a2".to_string());
+        register_map.insert("r7".to_string(), "%a3".to_string());
+        register_map.insert("r8".to_string(), "%a4".to_string());
+        register_map.insert("r9".to_string(), "%a5".to_string());
+
+        // Special purpose registers
+        register_map.insert("sp".to_string(), "%sp".to_string()); // Stack pointer (a7)
+        register_map.insert("sb".to_string(), "%fp".to_string()); // Frame pointer (a6)
+
+        K68CodeGen {
+            register_map,
+            cpu: 68020,
+        }
+    }
+
+    /// Whether `operand` names a data register.
+    fn is_data(operand: &str) -> bool {
+        operand
+            .strip_prefix("%d")
+            .and_then(|n| n.parse::<u32>().ok())
+            .is_some_and(|n| n <= 7)
+    }
+
+    /// Whether `operand` names an address register.
+    fn is_address(operand: &str) -> bool {
+        if operand == "%sp" || operand == "%fp" {
+            return true;
+        }
+        operand
+            .strip_prefix("%a")
+            .and_then(|n| n.parse::<u32>().ok())
+            .is_some_and(|n| n <= 7)
+    }
+
+    /// Whether `operand` names an integer register.
+    fn is_register(operand: &str) -> bool {
+        Self::is_data(operand) || Self::is_address(operand)
+    }
+
+    /// The value of the constant `operand` as the 32 bits a register holds,
+    /// including the unsigned masks `opt::flags` writes for `bt` of the top
+    /// bit.
+    fn immediate(operand: &str) -> Option<i64> {
+        opt::parse_immediate(operand)
+            .or_else(|| operand.trim().parse::<u64>().ok().map(|value| value as i64))
+            .map(|value| value as i32 as i64)
+    }
+
+    /// Whether `value` fits a 16-bit signed displacement.
+    fn is_disp16(value: i64) -> bool {
+        i16::try_from(value).is_ok()
+    }
+
+    /// Whether the processor multiplies and divides 32-bit operands, as the
+    /// 68020 and later do.
+    fn has_long_multiply(&self) -> bool {
+        self.cpu >= 68020
+    }
+
+    /// Whether the processor has the 64-bit product of `muls.l`, which the
+    /// 68060 dropped again.
+    fn has_wide_multiply(&self) -> bool {
+        (68020..=68040).contains(&self.cpu)
+    }
+
+    /// An `.error` for `operand` if it names a register beyond the map,
+    /// which the assembler would otherwise take for a symbol.
+    fn unmapped(&self, operand: &str) -> Option<String> {
+        let operand = operand.trim();
+        let number = operand.strip_prefix('r')?;
+        if number.is_empty()
+            || !number.chars().all(|c| c.is_ascii_digit())
+            || self.register_map.contains_key(operand)
+        {
+            return None;
+        }
+        Some(format!(
+            "    .error \"{} is not an m68k register\"\n",
+            operand
+        ))
+    }
+
+    /// `operand` as the source of an instruction, once its address is in
+    /// `%a1` if it is in memory: registers as they are, constants and
+    /// symbols as immediates.
+    fn source(&self, out: &mut String, operand: &str) -> String {
+        if opt::is_memory(operand) {
+            return self.address(out, operand, "%a1");
+        }
+        if let Some(error) = self.unmapped(operand) {
+            out.push_str(&error);
+            return operand.to_string();
+        }
+        let mapped = self.map_operand(operand);
+        if Self::is_register(&mapped) {
+            return mapped;
+        }
+        match Self::immediate(operand) {
+            Some(value) => format!("#{}", value),
+            None => format!("#{}", mapped),
+        }
+    }
+
+    /// Like `source`, but in `scratch` unless it is a data register or an
+    /// immediate, the only sources some instructions take.
+    fn data_source(&self, out: &mut String, operand: &str, scratch: &str) -> String {
+        let src = self.source(out, operand);
+        if Self::is_address(&src) || opt::is_memory(operand) {
+            emit!(out, "    move.l {},{}\n", src, scratch);
+            return scratch.to_string();
+        }
+        src
+    }
+
+    /// `body` run on the data register `dst` names, or on `%d6` holding its
+    /// value if it is an address register, which arithmetic beyond addition
+    /// and subtraction cannot work on.
+    fn in_data(&self, out: &mut String, dst: &str, body: impl FnOnce(&mut String, &str)) {
+        if let Some(error) = self.unmapped(dst) {
+            out.push_str(&error);
+            return;
+        }
+        let dst_reg = self.map_operand(dst);
+        if !Self::is_address(&dst_reg) {
+            body(out, &dst_reg);
+            return;
+        }
+        emit!(out, "    move.l {},%d6\n", dst_reg);
+        body(out, "%d6");
+        emit!(out, "    movea.l %d6,{}\n", dst_reg);
+    }
+
+    /// `operand` as base, index and displacement over the data and address
+    /// registers; a displacement past 32 bits wraps, as `lea` would.
+    fn split_address(&self, operand: &str) -> Result<Address, String> {
+        let mut address =
+            super::split_address(operand, |name| self.map_operand(name), Self::is_register)?;
+        address.displacement = address.displacement as i32 as i64;
+        Ok(address)
+    }
+
+    /// `symbolic + displacement` as one expression, without spaces, which
+    /// the assembler takes for the end of the operand.
+    fn expression(symbolic: &str, displacement: i64) -> String {
+        match (symbolic.is_empty(), displacement) {
+            (true, displacement) => displacement.to_string(),
+            (false, 0) => symbolic.to_string(),
+            (false, n) if n < 0 => format!("{}-{}", symbolic, n.unsigned_abs()),
+            (false, n) => format!("{}+{}", symbolic, n),
+        }
+    }
+
+    /// `(register)`, or `d(register)` with a displacement.
+    fn indirect(displacement: i64, register: &str) -> String {
+        match displacement {
+            0 => format!("({})", register),
+            n => format!("{}({})", n, register),
+        }
+    }
+
+    /// The effective address of the memory operand `operand`, computed into
+    /// the address register `scratch` first where no addressing mode takes
+    /// it as written: a data register as the base, a
+    /// symbol beside a register, a displacement beyond 16 bits (8 with an
+    /// index), or a scaled index on the 68000.
+    fn address(&self, out: &mut String, operand: &str, scratch: &str) -> String {
+        let mut address = address_or_error(out, self.split_address(operand));
+        // Only an address register can be the base.
+        if let (Some(base), Some((index, 0))) = (&address.base, &address.index)
+            && !Self::is_address(base)
+            && Self::is_address(index)
+        {
+            let (base, index) = (base.clone(), index.clone());
+            address.index = Some((base, 0));
+            address.base = Some(index);
+        }
+        let displacement = address.displacement;
+        let plain = address.symbolic.is_empty();
+        match (&address.base, &address.index) {
+            (None, None) => {
+                return Self::expression(&address.symbolic, displacement);
+            }
+            (Some(base), None)
+                if Self::is_address(base) && plain && Self::is_disp16(displacement) =>
+            {
+                return Self::indirect(displacement, base);
+            }
+            (Some(base), Some((index, shift)))
+                if Self::is_address(base)
+                    && plain
+                    && i8::try_from(displacement).is_ok()
+                    && (*shift == 0 || (self.cpu >= 68020 && *shift <= 3)) =>
+            {
+                let scale = match shift {
+                    0 => String::new(),
+                    shift => format!("*{}", 1 << shift),
+                };
+                let displacement = match displacement {
+                    0 => String::new(),
+                    n => n.to_string(),
+                };
+                return format!("{}({},{}.l{})", displacement, base, index, scale);
+            }
+            _ => {}
+        }
+
+        match (&address.base, &address.index) {
+            (base, Some((index, shift))) => {
+                emit!(out, "    movea.l {},{}\n", index, scratch);
+                for _ in 0..*shift {
+                    emit!(out, "    adda.l {s},{s}\n", s = scratch);
+                }
+                if let Some(base) = base {
+                    emit!(out, "    adda.l {},{}\n", base, scratch);
+                }
+            }
+            (Some(base), None) => {
+                emit!(out, "    movea.l {},{}\n", base, scratch);
+            }
+            (None, None) => unreachable!("absolute addresses need no register"),
+        }
+        if plain && Self::is_disp16(displacement) {
+            return Self::indirect(displacement, scratch);
+        }
+        emit!(
+            out,
+            "    adda.l #{},{}\n",
+            Self::expression(&address.symbolic, displacement),
+            scratch
+        );
+        format!("({})", scratch)
+    }
+
+    /// `op src,dst` for the arithmetic that takes only data registers and
+    /// immediates as its source and a data register as its destination.
+    fn binary(&self, out: &mut String, op: &str, dst: &str, src: &str) {
+        let src_op = self.data_source(out, src, "%d7");
+        self.in_data(out, dst, |out, d| {
+            emit!(out, "    {} {},{}\n", op, src_op, d)
+        });
+    }
+
+    /// A shift or rotate of `dst` by `count`, which x86 takes modulo 32;
+    /// the 68k shifts by up to 8 as an immediate and takes register counts
+    /// modulo 64.
+    fn shift(&self, out: &mut String, op: &str, dst: &str, count: &str) {
+        let count = match Self::immediate(count) {
+            Some(count) => match count & 31 {
+                0 => return,
+                n @ 1..=8 => format!("#{}", n),
+                n => {
+                    emit!(out, "    moveq #{},%d7\n", n);
+                    "%d7".to_string()
+                }
+            },
+            None => {
+                let count = self.source(out, count);
+                emit!(out, "    move.l {},%d7\n    and.l #31,%d7\n", count);
+                "%d7".to_string()
+            }
+        };
+        self.in_data(out, dst, |out, d| {
+            emit!(out, "    {}.l {},{}\n", op, count, d)
+        });
+    }
+
+    /// `op bit,dst` for `bset`, `bclr` and `bchg`, which take the bit
+    /// number of a data register modulo 32 as x86 does.
+    fn bit(&self, out: &mut String, op: &str, dst: &str, bit: &str) {
+        let bit = match Self::immediate(bit) {
+            Some(bit) => format!("#{}", bit & 31),
+            None => self.data_source(out, bit, "%d7"),
+        };
+        self.in_data(out, dst, |out, d| emit!(out, "    {} {},{}\n", op, bit, d));
+    }
+
+    /// `dst = dst op src` through the libgcc routine `routine`, which takes
+    /// its arguments on the stack and returns in %d0. The program's %d0 and
+    /// %d1 are saved around the call.
+    fn library(&self, out: &mut String, routine: &str, dst: &str, src: &str) {
+        let src_op = self.source(out, src);
+        emit!(
+            out,
+            concat!(
+                "    move.l {},%d7\n    movem.l %d0-%d1,-(%sp)\n    move.l %d7,-(%sp)\n",
+                "    move.l {},-(%sp)\n    jsr {}\n    addq.l #8,%sp\n    move.l %d0,%d7\n",
+                "    movem.l (%sp)+,%d0-%d1\n"
+            ),
+            src_op,
+            self.map_operand(dst),
+            routine
+        );
+        self.generate_mov(out, dst, "%d7");
+    }
+
+    /// The 64-bit product of `dst` and `src` from the 68020's `muls.l`
+    /// (`high`) or its remainder after division (`divsl.l`): the dividend
+    /// or low factor in `dst` and the second result in another register,
+    /// which then replaces it.
+    fn wide(&self, out: &mut String, op: &str, dst: &str, src: &str) {
+        let both_addresses =
+            Self::is_address(&self.map_operand(src)) && Self::is_address(&self.map_operand(dst));
+        // With %d6 holding `dst` and %d7 the second result, an address
+        // register source waits on the stack.
+        let src_op = if both_addresses {
+            emit!(out, "    move.l {},-(%sp)\n", self.map_operand(src));
+            "(%sp)+".to_string()
+        } else {
+            self.data_source(out, src, "%d7")
+        };
+        let second = if src_op == "%d7" { "%d6" } else { "%d7" };
+        self.in_data(out, dst, |out, d| {
+            emit!(
+                out,
+                "    {} {},{}:{d}\n    move.l {},{d}\n",
+                op,
+                src_op,
+                second,
+                second,
+                d = d
+            )
+        });
+    }
+
+    /// Sets the condition codes from `comparison`: `a - b`, or `a & b`
+    /// with the overflow and carry clear as `test` leaves them. For the
+    /// overflow of a sum `a` of `b`, the addition is done again from
+    /// `a - b`, and likewise for a difference.
+    fn compare(&self, out: &mut String, comparison: &Comparison) {
+        let a = self.source(out, &comparison.a);
+        if let Tested::Sum | Tested::Difference = comparison.tested {
+            let b = self.source(out, &comparison.b);
+            let (undo, redo) = if comparison.tested == Tested::Sum {
+                ("sub.l", "add.l")
+            } else {
+                ("add.l", "sub.l")
+            };
+            emit!(
+                out,
+                "    move.l {},%d7\n    {} {b},%d7\n    {} {b},%d7\n",
+                a,
+                undo,
+                redo,
+                b = b
+            );
+            return;
+        }
+        if comparison.tested == Tested::Mask {
+            // A move sets the codes from the value it moves, as `test` of a
+            // value with itself does.
+            if comparison.a == comparison.b {
+                if Self::is_data(&a) {
+                    emit!(out, "    tst.l {}\n", a);
+                    return;
+                }
+                emit!(out, "    move.l {},%d7\n", a);
+                return;
+            }
+            emit!(out, "    move.l {},%d7\n", a);
+            let b = self.data_source(out, &comparison.b, "%d6");
+            emit!(out, "    and.l {},%d7\n", b);
+            return;
+        }
+        let zero = Self::immediate(&comparison.b) == Some(0);
+        if Self::is_data(&a) && zero {
+            emit!(out, "    tst.l {}\n", a);
+            return;
+        }
+        let a = if Self::is_register(&a) {
+            a
+        } else {
+            emit!(out, "    move.l {},%d7\n", a);
+            if zero {
+                return;
+            }
+            "%d7".to_string()
+        };
+        let b = self.source(out, &comparison.b);
+        let op = if Self::is_address(&a) {
+            "cmpa.l"
+        } else {
+            "cmp.l"
+        };
+        emit!(out, "    {} {},{}\n", op, b, a);
+    }
+
+    /// The suffix of the branches and `scc` on `condition`, which tests the
+    /// overflow for a sum or difference.
+    fn condition_code(condition: Condition, tested: Tested) -> &'static str {
+        if let Tested::Sum | Tested::Difference = tested {
+            return match condition {
+                Condition::S => "vs",
+                Condition::Ns => "vc",
+                _ => unreachable!("the overflow is only set or clear, not {:?}", condition),
+            };
+        }
+        match condition {
+            Condition::Eq => "eq",
+            Condition::Ne => "ne",
+            Condition::Lt => "lt",
+            Condition::Le => "le",
+            Condition::Gt => "gt",
+            Condition::Ge => "ge",
+            Condition::B => "cs",
+            Condition::Be => "ls",
+            Condition::A => "hi",
+            Condition::Ae => "cc",
+            Condition::S => "mi",
+            Condition::Ns => "pl",
+        }
+    }
+
+    /// Sign-extends the low byte of `dst`, or its low word with `word`.
+    fn extend(&self, out: &mut String, dst: &str, word: bool) {
+        self.in_data(out, dst, |out, d| {
+            if word {
+                emit!(out, "    ext.l {}\n", d)
+            } else if self.cpu >= 68020 {
+                emit!(out, "    extb.l {}\n", d)
+            } else {
+                emit!(out, "    ext.w {d}\n    ext.l {d}\n", d = d)
+            }
+        });
+    }
+
+    /// The address in the register `target` names, in an address register
+    /// to jump through.
+    fn jump_target(&self, out: &mut String, target: &str) -> String {
+        let target = self.source(out, target);
+        if Self::is_address(&target) {
+            return format!("({})", target);
+        }
+        emit!(out, "    movea.l {},%a0\n", target);
+        "(%a0)".to_string()
+    }
+}
+
+impl ArchCodeGen for K68CodeGen {
+    fn get_register_map(&self) -> HashMap<String, String> {
+        self.register_map.clone()
+    }
+
+    fn get_syntax_header(&self) -> String {
+        // The assembler rejects the instructions the processor lacks.
+        format!(".cpu {}\n.text\n.align 2\n\n", self.cpu)
+    }
+
+    fn generate_mov(&self, out: &mut String, dst: &str, src: &str) {
+        if opt::is_memory(dst) {
+            self.generate_store(out, dst, src);
+            return;
+        }
+        if opt::is_memory(src) {
+            self.generate_load(out, dst, src);
+            return;
+        }
+        if let Some(error) = self.unmapped(dst).or_else(|| self.unmapped(src)) {
+            out.push_str(&error);
+            return;
+        }
+        let dst_reg = self.map_operand(dst);
+        let src_op = self.source(&mut String::new(), src);
+        if src_op == dst_reg {
+            return;
+        }
+        let value = Self::immediate(src);
+        if Self::is_address(&dst_reg) {
+            if value.is_none() && !Self::is_register(&src_op) {
+                // The address of a symbol.
+                {
+                    emit!(out, "    lea {},{}\n", &src_op[1..], dst_reg);
+                    return;
+                };
+            }
+            emit!(out, "    movea.l {},{}\n", src_op, dst_reg);
+            return;
+        }
+        if let Some(value) = value
+            && (-128..=127).contains(&value)
+            && Self::is_data(&dst_reg)
+        {
+            emit!(out, "    moveq #{},{}\n", value, dst_reg);
+            return;
+        }
+        emit!(out, "    move.l {},{}\n", src_op, dst_reg);
+    }
+
+    fn generate_lea(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        let address = self.address(out, src, "%a0");
+        if Self::is_address(&dst_reg) {
+            emit!(out, "    lea {},{}\n", address, dst_reg);
+            return;
+        }
+        emit!(out, "    lea {},%a0\n    move.l %a0,{}\n", address, dst_reg);
+    }
+
+    fn generate_load(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(error) = self.unmapped(dst) {
+            out.push_str(&error);
+            return;
+        }
+        let dst_reg = self.map_operand(dst);
+        let address = self.address(out, src, "%a0");
+        let op = if Self::is_address(&dst_reg) {
+            "movea.l"
+        } else {
+            "move.l"
+        };
+        emit!(out, "    {} {},{}\n", op, address, dst_reg);
+    }
+
+    fn generate_store(&self, out: &mut String, dst: &str, src: &str) {
+        let src_op = self.source(out, src);
+        let address = self.address(out, dst, "%a0");
+        emit!(out, "    move.l {},{}\n", src_op, address);
+    }
+
+    fn generate_add(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(error) = self.unmapped(dst) {
+            out.push_str(&error);
+            return;
+        }
+        let dst_reg = self.map_operand(dst);
+        let src_op = self.source(out, src);
+        match Self::immediate(src) {
+            Some(0) => {
+                return;
+            }
+            Some(value @ 1..=8) => {
+                emit!(out, "    addq.l #{},{}\n", value, dst_reg);
+                return;
+            }
+            Some(value @ -8..=-1) => {
+                emit!(out, "    subq.l #{},{}\n", -value, dst_reg);
+                return;
+            }
+            Some(value) if Self::is_address(&dst_reg) && Self::is_disp16(value) => {
+                emit!(out, "    lea {}({r}),{r}\n", value, r = dst_reg);
+                return;
+            }
+            _ => {}
+        }
+        let op = if Self::is_address(&dst_reg) {
+            "adda.l"
+        } else {
+            "add.l"
+        };
+        emit!(out, "    {} {},{}\n", op, src_op, dst_reg);
+    }
+
+    fn generate_sub(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(error) = self.unmapped(dst) {
+            out.push_str(&error);
+            return;
+        }
+        let dst_reg = self.map_operand(dst);
+        let src_op = self.source(out, src);
+        match Self::immediate(src) {
+            Some(0) => {
+                return;
+            }
+            Some(value @ 1..=8) => {
+                emit!(out, "    subq.l #{},{}\n", value, dst_reg);
+                return;
+            }
+            Some(value @ -8..=-1) => {
+                emit!(out, "    addq.l #{},{}\n", -value, dst_reg);
+                return;
+            }
+            Some(value) if Self::is_address(&dst_reg) && Self::is_disp16(-value) => {
+                emit!(out, "    lea {}({r}),{r}\n", -value, r = dst_reg);
+                return;
+            }
+            _ => {}
+        }
+        let op = if Self::is_address(&dst_reg) {
+            "suba.l"
+        } else {
+            "sub.l"
+        };
+        emit!(out, "    {} {},{}\n", op, src_op, dst_reg);
+    }
+
+    fn generate_mul(&self, out: &mut String, dst: &str, src: &str) {
+        if self.has_long_multiply() {
+            self.binary(out, "muls.l", dst, src);
+            return;
+        }
+        self.library(out, "__mulsi3", dst, src);
+    }
+
+    fn generate_div(&self, out: &mut String, dst: &str, src: &str) {
+        if self.has_long_multiply() {
+            self.binary(out, "divs.l", dst, src);
+            return;
+        }
+        self.library(out, "__divsi3", dst, src);
+    }
+
+    fn generate_inc(&self, out: &mut String, dst: &str) {
+        self.generate_add(out, dst, "1");
+    }
+
+    fn generate_dec(&self, out: &mut String, dst: &str) {
+        self.generate_sub(out, dst, "1");
+    }
+
+    fn generate_neg(&self, out: &mut String, dst: &str) {
+        self.in_data(out, dst, |out, d| emit!(out, "    neg.l {}\n", d));
+    }
+
+    fn generate_and(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "and.l", dst, src);
+    }
+
+    fn generate_or(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "or.l", dst, src);
+    }
+
+    fn generate_xor(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "eor.l", dst, src);
+    }
+
+    fn generate_not(&self, out: &mut String, dst: &str) {
+        self.in_data(out, dst, |out, d| emit!(out, "    not.l {}\n", d));
+    }
+
+    fn generate_shl(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "lsl", dst, src);
+    }
+
+    fn generate_shr(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "lsr", dst, src);
+    }
+
+    fn generate_cmp(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_test(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_jmp(&self, out: &mut String, label: &str) {
+        if Self::is_register(&self.map_operand(label)) {
+            let target = self.jump_target(out, label);
+            emit!(out, "    jmp {}\n", target);
+            return;
+        }
+        emit!(out, "    jra {}\n", label);
+    }
+
+    fn generate_je(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_jne(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_jg(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_jl(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_jge(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_jle(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_call(&self, out: &mut String, func: &str) {
+        if Self::is_register(&self.map_operand(func)) {
+            let target = self.jump_target(out, func);
+            emit!(out, "    jsr {}\n", target);
+            return;
+        }
+        emit!(out, "    jsr {}\n", func);
+    }
+
+    fn generate_ret(&self, out: &mut String) {
+        out.push_str("    rts\n");
+    }
+
+    fn generate_syscall(&self, out: &mut String, name: &str) {
+        let (syscall_num, arguments) = match name {
+            "read" => (3, 3),
+            "write" => (4, 3),
+            "exit" => (1, 1),
+            "open" => (5, 3),
+            "close" => (6, 1),
+            "mmap" => (90, 6),
+            "munmap" => (91, 2),
+            "brk" => (45, 1),
+            _ => {
+                emit!(
+                    out,
+                    "    | Unknown syscall: {}\n    moveq #0,%d0\n    trap #0\n",
+                    name
+                );
+                return;
+            }
+        };
+        // The arguments go on the stack first, as the program's registers
+        // overlap the kernel's: r0 is %d0, where the number goes.
+        let push = |out: &mut String| {
+            for n in (0..arguments).rev() {
+                let register = self.source(&mut String::new(), &format!("r{}", n));
+                emit!(out, "    move.l {},-(%sp)\n", register);
+            }
+        };
+        if arguments > 5 {
+            // `mmap` takes a pointer to its six arguments in %d1.
+            push(out);
+            emit!(
+                out,
+                "    move.l %d1,%d6\n    move.l %sp,%d1\n    moveq #{},%d0\n    trap #0\n    move.l %d6,%d1\n    lea {}(%sp),%sp\n",
+                syscall_num,
+                arguments * 4
+            );
+            return;
+        }
+        // %d1 onwards take the arguments, and get their values back after.
+        let registers = match arguments {
+            1 => "%d1".to_string(),
+            n => format!("%d1-%d{}", n),
+        };
+        emit!(out, "    movem.l {},-(%sp)\n", registers);
+        push(out);
+        // The kernel returns -errno on failure, as the other targets do.
+        emit!(
+            out,
+            "    movem.l (%sp)+,{r}\n    moveq #{},%d0\n    trap #0\n    movem.l (%sp)+,{r}\n",
+            syscall_num,
+            r = registers
+        );
+    }
+
+    fn map_operand(&self, operand: &str) -> String {
+        if operand.chars().all(|c| c.is_ascii_digit() || c == '-') {
+            return operand.to_string();
+        }
+
+        if operand.starts_with('[') && operand.ends_with(']') {
+            return self.map_memory_operand(operand);
+        }
+
+        if let Some(mapped) = self.register_map.get(operand) {
+            mapped.clone()
+        } else {
+            operand.to_string()
+        }
+    }
+
+    fn map_memory_operand(&self, operand: &str) -> String {
+        if !(operand.starts_with('[') && operand.ends_with(']')) {
+            return operand.to_string();
+        }
+        // Operands no addressing mode takes name the scratch register their
+        // address is computed into.
+        self.address(&mut String::new(), operand, "%a0")
+    }
+
+    fn generate_cmov_eq(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_cmov_ne(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_cmov_lt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_cmov_le(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_cmov_gt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_cmov_ge(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_cmov_ov(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_cmov_no(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_cmov_s(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_cmov_ns(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_cmov_p(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_cmov_np(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_cmov_a(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_cmov_ae(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_cmov_b(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_cmov_be(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_push(&self, out: &mut String, src: &str) {
+        let src_op = self.source(out, src);
+        emit!(out, "    move.l {},-(%sp)\n", src_op);
+    }
+
+    fn generate_pop(&self, out: &mut String, dst: &str) {
+        let dst_reg = self.map_operand(dst);
+        let op = if Self::is_address(&dst_reg) {
+            "movea.l"
+        } else {
+            "move.l"
+        };
+        emit!(out, "    {} (%sp)+,{}\n", op, dst_reg);
+    }
+
+    fn generate_pusha(&self, out: &mut String) {
+        emit!(out, "    movem.l {},-(%sp)\n", SAVED_BY_PUSHA);
+    }
+
+    fn generate_popa(&self, out: &mut String) {
+        emit!(out, "    movem.l (%sp)+,{}\n", SAVED_BY_PUSHA);
+    }
+
+    fn generate_enter(&self, out: &mut String, frame_size: &str, _nesting_level: &str) {
+        // `link` pushes %fp, points it at the saved copy and moves %sp past
+        // the frame, as `enter` does; the frame keeps %sp long-aligned.
+        match opt::parse_immediate(frame_size) {
+            Some(size) => {
+                let size = (size + 3) & !3;
+                if size <= 0x8000 {
+                    emit!(out, "    link.w %fp,#{}\n", -size);
+                } else if self.cpu >= 68020 {
+                    emit!(out, "    link.l %fp,#{}\n", -size);
+                } else {
+                    emit!(out, "    link.w %fp,#0\n    suba.l #{},%sp\n", size);
+                }
+            }
+            None => emit!(out, "    link.w %fp,#-({})\n", frame_size),
+        }
+    }
+
+    fn generate_leave(&self, out: &mut String) {
+        out.push_str("    unlk %fp\n");
+    }
+
+    fn generate_imul(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_mul(out, dst, src);
+    }
+
+    fn generate_idiv(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_div(out, dst, src);
+    }
+
+    fn generate_mod(&self, out: &mut String, dst: &str, src: &str) {
+        if self.has_long_multiply() {
+            self.wide(out, "divsl.l", dst, src);
+            return;
+        }
+        self.library(out, "__modsi3", dst, src);
+    }
+
+    fn generate_mulh(&self, out: &mut String, dst: &str, src: &str) {
+        if self.has_wide_multiply() {
+            self.wide(out, "muls.l", dst, src);
+            return;
+        }
+        // libgcc's 64-bit multiply of the sign-extended operands, which
+        // returns the high word in %d0. Each argument is pushed low word
+        // first, so the high word comes first in memory.
+        let src_op = self.source(out, src);
+        emit!(
+            out,
+            concat!(
+                "    move.l {},%d7\n    movem.l %d0-%d1,-(%sp)\n    move.l %d7,-(%sp)\n",
+                "    add.l %d7,%d7\n    subx.l %d7,%d7\n    move.l %d7,-(%sp)\n",
+                "    move.l {},%d7\n    move.l %d7,-(%sp)\n    add.l %d7,%d7\n",
+                "    subx.l %d7,%d7\n    move.l %d7,-(%sp)\n    jsr __muldi3\n",
+                "    lea 16(%sp),%sp\n    move.l %d0,%d7\n    movem.l (%sp)+,%d0-%d1\n"
+            ),
+            src_op,
+            self.map_operand(dst)
+        );
+        self.generate_mov(out, dst, "%d7");
+    }
+
+    fn generate_andn(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(value) = Self::immediate(src) {
+            self.generate_and(out, dst, &(!value).to_string());
+            return;
+        }
+        let src_op = self.source(out, src);
+        emit!(out, "    move.l {},%d7\n    not.l %d7\n", src_op);
+        self.in_data(out, dst, |out, d| emit!(out, "    and.l %d7,{}\n", d));
+    }
+
+    fn generate_sal(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_shl(out, dst, src);
+    }
+
+    fn generate_sar(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "asr", dst, src);
+    }
+
+    fn generate_rol(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "rol", dst, src);
+    }
+
+    fn generate_ror(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "ror", dst, src);
+    }
+
+    fn generate_rcl(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_rcr(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_bextr(&self, out: &mut String, dst: &str, src: &str, imm: &str) {
+        let src_op = self.source(out, src);
+        emit!(out, "    move.l {},%d7\n", src_op);
+        if let Some(control) = Self::immediate(imm) {
+            let start = control & 0xff;
+            let length = ((control >> 8) & 0xff).min(32 - start.min(32));
+            if length == 0 {
+                self.generate_mov(out, dst, "0");
+                return;
+            }
+            match start {
+                0 => {}
+                1..=8 => emit!(out, "    lsr.l #{},%d7\n", start),
+                _ => emit!(out, "    moveq #{},%d6\n    lsr.l %d6,%d7\n", start),
+            }
+            if length < 32 {
+                emit!(out, "    and.l #{},%d7\n", (1i64 << length) - 1);
+            }
+            self.generate_mov(out, dst, "%d7");
+            return;
+        }
+        // Shift the field down, clearing it for starts past the top, then to
+        // the top and back by 32 - length unless the length takes in
+        // everything; a register shift by 32 leaves nothing.
+        let control = self.source(out, imm);
+        emit!(
+            out,
+            concat!(
+                "    move.l {c},%d6\n    and.l #255,%d6\n    cmp.l #32,%d6\n    jcs 1f\n",
+                "    moveq #0,%d7\n1:\n    lsr.l %d6,%d7\n    move.l {c},%d6\n    lsr.l #8,%d6\n",
+                "    and.l #255,%d6\n    cmp.l #32,%d6\n    jcc 2f\n    neg.l %d6\n",
+                "    add.l #32,%d6\n    lsl.l %d6,%d7\n    lsr.l %d6,%d7\n2:\n"
+            ),
+            c = control
+        );
+        self.generate_mov(out, dst, "%d7");
+    }
+
+    fn generate_bsf(&self, out: &mut String, dst: &str, src: &str) {
+        // The lowest set bit alone is its own highest; -1 when there is none.
+        let src_op = self.source(out, src);
+        let scan = if self.cpu >= 68020 {
+            concat!(
+                "    move.l %d7,%d6\n    neg.l %d6\n    and.l %d6,%d7\n",
+                "    bfffo %d7{#0:#0},%d7\n    moveq #31,%d6\n    sub.l %d7,%d6\n",
+                "    move.l %d6,%d7\n"
+            )
+        } else {
+            // Shift bits out until one is set.
+            "    moveq #-1,%d6\n    tst.l %d7\n    jeq 2f\n1:\n    addq.l #1,%d6\n    lsr.l #1,%d7\n    jcc 1b\n2:\n    move.l %d6,%d7\n"
+        };
+        emit!(out, "    move.l {},%d7\n{}", src_op, scan);
+        self.generate_mov(out, dst, "%d7");
+    }
+
+    fn generate_bsr(&self, out: &mut String, dst: &str, src: &str) {
+        // 31 less the leading zeros; -1 when there is no set bit.
+        let src_op = self.source(out, src);
+        let scan = if self.cpu >= 68020 {
+            "    bfffo %d7{#0:#0},%d7\n    moveq #31,%d6\n    sub.l %d7,%d6\n    move.l %d6,%d7\n"
+        } else {
+            // Shift bits out until none are left.
+            "    moveq #-1,%d6\n    tst.l %d7\n    jeq 2f\n1:\n    addq.l #1,%d6\n    lsr.l #1,%d7\n    jne 1b\n2:\n    move.l %d6,%d7\n"
+        };
+        emit!(out, "    move.l {},%d7\n{}", src_op, scan);
+        self.generate_mov(out, dst, "%d7");
+    }
+
+    fn generate_bt(&self, _out: &mut String, _dst: &str, _bit: &str) {
+        // Only the flags hold the bit; `opt::flags` tests it in the reader.
+    }
+
+    fn generate_btr(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "bclr", dst, bit);
+    }
+
+    fn generate_bts(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "bset", dst, bit);
+    }
+
+    fn generate_btc(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "bchg", dst, bit);
+    }
+
+    fn generate_set_eq(&self, _out: &mut String, _dst: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_set_ne(&self, _out: &mut String, _dst: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_set_lt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_set_le(&self, _out: &mut String, _dst: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_set_gt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_set_ge(&self, _out: &mut String, _dst: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_set_ov(&self, _out: &mut String, _dst: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_set_no(&self, _out: &mut String, _dst: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_set_s(&self, _out: &mut String, _dst: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_set_ns(&self, _out: &mut String, _dst: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_set_p(&self, _out: &mut String, _dst: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_set_np(&self, _out: &mut String, _dst: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_set_a(&self, _out: &mut String, _dst: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_set_ae(&self, _out: &mut String, _dst: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_set_b(&self, _out: &mut String, _dst: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_set_be(&self, _out: &mut String, _dst: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_cmps(&self, _out: &mut String, _src1: &str, _src2: &str) {}
+
+    fn generate_scas(&self, _out: &mut String, _src: &str, _val: &str) {}
+
+    fn generate_stos(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_store(out, dst, src);
+    }
+
+    fn generate_lods(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_load(out, dst, src);
+    }
+
+    fn generate_movs(&self, out: &mut String, dst: &str, src: &str) {
+        // `move` copies from memory to memory.
+        let src_address = self.address(out, src, "%a1");
+        let dst_address = self.address(out, dst, "%a0");
+        emit!(out, "    move.l {},{}\n", src_address, dst_address);
+    }
+
+    fn generate_cbw(&self, out: &mut String, dst: &str) {
+        self.extend(out, dst, false);
+    }
+
+    fn generate_cwd(&self, out: &mut String, dst: &str) {
+        self.extend(out, dst, true);
+    }
+
+    fn generate_cdq(&self, _out: &mut String, _dst: &str) {}
+
+    fn generate_cqo(&self, out: &mut String, dst: &str) {
+        // The high half of the 64-bit sign extension: the sign bit shifted
+        // out into the extend flag, and subtracted from nothing.
+        self.in_data(out, dst, |out, d| {
+            emit!(out, "    add.l {d},{d}\n    subx.l {d},{d}\n", d = d)
+        });
+    }
+
+    fn generate_cwde(&self, out: &mut String, dst: &str) {
+        self.extend(out, dst, true);
+    }
+
+    fn generate_cdqe(&self, _out: &mut String, _dst: &str) {}
+
+    fn generate_jo(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_jno(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_js(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_jns(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_jp(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_jnp(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_ja(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_jae(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_jb(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_jbe(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_loop_eq(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_loop_ne(&self, _out: &mut String, _label: &str) {
+        unreachable!("m68k compares in each reader")
+    }
+
+    fn generate_in(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("    | m68k has no IN instruction, not supported.\n");
+    }
+
+    fn generate_out(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("    | m68k has no OUT instruction, not supported.\n");
+    }
+
+    fn generate_ins(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("    | m68k has no INS instruction, not supported.\n");
+    }
+
+    fn generate_outs(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("    | m68k has no OUTS instruction, not supported.\n");
+    }
+
+    fn generate_cpuid(&self, out: &mut String) {
+        out.push_str("    | m68k does not have CPUID\n");
+    }
+
+    fn generate_lfence(&self, out: &mut String) {
+        // `nop` waits for the pipeline and pending writes on the 68040 and
+        // 68060; earlier processors keep their accesses in order.
+        out.push_str("    nop\n");
+    }
+
+    fn generate_sfence(&self, out: &mut String) {
+        out.push_str("    nop\n");
+    }
+
+    fn generate_mfence(&self, out: &mut String) {
+        out.push_str("    nop\n");
+    }
+
+    fn generate_prefetch(&self, out: &mut String, _addr: &str) {
+        out.push_str("    | m68k has no prefetch instruction\n");
+    }
+
+    fn generate_clflush(&self, out: &mut String, _addr: &str) {
+        out.push_str("    | m68k pushes cache lines only in supervisor mode, not supported\n");
+    }
+
+    fn generate_clwb(&self, out: &mut String, _addr: &str) {
+        out.push_str("    | m68k pushes cache lines only in supervisor mode, not supported\n");
+    }
+
+    fn generate_global(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".globl {}\n", symbol);
+    }
+
+    fn generate_extern(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".extern {}\n", symbol);
+    }
+
+    fn generate_align(&self, out: &mut String, n: &str) {
+        emit!(out, ".p2align {}\n", n);
+    }
+
+    fn generate_data_byte(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .byte {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_word(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .word {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_dword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .long {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_qword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .quad {}\n", name, values.join(", "));
+    }
+
+    fn generate_reserve_byte(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip {}\n", name, count);
+    }
+
+    fn generate_reserve_word(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 2 * ({})\n", name, count);
+    }
+
+    fn generate_reserve_dword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 4 * ({})\n", name, count);
+    }
+
+    fn generate_reserve_qword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 8 * ({})\n", name, count);
+    }
+
+    fn generate_equ(&self, out: &mut String, name: &str, value: &str) {
+        emit!(out, ".set {}, {}\n", name, value);
+    }
+
+    fn generate_section(&self, out: &mut String, section: &Section) {
+        match section {
+            Section::Text => out.push_str(".section .text\n"),
+            Section::Data => out.push_str(".section .data\n"),
+            Section::Bss => out.push_str(".section .bss\n"),
+            Section::Rodata => out.push_str(".section .rodata\n"),
+            Section::Custom(s) => emit!(out, ".section {}\n", s),
+        }
+    }
+
+    fn generate_label(&self, out: &mut String, name: &str) {
+        emit!(out, "{}:\n", name);
+    }
+
+    fn get_register_width(&self) -> u32 {
+        32
+    }
+
+    fn get_reserved_registers(&self) -> Vec<String> {
+        ["%d6", "%d7", "%a0", "%a1", "%sp", "%fp"]
+            .iter()
+            .map(|r| r.to_string())
+            .collect()
+    }
+
+    fn get_callee_saved_registers(&self) -> Vec<String> {
+        DATA.iter()
+            .chain(ADDRESS.iter())
+            .map(|r| r.to_string())
+            .collect()
+    }
+
+    fn get_push_size(&self) -> u32 {
+        4
+    }
+
+    fn get_clobbered_registers(&self, instruction: &Instruction) -> Vec<String> {
+        use Instruction::*;
+        let registers: &[&str] = match instruction {
+            Syscall(_) => &["%d0"],
+            Call(_) => &["%d0", "%d1", "%a0", "%a1"],
+            _ => &[],
+        };
+        registers.iter().map(|r| r.to_string()).collect()
+    }
+
+    fn set_cpu(&mut self, cpu: &str) -> Result<(), String> {
+        match cpu.parse::<u32>() {
+            Ok(cpu @ (68000 | 68010 | 68020 | 68030 | 68040 | 68060)) => {
+                self.cpu = cpu;
+                Ok(())
+            }
+            _ => Err(format!(
+                "unknown m68k processor `{}`; expected 68000, 68010, 68020, 68030, 68040 or 68060",
+                cpu
+            )),
+        }
+    }
+
+    fn has_address_registers(&self) -> bool {
+        true
+    }
+
+    fn set_address_registers(&mut self, uses: HashMap<String, usize>) {
+        // The names used most as a base go to address registers, and the
+        // rest to data registers while they last; the sort keeps the order
+        // of names used as often.
+        let names: Vec<String> = (2..10).map(|n| format!("r{}", n)).collect();
+        let count = |name: &String| uses.get(name).copied().unwrap_or(0);
+        let mut addressed: Vec<&String> = names.iter().filter(|name| count(name) > 0).collect();
+        addressed.sort_by_key(|name| std::cmp::Reverse(count(name)));
+        addressed.truncate(ADDRESS.len());
+
+        let mut data = DATA.iter();
+        let mut address = ADDRESS.iter();
+        for name in &names {
+            let register = if addressed.contains(&name) {
+                address.next()
+            } else {
+                data.next().or_else(|| address.next())
+            };
+            if let Some(register) = register {
+                self.register_map.insert(name.clone(), register.to_string());
+            }
+        }
+    }
+
+    fn has_condition_flags(&self) -> bool {
+        false
+    }
+    fn compares(&self, tested: Tested) -> bool {
+        matches!(
+            tested,
+            Tested::Operands | Tested::Mask | Tested::Sum | Tested::Difference
+        )
+    }
+
+    fn generate_compare_branch(&self, out: &mut String, comparison: &Comparison, label: &str) {
+        self.compare(out, comparison);
+        emit!(
+            out,
+            "    j{} {}\n",
+            Self::condition_code(comparison.condition, comparison.tested),
+            label
+        );
+    }
+
+    fn generate_compare_set(&self, out: &mut String, comparison: &Comparison, dst: &str) {
+        // `scc` sets a byte to all ones or all zeros.
+        self.compare(out, comparison);
+        let condition = Self::condition_code(comparison.condition, comparison.tested);
+        let dst_reg = self.map_operand(dst);
+        if Self::is_data(&dst_reg) {
+            emit!(
+                out,
+                "    s{} {d}\n    and.l #1,{d}\n",
+                condition,
+                d = dst_reg
+            );
+            return;
+        }
+        emit!(out, "    s{} %d7\n    and.l #1,%d7\n", condition);
+        self.generate_mov(out, dst, "%d7");
+    }
+
+    fn generate_compare_cmov(
+        &self,
+        out: &mut String,
+        comparison: &Comparison,
+        dst: &str,
+        src: &str,
+    ) {
+        let skip = Self::condition_code(comparison.condition.negate(), comparison.tested);
+        self.compare(out, comparison);
+        emit!(out, "    j{} 1f\n", skip);
+        self.generate_mov(out, dst, src);
+        out.push_str("1:\n");
+    }
+}
@@ -0,0 +1,1379 @@
+use super::*;
+use std::collections::HashMap;
+
+pub struct MIPS32CodeGen {
+    register_map: HashMap<String, String>,
+    /// Symbols of the program. Code always reaches data through the GOT the
+    /// global pointer in $gp points into, so it is position-independent
+    /// whatever `-fpic` says.
+    pic: Option<Pic>,
+}
+
+impl MIPS32CodeGen {
+    pub fn new() -> Self {
+        let mut register_map = HashMap::with_capacity(32);
+
+        // Function argument registers (O32 ABI)
+        register_map.insert("r0".to_string(), "$a0".to_string()); // 1st arg/return value
+        register_map.insert("r1".to_string(), "$a1".to_string()); // 2nd arg
+        register_map.insert("r2".to_string(), "$a2".to_string()); // 3rd arg
+        register_map.insert("r3".to_string(), "$a3".to_string()); // 4th arg
+
+        // Temporary registers; O32 passes further arguments on the stack
+        register_map.insert("r4".to_string(), "$t0".to_string()); // 5th syscall arg
+        register_map.insert("r5".to_string(), "$t1".to_string()); // 6th syscall arg
+        register_map.insert("r6".to_string(), "$t2".to_string());
+        register_map.insert("r7".to_string(), "$t3".to_string());
+        register_map.insert("r8".to_string(), "$t4".to_string());
+        register_map.insert("r9".to_string(), "$t5".to_string());
+        register_map.insert("r10".to_string(), "$t6".to_string());
+        register_map.insert("r11".to_string(), "$t7".to_string());
+
+        // Saved registers
+        register_map.insert("r12".to_string(), "$s4".to_string());
+        register_map.insert("r13".to_string(), "$s5".to_string());
+        register_map.insert("r14".to_string(), "$s6".to_string());
+        register_map.insert("r15".to_string(), "$s7".to_string());
+        register_map.insert("r19".to_string(), "$s0".to_string());
+        register_map.insert("r20".to_string(), "$s1".to_string());
+        register_map.insert("r21".to_string(), "$s2".to_string());
+        register_map.insert("r22".to_string(), "$s3".to_string());
+
+        // Special purpose registers
+        register_map.insert("sp".to_string(), "$sp".to_string()); // Stack pointer
+        register_map.insert("sb".to_string(), "$fp".to_string()); // Frame pointer
+        register_map.insert("ip".to_string(), "$ra".to_string()); // Return address
+
+        MIPS32CodeGen {
+            register_map,
+            pic: None,
+        }
+    }
+
+    /// Whether `operand` names a general-purpose register.
+    fn is_register(operand: &str) -> bool {
+        let Some(name) = operand.strip_prefix('$') else {
+            return false;
+        };
+        let numbered = |prefix: &str, last: u32| {
+            name.strip_prefix(prefix)
+                .filter(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+                .and_then(|n| n.parse::<u32>().ok())
+                .is_some_and(|n| n <= last)
+        };
+        matches!(name, "zero" | "at" | "gp" | "sp" | "fp" | "ra")
+            || numbered("", 31)
+            || numbered("v", 1)
+            || numbered("a", 3)
+            || numbered("t", 9)
+            || numbered("s", 8)
+            || numbered("k", 1)
+    }
+
+    /// The value of the constant `operand` as the 32 bits a register holds,
+    /// including the unsigned masks `opt::flags` writes for `bt` of the top
+    /// bit.
+    fn immediate(operand: &str) -> Option<i64> {
+        opt::parse_immediate(operand)
+            .or_else(|| operand.trim().parse::<u64>().ok().map(|value| value as i64))
+            .map(|value| value as i32 as i64)
+    }
+
+    /// Builds `value` in `dst`, 16 bits at a time.
+    fn load_immediate(out: &mut String, dst: &str, value: i64) {
+        let value = value as i32;
+        if i16::try_from(value).is_ok() {
+            emit!(out, "    addiu {}, $zero, {}\n", dst, value);
+            return;
+        }
+        if (0..=0xffff).contains(&value) {
+            emit!(out, "    ori {}, $zero, {}\n", dst, value);
+            return;
+        }
+        emit!(out, "    lui {}, {}\n", dst, (value as u32) >> 16);
+        if value & 0xffff != 0 {
+            emit!(out, "    ori {d}, {d}, {}\n", value & 0xffff, d = dst);
+        }
+    }
+
+    /// `dst = src + value`, through $v1 for values beyond 16 bits.
+    fn add_constant(out: &mut String, dst: &str, src: &str, value: i64) {
+        if value == 0 && dst == src {
+            return;
+        }
+        if (-0x8000..=0x7fff).contains(&value) {
+            emit!(out, "    addiu {}, {}, {}\n", dst, src, value);
+            return;
+        }
+        Self::load_immediate(out, "$v1", value);
+        emit!(out, "    addu {}, {}, $v1\n", dst, src);
+    }
+
+    /// The address of `symbol + offset` in `dst`, from the GOT: a global
+    /// entry holds the address of a symbol another module may provide, a
+    /// page entry the 64K page around the others, which `%lo` completes.
+    fn address(&self, out: &mut String, pic: &Pic, dst: &str, symbol: &str, offset: i64) {
+        if pic.is_preemptible(symbol) {
+            emit!(out, "    lw {d}, %got({})($gp)\n", symbol, d = dst);
+            Self::add_constant(out, dst, dst, offset);
+            return;
+        }
+        emit!(
+            out,
+            "    lw {d}, %got({e})($gp)\n    addiu {d}, {d}, %lo({e})\n",
+            d = dst,
+            e = Pic::expression(symbol, offset)
+        );
+    }
+
+    /// The register holding `operand`, which goes in `scratch` first if it
+    /// is a constant or a symbol other than 0, which $zero holds.
+    fn in_register(&self, out: &mut String, operand: &str, scratch: &str) -> String {
+        let mapped = self.map_operand(operand);
+        if Self::is_register(&mapped) {
+            mapped
+        } else if Self::immediate(operand) == Some(0) {
+            "$zero".to_string()
+        } else {
+            self.generate_mov(out, scratch, operand);
+            scratch.to_string()
+        }
+    }
+
+    /// `operand` as base, index and displacement, which wraps around as
+    /// `addiu` does on an address.
+    fn split_address(&self, operand: &str) -> Result<Address, String> {
+        let mut address =
+            super::split_address(operand, |name| self.map_operand(name), Self::is_register)?;
+        address.displacement = address.displacement as i32 as i64;
+        Ok(address)
+    }
+
+    /// Puts `operand` in the form `displacement(base)`: symbols, other
+    /// non-register parts and indices go into $t9, a scaled index into $v1
+    /// first.
+    fn locate(&self, out: &mut String, operand: &str) -> (String, String) {
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(operand)
+        {
+            if pic.is_preemptible(symbol) {
+                if (-0x8000..=0x7fff).contains(&offset) {
+                    self.address(out, pic, "$t9", symbol, 0);
+                    return ("$t9".to_string(), offset.to_string());
+                }
+                self.address(out, pic, "$t9", symbol, offset);
+                return ("$t9".to_string(), "0".to_string());
+            }
+            // The access itself adds the low half of the address.
+            let expression = Pic::expression(symbol, offset);
+            emit!(out, "    lw $t9, %got({})($gp)\n", expression);
+            return ("$t9".to_string(), format!("%lo({})", expression));
+        }
+        let address = address_or_error(out, self.split_address(operand));
+        let mut displacement = address.displacement;
+        let mut base = match (address.base, address.symbolic.is_empty()) {
+            (Some(base), true) => base,
+            (base, _) => {
+                let expression = match (address.symbolic.is_empty(), displacement) {
+                    (true, displacement) => displacement.to_string(),
+                    (false, 0) => address.symbolic,
+                    (false, displacement) => format!("{} + {}", address.symbolic, displacement),
+                };
+                displacement = 0;
+                self.generate_mov(out, "$t9", &expression);
+                if let Some(base) = base {
+                    emit!(out, "    addu $t9, $t9, {}\n", base);
+                }
+                "$t9".to_string()
+            }
+        };
+        if let Some((index, shift)) = address.index {
+            let index = if shift > 0 {
+                emit!(out, "    sll $v1, {}, {}\n", index, shift);
+                "$v1".to_string()
+            } else {
+                index
+            };
+            emit!(out, "    addu $t9, {}, {}\n", base, index);
+            base = "$t9".to_string();
+        }
+        if !(-0x8000..=0x7fff).contains(&displacement) {
+            Self::add_constant(out, "$t9", &base, displacement);
+            base = "$t9".to_string();
+            displacement = 0;
+        }
+        (base, displacement.to_string())
+    }
+
+    /// `op value, operand` for the load or store `op`.
+    fn access(&self, out: &mut String, op: &str, value: &str, operand: &str) {
+        let (base, displacement) = self.locate(out, operand);
+        emit!(out, "    {} {}, {}({})\n", op, value, displacement, base);
+    }
+
+    /// `op dst, dst, src`, or `immediate dst, dst, src` when `src` is a
+    /// constant in `range`.
+    fn binary(
+        &self,
+        out: &mut String,
+        op: &str,
+        immediate: Option<(&str, std::ops::RangeInclusive<i64>)>,
+        dst: &str,
+        src: &str,
+    ) {
+        let dst_reg = self.map_operand(dst);
+        if let Some((immediate, range)) = immediate
+            && let Some(value) = Self::immediate(src)
+            && range.contains(&value)
+        {
+            emit!(
+                out,
+                "    {} {}, {}, {}\n",
+                immediate,
+                dst_reg,
+                dst_reg,
+                value
+            );
+            return;
+        }
+        let src_reg = self.in_register(out, src, "$t8");
+        emit!(out, "    {} {}, {}, {}\n", op, dst_reg, dst_reg, src_reg);
+    }
+
+    /// A division of `dst` by `src`, keeping the quotient from `lo` or the
+    /// remainder from `hi`.
+    fn divide(&self, out: &mut String, result: &str, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.in_register(out, src, "$t8");
+        // `div` with a destination is the assembler's macro, which adds a
+        // trap for division by zero.
+        emit!(
+            out,
+            "    div $zero, {d}, {}\n    {} {d}\n",
+            src_reg,
+            result,
+            d = dst_reg
+        );
+    }
+
+    /// A shift of `dst` by `count`, which MIPS takes modulo 32 like x86 does
+    /// for 32-bit operands.
+    fn shift(&self, out: &mut String, op: &str, dst: &str, count: &str) {
+        let dst_reg = self.map_operand(dst);
+        let count_reg = self.map_operand(count);
+        match Self::immediate(count) {
+            Some(count) => emit!(out, "    {} {d}, {d}, {}\n", op, count & 31, d = dst_reg),
+            None if Self::is_register(&count_reg) => {
+                emit!(out, "    {}v {d}, {d}, {}\n", op, count_reg, d = dst_reg);
+            }
+            // A symbol the assembler knows the value of.
+            None => emit!(
+                out,
+                "    {} {d}, {d}, ({}) & 31\n",
+                op,
+                count_reg,
+                d = dst_reg
+            ),
+        }
+    }
+
+    /// A rotate of `dst` by `count`; a left rotate is a right one by
+    /// 32 - count.
+    fn rotate(&self, out: &mut String, left: bool, dst: &str, count: &str) {
+        let dst_reg = self.map_operand(dst);
+        let count_reg = self.map_operand(count);
+        if Self::is_register(&count_reg) {
+            if !left {
+                emit!(out, "    rotrv {d}, {d}, {}\n", count_reg, d = dst_reg);
+                return;
+            }
+            emit!(
+                out,
+                "    negu $t8, {}\n    rotrv {d}, {d}, $t8\n",
+                count_reg,
+                d = dst_reg
+            );
+            return;
+        }
+        let amount = match Self::immediate(count) {
+            Some(count) if left => ((32 - (count & 31)) & 31).to_string(),
+            Some(count) => (count & 31).to_string(),
+            None if left => format!("(-({})) & 31", count_reg),
+            None => format!("({}) & 31", count_reg),
+        };
+        emit!(out, "    rotr {d}, {d}, {}\n", amount, d = dst_reg);
+    }
+
+    /// `op dst, dst, mask` for the single-bit mask `bit` selects, inverted
+    /// when `clear`.
+    fn bit(&self, out: &mut String, op: &str, clear: bool, dst: &str, bit: &str) {
+        let dst_reg = self.map_operand(dst);
+        let bit_reg = self.map_operand(bit);
+        match Self::immediate(bit) {
+            Some(bit) => {
+                let mask = 1i64 << (bit & 31);
+                if !clear && mask <= 0xffff {
+                    emit!(out, "    {}i {d}, {d}, {}\n", op, mask, d = dst_reg);
+                    return;
+                }
+                Self::load_immediate(out, "$t8", if clear { !mask } else { mask });
+            }
+            None if Self::is_register(&bit_reg) => {
+                emit!(
+                    out,
+                    "    addiu $t8, $zero, 1\n    sllv $t8, $t8, {}\n",
+                    bit_reg
+                );
+            }
+            None => emit!(
+                out,
+                "    addiu $t8, $zero, 1\n    sll $t8, $t8, ({}) & 31\n",
+                bit_reg
+            ),
+        }
+        if clear && Self::immediate(bit).is_none() {
+            out.push_str("    nor $t8, $t8, $zero\n");
+        }
+        emit!(out, "    {} {d}, {d}, $t8\n", op, d = dst_reg);
+    }
+
+    /// Registers holding the two sides of `comparison`, once they are
+    /// there: $zero for 0, $t8 and $t9 for other constants and
+    /// $t8 for the `a & b` of a mask. `b` stays a constant where `slti`
+    /// or `sltiu` can take it, and so can the `b + 1` of `a > b`.
+    fn compared(&self, out: &mut String, comparison: &Comparison) -> (String, String) {
+        let a = self.in_register(out, &comparison.a, "$t8");
+        let value = Self::immediate(&comparison.b);
+        if comparison.tested == Tested::Mask {
+            match value {
+                Some(value) if (0..=0xffff).contains(&value) => {
+                    emit!(out, "    andi $t8, {}, {}\n", a, value);
+                }
+                _ => {
+                    let b = self.in_register(out, &comparison.b, "$t9");
+                    emit!(out, "    and $t8, {}, {}\n", a, b);
+                }
+            }
+            return ("$t8".to_string(), "$zero".to_string());
+        }
+        let constants = match comparison.condition {
+            Condition::Lt | Condition::Le | Condition::Gt | Condition::Ge => Some(-0x8000..=0x7ffe),
+            Condition::B | Condition::Be | Condition::A | Condition::Ae => Some(1..=0x7ffe),
+            _ => None,
+        };
+        if let Some(value) = value
+            && constants.is_some_and(|constants| constants.contains(&value))
+        {
+            return (a, value.to_string());
+        }
+        let b = self.in_register(out, &comparison.b, "$t9");
+        (a, b)
+    }
+
+    /// `slt dst, a, b`, unsigned or with a constant `b` as needed.
+    fn less(out: &mut String, unsigned: bool, dst: &str, a: &str, b: &str) {
+        let op = match (unsigned, b.parse::<i64>().is_ok()) {
+            (false, false) => "slt",
+            (false, true) => "slti",
+            (true, false) => "sltu",
+            (true, true) => "sltiu",
+        };
+        emit!(out, "    {} {}, {}, {}\n", op, dst, a, b);
+    }
+
+    /// Tests `comparison`, and returns the branch that is taken when it
+    /// holds, without its target.
+    fn test(&self, out: &mut String, comparison: &Comparison) -> String {
+        let (a, b) = self.compared(out, comparison);
+        let unsigned = matches!(
+            comparison.condition,
+            Condition::B | Condition::Be | Condition::A | Condition::Ae
+        );
+        let zero = b == "$zero";
+        match comparison.condition {
+            Condition::Eq => format!("beq {}, {}", a, b),
+            Condition::Ne => format!("bne {}, {}", a, b),
+            Condition::Lt | Condition::S if zero => format!("bltz {}", a),
+            Condition::Ge | Condition::Ns if zero => format!("bgez {}", a),
+            Condition::Gt if zero => format!("bgtz {}", a),
+            Condition::Le if zero => format!("blez {}", a),
+            Condition::A if zero => format!("bne {}, $zero", a),
+            Condition::Be if zero => format!("beq {}, $zero", a),
+            Condition::S | Condition::Ns => {
+                emit!(out, "    subu $v1, {}, {}\n", a, b);
+                let mnemonic = if comparison.condition == Condition::S {
+                    "bltz"
+                } else {
+                    "bgez"
+                };
+                format!("{} $v1", mnemonic)
+            }
+            Condition::Lt | Condition::B | Condition::Ge | Condition::Ae => {
+                Self::less(out, unsigned, "$v1", &a, &b);
+                let holds = matches!(comparison.condition, Condition::Lt | Condition::B);
+                format!("{} $v1, $zero", if holds { "bne" } else { "beq" })
+            }
+            Condition::Gt | Condition::A | Condition::Le | Condition::Be => {
+                // a > b is b < a, or for a constant, a >= b + 1.
+                let greater = matches!(comparison.condition, Condition::Gt | Condition::A);
+                let holds = match b.parse::<i64>() {
+                    Ok(value) => {
+                        let bound = (value + 1).to_string();
+                        Self::less(out, unsigned, "$v1", &a, &bound);
+                        !greater
+                    }
+                    Err(_) => {
+                        Self::less(out, unsigned, "$v1", &b, &a);
+                        greater
+                    }
+                };
+                format!("{} $v1, $zero", if holds { "bne" } else { "beq" })
+            }
+        }
+    }
+
+    /// `branch` with the `nop` its delay slot needs under `.set noreorder`.
+    fn delayed(out: &mut String, branch: &str) {
+        emit!(out, "    {}\n    nop\n", branch);
+    }
+
+    /// The registers `pusha` saves and `popa` restores: every general-purpose
+    /// one the register map names but $sp, in a fixed order.
+    fn saved_by_pusha(&self) -> Vec<&String> {
+        let mut registers: Vec<&String> = self
+            .register_map
+            .values()
+            .filter(|r| Self::is_register(r) && *r != "$sp")
+            .collect();
+        registers.sort();
+        registers.dedup();
+        registers
+    }
+
+    /// `op dst, dst`, for negation and sign extension in place.
+    fn unary(&self, out: &mut String, op: &str, dst: &str) {
+        let dst_reg = self.map_operand(dst);
+        emit!(out, "    {} {}, {}\n", op, dst_reg, dst_reg);
+    }
+
+    /// `dst = 31 - x`, the bit index `clz` in `x` counts to: -1 for 32.
+    fn from_top(out: &mut String, dst: &str, x: &str) {
+        emit!(
+            out,
+            "    nor {d}, {}, $zero\n    addiu {d}, {d}, 32\n",
+            x,
+            d = dst
+        );
+    }
+}
+
+impl ArchCodeGen for MIPS32CodeGen {
+    fn get_register_map(&self) -> HashMap<String, String> {
+        self.register_map.clone()
+    }
+
+    fn get_syntax_header(&self) -> String {
+        // Every branch the backend emits fills its own delay slot.
+        ".abicalls\n.option pic2\n.set mips32r2\n.set noreorder\n.text\n.align 2\n\n".to_string()
+    }
+
+    fn generate_mov(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        let src_op = self.map_operand(src);
+
+        if let Some(value) = Self::immediate(src) {
+            Self::load_immediate(out, &dst_reg, value);
+            return;
+        }
+
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(src)
+        {
+            self.address(out, pic, &dst_reg, symbol, offset);
+            return;
+        }
+
+        if Self::is_register(&src_op) {
+            emit!(out, "    move {}, {}\n", dst_reg, src_op);
+            return;
+        }
+
+        // A constant the assembler knows the value of.
+        emit!(
+            out,
+            "    lui {d}, %hi({s})\n    addiu {d}, {d}, %lo({s})\n",
+            d = dst_reg,
+            s = src_op
+        );
+    }
+
+    fn generate_lea(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(src)
+        {
+            self.address(out, pic, &dst_reg, symbol, offset);
+            return;
+        }
+        let (base, displacement) = self.locate(out, src);
+        if displacement == "0" && base != dst_reg {
+            emit!(out, "    move {}, {}\n", dst_reg, base);
+        } else if displacement != "0" {
+            emit!(out, "    addiu {}, {}, {}\n", dst_reg, base, displacement);
+        }
+    }
+
+    fn generate_load(&self, out: &mut String, dst: &str, src: &str) {
+        self.access(out, "lw", &self.map_operand(dst), src);
+    }
+
+    fn generate_store(&self, out: &mut String, dst: &str, src: &str) {
+        let src_reg = self.in_register(out, src, "$t8");
+        self.access(out, "sw", &src_reg, dst);
+    }
+
+    fn generate_add(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "addu", Some(("addiu", -0x8000..=0x7fff)), dst, src);
+    }
+
+    fn generate_sub(&self, out: &mut String, dst: &str, src: &str) {
+        match Self::immediate(src) {
+            Some(value) if (-0x7fff..=0x8000).contains(&value) => {
+                let dst_reg = self.map_operand(dst);
+                emit!(out, "    addiu {}, {}, {}\n", dst_reg, dst_reg, -value);
+            }
+            _ => self.binary(out, "subu", None, dst, src),
+        }
+    }
+
+    fn generate_mul(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "mul", None, dst, src);
+    }
+
+    fn generate_div(&self, out: &mut String, dst: &str, src: &str) {
+        self.divide(out, "mflo", dst, src);
+    }
+
+    fn generate_inc(&self, out: &mut String, dst: &str) {
+        self.generate_add(out, dst, "1");
+    }
+
+    fn generate_dec(&self, out: &mut String, dst: &str) {
+        self.generate_add(out, dst, "-1");
+    }
+
+    fn generate_neg(&self, out: &mut String, dst: &str) {
+        self.unary(out, "negu", dst);
+    }
+
+    fn generate_and(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "and", Some(("andi", 0..=0xffff)), dst, src);
+    }
+
+    fn generate_or(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "or", Some(("ori", 0..=0xffff)), dst, src);
+    }
+
+    fn generate_xor(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "xor", Some(("xori", 0..=0xffff)), dst, src);
+    }
+
+    fn generate_not(&self, out: &mut String, dst: &str) {
+        let dst_reg = self.map_operand(dst);
+        emit!(out, "    nor {}, {}, $zero\n", dst_reg, dst_reg);
+    }
+
+    fn generate_shl(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "sll", dst, src);
+    }
+
+    fn generate_shr(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "srl", dst, src);
+    }
+
+    fn generate_cmp(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_test(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_jmp(&self, out: &mut String, label: &str) {
+        let target = self.map_operand(label);
+        if Self::is_register(&target) {
+            Self::delayed(out, &format!("jr {}", target));
+            return;
+        }
+        Self::delayed(out, &format!("b {}", label));
+    }
+
+    fn generate_je(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_jne(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_jg(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_jl(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_jge(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_jle(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_call(&self, out: &mut String, func: &str) {
+        // The callee gets its address in $t9, as the ABI passes it. Callees
+        // may spill their register arguments to the 16 bytes above $sp, and
+        // one from another module changes $gp, which is saved in the delay
+        // slot and restored after.
+        out.push_str("    addiu $sp, $sp, -24\n");
+        let target = self.map_operand(func);
+        if Self::is_register(&target) {
+            emit!(out, "    move $t9, {}\n", target);
+        } else if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(func)
+            && !pic.is_preemptible(symbol)
+        {
+            self.address(out, pic, "$t9", symbol, offset);
+        } else {
+            emit!(out, "    lw $t9, %call16({})($gp)\n", func);
+        }
+        out.push_str(
+            "    jalr $t9\n    sw $gp, 16($sp)\n    lw $gp, 16($sp)\n    addiu $sp, $sp, 24\n",
+        );
+    }
+
+    fn generate_ret(&self, out: &mut String) {
+        Self::delayed(out, "jr $ra");
+    }
+
+    fn generate_syscall(&self, out: &mut String, name: &str) {
+        // O32 numbers start at 4000.
+        let (syscall_num, arguments) = match name {
+            "read" => ("4003", 3),
+            "write" => ("4004", 3),
+            "exit" => ("4001", 1),
+            "open" => ("4005", 3),
+            "close" => ("4006", 1),
+            "mmap" => ("4090", 6),
+            "munmap" => ("4091", 2),
+            "brk" => ("4045", 1),
+            _ => {
+                emit!(
+                    out,
+                    "    # Unknown syscall: {}\n    li $v0, 0\n    syscall\n",
+                    name
+                );
+                return;
+            }
+        };
+        // The kernel takes the fifth and sixth arguments from the stack,
+        // past the 16 bytes the first four would have.
+        if arguments > 4 {
+            out.push_str("    addiu $sp, $sp, -24\n    sw $t0, 16($sp)\n    sw $t1, 20($sp)\n");
+        }
+        emit!(out, "    li $v0, {}\n    syscall\n", syscall_num);
+        if arguments > 4 {
+            out.push_str("    addiu $sp, $sp, 24\n");
+        }
+        // The kernel returns in $v0 and flags errors, a positive errno, in
+        // $a3. The delay slot moves the result to r0 either way, and an error
+        // is negated to match the other targets.
+        out.push_str("    beqz $a3, 1f\n    move $a0, $v0\n    negu $a0, $v0\n1:\n");
+    }
+
+    fn map_operand(&self, operand: &str) -> String {
+        if operand.chars().all(|c| c.is_ascii_digit() || c == '-') {
+            return operand.to_string();
+        }
+
+        if operand.starts_with('[') && operand.ends_with(']') {
+            return self.map_memory_operand(operand);
+        }
+
+        if let Some(mapped) = self.register_map.get(operand) {
+            mapped.clone()
+        } else {
+            operand.to_string()
+        }
+    }
+
+    fn map_memory_operand(&self, operand: &str) -> String {
+        if !(operand.starts_with('[') && operand.ends_with(']')) {
+            return operand.to_string();
+        }
+        let Ok(address) = self.split_address(operand) else {
+            return operand.to_string();
+        };
+        let base = address.base.unwrap_or_else(|| "$zero".to_string());
+        match (address.symbolic.is_empty(), address.displacement) {
+            (true, displacement) => format!("{}({})", displacement, base),
+            (false, 0) => format!("{}({})", address.symbolic, base),
+            (false, displacement) => {
+                format!("{} + {}({})", address.symbolic, displacement, base)
+            }
+        }
+    }
+
+    fn generate_cmov_eq(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_cmov_ne(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_cmov_lt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_cmov_le(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_cmov_gt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_cmov_ge(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_cmov_ov(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_cmov_no(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_cmov_s(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_cmov_ns(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_cmov_p(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_cmov_np(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_cmov_a(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_cmov_ae(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_cmov_b(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_cmov_be(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_push(&self, out: &mut String, src: &str) {
+        // Pushes keep $sp 8-byte aligned, as the ABI requires.
+        let src_reg = self.in_register(out, src, "$t8");
+        emit!(out, "    addiu $sp, $sp, -8\n    sw {}, 0($sp)\n", src_reg);
+    }
+
+    fn generate_pop(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
+            "    lw {}, 0($sp)\n    addiu $sp, $sp, 8\n",
+            self.map_operand(dst)
+        );
+    }
+
+    fn generate_pusha(&self, out: &mut String) {
+        let registers = self.saved_by_pusha();
+        let size = (registers.len() * 4).next_multiple_of(8);
+        emit!(out, "    addiu $sp, $sp, -{}\n", size);
+        for (i, register) in registers.iter().enumerate() {
+            emit!(out, "    sw {}, {}($sp)\n", register, i * 4);
+        }
+    }
+
+    fn generate_popa(&self, out: &mut String) {
+        let registers = self.saved_by_pusha();
+        let size = (registers.len() * 4).next_multiple_of(8);
+        for (i, register) in registers.iter().enumerate() {
+            emit!(out, "    lw {}, {}($sp)\n", register, i * 4);
+        }
+        emit!(out, "    addiu $sp, $sp, {}\n", size);
+    }
+
+    fn generate_enter(&self, out: &mut String, frame_size: &str, _nesting_level: &str) {
+        // $fp points at the saved $fp and return address, like the frame
+        // pointer on ARM64.
+        out.push_str(
+            "    addiu $sp, $sp, -8\n    sw $ra, 4($sp)\n    sw $fp, 0($sp)\n    move $fp, $sp\n",
+        );
+        if opt::parse_immediate(frame_size) != Some(0) {
+            self.generate_sub(out, "sp", frame_size);
+        }
+    }
+
+    fn generate_leave(&self, out: &mut String) {
+        out.push_str(
+            "    move $sp, $fp\n    lw $ra, 4($sp)\n    lw $fp, 0($sp)\n    addiu $sp, $sp, 8\n",
+        );
+    }
+
+    fn generate_imul(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_mul(out, dst, src);
+    }
+
+    fn generate_idiv(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_div(out, dst, src);
+    }
+
+    fn generate_mod(&self, out: &mut String, dst: &str, src: &str) {
+        self.divide(out, "mfhi", dst, src);
+    }
+
+    fn generate_mulh(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.in_register(out, src, "$t8");
+        emit!(
+            out,
+            "    mult {d}, {}\n    mfhi {d}\n",
+            src_reg,
+            d = dst_reg
+        );
+    }
+
+    fn generate_andn(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(value) = Self::immediate(src) {
+            self.generate_and(out, dst, &(!value).to_string());
+            return;
+        }
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.in_register(out, src, "$t8");
+        emit!(
+            out,
+            "    nor $t8, {}, $zero\n    and {d}, {d}, $t8\n",
+            src_reg,
+            d = dst_reg
+        );
+    }
+
+    fn generate_sal(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_shl(out, dst, src);
+    }
+
+    fn generate_sar(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "sra", dst, src);
+    }
+
+    fn generate_rol(&self, out: &mut String, dst: &str, src: &str) {
+        self.rotate(out, true, dst, src);
+    }
+
+    fn generate_ror(&self, out: &mut String, dst: &str, src: &str) {
+        self.rotate(out, false, dst, src);
+    }
+
+    fn generate_rcl(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_rcr(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_bextr(&self, out: &mut String, dst: &str, src: &str, imm: &str) {
+        let dst_reg = self.map_operand(dst);
+        let control_reg = self.map_operand(imm);
+        let src_reg = self.in_register(out, src, "$t8");
+        if let Some(control) = Self::immediate(imm) {
+            let start = control & 0xff;
+            let length = ((control >> 8) & 0xff).min(32 - start.min(32));
+            if length == 0 {
+                emit!(out, "    move {}, $zero\n", dst_reg);
+                return;
+            }
+            emit!(
+                out,
+                "    ext {}, {}, {}, {}\n",
+                dst_reg,
+                src_reg,
+                start,
+                length
+            );
+        } else if Self::is_register(&control_reg) {
+            // Shift the field to the top, then back down by 32 - length.
+            emit!(
+                out,
+                "    srlv $t8, {s}, {c}\n    srl {d}, {c}, 8\n    andi {d}, {d}, 255\n    negu {d}, {d}\n    sllv $t8, $t8, {d}\n    srlv {d}, $t8, {d}\n",
+                s = src_reg,
+                c = control_reg,
+                d = dst_reg
+            );
+        } else {
+            // A symbol the assembler knows the value of.
+            emit!(
+                out,
+                "    ext {}, {}, ({c}) & 255, (({c}) >> 8) & 255\n",
+                dst_reg,
+                src_reg,
+                c = control_reg
+            );
+        }
+    }
+
+    fn generate_bsf(&self, out: &mut String, dst: &str, src: &str) {
+        // The lowest set bit alone is its own highest.
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.in_register(out, src, "$t8");
+        emit!(
+            out,
+            "    negu $v1, {s}\n    and $v1, $v1, {s}\n    clz $v1, $v1\n",
+            s = src_reg
+        );
+        Self::from_top(out, &dst_reg, "$v1");
+    }
+
+    fn generate_bsr(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.in_register(out, src, "$t8");
+        emit!(out, "    clz $v1, {}\n", src_reg);
+        Self::from_top(out, &dst_reg, "$v1");
+    }
+
+    fn generate_bt(&self, _out: &mut String, _dst: &str, _bit: &str) {}
+
+    fn generate_btr(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "and", true, dst, bit);
+    }
+
+    fn generate_bts(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "or", false, dst, bit);
+    }
+
+    fn generate_btc(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "xor", false, dst, bit);
+    }
+
+    fn generate_set_eq(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_set_ne(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_set_lt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_set_le(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_set_gt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_set_ge(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_set_ov(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_set_no(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_set_s(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_set_ns(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_set_p(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_set_np(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_set_a(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_set_ae(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_set_b(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_set_be(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_cmps(&self, _out: &mut String, _src1: &str, _src2: &str) {}
+
+    fn generate_scas(&self, _out: &mut String, _src: &str, _val: &str) {}
+
+    fn generate_stos(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_store(out, dst, src);
+    }
+
+    fn generate_lods(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_load(out, dst, src);
+    }
+
+    fn generate_movs(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_load(out, "$t8", src);
+        self.generate_store(out, dst, "$t8");
+    }
+
+    fn generate_cbw(&self, out: &mut String, dst: &str) {
+        self.unary(out, "seb", dst);
+    }
+
+    fn generate_cwd(&self, out: &mut String, dst: &str) {
+        self.unary(out, "seh", dst);
+    }
+
+    fn generate_cdq(&self, _out: &mut String, _dst: &str) {}
+
+    fn generate_cqo(&self, out: &mut String, dst: &str) {
+        // The high half of the 64-bit sign extension: all sign bits.
+        let dst_reg = self.map_operand(dst);
+        emit!(out, "    sra {}, {}, 31\n", dst_reg, dst_reg);
+    }
+
+    fn generate_cwde(&self, out: &mut String, dst: &str) {
+        self.unary(out, "seh", dst);
+    }
+
+    fn generate_cdqe(&self, _out: &mut String, _dst: &str) {}
+
+    fn generate_jo(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_jno(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_js(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_jns(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_jp(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_jnp(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_ja(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_jae(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_jb(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_jbe(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_loop_eq(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_loop_ne(&self, _out: &mut String, _label: &str) {
+        unreachable!("MIPS compares in each reader")
+    }
+
+    fn generate_in(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("    # MIPS has no IN instruction, not supported.\n");
+    }
+
+    fn generate_out(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("    # MIPS has no OUT instruction, not supported.\n");
+    }
+
+    fn generate_ins(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("    # MIPS has no INS instruction, not supported.\n");
+    }
+
+    fn generate_outs(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("    # MIPS has no OUTS instruction, not supported.\n");
+    }
+
+    fn generate_cpuid(&self, out: &mut String) {
+        out.push_str("    # MIPS does not have CPUID\n");
+    }
+
+    fn generate_lfence(&self, out: &mut String) {
+        out.push_str("    sync\n");
+    }
+
+    fn generate_sfence(&self, out: &mut String) {
+        out.push_str("    sync\n");
+    }
+
+    fn generate_mfence(&self, out: &mut String) {
+        out.push_str("    sync\n");
+    }
+
+    fn generate_prefetch(&self, out: &mut String, addr: &str) {
+        // Hint 0 prefetches for a load.
+        let (base, displacement) = self.locate(out, addr);
+        emit!(out, "    pref 0, {}({})\n", displacement, base);
+    }
+
+    fn generate_clflush(&self, out: &mut String, _addr: &str) {
+        out.push_str("    # MIPS32 has no user-mode data cache flush\n");
+    }
+
+    fn generate_clwb(&self, out: &mut String, _addr: &str) {
+        out.push_str("    # MIPS32 has no user-mode data cache write-back\n");
+    }
+
+    fn generate_global(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".globl {}\n", symbol);
+    }
+
+    fn generate_extern(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".extern {}\n", symbol);
+    }
+
+    fn generate_align(&self, out: &mut String, n: &str) {
+        emit!(out, ".p2align {}\n", n);
+    }
+
+    fn generate_data_byte(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .byte {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_word(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .half {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_dword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .word {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_qword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .quad {}\n", name, values.join(", "));
+    }
+
+    fn generate_reserve_byte(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip {}\n", name, count);
+    }
+
+    fn generate_reserve_word(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 2 * ({})\n", name, count);
+    }
+
+    fn generate_reserve_dword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 4 * ({})\n", name, count);
+    }
+
+    fn generate_reserve_qword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 8 * ({})\n", name, count);
+    }
+
+    fn generate_equ(&self, out: &mut String, name: &str, value: &str) {
+        emit!(out, ".set {}, {}\n", name, value);
+    }
+
+    fn generate_section(&self, out: &mut String, section: &Section) {
+        match section {
+            Section::Text => out.push_str(".section .text\n"),
+            Section::Data => out.push_str(".section .data\n"),
+            Section::Bss => out.push_str(".section .bss\n"),
+            Section::Rodata => out.push_str(".section .rodata\n"),
+            Section::Custom(s) => emit!(out, ".section {}\n", s),
+        }
+    }
+
+    fn generate_label(&self, out: &mut String, name: &str) {
+        emit!(out, "{}:\n", name);
+    }
+
+    fn generate_function_label(&self, out: &mut String, symbol: &str) {
+        // Functions point $gp at the GOT from their own address, which `bal`
+        // leaves in $ra; the kernel does not pass it in $t9 to the entry
+        // point as callers do.
+        emit!(
+            out,
+            "{}:\n    move $t8, $ra\n    bal 1f\n    nop\n1:\n    lui $gp, %hi(_gp_disp)\n    addiu $gp, $gp, %lo(_gp_disp)\n    addu $gp, $gp, $ra\n    move $ra, $t8\n",
+            symbol
+        );
+    }
+
+    fn get_register_width(&self) -> u32 {
+        32
+    }
+
+    fn get_reserved_registers(&self) -> Vec<String> {
+        [
+            "$zero", "$at", "$v0", "$v1", "$t8", "$t9", "$k0", "$k1", "$gp", "$sp", "$fp", "$ra",
+        ]
+        .iter()
+        .map(|r| r.to_string())
+        .collect()
+    }
+
+    fn get_callee_saved_registers(&self) -> Vec<String> {
+        [
+            "$s0", "$s1", "$s2", "$s3", "$s4", "$s5", "$s6", "$s7", "$fp",
+        ]
+        .iter()
+        .map(|r| r.to_string())
+        .collect()
+    }
+
+    fn get_push_size(&self) -> u32 {
+        8
+    }
+
+    fn get_clobbered_registers(&self, instruction: &Instruction) -> Vec<String> {
+        use Instruction::*;
+        let registers: &[&str] = match instruction {
+            Syscall(_) => &[
+                "$at", "$v0", "$v1", "$a0", "$a3", "$t0", "$t1", "$t2", "$t3", "$t4", "$t5", "$t6",
+                "$t7", "$t8", "$t9",
+            ],
+            Call(_) => &[
+                "$at", "$v0", "$v1", "$a0", "$a1", "$a2", "$a3", "$t0", "$t1", "$t2", "$t3", "$t4",
+                "$t5", "$t6", "$t7", "$t8", "$t9", "$ra",
+            ],
+            _ => &[],
+        };
+        registers.iter().map(|r| r.to_string()).collect()
+    }
+
+    fn set_position_independent(&mut self, pic: Pic) -> Result<(), String> {
+        self.pic = Some(pic);
+        Ok(())
+    }
+
+    fn requires_position_independence(&self) -> bool {
+        true
+    }
+
+    fn has_condition_flags(&self) -> bool {
+        false
+    }
+
+    fn generate_compare_branch(&self, out: &mut String, comparison: &Comparison, label: &str) {
+        let branch = self.test(out, comparison);
+        Self::delayed(out, &format!("{}, {}", branch, label));
+    }
+
+    fn generate_compare_set(&self, out: &mut String, comparison: &Comparison, dst: &str) {
+        let (a, b) = self.compared(out, comparison);
+        let dst_reg = self.map_operand(dst);
+        let unsigned = matches!(
+            comparison.condition,
+            Condition::B | Condition::Be | Condition::A | Condition::Ae
+        );
+        // The difference, for conditions on it rather than on the operands.
+        let difference = |out: &mut String, op: &str| {
+            if b == "$zero" {
+                a.clone()
+            } else {
+                emit!(out, "    {} {}, {}, {}\n", op, dst_reg, a, b);
+                dst_reg.clone()
+            }
+        };
+        let inverted = match comparison.condition {
+            Condition::Eq => {
+                let difference = difference(out, "xor");
+                emit!(out, "    sltiu {}, {}, 1\n", dst_reg, difference);
+                false
+            }
+            Condition::Ne => {
+                let difference = difference(out, "xor");
+                emit!(out, "    sltu {}, $zero, {}\n", dst_reg, difference);
+                false
+            }
+            Condition::S | Condition::Ns => {
+                let difference = difference(out, "subu");
+                emit!(out, "    srl {}, {}, 31\n", dst_reg, difference);
+                comparison.condition == Condition::Ns
+            }
+            Condition::Lt | Condition::B | Condition::Ge | Condition::Ae => {
+                Self::less(out, unsigned, &dst_reg, &a, &b);
+                matches!(comparison.condition, Condition::Ge | Condition::Ae)
+            }
+            Condition::Gt | Condition::A | Condition::Le | Condition::Be => {
+                let greater = matches!(comparison.condition, Condition::Gt | Condition::A);
+                match b.parse::<i64>() {
+                    Ok(value) => {
+                        Self::less(out, unsigned, &dst_reg, &a, &(value + 1).to_string());
+                        greater
+                    }
+                    Err(_) => {
+                        Self::less(out, unsigned, &dst_reg, &b, &a);
+                        !greater
+                    }
+                }
+            }
+        };
+        // The rest are the opposite of a condition MIPS sets directly.
+        if inverted {
+            emit!(out, "    xori {}, {}, 1\n", dst_reg, dst_reg);
+        }
+    }
+
+    fn generate_compare_cmov(
+        &self,
+        out: &mut String,
+        comparison: &Comparison,
+        dst: &str,
+        src: &str,
+    ) {
+        // Branch around the move when the condition fails.
+        let skip = Comparison {
+            condition: comparison.condition.negate(),
+            ..comparison.clone()
+        };
+        self.generate_compare_branch(out, &skip, "1f");
+        self.generate_mov(out, dst, src);
+        out.push_str("1:\n");
+    }
+}
@@ -1,20 +1,42 @@
-use std::{collections::HashMap, process};
+use std::{
+    collections::{HashMap, HashSet},
+    process,
+};
 
 use crate::{
     arch::{
         amd32::AMD32CodeGen, amd64::AMD64CodeGen, arm32::ARM32CodeGen, arm64::ARM64CodeGen,
-        risc_v::RISCVCodeGen,
+        avr::AVRCodeGen, k68::K68CodeGen, mips32::MIPS32CodeGen, msp430::MSP430CodeGen,
+        powerpc64::PowerPC64CodeGen, risc_v::RISCVCodeGen, sh::SHCodeGen,
+        sparc64::SPARC64CodeGen, xtensa::XtensaCodeGen, z80::Z80CodeGen,
     },
-    core::{Section, TargetTriple},
-    platform::Platform,
+    core::{Instruction, Section, TargetTriple, opt},
+    platform::{Format, Platform},
 };
 
+/// `write!` to the `String` a `generate_*` method appends its code to, which
+/// cannot fail.
+macro_rules! emit {
+    ($out:expr, $($arg:tt)*) => {{
+        use std::fmt::Write as _;
+        let _ = write!($out, $($arg)*);
+    }};
+}
+
 pub mod amd32;
 pub mod amd64;
 pub mod arm32;
 pub mod arm64;
+pub mod avr;
+pub mod k68;
+pub mod mips32;
+pub mod msp430;
 pub mod powerpc64;
 pub mod risc_v;
+pub mod sh;
+pub mod sparc64;
+pub mod xtensa;
+pub mod z80;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Architecture {
@@ -47,15 +69,25 @@ pub enum Architecture {
     /// - Bare-metal/embedded
     RISCV,
 
-    /// Aliases: ppc64, powerpc64, ppc64le
+    /// Aliases: ppc64, powerpc64
+    ///
+    /// Big-endian, with the ELFv1 ABI and its function descriptors.
     ///
     /// Supported on:
     /// - Linux (>=2.6, especially on IBM POWER servers)
     /// - AIX
     /// - BSDs (some support)
-    /// - macOS (<=10.5, legacy only, 32-bit/64-bit split)
     PowerPC64,
 
+    /// Aliases: ppc64le, powerpc64le
+    ///
+    /// Little-endian, with the ELFv2 ABI.
+    ///
+    /// Supported on:
+    /// - Linux (>=3.13, POWER8 and later)
+    /// - BSDs (FreeBSD >=13)
+    PowerPC64LE,
+
     /// Aliases: x86, i386, ia-32, 32-bit x86
     ///
     /// Supported on:
@@ -81,6 +113,8 @@ pub enum Architecture {
     /// - Linux (>=2.4, especially in routers, embedded devices)
     /// - BSDs (partial)
     /// - Some proprietary embedded OSes
+    ///
+    /// The output assembles for either byte order.
     MIPS32,
 
     /// Aliases: sparc64, ultraSPARC
@@ -184,6 +218,305 @@ pub enum Architecture {
     Z80,
 }
 
+/// A condition a flag reader tests, as x86 defines it after `cmp a, b`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// Unsigned below.
+    B,
+    Be,
+    /// Unsigned above.
+    A,
+    Ae,
+    /// Sign bit of `a - b` set.
+    S,
+    Ns,
+}
+
+impl Condition {
+    pub fn negate(self) -> Condition {
+        match self {
+            Condition::Eq => Condition::Ne,
+            Condition::Ne => Condition::Eq,
+            Condition::Lt => Condition::Ge,
+            Condition::Le => Condition::Gt,
+            Condition::Gt => Condition::Le,
+            Condition::Ge => Condition::Lt,
+            Condition::B => Condition::Ae,
+            Condition::Be => Condition::A,
+            Condition::A => Condition::Be,
+            Condition::Ae => Condition::B,
+            Condition::S => Condition::Ns,
+            Condition::Ns => Condition::S,
+        }
+    }
+}
+
+/// What a flag reader tests on a target without condition flags: `a`
+/// against `b` as `cmp a, b` would, or what `tested` derives from them
+/// against zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comparison {
+    pub condition: Condition,
+    pub a: String,
+    pub b: String,
+    pub tested: Tested,
+}
+
+/// What a `Comparison` holds against its condition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tested {
+    /// `a` against `b`.
+    Operands,
+    /// `a & b` against zero, as `test a, b` would.
+    Mask,
+    /// Bit `b` of `a`, a register, against zero: `ne` when `bt a, b` would
+    /// set the carry.
+    Bit,
+    /// A word whose sign is set when `add` overflowed, given the sum `a` and
+    /// the addend `b`: `s` when the overflow flag is set.
+    Sum,
+    /// Likewise for the difference `a` that `sub` leaves, given `b`.
+    Difference,
+    /// The parity of the low byte of `a & b` against zero: `eq` when the
+    /// byte has an even number of bits set and the parity flag with it.
+    Parity,
+    /// The bit the last `rcl` or `rcr` rotated out, which the backend keeps
+    /// aside, against zero; `a` and `b` are unused.
+    Carry,
+}
+
+/// How a backend's calls and `enter` lay out a function's frame, for unwind
+/// information.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameLayout {
+    /// Bytes a call leaves above the stack pointer on entry: the return
+    /// address on x86, nothing where it goes to a link register.
+    pub return_address: i64,
+    /// The physical register `enter` points at the frame record and `leave`
+    /// restores the stack pointer from.
+    pub frame_pointer: &'static str,
+    /// Physical registers `enter` saves in the frame record, from the lowest
+    /// address up.
+    pub record: &'static [&'static str],
+    /// Bytes each register in the record takes.
+    pub slot: i64,
+}
+
+/// What position-independent code needs to know about a program's symbols.
+#[derive(Debug, Clone)]
+pub struct Pic {
+    pub format: Format,
+    /// Names that stand for addresses: labels, data and imports. Other
+    /// names, such as `equ` constants, are plain numbers.
+    pub addresses: HashSet<String>,
+    /// Addresses another module may provide, which code reaches through the
+    /// GOT: imports, and on ELF the program's own globals, which a shared
+    /// library can have preempted. COFF resolves everything at link time.
+    pub preemptible: HashSet<String>,
+    /// Names Mach-O spells with a leading underscore, as the platform
+    /// defines them: data and imports.
+    pub underscored: HashSet<String>,
+}
+
+impl Pic {
+    /// The symbols of `instructions`, as the object `format` sees them.
+    pub fn new(format: Format, instructions: &[Instruction]) -> Self {
+        let mut addresses = HashSet::new();
+        let mut preemptible = HashSet::new();
+        let mut underscored = HashSet::new();
+        for instruction in instructions {
+            match instruction {
+                Instruction::Label(name) => {
+                    addresses.insert(name.clone());
+                }
+                Instruction::DataByte(name, _)
+                | Instruction::DataWord(name, _)
+                | Instruction::DataDword(name, _)
+                | Instruction::DataQword(name, _)
+                | Instruction::ReserveByte(name, _)
+                | Instruction::ReserveWord(name, _)
+                | Instruction::ReserveDword(name, _)
+                | Instruction::ReserveQword(name, _) => {
+                    addresses.insert(name.clone());
+                    if format == Format::MachO {
+                        underscored.insert(name.clone());
+                    }
+                }
+                Instruction::Extern(name) => {
+                    addresses.insert(name.clone());
+                    if format == Format::MachO {
+                        underscored.insert(name.clone());
+                    }
+                    if format != Format::COFF {
+                        preemptible.insert(name.clone());
+                    }
+                }
+                Instruction::Global(name) if format == Format::ELF => {
+                    preemptible.insert(name.clone());
+                }
+                _ => {}
+            }
+        }
+        Pic {
+            format,
+            addresses,
+            preemptible,
+            underscored,
+        }
+    }
+
+    /// `operand` as an address and a constant offset from it, for `sym`,
+    /// `sym+n` and `sym-n`, with or without brackets.
+    pub fn address<'a>(&self, operand: &'a str) -> Option<(&'a str, i64)> {
+        let operand = operand.trim();
+        let inner = operand
+            .strip_prefix('[')
+            .and_then(|inner| inner.strip_suffix(']'))
+            .unwrap_or(operand)
+            .trim();
+        let (symbol, offset) = match inner.find(['+', '-']) {
+            Some(at) => {
+                let offset = opt::parse_immediate(inner[at + 1..].trim())?;
+                let offset = if inner[at..].starts_with('-') {
+                    offset.wrapping_neg()
+                } else {
+                    offset
+                };
+                (inner[..at].trim(), offset)
+            }
+            None => (inner, 0),
+        };
+        self.addresses.contains(symbol).then_some((symbol, offset))
+    }
+
+    /// Whether code must reach `symbol` through the GOT.
+    pub fn is_preemptible(&self, symbol: &str) -> bool {
+        self.preemptible.contains(symbol)
+    }
+
+    /// `symbol` as the object file names it.
+    pub fn name(&self, symbol: &str) -> String {
+        if self.underscored.contains(symbol) {
+            format!("_{}", symbol)
+        } else {
+            symbol.to_string()
+        }
+    }
+
+    /// `symbol + offset` as an assembler expression.
+    pub fn expression(symbol: &str, offset: i64) -> String {
+        match offset {
+            0 => symbol.to_string(),
+            n if n < 0 => format!("{} - {}", symbol, n.unsigned_abs()),
+            n => format!("{} + {}", symbol, n),
+        }
+    }
+}
+
+/// A memory operand taken apart: `base + index * scale + displacement`.
+#[derive(Debug, Default)]
+pub struct Address {
+    pub base: Option<String>,
+    /// The index register and the power of two it is scaled by.
+    pub index: Option<(String, u32)>,
+    pub displacement: i64,
+    /// Terms the assembler knows the value of: data, labels and `equ`
+    /// constants.
+    pub symbolic: String,
+}
+
+/// Takes the memory operand `operand` apart, brackets optional, for the
+/// backends that build addresses themselves. `map` gives the register a
+/// name stands for and `is_register` whether that can be a base or index;
+/// the first register is the base, and an index scaled by anything but a
+/// power of two is an error.
+pub fn split_address(
+    operand: &str,
+    map: impl Fn(&str) -> String,
+    is_register: impl Fn(&str) -> bool,
+) -> Result<Address, String> {
+    let immediate = |text: &str| {
+        opt::parse_immediate(text)
+            .or_else(|| text.trim().parse::<u64>().ok().map(|value| value as i64))
+    };
+    let operand = operand.trim();
+    let inner = operand
+        .strip_prefix('[')
+        .and_then(|inner| inner.strip_suffix(']'))
+        .unwrap_or(operand)
+        .trim();
+    let mut terms = Vec::new();
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        if i > 0 && (c == '+' || c == '-') {
+            terms.push(&inner[start..i]);
+            start = i;
+        }
+    }
+    terms.push(&inner[start..]);
+
+    let mut address = Address::default();
+    for term in terms {
+        let term = term.trim();
+        let (negative, text) = match term.strip_prefix('-') {
+            Some(text) => (true, text.trim()),
+            None => (false, term.strip_prefix('+').unwrap_or(term).trim()),
+        };
+        if let Some(value) = immediate(text) {
+            address.displacement += if negative { -value } else { value };
+            continue;
+        }
+        if let Some((register, scale)) = text.split_once('*') {
+            let (register, scale) = match immediate(scale) {
+                Some(scale) => (register, scale),
+                None => (scale, immediate(register).unwrap_or(1)),
+            };
+            let register = map(register.trim());
+            if is_register(&register) {
+                if scale <= 0 || scale & (scale - 1) != 0 {
+                    return Err(format!(
+                        "{} scales an index by {}, which is not a power of two",
+                        operand, scale
+                    ));
+                }
+                address.index = Some((register, scale.trailing_zeros()));
+                continue;
+            }
+        }
+        let mapped = map(text);
+        if is_register(&mapped) && !negative {
+            if address.base.is_none() {
+                address.base = Some(mapped);
+            } else {
+                address.index = Some((mapped, 0));
+            }
+            continue;
+        }
+        if !address.symbolic.is_empty() || negative {
+            address
+                .symbolic
+                .push_str(if negative { " - " } else { " + " });
+        }
+        address.symbolic.push_str(text);
+    }
+    Ok(address)
+}
+
+/// The address `split_address` found, or an `.error` in `out` for the
+/// operand it rejected.
+pub fn address_or_error(out: &mut String, address: Result<Address, String>) -> Address {
+    address.unwrap_or_else(|error| {
+        emit!(out, "    .error \"{}\"\n", error);
+        Address::default()
+    })
+}
+
 pub trait ArchCodeGen {
     fn get_register_map(&self) -> HashMap<String, String>;
     fn get_syntax_header(&self) -> String;
@@ -191,204 +524,471 @@ pub trait ArchCodeGen {
     //
     // Data Movement
     //
-    fn generate_mov(&self, dst: &str, src: &str) -> String;
-    fn generate_lea(&self, dst: &str, src: &str) -> String;
-    fn generate_load(&self, dst: &str, src: &str) -> String;
-    fn generate_store(&self, dst: &str, src: &str) -> String;
+    fn generate_mov(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_lea(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_load(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_store(&self, out: &mut String, dst: &str, src: &str);
 
     //
     // Conditional Moves
     //
-    fn generate_cmov_eq(&self, dst: &str, src: &str) -> String;
-    fn generate_cmov_ne(&self, dst: &str, src: &str) -> String;
-    fn generate_cmov_lt(&self, dst: &str, src: &str) -> String;
-    fn generate_cmov_le(&self, dst: &str, src: &str) -> String;
-    fn generate_cmov_gt(&self, dst: &str, src: &str) -> String;
-    fn generate_cmov_ge(&self, dst: &str, src: &str) -> String;
-    fn generate_cmov_ov(&self, dst: &str, src: &str) -> String;
-    fn generate_cmov_no(&self, dst: &str, src: &str) -> String;
-    fn generate_cmov_s(&self, dst: &str, src: &str) -> String;
-    fn generate_cmov_ns(&self, dst: &str, src: &str) -> String;
-    fn generate_cmov_p(&self, dst: &str, src: &str) -> String;
-    fn generate_cmov_np(&self, dst: &str, src: &str) -> String;
-    fn generate_cmov_a(&self, dst: &str, src: &str) -> String;
-    fn generate_cmov_ae(&self, dst: &str, src: &str) -> String;
-    fn generate_cmov_b(&self, dst: &str, src: &str) -> String;
-    fn generate_cmov_be(&self, dst: &str, src: &str) -> String;
+    fn generate_cmov_eq(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_cmov_ne(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_cmov_lt(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_cmov_le(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_cmov_gt(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_cmov_ge(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_cmov_ov(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_cmov_no(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_cmov_s(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_cmov_ns(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_cmov_p(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_cmov_np(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_cmov_a(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_cmov_ae(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_cmov_b(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_cmov_be(&self, out: &mut String, dst: &str, src: &str);
 
     //
     // Stack Operations
     //
-    fn generate_push(&self, src: &str) -> String;
-    fn generate_pop(&self, dst: &str) -> String;
-    fn generate_pusha(&self) -> String;
-    fn generate_popa(&self) -> String;
-    fn generate_enter(&self, frame_size: &str, nesting_level: &str) -> String;
-    fn generate_leave(&self) -> String;
+    fn generate_push(&self, out: &mut String, src: &str);
+    fn generate_pop(&self, out: &mut String, dst: &str);
+    fn generate_pusha(&self, out: &mut String);
+    fn generate_popa(&self, out: &mut String);
+    fn generate_enter(&self, out: &mut String, frame_size: &str, nesting_level: &str);
+    fn generate_leave(&self, out: &mut String);
 
     //
     // Arithmetic Operations
     //
-    fn generate_add(&self, dst: &str, src: &str) -> String;
-    fn generate_sub(&self, dst: &str, src: &str) -> String;
-    fn generate_mul(&self, dst: &str, src: &str) -> String;
-    fn generate_imul(&self, dst: &str, src: &str) -> String;
-    fn generate_div(&self, dst: &str, src: &str) -> String;
-    fn generate_idiv(&self, dst: &str, src: &str) -> String;
-    fn generate_mod(&self, dst: &str, src: &str) -> String;
-    fn generate_inc(&self, dst: &str) -> String;
-    fn generate_dec(&self, dst: &str) -> String;
-    fn generate_neg(&self, dst: &str) -> String;
+    fn generate_add(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_sub(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_mul(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_imul(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_div(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_idiv(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_mod(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_mulh(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_inc(&self, out: &mut String, dst: &str);
+    fn generate_dec(&self, out: &mut String, dst: &str);
+    fn generate_neg(&self, out: &mut String, dst: &str);
 
     //
     // Logical & Bitwise Operations
     //
-    fn generate_and(&self, dst: &str, src: &str) -> String;
-    fn generate_or(&self, dst: &str, src: &str) -> String;
-    fn generate_xor(&self, dst: &str, src: &str) -> String;
-    fn generate_not(&self, dst: &str) -> String;
-    fn generate_andn(&self, dst: &str, src: &str) -> String;
-    fn generate_shl(&self, dst: &str, src: &str) -> String;
-    fn generate_shr(&self, dst: &str, src: &str) -> String;
-    fn generate_sal(&self, dst: &str, src: &str) -> String;
-    fn generate_sar(&self, dst: &str, src: &str) -> String;
-    fn generate_rol(&self, dst: &str, src: &str) -> String;
-    fn generate_ror(&self, dst: &str, src: &str) -> String;
-    fn generate_rcl(&self, dst: &str, src: &str) -> String;
-    fn generate_rcr(&self, dst: &str, src: &str) -> String;
-    fn generate_bextr(&self, dst: &str, src: &str, imm: &str) -> String;
-    fn generate_bsf(&self, dst: &str, src: &str) -> String;
-    fn generate_bsr(&self, dst: &str, src: &str) -> String;
+    fn generate_and(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_or(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_xor(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_not(&self, out: &mut String, dst: &str);
+    fn generate_andn(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_shl(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_shr(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_sal(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_sar(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_rol(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_ror(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_rcl(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_rcr(&self, out: &mut String, dst: &str, src: &str);
+    /// The field of `src` that the control `imm` selects, into `dst`: as on
+    /// x86, bits 0-7 of the control are its start and bits 8-15 its length.
+    fn generate_bextr(&self, out: &mut String, dst: &str, src: &str, imm: &str);
+    fn generate_bsf(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_bsr(&self, out: &mut String, dst: &str, src: &str);
 
     //
     // Comparison & Conditional Sets
     //
-    fn generate_cmp(&self, op1: &str, op2: &str) -> String;
-    fn generate_test(&self, op1: &str, op2: &str) -> String;
-    fn generate_bt(&self, dst: &str, bit: &str) -> String;
-    fn generate_btr(&self, dst: &str, bit: &str) -> String;
-    fn generate_bts(&self, dst: &str, bit: &str) -> String;
-    fn generate_btc(&self, dst: &str, bit: &str) -> String;
-    fn generate_set_eq(&self, dst: &str) -> String;
-    fn generate_set_ne(&self, dst: &str) -> String;
-    fn generate_set_lt(&self, dst: &str) -> String;
-    fn generate_set_le(&self, dst: &str) -> String;
-    fn generate_set_gt(&self, dst: &str) -> String;
-    fn generate_set_ge(&self, dst: &str) -> String;
-    fn generate_set_ov(&self, dst: &str) -> String;
-    fn generate_set_no(&self, dst: &str) -> String;
-    fn generate_set_s(&self, dst: &str) -> String;
-    fn generate_set_ns(&self, dst: &str) -> String;
-    fn generate_set_p(&self, dst: &str) -> String;
-    fn generate_set_np(&self, dst: &str) -> String;
-    fn generate_set_a(&self, dst: &str) -> String;
-    fn generate_set_ae(&self, dst: &str) -> String;
-    fn generate_set_b(&self, dst: &str) -> String;
-    fn generate_set_be(&self, dst: &str) -> String;
+    fn generate_cmp(&self, out: &mut String, op1: &str, op2: &str);
+    fn generate_test(&self, out: &mut String, op1: &str, op2: &str);
+    /// `bt` leaves the bit only in the carry, so targets without flags emit
+    /// nothing for it: `opt::flags` tests the bit in each reader.
+    fn generate_bt(&self, out: &mut String, dst: &str, bit: &str);
+    fn generate_btr(&self, out: &mut String, dst: &str, bit: &str);
+    fn generate_bts(&self, out: &mut String, dst: &str, bit: &str);
+    fn generate_btc(&self, out: &mut String, dst: &str, bit: &str);
+    fn generate_set_eq(&self, out: &mut String, dst: &str);
+    fn generate_set_ne(&self, out: &mut String, dst: &str);
+    fn generate_set_lt(&self, out: &mut String, dst: &str);
+    fn generate_set_le(&self, out: &mut String, dst: &str);
+    fn generate_set_gt(&self, out: &mut String, dst: &str);
+    fn generate_set_ge(&self, out: &mut String, dst: &str);
+    fn generate_set_ov(&self, out: &mut String, dst: &str);
+    fn generate_set_no(&self, out: &mut String, dst: &str);
+    fn generate_set_s(&self, out: &mut String, dst: &str);
+    fn generate_set_ns(&self, out: &mut String, dst: &str);
+    fn generate_set_p(&self, out: &mut String, dst: &str);
+    fn generate_set_np(&self, out: &mut String, dst: &str);
+    fn generate_set_a(&self, out: &mut String, dst: &str);
+    fn generate_set_ae(&self, out: &mut String, dst: &str);
+    fn generate_set_b(&self, out: &mut String, dst: &str);
+    fn generate_set_be(&self, out: &mut String, dst: &str);
 
     //
     // String Operations
     //
-    fn generate_cmps(&self, src1: &str, src2: &str) -> String;
-    fn generate_scas(&self, src: &str, val: &str) -> String;
-    fn generate_stos(&self, dst: &str, src: &str) -> String;
-    fn generate_lods(&self, dst: &str, src: &str) -> String;
-    fn generate_movs(&self, dst: &str, src: &str) -> String;
+    /// `cmps` and `scas` leave their result only in the flags, so targets
+    /// without flags emit nothing for them: `opt::flags` rejects reading it.
+    fn generate_cmps(&self, out: &mut String, src1: &str, src2: &str);
+    fn generate_scas(&self, out: &mut String, src: &str, val: &str);
+    fn generate_stos(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_lods(&self, out: &mut String, dst: &str, src: &str);
+    fn generate_movs(&self, out: &mut String, dst: &str, src: &str);
 
     //
     // Data Conversion
     //
-    fn generate_cbw(&self, dst: &str) -> String;
-    fn generate_cwd(&self, dst: &str) -> String;
-    fn generate_cdq(&self, dst: &str) -> String;
-    fn generate_cqo(&self, dst: &str) -> String;
-    fn generate_cwde(&self, dst: &str) -> String;
-    fn generate_cdqe(&self, dst: &str) -> String;
+    fn generate_cbw(&self, out: &mut String, dst: &str);
+    fn generate_cwd(&self, out: &mut String, dst: &str);
+    /// Sign-extends the low 32 bits of `dst`, as does `generate_cdqe`; both
+    /// emit nothing where registers are 32 bits wide.
+    fn generate_cdq(&self, out: &mut String, dst: &str);
+    fn generate_cqo(&self, out: &mut String, dst: &str);
+    fn generate_cwde(&self, out: &mut String, dst: &str);
+    fn generate_cdqe(&self, out: &mut String, dst: &str);
 
     //
     // Control Flow
     //
-    fn generate_jmp(&self, label: &str) -> String;
-    fn generate_je(&self, label: &str) -> String;
-    fn generate_jne(&self, label: &str) -> String;
-    fn generate_jl(&self, label: &str) -> String;
-    fn generate_jle(&self, label: &str) -> String;
-    fn generate_jg(&self, label: &str) -> String;
-    fn generate_jge(&self, label: &str) -> String;
-    fn generate_jo(&self, label: &str) -> String;
-    fn generate_jno(&self, label: &str) -> String;
-    fn generate_js(&self, label: &str) -> String;
-    fn generate_jns(&self, label: &str) -> String;
-    fn generate_jp(&self, label: &str) -> String;
-    fn generate_jnp(&self, label: &str) -> String;
-    fn generate_ja(&self, label: &str) -> String;
-    fn generate_jae(&self, label: &str) -> String;
-    fn generate_jb(&self, label: &str) -> String;
-    fn generate_jbe(&self, label: &str) -> String;
-    fn generate_loop_eq(&self, label: &str) -> String;
-    fn generate_loop_ne(&self, label: &str) -> String;
-    fn generate_call(&self, func: &str) -> String;
-    fn generate_ret(&self) -> String;
+    fn generate_jmp(&self, out: &mut String, label: &str);
+    fn generate_je(&self, out: &mut String, label: &str);
+    fn generate_jne(&self, out: &mut String, label: &str);
+    fn generate_jl(&self, out: &mut String, label: &str);
+    fn generate_jle(&self, out: &mut String, label: &str);
+    fn generate_jg(&self, out: &mut String, label: &str);
+    fn generate_jge(&self, out: &mut String, label: &str);
+    fn generate_jo(&self, out: &mut String, label: &str);
+    fn generate_jno(&self, out: &mut String, label: &str);
+    fn generate_js(&self, out: &mut String, label: &str);
+    fn generate_jns(&self, out: &mut String, label: &str);
+    fn generate_jp(&self, out: &mut String, label: &str);
+    fn generate_jnp(&self, out: &mut String, label: &str);
+    fn generate_ja(&self, out: &mut String, label: &str);
+    fn generate_jae(&self, out: &mut String, label: &str);
+    fn generate_jb(&self, out: &mut String, label: &str);
+    fn generate_jbe(&self, out: &mut String, label: &str);
+    fn generate_loop_eq(&self, out: &mut String, label: &str);
+    fn generate_loop_ne(&self, out: &mut String, label: &str);
+    fn generate_call(&self, out: &mut String, func: &str);
+    fn generate_ret(&self, out: &mut String);
 
     //
     // I/O Operations
     //
-    fn generate_in(&self, dst: &str, port: &str) -> String;
-    fn generate_out(&self, port: &str, src: &str) -> String;
-    fn generate_ins(&self, dst: &str, port: &str) -> String;
-    fn generate_outs(&self, port: &str, src: &str) -> String;
+    fn generate_in(&self, out: &mut String, dst: &str, port: &str);
+    fn generate_out(&self, out: &mut String, port: &str, src: &str);
+    fn generate_ins(&self, out: &mut String, dst: &str, port: &str);
+    fn generate_outs(&self, out: &mut String, port: &str, src: &str);
 
     //
     // System & CPU Operations
     //
-    fn generate_cpuid(&self) -> String;
-    fn generate_lfence(&self) -> String;
-    fn generate_sfence(&self) -> String;
-    fn generate_mfence(&self) -> String;
-    fn generate_prefetch(&self, addr: &str) -> String;
-    fn generate_clflush(&self, addr: &str) -> String;
-    fn generate_clwb(&self, addr: &str) -> String;
+    fn generate_cpuid(&self, out: &mut String);
+    /// The fences emit nothing on a single in-order core, where memory
+    /// accesses happen in program order.
+    fn generate_lfence(&self, out: &mut String);
+    fn generate_sfence(&self, out: &mut String);
+    fn generate_mfence(&self, out: &mut String);
+    fn generate_prefetch(&self, out: &mut String, addr: &str);
+    fn generate_clflush(&self, out: &mut String, addr: &str);
+    fn generate_clwb(&self, out: &mut String, addr: &str);
 
     //
     // System Calls
     //
-    fn generate_syscall(&self, name: &str) -> String;
+    fn generate_syscall(&self, out: &mut String, name: &str);
 
     //
     // Directives
     //
-    fn generate_global(&self, symbol: &str) -> String;
-    fn generate_extern(&self, symbol: &str) -> String;
-    fn generate_align(&self, n: &str) -> String;
+    fn generate_global(&self, out: &mut String, symbol: &str);
+    fn generate_extern(&self, out: &mut String, symbol: &str);
+    fn generate_align(&self, out: &mut String, n: &str);
 
     //
     // Data Definition
     //
-    fn generate_data_byte(&self, name: &str, values: &[String]) -> String;
-    fn generate_data_word(&self, name: &str, values: &[String]) -> String;
-    fn generate_data_dword(&self, name: &str, values: &[String]) -> String;
-    fn generate_data_qword(&self, name: &str, values: &[String]) -> String;
+    fn generate_data_byte(&self, out: &mut String, name: &str, values: &[String]);
+    fn generate_data_word(&self, out: &mut String, name: &str, values: &[String]);
+    fn generate_data_dword(&self, out: &mut String, name: &str, values: &[String]);
+    fn generate_data_qword(&self, out: &mut String, name: &str, values: &[String]);
 
     //
     // Memory Reservation
     //
-    fn generate_reserve_byte(&self, name: &str, count: &str) -> String;
-    fn generate_reserve_word(&self, name: &str, count: &str) -> String;
-    fn generate_reserve_dword(&self, name: &str, count: &str) -> String;
-    fn generate_reserve_qword(&self, name: &str, count: &str) -> String;
+    fn generate_reserve_byte(&self, out: &mut String, name: &str, count: &str);
+    fn generate_reserve_word(&self, out: &mut String, name: &str, count: &str);
+    fn generate_reserve_dword(&self, out: &mut String, name: &str, count: &str);
+    fn generate_reserve_qword(&self, out: &mut String, name: &str, count: &str);
 
     //
     // Constants and Sections
     //
-    fn generate_equ(&self, name: &str, value: &str) -> String;
-    fn generate_section(&self, section: &Section) -> String;
-    fn generate_label(&self, name: &str) -> String;
+    fn generate_equ(&self, out: &mut String, name: &str, value: &str);
+    fn generate_section(&self, out: &mut String, section: &Section);
+    fn generate_label(&self, out: &mut String, name: &str);
+    /// The label that starts the function `symbol`, with anything the ABI
+    /// puts at a function's entry: a descriptor on ELFv1 PowerPC64, the TOC
+    /// pointer setup on ELFv2.
+    fn generate_function_label(&self, out: &mut String, symbol: &str) {
+        emit!(out, "{}:\n", symbol);
+    }
+    /// Whether every call target needs `generate_function_label`, not just
+    /// the labels the program declares functions, as on Xtensa where calls
+    /// only reach aligned code and windowed callees open their window.
+    fn needs_function_labels(&self) -> bool {
+        false
+    }
+    /// The constants the backend has put off placing, as on SH, whose
+    /// `mov.l` reaches its literal pool only up to 1 KiB ahead. Called after
+    /// each instruction, which expanded to `out[from..]`; `barrier` is true
+    /// where execution never falls through, after a jump or return and at the
+    /// end of a section, so the pool needs no branch around it. Writes
+    /// nothing until a pool is due.
+    fn generate_literal_pool(&self, out: &mut String, from: usize, barrier: bool) {
+        let _ = (out, from, barrier);
+    }
 
     //
     // Utility Methods
     //
     fn map_operand(&self, operand: &str) -> String;
     fn map_memory_operand(&self, operand: &str) -> String;
+
+    //
+    // Optimization Support
+    //
+    /// Width of a general-purpose register in bits.
+    fn get_register_width(&self) -> u32;
+    /// Physical registers that optimization passes must never hold values in:
+    /// stack/frame/link registers and the scratch registers this backend's
+    /// own expansions clobber.
+    fn get_reserved_registers(&self) -> Vec<String>;
+    /// Registers a callee must preserve under the target's calling convention.
+    fn get_callee_saved_registers(&self) -> Vec<String>;
+    /// Bytes one `push` moves the stack pointer by.
+    fn get_push_size(&self) -> u32;
+    /// Physical registers the expansion of `instruction` reads or overwrites
+    /// beyond its operands. For `call` these are the registers the calling
+    /// convention lets a callee clobber.
+    fn get_clobbered_registers(&self, instruction: &Instruction) -> Vec<String>;
+    /// Adds the target's static prediction hint to `out[from..]`, the
+    /// expansion of a conditional jump, for a jump that is `likely` taken or
+    /// not. Targets without such hints predict from the layout and leave it
+    /// unchanged.
+    fn generate_branch_hint(&self, out: &mut String, from: usize, likely: bool) {
+        let _ = (out, from, likely);
+    }
+    /// Writes a shorter expansion of `instruction` that may clobber the
+    /// physical registers `dead` reports as holding nothing needed afterwards,
+    /// instead of saving them; false, with nothing written, when there is
+    /// none.
+    fn generate_compact(
+        &self,
+        out: &mut String,
+        instruction: &Instruction,
+        dead: &dyn Fn(&str) -> bool,
+    ) -> bool {
+        let _ = (out, instruction, dead);
+        false
+    }
+
+    //
+    // Unwind Information
+    //
+    /// The frame layout unwind information describes functions with; `None`
+    /// where the backend has no fixed one and functions get no unwind
+    /// information, which `-g` then needs `--no-unwind` to go without.
+    fn get_frame_layout(&self) -> Option<FrameLayout> {
+        None
+    }
+
+    //
+    // Position Independence
+    //
+    /// Switches to position-independent code for a program with the symbols
+    /// `pic` describes: `lea`, `load`, `store` and `mov` of an address reach
+    /// it relative to the program counter, or through the GOT if it is
+    /// preemptible, and calls go through the PLT where the format needs it.
+    /// Optimization only asks about clobbers, so a backend may be switched
+    /// before the symbols are known.
+    fn set_position_independent(&mut self, pic: Pic) -> Result<(), String> {
+        let _ = pic;
+        Err("the architecture does not support position-independent code".to_string())
+    }
+    /// Whether all code for the target is position-independent, with or
+    /// without `-fpic`, as PowerPC64 code reaching data through the TOC and
+    /// MIPS code reaching it through the GOT are.
+    /// Such a backend must reach an address in any operand, not only in the
+    /// `lea`, `load`, `store`, `mov`, jumps and calls other backends do.
+    fn requires_position_independence(&self) -> bool {
+        false
+    }
+    /// Whether the target keeps `.rodata` in a program memory of its own,
+    /// which loads must read with other instructions than data memory, as
+    /// AVR does with `lpm`.
+    fn has_program_memory(&self) -> bool {
+        false
+    }
+    /// Tells a backend with program memory the names the program defines in
+    /// `.rodata`.
+    fn set_program_memory(&mut self, symbols: HashSet<String>) {
+        let _ = symbols;
+    }
+
+    //
+    // Instruction Set Extensions
+    //
+    /// Lets expansions use the target's optional bit-manipulation
+    /// instructions, Zbb on RISC-V, where they emit longer sequences of base
+    /// instructions otherwise.
+    fn set_bit_manipulation(&mut self) -> Result<(), String> {
+        Err("the architecture has no optional bit-manipulation instructions".to_string())
+    }
+    /// Selects the processor model `cpu` names, `68000` or `68020` on m68k,
+    /// which decides whether expansions use its instructions or emit longer
+    /// sequences and library calls.
+    fn set_cpu(&mut self, cpu: &str) -> Result<(), String> {
+        let _ = cpu;
+        Err("the architecture has no processor models to choose from".to_string())
+    }
+    /// Selects the calling convention `abi` names, `windowed` or `call0` on
+    /// Xtensa.
+    fn set_abi(&mut self, abi: &str) -> Result<(), String> {
+        let _ = abi;
+        Err("the architecture has a single calling convention".to_string())
+    }
+    /// Keeps the registers the backend emulates in memory at the RAM address
+    /// `origin`, apart from the program's image, on Z80.
+    fn set_ram(&mut self, origin: &str) -> Result<(), String> {
+        let _ = origin;
+        Err("the architecture keeps its registers in the processor".to_string())
+    }
+
+    //
+    // Register Classes
+    //
+    /// Whether the target splits its registers into classes that take
+    /// different operands, as the data and address registers of m68k do,
+    /// and places the program's registers by how it uses them.
+    fn has_address_registers(&self) -> bool {
+        false
+    }
+    /// Tells such a backend how often the program uses each name as the
+    /// unscaled base of a memory operand.
+    fn set_address_registers(&mut self, uses: HashMap<String, usize>) {
+        let _ = uses;
+    }
+
+    //
+    // Condition Flags
+    //
+    /// Whether `cmp`, `test` and arithmetic leave flags for later `jCC`,
+    /// `setCC` and `cmovCC`. Targets without them compare in each reader
+    /// instead: `opt::flags` pairs every reader with the comparison that
+    /// feeds it, and the `generate_compare_*` methods emit both at once.
+    fn has_condition_flags(&self) -> bool {
+        true
+    }
+    /// Whether the `generate_compare_*` methods take comparisons of this
+    /// kind, `Tested::Carry` standing for `rcl` and `rcr` as well. On targets
+    /// without condition flags, `opt::flags::expand` works the others out
+    /// into a register beforehand.
+    fn compares(&self, tested: Tested) -> bool {
+        matches!(tested, Tested::Operands | Tested::Mask)
+    }
+    /// Jump to `label` if the comparison holds.
+    fn generate_compare_branch(&self, out: &mut String, comparison: &Comparison, label: &str) {
+        self.generate_flags(out, comparison);
+        match comparison.condition {
+            Condition::Eq => self.generate_je(out, label),
+            Condition::Ne => self.generate_jne(out, label),
+            Condition::Lt => self.generate_jl(out, label),
+            Condition::Le => self.generate_jle(out, label),
+            Condition::Gt => self.generate_jg(out, label),
+            Condition::Ge => self.generate_jge(out, label),
+            Condition::B => self.generate_jb(out, label),
+            Condition::Be => self.generate_jbe(out, label),
+            Condition::A => self.generate_ja(out, label),
+            Condition::Ae => self.generate_jae(out, label),
+            Condition::S => self.generate_js(out, label),
+            Condition::Ns => self.generate_jns(out, label),
+        }
+    }
+    /// Set `dst` to 1 if the comparison holds and to 0 otherwise.
+    fn generate_compare_set(&self, out: &mut String, comparison: &Comparison, dst: &str) {
+        self.generate_flags(out, comparison);
+        match comparison.condition {
+            Condition::Eq => self.generate_set_eq(out, dst),
+            Condition::Ne => self.generate_set_ne(out, dst),
+            Condition::Lt => self.generate_set_lt(out, dst),
+            Condition::Le => self.generate_set_le(out, dst),
+            Condition::Gt => self.generate_set_gt(out, dst),
+            Condition::Ge => self.generate_set_ge(out, dst),
+            Condition::B => self.generate_set_b(out, dst),
+            Condition::Be => self.generate_set_be(out, dst),
+            Condition::A => self.generate_set_a(out, dst),
+            Condition::Ae => self.generate_set_ae(out, dst),
+            Condition::S => self.generate_set_s(out, dst),
+            Condition::Ns => self.generate_set_ns(out, dst),
+        }
+    }
+    /// Move `src` into `dst` if the comparison holds.
+    fn generate_compare_cmov(
+        &self,
+        out: &mut String,
+        comparison: &Comparison,
+        dst: &str,
+        src: &str,
+    ) {
+        self.generate_flags(out, comparison);
+        match comparison.condition {
+            Condition::Eq => self.generate_cmov_eq(out, dst, src),
+            Condition::Ne => self.generate_cmov_ne(out, dst, src),
+            Condition::Lt => self.generate_cmov_lt(out, dst, src),
+            Condition::Le => self.generate_cmov_le(out, dst, src),
+            Condition::Gt => self.generate_cmov_gt(out, dst, src),
+            Condition::Ge => self.generate_cmov_ge(out, dst, src),
+            Condition::B => self.generate_cmov_b(out, dst, src),
+            Condition::Be => self.generate_cmov_be(out, dst, src),
+            Condition::A => self.generate_cmov_a(out, dst, src),
+            Condition::Ae => self.generate_cmov_ae(out, dst, src),
+            Condition::S => self.generate_cmov_s(out, dst, src),
+            Condition::Ns => self.generate_cmov_ns(out, dst, src),
+        }
+    }
+    /// `rcl dst, count` through a carry that is set when the comparison
+    /// holds, keeping the bit rotated out for a later `Tested::Carry`.
+    fn generate_compare_rcl(
+        &self,
+        out: &mut String,
+        comparison: &Comparison,
+        dst: &str,
+        count: &str,
+    ) {
+        let _ = (out, comparison, dst, count);
+        unreachable!("targets with condition flags rotate through their own carry")
+    }
+    /// `rcr dst, count`, as `generate_compare_rcl` rotates left.
+    fn generate_compare_rcr(
+        &self,
+        out: &mut String,
+        comparison: &Comparison,
+        dst: &str,
+        count: &str,
+    ) {
+        let _ = (out, comparison, dst, count);
+        unreachable!("targets with condition flags rotate through their own carry")
+    }
+    /// The `cmp` or `test` that sets the flags a comparison reads.
+    fn generate_flags(&self, out: &mut String, comparison: &Comparison) {
+        match comparison.tested {
+            Tested::Operands => self.generate_cmp(out, &comparison.a, &comparison.b),
+            Tested::Mask => self.generate_test(out, &comparison.a, &comparison.b),
+            // `opt::flags` derives the others only for targets without flags.
+            tested => unreachable!("{:?} needs a target without condition flags", tested),
+        }
+    }
 }
 
 pub fn create_arch_codegen(architecture: &Architecture) -> Box<dyn ArchCodeGen> {
@@ -398,7 +998,16 @@ pub fn create_arch_codegen(architecture: &Architecture) -> Box<dyn ArchCodeGen>
         Architecture::ARM64 => Box::new(ARM64CodeGen::new()),
         Architecture::ARM32 => Box::new(ARM32CodeGen::new()),
         Architecture::RISCV => Box::new(RISCVCodeGen::new()),
-        // Architecture::PowerPC64 => Box::new(PowerPC64CodeGen::new()),
+        Architecture::MIPS32 => Box::new(MIPS32CodeGen::new()),
+        Architecture::PowerPC64 => Box::new(PowerPC64CodeGen::new()),
+        Architecture::PowerPC64LE => Box::new(PowerPC64CodeGen::elf_v2()),
+        Architecture::SPARC64 => Box::new(SPARC64CodeGen::new()),
+        Architecture::K68 => Box::new(K68CodeGen::new()),
+        Architecture::AVR => Box::new(AVRCodeGen::new()),
+        Architecture::MSP430 => Box::new(MSP430CodeGen::new()),
+        Architecture::Z80 => Box::new(Z80CodeGen::new()),
+        Architecture::Xtensa => Box::new(XtensaCodeGen::new()),
+        Architecture::SH => Box::new(SHCodeGen::new()),
         _ => {
             eprintln!(
                 "Error: Architecture {:?} is not currently implemented",
@@ -409,6 +1018,18 @@ pub fn create_arch_codegen(architecture: &Architecture) -> Box<dyn ArchCodeGen>
     }
 }
 
+/// Like `create_arch_codegen`, but picking the densest instruction set the
+/// architecture offers: Thumb-2 on ARM32, compressed instructions on RISC-V,
+/// the code density option on Xtensa.
+pub fn create_compact_arch_codegen(architecture: &Architecture) -> Box<dyn ArchCodeGen> {
+    match architecture {
+        Architecture::ARM32 => Box::new(ARM32CodeGen::thumb()),
+        Architecture::RISCV => Box::new(RISCVCodeGen::compressed()),
+        Architecture::Xtensa => Box::new(XtensaCodeGen::density()),
+        _ => create_arch_codegen(architecture),
+    }
+}
+
 struct ArchInfo {
     aliases: &'static [&'static str],
     supported: &'static [Platform],
@@ -445,15 +1066,23 @@ fn arch_db() -> HashMap<Architecture, ArchInfo> {
             ArchInfo {
                 aliases: &["riscv64", "riscv", "riscv64gc"],
                 supported: &[Linux, BSD, Embedded],
-                status: 2,
+                status: 3,
             },
         ),
         (
             PowerPC64,
             ArchInfo {
-                aliases: &["ppc64", "ppc64le", "powerpc64"],
-                supported: &[Linux, BSD, MacOS, Embedded],
-                status: 1,
+                aliases: &["ppc64", "powerpc64"],
+                supported: &[Linux, BSD, Embedded],
+                status: 2,
+            },
+        ),
+        (
+            PowerPC64LE,
+            ArchInfo {
+                aliases: &["ppc64le", "powerpc64le"],
+                supported: &[Linux, BSD, Embedded],
+                status: 2,
             },
         ),
         (
@@ -477,7 +1106,7 @@ fn arch_db() -> HashMap<Architecture, ArchInfo> {
             ArchInfo {
                 aliases: &["mips", "mips32", "mipsel", "mips32r2"],
                 supported: &[Linux, BSD, Embedded],
-                status: 0,
+                status: 2,
             },
         ),
         (
@@ -485,7 +1114,7 @@ fn arch_db() -> HashMap<Architecture, ArchInfo> {
             ArchInfo {
                 aliases: &["sparc64", "ultrasparc"],
                 supported: &[Linux, BSD, Solaris],
-                status: 0,
+                status: 2,
             },
         ),
         (
@@ -517,7 +1146,7 @@ fn arch_db() -> HashMap<Architecture, ArchInfo> {
             ArchInfo {
                 aliases: &["m68k", "68000", "k68"],
                 supported: &[Linux, MacOS, Embedded],
-                status: 0,
+                status: 2,
             },
         ),
         (
@@ -525,7 +1154,7 @@ fn arch_db() -> HashMap<Architecture, ArchInfo> {
             ArchInfo {
                 aliases: &["avr", "atmega"],
                 supported: &[Embedded],
-                status: 0,
+                status: 2,
             },
         ),
         (
@@ -533,7 +1162,7 @@ fn arch_db() -> HashMap<Architecture, ArchInfo> {
             ArchInfo {
                 aliases: &["msp430"],
                 supported: &[Embedded],
-                status: 0,
+                status: 2,
             },
         ),
         (
@@ -541,7 +1170,7 @@ fn arch_db() -> HashMap<Architecture, ArchInfo> {
             ArchInfo {
                 aliases: &["superh", "sh2", "sh3", "sh4"],
                 supported: &[Linux, Embedded],
-                status: 0,
+                status: 2,
             },
         ),
         (
@@ -565,7 +1194,7 @@ fn arch_db() -> HashMap<Architecture, ArchInfo> {
             ArchInfo {
                 aliases: &["xtensa"],
                 supported: &[Linux, Embedded],
-                status: 0,
+                status: 2,
             },
         ),
         (
@@ -581,7 +1210,7 @@ fn arch_db() -> HashMap<Architecture, ArchInfo> {
             ArchInfo {
                 aliases: &["z80", "zilog80"],
                 supported: &[Embedded],
-                status: 0,
+                status: 2,
             },
         ),
     ])
@@ -653,7 +1282,7 @@ pub fn list_target(aliases: bool) -> Vec<String> {
                         // Platform::BSD => "bsd",
                         // Platform::Solaris => "solaris",
                         // Platform::DOS => "dos",
-                        // Platform::Embedded => "embedded",
+                        Platform::Embedded => "embedded",
                         _ => "",
                     };
                     targets.push(format!("{}_{} [{}]", alias, platform_str, status));
@@ -668,7 +1297,7 @@ pub fn list_target(aliases: bool) -> Vec<String> {
                     // Platform::BSD => "bsd",
                     // Platform::Solaris => "solaris",
                     // Platform::DOS => "dos",
-                    // Platform::Embedded => "embedded",
+                    Platform::Embedded => "embedded",
                     _ => continue
                 };
                 
@@ -0,0 +1,1459 @@
+use super::*;
+use std::collections::HashMap;
+
+pub struct MSP430CodeGen {
+    register_map: HashMap<String, String>,
+}
+
+/// Where an access finds its operand: `displacement` bytes past `base`, or
+/// past nothing for an absolute address.
+struct Location {
+    base: Option<String>,
+    symbolic: String,
+    displacement: i64,
+}
+
+impl Location {
+    /// The operand for the word `offset` bytes in.
+    fn at(&self, offset: i64) -> String {
+        let displacement = self.displacement + offset;
+        let expression = match (self.symbolic.as_str(), displacement) {
+            ("", displacement) => displacement.to_string(),
+            (symbolic, 0) => symbolic.to_string(),
+            (symbolic, displacement) if displacement < 0 => {
+                format!("{}-{}", symbolic, -displacement)
+            }
+            (symbolic, displacement) => format!("{}+{}", symbolic, displacement),
+        };
+        match &self.base {
+            Some(base) => format!("{}({})", expression, base),
+            None => format!("&{}", expression),
+        }
+    }
+}
+
+/// How `shift` moves the bits of a register.
+#[derive(Clone, Copy, PartialEq)]
+enum Shift {
+    Left,
+    Right,
+    Arithmetic,
+    RotateLeft,
+    RotateRight,
+}
+
+/// The scratch register: call-clobbered in the ABI, and in no pair.
+const SCRATCH: &str = "r11";
+
+/// The bits of `SR` that stop the CPU and clocks in each low-power mode, as
+/// the `LPMx_bits` of TI's headers.
+const LOW_POWER: [u32; 5] = [0x10, 0x50, 0x90, 0xd0, 0xf0];
+
+/// The general interrupt enable bit of `SR`.
+const GIE: u32 = 0x08;
+
+impl MSP430CodeGen {
+    pub fn new() -> Self {
+        let mut register_map = HashMap::with_capacity(7);
+
+        // Pairs of word registers, high word first, beginning with the
+        // registers msp430-elf-gcc passes 32-bit arguments in
+        register_map.insert("r0".to_string(), "r13:r12".to_string()); // 1st arg/return value
+        register_map.insert("r1".to_string(), "r15:r14".to_string()); // 2nd arg
+        register_map.insert("r2".to_string(), "r10:r9".to_string());
+        register_map.insert("r3".to_string(), "r8:r7".to_string());
+        register_map.insert("r4".to_string(), "r6:r5".to_string());
+
+        // Special purpose registers
+        register_map.insert("sp".to_string(), "sp".to_string()); // Stack pointer
+        register_map.insert("sb".to_string(), "r4".to_string()); // Frame pointer
+
+        MSP430CodeGen { register_map }
+    }
+
+    /// The low and high word registers of the pair `name`.
+    fn pair(name: &str) -> Option<[&str; 2]> {
+        let (high, low) = name.split_once(':')?;
+        Some([low, high])
+    }
+
+    /// Whether `operand`, mapped, can be the base of an address.
+    fn is_base(operand: &str) -> bool {
+        operand == "sp" || operand == "r4" || Self::pair(operand).is_some()
+    }
+
+    /// The value of the constant `operand`, including the unsigned masks
+    /// `opt::flags` writes for `bt` of the top bit.
+    fn immediate(operand: &str) -> Option<i64> {
+        opt::parse_immediate(operand)
+            .or_else(|| operand.trim().parse::<u64>().ok().map(|value| value as i64))
+    }
+
+    /// The low and high words of `operand`, as source operands. Addresses
+    /// are 16 bits, so a symbol's high word is zero.
+    fn words(&self, operand: &str) -> [String; 2] {
+        let mapped = self.map_operand(operand.trim());
+        if let Some([low, high]) = Self::pair(&mapped) {
+            return [low.to_string(), high.to_string()];
+        }
+        if mapped == "sp" || mapped == "r4" {
+            return [mapped, "#0".to_string()];
+        }
+        match Self::immediate(operand) {
+            Some(value) => [
+                format!("#{}", value & 0xffff),
+                format!("#{}", (value >> 16) & 0xffff),
+            ],
+            None => [format!("#{}", mapped), "#0".to_string()],
+        }
+    }
+
+    /// The word registers of `dst` an instruction writes, lowest first: both
+    /// of a pair, or `sp` or `r4` alone.
+    fn destination(&self, dst: &str) -> Result<Vec<String>, String> {
+        let mapped = self.map_operand(dst.trim());
+        match Self::pair(&mapped) {
+            Some([low, high]) => Ok(vec![low.to_string(), high.to_string()]),
+            None if mapped == "sp" || mapped == "r4" => Ok(vec![mapped]),
+            None => Err(format!(
+                "    .error \"{} is not an MSP430 register\"\n",
+                dst
+            )),
+        }
+    }
+
+    /// `op src, dst` a word at a time, `ops` being the instructions for the
+    /// low word and the words above it.
+    fn chain(&self, out: &mut String, ops: [&str; 2], dst: &str, src: &str) {
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        let src = self.words(src);
+        for (i, word) in words.iter().enumerate() {
+            let op = ops[i.min(1)];
+            // Leave out what changes nothing: no flag outlives the
+            // instruction.
+            let idle = match op {
+                "bis.w" | "xor.w" | "bic.w" => src[i] == "#0",
+                "and.w" => src[i] == "#65535",
+                _ => false,
+            };
+            if !idle {
+                emit!(out, "    {} {}, {}\n", op, src[i], word);
+            }
+        }
+    }
+
+    /// `code` for each word register of `dst`, lowest first.
+    fn each(&self, out: &mut String, dst: &str, code: impl Fn(&mut String, &str)) {
+        match self.destination(dst) {
+            Ok(words) => {
+                for word in &words {
+                    code(out, word);
+                }
+            }
+            Err(error) => out.push_str(&error),
+        }
+    }
+
+    /// One bit of `shift` across `words`, lowest first.
+    fn step(shift: Shift, words: &[String]) -> String {
+        let low = &words[0];
+        let Some(high) = words.get(1) else {
+            return match shift {
+                Shift::Left => format!("    rla.w {}\n", low),
+                Shift::Right => format!("    clrc\n    rrc.w {}\n", low),
+                Shift::Arithmetic => format!("    rra.w {}\n", low),
+                Shift::RotateLeft => format!("    rla.w {}\n    adc.w {}\n", low, low),
+                Shift::RotateRight => format!("    bit.w #1, {}\n    rrc.w {}\n", low, low),
+            };
+        };
+        match shift {
+            Shift::Left => format!("    rla.w {}\n    rlc.w {}\n", low, high),
+            Shift::Right => format!("    clrc\n    rrc.w {}\n    rrc.w {}\n", high, low),
+            Shift::Arithmetic => format!("    rra.w {}\n    rrc.w {}\n", high, low),
+            // The bit rotated out comes back in through the carry.
+            Shift::RotateLeft => {
+                format!("    rla.w {}\n    rlc.w {}\n    adc.w {}\n", low, high, low)
+            }
+            Shift::RotateRight => format!(
+                "    bit.w #1, {}\n    rrc.w {}\n    rrc.w {}\n",
+                low, high, low
+            ),
+        }
+    }
+
+    /// `shift` of `dst` by `count`, which counts modulo 32 as x86 does.
+    fn shift(&self, out: &mut String, shift: Shift, dst: &str, count: &str) {
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        let step = Self::step(shift, &words);
+        let Some(count) = Self::immediate(count) else {
+            let [count, _] = self.words(count);
+            emit!(
+                out,
+                "    mov.w {}, {}\n    and.w #31, {}\n    jz 2f\n1:\n{}    dec.w {}\n    jnz 1b\n2:\n",
+                count,
+                SCRATCH,
+                SCRATCH,
+                step,
+                SCRATCH
+            );
+            return;
+        };
+        let mut count = count & 31;
+        // Sixteen bits at a time are a word move.
+        if count >= 16 && words.len() == 2 {
+            let (low, high) = (&words[0], &words[1]);
+            match shift {
+                Shift::Left => emit!(out, "    mov.w {}, {}\n    clr.w {}\n", low, high, low),
+                Shift::Right => emit!(out, "    mov.w {}, {}\n    clr.w {}\n", high, low, high),
+                Shift::Arithmetic => {
+                    emit!(out, "    mov.w {}, {}\n", high, low);
+                    Self::sign(out, high, high);
+                }
+                Shift::RotateLeft | Shift::RotateRight => emit!(
+                    out,
+                    "    mov.w {}, {}\n    mov.w {}, {}\n    mov.w {}, {}\n",
+                    low,
+                    SCRATCH,
+                    high,
+                    low,
+                    SCRATCH,
+                    high
+                ),
+            }
+            count -= 16;
+        }
+        match count {
+            0 => {}
+            1..=3 => out.push_str(&step.repeat(count as usize)),
+            _ => emit!(
+                out,
+                "    mov.w #{}, {}\n1:\n{}    dec.w {}\n    jnz 1b\n",
+                count,
+                SCRATCH,
+                step,
+                SCRATCH
+            ),
+        }
+    }
+
+    /// `dst = dst op src` through the libgcc routine `routine`, which takes
+    /// its operands in `r13:r12` and `r15:r14` and leaves the result in
+    /// `r13:r12`, or the high half of a 64-bit one in `r15:r14` when `high`.
+    /// It changes `r11` to `r15`.
+    fn library(&self, out: &mut String, routine: &str, high: bool, dst: &str, src: &str) {
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        let saved: Vec<&str> = ["r12", "r13", "r14", "r15"]
+            .into_iter()
+            .filter(|register| !words.iter().any(|word| word == register))
+            .collect();
+        let [src_low, src_high] = self.words(src);
+        let [dst_low, dst_high] = self.words(dst);
+        for register in &saved {
+            emit!(out, "    push.w {}\n", register);
+        }
+        // Through the stack, which stays right whichever registers the
+        // operands are in.
+        for word in [&src_high, &src_low, &dst_high, &dst_low] {
+            emit!(out, "    push.w {}\n", word);
+        }
+        out.push_str("    pop.w r12\n    pop.w r13\n    pop.w r14\n    pop.w r15\n");
+        emit!(out, "    call #{}\n", routine);
+        let result = if high { ["r14", "r15"] } else { ["r12", "r13"] };
+        for (word, result) in words.iter().zip(result) {
+            if word != result {
+                emit!(out, "    mov.w {}, {}\n", result, word);
+            }
+        }
+        for register in saved.iter().rev() {
+            emit!(out, "    pop.w {}\n", register);
+        }
+    }
+
+    /// Pushes the single-bit mask `bit` selects, four bytes at `0(sp)`.
+    fn mask(&self, out: &mut String, bit: &str) {
+        let [bit, _] = self.words(bit);
+        emit!(
+            out,
+            "    mov.w {}, {}\n    push.w #0\n    push.w #1\n    and.w #31, {}\n    jz 2f\n1:\n    rla.w 0(sp)\n    rlc.w 2(sp)\n    dec.w {}\n    jnz 1b\n2:\n",
+            bit,
+            SCRATCH,
+            SCRATCH,
+            SCRATCH
+        );
+    }
+
+    /// `op mask, dst` for the single-bit mask `bit` selects.
+    fn bit_op(&self, out: &mut String, op: &str, dst: &str, bit: &str) {
+        if let Some(bit) = Self::immediate(bit) {
+            self.chain(out, [op, op], dst, &(1i64 << (bit & 31)).to_string());
+            return;
+        }
+        self.mask(out, bit);
+        match self.destination(dst) {
+            Ok(words) => {
+                for (i, word) in words.iter().enumerate() {
+                    emit!(out, "    {} {}(sp), {}\n", op, 2 * i, word);
+                }
+            }
+            Err(error) => out.push_str(&error),
+        }
+        out.push_str("    add.w #4, sp\n");
+    }
+
+    /// Copies `src` to the registers of `dst` unless they are the same.
+    fn copy(&self, out: &mut String, dst: &str, src: &str) {
+        if self.map_operand(dst.trim()) == self.map_operand(src.trim()) {
+        } else {
+            self.generate_mov(out, dst, src);
+        }
+    }
+
+    /// Fills `to` with the sign bit of `from`: the carry out of its top bit,
+    /// less one, inverted.
+    fn sign(out: &mut String, from: &str, to: &str) {
+        if from != to {
+            emit!(out, "    mov.w {}, {}\n", from, to);
+        }
+        emit!(
+            out,
+            "    rla.w {to}\n    subc.w {to}, {to}\n    inv.w {to}\n",
+            to = to
+        );
+    }
+
+    /// Sets `dst` to the count in the scratch register, sign-extended.
+    fn set_to_scratch(&self, out: &mut String, dst: &str) {
+        self.each(out, dst, |out, word| {
+            emit!(out, "    mov.w {}, {}\n", SCRATCH, word)
+        });
+        match self.destination(dst) {
+            Ok(words) if words.len() == 2 => Self::sign(out, &words[0], &words[1]),
+            _ => {}
+        }
+    }
+
+    /// `operand` as base, index and displacement, where `sp`, `r4` or a
+    /// register pair can be the base.
+    fn split_address(&self, operand: &str) -> Result<Address, String> {
+        super::split_address(operand, |name| self.map_operand(name), Self::is_base)
+    }
+
+    /// The word register that holds the address in `base`.
+    fn base_register(base: &str) -> String {
+        match Self::pair(base) {
+            Some([low, _]) => low.to_string(),
+            None => base.to_string(),
+        }
+    }
+
+    /// Where `operand` is in memory, with `pushed` bytes on the stack since
+    /// the instruction started, once a scaled index is in the scratch
+    /// register.
+    fn locate(&self, out: &mut String, operand: &str, pushed: i64) -> Location {
+        let address = address_or_error(out, self.split_address(operand));
+        let mut displacement = address.displacement;
+        if address.base.as_deref() == Some("sp") {
+            displacement += pushed;
+        }
+        let base = address.base.as_deref().map(Self::base_register);
+        let Some((index, shift)) = &address.index else {
+            return Location {
+                base,
+                symbolic: address.symbolic,
+                displacement,
+            };
+        };
+        emit!(
+            out,
+            "    mov.w {}, {}\n",
+            Self::base_register(index),
+            SCRATCH
+        );
+        for _ in 0..*shift {
+            emit!(out, "    rla.w {}\n", SCRATCH);
+        }
+        if let Some(base) = &base {
+            emit!(out, "    add.w {}, {}\n", base, SCRATCH);
+        }
+        Location {
+            base: Some(SCRATCH.to_string()),
+            symbolic: address.symbolic,
+            displacement,
+        }
+    }
+
+    /// The width suffix and operand of the port `port`: a byte, but a word
+    /// in the 16-bit peripheral range 0x100-0x1ff.
+    fn port(&self, port: &str) -> (&'static str, String) {
+        let mapped = self.map_operand(port.trim());
+        match Self::immediate(port) {
+            Some(port @ 0x100..=0x1ff) => ("w", format!("&{}", port)),
+            Some(port) => ("b", format!("&{}", port)),
+            None => match Self::pair(&mapped) {
+                Some([low, _]) => ("b", format!("0({})", low)),
+                None if mapped == "sp" || mapped == "r4" => ("b", format!("0({})", mapped)),
+                None => ("b", format!("&{}", mapped)),
+            },
+        }
+    }
+
+    /// Whether `operand` is a word register, which `cmp` can subtract from.
+    fn is_register(operand: &str) -> bool {
+        !operand.starts_with('#') && !operand.contains('(') && !operand.starts_with('&')
+    }
+
+    /// Sets the flags `comparison` tests, and returns the condition to test
+    /// them for. The words are subtracted through the scratch register,
+    /// which ends up holding the high word of the result, so C, N and V hold
+    /// for all 32 bits and Z only for the high word; conditions that need
+    /// the operands the other way round swap them, and `s`/`ns` test the
+    /// sign bit into Z.
+    fn flags(&self, out: &mut String, comparison: &Comparison) -> Condition {
+        let (mut a, mut b) = (self.words(&comparison.a), self.words(&comparison.b));
+        let mut pushed = false;
+        if comparison.tested == Tested::Mask {
+            match comparison.condition {
+                Condition::Eq | Condition::Ne => {
+                    emit!(
+                        out,
+                        "    mov.w {}, {s}\n    and.w {}, {s}\n    jnz 3f\n    mov.w {}, {s}\n    and.w {}, {s}\n3:\n",
+                        a[0],
+                        b[0],
+                        a[1],
+                        b[1],
+                        s = SCRATCH
+                    );
+                    return comparison.condition;
+                }
+                Condition::S | Condition::Ns | Condition::Lt | Condition::Ge => {
+                    emit!(
+                        out,
+                        "    mov.w {}, {s}\n    and.w {}, {s}\n    bit.w #32768, {s}\n",
+                        a[1],
+                        b[1],
+                        s = SCRATCH
+                    );
+                    let condition = match comparison.condition {
+                        Condition::S | Condition::Lt => Condition::S,
+                        _ => Condition::Ns,
+                    };
+                    return condition;
+                }
+                _ => {
+                    // a & b on the stack, compared with zero below.
+                    out.push_str("    sub.w #4, sp\n");
+                    for i in 0..2 {
+                        emit!(
+                            out,
+                            "    mov.w {}, {s}\n    and.w {}, {s}\n    mov.w {s}, {}(sp)\n",
+                            a[i],
+                            b[i],
+                            2 * i,
+                            s = SCRATCH
+                        );
+                    }
+                    a = ["0(sp)".to_string(), "2(sp)".to_string()];
+                    b = ["#0".to_string(), "#0".to_string()];
+                    pushed = true;
+                }
+            }
+        }
+
+        let condition = comparison.condition;
+        match condition {
+            Condition::Eq | Condition::Ne => {
+                if Self::is_register(&a[0]) {
+                    emit!(out, "    cmp.w {}, {}\n", b[0], a[0]);
+                } else {
+                    emit!(
+                        out,
+                        "    mov.w {}, {s}\n    sub.w {}, {s}\n",
+                        a[0],
+                        b[0],
+                        s = SCRATCH
+                    );
+                }
+                out.push_str("    jnz 3f\n");
+                if Self::is_register(&a[1]) {
+                    emit!(out, "    cmp.w {}, {}\n", b[1], a[1]);
+                } else {
+                    emit!(
+                        out,
+                        "    mov.w {}, {s}\n    sub.w {}, {s}\n",
+                        a[1],
+                        b[1],
+                        s = SCRATCH
+                    );
+                }
+                out.push_str("3:\n");
+                if pushed {
+                    emit!(out, "    pop.w {s}\n    pop.w {s}\n", s = SCRATCH);
+                }
+                return condition;
+            }
+            _ => {}
+        }
+
+        let (swap, condition) = match condition {
+            Condition::Gt => (true, Condition::Lt),
+            Condition::Le => (true, Condition::Ge),
+            Condition::A => (true, Condition::B),
+            Condition::Be => (true, Condition::Ae),
+            condition => (false, condition),
+        };
+        let (x, y) = if swap { (&b, &a) } else { (&a, &b) };
+        if Self::is_register(&x[0]) {
+            emit!(out, "    cmp.w {}, {}\n", y[0], x[0]);
+        } else {
+            emit!(
+                out,
+                "    mov.w {}, {s}\n    sub.w {}, {s}\n",
+                x[0],
+                y[0],
+                s = SCRATCH
+            );
+        }
+        emit!(
+            out,
+            "    mov.w {}, {s}\n    subc.w {}, {s}\n",
+            x[1],
+            y[1],
+            s = SCRATCH
+        );
+        if matches!(condition, Condition::S | Condition::Ns) {
+            emit!(out, "    bit.w #32768, {}\n", SCRATCH);
+        }
+        if pushed {
+            // `pop` is a `mov`, which leaves the flags alone.
+            emit!(out, "    pop.w {s}\n    pop.w {s}\n", s = SCRATCH);
+        }
+        condition
+    }
+
+    /// `body`, skipped unless the flags `flags` set hold `condition`. Jumps
+    /// only reach 512 words, so `body` can be a `br` anywhere.
+    fn unless(out: &mut String, condition: Condition, body: impl FnOnce(&mut String)) {
+        let branch = match condition {
+            Condition::Eq => "jne",
+            Condition::Ne => "jeq",
+            Condition::Lt => "jge",
+            Condition::Ge => "jl",
+            Condition::B => "jhs",
+            Condition::Ae => "jlo",
+            // The sign bit, tested into Z.
+            Condition::S => "jeq",
+            Condition::Ns => "jne",
+            Condition::Gt | Condition::Le | Condition::A | Condition::Be => {
+                unreachable!("flags swaps the operands")
+            }
+        };
+        emit!(out, "    {} 1f\n", branch);
+        body(out);
+        out.push_str("1:\n");
+    }
+
+    /// The word registers `pusha` saves and `popa` restores: those of every
+    /// register the register map names but `sp`, in a fixed order. Words,
+    /// not names: `r4` the frame pointer is also the name of a pair.
+    fn saved_by_pusha(&self) -> Vec<&str> {
+        let mut registers: Vec<&str> = self
+            .register_map
+            .values()
+            .filter(|r| r.as_str() != "sp")
+            .flat_map(|r| r.split(':'))
+            .collect();
+        registers.sort();
+        registers.dedup();
+        registers
+    }
+}
+
+impl ArchCodeGen for MSP430CodeGen {
+    fn get_register_map(&self) -> HashMap<String, String> {
+        self.register_map.clone()
+    }
+
+    fn get_syntax_header(&self) -> String {
+        ".text\n\n".to_string()
+    }
+
+    fn generate_mov(&self, out: &mut String, dst: &str, src: &str) {
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        let src = self.words(src);
+        for (word, src) in words.iter().zip(&src) {
+            if word != src {
+                emit!(out, "    mov.w {}, {}\n", src, word);
+            }
+        }
+    }
+
+    fn generate_lea(&self, out: &mut String, dst: &str, src: &str) {
+        let location = self.locate(out, src, 0);
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        let operand = location.at(0);
+        match operand.split_once('(') {
+            // An absolute address is the constant itself.
+            None => emit!(out, "    mov.w #{}, {}\n", &operand[1..], words[0]),
+            Some((expression, base)) => {
+                let base = base.trim_end_matches(')');
+                if base != words[0] {
+                    emit!(out, "    mov.w {}, {}\n", base, words[0]);
+                }
+                if expression != "0" {
+                    emit!(out, "    add.w #{}, {}\n", expression, words[0]);
+                }
+            }
+        }
+        if let Some(high) = words.get(1) {
+            emit!(out, "    clr.w {}\n", high);
+        }
+    }
+
+    fn generate_load(&self, out: &mut String, dst: &str, src: &str) {
+        // The high word first: the low word may be the base.
+        let location = self.locate(out, src, 0);
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        for (i, word) in words.iter().enumerate().rev() {
+            emit!(out, "    mov.w {}, {}\n", location.at(2 * i as i64), word);
+        }
+    }
+
+    fn generate_store(&self, out: &mut String, dst: &str, src: &str) {
+        let location = self.locate(out, dst, 0);
+        for (i, word) in self.words(src).iter().enumerate() {
+            emit!(out, "    mov.w {}, {}\n", word, location.at(2 * i as i64));
+        }
+    }
+
+    fn generate_add(&self, out: &mut String, dst: &str, src: &str) {
+        self.chain(out, ["add.w", "addc.w"], dst, src);
+    }
+
+    fn generate_sub(&self, out: &mut String, dst: &str, src: &str) {
+        self.chain(out, ["sub.w", "subc.w"], dst, src);
+    }
+
+    fn generate_mul(&self, out: &mut String, dst: &str, src: &str) {
+        self.library(out, "__mspabi_mpyl", false, dst, src);
+    }
+
+    fn generate_div(&self, out: &mut String, dst: &str, src: &str) {
+        self.library(out, "__mspabi_divli", false, dst, src);
+    }
+
+    fn generate_inc(&self, out: &mut String, dst: &str) {
+        self.generate_add(out, dst, "1");
+    }
+
+    fn generate_dec(&self, out: &mut String, dst: &str) {
+        self.generate_sub(out, dst, "1");
+    }
+
+    fn generate_neg(&self, out: &mut String, dst: &str) {
+        self.each(out, dst, |out, word| emit!(out, "    inv.w {}\n", word));
+        self.generate_add(out, dst, "1");
+    }
+
+    fn generate_and(&self, out: &mut String, dst: &str, src: &str) {
+        self.chain(out, ["and.w", "and.w"], dst, src);
+    }
+
+    fn generate_or(&self, out: &mut String, dst: &str, src: &str) {
+        self.chain(out, ["bis.w", "bis.w"], dst, src);
+    }
+
+    fn generate_xor(&self, out: &mut String, dst: &str, src: &str) {
+        self.chain(out, ["xor.w", "xor.w"], dst, src);
+    }
+
+    fn generate_not(&self, out: &mut String, dst: &str) {
+        self.each(out, dst, |out, word| emit!(out, "    inv.w {}\n", word));
+    }
+
+    fn generate_shl(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, Shift::Left, dst, src);
+    }
+
+    fn generate_shr(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, Shift::Right, dst, src);
+    }
+
+    fn generate_cmp(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_test(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_jmp(&self, out: &mut String, label: &str) {
+        let mapped = self.map_operand(label);
+        match Self::pair(&mapped) {
+            Some([low, _]) => emit!(out, "    br {}\n", low),
+            None => emit!(out, "    br #{}\n", label),
+        }
+    }
+
+    fn generate_je(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_jne(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_jg(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_jl(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_jge(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_jle(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_call(&self, out: &mut String, func: &str) {
+        let mapped = self.map_operand(func);
+        match Self::pair(&mapped) {
+            Some([low, _]) => emit!(out, "    call {}\n", low),
+            None => emit!(out, "    call #{}\n", func),
+        }
+    }
+
+    fn generate_ret(&self, out: &mut String) {
+        out.push_str("    ret\n");
+    }
+
+    fn generate_syscall(&self, out: &mut String, name: &str) {
+        // No operating system: the names stand for what firmware asks of
+        // the CPU itself. Setting and clearing GIE takes a `nop` around it.
+        if let Some(mode) = name
+            .strip_prefix("lpm")
+            .and_then(|mode| mode.parse::<usize>().ok())
+            .filter(|&mode| mode < LOW_POWER.len())
+        {
+            // Sleeps with interrupts on, which wake it.
+            {
+                emit!(
+                    out,
+                    "    nop\n    bis.w #{}, sr\n    nop\n",
+                    LOW_POWER[mode] | GIE
+                );
+                return;
+            };
+        }
+        match name {
+            // LPM4 with interrupts off: only a reset wakes it.
+            "exit" => emit!(out, "    dint\n    nop\n    bis.w #{}, sr\n", LOW_POWER[4]),
+            // Clears the low-power bits of the `SR` an interrupt saved, so
+            // that `reti` returns to a running CPU. Only at the top of a
+            // handler, before it pushes anything.
+            "lpm_exit" => emit!(out, "    bic.w #{}, 0(sp)\n", LOW_POWER[4]),
+            "reti" => out.push_str("    reti\n"),
+            "eint" => out.push_str("    nop\n    eint\n    nop\n"),
+            "dint" => out.push_str("    dint\n    nop\n"),
+            _ => emit!(
+                out,
+                "    ; No operating system to handle syscall: {}\n",
+                name
+            ),
+        }
+    }
+
+    fn map_operand(&self, operand: &str) -> String {
+        if operand.chars().all(|c| c.is_ascii_digit() || c == '-') {
+            return operand.to_string();
+        }
+
+        if operand.starts_with('[') && operand.ends_with(']') {
+            return self.map_memory_operand(operand);
+        }
+
+        if let Some(mapped) = self.register_map.get(operand) {
+            mapped.clone()
+        } else {
+            operand.to_string()
+        }
+    }
+
+    fn map_memory_operand(&self, operand: &str) -> String {
+        if !(operand.starts_with('[') && operand.ends_with(']')) {
+            return operand.to_string();
+        }
+        let mut code = String::new();
+        let location = self.locate(&mut code, operand, 0);
+        if code.is_empty() {
+            location.at(0)
+        } else {
+            operand.to_string()
+        }
+    }
+
+    fn generate_cmov_eq(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_cmov_ne(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_cmov_lt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_cmov_le(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_cmov_gt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_cmov_ge(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_cmov_ov(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_cmov_no(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_cmov_s(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_cmov_ns(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_cmov_p(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_cmov_np(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_cmov_a(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_cmov_ae(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_cmov_b(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_cmov_be(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_push(&self, out: &mut String, src: &str) {
+        // The high word first, so that the value is little-endian in memory.
+        let [low, high] = self.words(src);
+        emit!(out, "    push.w {}\n    push.w {}\n", high, low);
+    }
+
+    fn generate_pop(&self, out: &mut String, dst: &str) {
+        match self.destination(dst) {
+            Ok(words) if words.len() == 2 => {
+                emit!(out, "    pop.w {}\n    pop.w {}\n", words[0], words[1]);
+            }
+            Ok(words) if words[0] == "sp" => out.push_str("    mov.w 0(sp), sp\n"),
+            Ok(words) => emit!(out, "    pop.w {}\n    incd.w sp\n", words[0]),
+            Err(error) => out.push_str(&error),
+        }
+    }
+
+    fn generate_pusha(&self, out: &mut String) {
+        for register in self.saved_by_pusha() {
+            emit!(out, "    push.w {}\n", register);
+        }
+    }
+
+    fn generate_popa(&self, out: &mut String) {
+        for register in self.saved_by_pusha().iter().rev() {
+            emit!(out, "    pop.w {}\n", register);
+        }
+    }
+
+    fn generate_enter(&self, out: &mut String, frame_size: &str, _nesting_level: &str) {
+        self.generate_push(out, "sb");
+        self.generate_mov(out, "sb", "sp");
+        if opt::parse_immediate(frame_size) != Some(0) {
+            self.generate_sub(out, "sp", frame_size);
+        }
+    }
+
+    fn generate_leave(&self, out: &mut String) {
+        self.generate_mov(out, "sp", "sb");
+        self.generate_pop(out, "sb");
+    }
+
+    fn generate_imul(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_mul(out, dst, src);
+    }
+
+    fn generate_idiv(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_div(out, dst, src);
+    }
+
+    fn generate_mod(&self, out: &mut String, dst: &str, src: &str) {
+        self.library(out, "__mspabi_remli", false, dst, src);
+    }
+
+    fn generate_mulh(&self, out: &mut String, dst: &str, src: &str) {
+        self.library(out, "__mspabi_mpysll", true, dst, src);
+    }
+
+    fn generate_andn(&self, out: &mut String, dst: &str, src: &str) {
+        self.chain(out, ["bic.w", "bic.w"], dst, src);
+    }
+
+    fn generate_sal(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_shl(out, dst, src);
+    }
+
+    fn generate_sar(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, Shift::Arithmetic, dst, src);
+    }
+
+    fn generate_rol(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, Shift::RotateLeft, dst, src);
+    }
+
+    fn generate_ror(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, Shift::RotateRight, dst, src);
+    }
+
+    fn generate_rcl(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_rcr(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_bextr(&self, out: &mut String, dst: &str, src: &str, imm: &str) {
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        if let Some(control) = Self::immediate(imm) {
+            let (start, length) = (control & 0xff, (control >> 8) & 0xff);
+            if start >= 32 {
+                self.each(out, dst, |out, word| emit!(out, "    clr.w {}\n", word));
+                return;
+            }
+            self.copy(out, dst, src);
+            self.shift(out, Shift::Right, dst, &start.to_string());
+            if length < 32 {
+                self.generate_and(out, dst, &((1i64 << length) - 1).to_string());
+            }
+            return;
+        }
+        // Shift the field down, then, unless the length keeps all 32 bits,
+        // up to the top and back by 32 - length, counted on the stack.
+        let [control, _] = self.words(imm);
+        let right = Self::step(Shift::Right, &words);
+        let left = Self::step(Shift::Left, &words);
+        emit!(out, "    push.w {}\n", control);
+        self.copy(out, dst, src);
+        emit!(
+            out,
+            "    mov.b 0(sp), {s}\n    tst.w {s}\n    jz 2f\n1:\n{right}    dec.w {s}\n    jnz 1b\n2:\n    mov.b 1(sp), {s}\n    cmp.w #32, {s}\n    jhs 5f\n    sub.w #32, {s}\n    inv.w {s}\n    inc.w {s}\n    mov.w {s}, 0(sp)\n3:\n{left}    dec.w {s}\n    jnz 3b\n    mov.w 0(sp), {s}\n4:\n{right}    dec.w {s}\n    jnz 4b\n5:\n    incd.w sp\n",
+            s = SCRATCH,
+            right = right,
+            left = left
+        );
+    }
+
+    fn generate_bsf(&self, out: &mut String, dst: &str, src: &str) {
+        // Counts the bits shifted out below the lowest set one, -1 for 0.
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        let test = match words.get(1) {
+            Some(high) => format!(
+                "    tst.w {}\n    jnz 1f\n    tst.w {}\n    jz 2f\n",
+                words[0], high
+            ),
+            None => format!("    tst.w {}\n    jz 2f\n", words[0]),
+        };
+        self.copy(out, dst, src);
+        emit!(
+            out,
+            "    mov.w #-1, {s}\n{test}1:\n    inc.w {s}\n{step}    jnc 1b\n2:\n",
+            s = SCRATCH,
+            test = test,
+            step = Self::step(Shift::Right, &words)
+        );
+        self.set_to_scratch(out, dst);
+    }
+
+    fn generate_bsr(&self, out: &mut String, dst: &str, src: &str) {
+        // Counts down from 31 to the highest set bit, -1 for 0.
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        self.copy(out, dst, src);
+        emit!(
+            out,
+            "    mov.w #32, {s}\n1:\n    dec.w {s}\n    jn 2f\n{step}    jnc 1b\n2:\n",
+            s = SCRATCH,
+            step = Self::step(Shift::Left, &words)
+        );
+        self.set_to_scratch(out, dst);
+    }
+
+    fn generate_bt(&self, _out: &mut String, _dst: &str, _bit: &str) {}
+
+    fn generate_btr(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit_op(out, "bic.w", dst, bit);
+    }
+
+    fn generate_bts(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit_op(out, "bis.w", dst, bit);
+    }
+
+    fn generate_btc(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit_op(out, "xor.w", dst, bit);
+    }
+
+    fn generate_set_eq(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_set_ne(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_set_lt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_set_le(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_set_gt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_set_ge(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_set_ov(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_set_no(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_set_s(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_set_ns(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_set_p(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_set_np(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_set_a(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_set_ae(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_set_b(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_set_be(&self, _out: &mut String, _dst: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_cmps(&self, _out: &mut String, _src1: &str, _src2: &str) {}
+
+    fn generate_scas(&self, _out: &mut String, _src: &str, _val: &str) {}
+
+    fn generate_stos(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_store(out, dst, src);
+    }
+
+    fn generate_lods(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_load(out, dst, src);
+    }
+
+    fn generate_movs(&self, out: &mut String, dst: &str, src: &str) {
+        // Memory to memory, unless both addresses need the scratch
+        // register: then through the stack.
+        let (mut src_code, mut dst_code) = (String::new(), String::new());
+        let from = self.locate(&mut src_code, src, 0);
+        let to = self.locate(&mut dst_code, dst, 0);
+        if src_code.is_empty() || dst_code.is_empty() {
+            out.push_str(&src_code);
+            out.push_str(&dst_code);
+            emit!(out, "    mov.w {}, {}\n", from.at(0), to.at(0));
+            emit!(out, "    mov.w {}, {}\n", from.at(2), to.at(2));
+            return;
+        }
+        out.push_str("    sub.w #4, sp\n");
+        let from = self.locate(out, src, 4);
+        emit!(
+            out,
+            "    mov.w {}, 0(sp)\n    mov.w {}, 2(sp)\n",
+            from.at(0),
+            from.at(2)
+        );
+        let to = self.locate(out, dst, 4);
+        emit!(
+            out,
+            "    mov.w 0(sp), {}\n    mov.w 2(sp), {}\n    add.w #4, sp\n",
+            to.at(0),
+            to.at(2)
+        );
+    }
+
+    fn generate_cbw(&self, out: &mut String, dst: &str) {
+        match self.destination(dst) {
+            Ok(words) => {
+                emit!(out, "    sxt {}\n", words[0]);
+                if let Some(high) = words.get(1) {
+                    Self::sign(out, &words[0], high);
+                }
+            }
+            Err(error) => out.push_str(&error),
+        }
+    }
+
+    fn generate_cwd(&self, out: &mut String, dst: &str) {
+        match self.destination(dst) {
+            Ok(words) if words.len() == 2 => Self::sign(out, &words[0], &words[1]),
+            Ok(_) => {}
+            Err(error) => out.push_str(&error),
+        }
+    }
+
+    fn generate_cdq(&self, _out: &mut String, _dst: &str) {}
+
+    fn generate_cqo(&self, out: &mut String, dst: &str) {
+        // The high half of the 64-bit sign extension: all sign bits.
+        match self.destination(dst) {
+            Ok(words) => {
+                let top = &words[words.len() - 1];
+                Self::sign(out, top, top);
+                for word in &words[..words.len() - 1] {
+                    emit!(out, "    mov.w {}, {}\n", top, word);
+                }
+            }
+            Err(error) => out.push_str(&error),
+        }
+    }
+
+    fn generate_cwde(&self, out: &mut String, dst: &str) {
+        self.generate_cwd(out, dst);
+    }
+
+    fn generate_cdqe(&self, _out: &mut String, _dst: &str) {}
+
+    fn generate_jo(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_jno(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_js(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_jns(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_jp(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_jnp(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_ja(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_jae(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_jb(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_jbe(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_loop_eq(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_loop_ne(&self, _out: &mut String, _label: &str) {
+        unreachable!("MSP430 compares in each reader")
+    }
+
+    fn generate_in(&self, out: &mut String, dst: &str, port: &str) {
+        // Peripherals are memory. A byte move to a register clears its
+        // upper byte.
+        let (width, port) = self.port(port);
+        match self.destination(dst) {
+            Ok(words) => {
+                emit!(out, "    mov.{} {}, {}\n", width, port, words[0]);
+                if let Some(high) = words.get(1) {
+                    emit!(out, "    clr.w {}\n", high);
+                }
+            }
+            Err(error) => out.push_str(&error),
+        }
+    }
+
+    fn generate_out(&self, out: &mut String, port: &str, src: &str) {
+        let (width, port) = self.port(port);
+        let [low, _] = self.words(src);
+        emit!(out, "    mov.{} {}, {}\n", width, low, port);
+    }
+
+    fn generate_ins(&self, out: &mut String, dst: &str, port: &str) {
+        let (width, port) = self.port(port);
+        let location = self.locate(out, dst, 0);
+        emit!(out, "    mov.{} {}, {}\n", width, port, location.at(0));
+    }
+
+    fn generate_outs(&self, out: &mut String, port: &str, src: &str) {
+        let (width, port) = self.port(port);
+        let location = self.locate(out, src, 0);
+        emit!(out, "    mov.{} {}, {}\n", width, location.at(0), port);
+    }
+
+    fn generate_cpuid(&self, out: &mut String) {
+        out.push_str("    ; MSP430 does not have CPUID\n");
+    }
+
+    fn generate_lfence(&self, _out: &mut String) {}
+
+    fn generate_sfence(&self, _out: &mut String) {}
+
+    fn generate_mfence(&self, _out: &mut String) {}
+
+    fn generate_prefetch(&self, out: &mut String, _addr: &str) {
+        out.push_str("    ; MSP430 has no cache to prefetch into\n");
+    }
+
+    fn generate_clflush(&self, out: &mut String, _addr: &str) {
+        out.push_str("    ; MSP430 has no cache to flush\n");
+    }
+
+    fn generate_clwb(&self, out: &mut String, _addr: &str) {
+        out.push_str("    ; MSP430 has no cache to write back\n");
+    }
+
+    fn generate_global(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".globl {}\n", symbol);
+    }
+
+    fn generate_extern(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".extern {}\n", symbol);
+    }
+
+    fn generate_align(&self, out: &mut String, n: &str) {
+        emit!(out, ".p2align {}\n", n);
+    }
+
+    fn generate_data_byte(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .byte {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_word(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .2byte {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_dword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .4byte {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_qword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .8byte {}\n", name, values.join(", "));
+    }
+
+    fn generate_reserve_byte(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .space {}\n", name, count);
+    }
+
+    fn generate_reserve_word(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .space {}*2\n", name, count);
+    }
+
+    fn generate_reserve_dword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .space {}*4\n", name, count);
+    }
+
+    fn generate_reserve_qword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .space {}*8\n", name, count);
+    }
+
+    fn generate_equ(&self, out: &mut String, name: &str, value: &str) {
+        emit!(out, ".set {}, {}\n", name, value);
+    }
+
+    fn generate_section(&self, out: &mut String, section: &Section) {
+        match section {
+            Section::Text => out.push_str(".section .text\n"),
+            Section::Data => out.push_str(".section .data\n"),
+            Section::Bss => out.push_str(".section .bss\n"),
+            Section::Rodata => out.push_str(".section .rodata\n"),
+            Section::Custom(s) => emit!(out, ".section {}\n", s),
+        }
+    }
+
+    fn generate_label(&self, out: &mut String, name: &str) {
+        emit!(out, "{}:\n", name);
+    }
+
+    fn get_register_width(&self) -> u32 {
+        32
+    }
+
+    fn get_reserved_registers(&self) -> Vec<String> {
+        vec![SCRATCH.to_string()]
+    }
+
+    fn get_callee_saved_registers(&self) -> Vec<String> {
+        ["r10:r9", "r8:r7", "r6:r5", "r4"]
+            .iter()
+            .map(|r| r.to_string())
+            .collect()
+    }
+
+    fn get_push_size(&self) -> u32 {
+        4
+    }
+
+    fn get_clobbered_registers(&self, instruction: &Instruction) -> Vec<String> {
+        use Instruction::*;
+        let registers: &[&str] = match instruction {
+            Call(_) => &["r13:r12", "r15:r14"],
+            _ => &[],
+        };
+        registers.iter().map(|r| r.to_string()).collect()
+    }
+
+    fn has_condition_flags(&self) -> bool {
+        false
+    }
+
+    fn generate_compare_branch(&self, out: &mut String, comparison: &Comparison, label: &str) {
+        let condition = self.flags(out, comparison);
+        Self::unless(out, condition, |out| emit!(out, "    br #{}\n", label));
+    }
+
+    fn generate_compare_set(&self, out: &mut String, comparison: &Comparison, dst: &str) {
+        // `mov` leaves the flags alone, and `clr` is a `mov`.
+        let condition = self.flags(out, comparison);
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        for word in &words {
+            emit!(out, "    clr.w {}\n", word);
+        }
+        Self::unless(out, condition, |out| {
+            emit!(out, "    mov.w #1, {}\n", words[0])
+        });
+    }
+
+    fn generate_compare_cmov(
+        &self,
+        out: &mut String,
+        comparison: &Comparison,
+        dst: &str,
+        src: &str,
+    ) {
+        let condition = self.flags(out, comparison);
+        Self::unless(out, condition, |out| self.generate_mov(out, dst, src));
+    }
+}
@@ -3,6 +3,12 @@ use std::collections::HashMap;
 
 pub struct PowerPC64CodeGen {
     register_map: HashMap<String, String>,
+    /// Follow ELFv2, the ABI of little-endian Linux, rather than ELFv1 with
+    /// its function descriptors.
+    elf_v2: bool,
+    /// Symbols of the program. Code always reaches data relative to the TOC
+    /// pointer in r2, so it is position-independent whatever `-fpic` says.
+    pic: Option<Pic>,
 }
 
 impl PowerPC64CodeGen {
@@ -10,347 +16,732 @@ impl PowerPC64CodeGen {
         let mut register_map = HashMap::with_capacity(32);
 
         // Function argument registers (PowerPC64 ABI)
-        register_map.insert("r0".to_string(), "r3".to_string()); // 1st arg/return value
-        register_map.insert("r1".to_string(), "r4".to_string()); // 2nd arg
-        register_map.insert("r2".to_string(), "r5".to_string()); // 3rd arg
-        register_map.insert("r3".to_string(), "r6".to_string()); // 4th arg
-        register_map.insert("r4".to_string(), "r7".to_string()); // 5th arg
-        register_map.insert("r5".to_string(), "r8".to_string()); // 6th arg
-        register_map.insert("r6".to_string(), "r9".to_string()); // 7th arg
-        register_map.insert("r7".to_string(), "r10".to_string()); // 8th arg
-
-        // Volatile registers (caller-saved)
-        register_map.insert("r8".to_string(), "r11".to_string()); // Volatile
-        register_map.insert("r9".to_string(), "r12".to_string()); // Volatile
-        register_map.insert("r10".to_string(), "r0".to_string()); // Special volatile (often used for syscalls)
-        register_map.insert("r11".to_string(), "r31".to_string()); // Non-volatile (callee-saved)
-        register_map.insert("r12".to_string(), "r30".to_string()); // Non-volatile
-        register_map.insert("r13".to_string(), "r29".to_string()); // Non-volatile
-        register_map.insert("r14".to_string(), "r28".to_string()); // Non-volatile
-        register_map.insert("r15".to_string(), "r27".to_string()); // Non-volatile
-
-        // Non-volatile registers
-        register_map.insert("r19".to_string(), "r14".to_string());
-        register_map.insert("r20".to_string(), "r15".to_string());
-        register_map.insert("r21".to_string(), "r16".to_string());
-        register_map.insert("r22".to_string(), "r17".to_string());
+        register_map.insert("r0".to_string(), "%r3".to_string()); // 1st arg/return value
+        register_map.insert("r1".to_string(), "%r4".to_string()); // 2nd arg
+        register_map.insert("r2".to_string(), "%r5".to_string()); // 3rd arg
+        register_map.insert("r3".to_string(), "%r6".to_string()); // 4th arg
+        register_map.insert("r4".to_string(), "%r7".to_string()); // 5th arg
+        register_map.insert("r5".to_string(), "%r8".to_string()); // 6th arg
+        register_map.insert("r6".to_string(), "%r9".to_string()); // 7th arg
+        register_map.insert("r7".to_string(), "%r10".to_string()); // 8th arg
+
+        // Non-volatile registers (callee-saved)
+        register_map.insert("r8".to_string(), "%r22".to_string());
+        register_map.insert("r9".to_string(), "%r23".to_string());
+        register_map.insert("r10".to_string(), "%r24".to_string());
+        register_map.insert("r11".to_string(), "%r25".to_string());
+        register_map.insert("r12".to_string(), "%r26".to_string());
+        register_map.insert("r13".to_string(), "%r27".to_string());
+        register_map.insert("r14".to_string(), "%r28".to_string());
+        register_map.insert("r15".to_string(), "%r29".to_string());
+        register_map.insert("r19".to_string(), "%r14".to_string());
+        register_map.insert("r20".to_string(), "%r15".to_string());
+        register_map.insert("r21".to_string(), "%r16".to_string());
+        register_map.insert("r22".to_string(), "%r17".to_string());
 
         // Special purpose registers
-        register_map.insert("sp".to_string(), "r1".to_string()); // Stack pointer
-        register_map.insert("sb".to_string(), "r31".to_string()); // Frame pointer (if used)
-        register_map.insert("ip".to_string(), "lr".to_string()); // Link register
-
-        PowerPC64CodeGen { register_map }
+        register_map.insert("sp".to_string(), "%r1".to_string()); // Stack pointer
+        register_map.insert("sb".to_string(), "%r31".to_string()); // Frame pointer
+        register_map.insert("ip".to_string(), "%lr".to_string()); // Link register
+
+        PowerPC64CodeGen {
+            register_map,
+            elf_v2: false,
+            pic: None,
+        }
     }
 
-    fn emit_load_imm(&self, rd: &str, imm: i64) -> String {
-        if imm >= -32768 && imm <= 32767 {
-            return format!("    addi {rd}, r0, {imm}\n");
+    pub fn elf_v2() -> Self {
+        PowerPC64CodeGen {
+            elf_v2: true,
+            ..Self::new()
         }
-        let upper = ((imm as i64 >> 16) & 0xFFFF) as i64;
-        let lower = (imm as i64 & 0xFFFF) as i64;
-        format!("    addis {rd}, r0, {upper}\n    ori {rd}, {rd}, {lower}\n")
     }
 
-    fn emit_load_addr_sym(&self, rd: &str, sym: &str) -> String {
-        format!("    addis {rd}, r0, {sym}@ha\n    addi {rd}, {rd}, {sym}@l\n")
+    /// Bytes a call reserves below the stack pointer for the callee: the
+    /// frame header where it saves the link register and TOC pointer, and
+    /// the parameter save area variadic functions spill their arguments to.
+    fn call_frame(&self) -> u32 {
+        if self.elf_v2 { 96 } else { 112 }
     }
 
-    fn emit_reg_move(&self, rd: &str, rs: &str) -> String {
-        format!("    or {rd}, {rs}, {rs}\n")
-    }
-}
-impl ArchCodeGen for PowerPC64CodeGen {
-    fn get_register_map(&self) -> HashMap<String, String> {
-        self.register_map.clone()
+    /// Where a call's frame header keeps the caller's TOC pointer.
+    fn toc_save(&self) -> u32 {
+        if self.elf_v2 { 24 } else { 40 }
     }
 
-    fn get_syntax_header(&self) -> String {
-        ".text\n.align 2\n\n".to_string()
+    /// Whether `operand` names a general-purpose register.
+    fn is_register(operand: &str) -> bool {
+        operand
+            .strip_prefix("%r")
+            .filter(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+            .and_then(|n| n.parse::<u32>().ok())
+            .is_some_and(|n| n <= 31)
     }
 
-    fn generate_mov(&self, dst: &str, src: &str) -> String {
-        let rd = self.map_operand(dst);
-        let s = self.map_operand(src);
+    /// The value of the constant `operand`, including the unsigned masks
+    /// `opt::flags` writes for `bt` of the top bit.
+    fn immediate(operand: &str) -> Option<i64> {
+        opt::parse_immediate(operand)
+            .or_else(|| operand.trim().parse::<u64>().ok().map(|value| value as i64))
+    }
 
-        if s.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            let v: i64 = s.parse().unwrap_or(0);
-            return self.emit_load_imm(&rd, v);
+    /// Builds `value` in `dst` 16 bits at a time.
+    fn load_immediate(out: &mut String, dst: &str, value: i64) {
+        if (-0x8000..=0x7fff).contains(&value) {
+            emit!(out, "    li {}, {}\n", dst, value);
+            return;
         }
-
-        if s.starts_with('r') || s == "lr" || s == "cr" {
-            return self.emit_reg_move(&rd, &s);
+        let low = |out: &mut String, op: &str, half: i64| {
+            if half & 0xffff != 0 {
+                emit!(out, "    {} {d}, {d}, {}\n", op, half & 0xffff, d = dst);
+            }
+        };
+        if i32::try_from(value).is_ok() || u32::try_from(value).is_ok() {
+            // `lis` sign-extends, which a 32-bit unsigned value undoes.
+            emit!(out, "    lis {}, {}\n", dst, (value >> 16) as i16);
+            low(out, "ori", value);
+            if i32::try_from(value).is_err() {
+                emit!(out, "    clrldi {d}, {d}, 32\n", d = dst);
+            }
+            return;
+        }
+        emit!(out, "    lis {}, {}\n", dst, (value >> 48) as i16);
+        low(out, "ori", value >> 32);
+        emit!(out, "    sldi {d}, {d}, 32\n", d = dst);
+        low(out, "oris", value >> 16);
+        low(out, "ori", value);
+    }
+
+    /// `dst = src + value`, through `addis` for the high half of values up
+    /// to 32 bits and %r0 beyond.
+    fn add_constant(out: &mut String, dst: &str, src: &str, value: i64) {
+        if value == 0 && dst == src {
+            return;
+        }
+        if (-0x8000..=0x7fff).contains(&value) {
+            emit!(out, "    addi {}, {}, {}\n", dst, src, value);
+            return;
         }
+        let high = (value + 0x8000) >> 16;
+        if let Ok(high) = i16::try_from(high) {
+            let low = value - ((high as i64) << 16);
+            emit!(out, "    addis {}, {}, {}\n", dst, src, high);
+            if low != 0 {
+                emit!(out, "    addi {d}, {d}, {}\n", low, d = dst);
+            }
+            return;
+        }
+        Self::load_immediate(out, "%r0", value);
+        emit!(out, "    add {}, {}, %r0\n", dst, src);
+    }
+
+    /// The address of `symbol + offset` in `dst`, relative to the TOC
+    /// pointer, or loaded from the GOT if another module may provide the
+    /// symbol.
+    fn address(&self, out: &mut String, pic: &Pic, dst: &str, symbol: &str, offset: i64) {
+        if !pic.is_preemptible(symbol) {
+            let expression = match offset {
+                0 => symbol.to_string(),
+                _ => format!("({})", Pic::expression(symbol, offset)),
+            };
+            emit!(
+                out,
+                "    addis {d}, %r2, {e}@toc@ha\n    addi {d}, {d}, {e}@toc@l\n",
+                d = dst,
+                e = expression
+            );
+            return;
+        }
+        emit!(
+            out,
+            "    addis {d}, %r2, {s}@got@ha\n    ld {d}, {s}@got@l({d})\n",
+            d = dst,
+            s = symbol
+        );
+        Self::add_constant(out, dst, dst, offset);
+    }
+
+    /// The register holding `operand`, which goes in `scratch` first if it
+    /// is a constant or a symbol.
+    fn in_register(&self, out: &mut String, operand: &str, scratch: &str) -> String {
+        let mapped = self.map_operand(operand);
+        if Self::is_register(&mapped) {
+            mapped
+        } else {
+            self.generate_mov(out, scratch, operand);
+            scratch.to_string()
+        }
+    }
 
-        self.emit_load_addr_sym(&rd, &s)
+    /// `operand` as base, index and displacement over the general-purpose
+    /// registers, for `locate` to turn into a D- or X-form access.
+    fn split_address(&self, operand: &str) -> Result<Address, String> {
+        super::split_address(operand, |name| self.map_operand(name), Self::is_register)
     }
 
-    fn generate_lea(&self, dst: &str, src: &str) -> String {
-        let rd = self.map_operand(dst);
-        let src_clean = if src.starts_with('[') && src.ends_with(']') {
-            &src[1..src.len() - 1]
+    /// Puts `operand` in the form `base + index + displacement`: symbols and
+    /// other non-register parts go into %r11, a scaled index into %r0. With
+    /// an index, the displacement is folded into the base and comes back 0.
+    fn locate(&self, out: &mut String, operand: &str) -> (String, Option<String>, i64) {
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(operand)
+        {
+            if pic.is_preemptible(symbol) {
+                self.address(out, pic, "%r11", symbol, 0);
+                return ("%r11".to_string(), None, offset);
+            }
+            self.address(out, pic, "%r11", symbol, offset);
+            return ("%r11".to_string(), None, 0);
+        }
+        let address = address_or_error(out, self.split_address(operand));
+        let mut displacement = address.displacement;
+        let base = match (address.base, address.symbolic.is_empty()) {
+            (Some(base), true) => base,
+            (base, _) => {
+                let expression = match (address.symbolic.is_empty(), displacement) {
+                    (true, displacement) => displacement.to_string(),
+                    (false, 0) => address.symbolic,
+                    (false, displacement) => format!("{} + {}", address.symbolic, displacement),
+                };
+                displacement = 0;
+                self.generate_mov(out, "%r11", &expression);
+                if let Some(base) = base {
+                    emit!(out, "    add %r11, %r11, {}\n", base);
+                }
+                "%r11".to_string()
+            }
+        };
+        let Some((index, shift)) = address.index else {
+            return (base, None, displacement);
+        };
+        let base = if displacement != 0 {
+            Self::add_constant(out, "%r11", &base, displacement);
+            "%r11".to_string()
         } else {
-            src
+            base
         };
+        let index = if shift > 0 {
+            emit!(out, "    sldi %r0, {}, {}\n", index, shift);
+            "%r0".to_string()
+        } else {
+            index
+        };
+        (base, Some(index), 0)
+    }
+
+    /// `op value, operand` for the doubleword load or store `op`, in its
+    /// indexed form where the displacement does not fit the DS field.
+    fn access(&self, out: &mut String, op: &str, value: &str, operand: &str) {
+        let (base, index, displacement) = self.locate(out, operand);
+        match index {
+            Some(index) => emit!(out, "    {}x {}, {}, {}\n", op, value, base, index),
+            None if (-0x8000..=0x7fff).contains(&displacement) && displacement % 4 == 0 => {
+                emit!(out, "    {} {}, {}({})\n", op, value, displacement, base);
+            }
+            None => {
+                Self::load_immediate(out, "%r0", displacement);
+                emit!(out, "    {}x {}, {}, %r0\n", op, value, base);
+            }
+        }
+    }
 
-        if !(src_clean.starts_with('r') || src_clean == "lr" || src_clean == "cr")
-            && !src_clean.contains('+')
-            && !src_clean.contains('-')
+    /// `op dst, dst, src`, or `immediate dst, dst, src` when `src` is a
+    /// constant in `range`.
+    fn binary(
+        &self,
+        out: &mut String,
+        op: &str,
+        immediate: Option<(&str, std::ops::RangeInclusive<i64>)>,
+        dst: &str,
+        src: &str,
+    ) {
+        let dst_reg = self.map_operand(dst);
+        if let Some((immediate, range)) = immediate
+            && let Some(value) = Self::immediate(src)
+            && range.contains(&value)
         {
-            return self.emit_load_addr_sym(&rd, src_clean);
+            emit!(
+                out,
+                "    {} {}, {}, {}\n",
+                immediate,
+                dst_reg,
+                dst_reg,
+                value
+            );
+            return;
         }
+        let src_reg = self.in_register(out, src, "%r12");
+        emit!(out, "    {} {}, {}, {}\n", op, dst_reg, dst_reg, src_reg);
+    }
 
-        if let Some((base, off)) = src_clean.split_once('+') {
-            let base = base.trim();
-            let off = off.trim();
-            let base_reg = self.map_operand(base);
-            if off.chars().all(|c| c.is_ascii_digit() || c == '-') {
-                return format!(
-                    "    or {rd}, {base_reg}, {base_reg}\n    addi {rd}, {rd}, {off}\n"
-                );
+    /// A bitwise `op` of `dst` with `src`, through the forms taking the low
+    /// or the high 16 bits of a constant.
+    fn logical(&self, out: &mut String, op: &str, low: &str, high: &str, dst: &str, src: &str) {
+        if let Some(value) = Self::immediate(src)
+            && value != 0
+            && value & 0xffff == 0
+            && (0..=0xffff).contains(&(value >> 16))
+        {
+            let dst_reg = self.map_operand(dst);
+            emit!(
+                out,
+                "    {} {}, {}, {}\n",
+                high,
+                dst_reg,
+                dst_reg,
+                value >> 16
+            );
+            return;
+        }
+        self.binary(out, op, Some((low, 0..=0xffff)), dst, src);
+    }
+
+    /// A shift of `dst` by `count`, which x86 takes modulo 64 and PowerPC
+    /// modulo 128.
+    fn shift(&self, out: &mut String, op: &str, immediate: &str, dst: &str, count: &str) {
+        let dst_reg = self.map_operand(dst);
+        let count_reg = self.map_operand(count);
+        match Self::immediate(count) {
+            Some(count) => emit!(
+                out,
+                "    {} {d}, {d}, {}\n",
+                immediate,
+                count & 63,
+                d = dst_reg
+            ),
+            None if Self::is_register(&count_reg) => emit!(
+                out,
+                "    clrldi %r0, {}, 58\n    {} {d}, {d}, %r0\n",
+                count_reg,
+                op,
+                d = dst_reg
+            ),
+            // A symbol the assembler knows the value of.
+            None => emit!(
+                out,
+                "    {} {d}, {d}, ({}) & 63\n",
+                immediate,
+                count_reg,
+                d = dst_reg
+            ),
+        }
+    }
+
+    /// A rotate of `dst` by `count`; a right rotate is a left one by
+    /// 64 - count.
+    fn rotate(&self, out: &mut String, left: bool, dst: &str, count: &str) {
+        let dst_reg = self.map_operand(dst);
+        let count_reg = self.map_operand(count);
+        if Self::is_register(&count_reg) {
+            if left {
+                emit!(out, "    rotld {d}, {d}, {}\n", count_reg, d = dst_reg);
+                return;
+            }
+            emit!(
+                out,
+                "    neg %r0, {}\n    rotld {d}, {d}, %r0\n",
+                count_reg,
+                d = dst_reg
+            );
+            return;
+        }
+        let amount = match Self::immediate(count) {
+            Some(count) if left => (count & 63).to_string(),
+            Some(count) => ((64 - (count & 63)) & 63).to_string(),
+            None if left => format!("({}) & 63", count_reg),
+            None => format!("(-({})) & 63", count_reg),
+        };
+        emit!(out, "    rotldi {d}, {d}, {}\n", amount, d = dst_reg);
+    }
+
+    /// `op dst, dst, mask` for the single-bit mask `bit` selects; `op` is
+    /// `or`, `xor` or `andc`.
+    fn bit(&self, out: &mut String, op: &str, dst: &str, bit: &str) {
+        let dst_reg = self.map_operand(dst);
+        let bit_reg = self.map_operand(bit);
+        match Self::immediate(bit) {
+            Some(bit) => {
+                let bit = bit & 63;
+                if op != "andc" && bit < 32 {
+                    let (suffix, shifted) = if bit < 16 {
+                        ("i", bit)
+                    } else {
+                        ("is", bit - 16)
+                    };
+                    emit!(
+                        out,
+                        "    {}{} {d}, {d}, {}\n",
+                        op,
+                        suffix,
+                        1 << shifted,
+                        d = dst_reg
+                    );
+                    return;
+                }
+                emit!(out, "    li %r0, 1\n    sldi %r0, %r0, {}\n", bit);
+            }
+            None if Self::is_register(&bit_reg) => emit!(
+                out,
+                "    clrldi %r0, {}, 58\n    li %r11, 1\n    sld %r0, %r11, %r0\n",
+                bit_reg
+            ),
+            None => emit!(
+                out,
+                "    li %r0, 1\n    sldi %r0, %r0, ({}) & 63\n",
+                bit_reg
+            ),
+        }
+        emit!(out, "    {} {d}, {d}, %r0\n", op, d = dst_reg);
+    }
+
+    /// Sets cr0 from `comparison`: a signed or unsigned compare of the
+    /// operands, the sign of their difference, or `a & b` against zero.
+    /// For the overflow of a sum `a` of `b`, the addition is done again
+    /// from `a - b` with XER cleared, leaving its overflow in the summary
+    /// bit, and likewise for a difference.
+    fn compare(&self, out: &mut String, comparison: &Comparison) {
+        let a = self.in_register(out, &comparison.a, "%r12");
+        if let Tested::Sum | Tested::Difference = comparison.tested {
+            let b = self.in_register(out, &comparison.b, "%r11");
+            let (undo, redo) = if comparison.tested == Tested::Sum {
+                (
+                    format!("subf %r0, {}, {}", b, a),
+                    format!("addo. %r0, %r0, {}", b),
+                )
+            } else {
+                (
+                    format!("add %r0, {}, {}", a, b),
+                    format!("subfo. %r0, {}, %r0", b),
+                )
+            };
+            emit!(
+                out,
+                "    {}\n    li %r12, 0\n    mtxer %r12\n    {}\n",
+                undo,
+                redo
+            );
+            return;
+        }
+        let b = Self::immediate(&comparison.b);
+        let unsigned = matches!(
+            comparison.condition,
+            Condition::B | Condition::Be | Condition::A | Condition::Ae
+        );
+        let register = |out: &mut String| self.in_register(out, &comparison.b, "%r11");
+        if comparison.tested == Tested::Mask {
+            match b {
+                Some(value) if (0..=0xffff).contains(&value) => {
+                    emit!(out, "    andi. %r0, {}, {}\n", a, value);
+                }
+                _ => {
+                    let b = register(out);
+                    emit!(out, "    and. %r0, {}, {}\n", a, b);
+                }
+            }
+            // `and.` compares the result as signed.
+            if unsigned {
+                out.push_str("    cmpldi %r0, 0\n");
             }
+            return;
+        }
+        let compare = match comparison.condition {
+            Condition::S | Condition::Ns if b == Some(0) => format!("cmpdi {}, 0", a),
+            Condition::S | Condition::Ns => format!("subf. %r0, {}, {}", register(out), a),
+            _ if unsigned => match b {
+                Some(value) if (0..=0xffff).contains(&value) => format!("cmpldi {}, {}", a, value),
+                _ => format!("cmpld {}, {}", a, register(out)),
+            },
+            _ => match b {
+                Some(value) if (-0x8000..=0x7fff).contains(&value) => {
+                    format!("cmpdi {}, {}", a, value)
+                }
+                _ => format!("cmpd {}, {}", a, register(out)),
+            },
+        };
+        emit!(out, "    {}\n", compare);
+    }
+
+    /// The cr0 bit `condition` reads after `compare`: lt, gt, eq or, for the
+    /// overflow of a sum or difference, so by position, and whether the
+    /// condition holds when it is clear.
+    fn condition_bit(condition: Condition, tested: Tested) -> (u32, bool) {
+        if let Tested::Sum | Tested::Difference = tested {
+            return match condition {
+                Condition::S => (3, false),
+                Condition::Ns => (3, true),
+                _ => unreachable!("the overflow is only set or clear, not {:?}", condition),
+            };
+        }
+        match condition {
+            Condition::Lt | Condition::B | Condition::S => (0, false),
+            Condition::Ge | Condition::Ae | Condition::Ns => (0, true),
+            Condition::Gt | Condition::A => (1, false),
+            Condition::Le | Condition::Be => (1, true),
+            Condition::Eq => (2, false),
+            Condition::Ne => (2, true),
         }
-        let base_reg = self.map_operand(src_clean);
-        self.emit_reg_move(&rd, &base_reg)
     }
 
-    fn generate_load(&self, dst: &str, src: &str) -> String {
-        let rd = self.map_operand(dst);
+    /// The registers `pusha` saves and `popa` restores: every general-purpose
+    /// one the register map names but r1, in a fixed order.
+    fn saved_by_pusha(&self) -> Vec<&String> {
+        let mut registers: Vec<&String> = self
+            .register_map
+            .values()
+            .filter(|r| Self::is_register(r) && *r != "%r1")
+            .collect();
+        registers.sort();
+        registers.dedup();
+        registers
+    }
 
-        if !src.starts_with('[') && !src.ends_with(']') {
-            return format!(
-                "{}    ld {rd}, {sym}@l({rd})\n",
-                self.emit_load_addr_sym(&rd, src),
-                sym = src
-            );
+    /// `op dst, dst`, for negation, complement and sign extension in place.
+    fn unary(&self, out: &mut String, op: &str, dst: &str) {
+        let dst_reg = self.map_operand(dst);
+        emit!(out, "    {} {}, {}\n", op, dst_reg, dst_reg);
+    }
+
+    /// The cache operation `op` on the block holding `addr`.
+    fn cache(&self, out: &mut String, op: &str, addr: &str) {
+        self.generate_lea(out, "%r11", addr);
+        emit!(out, "    {} 0, %r11\n", op);
+    }
+}
+
+impl ArchCodeGen for PowerPC64CodeGen {
+    fn get_register_map(&self) -> HashMap<String, String> {
+        self.register_map.clone()
+    }
+
+    fn get_syntax_header(&self) -> String {
+        let version = if self.elf_v2 { 2 } else { 1 };
+        format!(".abiversion {}\n.text\n.align 2\n\n", version)
+    }
+
+    fn generate_mov(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        let src_op = self.map_operand(src);
+
+        if dst_reg == "%lr" {
+            let src_reg = self.in_register(out, src, "%r12");
+            emit!(out, "    mtlr {}\n", src_reg);
+            return;
         }
 
-        let inner = &src[1..src.len() - 1].trim();
+        if let Some(value) = Self::immediate(src) {
+            Self::load_immediate(out, &dst_reg, value);
+            return;
+        }
 
-        if !(inner.starts_with('r') || inner == &"lr" || inner == &"cr")
-            && !inner.contains('+')
-            && !inner.contains('-')
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(src)
         {
-            return format!(
-                "{}    ld {rd}, {sym}@l({rd})\n",
-                self.emit_load_addr_sym(&rd, inner),
-                sym = inner
-            );
+            self.address(out, pic, &dst_reg, symbol, offset);
+            return;
         }
 
-        format!("    ld {rd}, {}\n", self.map_memory_operand(src))
-    }
+        if src_op == "%lr" {
+            emit!(out, "    mflr {}\n", dst_reg);
+            return;
+        }
 
-    fn generate_store(&self, dst: &str, src: &str) -> String {
-        let rs = self.map_operand(src);
-        if rs.chars().all(|c| c.is_ascii_digit()) {
-            return format!("    // ERROR: std requires a register, got {}\n", rs);
+        if Self::is_register(&src_op) {
+            emit!(out, "    mr {}, {}\n", dst_reg, src_op);
+            return;
         }
 
-        if dst.starts_with('[') && dst.ends_with(']') {
-            let inner = &dst[1..dst.len() - 1].trim();
-            if !(inner.starts_with('r') || inner == &"lr" || inner == &"cr")
-                && !inner.contains('+')
-                && !inner.contains('-')
-            {
-                return format!(
-                    "{}    std {rs}, {sym}@l(r11)\n",
-                    self.emit_load_addr_sym("r11", inner),
-                    sym = inner
-                );
+        // A constant the assembler knows the value of. `ori` rather than
+        // `addi` for the low half, which would read r0 as zero.
+        emit!(
+            out,
+            "    lis {d}, {s}@h\n    ori {d}, {d}, {s}@l\n",
+            d = dst_reg,
+            s = src_op
+        );
+    }
+
+    fn generate_lea(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(src)
+        {
+            self.address(out, pic, &dst_reg, symbol, offset);
+            return;
+        }
+        let (base, index, displacement) = self.locate(out, src);
+        match index {
+            Some(index) => emit!(out, "    add {}, {}, {}\n", dst_reg, base, index),
+            None if displacement == 0 && base != dst_reg => {
+                emit!(out, "    mr {}, {}\n", dst_reg, base);
             }
-            return format!("    std {rs}, {}\n", self.map_memory_operand(dst));
+            None => Self::add_constant(out, &dst_reg, &base, displacement),
         }
+    }
 
-        format!(
-            "{}    std {rs}, {sym}@l(r11)\n",
-            self.emit_load_addr_sym("r11", dst),
-            sym = dst
-        )
+    fn generate_load(&self, out: &mut String, dst: &str, src: &str) {
+        self.access(out, "ld", &self.map_operand(dst), src);
     }
 
-    fn generate_add(&self, dst: &str, src: &str) -> String {
-        let rd = self.map_operand(dst);
-        let s = self.map_operand(src);
+    fn generate_store(&self, out: &mut String, dst: &str, src: &str) {
+        let src_reg = self.in_register(out, src, "%r12");
+        self.access(out, "std", &src_reg, dst);
+    }
 
-        if s.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            let v: i64 = s.parse().unwrap_or(0);
-            if v >= -32768 && v <= 32767 {
-                return format!("    addi {rd}, {rd}, {v}\n");
+    fn generate_add(&self, out: &mut String, dst: &str, src: &str) {
+        match Self::immediate(src) {
+            Some(value) if i32::try_from(value).is_ok() => {
+                let dst_reg = self.map_operand(dst);
+                Self::add_constant(out, &dst_reg, &dst_reg, value);
             }
-            return format!("{}    add {rd}, {rd}, r11\n", self.emit_load_imm("r11", v));
+            _ => self.binary(out, "add", None, dst, src),
         }
-        format!("    add {rd}, {rd}, {s}\n")
     }
 
-    fn generate_sub(&self, dst: &str, src: &str) -> String {
-        let rd = self.map_operand(dst);
-        let s = self.map_operand(src);
-
-        if s.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            let v: i64 = s.parse().unwrap_or(0);
-            if v >= -32768 && v <= 32767 {
-                return format!("    addi {rd}, {rd}, {}\n", -v);
+    fn generate_sub(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        match Self::immediate(src) {
+            Some(value) if i32::try_from(value).is_ok() => {
+                Self::add_constant(out, &dst_reg, &dst_reg, -value)
+            }
+            _ => {
+                let src_reg = self.in_register(out, src, "%r12");
+                emit!(out, "    subf {d}, {}, {d}\n", src_reg, d = dst_reg);
             }
-            return format!("{}    sub {rd}, {rd}, r11\n", self.emit_load_imm("r11", v));
         }
-        format!("    sub {rd}, {rd}, {s}\n")
     }
 
-    fn generate_mul(&self, dst: &str, src: &str) -> String {
-        let rd = self.map_operand(dst);
-        let rs = self.map_operand(src);
-        format!("    mulld {rd}, {rd}, {rs}\n")
+    fn generate_mul(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "mulld", Some(("mulli", -0x8000..=0x7fff)), dst, src);
     }
 
-    fn generate_div(&self, dst: &str, src: &str) -> String {
-        let rd = self.map_operand(dst);
-        let rs = self.map_operand(src);
-        format!("    divd {rd}, {rd}, {rs}\n")
+    fn generate_div(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "divd", None, dst, src);
     }
 
-    fn generate_inc(&self, dst: &str) -> String {
-        let rd = self.map_operand(dst);
-        format!("    addi {rd}, {rd}, 1\n")
+    fn generate_inc(&self, out: &mut String, dst: &str) {
+        self.generate_add(out, dst, "1");
     }
 
-    fn generate_dec(&self, dst: &str) -> String {
-        let rd = self.map_operand(dst);
-        format!("    addi {rd}, {rd}, -1\n")
+    fn generate_dec(&self, out: &mut String, dst: &str) {
+        self.generate_add(out, dst, "-1");
     }
 
-    fn generate_neg(&self, dst: &str) -> String {
-        let rd = self.map_operand(dst);
-        format!("    neg {rd}, {rd}\n")
+    fn generate_neg(&self, out: &mut String, dst: &str) {
+        self.unary(out, "neg", dst);
     }
 
-    fn generate_and(&self, dst: &str, src: &str) -> String {
-        let rd = self.map_operand(dst);
-        let s = self.map_operand(src);
-
-        if s.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            let v: u32 = s.parse().unwrap_or(0);
-            if v <= 65535 {
-                return format!("    andi. {rd}, {rd}, {v}\n");
-            }
-            return format!(
-                "{}    and {rd}, {rd}, r11\n",
-                self.emit_load_imm("r11", v as i64)
-            );
-        }
-        format!("    and {rd}, {rd}, {s}\n")
+    fn generate_and(&self, out: &mut String, dst: &str, src: &str) {
+        self.logical(out, "and", "andi.", "andis.", dst, src);
     }
 
-    fn generate_or(&self, dst: &str, src: &str) -> String {
-        let rd = self.map_operand(dst);
-        let s = self.map_operand(src);
-
-        if s.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            let v: u32 = s.parse().unwrap_or(0);
-            if v <= 65535 {
-                return format!("    ori {rd}, {rd}, {v}\n");
-            }
-            return format!(
-                "{}    or {rd}, {rd}, r11\n",
-                self.emit_load_imm("r11", v as i64)
-            );
-        }
-        format!("    or {rd}, {rd}, {s}\n")
+    fn generate_or(&self, out: &mut String, dst: &str, src: &str) {
+        self.logical(out, "or", "ori", "oris", dst, src);
     }
 
-    fn generate_xor(&self, dst: &str, src: &str) -> String {
-        let rd = self.map_operand(dst);
-        let s = self.map_operand(src);
+    fn generate_xor(&self, out: &mut String, dst: &str, src: &str) {
+        self.logical(out, "xor", "xori", "xoris", dst, src);
+    }
 
-        if s.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            let v: u32 = s.parse().unwrap_or(0);
-            if v <= 65535 {
-                return format!("    xori {rd}, {rd}, {v}\n");
-            }
-            return format!(
-                "{}    xor {rd}, {rd}, r11\n",
-                self.emit_load_imm("r11", v as i64)
-            );
-        }
-        format!("    xor {rd}, {rd}, {s}\n")
+    fn generate_not(&self, out: &mut String, dst: &str) {
+        self.unary(out, "not", dst);
     }
 
-    fn generate_not(&self, dst: &str) -> String {
-        let rd = self.map_operand(dst);
-        format!("    nor {rd}, {rd}, r0\n")
+    fn generate_shl(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "sld", "sldi", dst, src);
     }
 
-    fn generate_shl(&self, dst: &str, src: &str) -> String {
-        let rd = self.map_operand(dst);
-        let s = self.map_operand(src);
-        if s.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            return format!("    sldi {rd}, {rd}, {s}\n");
-        }
-        format!("    sld {rd}, {rd}, {s}\n")
+    fn generate_shr(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "srd", "srdi", dst, src);
     }
 
-    fn generate_shr(&self, dst: &str, src: &str) -> String {
-        let rd = self.map_operand(dst);
-        let s = self.map_operand(src);
-        if s.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            return format!("    srdi {rd}, {rd}, {s}\n");
-        }
-        format!("    srd {rd}, {rd}, {s}\n")
+    fn generate_cmp(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_cmp(&self, op1: &str, op2: &str) -> String {
-        let r1 = self.map_operand(op1);
-        let s2 = self.map_operand(op2);
-        if s2.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            return format!("    cmpdi {r1}, {s2}\n");
-        }
-        format!("    cmpd {r1}, {s2}\n")
+    fn generate_test(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_test(&self, op1: &str, op2: &str) -> String {
-        let r1 = self.map_operand(op1);
-        let s2 = self.map_operand(op2);
-        if s2.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            let v: i64 = s2.parse().unwrap_or(0);
-            if v >= 0 && v <= 65535 {
-                return format!("    andi. r11, {r1}, {v}\n");
-            }
-            return format!("{}    and. r11, {r1}, r11\n", self.emit_load_imm("r11", v));
+    fn generate_jmp(&self, out: &mut String, label: &str) {
+        let target = self.map_operand(label);
+        if Self::is_register(&target) {
+            emit!(out, "    mtctr {}\n    bctr\n", target);
+            return;
         }
-        format!("    and. r11, {r1}, {s2}\n")
+        emit!(out, "    b {}\n", label);
     }
 
-    fn generate_jmp(&self, label: &str) -> String {
-        format!("    b {label}\n")
+    fn generate_je(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
-    fn generate_je(&self, label: &str) -> String {
-        format!("    beq {label}\n")
-    }
-    fn generate_jne(&self, label: &str) -> String {
-        format!("    bne {label}\n")
+
+    fn generate_jne(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
-    fn generate_jg(&self, label: &str) -> String {
-        format!("    bgt {label}\n")
+
+    fn generate_jg(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
-    fn generate_jl(&self, label: &str) -> String {
-        format!("    blt {label}\n")
+
+    fn generate_jl(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
-    fn generate_jge(&self, label: &str) -> String {
-        format!("    bge {label}\n")
+
+    fn generate_jge(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
-    fn generate_jle(&self, label: &str) -> String {
-        format!("    ble {label}\n")
+
+    fn generate_jle(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_call(&self, func: &str) -> String {
-        format!("    bl {func}\n")
+    fn generate_call(&self, out: &mut String, func: &str) {
+        let frame = self.call_frame();
+        let target = self.map_operand(func);
+        if !Self::is_register(&target) {
+            // The linker turns the `nop` into the TOC restore when the call
+            // goes through a stub.
+            {
+                emit!(
+                    out,
+                    "    stdu %r1, -{f}(%r1)\n    bl {}\n    nop\n    addi %r1, %r1, {f}\n",
+                    func,
+                    f = frame
+                );
+                return;
+            };
+        }
+        let save = self.toc_save();
+        if self.elf_v2 {
+            // The global entry point computes the callee's TOC from r12.
+            if target != "%r12" {
+                emit!(out, "    mr %r12, {}\n", target);
+            }
+            emit!(
+                out,
+                "    mtctr %r12\n    stdu %r1, -{f}(%r1)\n    std %r2, {s}(%r1)\n    bctrl\n    ld %r2, {s}(%r1)\n    addi %r1, %r1, {f}\n",
+                f = frame,
+                s = save
+            );
+            return;
+        }
+        // A function descriptor: the entry point, the TOC and the environment.
+        emit!(
+            out,
+            "    ld %r0, 0({t})\n    mtctr %r0\n    stdu %r1, -{f}(%r1)\n    std %r2, {s}(%r1)\n    ld %r11, 16({t})\n    ld %r2, 8({t})\n    bctrl\n    ld %r2, {s}(%r1)\n    addi %r1, %r1, {f}\n",
+            t = target,
+            f = frame,
+            s = save
+        );
     }
-    fn generate_ret(&self) -> String {
-        "    blr\n".to_string()
+
+    fn generate_ret(&self, out: &mut String) {
+        out.push_str("    blr\n");
     }
 
-    fn generate_syscall(&self, name: &str) -> String {
-        let nr = match name {
+    fn generate_syscall(&self, out: &mut String, name: &str) {
+        let syscall_num = match name {
             "read" => "3",
             "write" => "4",
             "exit" => "1",
@@ -359,474 +750,696 @@ impl ArchCodeGen for PowerPC64CodeGen {
             "mmap" => "90",
             "munmap" => "91",
             "brk" => "45",
-            _ => "0",
+            _ => {
+                emit!(
+                    out,
+                    "    # Unknown syscall: {}\n    li %r0, 0\n    sc\n",
+                    name
+                );
+                return;
+            }
         };
-        format!("    addi r0, r0, {nr}\n    sc\n")
+        // The kernel flags errors in cr0.SO and returns a positive errno;
+        // negate it to match the other targets.
+        emit!(
+            out,
+            "    li %r0, {}\n    sc\n    bns+ 1f\n    neg %r3, %r3\n1:\n",
+            syscall_num
+        );
     }
 
     fn map_operand(&self, operand: &str) -> String {
         if operand.chars().all(|c| c.is_ascii_digit() || c == '-') {
             return operand.to_string();
         }
+
         if operand.starts_with('[') && operand.ends_with(']') {
             return self.map_memory_operand(operand);
         }
-        if let Some(m) = self.register_map.get(operand) {
-            m.clone()
+
+        if let Some(mapped) = self.register_map.get(operand) {
+            mapped.clone()
         } else {
             operand.to_string()
         }
     }
 
     fn map_memory_operand(&self, operand: &str) -> String {
-        if operand.starts_with('[') && operand.ends_with(']') {
-            let inner = &operand[1..operand.len() - 1].trim();
-
-            if inner.contains('+') {
-                let parts: Vec<&str> = inner.split('+').map(|s| s.trim()).collect();
-                if parts.len() == 2 {
-                    let base = self
-                        .register_map
-                        .get(parts[0])
-                        .cloned()
-                        .unwrap_or_else(|| parts[0].to_string());
-                    if parts[1].chars().all(|c| c.is_ascii_digit() || c == '-') {
-                        return format!("{}({})", parts[1], base);
-                    } else {
-                        return format!("0({})", base);
-                    }
-                }
-            } else if inner.contains('-') {
-                let parts: Vec<&str> = inner.split('-').map(|s| s.trim()).collect();
-                if parts.len() == 2 {
-                    let base = self
-                        .register_map
-                        .get(parts[0])
-                        .cloned()
-                        .unwrap_or_else(|| parts[0].to_string());
-                    if parts[1].chars().all(|c| c.is_ascii_digit()) {
-                        return format!("-{}({})", parts[1], base);
-                    }
-                }
-            }
-
-            if let Some(mapped) = self.register_map.get(inner.clone()) {
-                return format!("0({})", mapped);
+        if !(operand.starts_with('[') && operand.ends_with(']')) {
+            return operand.to_string();
+        }
+        let Ok(address) = self.split_address(operand) else {
+            return operand.to_string();
+        };
+        let base = address.base.unwrap_or_else(|| "0".to_string());
+        match (address.symbolic.is_empty(), address.displacement) {
+            (true, displacement) => format!("{}({})", displacement, base),
+            (false, 0) => format!("{}({})", address.symbolic, base),
+            (false, displacement) => {
+                format!("{} + {}({})", address.symbolic, displacement, base)
             }
-            return format!("0({})", inner);
         }
-        operand.to_string()
     }
 
-    fn generate_cmov_eq(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_eq(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
+    }
+
+    fn generate_cmov_ne(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
+    }
+
+    fn generate_cmov_lt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
+    }
+
+    fn generate_cmov_le(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
+    }
+
+    fn generate_cmov_gt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_cmov_ne(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_ge(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_cmov_lt(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_ov(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_cmov_le(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_no(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_cmov_gt(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_s(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_cmov_ge(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_ns(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_cmov_ov(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_p(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_cmov_no(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_np(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_cmov_s(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_a(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_cmov_ns(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_ae(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
+    }
+
+    fn generate_cmov_b(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
+    }
+
+    fn generate_cmov_be(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
+    }
+
+    fn generate_push(&self, out: &mut String, src: &str) {
+        // Pushes keep r1 16-byte aligned, as the ABI requires.
+        let src_reg = self.in_register(out, src, "%r12");
+        emit!(out, "    stdu {}, -16(%r1)\n", src_reg);
+    }
+
+    fn generate_pop(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
+            "    ld {}, 0(%r1)\n    addi %r1, %r1, 16\n",
+            self.map_operand(dst)
+        );
+    }
+
+    fn generate_pusha(&self, out: &mut String) {
+        let registers = self.saved_by_pusha();
+        let size = (registers.len() * 8).next_multiple_of(16);
+        emit!(out, "    addi %r1, %r1, -{}\n", size);
+        for (i, register) in registers.iter().enumerate() {
+            emit!(out, "    std {}, {}(%r1)\n", register, i * 8);
+        }
     }
 
-    fn generate_cmov_p(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_popa(&self, out: &mut String) {
+        let registers = self.saved_by_pusha();
+        let size = (registers.len() * 8).next_multiple_of(16);
+        for (i, register) in registers.iter().enumerate() {
+            emit!(out, "    ld {}, {}(%r1)\n", register, i * 8);
+        }
+        emit!(out, "    addi %r1, %r1, {}\n", size);
     }
 
-    fn generate_cmov_np(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_enter(&self, out: &mut String, frame_size: &str, _nesting_level: &str) {
+        // r31 points at the saved r31 and link register, like the frame
+        // pointer on ARM64.
+        out.push_str(
+            "    mflr %r0\n    std %r0, -8(%r1)\n    stdu %r31, -16(%r1)\n    mr %r31, %r1\n",
+        );
+        if opt::parse_immediate(frame_size) != Some(0) {
+            self.generate_sub(out, "sp", frame_size);
+        }
     }
 
-    fn generate_cmov_a(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_leave(&self, out: &mut String) {
+        out.push_str(
+            "    mr %r1, %r31\n    ld %r0, 8(%r1)\n    ld %r31, 0(%r1)\n    addi %r1, %r1, 16\n    mtlr %r0\n",
+        );
     }
 
-    fn generate_cmov_ae(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_imul(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_mul(out, dst, src);
     }
 
-    fn generate_cmov_b(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_idiv(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_div(out, dst, src);
     }
 
-    fn generate_cmov_be(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_mod(&self, out: &mut String, dst: &str, src: &str) {
+        // dst - dst / src * src
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.in_register(out, src, "%r12");
+        emit!(
+            out,
+            "    divd %r0, {d}, {s}\n    mulld %r0, %r0, {s}\n    subf {d}, %r0, {d}\n",
+            d = dst_reg,
+            s = src_reg
+        );
     }
 
-    fn generate_push(&self, src: &str) -> String {
-        todo!()
+    fn generate_mulh(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "mulhd", None, dst, src);
     }
 
-    fn generate_pop(&self, dst: &str) -> String {
-        todo!()
+    fn generate_andn(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(value) = Self::immediate(src) {
+            self.generate_and(out, dst, &(!value).to_string());
+            return;
+        }
+        self.binary(out, "andc", None, dst, src);
     }
 
-    fn generate_pusha(&self) -> String {
-        todo!()
+    fn generate_sal(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_shl(out, dst, src);
     }
 
-    fn generate_popa(&self) -> String {
-        todo!()
+    fn generate_sar(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "srad", "sradi", dst, src);
     }
 
-    fn generate_enter(&self, frame_size: &str, nesting_level: &str) -> String {
-        todo!()
+    fn generate_rol(&self, out: &mut String, dst: &str, src: &str) {
+        self.rotate(out, true, dst, src);
     }
 
-    fn generate_leave(&self) -> String {
-        todo!()
+    fn generate_ror(&self, out: &mut String, dst: &str, src: &str) {
+        self.rotate(out, false, dst, src);
     }
 
-    fn generate_imul(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_rcl(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_idiv(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_rcr(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_mod(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_bextr(&self, out: &mut String, dst: &str, src: &str, imm: &str) {
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.in_register(out, src, "%r12");
+        if let Some(control) = Self::immediate(imm) {
+            let start = control & 0xff;
+            let length = ((control >> 8) & 0xff).min(64 - start.min(64));
+            if length == 0 {
+                emit!(out, "    li {}, 0\n", dst_reg);
+                return;
+            }
+            emit!(
+                out,
+                "    rldicl {}, {}, {}, {}\n",
+                dst_reg,
+                src_reg,
+                (64 - start) & 63,
+                64 - length
+            );
+            return;
+        }
+        // Shift the field to the top, then back down by 64 - length.
+        let control_reg = self.in_register(out, imm, "%r11");
+        emit!(
+            out,
+            "    clrldi %r0, {c}, 56\n    srd %r0, {}, %r0\n    rldicl %r11, {c}, 56, 56\n    subfic %r11, %r11, 64\n    sld %r0, %r0, %r11\n    srd {}, %r0, %r11\n",
+            src_reg,
+            dst_reg,
+            c = control_reg
+        );
     }
 
-    fn generate_andn(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_bsf(&self, out: &mut String, dst: &str, src: &str) {
+        // The lowest set bit alone is its own highest: 63 minus its leading
+        // zeros, or -1 when there is none.
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.in_register(out, src, "%r12");
+        emit!(
+            out,
+            "    neg %r0, {s}\n    and %r0, %r0, {s}\n    cntlzd %r0, %r0\n    subfic {}, %r0, 63\n",
+            dst_reg,
+            s = src_reg
+        );
     }
 
-    fn generate_sal(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_bsr(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.in_register(out, src, "%r12");
+        emit!(
+            out,
+            "    cntlzd %r0, {}\n    subfic {}, %r0, 63\n",
+            src_reg,
+            dst_reg
+        );
     }
 
-    fn generate_sar(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_bt(&self, _out: &mut String, _dst: &str, _bit: &str) {}
+
+    fn generate_btr(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "andc", dst, bit);
     }
 
-    fn generate_rol(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_bts(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "or", dst, bit);
     }
 
-    fn generate_ror(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_btc(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "xor", dst, bit);
     }
 
-    fn generate_rcl(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_set_eq(&self, _out: &mut String, _dst: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_rcr(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_set_ne(&self, _out: &mut String, _dst: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_bextr(&self, dst: &str, src: &str, imm: &str) -> String {
-        todo!()
+    fn generate_set_lt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_bsf(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_set_le(&self, _out: &mut String, _dst: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_bsr(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_set_gt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_bt(&self, dst: &str, bit: &str) -> String {
-        todo!()
+    fn generate_set_ge(&self, _out: &mut String, _dst: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_btr(&self, dst: &str, bit: &str) -> String {
-        todo!()
+    fn generate_set_ov(&self, _out: &mut String, _dst: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_bts(&self, dst: &str, bit: &str) -> String {
-        todo!()
+    fn generate_set_no(&self, _out: &mut String, _dst: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_btc(&self, dst: &str, bit: &str) -> String {
-        todo!()
+    fn generate_set_s(&self, _out: &mut String, _dst: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_set_eq(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_ns(&self, _out: &mut String, _dst: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_set_ne(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_p(&self, _out: &mut String, _dst: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_set_lt(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_np(&self, _out: &mut String, _dst: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_set_le(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_a(&self, _out: &mut String, _dst: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_set_gt(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_ae(&self, _out: &mut String, _dst: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_set_ge(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_b(&self, _out: &mut String, _dst: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_set_ov(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_be(&self, _out: &mut String, _dst: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_set_no(&self, dst: &str) -> String {
-        todo!()
+    fn generate_cmps(&self, _out: &mut String, _src1: &str, _src2: &str) {}
+
+    fn generate_scas(&self, _out: &mut String, _src: &str, _val: &str) {}
+
+    fn generate_stos(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_store(out, dst, src);
     }
 
-    fn generate_set_s(&self, dst: &str) -> String {
-        todo!()
+    fn generate_lods(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_load(out, dst, src);
     }
 
-    fn generate_set_ns(&self, dst: &str) -> String {
-        todo!()
+    fn generate_movs(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_load(out, "%r12", src);
+        self.generate_store(out, dst, "%r12");
     }
 
-    fn generate_set_p(&self, dst: &str) -> String {
-        todo!()
+    fn generate_cbw(&self, out: &mut String, dst: &str) {
+        self.unary(out, "extsb", dst);
     }
 
-    fn generate_set_np(&self, dst: &str) -> String {
-        todo!()
+    fn generate_cwd(&self, out: &mut String, dst: &str) {
+        self.unary(out, "extsh", dst);
     }
 
-    fn generate_set_a(&self, dst: &str) -> String {
-        todo!()
+    fn generate_cdq(&self, out: &mut String, dst: &str) {
+        self.unary(out, "extsw", dst);
     }
 
-    fn generate_set_ae(&self, dst: &str) -> String {
-        todo!()
+    fn generate_cqo(&self, out: &mut String, dst: &str) {
+        // The high half of the 128-bit sign extension: all sign bits.
+        let dst_reg = self.map_operand(dst);
+        emit!(out, "    sradi {}, {}, 63\n", dst_reg, dst_reg);
     }
 
-    fn generate_set_b(&self, dst: &str) -> String {
-        todo!()
+    fn generate_cwde(&self, out: &mut String, dst: &str) {
+        self.unary(out, "extsh", dst);
     }
 
-    fn generate_set_be(&self, dst: &str) -> String {
-        todo!()
+    fn generate_cdqe(&self, out: &mut String, dst: &str) {
+        self.unary(out, "extsw", dst);
     }
 
-    fn generate_cmps(&self, src1: &str, src2: &str) -> String {
-        todo!()
+    fn generate_jo(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_scas(&self, src: &str, val: &str) -> String {
-        todo!()
+    fn generate_jno(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_stos(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_js(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_lods(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_jns(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_movs(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_jp(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_cbw(&self, dst: &str) -> String {
-        todo!()
+    fn generate_jnp(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_cwd(&self, dst: &str) -> String {
-        todo!()
+    fn generate_ja(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_cdq(&self, dst: &str) -> String {
-        todo!()
+    fn generate_jae(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_cqo(&self, dst: &str) -> String {
-        todo!()
+    fn generate_jb(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_cwde(&self, dst: &str) -> String {
-        todo!()
+    fn generate_jbe(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_cdqe(&self, dst: &str) -> String {
-        todo!()
+    fn generate_loop_eq(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_jo(&self, label: &str) -> String {
-        todo!()
+    fn generate_loop_ne(&self, _out: &mut String, _label: &str) {
+        unreachable!("PowerPC64 compares in each reader")
     }
 
-    fn generate_jno(&self, label: &str) -> String {
-        todo!()
+    fn generate_in(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("    # PowerPC has no IN instruction, not supported.\n");
     }
 
-    fn generate_js(&self, label: &str) -> String {
-        todo!()
+    fn generate_out(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("    # PowerPC has no OUT instruction, not supported.\n");
     }
 
-    fn generate_jns(&self, label: &str) -> String {
-        todo!()
+    fn generate_ins(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("    # PowerPC has no INS instruction, not supported.\n");
     }
 
-    fn generate_jp(&self, label: &str) -> String {
-        todo!()
+    fn generate_outs(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("    # PowerPC has no OUTS instruction, not supported.\n");
     }
 
-    fn generate_jnp(&self, label: &str) -> String {
-        todo!()
+    fn generate_cpuid(&self, out: &mut String) {
+        out.push_str("    # PowerPC does not have CPUID\n");
     }
 
-    fn generate_ja(&self, label: &str) -> String {
-        todo!()
+    fn generate_lfence(&self, out: &mut String) {
+        out.push_str("    lwsync\n");
     }
 
-    fn generate_jae(&self, label: &str) -> String {
-        todo!()
+    fn generate_sfence(&self, out: &mut String) {
+        out.push_str("    lwsync\n");
     }
 
-    fn generate_jb(&self, label: &str) -> String {
-        todo!()
+    fn generate_mfence(&self, out: &mut String) {
+        out.push_str("    sync\n");
     }
 
-    fn generate_jbe(&self, label: &str) -> String {
-        todo!()
+    fn generate_prefetch(&self, out: &mut String, addr: &str) {
+        self.cache(out, "dcbt", addr);
     }
 
-    fn generate_loop_eq(&self, label: &str) -> String {
-        todo!()
+    fn generate_clflush(&self, out: &mut String, addr: &str) {
+        self.cache(out, "dcbf", addr);
     }
 
-    fn generate_loop_ne(&self, label: &str) -> String {
-        todo!()
+    fn generate_clwb(&self, out: &mut String, addr: &str) {
+        self.cache(out, "dcbst", addr);
     }
 
-    fn generate_in(&self, dst: &str, port: &str) -> String {
-        todo!()
+    fn generate_global(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".globl {}\n", symbol);
     }
 
-    fn generate_out(&self, port: &str, src: &str) -> String {
-        todo!()
+    fn generate_extern(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".extern {}\n", symbol);
     }
 
-    fn generate_ins(&self, dst: &str, port: &str) -> String {
-        todo!()
+    fn generate_align(&self, out: &mut String, n: &str) {
+        emit!(out, ".p2align {}\n", n);
     }
 
-    fn generate_outs(&self, port: &str, src: &str) -> String {
-        todo!()
+    fn generate_data_byte(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .byte {}\n", name, values.join(", "));
     }
 
-    fn generate_cpuid(&self) -> String {
-        todo!()
+    fn generate_data_word(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .short {}\n", name, values.join(", "));
     }
 
-    fn generate_lfence(&self) -> String {
-        todo!()
+    fn generate_data_dword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .long {}\n", name, values.join(", "));
     }
 
-    fn generate_sfence(&self) -> String {
-        todo!()
+    fn generate_data_qword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .quad {}\n", name, values.join(", "));
     }
 
-    fn generate_mfence(&self) -> String {
-        todo!()
+    fn generate_reserve_byte(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip {}\n", name, count);
     }
 
-    fn generate_prefetch(&self, addr: &str) -> String {
-        todo!()
+    fn generate_reserve_word(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 2 * ({})\n", name, count);
     }
 
-    fn generate_clflush(&self, addr: &str) -> String {
-        todo!()
+    fn generate_reserve_dword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 4 * ({})\n", name, count);
     }
 
-    fn generate_clwb(&self, addr: &str) -> String {
-        todo!()
+    fn generate_reserve_qword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 8 * ({})\n", name, count);
     }
 
-    fn generate_global(&self, symbol: &str) -> String {
-        todo!()
+    fn generate_equ(&self, out: &mut String, name: &str, value: &str) {
+        emit!(out, ".set {}, {}\n", name, value);
     }
 
-    fn generate_extern(&self, symbol: &str) -> String {
-        todo!()
+    fn generate_section(&self, out: &mut String, section: &Section) {
+        match section {
+            Section::Text => out.push_str(".section .text\n"),
+            Section::Data => out.push_str(".section .data\n"),
+            Section::Bss => out.push_str(".section .bss\n"),
+            Section::Rodata => out.push_str(".section .rodata\n"),
+            Section::Custom(s) => emit!(out, ".section {}\n", s),
+        }
     }
 
-    fn generate_align(&self, n: &str) -> String {
-        todo!()
+    fn generate_label(&self, out: &mut String, name: &str) {
+        emit!(out, "{}:\n", name);
     }
 
-    fn generate_data_byte(&self, name: &str, values: &[String]) -> String {
-        todo!()
+    fn generate_function_label(&self, out: &mut String, symbol: &str) {
+        if self.elf_v2 {
+            // Callers from other modules enter with the function's address in
+            // r12; the rest skip to the local entry point.
+            {
+                emit!(
+                    out,
+                    "{s}:\n    addis %r2, %r12, .TOC.-{s}@ha\n    addi %r2, %r2, .TOC.-{s}@l\n    .localentry {s}, .-{s}\n",
+                    s = symbol
+                );
+                return;
+            };
+        }
+        // The symbol names a descriptor of the code and the TOC it runs with.
+        emit!(
+            out,
+            "    .section \".opd\", \"aw\"\n    .p2align 3\n{s}:\n    .quad .L.{s}, .TOC.@tocbase, 0\n    .previous\n.L.{s}:\n",
+            s = symbol
+        );
     }
 
-    fn generate_data_word(&self, name: &str, values: &[String]) -> String {
-        todo!()
+    fn get_register_width(&self) -> u32 {
+        64
     }
 
-    fn generate_data_dword(&self, name: &str, values: &[String]) -> String {
-        todo!()
+    fn get_reserved_registers(&self) -> Vec<String> {
+        ["%r0", "%r1", "%r2", "%r11", "%r12", "%r13", "%r31", "%lr"]
+            .iter()
+            .map(|r| r.to_string())
+            .collect()
     }
 
-    fn generate_data_qword(&self, name: &str, values: &[String]) -> String {
-        todo!()
+    fn get_callee_saved_registers(&self) -> Vec<String> {
+        (14..=31).map(|n| format!("%r{}", n)).collect()
     }
 
-    fn generate_reserve_byte(&self, name: &str, count: &str) -> String {
-        todo!()
+    fn get_push_size(&self) -> u32 {
+        16
     }
 
-    fn generate_reserve_word(&self, name: &str, count: &str) -> String {
-        todo!()
+    fn get_clobbered_registers(&self, instruction: &Instruction) -> Vec<String> {
+        use Instruction::*;
+        let registers: &[&str] = match instruction {
+            Syscall(_) => &[
+                "%r0", "%r3", "%r4", "%r5", "%r6", "%r7", "%r8", "%r9", "%r10", "%r11", "%r12",
+            ],
+            Call(_) => &[
+                "%lr", "%ctr", "%r0", "%r3", "%r4", "%r5", "%r6", "%r7", "%r8", "%r9", "%r10",
+                "%r11", "%r12",
+            ],
+            _ => &[],
+        };
+        registers.iter().map(|r| r.to_string()).collect()
+    }
+
+    fn generate_branch_hint(&self, out: &mut String, from: usize, likely: bool) {
+        // `beq+ label` predicts taken, `beq- label` not taken.
+        let hint = if likely { "+" } else { "-" };
+        let branch = out.split_off(from);
+        for line in branch.lines() {
+            let indent = line.len() - line.trim_start().len();
+            let end = line[indent..]
+                .find(char::is_whitespace)
+                .map_or(line.len(), |n| indent + n);
+            let mnemonic = &line[indent..end];
+            if mnemonic.starts_with('b')
+                && !matches!(mnemonic, "b" | "bl" | "blr" | "bctr" | "bctrl")
+            {
+                emit!(out, "{}{}{}\n", &line[..end], hint, &line[end..]);
+            } else {
+                emit!(out, "{line}\n");
+            }
+        }
     }
 
-    fn generate_reserve_dword(&self, name: &str, count: &str) -> String {
-        todo!()
+    fn set_position_independent(&mut self, pic: Pic) -> Result<(), String> {
+        self.pic = Some(pic);
+        Ok(())
     }
 
-    fn generate_reserve_qword(&self, name: &str, count: &str) -> String {
-        todo!()
+    fn requires_position_independence(&self) -> bool {
+        true
     }
 
-    fn generate_equ(&self, name: &str, value: &str) -> String {
-        todo!()
+    fn has_condition_flags(&self) -> bool {
+        false
+    }
+    fn compares(&self, tested: Tested) -> bool {
+        matches!(
+            tested,
+            Tested::Operands | Tested::Mask | Tested::Sum | Tested::Difference
+        )
     }
 
-    fn generate_section(&self, section: &Section) -> String {
-        todo!()
+    fn generate_compare_branch(&self, out: &mut String, comparison: &Comparison, label: &str) {
+        let mnemonic = match Self::condition_bit(comparison.condition, comparison.tested) {
+            (0, false) => "blt",
+            (0, true) => "bge",
+            (1, false) => "bgt",
+            (1, true) => "ble",
+            (2, false) => "beq",
+            (2, true) => "bne",
+            (_, false) => "bso",
+            (_, true) => "bns",
+        };
+        self.compare(out, comparison);
+        emit!(out, "    {} {}\n", mnemonic, label);
+    }
+
+    fn generate_compare_set(&self, out: &mut String, comparison: &Comparison, dst: &str) {
+        // Rotate the cr0 bit from the top of the condition register to the
+        // bottom, and clear the rest.
+        let (bit, inverted) = Self::condition_bit(comparison.condition, comparison.tested);
+        let dst_reg = self.map_operand(dst);
+        self.compare(out, comparison);
+        emit!(
+            out,
+            "    mfcr %r0\n    rlwinm {}, %r0, {}, 31, 31\n",
+            dst_reg,
+            bit + 1
+        );
+        if inverted {
+            emit!(out, "    xori {d}, {d}, 1\n", d = dst_reg);
+        }
     }
 
-    fn generate_label(&self, name: &str) -> String {
-        todo!()
+    fn generate_compare_cmov(
+        &self,
+        out: &mut String,
+        comparison: &Comparison,
+        dst: &str,
+        src: &str,
+    ) {
+        // Branch around the move when the condition fails.
+        let skip = Comparison {
+            condition: comparison.condition.negate(),
+            ..comparison.clone()
+        };
+        self.generate_compare_branch(out, &skip, "1f");
+        self.generate_mov(out, dst, src);
+        out.push_str("1:\n");
     }
 }
@@ -3,6 +3,13 @@ use std::collections::HashMap;
 
 pub struct RISCVCodeGen {
     register_map: HashMap<String, String>,
+    /// Let the assembler use 16-bit encodings (the C extension).
+    compressed: bool,
+    /// Symbols of the program, once code is position-independent.
+    pic: Option<Pic>,
+    /// Use the Zbb bit-manipulation extension for rotates, bit scans, `andn`
+    /// and sign extension.
+    zbb: bool,
 }
 
 impl RISCVCodeGen {
@@ -25,21 +32,376 @@ impl RISCVCodeGen {
         register_map.insert("r10".to_string(), "t2".to_string()); // Temporary
         register_map.insert("r11".to_string(), "t3".to_string()); // Temporary
         register_map.insert("r12".to_string(), "t4".to_string()); // Temporary
-        register_map.insert("r13".to_string(), "t5".to_string()); // Temporary
-        register_map.insert("r14".to_string(), "t6".to_string()); // Temporary
 
-        // Saved registers
-        register_map.insert("r19".to_string(), "s0".to_string()); // Saved/frame pointer
-        register_map.insert("r20".to_string(), "s1".to_string()); // Saved
-        register_map.insert("r21".to_string(), "s2".to_string()); // Saved
-        register_map.insert("r22".to_string(), "s3".to_string()); // Saved
+        // Saved registers; t5 and t6 are the backend's own.
+        for n in 13..=23 {
+            register_map.insert(format!("r{}", n), format!("s{}", n - 12));
+        }
 
         // Special purpose registers
         register_map.insert("sp".to_string(), "sp".to_string()); // Stack pointer
         register_map.insert("sb".to_string(), "s0".to_string()); // Frame pointer
         register_map.insert("ip".to_string(), "ra".to_string()); // Return address
 
-        RISCVCodeGen { register_map }
+        RISCVCodeGen {
+            register_map,
+            compressed: false,
+            pic: None,
+            zbb: false,
+        }
+    }
+
+    pub fn compressed() -> Self {
+        RISCVCodeGen {
+            compressed: true,
+            ..Self::new()
+        }
+    }
+
+    /// Registers holding the two sides of `comparison`, once they are
+    /// there: `zero` for 0, t6 for other constants and for what `tested`
+    /// derives, with t5 to work it out. t5 keeps the carry of `rcl` and
+    /// `rcr`; nothing else needs it while that carry can still be read.
+    fn compared(&self, out: &mut String, comparison: &Comparison) -> (String, String) {
+        if comparison.tested == Tested::Carry {
+            return ("t5".to_string(), "zero".to_string());
+        }
+        let a = self.map_operand(&comparison.a);
+        let b = if crate::core::opt::parse_immediate(&comparison.b) == Some(0) {
+            "zero".to_string()
+        } else {
+            let b = self.map_operand(&comparison.b);
+            if self.register_map.values().any(|r| *r == b) {
+                b
+            } else {
+                emit!(out, "    li t6, {}\n", b);
+                "t6".to_string()
+            }
+        };
+        match comparison.tested {
+            Tested::Operands => return (a, b),
+            Tested::Mask => emit!(out, "    and t6, {}, {}\n", a, b),
+            Tested::Bit => emit!(out, "    srl t6, {}, {}\n    andi t6, t6, 1\n", a, b),
+            // The operand `a` held before is `a - b` for a sum and `a + b`
+            // for a difference, and the signs of the three tell the overflow.
+            Tested::Sum => emit!(
+                out,
+                "    sub t5, {a}, {b}\n    xor t5, t5, {a}\n    xor t6, {b}, {a}\n    and t6, t5, t6\n",
+                a = a,
+                b = b
+            ),
+            Tested::Difference => emit!(
+                out,
+                "    add t5, {a}, {b}\n    xor t6, t5, {b}\n    xor t5, t5, {a}\n    and t6, t5, t6\n",
+                a = a,
+                b = b
+            ),
+            Tested::Parity => {
+                if a == b {
+                    emit!(out, "    andi t6, {}, 255\n", a);
+                } else {
+                    emit!(out, "    and t6, {}, {}\n    andi t6, t6, 255\n", a, b);
+                }
+                if self.zbb {
+                    out.push_str("    cpop t6, t6\n");
+                } else {
+                    for shift in [4, 2, 1] {
+                        emit!(out, "    srli t5, t6, {}\n    xor t6, t6, t5\n", shift);
+                    }
+                }
+                out.push_str("    andi t6, t6, 1\n");
+            }
+            Tested::Carry => unreachable!("the carry is already in t5"),
+        }
+        ("t6".to_string(), "zero".to_string())
+    }
+
+    /// Rotates `dst` left or right by `count` through the carry `comparison`
+    /// tests, leaving the bit rotated out in t5.
+    fn rotate_through(
+        &self,
+        out: &mut String,
+        left: bool,
+        comparison: &Comparison,
+        dst: &str,
+        count: &str,
+    ) {
+        let count = opt::parse_immediate(count).expect("`opt::flags` allows constant counts") & 63;
+        let dst_reg = self.map_operand(dst);
+        self.generate_compare_set(out, comparison, "t6");
+        // With the carry, the rotate is one of 65 bits: the carry lands next
+        // to the bits `dst` keeps, and those it shifts out past the carry
+        // come back at the other end.
+        let (keep, back, carry, out_at) = if left {
+            ("sll", "srl", count - 1, 64 - count)
+        } else {
+            ("srl", "sll", 64 - count, count - 1)
+        };
+        if carry > 0 {
+            emit!(out, "    slli t6, t6, {}\n", carry);
+        }
+        if count > 1 {
+            emit!(
+                out,
+                "    {}i t5, {}, {}\n    or t6, t6, t5\n",
+                back,
+                dst_reg,
+                65 - count
+            );
+        }
+        match out_at {
+            0 => emit!(out, "    andi t5, {}, 1\n", dst_reg),
+            63 => emit!(out, "    srli t5, {}, 63\n", dst_reg),
+            _ => emit!(
+                out,
+                "    srli t5, {}, {}\n    andi t5, t5, 1\n",
+                dst_reg,
+                out_at
+            ),
+        }
+        emit!(
+            out,
+            "    {}i {d}, {d}, {}\n    or {d}, {d}, t6\n",
+            keep,
+            count,
+            d = dst_reg
+        );
+    }
+
+    /// The address of `symbol + offset` in `dst`, relative to pc, or loaded
+    /// from the GOT if another module may provide the symbol.
+    fn address(&self, out: &mut String, pic: &Pic, dst: &str, symbol: &str, offset: i64) {
+        if !pic.is_preemptible(symbol) {
+            emit!(
+                out,
+                "    lla {}, {}\n",
+                dst,
+                Pic::expression(symbol, offset)
+            );
+            return;
+        }
+        emit!(
+            out,
+            "1:\n    auipc {}, %got_pcrel_hi({})\n    ld {}, %pcrel_lo(1b)({})\n",
+            dst,
+            symbol,
+            dst,
+            dst
+        );
+        // `addi` takes 12-bit immediates.
+        let mut rest = offset;
+        while rest != 0 {
+            let step = rest.clamp(-2048, 2047);
+            emit!(out, "    addi {}, {}, {}\n", dst, dst, step);
+            rest -= step;
+        }
+    }
+
+    /// Whether `operand` names a RISC-V register.
+    fn is_register(operand: &str) -> bool {
+        let numbered = |prefix: &str, last: u32| {
+            operand
+                .strip_prefix(prefix)
+                .filter(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+                .and_then(|n| n.parse::<u32>().ok())
+                .is_some_and(|n| n <= last)
+        };
+        matches!(operand, "zero" | "ra" | "sp" | "gp" | "tp" | "fp")
+            || numbered("x", 31)
+            || numbered("a", 7)
+            || numbered("s", 11)
+            || numbered("t", 6)
+    }
+
+    /// The register holding `operand`, once it is there: `zero` for 0, t6 for
+    /// other constants and for symbols.
+    fn in_register(&self, out: &mut String, operand: &str) -> String {
+        let mapped = self.map_operand(operand);
+        if Self::is_register(&mapped) {
+            mapped
+        } else if opt::parse_immediate(operand) == Some(0) {
+            "zero".to_string()
+        } else {
+            self.generate_mov(out, "t6", operand);
+            "t6".to_string()
+        }
+    }
+
+    /// Whether the memory operand `operand` is based on a symbol rather than a
+    /// register, so accessing it takes t6 for the address.
+    fn is_symbolic(&self, operand: &str) -> bool {
+        if let Some(pic) = &self.pic
+            && pic.address(operand).is_some()
+        {
+            return true;
+        }
+        let inner = operand
+            .strip_prefix('[')
+            .and_then(|inner| inner.strip_suffix(']'))
+            .unwrap_or(operand)
+            .trim();
+        let base = inner
+            .split(['+', '-'])
+            .next()
+            .map(str::trim)
+            .unwrap_or(inner);
+        !base.is_empty() && !Self::is_register(&self.map_operand(base))
+    }
+
+    /// `op dst, dst, src`, or its immediate form when `immediate` and `src`
+    /// is a constant that fits in 12 bits.
+    fn binary(&self, out: &mut String, op: &str, immediate: bool, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        if immediate
+            && let Some(value) = opt::parse_immediate(src)
+            && (-2048..=2047).contains(&value)
+        {
+            emit!(out, "    {}i {}, {}, {}\n", op, dst_reg, dst_reg, value);
+            return;
+        }
+        let src_reg = self.in_register(out, src);
+        emit!(out, "    {} {}, {}, {}\n", op, dst_reg, dst_reg, src_reg);
+    }
+
+    /// A shift of `dst` by `count`, which x86 and RISC-V both take modulo 64.
+    fn shift(&self, out: &mut String, op: &str, dst: &str, count: &str) {
+        match opt::parse_immediate(count) {
+            Some(count) => {
+                let dst_reg = self.map_operand(dst);
+                emit!(
+                    out,
+                    "    {}i {}, {}, {}\n",
+                    op,
+                    dst_reg,
+                    dst_reg,
+                    count & 63
+                );
+            }
+            None => self.binary(out, op, false, dst, count),
+        }
+    }
+
+    /// A rotate of `dst` by `count`: `rori`, `rol` and `ror` with Zbb, two
+    /// shifts otherwise.
+    fn rotate(&self, out: &mut String, left: bool, dst: &str, count: &str) {
+        let dst_reg = self.map_operand(dst);
+        let count_reg = self.map_operand(count);
+        if Self::is_register(&count_reg) {
+            if self.zbb {
+                let op = if left { "rol" } else { "ror" };
+                emit!(out, "    {} {}, {}, {}\n", op, dst_reg, dst_reg, count_reg);
+                return;
+            }
+            // The bits shifted out come back from a shift by `-count`, which
+            // shifts by 64 - count.
+            let (first, second) = if left { ("srl", "sll") } else { ("sll", "srl") };
+            emit!(
+                out,
+                "    neg t6, {c}\n    {} t6, {d}, t6\n    {} {d}, {d}, {c}\n    or {d}, {d}, t6\n",
+                first,
+                second,
+                d = dst_reg,
+                c = count_reg
+            );
+            return;
+        }
+        // Constants, including symbols the assembler knows the value of.
+        let right = match opt::parse_immediate(count) {
+            Some(count) if left => ((64 - (count & 63)) & 63).to_string(),
+            Some(count) => (count & 63).to_string(),
+            None if left => format!("(-({})) & 63", count_reg),
+            None => format!("({}) & 63", count_reg),
+        };
+        if right == "0" {
+            return;
+        }
+        if self.zbb {
+            emit!(out, "    rori {}, {}, {}\n", dst_reg, dst_reg, right);
+            return;
+        }
+        let back = match right.parse::<i64>() {
+            Ok(right) => (64 - right).to_string(),
+            Err(_) => format!("(-({})) & 63", right),
+        };
+        emit!(
+            out,
+            "    slli t6, {d}, {}\n    srli {d}, {d}, {}\n    or {d}, {d}, t6\n",
+            back,
+            right,
+            d = dst_reg
+        );
+    }
+
+    /// `op dst, dst, mask` for the single-bit mask `bit` selects, inverted
+    /// when `clear`.
+    fn bit(&self, out: &mut String, op: &str, clear: bool, dst: &str, bit: &str) {
+        let dst_reg = self.map_operand(dst);
+        let bit_reg = self.map_operand(bit);
+        match opt::parse_immediate(bit) {
+            Some(bit) => {
+                let mask = 1i64 << (bit & 63);
+                let mask = if clear { !mask } else { mask };
+                if (-2048..=2047).contains(&mask) {
+                    emit!(out, "    {}i {}, {}, {}\n", op, dst_reg, dst_reg, mask);
+                    return;
+                }
+                emit!(out, "    li t6, {}\n", mask);
+            }
+            None if Self::is_register(&bit_reg) => {
+                emit!(out, "    li t6, 1\n    sll t6, t6, {}\n", bit_reg);
+            }
+            None => emit!(out, "    li t6, 1 << (({}) & 63)\n", bit_reg),
+        }
+        if clear && opt::parse_immediate(bit).is_none() {
+            if self.zbb {
+                emit!(out, "    andn {}, {}, t6\n", dst_reg, dst_reg);
+                return;
+            }
+            out.push_str("    not t6, t6\n");
+        }
+        emit!(out, "    {} {}, {}, t6\n", op, dst_reg, dst_reg);
+    }
+
+    /// Counts the shifts that empty t6 into `dst`, starting from -1: the
+    /// index of its highest set bit, or -1 when it has none.
+    fn highest_bit(out: &mut String, dst: &str) {
+        emit!(
+            out,
+            "    li {d}, -1\n1:\n    beqz t6, 2f\n    srli t6, t6, 1\n    addi {d}, {d}, 1\n    j 1b\n2:\n",
+            d = dst
+        );
+    }
+
+    /// The registers `pusha` saves and `popa` restores: every one the
+    /// register map names but sp, in a fixed order.
+    fn saved_by_pusha(&self) -> Vec<&String> {
+        let mut registers: Vec<&String> =
+            self.register_map.values().filter(|r| *r != "sp").collect();
+        registers.sort();
+        registers.dedup();
+        registers
+    }
+
+    /// Sign-extends the low `bits` bits of `dst` in place.
+    fn extend(&self, out: &mut String, dst: &str, bits: u32) {
+        let dst_reg = self.map_operand(dst);
+        match bits {
+            32 => emit!(out, "    sext.w {}, {}\n", dst_reg, dst_reg),
+            8 if self.zbb => emit!(out, "    sext.b {}, {}\n", dst_reg, dst_reg),
+            16 if self.zbb => emit!(out, "    sext.h {}, {}\n", dst_reg, dst_reg),
+            _ => emit!(
+                out,
+                "    slli {d}, {d}, {s}\n    srai {d}, {d}, {s}\n",
+                d = dst_reg,
+                s = 64 - bits
+            ),
+        }
+    }
+}
+
+impl Default for RISCVCodeGen {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -49,383 +411,281 @@ impl ArchCodeGen for RISCVCodeGen {
     }
 
     fn get_syntax_header(&self) -> String {
-        ".text\n.align 2\n\n".to_string()
+        if self.compressed {
+            ".option rvc\n.text\n.align 2\n\n".to_string()
+        } else {
+            ".text\n.align 2\n\n".to_string()
+        }
     }
 
-    fn generate_mov(&self, dst: &str, src: &str) -> String {
+    fn generate_mov(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
         let src_op = self.map_operand(src);
 
-        if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            let value: i64 = src_op.parse().unwrap_or(0);
-            if value >= -2048 && value <= 2047 {
-                return format!("    addi {}, zero, {}\n", dst_reg, src_op);
-            } else {
-                let upper = (value + 0x800) >> 12;
-                let lower = value & 0xfff;
-                if lower == 0 {
-                    return format!("    lui {}, {}\n", dst_reg, upper);
-                } else {
-                    return format!(
-                        "    lui {}, {}\n    addi {}, {}, {}\n",
-                        dst_reg, upper, dst_reg, dst_reg, lower as i32 as i16
-                    );
-                }
+        if let Some(value) = opt::parse_immediate(src) {
+            if (-2048..=2047).contains(&value) {
+                emit!(out, "    addi {}, zero, {}\n", dst_reg, value);
+                return;
             }
+            emit!(out, "    li {}, {}\n", dst_reg, value);
+            return;
         }
 
-        if src_op.starts_with('x')
-            || src_op == "sp"
-            || src_op.starts_with('a')
-            || src_op.starts_with('t')
-            || src_op.starts_with('s')
-            || src_op == "ra"
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(src)
         {
-            return format!("    mv {}, {}\n", dst_reg, src_op);
+            self.address(out, pic, &dst_reg, symbol, offset);
+            return;
+        }
+
+        if Self::is_register(&src_op) {
+            emit!(out, "    mv {}, {}\n", dst_reg, src_op);
+            return;
         }
 
         let is_label = !src_op
             .chars()
             .all(|c| c.is_ascii_alphanumeric() || c == '_');
         if is_label {
-            return format!("    la {}, {}\n", dst_reg, src_op);
+            emit!(out, "    la {}, {}\n", dst_reg, src_op);
         } else {
-            return format!("    li {}, {}\n", dst_reg, src_op);
+            emit!(out, "    li {}, {}\n", dst_reg, src_op);
         }
     }
 
-    fn generate_lea(&self, dst: &str, src: &str) -> String {
+    fn generate_lea(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(src)
+        {
+            self.address(out, pic, &self.map_operand(dst), symbol, offset);
+            return;
+        }
         let src_clean = if src.starts_with('[') && src.ends_with(']') {
             &src[1..src.len() - 1]
         } else {
             src
         };
-        format!("    la {}, {}\n", self.map_operand(dst), src_clean)
+        emit!(out, "    la {}, {}\n", self.map_operand(dst), src_clean);
     }
 
-    fn generate_load(&self, dst: &str, src: &str) -> String {
+    fn generate_load(&self, out: &mut String, dst: &str, src: &str) {
         let dst_reg = self.map_operand(dst);
 
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(src)
+        {
+            self.address(out, pic, &dst_reg, symbol, offset);
+            emit!(out, "    ld {}, 0({})\n", dst_reg, dst_reg);
+            return;
+        }
+
         if !src.starts_with('[') && !src.ends_with(']') {
-            return format!(
+            emit!(
+                out,
                 "    la {}, {}\n    ld {}, 0({})\n",
-                dst_reg, src, dst_reg, dst_reg
+                dst_reg,
+                src,
+                dst_reg,
+                dst_reg
             );
+            return;
         }
 
-        // Handle memory operands
-        let inner = &src[1..src.len() - 1].trim();
-
-        // Check if this looks like a label/symbol
-        if !inner.starts_with('x')
-            && !inner.starts_with('a')
-            && !inner.starts_with('t')
-            && !inner.starts_with('s')
-            && inner != &"sp"
-            && inner != &"ra"
-            && !inner.contains('+')
-            && !inner.contains('-')
-        {
-            return format!(
+        if self.is_symbolic(src) {
+            let inner = src[1..src.len() - 1].trim();
+            emit!(
+                out,
                 "    la {}, {}\n    ld {}, 0({})\n",
-                dst_reg, inner, dst_reg, dst_reg
+                dst_reg,
+                inner,
+                dst_reg,
+                dst_reg
             );
+            return;
         }
 
-        format!("    ld {}, {}\n", dst_reg, self.map_memory_operand(src))
+        emit!(
+            out,
+            "    ld {}, {}\n",
+            dst_reg,
+            self.map_memory_operand(src)
+        );
     }
 
-    fn generate_store(&self, dst: &str, src: &str) -> String {
+    fn generate_store(&self, out: &mut String, dst: &str, src: &str) {
         let src_reg = self.map_operand(src);
 
-        if src_reg.chars().all(|c| c.is_ascii_digit()) {
-            return format!("    // ERROR: sd requires a register, got {}\n", src_reg);
+        if opt::parse_immediate(src) == Some(0) {
+            self.generate_store(out, dst, "zero");
+            return;
         }
 
-        if dst.starts_with('[') && dst.ends_with(']') {
-            let inner = &dst[1..dst.len() - 1].trim();
-
-            if !inner.starts_with('x')
-                && !inner.starts_with('a')
-                && !inner.starts_with('t')
-                && !inner.starts_with('s')
-                && inner != &"sp"
-                && inner != &"ra"
-                && !inner.contains('+')
-                && !inner.contains('-')
-            {
-                return format!("    la t6, {}\n    sd {}, 0(t6)\n", inner, src_reg);
+        // The value needs a register, and t6 holds the address of a symbol,
+        // so constants and t6 itself go through a0, saved around the store.
+        if !Self::is_register(&src_reg) || (src_reg == "t6" && self.is_symbolic(dst)) {
+            if !self.is_symbolic(dst) {
+                self.generate_mov(out, "t6", src);
+                self.generate_store(out, dst, "t6");
+                return;
             }
-
-            return format!("    sd {}, {}\n", src_reg, self.map_memory_operand(dst));
+            out.push_str("    addi sp, sp, -16\n    sd a0, 0(sp)\n");
+            self.generate_mov(out, "a0", src);
+            self.generate_store(out, dst, "a0");
+            out.push_str("    ld a0, 0(sp)\n    addi sp, sp, 16\n");
+            return;
         }
 
-        format!("    la t6, {}\n    sd {}, 0(t6)\n", dst, src_reg)
-    }
-
-    fn generate_add(&self, dst: &str, src: &str) -> String {
-        let dst_reg = self.map_operand(dst);
-        let src_op = self.map_operand(src);
+        if let Some(pic) = &self.pic
+            && let Some((symbol, offset)) = pic.address(dst)
+        {
+            self.address(out, pic, "t6", symbol, offset);
+            emit!(out, "    sd {}, 0(t6)\n", src_reg);
+            return;
+        }
 
-        if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            let value: i32 = src_op.parse().unwrap_or(0);
-            if value >= -2048 && value <= 2047 {
-                format!("    addi {}, {}, {}\n", dst_reg, dst_reg, src_op)
-            } else {
-                format!(
-                    "    li t6, {}\n    add {}, {}, t6\n",
-                    src_op, dst_reg, dst_reg
-                )
+        if dst.starts_with('[') && dst.ends_with(']') {
+            if self.is_symbolic(dst) {
+                let inner = dst[1..dst.len() - 1].trim();
+                emit!(out, "    la t6, {}\n    sd {}, 0(t6)\n", inner, src_reg);
+                return;
             }
-        } else {
-            format!("    add {}, {}, {}\n", dst_reg, dst_reg, src_op)
+
+            emit!(
+                out,
+                "    sd {}, {}\n",
+                src_reg,
+                self.map_memory_operand(dst)
+            );
+            return;
         }
+
+        emit!(out, "    la t6, {}\n    sd {}, 0(t6)\n", dst, src_reg);
     }
 
-    fn generate_sub(&self, dst: &str, src: &str) -> String {
-        let dst_reg = self.map_operand(dst);
-        let src_op = self.map_operand(src);
+    fn generate_add(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "add", true, dst, src);
+    }
 
-        if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            let value: i32 = src_op.parse().unwrap_or(0);
-            if value >= -2047 && value <= 2048 {
-                format!("    addi {}, {}, {}\n", dst_reg, dst_reg, -value)
-            } else {
-                format!(
-                    "    li t6, {}\n    sub {}, {}, t6\n",
-                    src_op, dst_reg, dst_reg
-                )
+    fn generate_sub(&self, out: &mut String, dst: &str, src: &str) {
+        match opt::parse_immediate(src) {
+            Some(value) if (-2047..=2048).contains(&value) => {
+                let dst_reg = self.map_operand(dst);
+                emit!(out, "    addi {}, {}, {}\n", dst_reg, dst_reg, -value);
             }
-        } else if src_op.starts_with('a')
-            || src_op.starts_with('t')
-            || src_op.starts_with('s')
-            || src_op == "sp"
-            || src_op == "ra"
-        {
-            format!("    sub {}, {}, {}\n", dst_reg, dst_reg, src_op)
-        } else {
-            format!(
-                "    li t6, {}\n    sub {}, {}, t6\n",
-                src_op, dst_reg, dst_reg
-            )
+            _ => self.binary(out, "sub", false, dst, src),
         }
     }
 
-    fn generate_mul(&self, dst: &str, src: &str) -> String {
-        let dst_reg = self.map_operand(dst);
-        let src_op = self.map_operand(src);
-
-        if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            // RISC-V mul doesn't support immediates, load into temp register
-            format!(
-                "    li t6, {}\n    mul {}, {}, t6\n",
-                src_op, dst_reg, dst_reg
-            )
-        } else if src_op.starts_with('a')
-            || src_op.starts_with('t')
-            || src_op.starts_with('s')
-            || src_op == "sp"
-            || src_op == "ra"
-        {
-            format!("    mul {}, {}, {}\n", dst_reg, dst_reg, src_op)
-        } else {
-            // Handle symbolic constants by loading them first
-            format!(
-                "    li t6, {}\n    mul {}, {}, t6\n",
-                src_op, dst_reg, dst_reg
-            )
-        }
+    fn generate_mul(&self, out: &mut String, dst: &str, src: &str) {
+        // RISC-V mul doesn't support immediates
+        self.binary(out, "mul", false, dst, src);
     }
 
-    fn generate_div(&self, dst: &str, src: &str) -> String {
-        format!(
-            "    div {}, {}, {}\n",
-            self.map_operand(dst),
-            self.map_operand(dst),
-            self.map_operand(src)
-        )
+    fn generate_div(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "div", false, dst, src);
     }
 
-    fn generate_inc(&self, dst: &str) -> String {
-        format!(
+    fn generate_inc(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    addi {}, {}, 1\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_dec(&self, dst: &str) -> String {
-        format!(
+    fn generate_dec(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    addi {}, {}, -1\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_neg(&self, dst: &str) -> String {
-        format!(
+    fn generate_neg(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    neg {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_and(&self, dst: &str, src: &str) -> String {
-        let dst_reg = self.map_operand(dst);
-        let src_op = self.map_operand(src);
-
-        if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            let value: i32 = src_op.parse().unwrap_or(0);
-            if value >= -2048 && value <= 2047 {
-                format!("    andi {}, {}, {}\n", dst_reg, dst_reg, src_op)
-            } else {
-                format!(
-                    "    li t6, {}\n    and {}, {}, t6\n",
-                    src_op, dst_reg, dst_reg
-                )
-            }
-        } else {
-            format!("    and {}, {}, {}\n", dst_reg, dst_reg, src_op)
-        }
+    fn generate_and(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "and", true, dst, src);
     }
 
-    fn generate_or(&self, dst: &str, src: &str) -> String {
-        let dst_reg = self.map_operand(dst);
-        let src_op = self.map_operand(src);
-
-        if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            let value: i32 = src_op.parse().unwrap_or(0);
-            if value >= -2048 && value <= 2047 {
-                format!("    ori {}, {}, {}\n", dst_reg, dst_reg, src_op)
-            } else {
-                format!(
-                    "    li t6, {}\n    or {}, {}, t6\n",
-                    src_op, dst_reg, dst_reg
-                )
-            }
-        } else {
-            format!("    or {}, {}, {}\n", dst_reg, dst_reg, src_op)
-        }
+    fn generate_or(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "or", true, dst, src);
     }
 
-    fn generate_xor(&self, dst: &str, src: &str) -> String {
-        let dst_reg = self.map_operand(dst);
-        let src_op = self.map_operand(src);
-
-        if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            let value: i32 = src_op.parse().unwrap_or(0);
-            if value >= -2048 && value <= 2047 {
-                format!("    xori {}, {}, {}\n", dst_reg, dst_reg, src_op)
-            } else {
-                format!(
-                    "    li t6, {}\n    xor {}, {}, t6\n",
-                    src_op, dst_reg, dst_reg
-                )
-            }
-        } else {
-            format!("    xor {}, {}, {}\n", dst_reg, dst_reg, src_op)
-        }
+    fn generate_xor(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "xor", true, dst, src);
     }
 
-    fn generate_not(&self, dst: &str) -> String {
-        format!(
+    fn generate_not(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
             "    not {}, {}\n",
             self.map_operand(dst),
             self.map_operand(dst)
-        )
+        );
     }
 
-    fn generate_shl(&self, dst: &str, src: &str) -> String {
-        let dst_reg = self.map_operand(dst);
-        let src_op = self.map_operand(src);
-
-        if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    slli {}, {}, {}\n", dst_reg, dst_reg, src_op)
-        } else {
-            format!("    sll {}, {}, {}\n", dst_reg, dst_reg, src_op)
-        }
+    fn generate_shl(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "sll", dst, src);
     }
 
-    fn generate_shr(&self, dst: &str, src: &str) -> String {
-        let dst_reg = self.map_operand(dst);
-        let src_op = self.map_operand(src);
-
-        if src_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    srli {}, {}, {}\n", dst_reg, dst_reg, src_op)
-        } else {
-            format!("    srl {}, {}, {}\n", dst_reg, dst_reg, src_op)
-        }
+    fn generate_shr(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "srl", dst, src);
     }
 
-    fn generate_cmp(&self, op1: &str, op2: &str) -> String {
-        let op1_reg = self.map_operand(op1);
-        let op2_op = self.map_operand(op2);
-
-        if op2_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    li t6, {}\n    sub t6, {}, t6\n", op2_op, op1_reg)
-        } else if op2_op.starts_with('a')
-            || op2_op.starts_with('t')
-            || op2_op.starts_with('s')
-            || op2_op == "sp"
-            || op2_op == "ra"
-        {
-            format!("    sub t6, {}, {}\n", op1_reg, op2_op)
-        } else {
-            format!(
-                "    addi t6, zero, %lo({})\n    sub t6, {}, t6\n",
-                op2_op, op1_reg
-            )
-        }
+    fn generate_cmp(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_test(&self, op1: &str, op2: &str) -> String {
-        let op1_reg = self.map_operand(op1);
-        let op2_op = self.map_operand(op2);
-
-        if op2_op.chars().all(|c| c.is_ascii_digit() || c == '-') {
-            format!("    li t6, {}\n    and t6, {}, t6\n", op2_op, op1_reg)
-        } else {
-            format!("    and t6, {}, {}\n", op1_reg, op2_op)
-        }
+    fn generate_test(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_jmp(&self, label: &str) -> String {
-        format!("    j {}\n", label)
+    fn generate_jmp(&self, out: &mut String, label: &str) {
+        emit!(out, "    j {}\n", label);
     }
 
-    fn generate_je(&self, label: &str) -> String {
-        format!("    beqz t6, {}\n", label)
+    fn generate_je(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_jne(&self, label: &str) -> String {
-        format!("    bnez t6, {}\n", label)
+    fn generate_jne(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_jg(&self, label: &str) -> String {
-        format!("    bgtz t6, {}\n", label)
+    fn generate_jg(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_jl(&self, label: &str) -> String {
-        format!("    bltz t6, {}\n", label)
+    fn generate_jl(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_jge(&self, label: &str) -> String {
-        format!("    bgez t6, {}\n", label)
+    fn generate_jge(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_jle(&self, label: &str) -> String {
-        format!("    blez t6, {}\n", label)
+    fn generate_jle(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_call(&self, func: &str) -> String {
-        format!("    call {}\n", func)
+    fn generate_call(&self, out: &mut String, func: &str) {
+        emit!(out, "    call {}\n", func);
     }
 
-    fn generate_ret(&self) -> String {
-        "    ret\n".to_string()
+    fn generate_ret(&self, out: &mut String) {
+        out.push_str("    ret\n");
     }
 
-    fn generate_syscall(&self, name: &str) -> String {
+    fn generate_syscall(&self, out: &mut String, name: &str) {
         let syscall_num = match name {
             "read" => "63",
             "write" => "64",
@@ -436,13 +696,15 @@ impl ArchCodeGen for RISCVCodeGen {
             "munmap" => "215",
             "brk" => "214",
             _ => {
-                return format!(
+                emit!(
+                    out,
                     "    // Unknown syscall: {}\n    li a7, 0\n    ecall\n",
                     name
                 );
+                return;
             }
         };
-        format!("    li a7, {}\n    ecall\n", syscall_num)
+        emit!(out, "    li a7, {}\n    ecall\n", syscall_num);
     }
 
     fn map_operand(&self, operand: &str) -> String {
@@ -500,424 +762,698 @@ impl ArchCodeGen for RISCVCodeGen {
                 }
             }
 
-            if let Some(mapped) = self.register_map.get(&inner.to_string()) {
-                return format!("0({})", mapped);
+            match self.register_map.get(*inner) {
+                Some(mapped) => format!("0({})", mapped),
+                None => format!("0({})", inner),
             }
-            return format!("0({})", inner);
         } else {
             operand.to_string()
         }
     }
 
-    fn generate_cmov_eq(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_eq(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V has no condition flags")
+    }
+
+    fn generate_cmov_ne(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V has no condition flags")
+    }
+
+    fn generate_cmov_lt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V has no condition flags")
+    }
+
+    fn generate_cmov_le(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V has no condition flags")
+    }
+
+    fn generate_cmov_gt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_cmov_ne(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_ge(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_cmov_lt(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_ov(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_cmov_le(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_no(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_cmov_gt(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_s(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_cmov_ge(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_ns(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_cmov_ov(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_p(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_cmov_no(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_np(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_cmov_s(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_a(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_cmov_ns(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_ae(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_cmov_p(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_b(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_cmov_np(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cmov_be(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_cmov_a(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_push(&self, out: &mut String, src: &str) {
+        // Pushes keep sp 16-byte aligned, as the psABI requires.
+        let src_reg = self.in_register(out, src);
+        emit!(out, "    addi sp, sp, -16\n    sd {}, 0(sp)\n", src_reg);
     }
 
-    fn generate_cmov_ae(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_pop(&self, out: &mut String, dst: &str) {
+        emit!(
+            out,
+            "    ld {}, 0(sp)\n    addi sp, sp, 16\n",
+            self.map_operand(dst)
+        );
     }
 
-    fn generate_cmov_b(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_pusha(&self, out: &mut String) {
+        let registers = self.saved_by_pusha();
+        let size = (registers.len() * 8).next_multiple_of(16);
+        emit!(out, "    addi sp, sp, -{}\n", size);
+        for (i, register) in registers.iter().enumerate() {
+            emit!(out, "    sd {}, {}(sp)\n", register, i * 8);
+        }
     }
 
-    fn generate_cmov_be(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_popa(&self, out: &mut String) {
+        let registers = self.saved_by_pusha();
+        let size = (registers.len() * 8).next_multiple_of(16);
+        for (i, register) in registers.iter().enumerate() {
+            emit!(out, "    ld {}, {}(sp)\n", register, i * 8);
+        }
+        emit!(out, "    addi sp, sp, {}\n", size);
     }
 
-    fn generate_push(&self, src: &str) -> String {
-        todo!()
+    fn generate_enter(&self, out: &mut String, frame_size: &str, _nesting_level: &str) {
+        // s0 points at the saved s0 and ra, like the frame pointer on ARM64.
+        out.push_str("    addi sp, sp, -16\n    sd ra, 8(sp)\n    sd s0, 0(sp)\n    mv s0, sp\n");
+        if opt::parse_immediate(frame_size) != Some(0) {
+            self.generate_sub(out, "sp", frame_size);
+        }
     }
 
-    fn generate_pop(&self, dst: &str) -> String {
-        todo!()
+    fn generate_leave(&self, out: &mut String) {
+        out.push_str("    mv sp, s0\n    ld ra, 8(sp)\n    ld s0, 0(sp)\n    addi sp, sp, 16\n");
     }
 
-    fn generate_pusha(&self) -> String {
-        todo!()
+    fn generate_imul(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_mul(out, dst, src);
     }
 
-    fn generate_popa(&self) -> String {
-        todo!()
+    fn generate_idiv(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_div(out, dst, src);
     }
 
-    fn generate_enter(&self, frame_size: &str, nesting_level: &str) -> String {
-        todo!()
+    fn generate_mod(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "rem", false, dst, src);
     }
 
-    fn generate_leave(&self) -> String {
-        todo!()
+    fn generate_mulh(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "mulh", false, dst, src);
     }
 
-    fn generate_imul(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_andn(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(value) = opt::parse_immediate(src) {
+            self.generate_and(out, dst, &(!value).to_string());
+            return;
+        }
+        if self.zbb {
+            self.binary(out, "andn", false, dst, src);
+            return;
+        }
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.in_register(out, src);
+        emit!(
+            out,
+            "    not t6, {}\n    and {}, {}, t6\n",
+            src_reg,
+            dst_reg,
+            dst_reg
+        );
     }
 
-    fn generate_idiv(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_sal(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_shl(out, dst, src);
     }
 
-    fn generate_mod(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_sar(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "sra", dst, src);
     }
 
-    fn generate_andn(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_rol(&self, out: &mut String, dst: &str, src: &str) {
+        self.rotate(out, true, dst, src);
     }
 
-    fn generate_sal(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_ror(&self, out: &mut String, dst: &str, src: &str) {
+        self.rotate(out, false, dst, src);
     }
 
-    fn generate_sar(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_rcl(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V rotates through the carry in `generate_compare_rcl`")
     }
 
-    fn generate_rol(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_rcr(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("RISC-V rotates through the carry in `generate_compare_rcr`")
     }
 
-    fn generate_ror(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_bextr(&self, out: &mut String, dst: &str, src: &str, imm: &str) {
+        let dst_reg = self.map_operand(dst);
+        let control_reg = self.map_operand(imm);
+        let src_reg = self.in_register(out, src);
+        if let Some(control) = opt::parse_immediate(imm) {
+            let start = control & 0xff;
+            let length = ((control >> 8) & 0xff).min(64 - start.min(64));
+            if length == 0 {
+                emit!(out, "    mv {}, zero\n", dst_reg);
+                return;
+            }
+            emit!(
+                out,
+                "    slli {d}, {}, {}\n    srli {d}, {d}, {}\n",
+                src_reg,
+                64 - start - length,
+                64 - length,
+                d = dst_reg
+            );
+        } else if Self::is_register(&control_reg) {
+            // Lengths of 1 to 64 - start.
+            emit!(
+                out,
+                "    srl t6, {s}, {c}\n    srli {d}, {c}, 8\n    andi {d}, {d}, 255\n    neg {d}, {d}\n    sll t6, t6, {d}\n    srl {d}, t6, {d}\n",
+                s = src_reg,
+                c = control_reg,
+                d = dst_reg
+            );
+        } else {
+            // A symbol the assembler knows the value of.
+            let start = format!("(({}) & 255)", control_reg);
+            let length = format!("((({}) >> 8) & 255)", control_reg);
+            emit!(
+                out,
+                "    slli {d}, {}, 64 - {} - {}\n    srli {d}, {d}, 64 - {}\n",
+                src_reg,
+                start,
+                length,
+                length,
+                d = dst_reg
+            );
+        }
     }
 
-    fn generate_rcl(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_bsf(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.in_register(out, src);
+        if self.zbb {
+            emit!(out, "    ctz {}, {}\n", dst_reg, src_reg);
+            return;
+        }
+        // The lowest set bit alone is its own highest.
+        let lowest = if src_reg == "t6" {
+            format!("    neg {d}, t6\n    and t6, t6, {d}\n", d = dst_reg)
+        } else {
+            format!("    neg t6, {s}\n    and t6, {s}, t6\n", s = src_reg)
+        };
+        emit!(out, "{}", lowest);
+        Self::highest_bit(out, &dst_reg);
     }
 
-    fn generate_rcr(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_bsr(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.in_register(out, src);
+        if self.zbb {
+            // 63 - clz, for counts of 0 to 63.
+            emit!(
+                out,
+                "    clz {d}, {}\n    xori {d}, {d}, 63\n",
+                src_reg,
+                d = dst_reg
+            );
+            return;
+        }
+        if src_reg != "t6" {
+            emit!(out, "    mv t6, {}\n", src_reg);
+        }
+        Self::highest_bit(out, &dst_reg);
     }
 
-    fn generate_bextr(&self, dst: &str, src: &str, imm: &str) -> String {
-        todo!()
+    fn generate_bt(&self, _out: &mut String, _dst: &str, _bit: &str) {}
+
+    fn generate_btr(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "and", true, dst, bit);
     }
 
-    fn generate_bsf(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_bts(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "or", false, dst, bit);
     }
 
-    fn generate_bsr(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_btc(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "xor", false, dst, bit);
     }
 
-    fn generate_bt(&self, dst: &str, bit: &str) -> String {
-        todo!()
+    fn generate_set_eq(&self, _out: &mut String, _dst: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_btr(&self, dst: &str, bit: &str) -> String {
-        todo!()
+    fn generate_set_ne(&self, _out: &mut String, _dst: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_bts(&self, dst: &str, bit: &str) -> String {
-        todo!()
+    fn generate_set_lt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_btc(&self, dst: &str, bit: &str) -> String {
-        todo!()
+    fn generate_set_le(&self, _out: &mut String, _dst: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_set_eq(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_gt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_set_ne(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_ge(&self, _out: &mut String, _dst: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_set_lt(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_ov(&self, _out: &mut String, _dst: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_set_le(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_no(&self, _out: &mut String, _dst: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_set_gt(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_s(&self, _out: &mut String, _dst: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_set_ge(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_ns(&self, _out: &mut String, _dst: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_set_ov(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_p(&self, _out: &mut String, _dst: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_set_no(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_np(&self, _out: &mut String, _dst: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_set_s(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_a(&self, _out: &mut String, _dst: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_set_ns(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_ae(&self, _out: &mut String, _dst: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_set_p(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_b(&self, _out: &mut String, _dst: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_set_np(&self, dst: &str) -> String {
-        todo!()
+    fn generate_set_be(&self, _out: &mut String, _dst: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_set_a(&self, dst: &str) -> String {
-        todo!()
+    fn generate_cmps(&self, _out: &mut String, _src1: &str, _src2: &str) {}
+
+    fn generate_scas(&self, _out: &mut String, _src: &str, _val: &str) {}
+
+    fn generate_stos(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_store(out, dst, src);
     }
 
-    fn generate_set_ae(&self, dst: &str) -> String {
-        todo!()
+    fn generate_lods(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_load(out, dst, src);
     }
 
-    fn generate_set_b(&self, dst: &str) -> String {
-        todo!()
+    fn generate_movs(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_load(out, "t6", src);
+        self.generate_store(out, dst, "t6");
     }
 
-    fn generate_set_be(&self, dst: &str) -> String {
-        todo!()
+    fn generate_cbw(&self, out: &mut String, dst: &str) {
+        self.extend(out, dst, 8);
     }
 
-    fn generate_cmps(&self, src1: &str, src2: &str) -> String {
-        todo!()
+    fn generate_cwd(&self, out: &mut String, dst: &str) {
+        self.extend(out, dst, 16);
     }
 
-    fn generate_scas(&self, src: &str, val: &str) -> String {
-        todo!()
+    fn generate_cdq(&self, out: &mut String, dst: &str) {
+        self.extend(out, dst, 32);
     }
 
-    fn generate_stos(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cqo(&self, out: &mut String, dst: &str) {
+        // The high half of the 128-bit sign extension: all sign bits.
+        let dst_reg = self.map_operand(dst);
+        emit!(out, "    srai {}, {}, 63\n", dst_reg, dst_reg);
     }
 
-    fn generate_lods(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cwde(&self, out: &mut String, dst: &str) {
+        self.extend(out, dst, 16);
     }
 
-    fn generate_movs(&self, dst: &str, src: &str) -> String {
-        todo!()
+    fn generate_cdqe(&self, out: &mut String, dst: &str) {
+        self.extend(out, dst, 32);
     }
 
-    fn generate_cbw(&self, dst: &str) -> String {
-        todo!()
+    fn generate_jo(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_cwd(&self, dst: &str) -> String {
-        todo!()
+    fn generate_jno(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_cdq(&self, dst: &str) -> String {
-        todo!()
+    fn generate_js(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_cqo(&self, dst: &str) -> String {
-        todo!()
+    fn generate_jns(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_cwde(&self, dst: &str) -> String {
-        todo!()
+    fn generate_jp(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_cdqe(&self, dst: &str) -> String {
-        todo!()
+    fn generate_jnp(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_jo(&self, label: &str) -> String {
-        todo!()
+    fn generate_ja(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_jno(&self, label: &str) -> String {
-        todo!()
+    fn generate_jae(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_js(&self, label: &str) -> String {
-        todo!()
+    fn generate_jb(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_jns(&self, label: &str) -> String {
-        todo!()
+    fn generate_jbe(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_jp(&self, label: &str) -> String {
-        todo!()
+    fn generate_loop_eq(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_jnp(&self, label: &str) -> String {
-        todo!()
+    fn generate_loop_ne(&self, _out: &mut String, _label: &str) {
+        unreachable!("RISC-V has no condition flags")
     }
 
-    fn generate_ja(&self, label: &str) -> String {
-        todo!()
+    fn generate_in(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("    # RISC-V has no IN instruction, not supported.\n");
     }
 
-    fn generate_jae(&self, label: &str) -> String {
-        todo!()
+    fn generate_out(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("    # RISC-V has no OUT instruction, not supported.\n");
     }
 
-    fn generate_jb(&self, label: &str) -> String {
-        todo!()
+    fn generate_ins(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("    # RISC-V has no INS instruction, not supported.\n");
     }
 
-    fn generate_jbe(&self, label: &str) -> String {
-        todo!()
+    fn generate_outs(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("    # RISC-V has no OUTS instruction, not supported.\n");
     }
 
-    fn generate_loop_eq(&self, label: &str) -> String {
-        todo!()
+    fn generate_cpuid(&self, out: &mut String) {
+        out.push_str("    # RISC-V does not have CPUID\n");
     }
 
-    fn generate_loop_ne(&self, label: &str) -> String {
-        todo!()
+    fn generate_lfence(&self, out: &mut String) {
+        out.push_str("    fence r, r\n");
     }
 
-    fn generate_in(&self, dst: &str, port: &str) -> String {
-        todo!()
+    fn generate_sfence(&self, out: &mut String) {
+        out.push_str("    fence w, w\n");
     }
 
-    fn generate_out(&self, port: &str, src: &str) -> String {
-        todo!()
+    fn generate_mfence(&self, out: &mut String) {
+        out.push_str("    fence rw, rw\n");
     }
 
-    fn generate_ins(&self, dst: &str, port: &str) -> String {
-        todo!()
+    fn generate_prefetch(&self, _out: &mut String, _addr: &str) {
+        // A hint, and RV64GC has no instruction for it.
     }
 
-    fn generate_outs(&self, port: &str, src: &str) -> String {
-        todo!()
+    fn generate_clflush(&self, out: &mut String, _addr: &str) {
+        out.push_str("    # RV64GC does not support clflush\n");
     }
 
-    fn generate_cpuid(&self) -> String {
-        todo!()
+    fn generate_clwb(&self, out: &mut String, _addr: &str) {
+        out.push_str("    # RV64GC does not support clwb\n");
     }
 
-    fn generate_lfence(&self) -> String {
-        todo!()
+    fn generate_global(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".global {}\n", symbol);
     }
 
-    fn generate_sfence(&self) -> String {
-        todo!()
+    fn generate_extern(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".extern {}\n", symbol);
     }
 
-    fn generate_mfence(&self) -> String {
-        todo!()
+    fn generate_align(&self, out: &mut String, n: &str) {
+        emit!(out, ".p2align {}\n", n);
     }
 
-    fn generate_prefetch(&self, addr: &str) -> String {
-        todo!()
+    fn generate_data_byte(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .byte {}\n", name, values.join(", "));
     }
 
-    fn generate_clflush(&self, addr: &str) -> String {
-        todo!()
+    fn generate_data_word(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .half {}\n", name, values.join(", "));
     }
 
-    fn generate_clwb(&self, addr: &str) -> String {
-        todo!()
+    fn generate_data_dword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .word {}\n", name, values.join(", "));
     }
 
-    fn generate_global(&self, symbol: &str) -> String {
-        todo!()
+    fn generate_data_qword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .dword {}\n", name, values.join(", "));
     }
 
-    fn generate_extern(&self, symbol: &str) -> String {
-        todo!()
+    fn generate_reserve_byte(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip {}\n", name, count);
     }
 
-    fn generate_align(&self, n: &str) -> String {
-        todo!()
+    fn generate_reserve_word(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 2 * ({})\n", name, count);
     }
 
-    fn generate_data_byte(&self, name: &str, values: &[String]) -> String {
-        todo!()
+    fn generate_reserve_dword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 4 * ({})\n", name, count);
     }
 
-    fn generate_data_word(&self, name: &str, values: &[String]) -> String {
-        todo!()
+    fn generate_reserve_qword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 8 * ({})\n", name, count);
     }
 
-    fn generate_data_dword(&self, name: &str, values: &[String]) -> String {
-        todo!()
+    fn generate_equ(&self, out: &mut String, name: &str, value: &str) {
+        emit!(out, ".equ {}, {}\n", name, value);
     }
 
-    fn generate_data_qword(&self, name: &str, values: &[String]) -> String {
-        todo!()
+    fn generate_section(&self, out: &mut String, section: &Section) {
+        match section {
+            Section::Text => out.push_str(".section .text\n"),
+            Section::Data => out.push_str(".section .data\n"),
+            Section::Bss => out.push_str(".section .bss\n"),
+            Section::Rodata => out.push_str(".section .rodata\n"),
+            Section::Custom(s) => emit!(out, ".section {}\n", s),
+        }
+    }
+
+    fn generate_label(&self, out: &mut String, name: &str) {
+        emit!(out, "{}:\n", name);
+    }
+
+    fn get_register_width(&self) -> u32 {
+        64
+    }
+
+    fn get_reserved_registers(&self) -> Vec<String> {
+        ["sp", "s0", "ra", "t5", "t6"]
+            .iter()
+            .map(|r| r.to_string())
+            .collect()
+    }
+
+    fn get_callee_saved_registers(&self) -> Vec<String> {
+        [
+            "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11",
+        ]
+        .iter()
+        .map(|r| r.to_string())
+        .collect()
+    }
+
+    fn get_push_size(&self) -> u32 {
+        16
+    }
+
+    fn get_clobbered_registers(&self, instruction: &Instruction) -> Vec<String> {
+        use Instruction::*;
+        let registers: &[&str] = match instruction {
+            Syscall(_) => &["a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"],
+            Call(_) => &[
+                "ra", "t0", "t1", "t2", "t3", "t4", "t5", "t6", "a0", "a1", "a2", "a3", "a4", "a5",
+                "a6", "a7",
+            ],
+            _ => &[],
+        };
+        registers.iter().map(|r| r.to_string()).collect()
     }
 
-    fn generate_reserve_byte(&self, name: &str, count: &str) -> String {
-        todo!()
+    fn get_frame_layout(&self) -> Option<FrameLayout> {
+        // `enter` stores s0 and ra as a pair and points s0 at it.
+        Some(FrameLayout {
+            return_address: 0,
+            frame_pointer: "s0",
+            record: &["s0", "ra"],
+            slot: 8,
+        })
     }
 
-    fn generate_reserve_word(&self, name: &str, count: &str) -> String {
-        todo!()
+    fn set_position_independent(&mut self, pic: Pic) -> Result<(), String> {
+        self.pic = Some(pic);
+        Ok(())
     }
 
-    fn generate_reserve_dword(&self, name: &str, count: &str) -> String {
-        todo!()
+    fn set_bit_manipulation(&mut self) -> Result<(), String> {
+        self.zbb = true;
+        Ok(())
     }
 
-    fn generate_reserve_qword(&self, name: &str, count: &str) -> String {
-        todo!()
+    fn has_condition_flags(&self) -> bool {
+        false
+    }
+    fn compares(&self, _: Tested) -> bool {
+        true
     }
 
-    fn generate_equ(&self, name: &str, value: &str) -> String {
-        todo!()
+    fn generate_compare_branch(&self, out: &mut String, comparison: &Comparison, label: &str) {
+        let (a, b) = self.compared(out, comparison);
+        let branch = match comparison.condition {
+            Condition::Eq => format!("beq {}, {}", a, b),
+            Condition::Ne => format!("bne {}, {}", a, b),
+            Condition::Lt => format!("blt {}, {}", a, b),
+            Condition::Le => format!("ble {}, {}", a, b),
+            Condition::Gt => format!("bgt {}, {}", a, b),
+            Condition::Ge => format!("bge {}, {}", a, b),
+            Condition::B => format!("bltu {}, {}", a, b),
+            Condition::Be => format!("bleu {}, {}", a, b),
+            Condition::A => format!("bgtu {}, {}", a, b),
+            Condition::Ae => format!("bgeu {}, {}", a, b),
+            Condition::S | Condition::Ns => {
+                let difference = if b == "zero" {
+                    a
+                } else {
+                    emit!(out, "    sub t6, {}, {}\n", a, b);
+                    "t6".to_string()
+                };
+                let mnemonic = if comparison.condition == Condition::S {
+                    "bltz"
+                } else {
+                    "bgez"
+                };
+                format!("{} {}", mnemonic, difference)
+            }
+        };
+        emit!(out, "    {}, {}\n", branch, label);
     }
 
-    fn generate_section(&self, section: &Section) -> String {
-        todo!()
+    fn generate_compare_set(&self, out: &mut String, comparison: &Comparison, dst: &str) {
+        let (a, b) = self.compared(out, comparison);
+        let dst_reg = self.map_operand(dst);
+        // The difference, for conditions on it rather than on the operands.
+        let mut difference = |op: &str| {
+            if b == "zero" {
+                a.clone()
+            } else {
+                emit!(out, "    {} {}, {}, {}\n", op, dst_reg, a, b);
+                dst_reg.clone()
+            }
+        };
+        let set = match comparison.condition {
+            Condition::Eq => format!("seqz {}, {}", dst_reg, difference("xor")),
+            Condition::Ne => format!("snez {}, {}", dst_reg, difference("xor")),
+            Condition::S | Condition::Ns => format!("sltz {}, {}", dst_reg, difference("sub")),
+            Condition::Lt | Condition::Ge => format!("slt {}, {}, {}", dst_reg, a, b),
+            Condition::Gt | Condition::Le => format!("slt {}, {}, {}", dst_reg, b, a),
+            Condition::B | Condition::Ae => format!("sltu {}, {}, {}", dst_reg, a, b),
+            Condition::A | Condition::Be => format!("sltu {}, {}, {}", dst_reg, b, a),
+        };
+        emit!(out, "    {}\n", set);
+        // The rest are the opposite of a condition RISC-V sets directly.
+        if matches!(
+            comparison.condition,
+            Condition::Ge | Condition::Le | Condition::Ae | Condition::Be | Condition::Ns
+        ) {
+            emit!(out, "    xori {}, {}, 1\n", dst_reg, dst_reg);
+        }
     }
 
-    fn generate_label(&self, name: &str) -> String {
-        todo!()
+    fn generate_compare_cmov(
+        &self,
+        out: &mut String,
+        comparison: &Comparison,
+        dst: &str,
+        src: &str,
+    ) {
+        // Branch around the move when the condition fails.
+        let skip = Comparison {
+            condition: comparison.condition.negate(),
+            ..comparison.clone()
+        };
+        self.generate_compare_branch(out, &skip, "1f");
+        self.generate_mov(out, dst, src);
+        out.push_str("1:\n");
+    }
+
+    fn generate_compare_rcl(
+        &self,
+        out: &mut String,
+        comparison: &Comparison,
+        dst: &str,
+        count: &str,
+    ) {
+        self.rotate_through(out, true, comparison, dst, count);
+    }
+
+    fn generate_compare_rcr(
+        &self,
+        out: &mut String,
+        comparison: &Comparison,
+        dst: &str,
+        count: &str,
+    ) {
+        self.rotate_through(out, false, comparison, dst, count);
     }
 }
@@ -0,0 +1,1464 @@
+use super::*;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+pub struct SHCodeGen {
+    register_map: HashMap<String, String>,
+    /// Literal pool entries referenced since the last pool was placed: the
+    /// number of each entry's label and its value.
+    pool: RefCell<Vec<(usize, String)>>,
+    /// Bytes of code emitted since the first entry of `pool` was referenced.
+    pending: Cell<usize>,
+    /// Numbers the literal pool entries.
+    literals: Cell<usize>,
+}
+
+/// How far ahead of a `mov.l` its literal may lie: eight bits of
+/// displacement, counted in words.
+const POOL_REACH: usize = 1020;
+
+/// Room kept within `POOL_REACH` for the expansion that comes before the
+/// next chance to place the pool.
+const POOL_MARGIN: usize = 160;
+
+/// The syscall number and the arguments of each syscall the backend knows.
+const SYSCALLS: [(&str, i64, usize); 8] = [
+    ("exit", 1, 1),
+    ("read", 3, 3),
+    ("write", 4, 3),
+    ("open", 5, 3),
+    ("close", 6, 1),
+    ("brk", 45, 1),
+    // `old_mmap`, which takes its six arguments in registers and the
+    // offset in bytes.
+    ("mmap", 90, 6),
+    ("munmap", 91, 2),
+];
+
+/// The addressing modes of `mov.l`.
+enum Mode {
+    /// `@rn`
+    Indirect(String),
+    /// `@(disp,rn)`, with a displacement of 4 to 60 in steps of 4.
+    Displaced(i64, String),
+    /// `@(r0,rn)`
+    Indexed(String),
+}
+
+impl Mode {
+    fn operand(&self) -> String {
+        match self {
+            Mode::Indirect(base) => format!("@{}", base),
+            Mode::Displaced(displacement, base) => format!("@({},{})", displacement, base),
+            Mode::Indexed(base) => format!("@(r0,{})", base),
+        }
+    }
+}
+
+/// When a comparison holds, in terms of the T bit its compare leaves.
+enum Outcome {
+    Set,
+    Clear,
+    Always,
+    Never,
+}
+
+impl SHCodeGen {
+    pub fn new() -> Self {
+        let mut register_map = HashMap::with_capacity(16);
+
+        // Argument registers
+        register_map.insert("r0".to_string(), "r4".to_string()); // 1st arg/return value
+        register_map.insert("r1".to_string(), "r5".to_string()); // 2nd arg
+        register_map.insert("r2".to_string(), "r6".to_string()); // 3rd arg
+        register_map.insert("r3".to_string(), "r7".to_string()); // 4th arg
+
+        // Temporary registers
+        register_map.insert("r4".to_string(), "r3".to_string()); // 5th syscall arg
+
+        // Saved registers
+        register_map.insert("r5".to_string(), "r8".to_string()); // 6th syscall arg
+        register_map.insert("r6".to_string(), "r9".to_string());
+        register_map.insert("r7".to_string(), "r10".to_string());
+        register_map.insert("r8".to_string(), "r11".to_string());
+        register_map.insert("r9".to_string(), "r12".to_string());
+        register_map.insert("r10".to_string(), "r13".to_string());
+
+        // Special purpose registers
+        register_map.insert("sp".to_string(), "r15".to_string()); // Stack pointer
+        register_map.insert("sb".to_string(), "r14".to_string()); // Frame pointer
+
+        SHCodeGen {
+            register_map,
+            pool: RefCell::new(Vec::new()),
+            pending: Cell::new(0),
+            literals: Cell::new(0),
+        }
+    }
+
+    /// Whether `operand` names a general register.
+    fn is_register(operand: &str) -> bool {
+        operand
+            .strip_prefix('r')
+            .filter(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+            .and_then(|n| n.parse::<u32>().ok())
+            .is_some_and(|n| n <= 15)
+    }
+
+    /// The value of the constant `operand` as the 32 bits a register holds,
+    /// including the unsigned masks `opt::flags` writes for `bt` of the top
+    /// bit.
+    fn immediate(operand: &str) -> Option<i64> {
+        opt::parse_immediate(operand)
+            .or_else(|| operand.trim().parse::<u64>().ok().map(|value| value as i64))
+            .map(|value| value as i32 as i64)
+    }
+
+    /// Loads `value`, a constant or an expression the assembler knows the
+    /// value of, into `dst` from the literal pool. An entry already waiting
+    /// in the pool is shared.
+    fn literal(&self, out: &mut String, dst: &str, value: &str) {
+        let mut pool = self.pool.borrow_mut();
+        let n = match pool.iter().find(|(_, entry)| entry == value) {
+            Some((n, _)) => *n,
+            None => {
+                let n = self.literals.get();
+                self.literals.set(n + 1);
+                if pool.is_empty() {
+                    self.pending.set(0);
+                }
+                pool.push((n, value.to_string()));
+                n
+            }
+        };
+        emit!(out, "    mov.l .Lsh{},{}\n", n, dst);
+    }
+
+    /// Builds `value` in `dst`: `mov #imm` takes 8 bits, and the literal
+    /// pool holds the rest.
+    fn load_immediate(&self, out: &mut String, dst: &str, value: i64) {
+        let value = value as i32 as i64;
+        if (-128..=127).contains(&value) {
+            emit!(out, "    mov #{},{}\n", value, dst);
+            return;
+        }
+        self.literal(out, dst, &value.to_string());
+    }
+
+    /// `mov src,dst`, or nothing when they are the same register.
+    fn copy(out: &mut String, dst: &str, src: &str) {
+        if dst == src {
+            return;
+        }
+        emit!(out, "    mov {},{}\n", src, dst);
+    }
+
+    /// `dst += value`: `add #imm` takes 8 bits, and larger values go
+    /// through `scratch`.
+    fn add_constant(&self, out: &mut String, dst: &str, value: i64, scratch: &str) {
+        let value = value as i32 as i64;
+        if value == 0 {
+            return;
+        }
+        if (-128..=127).contains(&value) {
+            emit!(out, "    add #{},{}\n", value, dst);
+            return;
+        }
+        self.load_immediate(out, scratch, value);
+        emit!(out, "    add {},{}\n", scratch, dst);
+    }
+
+    /// The register holding `operand`, which goes in `scratch` first if it
+    /// is a constant, a symbol or in memory.
+    fn value(&self, out: &mut String, operand: &str, scratch: &str) -> String {
+        if opt::is_memory(operand) {
+            self.load(out, scratch, operand);
+            return scratch.to_string();
+        }
+        let mapped = self.map_operand(operand);
+        if Self::is_register(&mapped) {
+            return mapped;
+        }
+        match Self::immediate(operand) {
+            Some(value) => self.load_immediate(out, scratch, value),
+            None => self.literal(out, scratch, &mapped),
+        }
+        scratch.to_string()
+    }
+
+    /// Shifts `reg` by `count` with the fixed shifts `op` names (`shll` or
+    /// `shlr`, by 1, 2, 8 and 16), when that takes three instructions or
+    /// fewer.
+    fn fixed_shift(op: &str, reg: &str, count: i64) -> Option<String> {
+        let mut code = String::new();
+        let mut left = count;
+        let mut steps = 0;
+        for (amount, suffix) in [(16, "16"), (8, "8"), (2, "2"), (1, "")] {
+            while left >= amount {
+                code.push_str(&format!("    {}{} {}\n", op, suffix, reg));
+                left -= amount;
+                steps += 1;
+            }
+        }
+        (steps <= 3).then_some(code)
+    }
+
+    /// `operand` as base, index and displacement over r0-r15, the
+    /// displacement cut to the 32 bits an address has.
+    fn split_address(&self, operand: &str) -> Result<Address, String> {
+        let mut address =
+            super::split_address(operand, |name| self.map_operand(name), Self::is_register)?;
+        address.displacement = address.displacement as i32 as i64;
+        Ok(address)
+    }
+
+    /// `operand` as a `mov.l` addressing mode, once what it needs is in r0
+    /// and r1: symbols come from the literal pool, and
+    /// an index, scaled with shifts, or a displacement beyond 60 goes in r0
+    /// for `@(r0,rn)`.
+    fn locate(&self, out: &mut String, operand: &str) -> Mode {
+        let address = address_or_error(out, self.split_address(operand));
+        let mut displacement = address.displacement;
+        let base = if address.symbolic.is_empty() {
+            address.base
+        } else {
+            let expression = match displacement {
+                0 => address.symbolic,
+                d if d < 0 => format!("{} - {}", address.symbolic, -d),
+                d => format!("{} + {}", address.symbolic, d),
+            };
+            self.literal(out, "r1", &expression);
+            if let Some(base) = &address.base {
+                emit!(out, "    add {},r1\n", base);
+            }
+            displacement = 0;
+            Some("r1".to_string())
+        };
+        match (base, address.index) {
+            (None, None) => {
+                self.load_immediate(out, "r1", displacement);
+                Mode::Indirect("r1".to_string())
+            }
+            (Some(base), None) if displacement == 0 => Mode::Indirect(base),
+            (Some(base), None) if (4..=60).contains(&displacement) && displacement % 4 == 0 => {
+                Mode::Displaced(displacement, base)
+            }
+            (Some(base), None) => {
+                self.load_immediate(out, "r0", displacement);
+                Mode::Indexed(base)
+            }
+            (base, Some((index, shift))) => {
+                emit!(out, "    mov {},r0\n", index);
+                match Self::fixed_shift("shll", "r0", shift as i64) {
+                    Some(shifts) => out.push_str(&shifts),
+                    None => emit!(
+                        out,
+                        "    .error \"{} scales an index by more than 8\"\n",
+                        operand.trim()
+                    ),
+                }
+                // r1 is free here: a symbol leaves no displacement.
+                self.add_constant(out, "r0", displacement, "r1");
+                match base {
+                    Some(base) => Mode::Indexed(base),
+                    None => Mode::Indirect("r0".to_string()),
+                }
+            }
+        }
+    }
+
+    /// `mov.l` of the word at `operand` into `dst`.
+    fn load(&self, out: &mut String, dst: &str, operand: &str) {
+        let mode = self.locate(out, operand);
+        emit!(out, "    mov.l {},{}\n", mode.operand(), dst);
+    }
+
+    /// `mov.l` of the register `value` into the word at `operand`; `value`
+    /// must not be r0 or r1.
+    fn store(&self, out: &mut String, value: &str, operand: &str) {
+        let mode = self.locate(out, operand);
+        emit!(out, "    mov.l {},{}\n", value, mode.operand());
+    }
+
+    /// `op src,dst`, with a constant or memory `src` in r1.
+    fn binary(&self, out: &mut String, op: &str, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.value(out, src, "r1");
+        emit!(out, "    {} {},{}\n", op, src_reg, dst_reg);
+    }
+
+    /// `op dst,dst`, for the single-operand instructions that take a
+    /// separate source.
+    fn unary(&self, out: &mut String, op: &str, dst: &str) {
+        let dst_reg = self.map_operand(dst);
+        emit!(out, "    {} {},{}\n", op, dst_reg, dst_reg);
+    }
+
+    /// `dst = dst / src`, or the remainder, through libgcc's `__sdivsi3`,
+    /// which divides r4 by r5 into r0; SH has only the single-step `div1`.
+    /// Everything it may clobber and the return address in PR are saved
+    /// around the call.
+    fn divide(&self, out: &mut String, remainder: bool, dst: &str, src: &str) {
+        const SAVED: [&str; 5] = ["r3", "r4", "r5", "r6", "r7"];
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.value(out, src, "r1");
+        Self::copy(out, "r1", &src_reg);
+        emit!(out, "    mov {},r0\n    sts.l pr,@-r15\n", dst_reg);
+        for register in SAVED {
+            emit!(out, "    mov.l {},@-r15\n", register);
+        }
+        out.push_str("    mov r0,r4\n    mov r1,r5\n");
+        self.literal(out, "r2", "__sdivsi3");
+        out.push_str("    jsr @r2\n    nop\n");
+        for register in SAVED.iter().rev() {
+            emit!(out, "    mov.l @r15+,{}\n", register);
+        }
+        out.push_str("    lds.l @r15+,pr\n");
+        if !remainder {
+            Self::copy(out, &dst_reg, "r0");
+            return;
+        }
+        // dst - (dst / src) * src, as SH GCC computes remainders.
+        out.push_str("    mov r0,r2\n");
+        let src_reg = self.value(out, src, "r1");
+        emit!(
+            out,
+            "    mul.l {},r2\n    sts macl,r2\n    sub r2,{}\n",
+            src_reg,
+            dst_reg
+        );
+    }
+
+    /// A shift of `dst` by `count`: the fixed shifts or `shar` for small
+    /// constants, otherwise `shld`/`shad` by r0, negated for right shifts.
+    /// The count is taken modulo 32, as on x86.
+    fn shift(&self, out: &mut String, op: &str, right: bool, dst: &str, count: &str) {
+        let dst_reg = self.map_operand(dst);
+        if let Some(count) = Self::immediate(count).map(|count| count & 31) {
+            if count == 0 {
+                return;
+            }
+            if op == "shld" {
+                let fixed = if right { "shlr" } else { "shll" };
+                if let Some(code) = Self::fixed_shift(fixed, &dst_reg, count) {
+                    out.push_str(&code);
+                    return;
+                }
+            } else if count == 1 {
+                emit!(out, "    shar {}\n", dst_reg);
+                return;
+            } else if count == 31 {
+                // The sign bit into T, then 0 - 0 - T.
+                emit!(out, "    shll {d}\n    subc {d},{d}\n", d = dst_reg);
+                return;
+            }
+            let amount = if right { -count } else { count };
+            emit!(out, "    mov #{},r0\n    {} r0,{}\n", amount, op, dst_reg);
+            return;
+        }
+        let count_reg = self.value(out, count, "r0");
+        Self::copy(out, "r0", &count_reg);
+        out.push_str("    and #31,r0\n");
+        if right {
+            out.push_str("    neg r0,r0\n");
+        }
+        emit!(out, "    {} r0,{}\n", op, dst_reg);
+    }
+
+    /// A rotate of `dst` left by `count`, or right: `rotl`/`rotr` and
+    /// `swap.w` for the amounts they reach, otherwise the two shifts `shld`
+    /// makes with r0 and r0 - 32, joined.
+    fn rotate(&self, out: &mut String, left: bool, dst: &str, count: &str) {
+        let dst_reg = self.map_operand(dst);
+        let join = format!(
+            "    mov {d},r1\n    shld r0,{d}\n    add #-32,r0\n    shld r0,r1\n    or r1,{d}\n",
+            d = dst_reg
+        );
+        match Self::immediate(count) {
+            Some(count) => {
+                let amount = if left {
+                    count & 31
+                } else {
+                    (32 - (count & 31)) & 31
+                };
+                match amount {
+                    0 => {}
+                    16 => emit!(out, "    swap.w {d},{d}\n", d = dst_reg),
+                    1..=3 => {
+                        for _ in 0..amount {
+                            emit!(out, "    rotl {}\n", dst_reg);
+                        }
+                    }
+                    29..=31 => {
+                        for _ in amount..32 {
+                            emit!(out, "    rotr {}\n", dst_reg);
+                        }
+                    }
+                    _ => emit!(out, "    mov #{},r0\n{}", amount, join),
+                }
+            }
+            None => {
+                let count_reg = self.value(out, count, "r0");
+                Self::copy(out, "r0", &count_reg);
+                if !left {
+                    out.push_str("    neg r0,r0\n");
+                }
+                out.push_str("    and #31,r0\n");
+                out.push_str(&join);
+            }
+        }
+    }
+
+    /// `op mask,dst` for the single-bit mask `bit` selects, inverted when
+    /// `clear`.
+    fn bit(&self, out: &mut String, op: &str, clear: bool, dst: &str, bit: &str) {
+        let dst_reg = self.map_operand(dst);
+        match Self::immediate(bit) {
+            Some(bit) => {
+                let mask = 1i64 << (bit & 31);
+                self.load_immediate(out, "r1", if clear { !mask } else { mask });
+            }
+            None => {
+                let bit_reg = self.value(out, bit, "r0");
+                Self::copy(out, "r0", &bit_reg);
+                out.push_str("    and #31,r0\n    mov #1,r1\n    shld r0,r1\n");
+                if clear {
+                    out.push_str("    not r1,r1\n");
+                }
+            }
+        }
+        emit!(out, "    {} r1,{}\n", op, dst_reg);
+    }
+
+    /// The index of the highest set bit of r1 in `dst`, -1 for zero,
+    /// counted by shifting r1 out.
+    fn highest_bit(out: &mut String, dst: &str) {
+        emit!(
+            out,
+            "    mov #-1,{d}\n1:\n    tst r1,r1\n    bt 2f\n    shlr r1\n    bra 1b\n    add #1,{d}\n2:\n",
+            d = dst
+        );
+    }
+
+    /// The compare `condition` makes between the registers `a` and `b`:
+    /// `cmp/gt b,a` sets T when a > b, so the reversed conditions swap them.
+    fn register_compare(out: &mut String, condition: Condition, a: &str, b: &str) {
+        let (op, first, second) = match condition {
+            Condition::Eq | Condition::Ne => ("cmp/eq", b, a),
+            Condition::Gt => ("cmp/gt", b, a),
+            Condition::Ge => ("cmp/ge", b, a),
+            Condition::Lt => ("cmp/gt", a, b),
+            Condition::Le => ("cmp/ge", a, b),
+            Condition::A => ("cmp/hi", b, a),
+            Condition::Ae => ("cmp/hs", b, a),
+            Condition::B => ("cmp/hi", a, b),
+            Condition::Be => ("cmp/hs", a, b),
+            Condition::S | Condition::Ns => unreachable!("sign tests compare the difference"),
+        };
+        emit!(out, "    {} {},{}\n", op, first, second);
+    }
+
+    /// The test of `condition` between `reg` and zero.
+    fn zero_compare(out: &mut String, condition: Condition, reg: &str) -> Outcome {
+        match condition {
+            Condition::Eq | Condition::Be => {
+                emit!(out, "    tst {r},{r}\n", r = reg);
+                Outcome::Set
+            }
+            Condition::Ne | Condition::A => {
+                emit!(out, "    tst {r},{r}\n", r = reg);
+                Outcome::Clear
+            }
+            Condition::Ge | Condition::Ns => {
+                emit!(out, "    cmp/pz {}\n", reg);
+                Outcome::Set
+            }
+            Condition::Lt | Condition::S => {
+                emit!(out, "    cmp/pz {}\n", reg);
+                Outcome::Clear
+            }
+            Condition::Gt => {
+                emit!(out, "    cmp/pl {}\n", reg);
+                Outcome::Set
+            }
+            Condition::Le => {
+                emit!(out, "    cmp/pl {}\n", reg);
+                Outcome::Clear
+            }
+            Condition::B => Outcome::Never,
+            Condition::Ae => Outcome::Always,
+        }
+    }
+
+    /// Sets T for `comparison`, and returns how T tells whether it holds. `a`
+    /// is read from r2 when it is not in a register, `b` from r1, and r0 takes
+    /// the constants of `cmp/eq #imm` and `tst #imm`.
+    fn test(&self, out: &mut String, comparison: &Comparison) -> Outcome {
+        let condition = comparison.condition;
+        let a = self.value(out, &comparison.a, "r2");
+        let constant = Self::immediate(&comparison.b);
+        if comparison.tested == Tested::Mask {
+            let equality = matches!(condition, Condition::Eq | Condition::Ne);
+            let byte = constant.filter(|mask| (0..=255).contains(mask));
+            match (equality, byte) {
+                (true, Some(mask)) => {
+                    emit!(out, "    mov {},r0\n    tst #{},r0\n", a, mask)
+                }
+                (true, None) => {
+                    let b = self.value(out, &comparison.b, "r1");
+                    emit!(out, "    tst {},{}\n", b, a);
+                }
+                // Other conditions compare `a & b` with zero.
+                (false, _) => {
+                    let b = self.value(out, &comparison.b, "r1");
+                    emit!(out, "    mov {},r0\n    and {},r0\n", a, b);
+                    return Self::zero_compare(out, condition, "r0");
+                }
+            }
+            return if condition == Condition::Eq {
+                Outcome::Set
+            } else {
+                Outcome::Clear
+            };
+        }
+        match (condition, constant) {
+            (_, Some(0)) => Self::zero_compare(out, condition, &a),
+            (Condition::Eq | Condition::Ne, Some(value)) if (-128..=127).contains(&value) => {
+                emit!(out, "    mov {},r0\n    cmp/eq #{},r0\n", a, value);
+                if condition == Condition::Eq {
+                    Outcome::Set
+                } else {
+                    Outcome::Clear
+                }
+            }
+            // The sign of the difference.
+            (Condition::S | Condition::Ns, _) => {
+                let b = self.value(out, &comparison.b, "r1");
+                emit!(out, "    mov {},r0\n    sub {},r0\n", a, b);
+                Self::zero_compare(out, condition, "r0")
+            }
+            _ => {
+                let b = self.value(out, &comparison.b, "r1");
+                Self::register_compare(out, condition, &a, &b);
+                if condition == Condition::Ne {
+                    Outcome::Clear
+                } else {
+                    Outcome::Set
+                }
+            }
+        }
+    }
+
+    /// The registers `pusha` saves and `popa` restores: every one the
+    /// register map names but r15, in a fixed order.
+    fn saved_by_pusha(&self) -> Vec<&String> {
+        let mut registers: Vec<&String> = self
+            .register_map
+            .values()
+            .filter(|r| Self::is_register(r) && *r != "r15")
+            .collect();
+        registers.sort();
+        registers.dedup();
+        registers
+    }
+
+    /// The address of `addr` in r1, for the cache instructions that take
+    /// only `@rn`.
+    fn address(&self, out: &mut String, addr: &str) {
+        let mode = self.locate(out, addr);
+        match mode {
+            Mode::Indirect(base) => Self::copy(out, "r1", &base),
+            Mode::Displaced(displacement, base) => {
+                Self::copy(out, "r1", &base);
+                emit!(out, "    add #{},r1\n", displacement);
+            }
+            Mode::Indexed(base) => emit!(out, "    add {},r0\n    mov r0,r1\n", base),
+        }
+    }
+
+    /// Bytes `code` assembles to, at most: two for an instruction, four for
+    /// a `.long`, two for the padding of a `.p2align 2`.
+    fn size(code: &str) -> usize {
+        code.lines()
+            .filter(|line| line.starts_with(char::is_whitespace))
+            .map(|line| {
+                let line = line.trim();
+                if line.starts_with(".long") {
+                    4
+                } else if line.starts_with(".p2align") || line.starts_with(".align") {
+                    2
+                } else if line.starts_with('.') || line.starts_with('!') || line.is_empty() {
+                    0
+                } else {
+                    2
+                }
+            })
+            .sum()
+    }
+}
+
+impl ArchCodeGen for SHCodeGen {
+    fn get_register_map(&self) -> HashMap<String, String> {
+        self.register_map.clone()
+    }
+
+    fn get_syntax_header(&self) -> String {
+        ".text\n.p2align 2\n\n".to_string()
+    }
+
+    fn generate_mov(&self, out: &mut String, dst: &str, src: &str) {
+        // Only loads and stores reach memory.
+        if opt::is_memory(dst) {
+            self.generate_store(out, dst, src);
+            return;
+        }
+        if opt::is_memory(src) {
+            self.generate_load(out, dst, src);
+            return;
+        }
+        let dst_reg = self.map_operand(dst);
+        let src_op = self.map_operand(src);
+
+        if let Some(value) = Self::immediate(src) {
+            self.load_immediate(out, &dst_reg, value);
+            return;
+        }
+
+        if Self::is_register(&src_op) {
+            Self::copy(out, &dst_reg, &src_op);
+            return;
+        }
+
+        // Labels and constants the assembler knows the value of.
+        self.literal(out, &dst_reg, &src_op);
+    }
+
+    fn generate_lea(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        let address = address_or_error(out, self.split_address(src));
+        if address.base.is_none() && address.index.is_none() {
+            let expression = match (address.symbolic.is_empty(), address.displacement) {
+                (true, displacement) => {
+                    self.load_immediate(out, &dst_reg, displacement);
+                    return;
+                }
+                (false, 0) => address.symbolic,
+                (false, d) if d < 0 => format!("{} - {}", address.symbolic, -d),
+                (false, d) => format!("{} + {}", address.symbolic, d),
+            };
+            self.literal(out, &dst_reg, &expression);
+            return;
+        }
+        let mode = self.locate(out, src);
+        match mode {
+            Mode::Indirect(base) => Self::copy(out, &dst_reg, &base),
+            Mode::Displaced(displacement, base) => {
+                Self::copy(out, &dst_reg, &base);
+                emit!(out, "    add #{},{}\n", displacement, dst_reg);
+            }
+            Mode::Indexed(base) => {
+                emit!(out, "    add {},r0\n", base);
+                Self::copy(out, &dst_reg, "r0");
+            }
+        }
+    }
+
+    fn generate_load(&self, out: &mut String, dst: &str, src: &str) {
+        self.load(out, &self.map_operand(dst), src);
+    }
+
+    fn generate_store(&self, out: &mut String, dst: &str, src: &str) {
+        let src_reg = self.value(out, src, "r2");
+        self.store(out, &src_reg, dst);
+    }
+
+    fn generate_add(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(value) = Self::immediate(src) {
+            self.add_constant(out, &self.map_operand(dst), value, "r1");
+            return;
+        }
+        self.binary(out, "add", dst, src);
+    }
+
+    fn generate_sub(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(value) = Self::immediate(src) {
+            self.add_constant(out, &self.map_operand(dst), -value, "r1");
+            return;
+        }
+        self.binary(out, "sub", dst, src);
+    }
+
+    fn generate_mul(&self, out: &mut String, dst: &str, src: &str) {
+        // The product goes to MACL.
+        let dst_reg = self.map_operand(dst);
+        self.binary(out, "mul.l", dst, src);
+        emit!(out, "    sts macl,{}\n", dst_reg);
+    }
+
+    fn generate_div(&self, out: &mut String, dst: &str, src: &str) {
+        self.divide(out, false, dst, src);
+    }
+
+    fn generate_inc(&self, out: &mut String, dst: &str) {
+        self.generate_add(out, dst, "1");
+    }
+
+    fn generate_dec(&self, out: &mut String, dst: &str) {
+        self.generate_add(out, dst, "-1");
+    }
+
+    fn generate_neg(&self, out: &mut String, dst: &str) {
+        self.unary(out, "neg", dst);
+    }
+
+    fn generate_and(&self, out: &mut String, dst: &str, src: &str) {
+        // Masks of the low byte or half are zero extensions.
+        match Self::immediate(src) {
+            Some(0xff) => self.unary(out, "extu.b", dst),
+            Some(0xffff) => self.unary(out, "extu.w", dst),
+            _ => self.binary(out, "and", dst, src),
+        }
+    }
+
+    fn generate_or(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "or", dst, src);
+    }
+
+    fn generate_xor(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "xor", dst, src);
+    }
+
+    fn generate_not(&self, out: &mut String, dst: &str) {
+        self.unary(out, "not", dst);
+    }
+
+    fn generate_shl(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "shld", false, dst, src);
+    }
+
+    fn generate_shr(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "shld", true, dst, src);
+    }
+
+    fn generate_cmp(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_test(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_jmp(&self, out: &mut String, label: &str) {
+        let target = self.map_operand(label);
+        if Self::is_register(&target) {
+            emit!(out, "    jmp @{}\n    nop\n", target);
+            return;
+        }
+        emit!(out, "    bra {}\n    nop\n", label);
+    }
+
+    fn generate_je(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_jne(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_jg(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_jl(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_jge(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_jle(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_call(&self, out: &mut String, func: &str) {
+        // `bsr` reaches only 4 KiB; the pool holds the full address.
+        let target = self.map_operand(func);
+        if Self::is_register(&target) {
+            emit!(out, "    jsr @{}\n    nop\n", target);
+            return;
+        }
+        self.literal(out, "r1", func);
+        out.push_str("    jsr @r1\n    nop\n");
+    }
+
+    fn generate_ret(&self, out: &mut String) {
+        out.push_str("    rts\n    nop\n");
+    }
+
+    fn generate_syscall(&self, out: &mut String, name: &str) {
+        let Some((_, syscall_num, arguments)) =
+            SYSCALLS.iter().find(|(syscall, _, _)| *syscall == name)
+        else {
+            emit!(out, "    ! Unknown syscall: {}\n", name);
+            return;
+        };
+        // The kernel takes the number in r3 and the arguments in r4-r7, r0
+        // and r1, where r0-r3 already are, and returns in r0. r3 holds r4
+        // and waits in r2 meanwhile.
+        if *arguments > 4 {
+            out.push_str("    mov r3,r0\n");
+        }
+        if *arguments > 5 {
+            out.push_str("    mov r8,r1\n");
+        }
+        out.push_str("    mov r3,r2\n");
+        self.load_immediate(out, "r3", *syscall_num);
+        // As glibc does, padding after `trapa` works around SH-3 cores that
+        // run the instructions after it before taking the trap.
+        out.push_str("    trapa #0x17\n");
+        out.push_str(&"    or r0,r0\n".repeat(5));
+        out.push_str("    mov r2,r3\n    mov r0,r4\n");
+    }
+
+    fn map_operand(&self, operand: &str) -> String {
+        if operand.chars().all(|c| c.is_ascii_digit() || c == '-') {
+            return operand.to_string();
+        }
+
+        if operand.starts_with('[') && operand.ends_with(']') {
+            return self.map_memory_operand(operand);
+        }
+
+        if let Some(mapped) = self.register_map.get(operand) {
+            mapped.clone()
+        } else {
+            operand.to_string()
+        }
+    }
+
+    fn map_memory_operand(&self, operand: &str) -> String {
+        if !(operand.starts_with('[') && operand.ends_with(']')) {
+            return operand.to_string();
+        }
+        // Only operands that take no code to reach have a plain form.
+        let mut code = String::new();
+        let mode = self.locate(&mut code, operand);
+        if code.is_empty() {
+            mode.operand()
+        } else {
+            operand.to_string()
+        }
+    }
+
+    fn generate_cmov_eq(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_cmov_ne(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_cmov_lt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_cmov_le(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_cmov_gt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_cmov_ge(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_cmov_ov(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_cmov_no(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_cmov_s(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_cmov_ns(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_cmov_p(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_cmov_np(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_cmov_a(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_cmov_ae(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_cmov_b(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_cmov_be(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_push(&self, out: &mut String, src: &str) {
+        let src_reg = self.value(out, src, "r1");
+        emit!(out, "    mov.l {},@-r15\n", src_reg);
+    }
+
+    fn generate_pop(&self, out: &mut String, dst: &str) {
+        emit!(out, "    mov.l @r15+,{}\n", self.map_operand(dst));
+    }
+
+    fn generate_pusha(&self, out: &mut String) {
+        for register in self.saved_by_pusha() {
+            emit!(out, "    mov.l {},@-r15\n", register);
+        }
+    }
+
+    fn generate_popa(&self, out: &mut String) {
+        for register in self.saved_by_pusha().iter().rev() {
+            emit!(out, "    mov.l @r15+,{}\n", register);
+        }
+    }
+
+    fn generate_enter(&self, out: &mut String, frame_size: &str, _nesting_level: &str) {
+        // r14 points at the saved return address, with the saved r14 above
+        // it, as SH GCC lays out frames.
+        out.push_str("    mov.l r14,@-r15\n    sts.l pr,@-r15\n    mov r15,r14\n");
+        match opt::parse_immediate(frame_size) {
+            Some(size) => self.add_constant(out, "r15", -size, "r1"),
+            None => {
+                self.literal(out, "r1", frame_size);
+                out.push_str("    sub r1,r15\n");
+            }
+        }
+    }
+
+    fn generate_leave(&self, out: &mut String) {
+        out.push_str("    mov r14,r15\n    lds.l @r15+,pr\n    mov.l @r15+,r14\n");
+    }
+
+    fn generate_imul(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_mul(out, dst, src);
+    }
+
+    fn generate_idiv(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_div(out, dst, src);
+    }
+
+    fn generate_mod(&self, out: &mut String, dst: &str, src: &str) {
+        self.divide(out, true, dst, src);
+    }
+
+    fn generate_mulh(&self, out: &mut String, dst: &str, src: &str) {
+        // The 64-bit product goes to MACH:MACL.
+        let dst_reg = self.map_operand(dst);
+        self.binary(out, "dmuls.l", dst, src);
+        emit!(out, "    sts mach,{}\n", dst_reg);
+    }
+
+    fn generate_andn(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(value) = Self::immediate(src) {
+            self.generate_and(out, dst, &(!value).to_string());
+            return;
+        }
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.value(out, src, "r1");
+        emit!(out, "    not {},r1\n    and r1,{}\n", src_reg, dst_reg);
+    }
+
+    fn generate_sal(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_shl(out, dst, src);
+    }
+
+    fn generate_sar(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "shad", true, dst, src);
+    }
+
+    fn generate_rol(&self, out: &mut String, dst: &str, src: &str) {
+        self.rotate(out, true, dst, src);
+    }
+
+    fn generate_ror(&self, out: &mut String, dst: &str, src: &str) {
+        self.rotate(out, false, dst, src);
+    }
+
+    fn generate_rcl(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_rcr(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_bextr(&self, out: &mut String, dst: &str, src: &str, imm: &str) {
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.value(out, src, "r1");
+        if let Some(control) = Self::immediate(imm) {
+            let start = (control & 0xff).min(32);
+            let length = ((control >> 8) & 0xff).min(32 - start);
+            if length == 0 {
+                self.load_immediate(out, &dst_reg, 0);
+                return;
+            }
+            Self::copy(out, &dst_reg, &src_reg);
+            self.shift(out, "shld", true, dst, &start.to_string());
+            match length {
+                8 => emit!(out, "    extu.b {d},{d}\n", d = dst_reg),
+                16 => emit!(out, "    extu.w {d},{d}\n", d = dst_reg),
+                _ if start + length == 32 => {}
+                _ => {
+                    self.load_immediate(out, "r1", (1i64 << length) - 1);
+                    emit!(out, "    and r1,{}\n", dst_reg);
+                }
+            }
+            return;
+        }
+        // Shift the field down, then to the top and back by 32 - length.
+        Self::copy(out, "r1", &src_reg);
+        let control_reg = self.value(out, imm, "r2");
+        emit!(
+            out,
+            concat!(
+                "    mov {c},r0\n    and #255,r0\n    neg r0,r0\n    shld r0,r1\n",
+                "    mov {c},r0\n    shlr8 r0\n    and #255,r0\n    add #-32,r0\n",
+                "    neg r0,r0\n    shld r0,r1\n    neg r0,r0\n    shld r0,r1\n"
+            ),
+            c = control_reg
+        );
+        Self::copy(out, &dst_reg, "r1");
+    }
+
+    fn generate_bsf(&self, out: &mut String, dst: &str, src: &str) {
+        // The lowest set bit alone is its own highest.
+        let src_reg = self.value(out, src, "r1");
+        emit!(
+            out,
+            "    mov {},r1\n    neg r1,r0\n    and r0,r1\n",
+            src_reg
+        );
+        Self::highest_bit(out, &self.map_operand(dst));
+    }
+
+    fn generate_bsr(&self, out: &mut String, dst: &str, src: &str) {
+        let src_reg = self.value(out, src, "r1");
+        Self::copy(out, "r1", &src_reg);
+        Self::highest_bit(out, &self.map_operand(dst));
+    }
+
+    fn generate_bt(&self, _out: &mut String, _dst: &str, _bit: &str) {}
+
+    fn generate_btr(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "and", true, dst, bit);
+    }
+
+    fn generate_bts(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "or", false, dst, bit);
+    }
+
+    fn generate_btc(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "xor", false, dst, bit);
+    }
+
+    fn generate_set_eq(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_set_ne(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_set_lt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_set_le(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_set_gt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_set_ge(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_set_ov(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_set_no(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_set_s(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_set_ns(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_set_p(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_set_np(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_set_a(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_set_ae(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_set_b(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_set_be(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_cmps(&self, _out: &mut String, _src1: &str, _src2: &str) {}
+
+    fn generate_scas(&self, _out: &mut String, _src: &str, _val: &str) {}
+
+    fn generate_stos(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_store(out, dst, src);
+    }
+
+    fn generate_lods(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_load(out, dst, src);
+    }
+
+    fn generate_movs(&self, out: &mut String, dst: &str, src: &str) {
+        self.load(out, "r2", src);
+        self.store(out, "r2", dst);
+    }
+
+    fn generate_cbw(&self, out: &mut String, dst: &str) {
+        self.unary(out, "exts.b", dst);
+    }
+
+    fn generate_cwd(&self, out: &mut String, dst: &str) {
+        self.unary(out, "exts.w", dst);
+    }
+
+    fn generate_cdq(&self, _out: &mut String, _dst: &str) {}
+
+    fn generate_cqo(&self, out: &mut String, dst: &str) {
+        // The high half of the 64-bit sign extension: the sign bit into T,
+        // then 0 - 0 - T.
+        let dst_reg = self.map_operand(dst);
+        emit!(out, "    shll {d}\n    subc {d},{d}\n", d = dst_reg);
+    }
+
+    fn generate_cwde(&self, out: &mut String, dst: &str) {
+        self.unary(out, "exts.w", dst);
+    }
+
+    fn generate_cdqe(&self, _out: &mut String, _dst: &str) {}
+
+    fn generate_jo(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_jno(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_js(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_jns(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_jp(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_jnp(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_ja(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_jae(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_jb(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_jbe(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_loop_eq(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_loop_ne(&self, _out: &mut String, _label: &str) {
+        unreachable!("SH compares in each reader")
+    }
+
+    fn generate_in(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("    ! SH has no IN instruction, not supported.\n");
+    }
+
+    fn generate_out(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("    ! SH has no OUT instruction, not supported.\n");
+    }
+
+    fn generate_ins(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("    ! SH has no INS instruction, not supported.\n");
+    }
+
+    fn generate_outs(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("    ! SH has no OUTS instruction, not supported.\n");
+    }
+
+    fn generate_cpuid(&self, out: &mut String) {
+        out.push_str("    ! SH does not have CPUID\n");
+    }
+
+    fn generate_lfence(&self, out: &mut String) {
+        out.push_str("    ! SH-4 keeps its memory accesses in program order\n");
+    }
+
+    fn generate_sfence(&self, out: &mut String) {
+        out.push_str("    ! SH-4 keeps its memory accesses in program order\n");
+    }
+
+    fn generate_mfence(&self, out: &mut String) {
+        out.push_str("    ! SH-4 keeps its memory accesses in program order\n");
+    }
+
+    fn generate_prefetch(&self, out: &mut String, addr: &str) {
+        self.address(out, addr);
+        out.push_str("    pref @r1\n");
+    }
+
+    fn generate_clflush(&self, out: &mut String, addr: &str) {
+        // Writes the line back and invalidates it.
+        self.address(out, addr);
+        out.push_str("    ocbp @r1\n");
+    }
+
+    fn generate_clwb(&self, out: &mut String, addr: &str) {
+        self.address(out, addr);
+        out.push_str("    ocbwb @r1\n");
+    }
+
+    fn generate_global(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".globl {}\n", symbol);
+    }
+
+    fn generate_extern(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".extern {}\n", symbol);
+    }
+
+    fn generate_align(&self, out: &mut String, n: &str) {
+        emit!(out, ".p2align {}\n", n);
+    }
+
+    fn generate_data_byte(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .byte {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_word(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .short {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_dword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .long {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_qword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .quad {}\n", name, values.join(", "));
+    }
+
+    fn generate_reserve_byte(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip {}\n", name, count);
+    }
+
+    fn generate_reserve_word(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 2 * ({})\n", name, count);
+    }
+
+    fn generate_reserve_dword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 4 * ({})\n", name, count);
+    }
+
+    fn generate_reserve_qword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 8 * ({})\n", name, count);
+    }
+
+    fn generate_equ(&self, out: &mut String, name: &str, value: &str) {
+        emit!(out, ".set {}, {}\n", name, value);
+    }
+
+    fn generate_section(&self, out: &mut String, section: &Section) {
+        match section {
+            Section::Text => out.push_str(".section .text\n"),
+            Section::Data => out.push_str(".section .data\n"),
+            Section::Bss => out.push_str(".section .bss\n"),
+            Section::Rodata => out.push_str(".section .rodata\n"),
+            Section::Custom(s) => emit!(out, ".section {}\n", s),
+        }
+    }
+
+    fn generate_label(&self, out: &mut String, name: &str) {
+        emit!(out, "{}:\n", name);
+    }
+
+    fn generate_literal_pool(&self, out: &mut String, from: usize, barrier: bool) {
+        let mut pool = self.pool.borrow_mut();
+        if pool.is_empty() {
+            return;
+        }
+        self.pending
+            .set(self.pending.get() + Self::size(&out[from..]));
+        // The entries, after the branch around them and the padding.
+        let end = self.pending.get() + 6 + 4 * pool.len();
+        if !barrier && end + POOL_MARGIN <= POOL_REACH {
+            return;
+        }
+        if !barrier {
+            out.push_str("    bra 1f\n    nop\n");
+        }
+        out.push_str("    .p2align 2\n");
+        for (n, value) in pool.drain(..) {
+            emit!(out, ".Lsh{}:\n    .long {}\n", n, value);
+        }
+        if !barrier {
+            out.push_str("1:\n");
+        }
+    }
+
+    fn get_register_width(&self) -> u32 {
+        32
+    }
+
+    fn get_reserved_registers(&self) -> Vec<String> {
+        vec![
+            "r0".to_string(),
+            "r1".to_string(),
+            "r2".to_string(),
+            "r14".to_string(),
+            "r15".to_string(),
+        ]
+    }
+
+    fn get_callee_saved_registers(&self) -> Vec<String> {
+        vec![
+            "r8".to_string(),
+            "r9".to_string(),
+            "r10".to_string(),
+            "r11".to_string(),
+            "r12".to_string(),
+            "r13".to_string(),
+            "r14".to_string(),
+        ]
+    }
+
+    fn get_push_size(&self) -> u32 {
+        4
+    }
+
+    fn get_clobbered_registers(&self, instruction: &Instruction) -> Vec<String> {
+        use Instruction::*;
+        let registers: &[&str] = match instruction {
+            Syscall(_) => &["r0", "r1", "r2", "r4"],
+            Call(_) => &["r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7"],
+            _ => &[],
+        };
+        registers.iter().map(|r| r.to_string()).collect()
+    }
+
+    fn get_frame_layout(&self) -> Option<FrameLayout> {
+        // `enter` pushes r14 and then pr, and points r14 at the saved pr.
+        Some(FrameLayout {
+            return_address: 0,
+            frame_pointer: "r14",
+            record: &["pr", "r14"],
+            slot: 4,
+        })
+    }
+
+    fn has_condition_flags(&self) -> bool {
+        false
+    }
+
+    fn generate_compare_branch(&self, out: &mut String, comparison: &Comparison, label: &str) {
+        let outcome = self.test(out, comparison);
+        match outcome {
+            Outcome::Set => emit!(out, "    bt {}\n", label),
+            Outcome::Clear => emit!(out, "    bf {}\n", label),
+            Outcome::Always => emit!(out, "    bra {}\n    nop\n", label),
+            Outcome::Never => {}
+        }
+    }
+
+    fn generate_compare_set(&self, out: &mut String, comparison: &Comparison, dst: &str) {
+        let dst_reg = self.map_operand(dst);
+        let outcome = self.test(out, comparison);
+        match outcome {
+            Outcome::Set => emit!(out, "    movt {}\n", dst_reg),
+            // 0 - (-1) - T
+            Outcome::Clear => emit!(out, "    mov #-1,{d}\n    negc {d},{d}\n", d = dst_reg),
+            Outcome::Always => self.load_immediate(out, &dst_reg, 1),
+            Outcome::Never => self.load_immediate(out, &dst_reg, 0),
+        }
+    }
+
+    fn generate_compare_cmov(
+        &self,
+        out: &mut String,
+        comparison: &Comparison,
+        dst: &str,
+        src: &str,
+    ) {
+        // Branch around the move when the condition fails.
+        let outcome = self.test(out, comparison);
+        let skip = match outcome {
+            Outcome::Set => "bf",
+            Outcome::Clear => "bt",
+            Outcome::Always => {
+                self.generate_mov(out, dst, src);
+                return;
+            }
+            Outcome::Never => {
+                return;
+            }
+        };
+        emit!(out, "    {} 1f\n", skip);
+        self.generate_mov(out, dst, src);
+        out.push_str("1:\n");
+    }
+}
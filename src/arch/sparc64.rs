@@ -0,0 +1,1483 @@
+use super::*;
+use std::collections::HashMap;
+
+pub struct SPARC64CodeGen {
+    register_map: HashMap<String, String>,
+}
+
+/// The stack bias of the V9 ABI: `%sp` and `%fp` point 2047 bytes below
+/// the frames they stand for.
+const BIAS: i64 = 2047;
+
+/// Bytes at the bottom of every frame where the kernel spills a register
+/// window and callees dump their register arguments.
+const SAVE_AREA: i64 = 176;
+
+/// The second half of a `[base + offset]` operand.
+enum Offset {
+    Immediate(i64),
+    Register(String),
+    /// `%lo` of a symbol `sethi` put the rest of in the base.
+    Low(String),
+}
+
+impl SPARC64CodeGen {
+    pub fn new() -> Self {
+        let mut register_map = HashMap::with_capacity(32);
+
+        // Function argument registers (SPARC V9 ABI)
+        register_map.insert("r0".to_string(), "%o0".to_string()); // 1st arg/return value
+        register_map.insert("r1".to_string(), "%o1".to_string()); // 2nd arg
+        register_map.insert("r2".to_string(), "%o2".to_string()); // 3rd arg
+        register_map.insert("r3".to_string(), "%o3".to_string()); // 4th arg
+        register_map.insert("r4".to_string(), "%o4".to_string()); // 5th arg
+        register_map.insert("r5".to_string(), "%o5".to_string()); // 6th arg
+
+        // Application registers
+        register_map.insert("r6".to_string(), "%g2".to_string());
+        register_map.insert("r7".to_string(), "%g3".to_string());
+
+        // Local registers, which every register window has its own of
+        register_map.insert("r8".to_string(), "%l0".to_string());
+        register_map.insert("r9".to_string(), "%l1".to_string());
+        register_map.insert("r10".to_string(), "%l2".to_string());
+        register_map.insert("r11".to_string(), "%l3".to_string());
+        register_map.insert("r12".to_string(), "%l4".to_string());
+        register_map.insert("r13".to_string(), "%l5".to_string());
+        register_map.insert("r14".to_string(), "%l6".to_string());
+        register_map.insert("r15".to_string(), "%l7".to_string());
+
+        // Special purpose registers
+        register_map.insert("sp".to_string(), "%sp".to_string()); // Stack pointer
+        register_map.insert("sb".to_string(), "%fp".to_string()); // Frame pointer
+        register_map.insert("ip".to_string(), "%o7".to_string()); // Return address
+
+        SPARC64CodeGen { register_map }
+    }
+
+    /// Whether `operand` names an integer register.
+    fn is_register(operand: &str) -> bool {
+        if operand == "%sp" || operand == "%fp" {
+            return true;
+        }
+        operand
+            .strip_prefix('%')
+            .and_then(|name| {
+                let bank = name.chars().next()?;
+                let n = name[1..].parse::<u32>().ok()?;
+                Some("goli".contains(bank) && n <= 7)
+            })
+            .unwrap_or(false)
+    }
+
+    /// How far below the value a program sees `register` points: `sp`
+    /// reads as the first byte above the register save area, where `push`
+    /// puts its data, and `sb` as the top of the frame `enter` makes.
+    fn bias(register: &str) -> i64 {
+        match register {
+            "%sp" => BIAS + SAVE_AREA,
+            "%fp" => BIAS,
+            _ => 0,
+        }
+    }
+
+    /// The value of the constant `operand`, including the unsigned masks
+    /// `opt::flags` writes for `bt` of the top bit.
+    fn immediate(operand: &str) -> Option<i64> {
+        opt::parse_immediate(operand)
+            .or_else(|| operand.trim().parse::<u64>().ok().map(|value| value as i64))
+    }
+
+    /// Whether `value` fits the 13-bit signed immediate of most
+    /// instructions.
+    fn is_simm13(value: i64) -> bool {
+        (-4096..=4095).contains(&value)
+    }
+
+    /// Builds the sign-extended 32-bit `value` in `dst`.
+    fn load_word(out: &mut String, dst: &str, value: i64) {
+        if Self::is_simm13(value) {
+            emit!(out, "    mov {}, {}\n", value, dst);
+            return;
+        }
+        if value >= 0 {
+            // `sethi` clears the upper 32 bits.
+            emit!(out, "    sethi %hi({}), {}\n", value, dst);
+            if value & 0x3ff != 0 {
+                emit!(out, "    or {d}, {}, {d}\n", value & 0x3ff, d = dst);
+            }
+            return;
+        }
+        // Set the complement's high bits, then flip every bit above the low
+        // ten with a sign-extended `xor`.
+        emit!(
+            out,
+            "    sethi %hi({}), {d}\n    xor {d}, {}, {d}\n",
+            !value & 0xffff_ffff,
+            (value & 0x3ff) - 0x400,
+            d = dst
+        );
+    }
+
+    /// Builds `value` in `dst` without another register: the high half,
+    /// then the low one shifted in 12 bits at a time.
+    fn load_immediate(out: &mut String, dst: &str, value: i64) {
+        if i32::try_from(value).is_ok() || u32::try_from(value).is_ok() {
+            Self::load_word(out, dst, value);
+            return;
+        }
+        Self::load_word(out, dst, value >> 32);
+        let mut pending = 0;
+        for (width, shift) in [(8, 24), (12, 12), (12, 0)] {
+            pending += width;
+            let chunk = (value >> shift) & ((1 << width) - 1);
+            if chunk != 0 {
+                emit!(out, "    sllx {d}, {}, {d}\n", pending, d = dst);
+                emit!(out, "    or {d}, {}, {d}\n", chunk, d = dst);
+                pending = 0;
+            }
+        }
+        if pending > 0 {
+            emit!(out, "    sllx {d}, {}, {d}\n", pending, d = dst);
+        }
+    }
+
+    /// `dst = src + value`, through `%g5` for values beyond 13 bits.
+    fn add_constant(out: &mut String, dst: &str, src: &str, value: i64) {
+        if value == 0 {
+            if dst == src {
+                return;
+            }
+            emit!(out, "    mov {}, {}\n", src, dst);
+            return;
+        }
+        if Self::is_simm13(value) {
+            emit!(out, "    add {}, {}, {}\n", src, value, dst);
+            return;
+        }
+        Self::load_immediate(out, "%g5", value);
+        emit!(out, "    add {}, %g5, {}\n", src, dst);
+    }
+
+    /// The register holding the value of `operand`, which goes in `scratch`
+    /// first if it is a constant, a symbol or a biased register.
+    fn in_register(&self, out: &mut String, operand: &str, scratch: &str) -> String {
+        let mapped = self.map_operand(operand);
+        if Self::is_register(&mapped) && Self::bias(&mapped) == 0 {
+            mapped
+        } else {
+            self.generate_mov(out, scratch, operand);
+            scratch.to_string()
+        }
+    }
+
+    /// `body` run on the register `dst` names: `%g5` holding its value when
+    /// it is `sp` or `sb`, so operations other than addition see the same
+    /// value a program does.
+    fn in_place(&self, out: &mut String, dst: &str, body: impl FnOnce(&mut String, &str)) {
+        let dst_reg = self.map_operand(dst);
+        let bias = Self::bias(&dst_reg);
+        if bias == 0 {
+            body(out, &dst_reg);
+            return;
+        }
+        emit!(out, "    add {}, {}, %g5\n", dst_reg, bias);
+        body(out, "%g5");
+        emit!(out, "    add %g5, -{}, {}\n", bias, dst_reg);
+    }
+
+    /// `operand` as base, index and displacement, the displacement counted
+    /// from where a base or index of %sp or %fp really points.
+    fn split_address(&self, operand: &str) -> Result<Address, String> {
+        let mut address =
+            super::split_address(operand, |name| self.map_operand(name), Self::is_register)?;
+        let unscaled = match &address.index {
+            Some((index, 0)) => Some(index),
+            _ => None,
+        };
+        let bias: i64 = address
+            .base
+            .iter()
+            .chain(unscaled)
+            .map(|r| Self::bias(r))
+            .sum();
+        address.displacement += bias;
+        Ok(address)
+    }
+
+    /// Puts `operand` in the form `[base + offset]`: a scaled index goes into
+    /// %g5, symbols and whatever else does not fit the instruction into %g4.
+    fn locate(&self, out: &mut String, operand: &str) -> (String, Offset) {
+        let address = address_or_error(out, self.split_address(operand));
+        let index = address.index.map(|(index, shift)| {
+            if shift == 0 {
+                return index;
+            }
+            emit!(out, "    sllx {}, {}, %g5\n", index, shift);
+            "%g5".to_string()
+        });
+        if !address.symbolic.is_empty() {
+            let expression = match address.displacement {
+                0 => address.symbolic,
+                n if n < 0 => format!("{} - {}", address.symbolic, n.unsigned_abs()),
+                n => format!("{} + {}", address.symbolic, n),
+            };
+            let (base, index) = match (address.base, index) {
+                (None, None) => {
+                    emit!(out, "    sethi %hi({}), %g4\n", expression);
+                    return ("%g4".to_string(), Offset::Low(expression));
+                }
+                (Some(base), Some(index)) => (Some(base), Some(index)),
+                (base, index) => (None, base.or(index)),
+            };
+            self.symbol(out, "%g4", &expression);
+            if let Some(base) = base {
+                emit!(out, "    add %g4, {}, %g4\n", base);
+            }
+            let offset = index.map_or(Offset::Immediate(0), Offset::Register);
+            return ("%g4".to_string(), offset);
+        }
+        let displacement = address.displacement;
+        match (address.base, index) {
+            (base, Some(index)) => {
+                let base = match base {
+                    Some(base) if displacement == 0 => base,
+                    Some(base) if Self::is_simm13(displacement) => {
+                        emit!(out, "    add {}, {}, %g4\n", base, displacement);
+                        "%g4".to_string()
+                    }
+                    base => {
+                        Self::load_immediate(out, "%g4", displacement);
+                        if let Some(base) = base {
+                            emit!(out, "    add %g4, {}, %g4\n", base);
+                        }
+                        "%g4".to_string()
+                    }
+                };
+                (base, Offset::Register(index))
+            }
+            (base, None) if Self::is_simm13(displacement) => (
+                base.unwrap_or_else(|| "%g0".to_string()),
+                Offset::Immediate(displacement),
+            ),
+            (Some(base), None) => {
+                Self::load_immediate(out, "%g4", displacement);
+                (base, Offset::Register("%g4".to_string()))
+            }
+            (None, None) => {
+                Self::load_immediate(out, "%g4", displacement);
+                ("%g4".to_string(), Offset::Immediate(0))
+            }
+        }
+    }
+
+    /// `[base + offset]` in assembler syntax.
+    fn operand(base: &str, offset: &Offset) -> String {
+        match offset {
+            Offset::Immediate(0) => format!("[{}]", base),
+            Offset::Immediate(n) if *n < 0 => format!("[{}-{}]", base, n.unsigned_abs()),
+            Offset::Immediate(n) => format!("[{}+{}]", base, n),
+            Offset::Register(index) => format!("[{}+{}]", base, index),
+            Offset::Low(expression) => format!("[{}+%lo({})]", base, expression),
+        }
+    }
+
+    /// The address of the symbol `expression` in `dst`, which the
+    /// medium/low code model keeps below 4 GiB.
+    fn symbol(&self, out: &mut String, dst: &str, expression: &str) {
+        emit!(
+            out,
+            "    sethi %hi({e}), {d}\n    or {d}, %lo({e}), {d}\n",
+            d = dst,
+            e = expression
+        );
+    }
+
+    /// `op dst, dst, src`, with `src` as an immediate when it fits.
+    fn binary(&self, out: &mut String, op: &str, dst: &str, src: &str) {
+        let src_op = match Self::immediate(src) {
+            Some(value) if Self::is_simm13(value) => value.to_string(),
+            _ => self.in_register(out, src, "%g1"),
+        };
+        self.in_place(out, dst, |out, d| {
+            emit!(out, "    {} {d}, {}, {d}\n", op, src_op, d = d)
+        });
+    }
+
+    /// A shift of `dst` by `count`, which both x86 and the `x` forms of the
+    /// shifts take modulo 64.
+    fn shift(&self, out: &mut String, op: &str, dst: &str, count: &str) {
+        let count_reg = self.map_operand(count);
+        let count = match Self::immediate(count) {
+            Some(count) => (count & 63).to_string(),
+            None if Self::is_register(&count_reg) => self.in_register(out, count, "%g1"),
+            // A symbol the assembler knows the value of.
+            None => format!("({}) & 63", count_reg),
+        };
+        self.in_place(out, dst, |out, d| {
+            emit!(out, "    {} {d}, {}, {d}\n", op, count, d = d)
+        });
+    }
+
+    /// A rotate of `dst` by `count`, as the two shifts it is made of; a
+    /// right rotate is a left one the other way round.
+    fn rotate(&self, out: &mut String, left: bool, dst: &str, count: &str) {
+        let (first, second) = if left {
+            ("sllx", "srlx")
+        } else {
+            ("srlx", "sllx")
+        };
+        if let Some(count) = Self::immediate(count) {
+            let count = count & 63;
+            if count == 0 {
+                return;
+            }
+            self.in_place(out, dst, |out, d| {
+                emit!(
+                    out,
+                    "    {} {d}, {}, %g4\n    {} {d}, {}, {d}\n    or {d}, %g4, {d}\n",
+                    first,
+                    count,
+                    second,
+                    64 - count,
+                    d = d
+                )
+            });
+            return;
+        }
+        // The opposite shift by -count, which is 64 - count modulo 64; with
+        // a count of 0 both shifts leave the value as it is.
+        let count_reg = self.in_register(out, count, "%g1");
+        self.in_place(out, dst, |out, d| {
+            emit!(out,
+                "    {} {d}, {c}, %g4\n    sub %g0, {c}, %g1\n    {} {d}, %g1, {d}\n    or {d}, %g4, {d}\n",
+                first,
+                second,
+                c = count_reg,
+                d = d
+            )
+        });
+    }
+
+    /// `op dst, mask, dst` for the single-bit mask `bit` selects; `op` is
+    /// `or`, `xor` or `andn`.
+    fn bit(&self, out: &mut String, op: &str, dst: &str, bit: &str) {
+        let bit_reg = self.map_operand(bit);
+        match Self::immediate(bit) {
+            Some(bit) if bit & 63 < 12 => {
+                let mask = (1 << (bit & 63)).to_string();
+                self.in_place(out, dst, |out, d| {
+                    emit!(out, "    {} {d}, {}, {d}\n", op, mask, d = d)
+                });
+                return;
+            }
+            Some(bit) => emit!(out, "    mov 1, %g1\n    sllx %g1, {}, %g1\n", bit & 63),
+            None if Self::is_register(&bit_reg) => {
+                let bit_reg = self.in_register(out, bit, "%g1");
+                emit!(out, "    mov 1, %g4\n    sllx %g4, {}, %g1\n", bit_reg);
+            }
+            None => emit!(
+                out,
+                "    mov 1, %g1\n    sllx %g1, ({}) & 63, %g1\n",
+                bit_reg
+            ),
+        }
+        self.in_place(out, dst, |out, d| {
+            emit!(out, "    {} {d}, %g1, {d}\n", op, d = d)
+        });
+    }
+
+    /// Sets the 64-bit condition codes from `comparison`: `a - b`, or
+    /// `a & b` with the overflow and carry clear as `test` leaves them.
+    /// For the overflow of a sum `a` of `b`, the addition is done again
+    /// from `a - b` in %g5, and likewise for a difference. `a` goes into
+    /// %g5 and `b` into %g1 if they are not registers.
+    fn compare(&self, out: &mut String, comparison: &Comparison) {
+        let a = self.in_register(out, &comparison.a, "%g5");
+        let b = match Self::immediate(&comparison.b) {
+            Some(value) if Self::is_simm13(value) => value.to_string(),
+            _ => self.in_register(out, &comparison.b, "%g1"),
+        };
+        match comparison.tested {
+            Tested::Sum => {
+                emit!(
+                    out,
+                    "    sub {}, {b}, %g5\n    addcc %g5, {b}, %g0\n",
+                    a,
+                    b = b
+                );
+                return;
+            }
+            Tested::Difference => {
+                emit!(
+                    out,
+                    "    add {}, {b}, %g5\n    subcc %g5, {b}, %g0\n",
+                    a,
+                    b = b
+                );
+                return;
+            }
+            _ => {}
+        }
+        let op = if comparison.tested == Tested::Mask {
+            "btst"
+        } else {
+            "cmp"
+        };
+        // `btst` names the mask first.
+        let (first, second) = if comparison.tested == Tested::Mask {
+            (b, a)
+        } else {
+            (a, b)
+        };
+        emit!(out, "    {} {}, {}\n", op, first, second);
+    }
+
+    /// The suffix of the branches and conditional moves on `condition`,
+    /// which tests the overflow for a sum or difference.
+    fn condition_code(condition: Condition, tested: Tested) -> &'static str {
+        if let Tested::Sum | Tested::Difference = tested {
+            return match condition {
+                Condition::S => "vs",
+                Condition::Ns => "vc",
+                _ => unreachable!("the overflow is only set or clear, not {:?}", condition),
+            };
+        }
+        match condition {
+            Condition::Eq => "e",
+            Condition::Ne => "ne",
+            Condition::Lt => "l",
+            Condition::Le => "le",
+            Condition::Gt => "g",
+            Condition::Ge => "ge",
+            Condition::B => "cs",
+            Condition::Be => "leu",
+            Condition::A => "gu",
+            Condition::Ae => "cc",
+            Condition::S => "neg",
+            Condition::Ns => "pos",
+        }
+    }
+
+    /// The registers `pusha` saves and `popa` restores: every one the
+    /// register map names but `%sp`, in a fixed order.
+    fn saved_by_pusha(&self) -> Vec<&String> {
+        let mut registers: Vec<&String> = self
+            .register_map
+            .values()
+            .filter(|r| Self::is_register(r) && *r != "%sp")
+            .collect();
+        registers.sort();
+        registers.dedup();
+        registers
+    }
+
+    /// `op dst, dst`, for the shift pairs that sign-extend in place.
+    fn extend(&self, out: &mut String, dst: &str, bits: u32) {
+        self.in_place(out, dst, |out, d| {
+            emit!(
+                out,
+                "    sllx {d}, {b}, {d}\n    srax {d}, {b}, {d}\n",
+                b = 64 - bits,
+                d = d
+            )
+        });
+    }
+
+    /// `instruction`, then the `nop` in its delay slot.
+    fn delayed(out: &mut String, instruction: &str) {
+        emit!(out, "    {}\n    nop\n", instruction);
+    }
+}
+
+impl ArchCodeGen for SPARC64CodeGen {
+    fn get_register_map(&self) -> HashMap<String, String> {
+        self.register_map.clone()
+    }
+
+    fn get_syntax_header(&self) -> String {
+        // The program's r6 and r7 live in the application registers %g2
+        // and %g3, which the assembler wants declared.
+        ".register %g2, #scratch\n.register %g3, #scratch\n.text\n.align 4\n\n".to_string()
+    }
+
+    fn generate_mov(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        let src_op = self.map_operand(src);
+
+        if let Some(value) = Self::immediate(src) {
+            if Self::bias(&dst_reg) != 0 {
+                Self::load_immediate(out, "%g5", value);
+                emit!(out, "    add %g5, -{}, {}\n", Self::bias(&dst_reg), dst_reg);
+                return;
+            }
+            Self::load_immediate(out, &dst_reg, value);
+            return;
+        }
+
+        if Self::is_register(&src_op) {
+            // The difference of the biases keeps the value a program sees.
+            {
+                Self::add_constant(
+                    out,
+                    &dst_reg,
+                    &src_op,
+                    Self::bias(&src_op) - Self::bias(&dst_reg),
+                );
+                return;
+            };
+        }
+
+        // A symbol, or a constant the assembler knows the value of.
+        if Self::bias(&dst_reg) != 0 {
+            self.symbol(out, "%g5", &src_op);
+            emit!(out, "    add %g5, -{}, {}\n", Self::bias(&dst_reg), dst_reg);
+            return;
+        }
+        self.symbol(out, &dst_reg, &src_op);
+    }
+
+    fn generate_lea(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        let (base, offset) = self.locate(out, src);
+        let bias = Self::bias(&dst_reg);
+        let target = if bias == 0 { dst_reg.as_str() } else { "%g4" };
+        match offset {
+            Offset::Immediate(0) if base == target => {}
+            Offset::Immediate(0) => emit!(out, "    mov {}, {}\n", base, target),
+            Offset::Immediate(n) => {
+                emit!(out, "    add {}, {}, {}\n", base, n, target)
+            }
+            Offset::Register(index) => {
+                emit!(out, "    add {}, {}, {}\n", base, index, target)
+            }
+            Offset::Low(expression) => {
+                emit!(out, "    or {}, %lo({}), {}\n", base, expression, target)
+            }
+        }
+        if bias != 0 {
+            Self::add_constant(out, &dst_reg, "%g4", -bias);
+        }
+    }
+
+    fn generate_load(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        let (base, offset) = self.locate(out, src);
+        let address = Self::operand(&base, &offset);
+        if Self::bias(&dst_reg) != 0 {
+            emit!(out, "    ldx {}, %g1\n", address);
+            self.generate_mov(out, dst, "%g1");
+            return;
+        }
+        emit!(out, "    ldx {}, {}\n", address, dst_reg);
+    }
+
+    fn generate_store(&self, out: &mut String, dst: &str, src: &str) {
+        let src_reg = self.in_register(out, src, "%g1");
+        let (base, offset) = self.locate(out, dst);
+        emit!(
+            out,
+            "    stx {}, {}\n",
+            src_reg,
+            Self::operand(&base, &offset)
+        );
+    }
+
+    fn generate_add(&self, out: &mut String, dst: &str, src: &str) {
+        // Adding keeps the bias of `sp` and `sb`.
+        let dst_reg = self.map_operand(dst);
+        match Self::immediate(src) {
+            Some(value) if Self::is_simm13(value) => {
+                emit!(out, "    add {d}, {}, {d}\n", value, d = dst_reg)
+            }
+            _ => {
+                let src_reg = self.in_register(out, src, "%g1");
+                emit!(out, "    add {d}, {}, {d}\n", src_reg, d = dst_reg);
+            }
+        }
+    }
+
+    fn generate_sub(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        match Self::immediate(src) {
+            Some(value) if Self::is_simm13(value) => {
+                emit!(out, "    sub {d}, {}, {d}\n", value, d = dst_reg)
+            }
+            _ => {
+                let src_reg = self.in_register(out, src, "%g1");
+                emit!(out, "    sub {d}, {}, {d}\n", src_reg, d = dst_reg);
+            }
+        }
+    }
+
+    fn generate_mul(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "mulx", dst, src);
+    }
+
+    fn generate_div(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "sdivx", dst, src);
+    }
+
+    fn generate_inc(&self, out: &mut String, dst: &str) {
+        self.generate_add(out, dst, "1");
+    }
+
+    fn generate_dec(&self, out: &mut String, dst: &str) {
+        self.generate_sub(out, dst, "1");
+    }
+
+    fn generate_neg(&self, out: &mut String, dst: &str) {
+        self.in_place(out, dst, |out, d| {
+            emit!(out, "    sub %g0, {d}, {d}\n", d = d)
+        });
+    }
+
+    fn generate_and(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "and", dst, src);
+    }
+
+    fn generate_or(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "or", dst, src);
+    }
+
+    fn generate_xor(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "xor", dst, src);
+    }
+
+    fn generate_not(&self, out: &mut String, dst: &str) {
+        self.in_place(out, dst, |out, d| {
+            emit!(out, "    xnor {d}, %g0, {d}\n", d = d)
+        });
+    }
+
+    fn generate_shl(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "sllx", dst, src);
+    }
+
+    fn generate_shr(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "srlx", dst, src);
+    }
+
+    fn generate_cmp(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_test(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_jmp(&self, out: &mut String, label: &str) {
+        let target = self.map_operand(label);
+        if Self::is_register(&target) {
+            let target = self.in_register(out, label, "%g1");
+            Self::delayed(out, &format!("jmp {}", target));
+            return;
+        }
+        Self::delayed(out, &format!("ba %xcc, {}", label));
+    }
+
+    fn generate_je(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_jne(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_jg(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_jl(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_jge(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_jle(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_call(&self, out: &mut String, func: &str) {
+        // `sp` already leaves the callee its register save area.
+        let target = self.map_operand(func);
+        if Self::is_register(&target) {
+            let target = self.in_register(out, func, "%g1");
+            Self::delayed(out, &format!("call {}", target));
+            return;
+        }
+        Self::delayed(out, &format!("call {}", func));
+    }
+
+    fn generate_ret(&self, out: &mut String) {
+        // The return address is in %o7 after `leave`, as in a function
+        // that never ran `enter`.
+        Self::delayed(out, "retl");
+    }
+
+    fn generate_syscall(&self, out: &mut String, name: &str) {
+        let syscall_num = match name {
+            "read" => "3",
+            "write" => "4",
+            "exit" => "1",
+            "open" => "5",
+            "close" => "6",
+            "mmap" => "71",
+            "munmap" => "73",
+            "brk" => "17",
+            _ => {
+                emit!(
+                    out,
+                    "    ! Unknown syscall: {}\n    mov 0, %g1\n    ta 0x6d\n",
+                    name
+                );
+                return;
+            }
+        };
+        // The kernel flags errors in the carry and returns a positive errno;
+        // negate it to match the other targets.
+        emit!(
+            out,
+            "    mov {}, %g1\n    ta 0x6d\n    bcc,pt %xcc, 1f\n    nop\n    sub %g0, %o0, %o0\n1:\n",
+            syscall_num
+        );
+    }
+
+    fn map_operand(&self, operand: &str) -> String {
+        if operand.chars().all(|c| c.is_ascii_digit() || c == '-') {
+            return operand.to_string();
+        }
+
+        if operand.starts_with('[') && operand.ends_with(']') {
+            return self.map_memory_operand(operand);
+        }
+
+        if let Some(mapped) = self.register_map.get(operand) {
+            mapped.clone()
+        } else {
+            operand.to_string()
+        }
+    }
+
+    fn map_memory_operand(&self, operand: &str) -> String {
+        if !(operand.starts_with('[') && operand.ends_with(']')) {
+            return operand.to_string();
+        }
+        let Ok(address) = self.split_address(operand) else {
+            return operand.to_string();
+        };
+        let base = address.base.unwrap_or_else(|| "%g0".to_string());
+        let offset = match (address.symbolic.is_empty(), address.displacement) {
+            (true, displacement) => Offset::Immediate(displacement),
+            (false, 0) => Offset::Register(address.symbolic),
+            (false, displacement) => {
+                Offset::Register(format!("{} + {}", address.symbolic, displacement))
+            }
+        };
+        Self::operand(&base, &offset)
+    }
+
+    fn generate_cmov_eq(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_cmov_ne(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_cmov_lt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_cmov_le(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_cmov_gt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_cmov_ge(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_cmov_ov(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_cmov_no(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_cmov_s(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_cmov_ns(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_cmov_p(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_cmov_np(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_cmov_a(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_cmov_ae(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_cmov_b(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_cmov_be(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_push(&self, out: &mut String, src: &str) {
+        // Pushes keep %sp 16-byte aligned, as the ABI requires. The value is
+        // taken first, so `push sp` pushes it as it was.
+        let src_reg = self.in_register(out, src, "%g1");
+        emit!(
+            out,
+            "    add %sp, -16, %sp\n    stx {}, [%sp+{}]\n",
+            src_reg,
+            BIAS + SAVE_AREA
+        );
+    }
+
+    fn generate_pop(&self, out: &mut String, dst: &str) {
+        let dst_reg = self.map_operand(dst);
+        let value = if Self::bias(&dst_reg) == 0 {
+            dst_reg.as_str()
+        } else {
+            "%g1"
+        };
+        emit!(
+            out,
+            "    ldx [%sp+{}], {}\n    add %sp, 16, %sp\n",
+            BIAS + SAVE_AREA,
+            value
+        );
+        if value == "%g1" {
+            self.generate_mov(out, dst, "%g1");
+        }
+    }
+
+    fn generate_pusha(&self, out: &mut String) {
+        let registers = self.saved_by_pusha();
+        let size = (registers.len() * 8).next_multiple_of(16);
+        emit!(out, "    add %sp, -{}, %sp\n", size);
+        for (i, register) in registers.iter().enumerate() {
+            emit!(
+                out,
+                "    stx {}, [%sp+{}]\n",
+                register,
+                BIAS + SAVE_AREA + i as i64 * 8
+            );
+        }
+    }
+
+    fn generate_popa(&self, out: &mut String) {
+        let registers = self.saved_by_pusha();
+        let size = (registers.len() * 8).next_multiple_of(16);
+        for (i, register) in registers.iter().enumerate() {
+            emit!(
+                out,
+                "    ldx [%sp+{}], {}\n",
+                BIAS + SAVE_AREA + i as i64 * 8,
+                register
+            );
+        }
+        emit!(out, "    add %sp, {}, %sp\n", size);
+    }
+
+    fn generate_enter(&self, out: &mut String, frame_size: &str, _nesting_level: &str) {
+        // A new register window: the caller's registers stay behind, its
+        // %o registers turning into the %i ones, which carry the arguments
+        // back to r0-r5. `sb` is the caller's `sp` less its save area, and
+        // the frame and a new save area go below.
+        match opt::parse_immediate(frame_size) {
+            Some(size) => {
+                let frame = SAVE_AREA + (size + 15) / 16 * 16;
+                if Self::is_simm13(-frame) {
+                    emit!(out, "    save %sp, -{}, %sp\n", frame);
+                } else {
+                    Self::load_immediate(out, "%g1", -frame);
+                    out.push_str("    save %sp, %g1, %sp\n");
+                }
+            }
+            None => emit!(
+                out,
+                "    save %sp, -({} + ((({}) + 15) & -16)), %sp\n",
+                SAVE_AREA,
+                frame_size
+            ),
+        }
+        for n in 0..6 {
+            emit!(out, "    mov %i{n}, %o{n}\n");
+        }
+    }
+
+    fn generate_leave(&self, out: &mut String) {
+        // The results go back to the caller's %o registers with it.
+        for n in 0..6 {
+            emit!(out, "    mov %o{n}, %i{n}\n");
+        }
+        out.push_str("    restore\n");
+    }
+
+    fn generate_imul(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_mul(out, dst, src);
+    }
+
+    fn generate_idiv(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_div(out, dst, src);
+    }
+
+    fn generate_mod(&self, out: &mut String, dst: &str, src: &str) {
+        // dst - dst / src * src
+        let src_op = match Self::immediate(src) {
+            Some(value) if Self::is_simm13(value) => value.to_string(),
+            _ => self.in_register(out, src, "%g1"),
+        };
+        self.in_place(out, dst, |out, d| {
+            emit!(
+                out,
+                "    sdivx {d}, {s}, %g4\n    mulx %g4, {s}, %g4\n    sub {d}, %g4, {d}\n",
+                d = d,
+                s = src_op
+            )
+        });
+    }
+
+    fn generate_mulh(&self, out: &mut String, dst: &str, src: &str) {
+        // There is no high multiply before VIS3: add up the products of the
+        // 32-bit halves for the unsigned high half, then subtract the other
+        // operand for each negative one. The operands wait on the stack,
+        // big-endian, so each half loads with `lduw`.
+        let src_reg = self.in_register(out, src, "%g1");
+        let top = BIAS + SAVE_AREA;
+        let d = self.map_operand(dst);
+        emit!(
+            out,
+            "    add %sp, -32, %sp\n    stx {}, [%sp+{a}]\n    stx {}, [%sp+{b}]\n",
+            d,
+            src_reg,
+            a = top,
+            b = top + 8
+        );
+        let (a_high, a_low, b_high, b_low) = (top, top + 4, top + 8, top + 12);
+        emit!(
+            out,
+            concat!(
+                "    lduw [%sp+{al}], %g1\n    lduw [%sp+{bl}], %g4\n    mulx %g1, %g4, %g5\n",
+                "    srlx %g5, 32, %g5\n    lduw [%sp+{bh}], %g4\n    mulx %g1, %g4, %g1\n",
+                "    srl %g1, 0, {d}\n    add %g5, {d}, %g5\n    srlx %g1, 32, {d}\n",
+                "    lduw [%sp+{ah}], %g1\n    lduw [%sp+{bl}], %g4\n    mulx %g1, %g4, %g4\n",
+                "    srl %g4, 0, %g1\n    add %g5, %g1, %g5\n    srlx %g4, 32, %g4\n",
+                "    add {d}, %g4, {d}\n    lduw [%sp+{ah}], %g1\n    lduw [%sp+{bh}], %g4\n",
+                "    mulx %g1, %g4, %g4\n    add {d}, %g4, {d}\n    srlx %g5, 32, %g5\n",
+                "    add {d}, %g5, {d}\n    ldx [%sp+{a}], %g1\n    ldx [%sp+{b}], %g4\n",
+                "    srax %g1, 63, %g5\n    and %g5, %g4, %g5\n    sub {d}, %g5, {d}\n",
+                "    srax %g4, 63, %g5\n    and %g5, %g1, %g5\n    sub {d}, %g5, {d}\n",
+                "    add %sp, 32, %sp\n"
+            ),
+            al = a_low,
+            ah = a_high,
+            bl = b_low,
+            bh = b_high,
+            a = top,
+            b = top + 8,
+            d = d
+        );
+    }
+
+    fn generate_andn(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "andn", dst, src);
+    }
+
+    fn generate_sal(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_shl(out, dst, src);
+    }
+
+    fn generate_sar(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "srax", dst, src);
+    }
+
+    fn generate_rol(&self, out: &mut String, dst: &str, src: &str) {
+        self.rotate(out, true, dst, src);
+    }
+
+    fn generate_ror(&self, out: &mut String, dst: &str, src: &str) {
+        self.rotate(out, false, dst, src);
+    }
+
+    fn generate_rcl(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_rcr(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_bextr(&self, out: &mut String, dst: &str, src: &str, imm: &str) {
+        if let Some(control) = Self::immediate(imm) {
+            let start = control & 0xff;
+            let length = ((control >> 8) & 0xff).min(64 - start.min(64));
+            if length == 0 {
+                self.generate_mov(out, dst, "0");
+                return;
+            }
+            let src_reg = self.in_register(out, src, "%g1");
+            self.in_place(out, dst, |out, d| {
+                emit!(out, "    srlx {}, {}, {}\n", src_reg, start, d);
+                if length < 64 {
+                    emit!(
+                        out,
+                        "    sllx {d}, {l}, {d}\n    srlx {d}, {l}, {d}\n",
+                        l = 64 - length,
+                        d = d
+                    );
+                }
+            });
+            return;
+        }
+        let src_reg = self.in_register(out, src, "%g1");
+        // Shift the field down, clearing it for starts past the top; then
+        // shift it to the top and back by 64 - length, unless the length
+        // takes in everything. A length of 0 leaves nothing.
+        let control_reg = self.in_register(out, imm, "%g4");
+        emit!(
+            out,
+            concat!(
+                "    and {c}, 0xff, %g5\n    srlx {s}, %g5, %g1\n    cmp %g5, 64\n",
+                "    movcc %xcc, 0, %g1\n    srlx {c}, 8, %g5\n    and %g5, 0xff, %g5\n",
+                "    cmp %g5, 64\n    bcc %xcc, 1f\n    sub %g0, %g5, %g4\n",
+                "    sllx %g1, %g4, %g1\n    srlx %g1, %g4, %g1\n    movrz %g5, 0, %g1\n1:\n"
+            ),
+            c = control_reg,
+            s = src_reg
+        );
+        self.generate_mov(out, dst, "%g1");
+    }
+
+    fn generate_bsf(&self, out: &mut String, dst: &str, src: &str) {
+        // The bits below the lowest set one, counted; -1 when there is none.
+        let src_reg = self.in_register(out, src, "%g1");
+        emit!(
+            out,
+            "    sub %g0, {s}, %g5\n    and %g5, {s}, %g5\n    sub %g5, 1, %g5\n    popc %g5, %g5\n    movrz {s}, -1, %g5\n",
+            s = src_reg
+        );
+        self.generate_mov(out, dst, "%g5");
+    }
+
+    fn generate_bsr(&self, out: &mut String, dst: &str, src: &str) {
+        // Copy the highest set bit into every bit below it, and count them.
+        let src_reg = self.in_register(out, src, "%g1");
+        emit!(
+            out,
+            "    srlx {s}, 1, %g4\n    or {s}, %g4, %g5\n",
+            s = src_reg
+        );
+        for shift in [2, 4, 8, 16, 32] {
+            emit!(out, "    srlx %g5, {}, %g4\n    or %g5, %g4, %g5\n", shift);
+        }
+        out.push_str("    popc %g5, %g5\n    sub %g5, 1, %g5\n");
+        self.generate_mov(out, dst, "%g5");
+    }
+
+    fn generate_bt(&self, _out: &mut String, _dst: &str, _bit: &str) {}
+
+    fn generate_btr(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "andn", dst, bit);
+    }
+
+    fn generate_bts(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "or", dst, bit);
+    }
+
+    fn generate_btc(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "xor", dst, bit);
+    }
+
+    fn generate_set_eq(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_set_ne(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_set_lt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_set_le(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_set_gt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_set_ge(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_set_ov(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_set_no(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_set_s(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_set_ns(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_set_p(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_set_np(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_set_a(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_set_ae(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_set_b(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_set_be(&self, _out: &mut String, _dst: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_cmps(&self, _out: &mut String, _src1: &str, _src2: &str) {}
+
+    fn generate_scas(&self, _out: &mut String, _src: &str, _val: &str) {}
+
+    fn generate_stos(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_store(out, dst, src);
+    }
+
+    fn generate_lods(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_load(out, dst, src);
+    }
+
+    fn generate_movs(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_load(out, "%g1", src);
+        self.generate_store(out, dst, "%g1");
+    }
+
+    fn generate_cbw(&self, out: &mut String, dst: &str) {
+        self.extend(out, dst, 8);
+    }
+
+    fn generate_cwd(&self, out: &mut String, dst: &str) {
+        self.extend(out, dst, 16);
+    }
+
+    fn generate_cdq(&self, out: &mut String, dst: &str) {
+        // `sra` works on the low word and sign-extends the result.
+        self.in_place(out, dst, |out, d| {
+            emit!(out, "    sra {d}, 0, {d}\n", d = d)
+        });
+    }
+
+    fn generate_cqo(&self, out: &mut String, dst: &str) {
+        // The high half of the 128-bit sign extension: all sign bits.
+        self.in_place(out, dst, |out, d| {
+            emit!(out, "    srax {d}, 63, {d}\n", d = d)
+        });
+    }
+
+    fn generate_cwde(&self, out: &mut String, dst: &str) {
+        self.extend(out, dst, 16);
+    }
+
+    fn generate_cdqe(&self, out: &mut String, dst: &str) {
+        self.generate_cdq(out, dst);
+    }
+
+    fn generate_jo(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_jno(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_js(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_jns(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_jp(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_jnp(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_ja(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_jae(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_jb(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_jbe(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_loop_eq(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_loop_ne(&self, _out: &mut String, _label: &str) {
+        unreachable!("SPARC64 compares in each reader")
+    }
+
+    fn generate_in(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("    ! SPARC has no IN instruction, not supported.\n");
+    }
+
+    fn generate_out(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("    ! SPARC has no OUT instruction, not supported.\n");
+    }
+
+    fn generate_ins(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("    ! SPARC has no INS instruction, not supported.\n");
+    }
+
+    fn generate_outs(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("    ! SPARC has no OUTS instruction, not supported.\n");
+    }
+
+    fn generate_cpuid(&self, out: &mut String) {
+        out.push_str("    ! SPARC does not have CPUID\n");
+    }
+
+    fn generate_lfence(&self, out: &mut String) {
+        out.push_str("    membar #LoadLoad | #LoadStore\n");
+    }
+
+    fn generate_sfence(&self, out: &mut String) {
+        out.push_str("    membar #StoreStore\n");
+    }
+
+    fn generate_mfence(&self, out: &mut String) {
+        out.push_str("    membar #LoadLoad | #LoadStore | #StoreLoad | #StoreStore\n");
+    }
+
+    fn generate_prefetch(&self, out: &mut String, addr: &str) {
+        let (base, offset) = self.locate(out, addr);
+        emit!(out, "    prefetch {}, 0\n", Self::operand(&base, &offset));
+    }
+
+    fn generate_clflush(&self, out: &mut String, _addr: &str) {
+        out.push_str("    ! SPARC caches are coherent, nothing to flush\n");
+    }
+
+    fn generate_clwb(&self, out: &mut String, _addr: &str) {
+        out.push_str("    ! SPARC caches are coherent, nothing to write back\n");
+    }
+
+    fn generate_global(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".globl {}\n", symbol);
+    }
+
+    fn generate_extern(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".extern {}\n", symbol);
+    }
+
+    fn generate_align(&self, out: &mut String, n: &str) {
+        emit!(out, ".p2align {}\n", n);
+    }
+
+    fn generate_data_byte(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .byte {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_word(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .half {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_dword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .word {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_qword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .xword {}\n", name, values.join(", "));
+    }
+
+    fn generate_reserve_byte(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip {}\n", name, count);
+    }
+
+    fn generate_reserve_word(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 2 * ({})\n", name, count);
+    }
+
+    fn generate_reserve_dword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 4 * ({})\n", name, count);
+    }
+
+    fn generate_reserve_qword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 8 * ({})\n", name, count);
+    }
+
+    fn generate_equ(&self, out: &mut String, name: &str, value: &str) {
+        emit!(out, ".set {}, {}\n", name, value);
+    }
+
+    fn generate_section(&self, out: &mut String, section: &Section) {
+        match section {
+            Section::Text => out.push_str(".section .text\n"),
+            Section::Data => out.push_str(".section .data\n"),
+            Section::Bss => out.push_str(".section .bss\n"),
+            Section::Rodata => out.push_str(".section .rodata\n"),
+            Section::Custom(s) => emit!(out, ".section {}\n", s),
+        }
+    }
+
+    fn generate_label(&self, out: &mut String, name: &str) {
+        emit!(out, "{}:\n", name);
+    }
+
+    fn get_register_width(&self) -> u32 {
+        64
+    }
+
+    fn get_reserved_registers(&self) -> Vec<String> {
+        ["%g1", "%g4", "%g5", "%sp", "%fp", "%o7"]
+            .iter()
+            .map(|r| r.to_string())
+            .collect()
+    }
+
+    fn get_callee_saved_registers(&self) -> Vec<String> {
+        (0..8).map(|n| format!("%l{}", n)).collect()
+    }
+
+    fn get_push_size(&self) -> u32 {
+        16
+    }
+
+    fn get_clobbered_registers(&self, instruction: &Instruction) -> Vec<String> {
+        use Instruction::*;
+        let registers: &[&str] = match instruction {
+            Syscall(_) => &["%g1", "%o0", "%o1"],
+            Call(_) => &[
+                "%o0", "%o1", "%o2", "%o3", "%o4", "%o5", "%o7", "%g1", "%g2", "%g3", "%g4", "%g5",
+            ],
+            _ => &[],
+        };
+        registers.iter().map(|r| r.to_string()).collect()
+    }
+
+    fn generate_branch_hint(&self, out: &mut String, from: usize, likely: bool) {
+        // `bl,pt %xcc, label` predicts taken, `bl,pn %xcc, label` not taken.
+        let hint = if likely { ",pt" } else { ",pn" };
+        let branch = out.split_off(from);
+        for line in branch.lines() {
+            let indent = line.len() - line.trim_start().len();
+            let end = line[indent..]
+                .find(char::is_whitespace)
+                .map_or(line.len(), |n| indent + n);
+            let mnemonic = &line[indent..end];
+            if mnemonic.starts_with('b')
+                && !mnemonic.contains(',')
+                && !matches!(mnemonic, "ba" | "btst")
+            {
+                emit!(out, "{}{}{}\n", &line[..end], hint, &line[end..]);
+            } else {
+                emit!(out, "{line}\n");
+            }
+        }
+    }
+
+    fn has_condition_flags(&self) -> bool {
+        false
+    }
+    fn compares(&self, tested: Tested) -> bool {
+        matches!(
+            tested,
+            Tested::Operands | Tested::Mask | Tested::Sum | Tested::Difference
+        )
+    }
+
+    fn generate_compare_branch(&self, out: &mut String, comparison: &Comparison, label: &str) {
+        let mnemonic = format!(
+            "b{}",
+            Self::condition_code(comparison.condition, comparison.tested)
+        );
+        self.compare(out, comparison);
+        Self::delayed(out, &format!("{} %xcc, {}", mnemonic, label));
+    }
+
+    fn generate_compare_set(&self, out: &mut String, comparison: &Comparison, dst: &str) {
+        // Neither `mov` touches the condition codes.
+        self.compare(out, comparison);
+        let condition = Self::condition_code(comparison.condition, comparison.tested);
+        self.in_place(out, dst, |out, d| {
+            emit!(
+                out,
+                "    mov 0, {d}\n    mov{} %xcc, 1, {d}\n",
+                condition,
+                d = d
+            )
+        });
+    }
+
+    fn generate_compare_cmov(
+        &self,
+        out: &mut String,
+        comparison: &Comparison,
+        dst: &str,
+        src: &str,
+    ) {
+        // The source is ready before the compare, which leaves %g4 alone.
+        let src_op = match Self::immediate(src) {
+            Some(value) if (-1024..=1023).contains(&value) => value.to_string(),
+            _ => self.in_register(out, src, "%g4"),
+        };
+        let condition = Self::condition_code(comparison.condition, comparison.tested);
+        self.compare(out, comparison);
+        self.in_place(out, dst, |out, d| {
+            emit!(out, "    mov{} %xcc, {}, {}\n", condition, src_op, d)
+        });
+    }
+}
@@ -0,0 +1,1535 @@
+use super::*;
+use std::cell::Cell;
+use std::collections::HashMap;
+
+pub struct XtensaCodeGen {
+    register_map: HashMap<String, String>,
+    /// Whether functions run in register windows of their own, opened with
+    /// `entry` and closed with `retw` as ESP-IDF builds them, rather than
+    /// under the call0 ABI.
+    windowed: bool,
+    /// Whether to use the 16-bit instructions of the code density option
+    /// where their operands fit.
+    narrow: bool,
+    /// Numbers the entries the backend adds to the literal pool.
+    literals: Cell<usize>,
+}
+
+/// The constants `beqi`, `bnei`, `blti` and `bgei` compare with.
+const B4CONST: [i64; 16] = [-1, 1, 2, 3, 4, 5, 6, 7, 8, 10, 12, 16, 32, 64, 128, 256];
+
+/// The constants `bltui` and `bgeui` compare with.
+const B4CONSTU: [i64; 16] = [
+    32768, 65536, 2, 3, 4, 5, 6, 7, 8, 10, 12, 16, 32, 64, 128, 256,
+];
+
+/// Bytes `entry` reserves for the register save areas window overflows
+/// spill the caller's and the function's own registers into.
+const SAVE_AREA: i64 = 32;
+
+/// The registers the Linux kernel takes syscall arguments from, in order.
+const SYSCALL_ARGUMENTS: [&str; 6] = ["a6", "a3", "a4", "a5", "a8", "a9"];
+
+/// The right side of a comparison, as a branch takes it.
+enum Operand {
+    Zero,
+    Constant(i64),
+    Register(String),
+}
+
+impl XtensaCodeGen {
+    pub fn new() -> Self {
+        let mut register_map = HashMap::with_capacity(16);
+
+        // Outgoing arguments of `call8`, which the callee finds in its own
+        // a2-a7; each function's entry copies its arguments here.
+        register_map.insert("r0".to_string(), "a10".to_string()); // 1st arg/return value
+        register_map.insert("r1".to_string(), "a11".to_string()); // 2nd arg
+        register_map.insert("r2".to_string(), "a12".to_string()); // 3rd arg
+        register_map.insert("r3".to_string(), "a13".to_string()); // 4th arg
+        register_map.insert("r4".to_string(), "a14".to_string()); // 5th arg
+        register_map.insert("r5".to_string(), "a15".to_string()); // 6th arg
+
+        // The window's own registers, which calls leave alone
+        register_map.insert("r6".to_string(), "a2".to_string());
+        register_map.insert("r7".to_string(), "a3".to_string());
+        register_map.insert("r8".to_string(), "a4".to_string());
+        register_map.insert("r9".to_string(), "a5".to_string());
+        register_map.insert("r10".to_string(), "a6".to_string());
+
+        // Special purpose registers
+        register_map.insert("sp".to_string(), "a1".to_string()); // Stack pointer
+        register_map.insert("sb".to_string(), "a7".to_string()); // Frame pointer
+
+        XtensaCodeGen {
+            register_map,
+            windowed: true,
+            narrow: false,
+            literals: Cell::new(0),
+        }
+    }
+
+    pub fn call0() -> Self {
+        let mut register_map = HashMap::with_capacity(16);
+
+        // Argument registers; a2-a5 return values
+        register_map.insert("r0".to_string(), "a2".to_string()); // 1st arg/return value
+        register_map.insert("r1".to_string(), "a3".to_string()); // 2nd arg
+        register_map.insert("r2".to_string(), "a4".to_string()); // 3rd arg
+        register_map.insert("r3".to_string(), "a5".to_string()); // 4th arg
+        register_map.insert("r4".to_string(), "a6".to_string()); // 5th arg
+        register_map.insert("r5".to_string(), "a7".to_string()); // 6th arg
+
+        // Temporary registers
+        register_map.insert("r6".to_string(), "a10".to_string());
+        register_map.insert("r7".to_string(), "a11".to_string());
+
+        // Saved registers
+        register_map.insert("r8".to_string(), "a12".to_string());
+        register_map.insert("r9".to_string(), "a13".to_string());
+        register_map.insert("r10".to_string(), "a14".to_string());
+
+        // Special purpose registers
+        register_map.insert("sp".to_string(), "a1".to_string()); // Stack pointer
+        register_map.insert("sb".to_string(), "a15".to_string()); // Frame pointer
+
+        XtensaCodeGen {
+            register_map,
+            windowed: false,
+            narrow: false,
+            literals: Cell::new(0),
+        }
+    }
+
+    pub fn density() -> Self {
+        XtensaCodeGen {
+            narrow: true,
+            ..Self::new()
+        }
+    }
+
+    /// Whether `operand` names an address register.
+    fn is_register(operand: &str) -> bool {
+        operand
+            .strip_prefix('a')
+            .filter(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+            .and_then(|n| n.parse::<u32>().ok())
+            .is_some_and(|n| n <= 15)
+    }
+
+    /// The value of the constant `operand` as the 32 bits a register holds,
+    /// including the unsigned masks `opt::flags` writes for `bt` of the top
+    /// bit.
+    fn immediate(operand: &str) -> Option<i64> {
+        opt::parse_immediate(operand)
+            .or_else(|| operand.trim().parse::<u64>().ok().map(|value| value as i64))
+            .map(|value| value as i32 as i64)
+    }
+
+    /// `op` in its 16-bit form when the density option is on.
+    fn narrowed(&self, op: &str) -> String {
+        if self.narrow {
+            format!("{}.n", op)
+        } else {
+            op.to_string()
+        }
+    }
+
+    /// `value`, a constant or an expression the assembler knows the value
+    /// of, loaded into `dst` from the literal pool.
+    fn literal(&self, out: &mut String, dst: &str, value: &str) {
+        let n = self.literals.get();
+        self.literals.set(n + 1);
+        emit!(
+            out,
+            "    .literal .Lxt{n}, {}\n    l32r {}, .Lxt{n}\n",
+            value,
+            dst,
+            n = n
+        );
+    }
+
+    /// Builds `value` in `dst`: `movi` takes 12 bits, and the literal pool
+    /// holds the rest.
+    fn load_immediate(&self, out: &mut String, dst: &str, value: i64) {
+        let value = value as i32 as i64;
+        if self.narrow && (-32..=95).contains(&value) {
+            emit!(out, "    movi.n {}, {}\n", dst, value);
+            return;
+        }
+        if (-2048..=2047).contains(&value) {
+            emit!(out, "    movi {}, {}\n", dst, value);
+            return;
+        }
+        self.literal(out, dst, &value.to_string());
+    }
+
+    /// `mov dst, src`, or nothing when they are the same register.
+    fn copy(&self, out: &mut String, dst: &str, src: &str) {
+        if dst == src {
+            return;
+        }
+        emit!(out, "    {} {}, {}\n", self.narrowed("mov"), dst, src);
+    }
+
+    /// `dst = src + value`: `addi` takes 8 bits, `addmi` multiples of 256
+    /// up to 32K, and the literal pool the rest, in a9 when `dst` is `src`,
+    /// which then must not be a9.
+    fn add_constant(&self, out: &mut String, dst: &str, src: &str, value: i64) {
+        let value = value as i32 as i64;
+        if value == 0 {
+            self.copy(out, dst, src);
+            return;
+        }
+        if self.narrow && (value == -1 || (1..=15).contains(&value)) {
+            emit!(out, "    addi.n {}, {}, {}\n", dst, src, value);
+            return;
+        }
+        if (-128..=127).contains(&value) {
+            emit!(out, "    addi {}, {}, {}\n", dst, src, value);
+            return;
+        }
+        let high = (value + 128) & !255;
+        let low = value - high;
+        if (-32768..=32512).contains(&high) {
+            emit!(out, "    addmi {}, {}, {}\n", dst, src, high);
+            if low != 0 {
+                emit!(out, "    addi {d}, {d}, {}\n", low, d = dst);
+            }
+            return;
+        }
+        let scratch = if dst == src { "a9" } else { dst };
+        self.literal(out, scratch, &value.to_string());
+        emit!(out, "    add {}, {}, {}\n", dst, src, scratch);
+    }
+
+    /// `code` writing `dst`. Under the windowed ABI, a write to the stack
+    /// pointer goes through a9 and `movsp`, which moves the caller's
+    /// register save area along if a window overflow has spilled it there.
+    fn writing(&self, out: &mut String, dst: &str, code: impl FnOnce(&mut String, &str)) {
+        if self.windowed && dst == "a1" {
+            code(out, "a9");
+            out.push_str("    movsp a1, a9\n");
+            return;
+        }
+        code(out, dst);
+    }
+
+    /// Moves the stack pointer by `bytes`.
+    fn adjust_sp(&self, out: &mut String, bytes: i64) {
+        self.writing(out, "a1", |out, dst| {
+            self.add_constant(out, dst, "a1", bytes)
+        });
+    }
+
+    /// The register holding `operand`, which goes in `scratch` first if it
+    /// is a constant or a symbol.
+    fn in_register(&self, out: &mut String, operand: &str, scratch: &str) -> String {
+        let mapped = self.map_operand(operand);
+        if Self::is_register(&mapped) {
+            mapped
+        } else {
+            self.generate_mov(out, scratch, operand);
+            scratch.to_string()
+        }
+    }
+
+    /// `operand` as base, index and displacement over a0-a15, the
+    /// displacement cut to the 32 bits of an `l32r` literal.
+    fn split_address(&self, operand: &str) -> Result<Address, String> {
+        let mut address =
+            super::split_address(operand, |name| self.map_operand(name), Self::is_register)?;
+        address.displacement = address.displacement as i32 as i64;
+        Ok(address)
+    }
+
+    /// Puts `operand` in the form `base, offset` with the offset a multiple of
+    /// 4 up to 1020, as `l32i` and `s32i` take it: symbols and larger offsets
+    /// go into a9 first, and an index is added on top with
+    /// `addx2`/`addx4`/`addx8`.
+    fn locate(&self, out: &mut String, operand: &str) -> (String, i64) {
+        let address = address_or_error(out, self.split_address(operand));
+        let fits = |offset: i64| (0..=1020).contains(&offset) && offset % 4 == 0;
+        let mut offset = address.displacement;
+        let mut base = address.base;
+        if !address.symbolic.is_empty() {
+            let expression = match offset {
+                0 => address.symbolic,
+                offset => format!("{} + {}", address.symbolic, offset),
+            };
+            self.literal(out, "a9", &expression);
+            if let Some(base) = &base {
+                emit!(out, "    add a9, a9, {}\n", base);
+            }
+            base = Some("a9".to_string());
+            offset = 0;
+        } else if !fits(offset) || (base.is_none() && address.index.is_none()) {
+            match &base {
+                Some(base) => self.add_constant(out, "a9", base, offset),
+                None => self.load_immediate(out, "a9", offset),
+            }
+            base = Some("a9".to_string());
+            offset = 0;
+        }
+        let base = match (base, address.index) {
+            (Some(base), None) => base,
+            (None, None) => unreachable!("addresses without a base are built in a9"),
+            (None, Some((index, 0))) => index,
+            (None, Some((index, shift))) => {
+                emit!(out, "    slli a9, {}, {}\n", index, shift);
+                "a9".to_string()
+            }
+            (Some(base), Some((index, shift))) => {
+                match shift {
+                    0 => emit!(out, "    add a9, {}, {}\n", base, index),
+                    1..=3 => {
+                        emit!(out, "    addx{} a9, {}, {}\n", 1 << shift, index, base)
+                    }
+                    _ => emit!(
+                        out,
+                        "    .error \"{} scales an index by more than 8\"\n",
+                        operand.trim()
+                    ),
+                }
+                "a9".to_string()
+            }
+        };
+        (base, offset)
+    }
+
+    /// `op value, operand` for the 32-bit load or store `op`.
+    fn access(&self, out: &mut String, op: &str, value: &str, operand: &str) {
+        let (base, offset) = self.locate(out, operand);
+        let op = if offset <= 60 {
+            self.narrowed(op)
+        } else {
+            op.to_string()
+        };
+        emit!(out, "    {} {}, {}, {}\n", op, value, base, offset);
+    }
+
+    /// `op dst, dst, src`, with a constant `src` in a8.
+    fn binary(&self, out: &mut String, op: &str, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.in_register(out, src, "a8");
+        self.writing(out, &dst_reg, |out, target| {
+            emit!(out, "    {} {}, {}, {}\n", op, target, dst_reg, src_reg)
+        });
+    }
+
+    /// A shift of `dst` by `count`: an immediate form, or `op` by the shift
+    /// amount register for a count in a register. `op` is `sll`, `srl` or
+    /// `sra`, and like x86 takes the count modulo 32.
+    fn shift(&self, out: &mut String, op: &str, dst: &str, count: &str) {
+        let dst_reg = self.map_operand(dst);
+        match Self::immediate(count).map(|count| count & 31) {
+            Some(0) => {}
+            Some(count) => match op {
+                "sll" => emit!(out, "    slli {d}, {d}, {}\n", count, d = dst_reg),
+                "sra" => emit!(out, "    srai {d}, {d}, {}\n", count, d = dst_reg),
+                // `srli` shifts by up to 15; `extui` takes the 16 or fewer
+                // bits that remain after a longer shift.
+                _ if count < 16 => emit!(out, "    srli {d}, {d}, {}\n", count, d = dst_reg),
+                _ => emit!(
+                    out,
+                    "    extui {d}, {d}, {}, {}\n",
+                    count,
+                    32 - count,
+                    d = dst_reg
+                ),
+            },
+            None => {
+                let count_reg = self.in_register(out, count, "a8");
+                let set = if op == "sll" { "ssl" } else { "ssr" };
+                emit!(
+                    out,
+                    "    {} {}\n    {} {d}, {d}\n",
+                    set,
+                    count_reg,
+                    op,
+                    d = dst_reg
+                );
+            }
+        }
+    }
+
+    /// A rotate of `dst` by `count`: a funnel shift of `dst` joined with
+    /// itself, right by the shift amount register.
+    fn rotate(&self, out: &mut String, left: bool, dst: &str, count: &str) {
+        let dst_reg = self.map_operand(dst);
+        match Self::immediate(count) {
+            Some(count) => {
+                let amount = if left {
+                    (32 - (count & 31)) & 31
+                } else {
+                    count & 31
+                };
+                if amount == 0 {
+                    return;
+                }
+                emit!(
+                    out,
+                    "    ssai {}\n    src {d}, {d}, {d}\n",
+                    amount,
+                    d = dst_reg
+                );
+            }
+            None => {
+                // `ssl` sets the amount to 32 - count, a right rotate by
+                // which is a left one by count.
+                let count_reg = self.in_register(out, count, "a8");
+                let set = if left { "ssl" } else { "ssr" };
+                emit!(
+                    out,
+                    "    {} {}\n    src {d}, {d}, {d}\n",
+                    set,
+                    count_reg,
+                    d = dst_reg
+                );
+            }
+        }
+    }
+
+    /// `op dst, dst, mask` for the single-bit mask `bit` selects, inverted
+    /// when `clear`.
+    fn bit(&self, out: &mut String, op: &str, clear: bool, dst: &str, bit: &str) {
+        let dst_reg = self.map_operand(dst);
+        match Self::immediate(bit) {
+            Some(bit) => {
+                let mask = 1i64 << (bit & 31);
+                self.load_immediate(out, "a9", if clear { !mask } else { mask });
+            }
+            None => {
+                let bit_reg = self.in_register(out, bit, "a8");
+                emit!(out, "    ssl {}\n    movi a9, 1\n    sll a9, a9\n", bit_reg);
+                if clear {
+                    out.push_str("    movi a8, -1\n    xor a9, a9, a8\n");
+                }
+            }
+        }
+        emit!(out, "    {} {d}, {d}, a9\n", op, d = dst_reg);
+    }
+
+    /// `dst = 31 - x`, the bit index `nsau` in `x` counts to: -1 for 32.
+    fn bit_index(&self, out: &mut String, dst: &str, x: &str) {
+        emit!(out, "    movi a9, 31\n    sub {}, a9, {}\n", dst, x);
+    }
+
+    /// The register holding the left side of `comparison` and the right side,
+    /// put in a8 and a9 first where they are not in registers: constants stay
+    /// as they are for the branches that take them. For a mask, a8 holds
+    /// `a & b`, compared with zero.
+    fn compared(&self, out: &mut String, comparison: &Comparison) -> (String, Operand) {
+        let a = self.in_register(out, &comparison.a, "a8");
+        if comparison.tested == Tested::Mask {
+            let b = self.in_register(out, &comparison.b, "a9");
+            emit!(out, "    and a8, {}, {}\n", a, b);
+            return ("a8".to_string(), Operand::Zero);
+        }
+        match Self::immediate(&comparison.b) {
+            Some(0) => (a, Operand::Zero),
+            Some(value) => (a, Operand::Constant(value)),
+            None => {
+                let b = self.in_register(out, &comparison.b, "a9");
+                (a, Operand::Register(b))
+            }
+        }
+    }
+
+    /// The branch on `condition` between the registers `a` and `b`.
+    fn register_branch(condition: Condition, a: &str, b: &str) -> String {
+        let (op, first, second) = match condition {
+            Condition::Eq => ("beq", a, b),
+            Condition::Ne => ("bne", a, b),
+            Condition::Lt => ("blt", a, b),
+            Condition::Ge => ("bge", a, b),
+            Condition::Gt => ("blt", b, a),
+            Condition::Le => ("bge", b, a),
+            Condition::B => ("bltu", a, b),
+            Condition::Ae => ("bgeu", a, b),
+            Condition::A => ("bltu", b, a),
+            Condition::Be => ("bgeu", b, a),
+            Condition::S | Condition::Ns => unreachable!("sign tests compare the difference"),
+        };
+        format!("{} {}, {}, ", op, first, second)
+    }
+
+    /// The branch on `condition` between `a` and the constant `value`, if
+    /// an immediate form takes it: a > b is a >= b + 1, and a <= b is
+    /// a < b + 1.
+    fn immediate_branch(condition: Condition, a: &str, value: i64) -> Option<String> {
+        let unsigned = value as u32 as i64;
+        let (op, constant, table) = match condition {
+            Condition::Eq => ("beqi", value, &B4CONST),
+            Condition::Ne => ("bnei", value, &B4CONST),
+            Condition::Lt => ("blti", value, &B4CONST),
+            Condition::Ge => ("bgei", value, &B4CONST),
+            Condition::Gt => ("bgei", value + 1, &B4CONST),
+            Condition::Le => ("blti", value + 1, &B4CONST),
+            Condition::B => ("bltui", unsigned, &B4CONSTU),
+            Condition::Ae => ("bgeui", unsigned, &B4CONSTU),
+            Condition::A => ("bgeui", unsigned + 1, &B4CONSTU),
+            Condition::Be => ("bltui", unsigned + 1, &B4CONSTU),
+            Condition::S | Condition::Ns => return None,
+        };
+        table
+            .contains(&constant)
+            .then(|| format!("{} {}, {}, ", op, a, constant))
+    }
+
+    /// Tests `comparison`, and returns the branch that is taken when it
+    /// holds, up to its target; no branch when it never holds.
+    fn test(&self, out: &mut String, comparison: &Comparison) -> Option<String> {
+        let condition = comparison.condition;
+        if comparison.tested == Tested::Mask && matches!(condition, Condition::Eq | Condition::Ne) {
+            // `test` of a single bit tests it directly, and other masks
+            // with `bany`/`bnone`.
+            let a = self.in_register(out, &comparison.a, "a8");
+            let equal = condition == Condition::Eq;
+            if let Some(mask) = Self::immediate(&comparison.b)
+                && (mask as u32).is_power_of_two()
+            {
+                let op = if equal { "bbci" } else { "bbsi" };
+                let bit = (mask as u32).trailing_zeros();
+                return Some(format!("{} {}, {}, ", op, a, bit));
+            }
+            let b = self.in_register(out, &comparison.b, "a9");
+            let op = if equal { "bnone" } else { "bany" };
+            return Some(format!("{} {}, {}, ", op, a, b));
+        }
+        let (a, b) = self.compared(out, comparison);
+        let branch = match (condition, b) {
+            (Condition::Eq | Condition::Be, Operand::Zero) => format!("beqz {}, ", a),
+            (Condition::Ne | Condition::A, Operand::Zero) => format!("bnez {}, ", a),
+            (Condition::Lt | Condition::S, Operand::Zero) => format!("bltz {}, ", a),
+            (Condition::Ge | Condition::Ns, Operand::Zero) => format!("bgez {}, ", a),
+            (Condition::Gt, Operand::Zero) => format!("bgei {}, 1, ", a),
+            (Condition::Le, Operand::Zero) => format!("blti {}, 1, ", a),
+            (Condition::B, Operand::Zero) => return None,
+            (Condition::Ae, Operand::Zero) => "j ".to_string(),
+            (Condition::S | Condition::Ns, b) => {
+                match b {
+                    Operand::Constant(value) => self.add_constant(out, "a9", &a, -value),
+                    Operand::Register(b) => emit!(out, "    sub a9, {}, {}\n", a, b),
+                    Operand::Zero => unreachable!("sign tests against zero read `a`"),
+                }
+                let op = if condition == Condition::S {
+                    "bltz"
+                } else {
+                    "bgez"
+                };
+                format!("{} a9, ", op)
+            }
+            (condition, Operand::Constant(value)) => {
+                match Self::immediate_branch(condition, &a, value) {
+                    Some(branch) => branch,
+                    None => {
+                        self.load_immediate(out, "a9", value);
+                        Self::register_branch(condition, &a, "a9")
+                    }
+                }
+            }
+            (condition, Operand::Register(b)) => Self::register_branch(condition, &a, &b),
+        };
+        Some(branch)
+    }
+
+    /// The registers `pusha` saves and `popa` restores: every one the
+    /// register map names but a1, in a fixed order.
+    fn saved_by_pusha(&self) -> Vec<&String> {
+        let mut registers: Vec<&String> = self
+            .register_map
+            .values()
+            .filter(|r| Self::is_register(r) && *r != "a1")
+            .collect();
+        registers.sort();
+        registers.dedup();
+        registers
+    }
+
+    /// `op dst, dst`, for negation.
+    fn unary(&self, out: &mut String, op: &str, dst: &str) {
+        let dst_reg = self.map_operand(dst);
+        emit!(out, "    {} {}, {}\n", op, dst_reg, dst_reg);
+    }
+
+    /// Sign-extends `dst` from bit `bit`.
+    fn extend(&self, out: &mut String, dst: &str, bit: u32) {
+        let dst_reg = self.map_operand(dst);
+        emit!(out, "    sext {d}, {d}, {}\n", bit, d = dst_reg);
+    }
+}
+
+impl ArchCodeGen for XtensaCodeGen {
+    fn get_register_map(&self) -> HashMap<String, String> {
+        self.register_map.clone()
+    }
+
+    fn get_syntax_header(&self) -> String {
+        // With `--text-section-literals` the assembler puts the literal pool
+        // here, ahead of the `l32r`s that read it backwards.
+        ".text\n.literal_position\n.align 4\n\n".to_string()
+    }
+
+    fn generate_mov(&self, out: &mut String, dst: &str, src: &str) {
+        // Only loads and stores reach memory.
+        if opt::is_memory(dst) {
+            self.generate_store(out, dst, src);
+            return;
+        }
+        if opt::is_memory(src) {
+            self.generate_load(out, dst, src);
+            return;
+        }
+        let dst_reg = self.map_operand(dst);
+        let src_op = self.map_operand(src);
+
+        if let Some(value) = Self::immediate(src) {
+            self.writing(out, &dst_reg, |out, dst| {
+                self.load_immediate(out, dst, value)
+            });
+            return;
+        }
+
+        if Self::is_register(&src_op) {
+            if self.windowed && dst_reg == "a1" {
+                emit!(out, "    movsp a1, {}\n", src_op);
+                return;
+            }
+            emit!(
+                out,
+                "    {} {}, {}\n",
+                self.narrowed("mov"),
+                dst_reg,
+                src_op
+            );
+            return;
+        }
+
+        // Labels and constants the assembler knows the value of.
+        self.writing(out, &dst_reg, |out, dst| self.literal(out, dst, &src_op));
+    }
+
+    fn generate_lea(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        let address = address_or_error(out, self.split_address(src));
+        if address.base.is_none() && address.index.is_none() {
+            let expression = match (address.symbolic.is_empty(), address.displacement) {
+                (true, displacement) => {
+                    self.generate_mov(out, dst, &displacement.to_string());
+                    return;
+                }
+                (false, 0) => address.symbolic,
+                (false, displacement) => format!("{} + {}", address.symbolic, displacement),
+            };
+            self.writing(out, &dst_reg, |out, dst| {
+                self.literal(out, dst, &expression)
+            });
+            return;
+        }
+        let (base, offset) = self.locate(out, src);
+        self.writing(out, &dst_reg, |out, dst| {
+            self.add_constant(out, dst, &base, offset)
+        });
+    }
+
+    fn generate_load(&self, out: &mut String, dst: &str, src: &str) {
+        self.access(out, "l32i", &self.map_operand(dst), src);
+    }
+
+    fn generate_store(&self, out: &mut String, dst: &str, src: &str) {
+        let src_reg = self.in_register(out, src, "a8");
+        self.access(out, "s32i", &src_reg, dst);
+    }
+
+    fn generate_add(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(value) = Self::immediate(src) {
+            let dst_reg = self.map_operand(dst);
+            self.writing(out, &dst_reg, |out, target| {
+                self.add_constant(out, target, &dst_reg, value)
+            });
+            return;
+        }
+        self.binary(out, &self.narrowed("add"), dst, src);
+    }
+
+    fn generate_sub(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(value) = Self::immediate(src) {
+            self.generate_add(out, dst, &(-value).to_string());
+            return;
+        }
+        self.binary(out, "sub", dst, src);
+    }
+
+    fn generate_mul(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "mull", dst, src);
+    }
+
+    fn generate_div(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "quos", dst, src);
+    }
+
+    fn generate_inc(&self, out: &mut String, dst: &str) {
+        self.generate_add(out, dst, "1");
+    }
+
+    fn generate_dec(&self, out: &mut String, dst: &str) {
+        self.generate_add(out, dst, "-1");
+    }
+
+    fn generate_neg(&self, out: &mut String, dst: &str) {
+        self.unary(out, "neg", dst);
+    }
+
+    fn generate_and(&self, out: &mut String, dst: &str, src: &str) {
+        // A mask of the low 16 bits or fewer is a field extraction.
+        if let Some(value) = Self::immediate(src) {
+            let bits = (value as u32).trailing_ones();
+            if (1..=16).contains(&bits) && (value as u32) >> bits == 0 {
+                let dst_reg = self.map_operand(dst);
+                emit!(out, "    extui {d}, {d}, 0, {}\n", bits, d = dst_reg);
+                return;
+            }
+        }
+        self.binary(out, "and", dst, src);
+    }
+
+    fn generate_or(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "or", dst, src);
+    }
+
+    fn generate_xor(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "xor", dst, src);
+    }
+
+    fn generate_not(&self, out: &mut String, dst: &str) {
+        self.binary(out, "xor", dst, "-1");
+    }
+
+    fn generate_shl(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "sll", dst, src);
+    }
+
+    fn generate_shr(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "srl", dst, src);
+    }
+
+    fn generate_cmp(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_test(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_jmp(&self, out: &mut String, label: &str) {
+        let target = self.map_operand(label);
+        if Self::is_register(&target) {
+            emit!(out, "    jx {}\n", target);
+            return;
+        }
+        emit!(out, "    j {}\n", label);
+    }
+
+    fn generate_je(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_jne(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_jg(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_jl(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_jge(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_jle(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_call(&self, out: &mut String, func: &str) {
+        // `call8` rotates the window by eight registers, so the callee gets
+        // r0-r5 as its a2-a7 and its return address and stack pointer in
+        // a8 and a9.
+        let size = if self.windowed { 8 } else { 0 };
+        let target = self.map_operand(func);
+        if Self::is_register(&target) {
+            emit!(out, "    callx{} {}\n", size, target);
+            return;
+        }
+        emit!(out, "    call{} {}\n", size, func);
+    }
+
+    fn generate_ret(&self, out: &mut String) {
+        if !self.windowed {
+            emit!(out, "    {}\n", self.narrowed("ret"));
+            return;
+        }
+        // The results go back to the caller's r0-r5 with the window.
+        for n in 0..6 {
+            self.copy(out, &format!("a{}", n + 2), &format!("a{}", n + 10));
+        }
+        emit!(out, "    {}\n", self.narrowed("retw"));
+    }
+
+    fn generate_syscall(&self, out: &mut String, name: &str) {
+        let (syscall_num, arguments) = match name {
+            "read" => (12, 3),
+            "write" => (13, 3),
+            "exit" => (118, 1),
+            "open" => (8, 3),
+            "close" => (9, 1),
+            // `mmap2`, whose offset counts 4096-byte pages.
+            "mmap" => (80, 6),
+            "munmap" => (81, 2),
+            "brk" => (83, 1),
+            _ => {
+                emit!(out, "    # Unknown syscall: {}\n", name);
+                return;
+            }
+        };
+        // The kernel takes the number in a2 and the arguments in a6, a3,
+        // a4, a5, a8 and a9, returns the result or -errno in a2 and leaves
+        // the other registers alone.
+        if !self.windowed {
+            // r1-r3 are in place already; r4 moves to a8, freeing a6 for r0,
+            // and comes back after.
+            out.push_str("    mov a8, a6\n");
+            if arguments > 5 {
+                out.push_str("    mov a9, a7\n");
+            }
+            out.push_str("    mov a6, a2\n");
+            self.load_immediate(out, "a2", syscall_num);
+            out.push_str("    syscall\n    mov a6, a8\n");
+            return;
+        }
+        // The arguments come from r0-r5 in a10-a15, and the window's own a2
+        // to a6 that the kernel reads from are saved: in a8 and a9 for a
+        // single argument, on the stack for more.
+        let saved: Vec<&str> = ["a2"]
+            .into_iter()
+            .chain(SYSCALL_ARGUMENTS.into_iter().take(arguments.min(4)))
+            .collect();
+        let on_stack = arguments > 1;
+        if on_stack {
+            self.adjust_sp(out, -SAVE_AREA);
+            for (i, register) in saved.iter().enumerate() {
+                emit!(out, "    s32i {}, a1, {}\n", register, i * 4);
+            }
+        } else {
+            out.push_str("    mov a8, a2\n    mov a9, a6\n");
+        }
+        for (n, register) in SYSCALL_ARGUMENTS.iter().enumerate().take(arguments) {
+            emit!(out, "    mov {}, a{}\n", register, n + 10);
+        }
+        self.load_immediate(out, "a2", syscall_num);
+        out.push_str("    syscall\n    mov a10, a2\n");
+        if on_stack {
+            for (i, register) in saved.iter().enumerate() {
+                emit!(out, "    l32i {}, a1, {}\n", register, i * 4);
+            }
+            self.adjust_sp(out, SAVE_AREA);
+        } else {
+            out.push_str("    mov a2, a8\n    mov a6, a9\n");
+        }
+    }
+
+    fn map_operand(&self, operand: &str) -> String {
+        if operand.chars().all(|c| c.is_ascii_digit() || c == '-') {
+            return operand.to_string();
+        }
+
+        if operand.starts_with('[') && operand.ends_with(']') {
+            return self.map_memory_operand(operand);
+        }
+
+        if let Some(mapped) = self.register_map.get(operand) {
+            mapped.clone()
+        } else {
+            operand.to_string()
+        }
+    }
+
+    fn map_memory_operand(&self, operand: &str) -> String {
+        if !(operand.starts_with('[') && operand.ends_with(']')) {
+            return operand.to_string();
+        }
+        let Ok(address) = self.split_address(operand) else {
+            return operand.to_string();
+        };
+        let base = address.base.unwrap_or_else(|| "a9".to_string());
+        match (address.symbolic.is_empty(), address.displacement) {
+            (true, displacement) => format!("{}, {}", base, displacement),
+            (false, 0) => format!("{}, {}", base, address.symbolic),
+            (false, displacement) => {
+                format!("{}, {} + {}", base, address.symbolic, displacement)
+            }
+        }
+    }
+
+    fn generate_cmov_eq(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_cmov_ne(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_cmov_lt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_cmov_le(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_cmov_gt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_cmov_ge(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_cmov_ov(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_cmov_no(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_cmov_s(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_cmov_ns(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_cmov_p(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_cmov_np(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_cmov_a(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_cmov_ae(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_cmov_b(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_cmov_be(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_push(&self, out: &mut String, src: &str) {
+        // Pushes keep a1 16-byte aligned, as the ABI requires.
+        let src_reg = self.in_register(out, src, "a8");
+        self.adjust_sp(out, -16);
+        emit!(out, "    s32i {}, a1, 0\n", src_reg);
+    }
+
+    fn generate_pop(&self, out: &mut String, dst: &str) {
+        emit!(out, "    l32i {}, a1, 0\n", self.map_operand(dst));
+        self.adjust_sp(out, 16);
+    }
+
+    fn generate_pusha(&self, out: &mut String) {
+        let registers = self.saved_by_pusha();
+        let size = (registers.len() * 4).next_multiple_of(16);
+        self.adjust_sp(out, -(size as i64));
+        for (i, register) in registers.iter().enumerate() {
+            emit!(out, "    s32i {}, a1, {}\n", register, i * 4);
+        }
+    }
+
+    fn generate_popa(&self, out: &mut String) {
+        let registers = self.saved_by_pusha();
+        let size = (registers.len() * 4).next_multiple_of(16);
+        for (i, register) in registers.iter().enumerate() {
+            emit!(out, "    l32i {}, a1, {}\n", register, i * 4);
+        }
+        self.adjust_sp(out, size as i64);
+    }
+
+    fn generate_enter(&self, out: &mut String, frame_size: &str, _nesting_level: &str) {
+        // Under the windowed ABI, `entry` has opened the frame already and
+        // the caller's frame pointer is in its own window. Under call0, a15
+        // points at the saved a15 and return address, as $fp does on MIPS.
+        let fp = self.map_operand("sb");
+        if self.windowed {
+            self.copy(out, &fp, "a1");
+        } else {
+            self.adjust_sp(out, -16);
+            out.push_str("    s32i a0, a1, 4\n    s32i a15, a1, 0\n    mov a15, a1\n");
+        }
+        match opt::parse_immediate(frame_size) {
+            Some(0) => {}
+            Some(size) => self.adjust_sp(out, -((size + 15) / 16 * 16)),
+            None => {
+                self.literal(out, "a9", &format!("-((({}) + 15) & -16)", frame_size));
+                self.writing(out, "a1", |out, dst| {
+                    emit!(out, "    add {}, a1, a9\n", dst)
+                });
+            }
+        }
+    }
+
+    fn generate_leave(&self, out: &mut String) {
+        if self.windowed {
+            out.push_str("    movsp a1, a7\n");
+            return;
+        }
+        out.push_str(
+            "    mov a1, a15\n    l32i a0, a1, 4\n    l32i a15, a1, 0\n    addi a1, a1, 16\n",
+        );
+    }
+
+    fn generate_imul(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_mul(out, dst, src);
+    }
+
+    fn generate_idiv(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_div(out, dst, src);
+    }
+
+    fn generate_mod(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "rems", dst, src);
+    }
+
+    fn generate_mulh(&self, out: &mut String, dst: &str, src: &str) {
+        self.binary(out, "mulsh", dst, src);
+    }
+
+    fn generate_andn(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(value) = Self::immediate(src) {
+            self.generate_and(out, dst, &(!value).to_string());
+            return;
+        }
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.in_register(out, src, "a9");
+        emit!(
+            out,
+            "    movi a8, -1\n    xor a8, a8, {}\n    and {d}, {d}, a8\n",
+            src_reg,
+            d = dst_reg
+        );
+    }
+
+    fn generate_sal(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_shl(out, dst, src);
+    }
+
+    fn generate_sar(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, "sra", dst, src);
+    }
+
+    fn generate_rol(&self, out: &mut String, dst: &str, src: &str) {
+        self.rotate(out, true, dst, src);
+    }
+
+    fn generate_ror(&self, out: &mut String, dst: &str, src: &str) {
+        self.rotate(out, false, dst, src);
+    }
+
+    fn generate_rcl(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_rcr(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_bextr(&self, out: &mut String, dst: &str, src: &str, imm: &str) {
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.in_register(out, src, "a8");
+        if let Some(control) = Self::immediate(imm) {
+            let start = control & 0xff;
+            let length = ((control >> 8) & 0xff).min(32 - start.min(32));
+            if length == 0 {
+                self.load_immediate(out, &dst_reg, 0);
+                return;
+            }
+            if length <= 16 {
+                emit!(
+                    out,
+                    "    extui {}, {}, {}, {}\n",
+                    dst_reg,
+                    src_reg,
+                    start,
+                    length
+                );
+                return;
+            }
+            // Wider fields go to the top and back down.
+            let up = 32 - start - length;
+            if up > 0 {
+                emit!(out, "    slli {}, {}, {}\n", dst_reg, src_reg, up);
+            } else {
+                self.copy(out, &dst_reg, &src_reg);
+            }
+            emit!(out, "    srli {d}, {d}, {}\n", 32 - length, d = dst_reg);
+            return;
+        }
+        // Shift the field down, then to the top and back by 32 - length.
+        let control_reg = self.in_register(out, imm, "a9");
+        emit!(
+            out,
+            "    ssr {c}\n    srl a8, {s}\n    extui a9, {c}, 8, 8\n    neg a9, a9\n    addi a9, a9, 32\n    ssl a9\n    sll a8, a8\n    ssr a9\n    srl {d}, a8\n",
+            c = control_reg,
+            s = src_reg,
+            d = dst_reg
+        );
+    }
+
+    fn generate_bsf(&self, out: &mut String, dst: &str, src: &str) {
+        // The lowest set bit alone is its own highest.
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.in_register(out, src, "a9");
+        emit!(
+            out,
+            "    neg a8, {s}\n    and a8, a8, {s}\n    nsau a8, a8\n",
+            s = src_reg
+        );
+        self.bit_index(out, &dst_reg, "a8");
+    }
+
+    fn generate_bsr(&self, out: &mut String, dst: &str, src: &str) {
+        let dst_reg = self.map_operand(dst);
+        let src_reg = self.in_register(out, src, "a9");
+        emit!(out, "    nsau a8, {}\n", src_reg);
+        self.bit_index(out, &dst_reg, "a8");
+    }
+
+    fn generate_bt(&self, _out: &mut String, _dst: &str, _bit: &str) {}
+
+    fn generate_btr(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "and", true, dst, bit);
+    }
+
+    fn generate_bts(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "or", false, dst, bit);
+    }
+
+    fn generate_btc(&self, out: &mut String, dst: &str, bit: &str) {
+        self.bit(out, "xor", false, dst, bit);
+    }
+
+    fn generate_set_eq(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_set_ne(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_set_lt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_set_le(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_set_gt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_set_ge(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_set_ov(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_set_no(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_set_s(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_set_ns(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_set_p(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_set_np(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_set_a(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_set_ae(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_set_b(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_set_be(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_cmps(&self, _out: &mut String, _src1: &str, _src2: &str) {}
+
+    fn generate_scas(&self, _out: &mut String, _src: &str, _val: &str) {}
+
+    fn generate_stos(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_store(out, dst, src);
+    }
+
+    fn generate_lods(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_load(out, dst, src);
+    }
+
+    fn generate_movs(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_load(out, "a8", src);
+        self.generate_store(out, dst, "a8");
+    }
+
+    fn generate_cbw(&self, out: &mut String, dst: &str) {
+        self.extend(out, dst, 7);
+    }
+
+    fn generate_cwd(&self, out: &mut String, dst: &str) {
+        self.extend(out, dst, 15);
+    }
+
+    fn generate_cdq(&self, _out: &mut String, _dst: &str) {}
+
+    fn generate_cqo(&self, out: &mut String, dst: &str) {
+        // The high half of the 64-bit sign extension: all sign bits.
+        let dst_reg = self.map_operand(dst);
+        emit!(out, "    srai {}, {}, 31\n", dst_reg, dst_reg);
+    }
+
+    fn generate_cwde(&self, out: &mut String, dst: &str) {
+        self.extend(out, dst, 15);
+    }
+
+    fn generate_cdqe(&self, _out: &mut String, _dst: &str) {}
+
+    fn generate_jo(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_jno(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_js(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_jns(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_jp(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_jnp(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_ja(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_jae(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_jb(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_jbe(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_loop_eq(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_loop_ne(&self, _out: &mut String, _label: &str) {
+        unreachable!("Xtensa compares in each reader")
+    }
+
+    fn generate_in(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("    # Xtensa has no IN instruction, not supported.\n");
+    }
+
+    fn generate_out(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("    # Xtensa has no OUT instruction, not supported.\n");
+    }
+
+    fn generate_ins(&self, out: &mut String, _dst: &str, _port: &str) {
+        out.push_str("    # Xtensa has no INS instruction, not supported.\n");
+    }
+
+    fn generate_outs(&self, out: &mut String, _port: &str, _src: &str) {
+        out.push_str("    # Xtensa has no OUTS instruction, not supported.\n");
+    }
+
+    fn generate_cpuid(&self, out: &mut String) {
+        out.push_str("    # Xtensa does not have CPUID\n");
+    }
+
+    fn generate_lfence(&self, out: &mut String) {
+        out.push_str("    memw\n");
+    }
+
+    fn generate_sfence(&self, out: &mut String) {
+        out.push_str("    memw\n");
+    }
+
+    fn generate_mfence(&self, out: &mut String) {
+        out.push_str("    memw\n");
+    }
+
+    fn generate_prefetch(&self, out: &mut String, _addr: &str) {
+        out.push_str("    # Xtensa has no data prefetch on the ESP32\n");
+    }
+
+    fn generate_clflush(&self, out: &mut String, _addr: &str) {
+        out.push_str("    # Xtensa has no data cache to flush on the ESP32\n");
+    }
+
+    fn generate_clwb(&self, out: &mut String, _addr: &str) {
+        out.push_str("    # Xtensa has no data cache to write back on the ESP32\n");
+    }
+
+    fn generate_global(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".globl {}\n", symbol);
+    }
+
+    fn generate_extern(&self, out: &mut String, symbol: &str) {
+        emit!(out, ".extern {}\n", symbol);
+    }
+
+    fn generate_align(&self, out: &mut String, n: &str) {
+        emit!(out, ".p2align {}\n", n);
+    }
+
+    fn generate_data_byte(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .byte {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_word(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .short {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_dword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .word {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_qword(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: .quad {}\n", name, values.join(", "));
+    }
+
+    fn generate_reserve_byte(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip {}\n", name, count);
+    }
+
+    fn generate_reserve_word(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 2 * ({})\n", name, count);
+    }
+
+    fn generate_reserve_dword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 4 * ({})\n", name, count);
+    }
+
+    fn generate_reserve_qword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: .skip 8 * ({})\n", name, count);
+    }
+
+    fn generate_equ(&self, out: &mut String, name: &str, value: &str) {
+        emit!(out, ".set {}, {}\n", name, value);
+    }
+
+    fn generate_section(&self, out: &mut String, section: &Section) {
+        match section {
+            Section::Text => out.push_str(".section .text\n"),
+            Section::Data => out.push_str(".section .data\n"),
+            Section::Bss => out.push_str(".section .bss\n"),
+            Section::Rodata => out.push_str(".section .rodata\n"),
+            Section::Custom(s) => emit!(out, ".section {}\n", s),
+        }
+    }
+
+    fn generate_label(&self, out: &mut String, name: &str) {
+        emit!(out, "{}:\n", name);
+    }
+
+    fn generate_function_label(&self, out: &mut String, symbol: &str) {
+        // Calls only reach word-aligned code. Under the windowed ABI each
+        // function opens its window and copies its arguments from a2-a7 to
+        // r0-r5; the kernel jumps to `_start` without a call to return from.
+        let label = format!(".align 4\n{}:\n", symbol);
+        if !self.windowed || symbol == "_start" {
+            out.push_str(&label);
+            return;
+        }
+        out.push_str(&label);
+        emit!(out, "    entry a1, {}\n", SAVE_AREA);
+        for n in 0..6 {
+            self.copy(out, &format!("a{}", n + 10), &format!("a{}", n + 2));
+        }
+    }
+
+    fn needs_function_labels(&self) -> bool {
+        true
+    }
+
+    fn get_register_width(&self) -> u32 {
+        32
+    }
+
+    fn get_reserved_registers(&self) -> Vec<String> {
+        let registers: &[&str] = if self.windowed {
+            &["a0", "a1", "a7", "a8", "a9"]
+        } else {
+            &["a0", "a1", "a8", "a9", "a15"]
+        };
+        registers.iter().map(|r| r.to_string()).collect()
+    }
+
+    fn get_callee_saved_registers(&self) -> Vec<String> {
+        // The windowed ABI keeps the caller's registers out of the callee's
+        // reach, as SPARC's windows do.
+        let registers: &[&str] = if self.windowed {
+            &["a2", "a3", "a4", "a5", "a6", "a7"]
+        } else {
+            &["a12", "a13", "a14", "a15"]
+        };
+        registers.iter().map(|r| r.to_string()).collect()
+    }
+
+    fn get_push_size(&self) -> u32 {
+        16
+    }
+
+    fn get_clobbered_registers(&self, instruction: &Instruction) -> Vec<String> {
+        use Instruction::*;
+        let registers: &[&str] = match (instruction, self.windowed) {
+            (Syscall(_), true) => &["a8", "a9", "a10"],
+            (Syscall(_), false) => &["a2", "a8", "a9"],
+            (Call(_), true) => &["a8", "a9", "a10", "a11", "a12", "a13", "a14", "a15"],
+            (Call(_), false) => &[
+                "a0", "a2", "a3", "a4", "a5", "a6", "a7", "a8", "a9", "a10", "a11",
+            ],
+            _ => &[],
+        };
+        registers.iter().map(|r| r.to_string()).collect()
+    }
+
+    fn set_abi(&mut self, abi: &str) -> Result<(), String> {
+        let narrow = self.narrow;
+        *self = match abi {
+            "windowed" => Self::new(),
+            "call0" => Self::call0(),
+            _ => {
+                return Err(format!(
+                    "unknown Xtensa ABI `{}`; expected windowed or call0",
+                    abi
+                ));
+            }
+        };
+        self.narrow = narrow;
+        Ok(())
+    }
+
+    fn has_condition_flags(&self) -> bool {
+        false
+    }
+
+    fn generate_compare_branch(&self, out: &mut String, comparison: &Comparison, label: &str) {
+        if let Some(branch) = self.test(out, comparison) {
+            emit!(out, "    {}{}\n", branch, label);
+        }
+    }
+
+    fn generate_compare_set(&self, out: &mut String, comparison: &Comparison, dst: &str) {
+        let dst_reg = self.map_operand(dst);
+        let Some(branch) = self.test(out, comparison) else {
+            self.load_immediate(out, &dst_reg, 0);
+            return;
+        };
+        // `dst` can be set ahead of a branch that does not read it.
+        if !branch.split([' ', ',']).any(|operand| operand == dst_reg) {
+            self.load_immediate(out, &dst_reg, 1);
+            emit!(out, "    {}1f\n", branch);
+            self.load_immediate(out, &dst_reg, 0);
+            out.push_str("1:\n");
+            return;
+        }
+        emit!(out, "    {}1f\n", branch);
+        self.load_immediate(out, &dst_reg, 0);
+        out.push_str("    j 2f\n1:\n");
+        self.load_immediate(out, &dst_reg, 1);
+        out.push_str("2:\n");
+    }
+
+    fn generate_compare_cmov(
+        &self,
+        out: &mut String,
+        comparison: &Comparison,
+        dst: &str,
+        src: &str,
+    ) {
+        // Comparisons with zero have conditional moves of their own.
+        let dst_reg = self.map_operand(dst);
+        let op = match comparison.condition {
+            Condition::Eq => Some("moveqz"),
+            Condition::Ne => Some("movnez"),
+            Condition::Lt | Condition::S => Some("movltz"),
+            Condition::Ge | Condition::Ns => Some("movgez"),
+            _ => None,
+        };
+        if comparison.tested != Tested::Mask
+            && Self::immediate(&comparison.b) == Some(0)
+            && let Some(op) = op
+        {
+            let a = self.in_register(out, &comparison.a, "a8");
+            let src_reg = self.in_register(out, src, "a9");
+            emit!(out, "    {} {}, {}, {}\n", op, dst_reg, src_reg, a);
+            return;
+        }
+        // Branch around the move when the condition fails.
+        let skip = Comparison {
+            condition: comparison.condition.negate(),
+            ..comparison.clone()
+        };
+        self.generate_compare_branch(out, &skip, "1f");
+        self.generate_mov(out, dst, src);
+        out.push_str("1:\n");
+    }
+}
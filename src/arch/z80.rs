@@ -0,0 +1,1573 @@
+use super::*;
+use std::cell::Cell;
+use std::collections::HashMap;
+
+pub struct Z80CodeGen {
+    register_map: HashMap<String, String>,
+    /// Numbers the labels of the backend's own loops: sjasmplus and z80asm
+    /// agree on no form of temporary label.
+    labels: Cell<usize>,
+    /// Address of the RAM the registers and temporaries are kept in.
+    ram: i64,
+}
+
+/// One 16-bit half of an operand.
+#[derive(Debug, Clone, PartialEq)]
+enum Word {
+    /// The word at an address: a half of a register in memory.
+    Memory(String),
+    Constant(String),
+    /// The stack pointer, and the frame pointer `ix`.
+    Sp,
+    Ix,
+}
+
+/// How `shift` moves the bits of `de:hl`.
+#[derive(Clone, Copy, PartialEq)]
+enum Shift {
+    Left,
+    Right,
+    Arithmetic,
+    RotateLeft,
+    RotateRight,
+}
+
+/// Registers the program names, each four bytes of memory.
+const REGISTERS: usize = 16;
+
+/// Where the registers are kept unless `-mram` says otherwise: the upper
+/// half of the address space, RAM on boards that map ROM at 0.
+const RAM: i64 = 0x8000;
+
+/// Four-byte temporaries for the runtime and for sequences that need a
+/// register of their own.
+const TEMPORARIES: usize = 6;
+
+/// The routines for what the Z80 has no instructions for, on the
+/// temporaries `__t0` to `__t4`. None of them changes `ix` or a register
+/// the program names.
+const RUNTIME: &str = "; __t3:__t2 = __t0 * __t1, unsigned
+__mul32:
+    ld hl, (__t0)
+    ld (__t4), hl
+    ld hl, (__t0+2)
+    ld (__t4+2), hl
+    ld hl, 0
+    ld (__t2), hl
+    ld (__t2+2), hl
+    ld (__t3), hl
+    ld (__t3+2), hl
+    ld b, 32
+__mul32_loop:
+    ld hl, (__t2)
+    add hl, hl
+    ld (__t2), hl
+    ld hl, (__t2+2)
+    adc hl, hl
+    ld (__t2+2), hl
+    ld hl, (__t3)
+    adc hl, hl
+    ld (__t3), hl
+    ld hl, (__t3+2)
+    adc hl, hl
+    ld (__t3+2), hl
+    ld hl, (__t4)
+    add hl, hl
+    ld (__t4), hl
+    ld hl, (__t4+2)
+    adc hl, hl
+    ld (__t4+2), hl
+    jp nc, __mul32_next
+    ld hl, (__t2)
+    ld de, (__t1)
+    add hl, de
+    ld (__t2), hl
+    ld hl, (__t2+2)
+    ld de, (__t1+2)
+    adc hl, de
+    ld (__t2+2), hl
+    ld hl, (__t3)
+    ld de, 0
+    adc hl, de
+    ld (__t3), hl
+    ld hl, (__t3+2)
+    adc hl, de
+    ld (__t3+2), hl
+__mul32_next:
+    dec b
+    jp nz, __mul32_loop
+    ret
+; __t3 = the high half of __t0 * __t1, signed
+__mulh32:
+    call __mul32
+    ld a, (__t0+3)
+    or a
+    jp p, __mulh32_positive
+    ld hl, (__t3)
+    ld de, (__t1)
+    or a
+    sbc hl, de
+    ld (__t3), hl
+    ld hl, (__t3+2)
+    ld de, (__t1+2)
+    sbc hl, de
+    ld (__t3+2), hl
+__mulh32_positive:
+    ld a, (__t1+3)
+    or a
+    ret p
+    ld hl, (__t3)
+    ld de, (__t0)
+    or a
+    sbc hl, de
+    ld (__t3), hl
+    ld hl, (__t3+2)
+    ld de, (__t0+2)
+    sbc hl, de
+    ld (__t3+2), hl
+    ret
+; __t0 = __t0 / __t1, __t2 = __t0 % __t1, signed
+__divmod32:
+    ld a, (__t0+3)
+    ld hl, __t1+3
+    xor (hl)
+    push af
+    ld a, (__t0+3)
+    or a
+    push af
+    ld hl, __t0
+    call __abs32
+    ld hl, __t1
+    call __abs32
+    ld hl, 0
+    ld (__t2), hl
+    ld (__t2+2), hl
+    ld b, 32
+__divmod32_loop:
+    ld hl, (__t0)
+    add hl, hl
+    ld (__t0), hl
+    ld hl, (__t0+2)
+    adc hl, hl
+    ld (__t0+2), hl
+    ld hl, (__t2)
+    adc hl, hl
+    ld (__t2), hl
+    ld hl, (__t2+2)
+    adc hl, hl
+    ld (__t2+2), hl
+    ld hl, (__t2)
+    ld de, (__t1)
+    or a
+    sbc hl, de
+    ld (__t3), hl
+    ld hl, (__t2+2)
+    ld de, (__t1+2)
+    sbc hl, de
+    jp c, __divmod32_next
+    ld (__t2+2), hl
+    ld hl, (__t3)
+    ld (__t2), hl
+    ld hl, __t0
+    set 0, (hl)
+__divmod32_next:
+    dec b
+    jp nz, __divmod32_loop
+    pop af
+    ld hl, __t2
+    call m, __neg32
+    pop af
+    ld hl, __t0
+    call m, __neg32
+    ret
+; Negates the value at hl if it is negative
+__abs32:
+    push hl
+    inc hl
+    inc hl
+    inc hl
+    bit 7, (hl)
+    pop hl
+    ret z
+; Negates the value at hl
+__neg32:
+    xor a
+    sub (hl)
+    ld (hl), a
+    inc hl
+    ld a, 0
+    sbc a, (hl)
+    ld (hl), a
+    inc hl
+    ld a, 0
+    sbc a, (hl)
+    ld (hl), a
+    inc hl
+    ld a, 0
+    sbc a, (hl)
+    ld (hl), a
+    ret
+; Calls the address in hl
+__call_hl:
+    jp (hl)
+";
+
+impl Z80CodeGen {
+    pub fn new() -> Self {
+        let mut register_map = HashMap::with_capacity(REGISTERS + 2);
+
+        // Registers live in memory, four bytes each, since the Z80's own
+        // are 16 bits wide and too few
+        for n in 0..REGISTERS {
+            register_map.insert(format!("r{}", n), format!("__r{}", n));
+        }
+
+        // Special purpose registers
+        register_map.insert("sp".to_string(), "sp".to_string()); // Stack pointer
+        register_map.insert("sb".to_string(), "ix".to_string()); // Frame pointer
+
+        Z80CodeGen {
+            register_map,
+            labels: Cell::new(0),
+            ram: RAM,
+        }
+    }
+
+    /// A label no other code uses.
+    fn label(&self) -> String {
+        let n = self.labels.get();
+        self.labels.set(n + 1);
+        format!("__z80_{}", n)
+    }
+
+    /// Whether `name` is one of the registers or temporaries in memory.
+    fn is_memory_register(name: &str) -> bool {
+        name.strip_prefix("__r")
+            .or_else(|| name.strip_prefix("__t"))
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|c| c.is_ascii_digit()))
+    }
+
+    /// Whether `operand`, mapped, can be the base of an address.
+    fn is_base(operand: &str) -> bool {
+        operand == "sp" || operand == "ix" || Self::is_memory_register(operand)
+    }
+
+    /// The value of the constant `operand`, including the unsigned masks
+    /// `opt::flags` writes for `bt` of the top bit.
+    fn immediate(operand: &str) -> Option<i64> {
+        opt::parse_immediate(operand)
+            .or_else(|| operand.trim().parse::<u64>().ok().map(|value| value as i64))
+    }
+
+    /// The low and high words of `operand`.
+    fn words(&self, operand: &str) -> [Word; 2] {
+        let mapped = self.map_operand(operand.trim());
+        let zero = Word::Constant("0".to_string());
+        match mapped.as_str() {
+            "sp" => [Word::Sp, zero],
+            "ix" => [Word::Ix, zero],
+            name if Self::is_memory_register(name) => [
+                Word::Memory(name.to_string()),
+                Word::Memory(format!("{}+2", name)),
+            ],
+            _ => match Self::immediate(operand) {
+                Some(value) => [
+                    Word::Constant((value & 0xffff).to_string()),
+                    Word::Constant(((value >> 16) & 0xffff).to_string()),
+                ],
+                // An operand in parentheses is an address to the assemblers.
+                None if mapped
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '.') =>
+                {
+                    [
+                        Word::Constant(mapped.clone()),
+                        Word::Constant(format!("{} >> 16", mapped)),
+                    ]
+                }
+                None => [
+                    Word::Constant(format!("0 + {}", mapped)),
+                    Word::Constant(format!("0 + ({}) >> 16", mapped)),
+                ],
+            },
+        }
+    }
+
+    /// The words of `dst` an instruction writes: both halves of a register
+    /// in memory, the 16 bits of `sp` and `ix`.
+    fn destination(&self, dst: &str) -> Result<Vec<Word>, String> {
+        match self.words(dst) {
+            [Word::Constant(_), _] => {
+                Err(format!("    .error \"{} is not a Z80 register\"\n", dst))
+            }
+            [low @ (Word::Sp | Word::Ix), _] => Ok(vec![low]),
+            [low, high] => Ok(vec![low, high]),
+        }
+    }
+
+    /// Puts `word` in the register pair `pair`: `hl`, `de` or `bc`. Only
+    /// loading `sp` into `hl` changes the flags, and only `hl` and `pair`
+    /// change.
+    fn load(out: &mut String, word: &Word, pair: &str) {
+        match word {
+            Word::Memory(address) => emit!(out, "    ld {}, ({})\n", pair, address),
+            Word::Constant(value) => emit!(out, "    ld {}, {}\n", pair, value),
+            Word::Sp => match pair {
+                "hl" => out.push_str("    ld hl, 0\n    add hl, sp\n"),
+                "de" => {
+                    out.push_str("    ex de, hl\n    ld hl, 0\n    add hl, sp\n    ex de, hl\n")
+                }
+                _ => emit!(
+                    out,
+                    "    push hl\n    ld hl, 2\n    add hl, sp\n    ld {}, h\n    ld {}, l\n    pop hl\n",
+                    &pair[..1],
+                    &pair[1..]
+                ),
+            },
+            Word::Ix => emit!(out, "    push ix\n    pop {}\n", pair),
+        }
+    }
+
+    /// Writes the register pair `pair`, `hl` or `de`, to `word`, leaving the
+    /// flags alone.
+    fn store(out: &mut String, word: &Word, pair: &str) {
+        match word {
+            Word::Memory(address) => emit!(out, "    ld ({}), {}\n", address, pair),
+            Word::Sp if pair == "hl" => out.push_str("    ld sp, hl\n"),
+            Word::Sp => out.push_str("    ex de, hl\n    ld sp, hl\n    ex de, hl\n"),
+            Word::Ix => emit!(out, "    push {}\n    pop ix\n", pair),
+            Word::Constant(value) => emit!(out, "    .error \"{} is not a Z80 register\"\n", value),
+        }
+    }
+
+    /// Puts `operand` in `de:hl`.
+    fn value_in(&self, out: &mut String, operand: &str) {
+        let [low, high] = self.words(operand);
+        Self::load(out, &high, "de");
+        Self::load(out, &low, "hl");
+    }
+
+    /// Writes `de:hl` to `dst`.
+    fn value_out(&self, out: &mut String, dst: &str) {
+        match self.destination(dst) {
+            Ok(words) => {
+                Self::store(out, &words[0], "hl");
+                if let Some(high) = words.get(1) {
+                    Self::store(out, high, "de");
+                }
+            }
+            Err(error) => out.push_str(&error),
+        }
+    }
+
+    /// `dst = dst op src` a word at a time, `body` working on `hl` and `de`
+    /// with the word's index.
+    fn words_of(&self, out: &mut String, dst: &str, src: &str, body: impl Fn(&mut String, usize)) {
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        let src = self.words(src);
+        for (i, word) in words.iter().enumerate() {
+            Self::load(out, &src[i], "de");
+            Self::load(out, word, "hl");
+            body(out, i);
+            Self::store(out, word, "hl");
+        }
+    }
+
+    /// `op` of `h` with `d` and of `l` with `e`, into `hl`.
+    fn bytes(out: &mut String, op: &str) {
+        emit!(
+            out,
+            "    ld a, h\n    {op} d\n    ld h, a\n    ld a, l\n    {op} e\n    ld l, a\n",
+            op = op
+        );
+    }
+
+    /// One bit of `shift` across `de:hl`.
+    fn step(shift: Shift) -> &'static str {
+        match shift {
+            Shift::Left => "    add hl, hl\n    rl e\n    rl d\n",
+            Shift::Right => "    srl d\n    rr e\n    rr h\n    rr l\n",
+            Shift::Arithmetic => "    sra d\n    rr e\n    rr h\n    rr l\n",
+            Shift::RotateLeft => "    ld a, d\n    rla\n    adc hl, hl\n    rl e\n    rl d\n",
+            Shift::RotateRight => "    ld a, l\n    rra\n    rr d\n    rr e\n    rr h\n    rr l\n",
+        }
+    }
+
+    /// `shift` of `dst` by `count`, which counts modulo 32 as x86 does.
+    fn shift(&self, out: &mut String, shift: Shift, dst: &str, count: &str) {
+        let immediate = Self::immediate(count).map(|count| count & 31);
+        if immediate == Some(0) {
+            return;
+        }
+        self.value_in(out, dst);
+        let skip = match immediate {
+            Some(count) => {
+                emit!(out, "    ld b, {}\n", count);
+                None
+            }
+            None => {
+                let skip = self.label();
+                let [low, _] = self.words(count);
+                Self::load(out, &low, "bc");
+                emit!(
+                    out,
+                    "    ld a, c\n    and 31\n    jr z, {}\n    ld b, a\n",
+                    skip
+                );
+                Some(skip)
+            }
+        };
+        let repeat = self.label();
+        emit!(
+            out,
+            "{}:\n{}    djnz {}\n",
+            repeat,
+            Self::step(shift),
+            repeat
+        );
+        if let Some(skip) = skip {
+            emit!(out, "{}:\n", skip);
+        }
+        self.value_out(out, dst);
+    }
+
+    /// `dst = dst op src` through the runtime routine `routine`, which
+    /// leaves the result in the temporary `result`.
+    fn runtime(&self, out: &mut String, routine: &str, result: &str, dst: &str, src: &str) {
+        self.generate_mov(out, "__t1", src);
+        self.generate_mov(out, "__t0", dst);
+        emit!(out, "    call {}\n", routine);
+        self.generate_mov(out, dst, result);
+    }
+
+    /// Puts the single-bit mask `bit` selects in the temporary `__t5`.
+    fn mask(&self, out: &mut String, bit: &str) {
+        let [low, _] = self.words(bit);
+        let (repeat, done) = (self.label(), self.label());
+        Self::load(out, &low, "bc");
+        emit!(
+            out,
+            "    ld a, c\n    and 31\n    ld b, a\n    ld hl, 1\n    ld de, 0\n    jr z, {done}\n{repeat}:\n{}    djnz {repeat}\n{done}:\n    ld (__t5), hl\n    ld (__t5+2), de\n",
+            Self::step(Shift::Left),
+            repeat = repeat,
+            done = done
+        );
+    }
+
+    /// Sets `dst` to the signed byte in `c`.
+    fn extend_c(&self, out: &mut String, dst: &str) {
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        out.push_str("    ld l, c\n    ld a, c\n    rla\n    sbc a, a\n    ld h, a\n");
+        Self::store(out, &words[0], "hl");
+        if let Some(high) = words.get(1) {
+            out.push_str("    ld l, a\n");
+            Self::store(out, high, "hl");
+        }
+    }
+
+    /// The parts of the memory operand `operand` as `split_address` finds
+    /// them.
+    fn split_address(&self, operand: &str) -> Result<Address, String> {
+        super::split_address(operand, |name| self.map_operand(name), Self::is_base)
+    }
+
+    /// The displacement of `operand` from `ix`, when `(ix+d)` reaches all
+    /// four of its bytes.
+    fn frame_slot(&self, operand: &str) -> Option<i64> {
+        let address = self.split_address(operand).ok()?;
+        (address.base.as_deref() == Some("ix")
+            && address.index.is_none()
+            && address.symbolic.is_empty()
+            && (-128..=124).contains(&address.displacement))
+        .then_some(address.displacement)
+    }
+
+    /// Puts the address `operand` names in `hl`, with `pushed` bytes on the
+    /// stack since the instruction started. Changes `de` and the flags.
+    fn address(&self, out: &mut String, operand: &str, pushed: i64) {
+        let address = address_or_error(out, self.split_address(operand));
+        let mut displacement = address.displacement;
+        if address.base.as_deref() == Some("sp") {
+            displacement += pushed;
+        }
+        let constant = match (address.symbolic.as_str(), displacement) {
+            ("", displacement) => displacement.to_string(),
+            (symbolic, 0) => symbolic.to_string(),
+            (symbolic, displacement) => format!("{} + ({})", symbolic, displacement),
+        };
+        match address.base.as_deref() {
+            Some("sp") => emit!(out, "    ld hl, {}\n    add hl, sp\n", constant),
+            Some(base) => {
+                let base = if base == "ix" {
+                    Word::Ix
+                } else {
+                    Word::Memory(base.to_string())
+                };
+                Self::load(out, &base, "hl");
+                if constant != "0" {
+                    emit!(out, "    ld de, {}\n    add hl, de\n", constant);
+                }
+            }
+            None => emit!(out, "    ld hl, {}\n", constant),
+        }
+        if let Some((index, shift)) = &address.index {
+            out.push_str("    push hl\n");
+            match index.as_str() {
+                "sp" => out.push_str("    ld hl, 2\n    add hl, sp\n"),
+                "ix" => Self::load(out, &Word::Ix, "hl"),
+                index => Self::load(out, &Word::Memory(index.to_string()), "hl"),
+            }
+            for _ in 0..*shift {
+                out.push_str("    add hl, hl\n");
+            }
+            out.push_str("    pop de\n    add hl, de\n");
+        }
+    }
+
+    /// The flags `comparison` leaves, and the condition that holds when it
+    /// is true. Ordered comparisons subtract all 32 bits, with the sign bits
+    /// flipped for signed ones so that the borrow decides them too.
+    fn flags(&self, out: &mut String, comparison: &Comparison) -> &'static str {
+        let (a, b) = if comparison.tested == Tested::Mask {
+            self.generate_mov(out, "__t5", &comparison.a);
+            self.words_of(out, "__t5", &comparison.b, |out, _| Self::bytes(out, "and"));
+            (self.words("__t5"), self.words("0"))
+        } else {
+            (self.words(&comparison.a), self.words(&comparison.b))
+        };
+
+        let (swap, bias, condition) = match comparison.condition {
+            Condition::Eq | Condition::Ne => {
+                let differ = self.label();
+                Self::load(out, &b[0], "de");
+                Self::load(out, &a[0], "hl");
+                emit!(out, "    or a\n    sbc hl, de\n    jr nz, {}\n", differ);
+                Self::load(out, &b[1], "de");
+                Self::load(out, &a[1], "hl");
+                emit!(out, "    or a\n    sbc hl, de\n{}:\n", differ);
+                let condition = if comparison.condition == Condition::Eq {
+                    "z"
+                } else {
+                    "nz"
+                };
+                return condition;
+            }
+            Condition::Lt => (false, true, "c"),
+            Condition::Ge => (false, true, "nc"),
+            Condition::Gt => (true, true, "c"),
+            Condition::Le => (true, true, "nc"),
+            Condition::B => (false, false, "c"),
+            Condition::Ae => (false, false, "nc"),
+            Condition::A => (true, false, "c"),
+            Condition::Be => (true, false, "nc"),
+            Condition::S => (false, false, "m"),
+            Condition::Ns => (false, false, "p"),
+        };
+        let (x, y) = if swap { (b, a) } else { (a, b) };
+        Self::load(out, &y[1], "hl");
+        if bias {
+            out.push_str("    ld a, h\n    xor 128\n    ld h, a\n");
+        }
+        out.push_str("    ld b, h\n    ld c, l\n");
+        Self::load(out, &y[0], "de");
+        Self::load(out, &x[0], "hl");
+        out.push_str("    or a\n    sbc hl, de\n");
+        Self::load(out, &x[1], "hl");
+        if bias {
+            out.push_str("    push af\n    ld a, h\n    xor 128\n    ld h, a\n    pop af\n");
+        }
+        out.push_str("    sbc hl, bc\n");
+        condition
+    }
+
+    /// The condition that holds when `condition` does not.
+    fn inverse(condition: &str) -> &'static str {
+        match condition {
+            "z" => "nz",
+            "nz" => "z",
+            "c" => "nc",
+            "nc" => "c",
+            "m" => "p",
+            _ => "m",
+        }
+    }
+
+    /// The registers `pusha` saves and `popa` restores: every one the
+    /// register map names but `sp`, in a fixed order.
+    fn saved_by_pusha(&self) -> Vec<&String> {
+        let mut registers: Vec<&String> = self
+            .register_map
+            .values()
+            .filter(|r| r.as_str() != "sp")
+            .collect();
+        registers.sort();
+        registers.dedup();
+        registers
+    }
+}
+
+impl ArchCodeGen for Z80CodeGen {
+    fn get_register_map(&self) -> HashMap<String, String> {
+        self.register_map.clone()
+    }
+
+    fn get_syntax_header(&self) -> String {
+        // A flat binary runs from its first byte: jump over the runtime.
+        // The registers are written, so they are kept in RAM rather than in
+        // the image.
+        let mut header = "    org 0\n    jp _start\n\n".to_string();
+        let names = (0..REGISTERS)
+            .map(|n| format!("__r{}", n))
+            .chain((0..TEMPORARIES).map(|n| format!("__t{}", n)));
+        for (n, name) in names.enumerate() {
+            header.push_str(&format!("{} equ 0x{:04x}\n", name, self.ram + 4 * n as i64));
+        }
+        header + "\n" + RUNTIME + "\n"
+    }
+
+    fn generate_mov(&self, out: &mut String, dst: &str, src: &str) {
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        let src = self.words(src);
+        for (word, src) in words.iter().zip(&src) {
+            Self::load(out, src, "hl");
+            Self::store(out, word, "hl");
+        }
+    }
+
+    fn generate_lea(&self, out: &mut String, dst: &str, src: &str) {
+        let address = address_or_error(out, self.split_address(src));
+        if address.base.is_none() && address.index.is_none() {
+            let expression = match (address.symbolic.is_empty(), address.displacement) {
+                (true, displacement) => displacement.to_string(),
+                (false, 0) => address.symbolic,
+                (false, displacement) => format!("{} + ({})", address.symbolic, displacement),
+            };
+            self.generate_mov(out, dst, &expression);
+            return;
+        }
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        self.address(out, src, 0);
+        Self::store(out, &words[0], "hl");
+        if let Some(high) = words.get(1) {
+            out.push_str("    ld hl, 0\n");
+            Self::store(out, high, "hl");
+        }
+    }
+
+    fn generate_load(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(d) = self.frame_slot(src) {
+            emit!(
+                out,
+                "    ld l, (ix{:+})\n    ld h, (ix{:+})\n    ld e, (ix{:+})\n    ld d, (ix{:+})\n",
+                d,
+                d + 1,
+                d + 2,
+                d + 3
+            );
+            self.value_out(out, dst);
+            return;
+        }
+        self.address(out, src, 0);
+        out.push_str("    ld e, (hl)\n    inc hl\n    ld d, (hl)\n    inc hl\n    ld a, (hl)\n    inc hl\n    ld h, (hl)\n    ld l, a\n    ex de, hl\n");
+        self.value_out(out, dst);
+    }
+
+    fn generate_store(&self, out: &mut String, dst: &str, src: &str) {
+        if let Some(d) = self.frame_slot(dst) {
+            self.value_in(out, src);
+            emit!(
+                out,
+                "    ld (ix{:+}), l\n    ld (ix{:+}), h\n    ld (ix{:+}), e\n    ld (ix{:+}), d\n",
+                d,
+                d + 1,
+                d + 2,
+                d + 3
+            );
+            return;
+        }
+        let [low, high] = self.words(src);
+        self.address(out, dst, 0);
+        Self::load(out, &low, "de");
+        Self::load(out, &high, "bc");
+        out.push_str("    ld (hl), e\n    inc hl\n    ld (hl), d\n    inc hl\n    ld (hl), c\n    inc hl\n    ld (hl), b\n");
+    }
+
+    fn generate_add(&self, out: &mut String, dst: &str, src: &str) {
+        self.words_of(out, dst, src, |out, i| {
+            if i == 0 {
+                out.push_str("    add hl, de\n")
+            } else {
+                out.push_str("    adc hl, de\n")
+            }
+        });
+    }
+
+    fn generate_sub(&self, out: &mut String, dst: &str, src: &str) {
+        self.words_of(out, dst, src, |out, i| {
+            if i == 0 {
+                out.push_str("    or a\n    sbc hl, de\n")
+            } else {
+                out.push_str("    sbc hl, de\n")
+            }
+        });
+    }
+
+    fn generate_mul(&self, out: &mut String, dst: &str, src: &str) {
+        self.runtime(out, "__mul32", "__t2", dst, src);
+    }
+
+    fn generate_div(&self, out: &mut String, dst: &str, src: &str) {
+        self.runtime(out, "__divmod32", "__t0", dst, src);
+    }
+
+    fn generate_inc(&self, out: &mut String, dst: &str) {
+        self.generate_add(out, dst, "1");
+    }
+
+    fn generate_dec(&self, out: &mut String, dst: &str) {
+        self.generate_sub(out, dst, "1");
+    }
+
+    fn generate_neg(&self, out: &mut String, dst: &str) {
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        for (i, word) in words.iter().enumerate() {
+            Self::load(out, word, "de");
+            out.push_str("    ld hl, 0\n");
+            if i == 0 {
+                out.push_str("    or a\n");
+            }
+            out.push_str("    sbc hl, de\n");
+            Self::store(out, word, "hl");
+        }
+    }
+
+    fn generate_and(&self, out: &mut String, dst: &str, src: &str) {
+        self.words_of(out, dst, src, |out, _| Self::bytes(out, "and"));
+    }
+
+    fn generate_or(&self, out: &mut String, dst: &str, src: &str) {
+        self.words_of(out, dst, src, |out, _| Self::bytes(out, "or"));
+    }
+
+    fn generate_xor(&self, out: &mut String, dst: &str, src: &str) {
+        self.words_of(out, dst, src, |out, _| Self::bytes(out, "xor"));
+    }
+
+    fn generate_not(&self, out: &mut String, dst: &str) {
+        self.words_of(out, dst, "0", |out, _| {
+            out.push_str("    ld a, h\n    cpl\n    ld h, a\n    ld a, l\n    cpl\n    ld l, a\n")
+        });
+    }
+
+    fn generate_shl(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, Shift::Left, dst, src);
+    }
+
+    fn generate_shr(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, Shift::Right, dst, src);
+    }
+
+    fn generate_cmp(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_test(&self, _out: &mut String, _op1: &str, _op2: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_jmp(&self, out: &mut String, label: &str) {
+        match self.words(label) {
+            [word @ (Word::Memory(_) | Word::Ix | Word::Sp), _] => {
+                Self::load(out, &word, "hl");
+                out.push_str("    jp (hl)\n");
+            }
+            _ => emit!(out, "    jp {}\n", label),
+        }
+    }
+
+    fn generate_je(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_jne(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_jg(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_jl(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_jge(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_jle(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_call(&self, out: &mut String, func: &str) {
+        // There is no `call (hl)`; the runtime's `__call_hl` jumps there.
+        match self.words(func) {
+            [word @ (Word::Memory(_) | Word::Ix | Word::Sp), _] => {
+                Self::load(out, &word, "hl");
+                out.push_str("    call __call_hl\n");
+            }
+            _ => emit!(out, "    call {}\n", func),
+        }
+    }
+
+    fn generate_ret(&self, out: &mut String) {
+        out.push_str("    ret\n");
+    }
+
+    fn generate_syscall(&self, out: &mut String, name: &str) {
+        match name {
+            // Nothing wakes the processor from `halt` with interrupts off.
+            "exit" => out.push_str("    di\n    halt\n"),
+            _ => emit!(
+                out,
+                "    ; No operating system to handle syscall: {}\n",
+                name
+            ),
+        }
+    }
+
+    fn map_operand(&self, operand: &str) -> String {
+        if operand.chars().all(|c| c.is_ascii_digit() || c == '-') {
+            return operand.to_string();
+        }
+
+        if operand.starts_with('[') && operand.ends_with(']') {
+            return self.map_memory_operand(operand);
+        }
+
+        if let Some(mapped) = self.register_map.get(operand) {
+            mapped.clone()
+        } else {
+            operand.to_string()
+        }
+    }
+
+    fn map_memory_operand(&self, operand: &str) -> String {
+        if !(operand.starts_with('[') && operand.ends_with(']')) {
+            return operand.to_string();
+        }
+        match self.frame_slot(operand) {
+            Some(d) => format!("(ix{:+})", d),
+            None => operand.to_string(),
+        }
+    }
+
+    fn generate_cmov_eq(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_cmov_ne(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_cmov_lt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_cmov_le(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_cmov_gt(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_cmov_ge(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_cmov_ov(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_cmov_no(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_cmov_s(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_cmov_ns(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_cmov_p(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_cmov_np(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_cmov_a(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_cmov_ae(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_cmov_b(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_cmov_be(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_push(&self, out: &mut String, src: &str) {
+        // The high word first, so that the value is little-endian in memory.
+        self.value_in(out, src);
+        out.push_str("    push de\n    push hl\n");
+    }
+
+    fn generate_pop(&self, out: &mut String, dst: &str) {
+        out.push_str("    pop hl\n    pop de\n");
+        self.value_out(out, dst);
+    }
+
+    fn generate_pusha(&self, out: &mut String) {
+        for register in self.saved_by_pusha() {
+            self.generate_push(out, register);
+        }
+    }
+
+    fn generate_popa(&self, out: &mut String) {
+        for register in self.saved_by_pusha().iter().rev() {
+            self.generate_pop(out, register);
+        }
+    }
+
+    fn generate_enter(&self, out: &mut String, frame_size: &str, _nesting_level: &str) {
+        self.generate_push(out, "sb");
+        self.generate_mov(out, "sb", "sp");
+        if opt::parse_immediate(frame_size) != Some(0) {
+            self.generate_sub(out, "sp", frame_size);
+        }
+    }
+
+    fn generate_leave(&self, out: &mut String) {
+        self.generate_mov(out, "sp", "sb");
+        self.generate_pop(out, "sb");
+    }
+
+    fn generate_imul(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_mul(out, dst, src);
+    }
+
+    fn generate_idiv(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_div(out, dst, src);
+    }
+
+    fn generate_mod(&self, out: &mut String, dst: &str, src: &str) {
+        self.runtime(out, "__divmod32", "__t2", dst, src);
+    }
+
+    fn generate_mulh(&self, out: &mut String, dst: &str, src: &str) {
+        self.runtime(out, "__mulh32", "__t3", dst, src);
+    }
+
+    fn generate_andn(&self, out: &mut String, dst: &str, src: &str) {
+        self.words_of(out, dst, src, |out, _| {
+            out.push_str("    ld a, d\n    cpl\n    and h\n    ld h, a\n    ld a, e\n    cpl\n    and l\n    ld l, a\n")
+        });
+    }
+
+    fn generate_sal(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_shl(out, dst, src);
+    }
+
+    fn generate_sar(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, Shift::Arithmetic, dst, src);
+    }
+
+    fn generate_rol(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, Shift::RotateLeft, dst, src);
+    }
+
+    fn generate_ror(&self, out: &mut String, dst: &str, src: &str) {
+        self.shift(out, Shift::RotateRight, dst, src);
+    }
+
+    fn generate_rcl(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_rcr(&self, _out: &mut String, _dst: &str, _src: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_bextr(&self, out: &mut String, dst: &str, src: &str, imm: &str) {
+        // Shift the field down, then, unless the length keeps all 32 bits, up
+        // to the top and back by 32 - length.
+        let [control, _] = self.words(imm);
+        let labels: Vec<String> = (0..5).map(|_| self.label()).collect();
+        let right = Self::step(Shift::Right);
+        Self::load(out, &control, "bc");
+        self.value_in(out, src);
+        emit!(
+            out,
+            "    push bc\n    ld a, c\n    or a\n    jr z, {l1}\n    ld b, a\n{l0}:\n{right}    djnz {l0}\n{l1}:\n    pop bc\n    ld a, b\n    cp 32\n    jr nc, {l4}\n    ld a, 32\n    sub b\n    ld b, a\n    ld c, a\n{l2}:\n{left}    djnz {l2}\n    ld b, c\n{l3}:\n{right}    djnz {l3}\n{l4}:\n",
+            l0 = labels[0],
+            l1 = labels[1],
+            l2 = labels[2],
+            l3 = labels[3],
+            l4 = labels[4],
+            right = right,
+            left = Self::step(Shift::Left)
+        );
+        self.value_out(out, dst);
+    }
+
+    fn generate_bsf(&self, out: &mut String, dst: &str, src: &str) {
+        // Counts the bits shifted out below the lowest set one, -1 for 0.
+        let (repeat, done) = (self.label(), self.label());
+        self.value_in(out, src);
+        emit!(
+            out,
+            "    ld a, h\n    or l\n    or d\n    or e\n    ld c, 255\n    jr z, {done}\n{repeat}:\n    inc c\n{}    jr nc, {repeat}\n{done}:\n",
+            Self::step(Shift::Right),
+            repeat = repeat,
+            done = done
+        );
+        self.extend_c(out, dst);
+    }
+
+    fn generate_bsr(&self, out: &mut String, dst: &str, src: &str) {
+        // Counts down from 31 to the highest set bit, -1 for 0.
+        let (repeat, done) = (self.label(), self.label());
+        self.value_in(out, src);
+        emit!(
+            out,
+            "    ld c, 32\n{repeat}:\n    dec c\n    jp m, {done}\n{}    jr nc, {repeat}\n{done}:\n",
+            Self::step(Shift::Left),
+            repeat = repeat,
+            done = done
+        );
+        self.extend_c(out, dst);
+    }
+
+    fn generate_bt(&self, _out: &mut String, _dst: &str, _bit: &str) {}
+
+    fn generate_btr(&self, out: &mut String, dst: &str, bit: &str) {
+        match Self::immediate(bit) {
+            Some(bit) => {
+                self.generate_and(out, dst, &(!(1i64 << (bit & 31)) & 0xffffffff).to_string())
+            }
+            None => {
+                self.mask(out, bit);
+                self.generate_andn(out, dst, "__t5");
+            }
+        }
+    }
+
+    fn generate_bts(&self, out: &mut String, dst: &str, bit: &str) {
+        match Self::immediate(bit) {
+            Some(bit) => self.generate_or(out, dst, &(1i64 << (bit & 31)).to_string()),
+            None => {
+                self.mask(out, bit);
+                self.generate_or(out, dst, "__t5");
+            }
+        }
+    }
+
+    fn generate_btc(&self, out: &mut String, dst: &str, bit: &str) {
+        match Self::immediate(bit) {
+            Some(bit) => self.generate_xor(out, dst, &(1i64 << (bit & 31)).to_string()),
+            None => {
+                self.mask(out, bit);
+                self.generate_xor(out, dst, "__t5");
+            }
+        }
+    }
+
+    fn generate_set_eq(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_set_ne(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_set_lt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_set_le(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_set_gt(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_set_ge(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_set_ov(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_set_no(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_set_s(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_set_ns(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_set_p(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_set_np(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_set_a(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_set_ae(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_set_b(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_set_be(&self, _out: &mut String, _dst: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_cmps(&self, _out: &mut String, _src1: &str, _src2: &str) {}
+
+    fn generate_scas(&self, _out: &mut String, _src: &str, _val: &str) {}
+
+    fn generate_stos(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_store(out, dst, src);
+    }
+
+    fn generate_lods(&self, out: &mut String, dst: &str, src: &str) {
+        self.generate_load(out, dst, src);
+    }
+
+    fn generate_movs(&self, out: &mut String, dst: &str, src: &str) {
+        self.address(out, src, 0);
+        out.push_str("    push hl\n");
+        self.address(out, dst, 2);
+        out.push_str("    ex de, hl\n    pop hl\n    ldi\n    ldi\n    ldi\n    ldi\n");
+    }
+
+    fn generate_cbw(&self, out: &mut String, dst: &str) {
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        Self::load(out, &words[0], "hl");
+        out.push_str("    ld a, l\n    rla\n    sbc a, a\n    ld h, a\n");
+        Self::store(out, &words[0], "hl");
+        if let Some(high) = words.get(1) {
+            out.push_str("    ld l, a\n");
+            Self::store(out, high, "hl");
+        }
+    }
+
+    fn generate_cwd(&self, out: &mut String, dst: &str) {
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        let Some(high) = words.get(1) else {
+            return;
+        };
+        Self::load(out, &words[0], "hl");
+        out.push_str("    ld a, h\n    rla\n    sbc a, a\n    ld h, a\n    ld l, a\n");
+        Self::store(out, high, "hl");
+    }
+
+    fn generate_cdq(&self, _out: &mut String, _dst: &str) {}
+
+    fn generate_cqo(&self, out: &mut String, dst: &str) {
+        // The high half of the 64-bit sign extension: all sign bits.
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        let top = words.last().unwrap();
+        Self::load(out, top, "hl");
+        out.push_str("    ld a, h\n    rla\n    sbc a, a\n    ld h, a\n    ld l, a\n");
+        for word in &words {
+            Self::store(out, word, "hl");
+        }
+    }
+
+    fn generate_cwde(&self, out: &mut String, dst: &str) {
+        self.generate_cwd(out, dst);
+    }
+
+    fn generate_cdqe(&self, _out: &mut String, _dst: &str) {}
+
+    fn generate_jo(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_jno(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_js(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_jns(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_jp(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_jnp(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_ja(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_jae(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_jb(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_jbe(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_loop_eq(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_loop_ne(&self, _out: &mut String, _label: &str) {
+        unreachable!("Z80 compares in each reader")
+    }
+
+    fn generate_in(&self, out: &mut String, dst: &str, port: &str) {
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        // Ports are a byte wide. `in a, (n)` puts `a` on the upper half of
+        // the address bus, `in a, (c)` all of `bc`.
+        match Self::immediate(port) {
+            Some(port @ 0..=255) => emit!(out, "    in a, ({})\n", port),
+            _ => {
+                Self::load(out, &self.words(port)[0], "bc");
+                out.push_str("    in a, (c)\n");
+            }
+        }
+        out.push_str("    ld l, a\n    ld h, 0\n");
+        Self::store(out, &words[0], "hl");
+        if let Some(high) = words.get(1) {
+            out.push_str("    ld hl, 0\n");
+            Self::store(out, high, "hl");
+        }
+    }
+
+    fn generate_out(&self, out: &mut String, port: &str, src: &str) {
+        Self::load(out, &self.words(src)[0], "hl");
+        match Self::immediate(port) {
+            Some(port @ 0..=255) => emit!(out, "    ld a, l\n    out ({}), a\n", port),
+            _ => {
+                Self::load(out, &self.words(port)[0], "bc");
+                out.push_str("    ld a, l\n    out (c), a\n");
+            }
+        }
+    }
+
+    fn generate_ins(&self, out: &mut String, dst: &str, port: &str) {
+        self.address(out, dst, 0);
+        Self::load(out, &self.words(port)[0], "bc");
+        out.push_str("    ini\n");
+    }
+
+    fn generate_outs(&self, out: &mut String, port: &str, src: &str) {
+        // `outi` counts `b` down before it puts `bc` on the address bus.
+        self.address(out, src, 0);
+        Self::load(out, &self.words(port)[0], "bc");
+        out.push_str("    inc b\n    outi\n");
+    }
+
+    fn generate_cpuid(&self, out: &mut String) {
+        out.push_str("    ; Z80 does not have CPUID\n");
+    }
+
+    fn generate_lfence(&self, _out: &mut String) {}
+
+    fn generate_sfence(&self, _out: &mut String) {}
+
+    fn generate_mfence(&self, _out: &mut String) {}
+
+    fn generate_prefetch(&self, out: &mut String, _addr: &str) {
+        out.push_str("    ; Z80 has no cache to prefetch into\n");
+    }
+
+    fn generate_clflush(&self, out: &mut String, _addr: &str) {
+        out.push_str("    ; Z80 has no cache to flush\n");
+    }
+
+    fn generate_clwb(&self, out: &mut String, _addr: &str) {
+        out.push_str("    ; Z80 has no cache to write back\n");
+    }
+
+    fn generate_global(&self, out: &mut String, symbol: &str) {
+        // A flat binary has no symbol table.
+        emit!(out, "; global {}\n", symbol);
+    }
+
+    fn generate_extern(&self, out: &mut String, symbol: &str) {
+        emit!(out, "; extern {}\n", symbol);
+    }
+
+    fn generate_align(&self, out: &mut String, n: &str) {
+        match n.trim().parse::<u32>() {
+            Ok(n) => emit!(out, "    align {}\n", 1u64 << n.min(15)),
+            Err(_) => emit!(out, "    align 1 << ({})\n", n),
+        }
+    }
+
+    fn generate_data_byte(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: db {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_word(&self, out: &mut String, name: &str, values: &[String]) {
+        emit!(out, "{}: dw {}\n", name, values.join(", "));
+    }
+
+    fn generate_data_dword(&self, out: &mut String, name: &str, values: &[String]) {
+        let words: Vec<String> = values
+            .iter()
+            .flat_map(|value| {
+                let [low, high] = self.words(value);
+                [low, high]
+            })
+            .map(|word| match word {
+                Word::Constant(value) => value,
+                _ => unreachable!("data is constant"),
+            })
+            .collect();
+        emit!(out, "{}: dw {}\n", name, words.join(", "));
+    }
+
+    fn generate_data_qword(&self, out: &mut String, name: &str, values: &[String]) {
+        // The upper 32 bits are the sign of the lower.
+        let words: Vec<String> = values
+            .iter()
+            .flat_map(|value| {
+                let [low, high] = self.words(value);
+                let sign = match Self::immediate(value) {
+                    Some(value) => ((value >> 63) & 0xffff).to_string(),
+                    None => "0".to_string(),
+                };
+                [
+                    low,
+                    high,
+                    Word::Constant(sign.clone()),
+                    Word::Constant(sign),
+                ]
+            })
+            .map(|word| match word {
+                Word::Constant(value) => value,
+                _ => unreachable!("data is constant"),
+            })
+            .collect();
+        emit!(out, "{}: dw {}\n", name, words.join(", "));
+    }
+
+    fn generate_reserve_byte(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: ds {}\n", name, count);
+    }
+
+    fn generate_reserve_word(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: ds 2 * ({})\n", name, count);
+    }
+
+    fn generate_reserve_dword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: ds 4 * ({})\n", name, count);
+    }
+
+    fn generate_reserve_qword(&self, out: &mut String, name: &str, count: &str) {
+        emit!(out, "{}: ds 8 * ({})\n", name, count);
+    }
+
+    fn generate_equ(&self, out: &mut String, name: &str, value: &str) {
+        emit!(out, "{} equ {}\n", name, value);
+    }
+
+    fn generate_section(&self, out: &mut String, section: &Section) {
+        // A flat binary has no sections: everything follows in order.
+        match section {
+            Section::Text => out.push_str("; section .text\n"),
+            Section::Data => out.push_str("; section .data\n"),
+            Section::Bss => out.push_str("; section .bss\n"),
+            Section::Rodata => out.push_str("; section .rodata\n"),
+            Section::Custom(s) => emit!(out, "; section {}\n", s),
+        }
+    }
+
+    fn generate_label(&self, out: &mut String, name: &str) {
+        emit!(out, "{}:\n", name);
+    }
+
+    fn get_register_width(&self) -> u32 {
+        32
+    }
+
+    fn get_reserved_registers(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn get_callee_saved_registers(&self) -> Vec<String> {
+        let mut registers: Vec<String> = (8..REGISTERS).map(|n| format!("__r{}", n)).collect();
+        registers.push("ix".to_string());
+        registers
+    }
+
+    fn get_push_size(&self) -> u32 {
+        4
+    }
+
+    fn get_clobbered_registers(&self, instruction: &Instruction) -> Vec<String> {
+        use Instruction::*;
+        match instruction {
+            Call(_) => (0..8).map(|n| format!("__r{}", n)).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn set_ram(&mut self, origin: &str) -> Result<(), String> {
+        let size = 4 * (REGISTERS + TEMPORARIES) as i64;
+        match opt::parse_immediate(origin) {
+            Some(ram) if (0..=0x10000 - size).contains(&ram) => {
+                self.ram = ram;
+                Ok(())
+            }
+            _ => Err(format!(
+                "the Z80 registers need {} bytes of RAM at an address up to 0x{:x}, not `{}`",
+                size,
+                0x10000 - size,
+                origin
+            )),
+        }
+    }
+
+    fn has_condition_flags(&self) -> bool {
+        false
+    }
+
+    fn generate_compare_branch(&self, out: &mut String, comparison: &Comparison, label: &str) {
+        let condition = self.flags(out, comparison);
+        emit!(out, "    jp {}, {}\n", condition, label);
+    }
+
+    fn generate_compare_set(&self, out: &mut String, comparison: &Comparison, dst: &str) {
+        // Neither `ld` nor `inc` of a pair changes the flags.
+        let condition = self.flags(out, comparison);
+        let words = match self.destination(dst) {
+            Ok(words) => words,
+            Err(error) => {
+                out.push_str(&error);
+                return;
+            }
+        };
+        let skip = self.label();
+        out.push_str("    ld hl, 0\n");
+        if let Some(high) = words.get(1) {
+            Self::store(out, high, "hl");
+        }
+        emit!(
+            out,
+            "    jp {}, {}\n    inc hl\n{}:\n",
+            Self::inverse(condition),
+            skip,
+            skip
+        );
+        Self::store(out, &words[0], "hl");
+    }
+
+    fn generate_compare_cmov(
+        &self,
+        out: &mut String,
+        comparison: &Comparison,
+        dst: &str,
+        src: &str,
+    ) {
+        let condition = self.flags(out, comparison);
+        let skip = self.label();
+        emit!(out, "    jp {}, {}\n", Self::inverse(condition), skip);
+        self.generate_mov(out, dst, src);
+        emit!(out, "{}:\n", skip);
+    }
+}
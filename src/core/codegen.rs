@@ -1,10 +1,18 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
 use crate::{
-    arch::{ArchCodeGen, create_arch_codegen},
-    platform::{PlatformCodeGen, create_platform_codegen},
+    arch::{ArchCodeGen, Comparison, Pic, create_arch_codegen, create_compact_arch_codegen},
+    core::listing::{self, Emitted},
+    core::opt::{self, OptReport, Registers, Target, flags::Lowered},
+    platform::{Frame, PlatformCodeGen, create_platform_codegen},
 };
 
 use super::*;
 
+/// Bytes of assembly the emitter collects before writing them out.
+const FLUSH_SIZE: usize = 64 * 1024;
+
 /// Configuration options for code generation and optimization.
 #[derive(Debug, Clone)]
 pub struct CodeGenConfig {
@@ -40,6 +48,10 @@ pub struct CodeGenConfig {
     /// to reduce loop overhead and increase instruction-level parallelism.
     pub enable_loop_unrolling: bool,
 
+    /// How many copies of a loop body unrolling makes, unless a `.unroll`
+    /// directive asks for another count.
+    pub unroll_factor: u32,
+
     /// Enables branch prediction hints: rearranges branches or inserts hints to improve
     /// CPU branch prediction accuracy.
     pub enable_branch_prediction_optimization: bool,
@@ -59,128 +71,616 @@ pub struct CodeGenConfig {
     /// Enables strength reduction: replaces expensive operations with cheaper equivalents
     /// (e.g., replacing multiplication with addition or shifts where possible).
     pub enable_strength_reduction: bool,
+
+    /// Source file to attribute the generated code to, line by line, so
+    /// debuggers show UASM source. The parser must insert `.loc` markers.
+    pub debug_source: Option<String>,
+
+    /// Emits unwind information for functions, so debuggers, profilers and
+    /// exceptions can walk the stack through them.
+    pub unwind_info: bool,
+
+    /// Emits position-independent code, which addresses symbols relative to
+    /// the program counter or through the GOT, for shared libraries and PIE
+    /// executables.
+    pub position_independent: bool,
+
+    /// Lets the backend use optional bit-manipulation instructions, Zbb on
+    /// RISC-V, which not every implementation of the target has.
+    pub bit_manipulation: bool,
+
+    /// The processor model to generate code for, `68000` or `68020` on
+    /// m68k; the backend's default when unset.
+    pub cpu: Option<String>,
+
+    /// The calling convention to generate code for, `windowed` or `call0`
+    /// on Xtensa; the backend's default when unset.
+    pub abi: Option<String>,
+
+    /// The RAM address the registers emulated in memory are kept at on Z80;
+    /// the backend's default when unset.
+    pub ram: Option<String>,
+}
+
+impl Default for CodeGenConfig {
+    /// Only dead code elimination is on by default; it never changes what a
+    /// program computes. Unwind information is emitted too.
+    fn default() -> Self {
+        CodeGenConfig {
+            enable_peephole_optimization: false,
+            enable_instruction_section: false,
+            enable_target_specific_optimizations: false,
+            enable_size_minimization: false,
+            enable_constant_folding: false,
+            enable_dead_code_elimination: true,
+            enable_common_subexpression_elimination: false,
+            enable_loop_unrolling: false,
+            unroll_factor: 4,
+            enable_branch_prediction_optimization: false,
+            enable_register_allocation_optimization: false,
+            enable_instruction_scheduling: false,
+            enable_function_inlining: false,
+            enable_strength_reduction: false,
+            debug_source: None,
+            unwind_info: true,
+            position_independent: false,
+            bit_manipulation: false,
+            cpu: None,
+            abi: None,
+            ram: None,
+        }
+    }
 }
 
 pub struct CodeGenerator {
     arch_codegen: Box<dyn ArchCodeGen>,
     platform_codegen: Box<dyn PlatformCodeGen>,
     target: TargetTriple,
+    config: CodeGenConfig,
 }
 
 impl CodeGenerator {
     pub fn new(target: TargetTriple) -> Self {
-        let arch_codegen = create_arch_codegen(&target.architecture);
+        Self::with_config(target, CodeGenConfig::default())
+    }
+
+    pub fn with_config(target: TargetTriple, config: CodeGenConfig) -> Self {
+        let mut arch_codegen = create_arch(&target, &config);
+        if config.position_independent {
+            // The symbols are not known yet, but optimization only needs the
+            // registers position-independent code clobbers. A backend that
+            // cannot switch fails when the program is emitted.
+            let _ = arch_codegen.set_position_independent(Pic::new(target.format, &[]));
+        }
         let platform_codegen = create_platform_codegen(&target.platform, &target.architecture);
 
         CodeGenerator {
             arch_codegen,
             platform_codegen,
             target,
+            config,
         }
     }
 
-    pub fn generate(&self, instructions: &[Instruction]) -> String {
-        let mut output = String::with_capacity(64);
-        output.push_str(&self.arch_codegen.get_syntax_header());
+    /// Runs the IR passes enabled in the config.
+    pub fn optimize(&self, instructions: &[Instruction]) -> (Vec<Instruction>, OptReport) {
+        let (instructions, report, _) = self.run_passes(instructions);
+        (instructions, report)
+    }
+
+    /// Like `optimize`, failing on a register the target does not have that
+    /// is still left in the result.
+    fn optimized(
+        &self,
+        instructions: &[Instruction],
+    ) -> Result<(Vec<Instruction>, OptReport), String> {
+        let (instructions, report, allocated) = self.run_passes(instructions);
+        // Only the allocator can leave such a name behind.
+        if allocated {
+            self.placed(&instructions, &report)?;
+        }
+        Ok((instructions, report))
+    }
+
+    /// Runs the IR passes, also telling whether the register allocator ran.
+    fn run_passes(&self, instructions: &[Instruction]) -> (Vec<Instruction>, OptReport, bool) {
+        let mut report = OptReport::default();
+        let register_map = self.arch_codegen.get_register_map();
+        let names = opt::regalloc::names(instructions);
+        let mut instructions =
+            opt::regalloc::separate(instructions, &names, &register_map, &mut report);
+        // Renaming gives names beyond the map other names beyond it.
+        let mut unmapped = names.iter().any(|name| !register_map.contains_key(*name));
+        let target = self.opt_target();
+        // Comparisons and rotates the backend cannot emit become code it can,
+        // working in fresh names the allocator then places.
+        if !target.flags {
+            let locals = opt::regalloc::locals(&instructions, &register_map, &target);
+            let regs = Registers::with_locals(&register_map, &locals);
+            let arch = &self.arch_codegen;
+            instructions =
+                opt::flags::expand(instructions, &regs, target.width, &|t| arch.compares(t));
+            unmapped = unmapped || opt::regalloc::unmapped(&instructions, &register_map).is_some();
+        }
+        // Registers the target does not have are placed even when allocation
+        // is off, since nothing else can emit them.
+        let allocate = self.config.enable_register_allocation_optimization || unmapped;
+        if self.config.enable_function_inlining {
+            let locals = if allocate {
+                opt::regalloc::locals(&instructions, &register_map, &target)
+            } else {
+                HashSet::new()
+            };
+            let regs = Registers::new(&register_map);
+            instructions = opt::inline::run(
+                instructions,
+                &regs,
+                &locals,
+                self.config.enable_size_minimization,
+                self.config.enable_dead_code_elimination,
+                &mut report,
+            );
+        }
+        // With allocation on, names without a register of their own are
+        // placed by the allocator instead of aliasing the backend's map.
+        let regs = if allocate {
+            let locals = opt::regalloc::locals(&instructions, &register_map, &target);
+            Registers::with_locals(&register_map, &locals)
+        } else {
+            Registers::new(&register_map)
+        };
+
+        if self.config.enable_strength_reduction {
+            instructions = opt::strength::run(instructions, &regs, &target, &mut report);
+        }
+        if self.config.enable_loop_unrolling {
+            instructions = opt::unroll::run(
+                instructions,
+                &regs,
+                &target,
+                self.config.unroll_factor,
+                &mut report,
+            );
+        }
+        let clobbers = |i: &Instruction| self.arch_codegen.get_clobbered_registers(i);
+        if self.config.enable_common_subexpression_elimination {
+            instructions = opt::cse::run(instructions, &regs, &clobbers, &mut report);
+        }
+        if self.config.enable_dead_code_elimination {
+            instructions = opt::dce::run(instructions, &regs, &mut report);
+        }
+        if allocate {
+            instructions = opt::regalloc::run(instructions, &regs, &target, &clobbers, &mut report);
+        }
+        // Scheduling sees the registers the allocator picked.
+        if self.config.enable_instruction_scheduling {
+            let regs = Registers::new(&register_map);
+            instructions = opt::schedule::run(instructions, &regs, &target, &clobbers, &mut report);
+        }
+        if self.config.enable_size_minimization {
+            let regs = Registers::new(&register_map);
+            instructions = opt::size::run(instructions, &regs, &target, &mut report);
+        }
+        if self.config.enable_branch_prediction_optimization {
+            instructions = opt::layout::run(
+                instructions,
+                &target,
+                self.config.enable_size_minimization,
+                &mut report,
+            );
+        }
+
+        (instructions, report, allocate)
+    }
+
+    /// What the IR passes know about the target.
+    fn opt_target(&self) -> Target {
+        Target {
+            architecture: self.target.architecture,
+            width: self.arch_codegen.get_register_width(),
+            reserved: self
+                .arch_codegen
+                .get_reserved_registers()
+                .into_iter()
+                .collect(),
+            callee_saved: self
+                .arch_codegen
+                .get_callee_saved_registers()
+                .into_iter()
+                .collect(),
+            push_size: self.arch_codegen.get_push_size(),
+            flags: self.arch_codegen.has_condition_flags(),
+        }
+    }
+
+    pub fn generate(&self, instructions: &[Instruction]) -> Result<String, String> {
+        Ok(self.generate_with_report(instructions)?.0)
+    }
+
+    /// Like `generate`, also returning what the optimization passes changed.
+    pub fn generate_with_report(
+        &self,
+        instructions: &[Instruction],
+    ) -> Result<(String, OptReport), String> {
+        let mut output = Vec::new();
+        let report = self.generate_to(instructions, &mut output)?;
+        let output = String::from_utf8(output).expect("backends emit UTF-8");
+        Ok((output, report))
+    }
+
+    /// Like `generate_with_report`, writing the assembly to `out` as it is
+    /// emitted instead of collecting it. Nothing is written when the program
+    /// cannot be compiled for the target.
+    pub fn generate_to<W: Write>(
+        &self,
+        instructions: &[Instruction],
+        out: &mut W,
+    ) -> Result<OptReport, String> {
+        let (instructions, report) = self.optimized(instructions)?;
+        self.emit(&instructions, out, None)?;
+        Ok(report)
+    }
+
+    /// Like `generate_to`, also returning a listing of `source` that shows
+    /// each line beside the assembly it produced. The instructions must have
+    /// been parsed from `source` with line information.
+    pub fn generate_listing_to<W: Write>(
+        &self,
+        instructions: &[Instruction],
+        source: &str,
+        out: &mut W,
+    ) -> Result<(OptReport, String), String> {
+        let (instructions, report) = self.optimized(instructions)?;
+        let mut emitted = Emitted::default();
+        self.emit(&instructions, out, Some(&mut emitted))?;
+        let listing = listing::render(source, &self.target, &instructions, &emitted);
+        Ok((report, listing))
+    }
+
+    /// Fails on a register the target does not have that is still left in
+    /// `instructions`, giving the allocator's reason for leaving it.
+    fn placed(&self, instructions: &[Instruction], report: &OptReport) -> Result<(), String> {
+        let register_map = self.arch_codegen.get_register_map();
+        let Some(name) = opt::regalloc::unmapped(instructions, &register_map) else {
+            return Ok(());
+        };
+        let mut message = format!(
+            "{:?} has no register for `{}`",
+            self.target.architecture, name
+        );
+        for note in &report.notes {
+            if note.pass == "regalloc" && note.message.starts_with("left ") {
+                message.push_str(&format!("; regalloc {}", note.message));
+            }
+        }
+        Err(message)
+    }
+
+    /// Emits `instructions`, collecting what each produced into `listing`.
+    fn emit<W: Write>(
+        &self,
+        instructions: &[Instruction],
+        out: &mut W,
+        mut listing: Option<&mut Emitted>,
+    ) -> Result<(), String> {
+        let write = |out: &mut W, output: &mut String| {
+            let written = out.write_all(output.as_bytes());
+            output.clear();
+            written.map_err(|err| format!("cannot write the output: {}", err))
+        };
+        if self.config.bit_manipulation {
+            create_arch_codegen(&self.target.architecture).set_bit_manipulation()?;
+        }
+        if let Some(cpu) = &self.config.cpu {
+            create_arch_codegen(&self.target.architecture).set_cpu(cpu)?;
+        }
+        if let Some(abi) = &self.config.abi {
+            create_arch_codegen(&self.target.architecture).set_abi(abi)?;
+        }
+        if let Some(ram) = &self.config.ram {
+            create_arch_codegen(&self.target.architecture).set_ram(ram)?;
+        }
+        // Position-independent code comes from a backend that knows which
+        // names in the program are addresses. Backends that are always
+        // position-independent reach an address in any operand.
+        // Backends with a separate program memory learn which names are in
+        // it the same way, and backends with address registers which names
+        // the program addresses memory through.
+        let pic_codegen;
+        let always_pic = self.arch_codegen.requires_position_independence();
+        let pic = self.config.position_independent || always_pic;
+        let program_memory = self.arch_codegen.has_program_memory();
+        let address_registers = self.arch_codegen.has_address_registers();
+        let arch: &dyn ArchCodeGen = if pic || program_memory || address_registers {
+            let mut codegen = create_arch(&self.target, &self.config);
+            if pic {
+                let pic = Pic::new(self.target.format, instructions);
+                if !always_pic {
+                    for instruction in instructions {
+                        check_position_independent(&pic, instruction)?;
+                    }
+                }
+                codegen.set_position_independent(pic)?;
+            }
+            if program_memory {
+                codegen.set_program_memory(read_only_data(instructions));
+            }
+            if address_registers {
+                codegen.set_address_registers(address_uses(instructions));
+            }
+            pic_codegen = codegen;
+            pic_codegen.as_ref()
+        } else {
+            self.arch_codegen.as_ref()
+        };
+        // Every instruction is emitted into this buffer, which goes to `out`
+        // whenever it fills up.
+        let mut output = String::with_capacity(FLUSH_SIZE + 256);
+        output.push_str(&arch.get_syntax_header());
+
+        // Targets without condition flags test each comparison where it is
+        // read.
+        let lowered = if arch.has_condition_flags() {
+            None
+        } else {
+            let regs = Registers::new(&arch.get_register_map());
+            Some(opt::flags::lower(instructions, &regs)?)
+        };
+
+        // When minimizing size, backends may clobber registers that are dead
+        // instead of saving them.
+        let live_after = self.config.enable_size_minimization.then(|| {
+            let regs = Registers::new(&arch.get_register_map());
+            opt::size::live_after(instructions, &regs)
+        });
+        let unwind = match arch.get_frame_layout() {
+            Some(layout) if self.config.unwind_info => {
+                let regs = Registers::new(&arch.get_register_map());
+                opt::unwind::describe(instructions, &regs, &self.opt_target(), &layout)?
+            }
+            // A debugger could not walk the stack past the first frame.
+            None if self.config.unwind_info && self.config.debug_source.is_some() => {
+                return Err("-g needs unwind information, which this target's frames cannot \
+                            be described with; pass --no-unwind for line information alone"
+                    .to_string());
+            }
+            _ => opt::unwind::Unwind::default(),
+        };
+        let mut function_labels: HashSet<String> = opt::unwind::functions(instructions)
+            .into_iter()
+            .map(|(name, _, _)| name)
+            .collect();
+        if arch.needs_function_labels() {
+            let labels: HashSet<&String> = instructions
+                .iter()
+                .filter_map(|instruction| match instruction {
+                    Instruction::Label(name) => Some(name),
+                    _ => None,
+                })
+                .collect();
+            function_labels.extend(instructions.iter().filter_map(|instruction| {
+                match instruction {
+                    Instruction::Call(name) if labels.contains(name) => Some(name.clone()),
+                    _ => None,
+                }
+            }));
+        }
+        let mut functions = unwind.functions.iter().peekable();
+        // The function being emitted and the frame last described in it.
+        let mut function: Option<(&opt::unwind::Function, Frame)> = None;
+        // The frame before the current instruction, once it is known.
+        let mut frame: Option<&Frame> = None;
 
-        for instruction in instructions {
+        if let Some(path) = &self.config.debug_source {
+            output.push_str(&self.platform_codegen.get_line_table_start(path));
+        }
+        if let Some(emitted) = listing.as_deref_mut() {
+            emitted.header = output.clone();
+        }
+        // Where the code of the previous instruction starts in `output`.
+        let mut produced = None;
+        // Source line of the code that follows, and the last one emitted.
+        let (mut line, mut emitted_line) = (None, None);
+        let mut in_text = true;
+        // Prediction for the conditional jump that comes next, if any.
+        let mut hint = None;
+        for (i, instruction) in instructions.iter().enumerate() {
+            if let Some(from) = produced {
+                let barrier = opt::cfg::is_terminator(&instructions[i - 1])
+                    || matches!(instruction, Instruction::Section(_));
+                arch.generate_literal_pool(&mut output, from, barrier);
+            }
+            if let Some(emitted) = listing.as_deref_mut()
+                && let Some(from) = produced.take()
+            {
+                emitted.code.push(output[from..].to_string());
+            }
+            if output.len() >= FLUSH_SIZE {
+                write(out, &mut output)?;
+            }
+            // Where the directives before this instruction's code start.
+            let directives = output.len();
+            match instruction {
+                Instruction::Section(section) => in_text = matches!(section, Section::Text),
+                Instruction::Loc(n) => line = Some(*n),
+                _ => {}
+            }
+            if let Some((f, _)) = function
+                && f.end == i
+            {
+                output.push_str(&self.unwind_end(f));
+                function = None;
+            }
+            if let Some(f) = functions.next_if(|f| f.start == i) {
+                output.push_str(&self.platform_codegen.get_unwind_start(&f.name));
+                function = Some((f, f.entry.clone()));
+                frame = None;
+            }
+            // There are no frames when unwind information is off.
+            if let Some(Some(known)) = unwind.frames.get(i) {
+                frame = Some(known);
+            }
+            if let Some((f, described)) = &mut function {
+                let prologue_end = i == f.prologue_end;
+                if (prologue_end || opt::is_code(instruction))
+                    && let Some(frame) = frame
+                    && frame != described
+                {
+                    output.push_str(&self.platform_codegen.get_unwind_step(
+                        described,
+                        frame,
+                        i <= f.prologue_end,
+                    ));
+                    *described = frame.clone();
+                }
+                if prologue_end {
+                    output.push_str(&self.platform_codegen.get_prologue_end());
+                }
+            }
+            let dropped = matches!(
+                lowered.as_ref().map(|lowered| &lowered[i]),
+                Some(Lowered::Drop)
+            );
+            if self.config.debug_source.is_some()
+                && in_text
+                && opt::is_code(instruction)
+                && !dropped
+                && line != emitted_line
+                && let Some(n) = line
+            {
+                output.push_str(&self.platform_codegen.get_line_directive(n));
+                emitted_line = line;
+            }
+            let start = output.len();
+            produced = Some(start);
+            if let Some(emitted) = listing.as_deref_mut() {
+                emitted.directives.push(output[directives..start].to_string());
+            }
+            let compact = live_after.as_ref().is_some_and(|live| {
+                let dead = |register: &str| !live[i].contains(register);
+                arch.generate_compact(&mut output, instruction, &dead)
+            });
+            if compact {
+                hint = None;
+                continue;
+            }
+            match lowered.as_ref().map(|lowered| &lowered[i]) {
+                Some(Lowered::Drop) => continue,
+                Some(Lowered::Read(comparison)) => {
+                    self.generate_fused(&mut output, arch, instruction, comparison);
+                    if let Some(likely) = hint.take()
+                        && opt::cfg::branch_target(instruction).is_some()
+                    {
+                        arch.generate_branch_hint(&mut output, start, likely);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
             match instruction {
                 Instruction::Section(section) => {
                     output.push_str(&self.platform_codegen.get_section_prefix(section));
                 }
+                Instruction::Label(name) if function_labels.contains(name) => {
+                    arch.generate_function_label(&mut output, name);
+                }
                 Instruction::Label(name) => {
-                    output.push_str(&format!("{}:\n", name));
+                    output.push_str(name);
+                    output.push_str(":\n");
                 }
                 Instruction::Mov((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_mov(dst, src));
+                    arch.generate_mov(&mut output, dst, src);
                 }
                 Instruction::Lea((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_lea(dst, src));
+                    arch.generate_lea(&mut output, dst, src);
                 }
                 Instruction::Load((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_load(dst, src));
+                    arch.generate_load(&mut output, dst, src);
                 }
                 Instruction::Store((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_store(dst, src));
+                    arch.generate_store(&mut output, dst, src);
                 }
                 Instruction::Add((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_add(dst, src));
+                    arch.generate_add(&mut output, dst, src);
                 }
                 Instruction::Sub((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_sub(dst, src));
+                    arch.generate_sub(&mut output, dst, src);
                 }
                 Instruction::Mul((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_mul(dst, src));
+                    arch.generate_mul(&mut output, dst, src);
                 }
                 Instruction::Div((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_div(dst, src));
+                    arch.generate_div(&mut output, dst, src);
                 }
                 Instruction::Inc(dst) => {
-                    output.push_str(&self.arch_codegen.generate_inc(dst));
+                    arch.generate_inc(&mut output, dst);
                 }
                 Instruction::Dec(dst) => {
-                    output.push_str(&self.arch_codegen.generate_dec(dst));
+                    arch.generate_dec(&mut output, dst);
                 }
                 Instruction::Neg(dst) => {
-                    output.push_str(&self.arch_codegen.generate_neg(dst));
+                    arch.generate_neg(&mut output, dst);
                 }
                 Instruction::And((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_and(dst, src));
+                    arch.generate_and(&mut output, dst, src);
                 }
                 Instruction::Or((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_or(dst, src));
+                    arch.generate_or(&mut output, dst, src);
                 }
                 Instruction::Xor((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_xor(dst, src));
+                    arch.generate_xor(&mut output, dst, src);
                 }
                 Instruction::Not(dst) => {
-                    output.push_str(&self.arch_codegen.generate_not(dst));
+                    arch.generate_not(&mut output, dst);
                 }
                 Instruction::Shl((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_shl(dst, src));
+                    arch.generate_shl(&mut output, dst, src);
                 }
                 Instruction::Shr((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_shr(dst, src));
+                    arch.generate_shr(&mut output, dst, src);
                 }
                 Instruction::Cmp((op1, op2)) => {
-                    output.push_str(&self.arch_codegen.generate_cmp(op1, op2));
+                    arch.generate_cmp(&mut output, op1, op2);
                 }
                 Instruction::Test((op1, op2)) => {
-                    output.push_str(&self.arch_codegen.generate_test(op1, op2));
+                    arch.generate_test(&mut output, op1, op2);
                 }
                 Instruction::Jmp(label) => {
-                    output.push_str(&self.arch_codegen.generate_jmp(label));
+                    arch.generate_jmp(&mut output, label);
                 }
                 Instruction::Je(label) => {
-                    output.push_str(&self.arch_codegen.generate_je(label));
+                    arch.generate_je(&mut output, label);
                 }
                 Instruction::Jne(label) => {
-                    output.push_str(&self.arch_codegen.generate_jne(label));
+                    arch.generate_jne(&mut output, label);
                 }
                 Instruction::Jg(label) => {
-                    output.push_str(&self.arch_codegen.generate_jg(label));
+                    arch.generate_jg(&mut output, label);
                 }
                 Instruction::Jl(label) => {
-                    output.push_str(&self.arch_codegen.generate_jl(label));
+                    arch.generate_jl(&mut output, label);
                 }
                 Instruction::Jge(label) => {
-                    output.push_str(&self.arch_codegen.generate_jge(label));
+                    arch.generate_jge(&mut output, label);
                 }
                 Instruction::Jle(label) => {
-                    output.push_str(&self.arch_codegen.generate_jle(label));
+                    arch.generate_jle(&mut output, label);
                 }
                 Instruction::Call(func) => {
-                    output.push_str(&self.arch_codegen.generate_call(func));
+                    arch.generate_call(&mut output, func);
                 }
                 Instruction::Ret => {
-                    output.push_str(&self.arch_codegen.generate_ret());
+                    arch.generate_ret(&mut output);
                 }
                 Instruction::Syscall(name) => {
-                    output.push_str(&self.arch_codegen.generate_syscall(name));
+                    arch.generate_syscall(&mut output, name);
                 }
                 Instruction::Global(symbol) => {
                     output.push_str(&self.platform_codegen.get_global_directive(symbol));
                 }
+                Instruction::Function(symbol) => {
+                    output.push_str(&self.platform_codegen.get_function_directive(symbol));
+                }
                 Instruction::Extern(symbol) => {
                     output.push_str(&self.platform_codegen.get_extern_directive(symbol));
                 }
@@ -224,299 +724,376 @@ impl CodeGenerator {
                 }
                 // Conditional move instructions
                 Instruction::CmovEq((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_cmov_eq(dst, src));
+                    arch.generate_cmov_eq(&mut output, dst, src);
                 }
                 Instruction::CmovNe((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_cmov_ne(dst, src));
+                    arch.generate_cmov_ne(&mut output, dst, src);
                 }
                 Instruction::CmovLt((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_cmov_lt(dst, src));
+                    arch.generate_cmov_lt(&mut output, dst, src);
                 }
                 Instruction::CmovLe((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_cmov_le(dst, src));
+                    arch.generate_cmov_le(&mut output, dst, src);
                 }
                 Instruction::CmovGt((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_cmov_gt(dst, src));
+                    arch.generate_cmov_gt(&mut output, dst, src);
                 }
                 Instruction::CmovGe((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_cmov_ge(dst, src));
+                    arch.generate_cmov_ge(&mut output, dst, src);
                 }
                 Instruction::CmovOv((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_cmov_ov(dst, src));
+                    arch.generate_cmov_ov(&mut output, dst, src);
                 }
                 Instruction::CmovNo((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_cmov_no(dst, src));
+                    arch.generate_cmov_no(&mut output, dst, src);
                 }
                 Instruction::CmovS((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_cmov_s(dst, src));
+                    arch.generate_cmov_s(&mut output, dst, src);
                 }
                 Instruction::CmovNs((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_cmov_ns(dst, src));
+                    arch.generate_cmov_ns(&mut output, dst, src);
                 }
                 Instruction::CmovP((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_cmov_p(dst, src));
+                    arch.generate_cmov_p(&mut output, dst, src);
                 }
                 Instruction::CmovNp((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_cmov_np(dst, src));
+                    arch.generate_cmov_np(&mut output, dst, src);
                 }
                 Instruction::CmovA((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_cmov_a(dst, src));
+                    arch.generate_cmov_a(&mut output, dst, src);
                 }
                 Instruction::CmovAe((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_cmov_ae(dst, src));
+                    arch.generate_cmov_ae(&mut output, dst, src);
                 }
                 Instruction::CmovB((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_cmov_b(dst, src));
+                    arch.generate_cmov_b(&mut output, dst, src);
                 }
                 Instruction::CmovBe((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_cmov_be(dst, src));
+                    arch.generate_cmov_be(&mut output, dst, src);
                 }
                 // Stack operations
                 Instruction::Push(src) => {
-                    output.push_str(&self.arch_codegen.generate_push(src));
+                    arch.generate_push(&mut output, src);
                 }
                 Instruction::Pop(dst) => {
-                    output.push_str(&self.arch_codegen.generate_pop(dst));
+                    arch.generate_pop(&mut output, dst);
                 }
                 Instruction::Pusha => {
-                    output.push_str(&self.arch_codegen.generate_pusha());
+                    arch.generate_pusha(&mut output);
                 }
                 Instruction::Popa => {
-                    output.push_str(&self.arch_codegen.generate_popa());
+                    arch.generate_popa(&mut output);
                 }
                 Instruction::Enter((frame_size, nesting)) => {
-                    output.push_str(&self.arch_codegen.generate_enter(frame_size, nesting));
+                    arch.generate_enter(&mut output, frame_size, nesting);
                 }
                 Instruction::Leave => {
-                    output.push_str(&self.arch_codegen.generate_leave());
+                    arch.generate_leave(&mut output);
                 }
                 // Additional arithmetic operations
                 Instruction::Imul((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_imul(dst, src));
+                    arch.generate_imul(&mut output, dst, src);
                 }
                 Instruction::Idiv((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_idiv(dst, src));
+                    arch.generate_idiv(&mut output, dst, src);
                 }
                 Instruction::Mod((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_mod(dst, src));
+                    arch.generate_mod(&mut output, dst, src);
+                }
+                Instruction::Mulh((dst, src)) => {
+                    arch.generate_mulh(&mut output, dst, src);
                 }
                 Instruction::Andn((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_andn(dst, src));
+                    arch.generate_andn(&mut output, dst, src);
                 }
                 // Shift and rotate operations
                 Instruction::Sal((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_sal(dst, src));
+                    arch.generate_sal(&mut output, dst, src);
                 }
                 Instruction::Sar((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_sar(dst, src));
+                    arch.generate_sar(&mut output, dst, src);
                 }
                 Instruction::Rol((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_rol(dst, src));
+                    arch.generate_rol(&mut output, dst, src);
                 }
                 Instruction::Ror((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_ror(dst, src));
+                    arch.generate_ror(&mut output, dst, src);
                 }
                 Instruction::Rcl((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_rcl(dst, src));
+                    arch.generate_rcl(&mut output, dst, src);
                 }
                 Instruction::Rcr((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_rcr(dst, src));
+                    arch.generate_rcr(&mut output, dst, src);
                 }
                 // Bit manipulation operations
                 Instruction::Bextr((dst, src, imm)) => {
-                    output.push_str(&self.arch_codegen.generate_bextr(dst, src, imm));
+                    arch.generate_bextr(&mut output, dst, src, imm);
                 }
                 Instruction::Bsf((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_bsf(dst, src));
+                    arch.generate_bsf(&mut output, dst, src);
                 }
                 Instruction::Bsr((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_bsr(dst, src));
+                    arch.generate_bsr(&mut output, dst, src);
                 }
                 Instruction::Bt((dst, bit)) => {
-                    output.push_str(&self.arch_codegen.generate_bt(dst, bit));
+                    arch.generate_bt(&mut output, dst, bit);
                 }
                 Instruction::Btr((dst, bit)) => {
-                    output.push_str(&self.arch_codegen.generate_btr(dst, bit));
+                    arch.generate_btr(&mut output, dst, bit);
                 }
                 Instruction::Bts((dst, bit)) => {
-                    output.push_str(&self.arch_codegen.generate_bts(dst, bit));
+                    arch.generate_bts(&mut output, dst, bit);
                 }
                 Instruction::Btc((dst, bit)) => {
-                    output.push_str(&self.arch_codegen.generate_btc(dst, bit));
+                    arch.generate_btc(&mut output, dst, bit);
                 }
                 // Set condition code operations
                 Instruction::SetEq(dst) => {
-                    output.push_str(&self.arch_codegen.generate_set_eq(dst));
+                    arch.generate_set_eq(&mut output, dst);
                 }
                 Instruction::SetNe(dst) => {
-                    output.push_str(&self.arch_codegen.generate_set_ne(dst));
+                    arch.generate_set_ne(&mut output, dst);
                 }
                 Instruction::SetLt(dst) => {
-                    output.push_str(&self.arch_codegen.generate_set_lt(dst));
+                    arch.generate_set_lt(&mut output, dst);
                 }
                 Instruction::SetLe(dst) => {
-                    output.push_str(&self.arch_codegen.generate_set_le(dst));
+                    arch.generate_set_le(&mut output, dst);
                 }
                 Instruction::SetGt(dst) => {
-                    output.push_str(&self.arch_codegen.generate_set_gt(dst));
+                    arch.generate_set_gt(&mut output, dst);
                 }
                 Instruction::SetGe(dst) => {
-                    output.push_str(&self.arch_codegen.generate_set_ge(dst));
+                    arch.generate_set_ge(&mut output, dst);
                 }
                 Instruction::SetOv(dst) => {
-                    output.push_str(&self.arch_codegen.generate_set_ov(dst));
+                    arch.generate_set_ov(&mut output, dst);
                 }
                 Instruction::SetNo(dst) => {
-                    output.push_str(&self.arch_codegen.generate_set_no(dst));
+                    arch.generate_set_no(&mut output, dst);
                 }
                 Instruction::SetS(dst) => {
-                    output.push_str(&self.arch_codegen.generate_set_s(dst));
+                    arch.generate_set_s(&mut output, dst);
                 }
                 Instruction::SetNs(dst) => {
-                    output.push_str(&self.arch_codegen.generate_set_ns(dst));
+                    arch.generate_set_ns(&mut output, dst);
                 }
                 Instruction::SetP(dst) => {
-                    output.push_str(&self.arch_codegen.generate_set_p(dst));
+                    arch.generate_set_p(&mut output, dst);
                 }
                 Instruction::SetNp(dst) => {
-                    output.push_str(&self.arch_codegen.generate_set_np(dst));
+                    arch.generate_set_np(&mut output, dst);
                 }
                 Instruction::SetA(dst) => {
-                    output.push_str(&self.arch_codegen.generate_set_a(dst));
+                    arch.generate_set_a(&mut output, dst);
                 }
                 Instruction::SetAe(dst) => {
-                    output.push_str(&self.arch_codegen.generate_set_ae(dst));
+                    arch.generate_set_ae(&mut output, dst);
                 }
                 Instruction::SetB(dst) => {
-                    output.push_str(&self.arch_codegen.generate_set_b(dst));
+                    arch.generate_set_b(&mut output, dst);
                 }
                 Instruction::SetBe(dst) => {
-                    output.push_str(&self.arch_codegen.generate_set_be(dst));
+                    arch.generate_set_be(&mut output, dst);
                 }
                 // String operations
                 Instruction::Cmps((src1, src2)) => {
-                    output.push_str(&self.arch_codegen.generate_cmps(src1, src2));
+                    arch.generate_cmps(&mut output, src1, src2);
                 }
                 Instruction::Scas((src, val)) => {
-                    output.push_str(&self.arch_codegen.generate_scas(src, val));
+                    arch.generate_scas(&mut output, src, val);
                 }
                 Instruction::Stos((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_stos(dst, src));
+                    arch.generate_stos(&mut output, dst, src);
                 }
                 Instruction::Lods((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_lods(dst, src));
+                    arch.generate_lods(&mut output, dst, src);
                 }
                 Instruction::Movs((dst, src)) => {
-                    output.push_str(&self.arch_codegen.generate_movs(dst, src));
+                    arch.generate_movs(&mut output, dst, src);
                 }
                 // Data conversion operations
                 Instruction::Cbw(dst) => {
-                    output.push_str(&self.arch_codegen.generate_cbw(dst));
+                    arch.generate_cbw(&mut output, dst);
                 }
                 Instruction::Cwd(dst) => {
-                    output.push_str(&self.arch_codegen.generate_cwd(dst));
+                    arch.generate_cwd(&mut output, dst);
                 }
                 Instruction::Cdq(dst) => {
-                    output.push_str(&self.arch_codegen.generate_cdq(dst));
+                    arch.generate_cdq(&mut output, dst);
                 }
                 Instruction::Cqo(dst) => {
-                    output.push_str(&self.arch_codegen.generate_cqo(dst));
+                    arch.generate_cqo(&mut output, dst);
                 }
                 Instruction::Cwde(dst) => {
-                    output.push_str(&self.arch_codegen.generate_cwde(dst));
+                    arch.generate_cwde(&mut output, dst);
                 }
                 Instruction::Cdqe(dst) => {
-                    output.push_str(&self.arch_codegen.generate_cdqe(dst));
+                    arch.generate_cdqe(&mut output, dst);
                 }
                 // Additional jump instructions
                 Instruction::Jo(label) => {
-                    output.push_str(&self.arch_codegen.generate_jo(label));
+                    arch.generate_jo(&mut output, label);
                 }
                 Instruction::Jno(label) => {
-                    output.push_str(&self.arch_codegen.generate_jno(label));
+                    arch.generate_jno(&mut output, label);
                 }
                 Instruction::Js(label) => {
-                    output.push_str(&self.arch_codegen.generate_js(label));
+                    arch.generate_js(&mut output, label);
                 }
                 Instruction::Jns(label) => {
-                    output.push_str(&self.arch_codegen.generate_jns(label));
+                    arch.generate_jns(&mut output, label);
                 }
                 Instruction::Jp(label) => {
-                    output.push_str(&self.arch_codegen.generate_jp(label));
+                    arch.generate_jp(&mut output, label);
                 }
                 Instruction::Jnp(label) => {
-                    output.push_str(&self.arch_codegen.generate_jnp(label));
+                    arch.generate_jnp(&mut output, label);
                 }
                 Instruction::Ja(label) => {
-                    output.push_str(&self.arch_codegen.generate_ja(label));
+                    arch.generate_ja(&mut output, label);
                 }
                 Instruction::Jae(label) => {
-                    output.push_str(&self.arch_codegen.generate_jae(label));
+                    arch.generate_jae(&mut output, label);
                 }
                 Instruction::Jb(label) => {
-                    output.push_str(&self.arch_codegen.generate_jb(label));
+                    arch.generate_jb(&mut output, label);
                 }
                 Instruction::Jbe(label) => {
-                    output.push_str(&self.arch_codegen.generate_jbe(label));
+                    arch.generate_jbe(&mut output, label);
                 }
                 Instruction::LoopEq(label) => {
-                    output.push_str(&self.arch_codegen.generate_loop_eq(label));
+                    arch.generate_loop_eq(&mut output, label);
                 }
                 Instruction::LoopNe(label) => {
-                    output.push_str(&self.arch_codegen.generate_loop_ne(label));
+                    arch.generate_loop_ne(&mut output, label);
                 }
                 // I/O operations
                 Instruction::In((dst, port)) => {
-                    output.push_str(&self.arch_codegen.generate_in(dst, port));
+                    arch.generate_in(&mut output, dst, port);
                 }
                 Instruction::Out((port, src)) => {
-                    output.push_str(&self.arch_codegen.generate_out(port, src));
+                    arch.generate_out(&mut output, port, src);
                 }
                 Instruction::Ins((dst, port)) => {
-                    output.push_str(&self.arch_codegen.generate_ins(dst, port));
+                    arch.generate_ins(&mut output, dst, port);
                 }
                 Instruction::Outs((port, src)) => {
-                    output.push_str(&self.arch_codegen.generate_outs(port, src));
+                    arch.generate_outs(&mut output, port, src);
                 }
                 // System and memory operations
                 Instruction::Cpuid => {
-                    output.push_str(&self.arch_codegen.generate_cpuid());
+                    arch.generate_cpuid(&mut output);
                 }
                 Instruction::Lfence => {
-                    output.push_str(&self.arch_codegen.generate_lfence());
+                    arch.generate_lfence(&mut output);
                 }
                 Instruction::Sfence => {
-                    output.push_str(&self.arch_codegen.generate_sfence());
+                    arch.generate_sfence(&mut output);
                 }
                 Instruction::Mfence => {
-                    output.push_str(&self.arch_codegen.generate_mfence());
+                    arch.generate_mfence(&mut output);
                 }
                 Instruction::Prefetch(addr) => {
-                    output.push_str(&self.arch_codegen.generate_prefetch(addr));
+                    arch.generate_prefetch(&mut output, addr);
                 }
                 Instruction::Clflush(addr) => {
-                    output.push_str(&self.arch_codegen.generate_clflush(addr));
+                    arch.generate_clflush(&mut output, addr);
                 }
                 Instruction::Clwb(addr) => {
-                    output.push_str(&self.arch_codegen.generate_clwb(addr));
+                    arch.generate_clwb(&mut output, addr);
                 }
                 // Directive operations
                 Instruction::Align(n) => {
-                    output.push_str(&self.arch_codegen.generate_align(n));
+                    arch.generate_align(&mut output, n);
                 }
+                // Hints for the optimization passes.
+                Instruction::Unroll(_) | Instruction::Inline | Instruction::NoInline => {}
+                Instruction::Likely => hint = Some(true),
+                Instruction::Unlikely => hint = Some(false),
+                Instruction::Loc(_) => {}
                 Instruction::ReserveWord(name, size) => {
-                    output.push_str(&self.arch_codegen.generate_reserve_word(name, size));
+                    arch.generate_reserve_word(&mut output, name, size);
                 }
                 Instruction::ReserveDword(name, size) => {
-                    output.push_str(&self.arch_codegen.generate_reserve_dword(name, size));
+                    arch.generate_reserve_dword(&mut output, name, size);
                 }
                 Instruction::ReserveQword(name, size) => {
-                    output.push_str(&self.arch_codegen.generate_reserve_qword(name, size));
+                    arch.generate_reserve_qword(&mut output, name, size);
                 }
             }
+            if matches!(instruction, Instruction::Label(_)) {
+                hint = None;
+            } else if opt::is_code(instruction)
+                && let Some(likely) = hint.take()
+                && !matches!(instruction, Instruction::Jmp(_))
+                && opt::cfg::branch_target(instruction).is_some()
+            {
+                arch.generate_branch_hint(&mut output, start, likely);
+            }
         }
 
-        output
+        if let Some(from) = produced {
+            arch.generate_literal_pool(&mut output, from, true);
+        }
+        if let Some(emitted) = listing.as_deref_mut()
+            && let Some(from) = produced
+        {
+            emitted.code.push(output[from..].to_string());
+        }
+        let end = output.len();
+        if let Some((f, _)) = function {
+            output.push_str(&self.unwind_end(f));
+        }
+        if self.config.debug_source.is_some() {
+            output.push_str(&self.platform_codegen.get_line_table_end());
+        }
+        if let Some(emitted) = listing {
+            emitted.footer = output[end..].to_string();
+        }
+        write(out, &mut output)?;
+        out.flush()
+            .map_err(|err| format!("cannot write the output: {}", err))
+    }
+
+    /// Closes the unwind information of `function`, whose prologue may run
+    /// to its end.
+    fn unwind_end(&self, function: &opt::unwind::Function) -> String {
+        let mut result = String::new();
+        if function.prologue_end == function.end {
+            result.push_str(&self.platform_codegen.get_prologue_end());
+        }
+        result + &self.platform_codegen.get_unwind_end()
+    }
+
+    /// A flag reader together with the comparison it tests.
+    fn generate_fused(
+        &self,
+        out: &mut String,
+        arch: &dyn ArchCodeGen,
+        instruction: &Instruction,
+        comparison: &Comparison,
+    ) {
+        use Instruction::*;
+        match instruction {
+            SetEq(dst) | SetNe(dst) | SetLt(dst) | SetLe(dst) | SetGt(dst) | SetGe(dst)
+            | SetB(dst) | SetBe(dst) | SetA(dst) | SetAe(dst) | SetS(dst) | SetNs(dst)
+            | SetOv(dst) | SetNo(dst) | SetP(dst) | SetNp(dst) => {
+                arch.generate_compare_set(out, comparison, dst)
+            }
+            CmovEq((dst, src)) | CmovNe((dst, src)) | CmovLt((dst, src)) | CmovLe((dst, src))
+            | CmovGt((dst, src)) | CmovGe((dst, src)) | CmovB((dst, src)) | CmovBe((dst, src))
+            | CmovA((dst, src)) | CmovAe((dst, src)) | CmovS((dst, src)) | CmovNs((dst, src))
+            | CmovOv((dst, src)) | CmovNo((dst, src)) | CmovP((dst, src)) | CmovNp((dst, src)) => {
+                arch.generate_compare_cmov(out, comparison, dst, src)
+            }
+            Rcl((dst, count)) => arch.generate_compare_rcl(out, comparison, dst, count),
+            Rcr((dst, count)) => arch.generate_compare_rcr(out, comparison, dst, count),
+            _ => match opt::cfg::branch_target(instruction) {
+                Some(label) => arch.generate_compare_branch(out, comparison, label),
+                None => unreachable!("`{}` does not read the flags", instruction),
+            },
+        }
     }
 
     fn process_data_values(&self, values: &[String]) -> Vec<String> {
@@ -564,4 +1141,110 @@ impl CodeGenerator {
     pub fn get_target(&self) -> &TargetTriple {
         &self.target
     }
+
+    pub fn get_config(&self) -> &CodeGenConfig {
+        &self.config
+    }
+}
+
+/// The backend for `target`, with the instruction set `config` asks for.
+fn create_arch(target: &TargetTriple, config: &CodeGenConfig) -> Box<dyn ArchCodeGen> {
+    let mut arch = if config.enable_size_minimization {
+        create_compact_arch_codegen(&target.architecture)
+    } else {
+        create_arch_codegen(&target.architecture)
+    };
+    if config.bit_manipulation {
+        // A backend without them fails when the program is emitted.
+        let _ = arch.set_bit_manipulation();
+    }
+    if let Some(cpu) = &config.cpu {
+        let _ = arch.set_cpu(cpu);
+    }
+    if let Some(abi) = &config.abi {
+        let _ = arch.set_abi(abi);
+    }
+    if let Some(ram) = &config.ram {
+        let _ = arch.set_ram(ram);
+    }
+    arch
+}
+
+/// The names `instructions` define in `.rodata`.
+fn read_only_data(instructions: &[Instruction]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    let mut in_rodata = false;
+    for instruction in instructions {
+        match instruction {
+            Instruction::Section(section) => in_rodata = matches!(section, Section::Rodata),
+            Instruction::Label(name)
+            | Instruction::DataByte(name, _)
+            | Instruction::DataWord(name, _)
+            | Instruction::DataDword(name, _)
+            | Instruction::DataQword(name, _)
+            | Instruction::ReserveByte(name, _)
+            | Instruction::ReserveWord(name, _)
+            | Instruction::ReserveDword(name, _)
+            | Instruction::ReserveQword(name, _)
+                if in_rodata =>
+            {
+                names.insert(name.clone());
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// How often each name in `instructions` is the unscaled base of a memory
+/// operand.
+fn address_uses(instructions: &[Instruction]) -> HashMap<String, usize> {
+    let mut uses = HashMap::new();
+    for instruction in instructions.iter().filter(|i| opt::is_code(i)) {
+        for operand in opt::cfg::operands(instruction) {
+            if !opt::is_memory(&operand) {
+                continue;
+            }
+            let tokens: Vec<(&str, bool)> = opt::tokens(&operand).collect();
+            for (i, &(token, identifier)) in tokens.iter().enumerate() {
+                let scaled = |n: Option<usize>| {
+                    n.and_then(|n| tokens.get(n))
+                        .is_some_and(|(token, _)| token.contains('*'))
+                };
+                if identifier && !scaled(i.checked_sub(1)) && !scaled(Some(i + 1)) {
+                    *uses.entry(token.to_string()).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+    uses
+}
+
+/// Fails if `instruction` uses an address where position-independent code
+/// cannot: only `mov` and `lea` of an address, `load` and `store` through
+/// one, and jumps and calls to one can be made relative to the program
+/// counter.
+fn check_position_independent(pic: &Pic, instruction: &Instruction) -> Result<(), String> {
+    use Instruction::*;
+    let addressed = match instruction {
+        Mov((_, src)) if !opt::is_memory(src) => Some(src.as_str()),
+        Lea((_, src)) | Load((_, src)) => Some(src.as_str()),
+        Store((dst, _)) => Some(dst.as_str()),
+        Call(target) => Some(target.as_str()),
+        _ => opt::cfg::branch_target(instruction),
+    };
+    let addressed = addressed.filter(|operand| pic.address(operand).is_some());
+    for operand in opt::cfg::operands(instruction) {
+        if Some(operand.as_str()) == addressed {
+            continue;
+        }
+        if let Some(symbol) = opt::identifiers(&operand).find(|name| pic.addresses.contains(*name))
+        {
+            return Err(format!(
+                "`{}` addresses `{}` directly, which position-independent code cannot; `lea` it into a register first",
+                instruction, symbol
+            ));
+        }
+    }
+    Ok(())
 }
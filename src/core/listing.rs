@@ -0,0 +1,277 @@
+//! Annotated listings: every UASM source line beside the assembly it
+//! produced, followed by the sections and symbols of the program.
+//!
+//! Code is attributed to its line by the `.loc` markers the parser inserts
+//! with line information. Labels, data and other directives carry none, so
+//! they are found by their leading tokens (`loop:`, `msg db`, `global main`)
+//! instead; generated ones, such as the labels of inlined copies, are listed
+//! without a line. Code that optimization moved or copied lists its line
+//! again where it ended up. Line and unwind directives are listed without a
+//! line where they were emitted, so the assembly column is the output as is.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use super::opt::is_code;
+use super::{Instruction, Section, TargetTriple};
+
+/// Widest source column; longer lines push their assembly to the right.
+const MAX_SOURCE_WIDTH: usize = 48;
+
+/// The assembly emitted for a program, by instruction.
+#[derive(Debug, Clone, Default)]
+pub struct Emitted {
+    /// Assembly before the first instruction, such as the syntax header.
+    pub header: String,
+    /// Line and unwind directives emitted before each instruction's code.
+    pub directives: Vec<String>,
+    /// Assembly of each instruction.
+    pub code: Vec<String>,
+    /// Directives after the last instruction, closing its function.
+    pub footer: String,
+}
+
+/// A section and what it holds.
+struct SectionSummary {
+    name: String,
+    lines: HashSet<usize>,
+    assembly: usize,
+}
+
+/// A symbol the program defines or imports.
+struct Symbol {
+    name: String,
+    kind: &'static str,
+    /// `global`, `local`, or empty for imports and constants.
+    binding: &'static str,
+    section: Option<String>,
+    line: Option<usize>,
+}
+
+/// Renders the listing of `source`, whose `instructions` (after
+/// optimization) produced `emitted` for `target`.
+pub fn render(
+    source: &str,
+    target: &TargetTriple,
+    instructions: &[Instruction],
+    emitted: &Emitted,
+) -> String {
+    let lines: Vec<String> = source
+        .lines()
+        .map(|line| line.replace('\t', "    "))
+        .collect();
+    let width = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0)
+        .clamp(6, MAX_SOURCE_WIDTH);
+
+    // Every source line by its leading tokens, for what has no `.loc`.
+    let mut keyed: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, line) in lines.iter().enumerate() {
+        let code = line.split(';').next().unwrap_or_default();
+        if let Some(key) = key(code) {
+            keyed.entry(key).or_default().push(i + 1);
+        }
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "; UASM listing for {:?} {:?} ({:?})",
+        target.architecture, target.platform, target.format
+    );
+    let _ = writeln!(out, "{:>5}  {:<width$}  | assembly", "line", "source");
+    for asm in emitted.header.lines() {
+        row(&mut out, None, "", asm, width);
+    }
+
+    let mut sections: Vec<SectionSummary> = Vec::new();
+    let mut section = None;
+    let mut loc = None;
+    // The line the last assembly was attributed to, and the furthest one
+    // listed so far.
+    let (mut last, mut furthest) = (None, 0);
+    for (i, instruction) in instructions.iter().enumerate() {
+        match instruction {
+            Instruction::Loc(n) => loc = Some(*n),
+            Instruction::Section(s) => {
+                let name = s.to_string();
+                section = Some(match sections.iter().position(|s| s.name == name) {
+                    Some(index) => index,
+                    None => {
+                        sections.push(SectionSummary {
+                            name,
+                            lines: HashSet::new(),
+                            assembly: 0,
+                        });
+                        sections.len() - 1
+                    }
+                });
+            }
+            _ => {}
+        }
+        if let Some(directives) = emitted.directives.get(i) {
+            for asm in directives.lines() {
+                row(&mut out, None, "", asm, width);
+            }
+        }
+        let code = emitted.code.get(i).map(String::as_str).unwrap_or_default();
+        if code.trim().is_empty() {
+            continue;
+        }
+        let line = if is_code(instruction) {
+            loc
+        } else {
+            key(&instruction.to_string()).and_then(|key| {
+                let candidates = keyed.get(&key)?;
+                candidates
+                    .iter()
+                    .find(|&&n| n > furthest)
+                    .or_else(|| candidates.first())
+                    .copied()
+            })
+        };
+
+        let mut asm = code.lines();
+        match line {
+            Some(n) if Some(n) != last && n <= lines.len() => {
+                for skipped in furthest + 1..n {
+                    row(&mut out, Some(skipped), &lines[skipped - 1], "", width);
+                }
+                row(
+                    &mut out,
+                    Some(n),
+                    &lines[n - 1],
+                    asm.next().unwrap_or_default(),
+                    width,
+                );
+                furthest = furthest.max(n);
+                last = line;
+            }
+            _ => {}
+        }
+        for rest in asm {
+            row(&mut out, None, "", rest, width);
+        }
+
+        if let Some(index) = section {
+            let summary = &mut sections[index];
+            summary.assembly += code.lines().filter(|l| !l.trim().is_empty()).count();
+            summary.lines.extend(line);
+        }
+    }
+    for asm in emitted.footer.lines() {
+        row(&mut out, None, "", asm, width);
+    }
+    for n in furthest + 1..=lines.len() {
+        row(&mut out, Some(n), &lines[n - 1], "", width);
+    }
+
+    let _ = writeln!(out, "\n; Sections");
+    for summary in &sections {
+        let _ = writeln!(
+            out,
+            ";   {:<10} {:>6} source lines {:>6} assembly lines",
+            summary.name,
+            summary.lines.len(),
+            summary.assembly
+        );
+    }
+
+    let symbols = symbols(instructions, &keyed);
+    let _ = writeln!(out, "\n; Symbols");
+    let name_width = symbols.iter().map(|s| s.name.len()).max().unwrap_or(0);
+    for symbol in &symbols {
+        let mut entry = format!(
+            ";   {:<name_width$}  {:<8} {:<6} {:<8}",
+            symbol.name,
+            symbol.kind,
+            symbol.binding,
+            symbol.section.as_deref().unwrap_or("")
+        );
+        if let Some(n) = symbol.line {
+            let _ = write!(entry, " line {}", n);
+        }
+        let _ = writeln!(out, "{}", entry.trim_end());
+    }
+    out
+}
+
+/// One listing row: a source line, if any, and a line of assembly.
+fn row(out: &mut String, number: Option<usize>, source: &str, asm: &str, width: usize) {
+    let number = number.map(|n| n.to_string()).unwrap_or_default();
+    let row = format!("{:>5}  {:<width$}  | {}", number, source, asm);
+    let _ = writeln!(out, "{}", row.trim_end());
+}
+
+/// The first two tokens of a line of UASM, which name what it defines.
+fn key(code: &str) -> Option<String> {
+    let tokens: Vec<&str> = code
+        .split_whitespace()
+        .take(2)
+        .map(|token| token.trim_end_matches([':', ',']))
+        .collect();
+    (!tokens.is_empty()).then(|| tokens.join(" "))
+}
+
+/// The symbols `instructions` define and import, in order of appearance.
+fn symbols(instructions: &[Instruction], keyed: &HashMap<String, Vec<usize>>) -> Vec<Symbol> {
+    let mut global = HashSet::new();
+    let mut function = HashSet::new();
+    for instruction in instructions {
+        match instruction {
+            Instruction::Global(name) => {
+                global.insert(name.as_str());
+            }
+            Instruction::Function(name) => {
+                function.insert(name.as_str());
+            }
+            _ => {}
+        }
+    }
+
+    let mut symbols = Vec::new();
+    let mut section = Section::Text.to_string();
+    for instruction in instructions {
+        let (name, kind) = match instruction {
+            Instruction::Section(s) => {
+                section = s.to_string();
+                continue;
+            }
+            Instruction::Label(name) => {
+                let declared = global.contains(name.as_str()) || function.contains(name.as_str());
+                (name, if declared { "function" } else { "label" })
+            }
+            Instruction::DataByte(name, _)
+            | Instruction::DataWord(name, _)
+            | Instruction::DataDword(name, _)
+            | Instruction::DataQword(name, _) => (name, "data"),
+            Instruction::ReserveByte(name, _)
+            | Instruction::ReserveWord(name, _)
+            | Instruction::ReserveDword(name, _)
+            | Instruction::ReserveQword(name, _) => (name, "reserved"),
+            Instruction::Equ(name, _) => (name, "constant"),
+            Instruction::Extern(name) => (name, "extern"),
+            _ => continue,
+        };
+        let defined = kind != "extern" && kind != "constant";
+        let binding = match defined {
+            true if global.contains(name.as_str()) => "global",
+            true => "local",
+            false => "",
+        };
+        let line = key(&instruction.to_string())
+            .and_then(|key| keyed.get(&key))
+            .and_then(|lines| lines.first().copied());
+        symbols.push(Symbol {
+            name: name.clone(),
+            kind,
+            binding,
+            section: defined.then(|| section.clone()),
+            line,
+        });
+    }
+    symbols
+}
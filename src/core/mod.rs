@@ -4,6 +4,8 @@ use crate::{
 };
 
 pub mod codegen;
+pub mod listing;
+pub mod opt;
 pub mod parser;
 
 #[derive(Debug, Clone)]
@@ -23,6 +25,7 @@ impl TargetTriple {
             Platform::Windows => Format::COFF,
             Platform::MacOS => Format::MachO,
             Platform::DOS => Format::MZ,
+            Platform::Embedded if architecture == Architecture::Z80 => Format::Binary,
             Platform::Embedded => Format::ELF, // most toolchains emit ELF for bare metal
         };
 
@@ -346,6 +349,14 @@ pub enum Instruction {
     /// ```
     Mod((String, String)),
 
+    /// High half of a signed multiplication
+    ///
+    /// Example:
+    /// ```asm
+    /// mulh r0, r1     ; r0 = (r0 * r1) >> 64, >> 32 on 32-bit targets
+    /// ```
+    Mulh((String, String)),
+
     /// Increment
     ///
     /// Example:
@@ -1077,6 +1088,15 @@ pub enum Instruction {
     /// ```
     Global(String),
 
+    /// Declare the label of that name a function, for unwind information and
+    /// the symbol table, without exporting it
+    ///
+    /// Example:
+    /// ```asm
+    /// function helper
+    /// ```
+    Function(String),
+
     /// Import symbol from another module
     ///
     /// Example:
@@ -1095,6 +1115,62 @@ pub enum Instruction {
     /// ```
     Align(String),
 
+    /// Unroll the loop whose header label follows by n; `.unroll 1` keeps it
+    /// rolled. A hint for the optimizer that emits no code.
+    ///
+    /// Example:
+    /// ```asm
+    /// .unroll 8
+    /// loop:
+    /// ```
+    Unroll(String),
+
+    /// Always inline the function whose label follows at its call sites.
+    ///
+    /// Example:
+    /// ```asm
+    /// .inline
+    /// square:
+    /// ```
+    Inline,
+
+    /// Never inline the function whose label follows.
+    ///
+    /// Example:
+    /// ```asm
+    /// .noinline
+    /// helper:
+    /// ```
+    NoInline,
+
+    /// The conditional jump that follows is usually taken.
+    ///
+    /// Example:
+    /// ```asm
+    /// .likely
+    /// jne loop
+    /// ```
+    Likely,
+
+    /// The conditional jump that follows is rarely taken.
+    ///
+    /// Example:
+    /// ```asm
+    /// .unlikely
+    /// je error
+    /// ```
+    Unlikely,
+
+    /// The instructions that follow come from this line of the source; the
+    /// parser inserts it when line information is on (`-g`).
+    ///
+    /// Example:
+    /// ```asm
+    /// .loc 12
+    /// add r0, r1
+    /// ```
+    Loc(usize),
+
     //
     // Data definition
     //
@@ -1193,4 +1269,172 @@ pub enum Instruction {
     /// section .rodata
     /// ```
     Section(Section),
-}
\ No newline at end of file
+}
+impl std::fmt::Display for Section {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Section::Text => write!(f, ".text"),
+            Section::Data => write!(f, ".data"),
+            Section::Bss => write!(f, ".bss"),
+            Section::Rodata => write!(f, ".rodata"),
+            Section::Custom(name) => write!(f, ".{}", name),
+        }
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    /// Renders the instruction back in UASM syntax, as accepted by the parser.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Instruction::*;
+
+        let (mnemonic, operands): (&str, Vec<&str>) = match self {
+            Label(name) => return write!(f, "{}:", name),
+            Section(section) => return write!(f, "section {}", section),
+            DataByte(name, values) => return write!(f, "{} db {}", name, values.join(", ")),
+            DataWord(name, values) => return write!(f, "{} dw {}", name, values.join(", ")),
+            DataDword(name, values) => return write!(f, "{} dd {}", name, values.join(", ")),
+            DataQword(name, values) => return write!(f, "{} dq {}", name, values.join(", ")),
+            ReserveByte(name, count) => return write!(f, "{} resb {}", name, count),
+            ReserveWord(name, count) => return write!(f, "{} resw {}", name, count),
+            ReserveDword(name, count) => return write!(f, "{} resd {}", name, count),
+            ReserveQword(name, count) => return write!(f, "{} resq {}", name, count),
+            Equ(name, value) => return write!(f, "{} equ {}", name, value),
+
+            Mov((a, b)) => ("mov", vec![a, b]),
+            Lea((a, b)) => ("lea", vec![a, b]),
+            Load((a, b)) => ("load", vec![a, b]),
+            Store((a, b)) => ("store", vec![a, b]),
+            CmovEq((a, b)) => ("cmoveq", vec![a, b]),
+            CmovNe((a, b)) => ("cmovne", vec![a, b]),
+            CmovLt((a, b)) => ("cmovlt", vec![a, b]),
+            CmovLe((a, b)) => ("cmovle", vec![a, b]),
+            CmovGt((a, b)) => ("cmovgt", vec![a, b]),
+            CmovGe((a, b)) => ("cmovge", vec![a, b]),
+            CmovOv((a, b)) => ("cmovov", vec![a, b]),
+            CmovNo((a, b)) => ("cmovno", vec![a, b]),
+            CmovS((a, b)) => ("cmovs", vec![a, b]),
+            CmovNs((a, b)) => ("cmovns", vec![a, b]),
+            CmovP((a, b)) => ("cmovp", vec![a, b]),
+            CmovNp((a, b)) => ("cmovnp", vec![a, b]),
+            CmovA((a, b)) => ("cmova", vec![a, b]),
+            CmovAe((a, b)) => ("cmovae", vec![a, b]),
+            CmovB((a, b)) => ("cmovb", vec![a, b]),
+            CmovBe((a, b)) => ("cmovbe", vec![a, b]),
+            Push(a) => ("push", vec![a]),
+            Pop(a) => ("pop", vec![a]),
+            Pusha => ("pusha", vec![]),
+            Popa => ("popa", vec![]),
+            Enter((a, b)) => ("enter", vec![a, b]),
+            Leave => ("leave", vec![]),
+            Add((a, b)) => ("add", vec![a, b]),
+            Sub((a, b)) => ("sub", vec![a, b]),
+            Mul((a, b)) => ("mul", vec![a, b]),
+            Imul((a, b)) => ("imul", vec![a, b]),
+            Div((a, b)) => ("div", vec![a, b]),
+            Idiv((a, b)) => ("idiv", vec![a, b]),
+            Mod((a, b)) => ("mod", vec![a, b]),
+            Mulh((a, b)) => ("mulh", vec![a, b]),
+            Inc(a) => ("inc", vec![a]),
+            Dec(a) => ("dec", vec![a]),
+            Neg(a) => ("neg", vec![a]),
+            And((a, b)) => ("and", vec![a, b]),
+            Or((a, b)) => ("or", vec![a, b]),
+            Xor((a, b)) => ("xor", vec![a, b]),
+            Not(a) => ("not", vec![a]),
+            Andn((a, b)) => ("andn", vec![a, b]),
+            Shl((a, b)) => ("shl", vec![a, b]),
+            Shr((a, b)) => ("shr", vec![a, b]),
+            Sal((a, b)) => ("sal", vec![a, b]),
+            Sar((a, b)) => ("sar", vec![a, b]),
+            Rol((a, b)) => ("rol", vec![a, b]),
+            Ror((a, b)) => ("ror", vec![a, b]),
+            Rcl((a, b)) => ("rcl", vec![a, b]),
+            Rcr((a, b)) => ("rcr", vec![a, b]),
+            Bextr((a, b, c)) => ("bextr", vec![a, b, c]),
+            Bsf((a, b)) => ("bsf", vec![a, b]),
+            Bsr((a, b)) => ("bsr", vec![a, b]),
+            Cmp((a, b)) => ("cmp", vec![a, b]),
+            Test((a, b)) => ("test", vec![a, b]),
+            Bt((a, b)) => ("bt", vec![a, b]),
+            Btr((a, b)) => ("btr", vec![a, b]),
+            Bts((a, b)) => ("bts", vec![a, b]),
+            Btc((a, b)) => ("btc", vec![a, b]),
+            SetEq(a) => ("seteq", vec![a]),
+            SetNe(a) => ("setne", vec![a]),
+            SetLt(a) => ("setlt", vec![a]),
+            SetLe(a) => ("setle", vec![a]),
+            SetGt(a) => ("setgt", vec![a]),
+            SetGe(a) => ("setge", vec![a]),
+            SetOv(a) => ("setov", vec![a]),
+            SetNo(a) => ("setno", vec![a]),
+            SetS(a) => ("sets", vec![a]),
+            SetNs(a) => ("setns", vec![a]),
+            SetP(a) => ("setp", vec![a]),
+            SetNp(a) => ("setnp", vec![a]),
+            SetA(a) => ("seta", vec![a]),
+            SetAe(a) => ("setae", vec![a]),
+            SetB(a) => ("setb", vec![a]),
+            SetBe(a) => ("setbe", vec![a]),
+            Cmps((a, b)) => ("cmps", vec![a, b]),
+            Scas((a, b)) => ("scas", vec![a, b]),
+            Stos((a, b)) => ("stos", vec![a, b]),
+            Lods((a, b)) => ("lods", vec![a, b]),
+            Movs((a, b)) => ("movs", vec![a, b]),
+            Cbw(a) => ("cbw", vec![a]),
+            Cwd(a) => ("cwd", vec![a]),
+            Cdq(a) => ("cdq", vec![a]),
+            Cqo(a) => ("cqo", vec![a]),
+            Cwde(a) => ("cwde", vec![a]),
+            Cdqe(a) => ("cdqe", vec![a]),
+            Jmp(a) => ("jmp", vec![a]),
+            Je(a) => ("je", vec![a]),
+            Jne(a) => ("jne", vec![a]),
+            Jl(a) => ("jl", vec![a]),
+            Jle(a) => ("jle", vec![a]),
+            Jg(a) => ("jg", vec![a]),
+            Jge(a) => ("jge", vec![a]),
+            Jo(a) => ("jo", vec![a]),
+            Jno(a) => ("jno", vec![a]),
+            Js(a) => ("js", vec![a]),
+            Jns(a) => ("jns", vec![a]),
+            Jp(a) => ("jp", vec![a]),
+            Jnp(a) => ("jnp", vec![a]),
+            Ja(a) => ("ja", vec![a]),
+            Jae(a) => ("jae", vec![a]),
+            Jb(a) => ("jb", vec![a]),
+            Jbe(a) => ("jbe", vec![a]),
+            LoopEq(a) => ("loopeq", vec![a]),
+            LoopNe(a) => ("loopne", vec![a]),
+            Call(a) => ("call", vec![a]),
+            Ret => ("ret", vec![]),
+            In((a, b)) => ("in", vec![a, b]),
+            Out((a, b)) => ("out", vec![a, b]),
+            Ins((a, b)) => ("ins", vec![a, b]),
+            Outs((a, b)) => ("outs", vec![a, b]),
+            Cpuid => ("cpuid", vec![]),
+            Lfence => ("lfence", vec![]),
+            Sfence => ("sfence", vec![]),
+            Mfence => ("mfence", vec![]),
+            Prefetch(a) => ("prefetch", vec![a]),
+            Clflush(a) => ("clflush", vec![a]),
+            Clwb(a) => ("clwb", vec![a]),
+            Syscall(a) => ("syscall", vec![a]),
+            Global(a) => ("global", vec![a]),
+            Function(a) => ("function", vec![a]),
+            Extern(a) => ("extern", vec![a]),
+            Align(a) => ("align", vec![a]),
+            Unroll(a) => (".unroll", vec![a]),
+            Inline => (".inline", vec![]),
+            NoInline => (".noinline", vec![]),
+            Likely => (".likely", vec![]),
+            Unlikely => (".unlikely", vec![]),
+            Loc(line) => return write!(f, ".loc {}", line),
+        };
+
+        if operands.is_empty() {
+            write!(f, "{}", mnemonic)
+        } else {
+            write!(f, "{} {}", mnemonic, operands.join(", "))
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! Basic blocks and control-flow edges over the instruction list.
+
+use std::collections::HashMap;
+
+use super::{identifiers, is_code};
+use crate::core::{Instruction, Section};
+
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    /// Index of the first instruction (a `Label` when the block has one).
+    pub start: usize,
+    /// One past the last instruction.
+    pub end: usize,
+    pub label: Option<String>,
+    /// Whether the block lives in an executable section.
+    pub in_text: bool,
+    pub succs: Vec<usize>,
+    /// Blocks entered through a `call` in this block.
+    pub callees: Vec<usize>,
+    /// Control may leave the program's known code here (`ret`, a jump to an
+    /// external symbol, or running off the end of a section).
+    pub exits: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+    /// Block index of every label defined in an executable section.
+    pub labels: HashMap<String, usize>,
+}
+
+/// Target of a jump or conditional branch.
+pub fn branch_target(instruction: &Instruction) -> Option<&str> {
+    use Instruction::*;
+    match instruction {
+        Jmp(l) | Je(l) | Jne(l) | Jl(l) | Jle(l) | Jg(l) | Jge(l) | Jo(l) | Jno(l) | Js(l)
+        | Jns(l) | Jp(l) | Jnp(l) | Ja(l) | Jae(l) | Jb(l) | Jbe(l) | LoopEq(l) | LoopNe(l) => {
+            Some(l.as_str())
+        }
+        _ => None,
+    }
+}
+
+/// The register or memory operand an instruction writes.
+pub fn destination(instruction: &Instruction) -> Option<&str> {
+    use Instruction::*;
+    match instruction {
+        Mov((d, _)) | Lea((d, _)) | Load((d, _)) | CmovEq((d, _)) | CmovNe((d, _))
+        | CmovLt((d, _)) | CmovLe((d, _)) | CmovGt((d, _)) | CmovGe((d, _)) | CmovOv((d, _))
+        | CmovNo((d, _)) | CmovS((d, _)) | CmovNs((d, _)) | CmovP((d, _)) | CmovNp((d, _))
+        | CmovA((d, _)) | CmovAe((d, _)) | CmovB((d, _)) | CmovBe((d, _)) | Add((d, _))
+        | Sub((d, _)) | Mul((d, _)) | Imul((d, _)) | Div((d, _)) | Idiv((d, _)) | Mod((d, _))
+        | Mulh((d, _)) | And((d, _)) | Or((d, _)) | Xor((d, _)) | Andn((d, _)) | Shl((d, _))
+        | Shr((d, _)) | Sal((d, _)) | Sar((d, _)) | Rol((d, _)) | Ror((d, _)) | Rcl((d, _))
+        | Rcr((d, _)) | Btr((d, _)) | Bts((d, _)) | Btc((d, _)) | Bsf((d, _)) | Bsr((d, _))
+        | Bextr((d, _, _)) | Pop(d) | Inc(d) | Dec(d) | Neg(d) | Not(d) => Some(d),
+        _ => None,
+    }
+}
+
+/// Instructions after which control never falls through: jumps, returns and
+/// the `exit` system call.
+pub fn is_terminator(instruction: &Instruction) -> bool {
+    match instruction {
+        Instruction::Jmp(_) | Instruction::Ret => true,
+        Instruction::Syscall(name) => name.trim() == "exit",
+        _ => false,
+    }
+}
+
+/// Instructions that end a basic block.
+pub fn ends_block(instruction: &Instruction) -> bool {
+    is_terminator(instruction) || branch_target(instruction).is_some()
+}
+
+impl Cfg {
+    pub fn build(instructions: &[Instruction]) -> Cfg {
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        let mut labels = HashMap::new();
+        let mut in_text = true;
+        let mut start = 0;
+
+        let close = |blocks: &mut Vec<BasicBlock>, start: usize, end: usize, in_text: bool| {
+            if end > start {
+                let label = match &instructions[start] {
+                    Instruction::Label(name) => Some(name.clone()),
+                    _ => None,
+                };
+                blocks.push(BasicBlock {
+                    start,
+                    end,
+                    label,
+                    in_text,
+                    succs: Vec::new(),
+                    callees: Vec::new(),
+                    exits: false,
+                });
+            }
+        };
+
+        for (i, instruction) in instructions.iter().enumerate() {
+            match instruction {
+                Instruction::Label(_) | Instruction::Section(_) => {
+                    close(&mut blocks, start, i, in_text);
+                    start = i;
+                    if let Instruction::Section(section) = instruction {
+                        in_text = matches!(section, Section::Text);
+                    }
+                }
+                _ if ends_block(instruction) => {
+                    close(&mut blocks, start, i + 1, in_text);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        close(&mut blocks, start, instructions.len(), in_text);
+
+        for (b, block) in blocks.iter().enumerate() {
+            if let (Some(name), true) = (&block.label, block.in_text) {
+                labels.insert(name.clone(), b);
+            }
+        }
+
+        for b in 0..blocks.len() {
+            if !blocks[b].in_text {
+                continue;
+            }
+            let (start, end) = (blocks[b].start, blocks[b].end);
+            let mut succs = Vec::new();
+            let mut exits = false;
+
+            let last = instructions[start..end].iter().rev().find(|i| is_code(i));
+            let falls_through = !matches!(last, Some(i) if is_terminator(i));
+
+            if let Some(target) = last.and_then(branch_target) {
+                match labels.get(target) {
+                    Some(&t) => succs.push(t),
+                    None => exits = true,
+                }
+            }
+            if matches!(last, Some(Instruction::Ret)) {
+                exits = true;
+            }
+            if falls_through {
+                match blocks.get(b + 1) {
+                    Some(next)
+                        if next.in_text
+                            && !matches!(instructions[next.start], Instruction::Section(_)) =>
+                    {
+                        succs.push(b + 1)
+                    }
+                    _ => exits = true,
+                }
+            }
+
+            let callees = instructions[start..end]
+                .iter()
+                .filter_map(|i| match i {
+                    Instruction::Call(func) => labels.get(func.as_str()).copied(),
+                    _ => None,
+                })
+                .collect();
+
+            succs.dedup();
+            blocks[b].succs = succs;
+            blocks[b].callees = callees;
+            blocks[b].exits = exits;
+        }
+
+        Cfg { blocks, labels }
+    }
+
+    /// The program's entry points: `_start`, global symbols, and labels whose
+    /// address is taken by something other than a jump or call.
+    pub fn entries(&self, instructions: &[Instruction]) -> Vec<usize> {
+        let mut entries: Vec<usize> = self.labels.get("_start").copied().into_iter().collect();
+
+        for instruction in instructions {
+            let names: Vec<&str> = match instruction {
+                Instruction::Global(symbol) => vec![symbol.as_str()],
+                Instruction::Call(_) | Instruction::Label(_) | Instruction::Function(_) => {
+                    Vec::new()
+                }
+                _ if branch_target(instruction).is_some() => Vec::new(),
+                _ => referenced_names(instruction),
+            };
+            for name in names {
+                if let Some(&b) = self.labels.get(name) {
+                    entries.push(b);
+                }
+            }
+        }
+
+        entries
+    }
+
+    /// Blocks control can reach from the program's entry points.
+    pub fn reachable(&self, instructions: &[Instruction]) -> Vec<bool> {
+        let mut seen = vec![false; self.blocks.len()];
+        let mut work = self.entries(instructions);
+
+        while let Some(b) = work.pop() {
+            if std::mem::replace(&mut seen[b], true) {
+                continue;
+            }
+            work.extend(self.blocks[b].succs.iter().copied());
+            work.extend(self.blocks[b].callees.iter().copied());
+        }
+        seen
+    }
+}
+
+/// Every identifier an instruction's operands or data values mention.
+pub fn referenced_names(instruction: &Instruction) -> Vec<&str> {
+    use Instruction::*;
+    let operands: Vec<&String> = match instruction {
+        Label(_) | Section(_) | Pusha | Popa | Leave | Ret | Cpuid | Lfence | Sfence | Mfence
+        | Inline | NoInline | Likely | Unlikely | Loc(_) => Vec::new(),
+        DataByte(_, v) | DataWord(_, v) | DataDword(_, v) | DataQword(_, v) => v.iter().collect(),
+        ReserveByte(_, c) | ReserveWord(_, c) | ReserveDword(_, c) | ReserveQword(_, c) => {
+            vec![c]
+        }
+        Equ(_, v) => vec![v],
+        Bextr((a, b, c)) => vec![a, b, c],
+        Mov((a, b)) | Lea((a, b)) | Load((a, b)) | Store((a, b)) | CmovEq((a, b))
+        | CmovNe((a, b)) | CmovLt((a, b)) | CmovLe((a, b)) | CmovGt((a, b)) | CmovGe((a, b))
+        | CmovOv((a, b)) | CmovNo((a, b)) | CmovS((a, b)) | CmovNs((a, b)) | CmovP((a, b))
+        | CmovNp((a, b)) | CmovA((a, b)) | CmovAe((a, b)) | CmovB((a, b)) | CmovBe((a, b))
+        | Enter((a, b)) | Add((a, b)) | Sub((a, b)) | Mul((a, b)) | Imul((a, b)) | Div((a, b))
+        | Idiv((a, b)) | Mod((a, b)) | Mulh((a, b)) | And((a, b)) | Or((a, b)) | Xor((a, b))
+        | Andn((a, b)) | Shl((a, b)) | Shr((a, b)) | Sal((a, b)) | Sar((a, b)) | Rol((a, b))
+        | Ror((a, b)) | Rcl((a, b)) | Rcr((a, b)) | Bsf((a, b)) | Bsr((a, b)) | Cmp((a, b))
+        | Test((a, b)) | Bt((a, b)) | Btr((a, b)) | Bts((a, b)) | Btc((a, b)) | Cmps((a, b))
+        | Scas((a, b)) | Stos((a, b)) | Lods((a, b)) | Movs((a, b)) | In((a, b)) | Out((a, b))
+        | Ins((a, b)) | Outs((a, b)) => vec![a, b],
+        Push(a) | Pop(a) | Inc(a) | Dec(a) | Neg(a) | Not(a) | SetEq(a) | SetNe(a) | SetLt(a)
+        | SetLe(a) | SetGt(a) | SetGe(a) | SetOv(a) | SetNo(a) | SetS(a) | SetNs(a) | SetP(a)
+        | SetNp(a) | SetA(a) | SetAe(a) | SetB(a) | SetBe(a) | Cbw(a) | Cwd(a) | Cdq(a)
+        | Cqo(a) | Cwde(a) | Cdqe(a) | Jmp(a) | Je(a) | Jne(a) | Jl(a) | Jle(a) | Jg(a)
+        | Jge(a) | Jo(a) | Jno(a) | Js(a) | Jns(a) | Jp(a) | Jnp(a) | Ja(a) | Jae(a) | Jb(a)
+        | Jbe(a) | LoopEq(a) | LoopNe(a) | Call(a) | Prefetch(a) | Clflush(a) | Clwb(a)
+        | Syscall(a) | Global(a) | Function(a) | Extern(a) | Align(a) | Unroll(a) => vec![a],
+    };
+    operands
+        .into_iter()
+        .flat_map(|op| identifiers(op))
+        .collect()
+}
+
+/// The operands of a code instruction, for rewriting them in place. Labels,
+/// sections, data definitions and symbol directives have none.
+pub fn operands_mut(instruction: &mut Instruction) -> Vec<&mut String> {
+    use Instruction::*;
+    match instruction {
+        Label(_)
+        | Section(_)
+        | Pusha
+        | Popa
+        | Leave
+        | Ret
+        | Cpuid
+        | Lfence
+        | Sfence
+        | Mfence
+        | DataByte(_, _)
+        | DataWord(_, _)
+        | DataDword(_, _)
+        | DataQword(_, _)
+        | ReserveByte(_, _)
+        | ReserveWord(_, _)
+        | ReserveDword(_, _)
+        | ReserveQword(_, _)
+        | Equ(_, _)
+        | Global(_)
+        | Function(_)
+        | Extern(_)
+        | Align(_)
+        | Unroll(_)
+        | Inline
+        | NoInline
+        | Likely
+        | Unlikely
+        | Loc(_) => Vec::new(),
+        Bextr((a, b, c)) => vec![a, b, c],
+        Mov((a, b)) | Lea((a, b)) | Load((a, b)) | Store((a, b)) | CmovEq((a, b))
+        | CmovNe((a, b)) | CmovLt((a, b)) | CmovLe((a, b)) | CmovGt((a, b)) | CmovGe((a, b))
+        | CmovOv((a, b)) | CmovNo((a, b)) | CmovS((a, b)) | CmovNs((a, b)) | CmovP((a, b))
+        | CmovNp((a, b)) | CmovA((a, b)) | CmovAe((a, b)) | CmovB((a, b)) | CmovBe((a, b))
+        | Enter((a, b)) | Add((a, b)) | Sub((a, b)) | Mul((a, b)) | Imul((a, b)) | Div((a, b))
+        | Idiv((a, b)) | Mod((a, b)) | Mulh((a, b)) | And((a, b)) | Or((a, b)) | Xor((a, b))
+        | Andn((a, b)) | Shl((a, b)) | Shr((a, b)) | Sal((a, b)) | Sar((a, b)) | Rol((a, b))
+        | Ror((a, b)) | Rcl((a, b)) | Rcr((a, b)) | Bsf((a, b)) | Bsr((a, b)) | Cmp((a, b))
+        | Test((a, b)) | Bt((a, b)) | Btr((a, b)) | Bts((a, b)) | Btc((a, b)) | Cmps((a, b))
+        | Scas((a, b)) | Stos((a, b)) | Lods((a, b)) | Movs((a, b)) | In((a, b)) | Out((a, b))
+        | Ins((a, b)) | Outs((a, b)) => vec![a, b],
+        Push(a) | Pop(a) | Inc(a) | Dec(a) | Neg(a) | Not(a) | SetEq(a) | SetNe(a) | SetLt(a)
+        | SetLe(a) | SetGt(a) | SetGe(a) | SetOv(a) | SetNo(a) | SetS(a) | SetNs(a) | SetP(a)
+        | SetNp(a) | SetA(a) | SetAe(a) | SetB(a) | SetBe(a) | Cbw(a) | Cwd(a) | Cdq(a)
+        | Cqo(a) | Cwde(a) | Cdqe(a) | Jmp(a) | Je(a) | Jne(a) | Jl(a) | Jle(a) | Jg(a)
+        | Jge(a) | Jo(a) | Jno(a) | Js(a) | Jns(a) | Jp(a) | Jnp(a) | Ja(a) | Jae(a) | Jb(a)
+        | Jbe(a) | LoopEq(a) | LoopNe(a) | Call(a) | Prefetch(a) | Clflush(a) | Clwb(a)
+        | Syscall(a) => vec![a],
+    }
+}
+
+/// Copies of the operands `operands_mut` exposes.
+pub fn operands(instruction: &Instruction) -> Vec<String> {
+    let mut copy = instruction.clone();
+    operands_mut(&mut copy)
+        .into_iter()
+        .map(|s| s.clone())
+        .collect()
+}
@@ -0,0 +1,379 @@
+//! Common subexpression elimination and copy propagation.
+//!
+//! Local value numbering over each basic block. Every register holds a value
+//! number; `lea`, `load`, arithmetic and copies are keyed by the value numbers
+//! of their operands, so a computation whose value some register already holds
+//! becomes a `mov` from that register, or disappears when the destination
+//! holds it already. Loads are keyed by their address and forgotten when
+//! memory may change; a `store` makes its value available to later loads of
+//! the same address.
+//!
+//! Reads of a copy are rewritten to read the register the value was first
+//! computed in, which leaves `mov` chains dead for `dce` to remove. Operands
+//! that mention the stack or frame pointer are left alone, since `push` and
+//! `pop` move them without writing them.
+
+use std::collections::HashMap;
+
+use super::cfg::Cfg;
+use super::liveness::{Liveness, effects, step};
+use super::{FLAGS, OptReport, Registers, is_memory, parse_immediate, tokens};
+use crate::core::Instruction;
+
+const PASS: &str = "cse";
+
+pub fn run(
+    instructions: Vec<Instruction>,
+    regs: &Registers,
+    clobbers: &dyn Fn(&Instruction) -> Vec<String>,
+    report: &mut OptReport,
+) -> Vec<Instruction> {
+    let cfg = Cfg::build(&instructions);
+    let liveness = Liveness::analyze(&instructions, &cfg, regs);
+    let mut rewritten: Vec<Option<Instruction>> = instructions.iter().cloned().map(Some).collect();
+
+    for (b, block) in cfg.blocks.iter().enumerate() {
+        if !block.in_text {
+            continue;
+        }
+        let mut flags_after = vec![false; block.end - block.start];
+        let mut live = liveness.live_out[b].clone();
+        for i in (block.start..block.end).rev() {
+            flags_after[i - block.start] = live.contains(FLAGS);
+            step(&mut live, &effects(&instructions[i], regs), regs);
+        }
+
+        let mut table = Table::new(regs);
+        for i in block.start..block.end {
+            let original = &instructions[i];
+            let mut instruction = original.clone();
+            table.propagate(&mut instruction);
+
+            let writes_flags = effects(&instruction, regs).defs.iter().any(|d| d == FLAGS);
+            let flags_free = !writes_flags || !flags_after[i - block.start];
+            let result = match table.value(&instruction) {
+                Some((dst, value)) if flags_free => {
+                    if table.holds(&dst, value) {
+                        None
+                    } else if let Some(holder) = table.holder(value, &instruction) {
+                        let name = table.name(&holder);
+                        let dst = destination(&instruction).to_string();
+                        Some(Instruction::Mov((dst, name)))
+                    } else {
+                        Some(instruction.clone())
+                    }
+                }
+                _ => Some(instruction.clone()),
+            };
+
+            match &result {
+                None => report.note(PASS, format!("removed redundant `{}`", original)),
+                Some(new) if new.to_string() != original.to_string() => {
+                    report.note(PASS, format!("replaced `{}` with `{}`", original, new))
+                }
+                _ => {}
+            }
+            table.apply(&instruction, clobbers);
+            rewritten[i] = result;
+        }
+    }
+
+    rewritten.into_iter().flatten().collect()
+}
+
+/// The destination operand of an instruction `Table::value` understands.
+fn destination(instruction: &Instruction) -> &str {
+    use Instruction::*;
+    match instruction {
+        Mov((d, _)) | Lea((d, _)) | Load((d, _)) | Add((d, _)) | Sub((d, _)) | Mul((d, _))
+        | Imul((d, _)) | And((d, _)) | Or((d, _)) | Xor((d, _)) | Shl((d, _)) | Shr((d, _))
+        | Sal((d, _)) | Sar((d, _)) | Inc(d) | Dec(d) | Neg(d) | Not(d) => d.trim(),
+        _ => "",
+    }
+}
+
+/// Value numbers for one basic block.
+struct Table<'a> {
+    regs: &'a Registers,
+    next: u32,
+    /// Value held by each physical register.
+    values: HashMap<String, u32>,
+    /// Registers holding each value, oldest first.
+    holders: HashMap<u32, Vec<String>>,
+    /// A name to emit for each physical register.
+    names: HashMap<String, String>,
+    /// Values of computations, keyed by operation and operand values.
+    exprs: HashMap<String, u32>,
+    /// Values in memory, keyed by address, and whether the address is
+    /// computed from registers.
+    memory: HashMap<String, (u32, bool)>,
+}
+
+impl<'a> Table<'a> {
+    fn new(regs: &'a Registers) -> Self {
+        Table {
+            regs,
+            next: 0,
+            values: HashMap::new(),
+            holders: HashMap::new(),
+            names: HashMap::new(),
+            exprs: HashMap::new(),
+            memory: HashMap::new(),
+        }
+    }
+
+    fn fresh(&mut self) -> u32 {
+        self.next += 1;
+        self.next
+    }
+
+    fn is_stack(&self, phys: &str) -> bool {
+        ["sp", "sb"]
+            .iter()
+            .any(|alias| self.regs.resolve(alias) == Some(phys))
+    }
+
+    /// Value of a register, numbering it on first sight.
+    fn register(&mut self, name: &str) -> Option<u32> {
+        let phys = self.regs.resolve(name)?.to_string();
+        if self.is_stack(&phys) {
+            return None;
+        }
+        self.names.entry(phys.clone()).or_insert(name.to_string());
+        if let Some(&value) = self.values.get(&phys) {
+            return Some(value);
+        }
+        let value = self.fresh();
+        self.values.insert(phys.clone(), value);
+        self.holders.entry(value).or_default().push(phys);
+        Some(value)
+    }
+
+    /// An operand in terms of values: registers become `vN`, immediates
+    /// their value. `None` when it mentions the stack pointer.
+    fn key(&mut self, operand: &str) -> Option<String> {
+        let operand = operand.trim();
+        if let Some(value) = parse_immediate(operand) {
+            return Some(format!("#{}", value));
+        }
+        let mut key = String::with_capacity(operand.len());
+        for (token, is_name) in tokens(operand) {
+            if is_name && self.regs.resolve(token).is_some() {
+                key.push_str(&format!("v{}", self.register(token)?));
+            } else {
+                key.push_str(token);
+            }
+        }
+        Some(key)
+    }
+
+    fn holds(&self, phys: &str, value: u32) -> bool {
+        self.values.get(phys) == Some(&value)
+    }
+
+    /// The oldest register holding `value`, other than the destination of
+    /// `instruction`.
+    fn holder(&self, value: u32, instruction: &Instruction) -> Option<String> {
+        let dst = self.regs.as_destination(destination(instruction));
+        self.holders
+            .get(&value)?
+            .iter()
+            .find(|h| Some(*h) != dst.as_ref())
+            .cloned()
+    }
+
+    fn name(&self, phys: &str) -> String {
+        self.names
+            .get(phys)
+            .cloned()
+            .unwrap_or_else(|| phys.to_string())
+    }
+
+    /// Rewrites the operands `instruction` only reads to use the oldest
+    /// register holding the same value.
+    fn propagate(&mut self, instruction: &mut Instruction) {
+        use Instruction::*;
+        let operands: Vec<&mut String> =
+            match instruction {
+                Mov((_, s)) | Lea((_, s)) | Load((_, s)) => vec![s],
+                Add((d, s)) | Sub((d, s)) | And((d, s)) | Or((d, s)) | Xor((d, s)) => {
+                    if is_memory(d) { vec![d, s] } else { vec![s] }
+                }
+                Store((d, s)) | Cmp((d, s)) | Test((d, s)) => vec![d, s],
+                Push(s) => vec![s],
+                _ => return,
+            };
+        for operand in operands {
+            if self
+                .regs
+                .in_operand(operand)
+                .iter()
+                .any(|r| self.is_stack(r))
+            {
+                continue;
+            }
+            let mut new = String::with_capacity(operand.len());
+            for (token, is_name) in tokens(operand) {
+                let replacement = if is_name && self.regs.resolve(token).is_some() {
+                    self.register(token)
+                        .and_then(|value| self.holders.get(&value)?.first().cloned())
+                        .filter(|holder| self.regs.resolve(token) != Some(holder.as_str()))
+                        .map(|holder| self.name(&holder))
+                } else {
+                    None
+                };
+                new.push_str(replacement.as_deref().unwrap_or(token));
+            }
+            *operand = new;
+        }
+    }
+
+    /// The register an instruction writes and the value it computes, for
+    /// instructions whose result depends only on their operands.
+    fn value(&mut self, instruction: &Instruction) -> Option<(String, u32)> {
+        use Instruction::*;
+        let dst = self.regs.as_destination(destination(instruction))?;
+        if self.is_stack(&dst) {
+            return None;
+        }
+        let (op, a, b) = match instruction {
+            Mov((_, s)) if !is_memory(s) => {
+                let key = self.key(s)?;
+                return match self.regs.resolve(s.trim()) {
+                    Some(_) => Some((dst, self.register(s.trim())?)),
+                    None => Some((dst, self.expression(format!("const {}", key)))),
+                };
+            }
+            Lea((_, s)) => ("lea", self.key(s)?, String::new()),
+            Load((_, s)) => {
+                let address = self.key(s)?;
+                let value = match self.memory.get(&address) {
+                    Some(&(value, _)) => value,
+                    None => self.fresh(),
+                };
+                return Some((dst, value));
+            }
+            Add((d, s)) | Mul((d, s)) | Imul((d, s)) | And((d, s)) | Or((d, s)) | Xor((d, s))
+                if !is_memory(s) =>
+            {
+                let (mut a, mut b) = (self.key(d)?, self.key(s)?);
+                if a > b {
+                    std::mem::swap(&mut a, &mut b);
+                }
+                let op = match instruction {
+                    Add(_) => "add",
+                    Mul(_) | Imul(_) => "mul",
+                    And(_) => "and",
+                    Or(_) => "or",
+                    _ => "xor",
+                };
+                (op, a, b)
+            }
+            Sub((d, s)) | Shl((d, s)) | Sal((d, s)) | Shr((d, s)) | Sar((d, s))
+                if !is_memory(s) =>
+            {
+                let op = match instruction {
+                    Sub(_) => "sub",
+                    Shl(_) | Sal(_) => "shl",
+                    Shr(_) => "shr",
+                    _ => "sar",
+                };
+                (op, self.key(d)?, self.key(s)?)
+            }
+            Inc(d) => ("add", self.key(d)?, "#1".to_string()),
+            Dec(d) => ("sub", self.key(d)?, "#1".to_string()),
+            Neg(d) => ("neg", self.key(d)?, String::new()),
+            Not(d) => ("not", self.key(d)?, String::new()),
+            _ => return None,
+        };
+        let value = self.expression(format!("{} {} {}", op, a, b));
+        Some((dst, value))
+    }
+
+    fn expression(&mut self, key: String) -> u32 {
+        if let Some(&value) = self.exprs.get(&key) {
+            return value;
+        }
+        let value = self.fresh();
+        self.exprs.insert(key, value);
+        value
+    }
+
+    fn assign(&mut self, phys: String, value: u32) {
+        if let Some(old) = self.values.insert(phys.clone(), value)
+            && let Some(holders) = self.holders.get_mut(&old)
+        {
+            holders.retain(|h| *h != phys);
+        }
+        self.holders.entry(value).or_default().push(phys);
+    }
+
+    /// Records what `instruction` leaves in registers and memory.
+    fn apply(&mut self, instruction: &Instruction, clobbers: &dyn Fn(&Instruction) -> Vec<String>) {
+        use Instruction::*;
+        let e = effects(instruction, self.regs);
+        if e.barrier {
+            *self = Table {
+                next: self.next,
+                ..Table::new(self.regs)
+            };
+            return;
+        }
+
+        if let Some((dst, value)) = self.value(instruction) {
+            if let Load((_, s)) = instruction
+                && let Some(address) = self.key(s)
+            {
+                let based = !self.regs.in_operand(s).is_empty();
+                self.memory.insert(address, (value, based));
+            }
+            let name = destination(instruction).to_string();
+            self.names.insert(dst.clone(), name);
+            self.assign(dst, value);
+        } else {
+            for def in e.defs.iter().filter(|d| *d != FLAGS) {
+                let value = self.fresh();
+                self.assign(def.clone(), value);
+            }
+        }
+        for clobbered in clobbers(instruction) {
+            if let Some(phys) = self.regs.resolve(&clobbered).map(str::to_string) {
+                let value = self.fresh();
+                self.assign(phys, value);
+            }
+        }
+
+        match instruction {
+            Store((d, s)) => {
+                let address = self.key(d);
+                let based = !self.regs.in_operand(d).is_empty();
+                if based {
+                    self.memory.clear();
+                } else {
+                    // Distinct symbols do not overlap; addresses computed
+                    // from registers may point anywhere.
+                    self.memory.retain(|_, &mut (_, based)| !based);
+                    if let Some(address) = &address {
+                        self.memory.remove(address);
+                    }
+                }
+                let value = if self.regs.resolve(s.trim()).is_some() {
+                    self.register(s.trim())
+                } else {
+                    self.key(s)
+                        .map(|key| self.expression(format!("const {}", key)))
+                };
+                if let (Some(address), Some(value)) = (address, value) {
+                    self.memory.insert(address, (value, based));
+                }
+            }
+            Push(_) | Pop(_) | Enter(_) | Leave => {
+                self.memory.retain(|_, &mut (_, based)| !based);
+            }
+            _ if e.side_effect && !matches!(instruction, Div(_) | Idiv(_) | Mod(_)) => {
+                self.memory.clear();
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Dead code elimination.
+//!
+//! Runs in two stages:
+//! - blocks no entry point (`_start`, a global symbol or a label whose
+//!   address is taken) can reach are dropped, which covers code after an
+//!   unconditional `jmp`/`ret` or `syscall exit` up to the next referenced
+//!   label as well as non-global functions nothing calls;
+//! - register writes whose value is never read are dropped, using liveness
+//!   on the physical registers the backend will emit.
+
+use std::collections::HashSet;
+
+use super::cfg::{Cfg, branch_target, referenced_names};
+use super::liveness::{Liveness, effects, step};
+use super::{OptReport, Registers, is_code};
+use crate::core::Instruction;
+
+const PASS: &str = "dce";
+
+pub fn run(
+    instructions: Vec<Instruction>,
+    regs: &Registers,
+    report: &mut OptReport,
+) -> Vec<Instruction> {
+    let instructions = remove_unreachable(instructions, report);
+    remove_dead_writes(instructions, regs, report)
+}
+
+fn remove_unreachable(instructions: Vec<Instruction>, report: &mut OptReport) -> Vec<Instruction> {
+    let cfg = Cfg::build(&instructions);
+    let reachable = cfg.reachable(&instructions);
+
+    let mut referenced: HashSet<&str> = HashSet::new();
+    for instruction in &instructions {
+        referenced.extend(referenced_names(instruction));
+    }
+
+    let mut keep = vec![true; instructions.len()];
+    let mut b = 0;
+    while b < cfg.blocks.len() {
+        if !cfg.blocks[b].in_text || reachable[b] {
+            b += 1;
+            continue;
+        }
+
+        // Group dead blocks up to the next label into one report line.
+        let first = &cfg.blocks[b];
+        let first_index = b;
+        let mut removed = 0;
+        while b < cfg.blocks.len()
+            && cfg.blocks[b].in_text
+            && !reachable[b]
+            && (b == first_index || cfg.blocks[b].label.is_none())
+        {
+            let block = &cfg.blocks[b];
+            for i in block.start..block.end {
+                if is_code(&instructions[i]) || matches!(instructions[i], Instruction::Label(_)) {
+                    keep[i] = false;
+                    removed += usize::from(is_code(&instructions[i]));
+                }
+            }
+            b += 1;
+        }
+        if removed == 0 {
+            continue;
+        }
+
+        let message = match &first.label {
+            Some(name) if !referenced.contains(name.as_str()) => {
+                format!(
+                    "removed unreferenced function `{}` ({} instructions)",
+                    name, removed
+                )
+            }
+            Some(name) => format!(
+                "removed unreachable block `{}` ({} instructions)",
+                name, removed
+            ),
+            None => {
+                let after = instructions[..first.start]
+                    .iter()
+                    .rev()
+                    .find(|i| is_code(i))
+                    .map(|i| format!(" after `{}`", i))
+                    .unwrap_or_default();
+                format!("removed {} unreachable instructions{}", removed, after)
+            }
+        };
+        report.note(PASS, message);
+    }
+
+    retain(instructions, &keep)
+}
+
+fn remove_dead_writes(
+    mut instructions: Vec<Instruction>,
+    regs: &Registers,
+    report: &mut OptReport,
+) -> Vec<Instruction> {
+    loop {
+        let cfg = Cfg::build(&instructions);
+        let liveness = Liveness::analyze(&instructions, &cfg, regs);
+        let mut keep = vec![true; instructions.len()];
+        let mut removed = 0;
+
+        for (b, block) in cfg.blocks.iter().enumerate() {
+            if !block.in_text {
+                continue;
+            }
+            let mut live = liveness.live_out[b].clone();
+            for i in (block.start..block.end).rev() {
+                let e = effects(&instructions[i], regs);
+                let dead = is_code(&instructions[i])
+                    && branch_target(&instructions[i]).is_none()
+                    && !e.side_effect
+                    && !e.barrier
+                    && !e.defs.is_empty()
+                    && e.defs.iter().all(|d| !live.contains(d));
+
+                if dead {
+                    keep[i] = false;
+                    removed += 1;
+                    report.note(PASS, format!("removed dead write `{}`", instructions[i]));
+                } else {
+                    step(&mut live, &e, regs);
+                }
+            }
+        }
+
+        if removed == 0 {
+            return instructions;
+        }
+        instructions = retain(instructions, &keep);
+    }
+}
+
+fn retain(instructions: Vec<Instruction>, keep: &[bool]) -> Vec<Instruction> {
+    instructions
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(instruction, &keep)| keep.then_some(instruction))
+        .collect()
+}
@@ -0,0 +1,693 @@
+//! Condition flags on targets that have none.
+//!
+//! UASM follows x86: `cmp`, `test` and arithmetic set flags that a later
+//! `jCC`, `setCC` or `cmovCC` reads. RISC-V compares inside its branches
+//! instead (`blt a0, a1, label`), so every flag reader is paired here with
+//! the instruction that set its flags and the backend emits both as one
+//! comparison. The `cmp` or `test` itself then emits nothing.
+//!
+//! A reader is paired with the last flag setter before it in its block, or
+//! in the block that falls into it when that is its only way in. After
+//! `test a, b` it compares `a & b` with zero, and after `and`, `or`, `xor` or
+//! `andn` the result, which is all those flags describe. Other arithmetic
+//! leaves only the zero and sign flags comparable with its result, so those
+//! readers must test equality or sign. The parity of a result's low byte is
+//! read the same way, and so is the overflow of `add`, `sub`, `inc` and
+//! `dec`, which is worked out from the result and the operand.
+//!
+//! `bt a, n` leaves bit `n` of `a` in the carry, for `b` (`c`) and `ae`
+//! (`nc`) readers, and for `s` and `ns` too: x86 leaves the sign undefined,
+//! and reading the bit is what programs mean by it. A rotate leaves the bit
+//! it moved last there, and `rcl` and `rcr` by a constant rotate through
+//! the carry, which is then kept in a register.
+//!
+//! Backends that cannot compare all of these themselves get the rest from
+//! `expand`, which works each value out into a register ahead of its reader
+//! and turns the rotates into shifts.
+//!
+//! Anything else is an error: a reader with no setter in reach, a setter
+//! whose operands change before the reader, memory operands, and conditions
+//! with no native form (the overflow of `cmp`, say, or the parity of a
+//! difference it does not keep; `loope`/`loopne` read the zero flag too).
+
+use super::cfg::{Cfg, branch_target, operands, referenced_names};
+use super::liveness::effects;
+use super::{FLAGS, Registers, is_memory, parse_immediate, virtual_index};
+use crate::arch::{Comparison, Condition, Tested};
+use crate::core::Instruction;
+
+/// How the emitter treats an instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lowered {
+    /// Emitted as usual.
+    Keep,
+    /// Not emitted: a `cmp` or `test`, whose readers compare for
+    /// themselves, or a rotate through the carry by nothing.
+    Drop,
+    /// A flag reader, emitted together with the comparison it tests.
+    Read(Comparison),
+}
+
+/// The flag a reader tests.
+#[derive(Debug, Clone, Copy)]
+enum Flag {
+    Condition(Condition),
+    /// The overflow flag, set or clear.
+    Overflow(bool),
+    /// The parity flag, set or clear.
+    Parity(bool),
+}
+
+/// What the flags hold at some point.
+enum Flags<'a> {
+    /// Nothing in reach set them.
+    Unknown,
+    /// `cmp a, b` or `test a, b`.
+    Compared {
+        setter: &'a Instruction,
+        a: &'a str,
+        b: &'a str,
+        mask: bool,
+    },
+    /// The result of arithmetic on `dst`; `all` when every condition reads it
+    /// like a comparison with zero, as after the logical instructions.
+    /// `overflow` tells the overflow from `dst` and the operand `setter`
+    /// took, until that operand changes.
+    Result {
+        setter: &'a Instruction,
+        dst: &'a str,
+        all: bool,
+        overflow: Option<(Tested, &'a str)>,
+    },
+    /// The carry is set when `carry` holds.
+    Carry {
+        setter: &'a Instruction,
+        carry: Comparison,
+    },
+    /// Set by `setter`, in a way no condition can be compared from.
+    Opaque(&'a Instruction),
+    /// `setter`'s operands changed at `by`.
+    Stale {
+        setter: &'a Instruction,
+        by: &'a Instruction,
+    },
+}
+
+pub fn lower(instructions: &[Instruction], regs: &Registers) -> Result<Vec<Lowered>, String> {
+    let cfg = Cfg::build(instructions);
+    let mut preds = vec![Vec::new(); cfg.blocks.len()];
+    for (b, block) in cfg.blocks.iter().enumerate() {
+        for &s in &block.succs {
+            preds[s].push(b);
+        }
+    }
+
+    let mut lowered = vec![Lowered::Keep; instructions.len()];
+    let mut flags = Flags::Unknown;
+    for (b, block) in cfg.blocks.iter().enumerate() {
+        if !block.in_text {
+            continue;
+        }
+        let falls_in = b > 0 && !preds[b].is_empty() && preds[b].iter().all(|&p| p == b - 1);
+        if !falls_in {
+            flags = Flags::Unknown;
+        }
+        for i in block.start..block.end {
+            let instruction = &instructions[i];
+            let e = effects(instruction, regs);
+            if let Some(flag) = flag(instruction) {
+                lowered[i] = Lowered::Read(read(instruction, flag, &flags)?);
+            } else if let Instruction::Rcl((dst, count)) | Instruction::Rcr((dst, count)) =
+                instruction
+            {
+                if is_memory(dst) {
+                    return Err(format!(
+                        "`{}` rotates memory through the carry; load it into a register first",
+                        instruction
+                    ));
+                }
+                match parse_immediate(count) {
+                    // x86 takes the count modulo 64, and a rotate by nothing
+                    // leaves the flags alone.
+                    Some(count) if count & 63 == 0 => {
+                        lowered[i] = Lowered::Drop;
+                        continue;
+                    }
+                    Some(_) => {
+                        let carry = Flag::Condition(Condition::B);
+                        lowered[i] = Lowered::Read(read(instruction, carry, &flags)?);
+                    }
+                    None => {
+                        return Err(format!(
+                            "`{}` rotates through the carry by a count in a register, which has \
+                             no native form on a target without condition flags",
+                            instruction
+                        ));
+                    }
+                }
+            } else if matches!(instruction, Instruction::LoopEq(_) | Instruction::LoopNe(_))
+                || e.uses.iter().any(|u| u == FLAGS) && !e.defs.iter().any(|d| d == FLAGS)
+            {
+                return Err(format!(
+                    "`{}` has no native form on a target without condition flags",
+                    instruction
+                ));
+            }
+            let changes =
+                |operand: &str| regs.in_operand(operand).iter().any(|r| e.defs.contains(r));
+            let changed = match &mut flags {
+                Flags::Compared { a, b, .. } => changes(a) || changes(b),
+                Flags::Result { dst, overflow, .. } => {
+                    if overflow.is_some_and(|(_, src)| changes(src)) {
+                        *overflow = None;
+                    }
+                    changes(dst)
+                }
+                Flags::Carry { carry, .. } => changes(&carry.a) || changes(&carry.b),
+                _ => false,
+            };
+            if let Flags::Compared { setter, .. }
+            | Flags::Result { setter, .. }
+            | Flags::Carry { setter, .. } = flags
+                && (changed || e.barrier)
+            {
+                flags = Flags::Stale {
+                    setter,
+                    by: instruction,
+                };
+            }
+            if e.defs.iter().any(|d| d == FLAGS) || e.barrier {
+                flags = setter(instruction, regs);
+                if matches!(instruction, Instruction::Cmp(_) | Instruction::Test(_)) {
+                    lowered[i] = Lowered::Drop;
+                }
+            }
+        }
+    }
+    Ok(lowered)
+}
+
+/// Works out the comparisons a target cannot test itself, for the targets
+/// without condition flags. `compares` tells which kinds its backend takes,
+/// `Tested::Carry` standing for `rcl` and `rcr` too, and `width` is its
+/// register width in bits.
+///
+/// The value any other comparison tests goes into a fresh register ahead of
+/// its reader, which then compares that with zero. A rotate through the
+/// carry becomes shifts, with the carry it leaves in a register of its own.
+/// Readers after such code compare again, since the code changed what they
+/// would have read. A program `lower` rejects comes back as it was, for the
+/// emitter to report.
+pub fn expand(
+    instructions: Vec<Instruction>,
+    regs: &Registers,
+    width: u32,
+    compares: &dyn Fn(Tested) -> bool,
+) -> Vec<Instruction> {
+    let Ok(lowered) = lower(&instructions, regs) else {
+        return instructions;
+    };
+    let rotates = |instruction: &Instruction| {
+        matches!(instruction, Instruction::Rcl(_) | Instruction::Rcr(_)) && !compares(Tested::Carry)
+    };
+    let needed = instructions
+        .iter()
+        .zip(&lowered)
+        .any(|(instruction, lowered)| match lowered {
+            Lowered::Read(comparison) => !compares(comparison.tested) || rotates(instruction),
+            _ => false,
+        });
+    if !needed {
+        return instructions;
+    }
+
+    let next = instructions
+        .iter()
+        .flat_map(referenced_names)
+        .filter_map(virtual_index)
+        .chain(regs.virtuals().iter().filter_map(|v| virtual_index(v)))
+        .max()
+        .map_or(0, |n| n + 1);
+    let names = Names {
+        value: format!("r{}", next),
+        spare: format!("r{}", next + 1),
+        carry_in: format!("r{}", next + 2),
+        carry: format!("r{}", next + 3),
+    };
+
+    let mut out = Vec::with_capacity(instructions.len());
+    // Whether code put in since the last flag setter changed the flags.
+    let mut changed = false;
+    for (instruction, lowered) in instructions.iter().zip(&lowered) {
+        if let Lowered::Read(comparison) = lowered {
+            let derived = !compares(comparison.tested);
+            if rotates(instruction) {
+                // Only `b` and `ae` read the carry from its setter, so the
+                // carry taken in is compared for afresh.
+                names.retest(comparison, &mut out);
+                out.push(set(comparison.condition, &names.carry_in));
+                names.rotate(instruction, width, &mut out);
+                changed = true;
+                continue;
+            }
+            if changed || derived {
+                names.retest(comparison, &mut out);
+                out.push(reread(instruction, comparison.condition));
+                changed = true;
+                continue;
+            }
+        }
+        // A rotate through the carry by nothing leaves the flags alone.
+        let e = effects(instruction, regs);
+        let unmoved = matches!(
+            (instruction, lowered),
+            (Instruction::Rcl(_) | Instruction::Rcr(_), Lowered::Drop)
+        );
+        if (e.defs.iter().any(|d| d == FLAGS) || e.barrier) && !unmoved {
+            changed = false;
+        }
+        out.push(instruction.clone());
+    }
+    out
+}
+
+/// The registers `expand` works in.
+struct Names {
+    /// The value a derived comparison tests.
+    value: String,
+    spare: String,
+    /// The carry a rotate takes in.
+    carry_in: String,
+    /// The carry the last rotate left.
+    carry: String,
+}
+
+impl Names {
+    /// Sets the flags again so that a reader of `comparison.condition`
+    /// tests `comparison`.
+    fn retest(&self, comparison: &Comparison, out: &mut Vec<Instruction>) {
+        use Instruction::*;
+        let (a, b) = (comparison.a.as_str(), comparison.b.as_str());
+        let value = self.value.as_str();
+        let spare = self.spare.as_str();
+        let pair = |x: &str, y: &str| (x.to_string(), y.to_string());
+        match comparison.tested {
+            Tested::Operands => out.push(Cmp(pair(a, b))),
+            Tested::Mask => out.push(Test(pair(a, b))),
+            Tested::Carry => out.push(Cmp(pair(&self.carry, "0"))),
+            Tested::Bit => out.extend([
+                Mov(pair(value, a)),
+                Shr(pair(value, b)),
+                And(pair(value, "1")),
+                Cmp(pair(value, "0")),
+            ]),
+            // `a` is the sum and `b` the addend, so `a - b` is what was
+            // added to: the sum overflowed when its sign differs from both.
+            Tested::Sum => out.extend([
+                Mov(pair(value, a)),
+                Sub(pair(value, b)),
+                Xor(pair(value, a)),
+                Mov(pair(spare, b)),
+                Xor(pair(spare, a)),
+                And(pair(value, spare)),
+                Cmp(pair(value, "0")),
+            ]),
+            // `a + b` is what `b` was taken from, and the difference
+            // overflowed when that and `b` differ in sign, as do that and `a`.
+            Tested::Difference => out.extend([
+                Mov(pair(value, a)),
+                Add(pair(value, b)),
+                Mov(pair(spare, value)),
+                Xor(pair(value, b)),
+                Xor(pair(spare, a)),
+                And(pair(value, spare)),
+                Cmp(pair(value, "0")),
+            ]),
+            // The parity flag is set when the low byte has an even number of
+            // ones, which leaves zero here.
+            Tested::Parity => {
+                out.push(Mov(pair(value, a)));
+                if a != b {
+                    out.push(And(pair(value, b)));
+                }
+                out.push(And(pair(value, "255")));
+                for shift in ["4", "2", "1"] {
+                    out.extend([
+                        Mov(pair(spare, value)),
+                        Shr(pair(spare, shift)),
+                        Xor(pair(value, spare)),
+                    ]);
+                }
+                out.extend([And(pair(value, "1")), Cmp(pair(value, "0"))]);
+            }
+        }
+    }
+
+    /// `rcl` or `rcr` by a constant, through the carry in `carry_in`, leaving
+    /// the bit it rotates out in `carry`.
+    fn rotate(&self, instruction: &Instruction, width: u32, out: &mut Vec<Instruction>) {
+        use Instruction::*;
+        let (Rcl((dst, count)) | Rcr((dst, count))) = instruction else {
+            unreachable!("only rotates through the carry are expanded")
+        };
+        let pair = |x: &str, y: &str| (x.to_string(), y.to_string());
+        let by = |x: &str, n: i64| (x.to_string(), n.to_string());
+        let (value, spare) = (self.value.as_str(), self.spare.as_str());
+        let carry_in = self.carry_in.as_str();
+        // x86 takes the count modulo 64 for 64-bit operands and modulo 32
+        // for narrower ones.
+        let w = width as i64;
+        let n = parse_immediate(count).expect("`lower` takes constant counts only") & (w - 1);
+        if n == 0 {
+            out.push(Mov(pair(&self.carry, carry_in)));
+            return;
+        }
+        // Rotating `w + 1` bits by `n`: the bit `n` places short of the end
+        // goes to the carry, the carry to the bit after the shifted part,
+        // and the bits before it wrap around.
+        let left = matches!(instruction, Rcl(_));
+        let (out_bit, carry_at) = if left { (w - n, n - 1) } else { (n - 1, w - n) };
+        out.push(Mov(pair(value, dst)));
+        if out_bit > 0 {
+            out.push(Shr(by(value, out_bit)));
+        }
+        out.push(And(pair(value, "1")));
+        if n > 1 {
+            out.push(Mov(pair(spare, dst)));
+            out.push(if left {
+                Shr(by(spare, w + 1 - n))
+            } else {
+                Shl(by(spare, w + 1 - n))
+            });
+        }
+        out.push(if left {
+            Shl(by(dst, n))
+        } else {
+            Shr(by(dst, n))
+        });
+        if n > 1 {
+            out.push(Or(pair(dst, spare)));
+        }
+        if carry_at > 0 {
+            out.push(Shl(by(carry_in, carry_at)));
+        }
+        out.push(Or(pair(dst, carry_in)));
+        out.push(Mov(pair(&self.carry, value)));
+    }
+}
+
+/// `instruction`, a flag reader, testing `condition` instead.
+fn reread(instruction: &Instruction, condition: Condition) -> Instruction {
+    match (branch_target(instruction), operands(instruction).as_slice()) {
+        (Some(label), _) => jump(condition, label),
+        (None, [dst]) => set(condition, dst),
+        (None, [dst, src]) => cmov(condition, dst, src),
+        _ => unreachable!("flag readers jump, set or move"),
+    }
+}
+
+fn jump(condition: Condition, label: &str) -> Instruction {
+    use Instruction::*;
+    let label = label.to_string();
+    match condition {
+        Condition::Eq => Je(label),
+        Condition::Ne => Jne(label),
+        Condition::Lt => Jl(label),
+        Condition::Le => Jle(label),
+        Condition::Gt => Jg(label),
+        Condition::Ge => Jge(label),
+        Condition::B => Jb(label),
+        Condition::Be => Jbe(label),
+        Condition::A => Ja(label),
+        Condition::Ae => Jae(label),
+        Condition::S => Js(label),
+        Condition::Ns => Jns(label),
+    }
+}
+
+fn set(condition: Condition, dst: &str) -> Instruction {
+    use Instruction::*;
+    let dst = dst.to_string();
+    match condition {
+        Condition::Eq => SetEq(dst),
+        Condition::Ne => SetNe(dst),
+        Condition::Lt => SetLt(dst),
+        Condition::Le => SetLe(dst),
+        Condition::Gt => SetGt(dst),
+        Condition::Ge => SetGe(dst),
+        Condition::B => SetB(dst),
+        Condition::Be => SetBe(dst),
+        Condition::A => SetA(dst),
+        Condition::Ae => SetAe(dst),
+        Condition::S => SetS(dst),
+        Condition::Ns => SetNs(dst),
+    }
+}
+
+fn cmov(condition: Condition, dst: &str, src: &str) -> Instruction {
+    use Instruction::*;
+    let operands = (dst.to_string(), src.to_string());
+    match condition {
+        Condition::Eq => CmovEq(operands),
+        Condition::Ne => CmovNe(operands),
+        Condition::Lt => CmovLt(operands),
+        Condition::Le => CmovLe(operands),
+        Condition::Gt => CmovGt(operands),
+        Condition::Ge => CmovGe(operands),
+        Condition::B => CmovB(operands),
+        Condition::Be => CmovBe(operands),
+        Condition::A => CmovA(operands),
+        Condition::Ae => CmovAe(operands),
+        Condition::S => CmovS(operands),
+        Condition::Ns => CmovNs(operands),
+    }
+}
+
+/// The flags `instruction` leaves.
+fn setter<'a>(instruction: &'a Instruction, regs: &Registers) -> Flags<'a> {
+    use Instruction::*;
+    let carry = |condition, a: &str, b: &str, tested| Flags::Carry {
+        setter: instruction,
+        carry: Comparison {
+            condition,
+            a: a.to_string(),
+            b: b.to_string(),
+            tested,
+        },
+    };
+    let rotated = |count: &str| parse_immediate(count).is_some_and(|count| count & 63 != 0);
+    match instruction {
+        Cmp((a, b)) => Flags::Compared {
+            setter: instruction,
+            a,
+            b,
+            mask: false,
+        },
+        // `test a, a` is a comparison of `a` with zero.
+        Test((a, b)) if a == b => Flags::Compared {
+            setter: instruction,
+            a,
+            b: "0",
+            mask: false,
+        },
+        Test((a, b)) => Flags::Compared {
+            setter: instruction,
+            a,
+            b,
+            mask: true,
+        },
+        And((dst, _)) | Or((dst, _)) | Xor((dst, _)) | Andn((dst, _)) => Flags::Result {
+            setter: instruction,
+            dst,
+            all: true,
+            overflow: None,
+        },
+        Add((dst, src)) | Sub((dst, src)) => {
+            // `add r0, r0` leaves no operand to tell the overflow from.
+            let kept = !regs
+                .in_operand(src)
+                .iter()
+                .any(|r| regs.in_operand(dst).contains(r));
+            let tested = if matches!(instruction, Add(_)) {
+                Tested::Sum
+            } else {
+                Tested::Difference
+            };
+            Flags::Result {
+                setter: instruction,
+                dst,
+                all: false,
+                overflow: kept.then_some((tested, src.as_str())),
+            }
+        }
+        Inc(dst) | Dec(dst) => Flags::Result {
+            setter: instruction,
+            dst,
+            all: false,
+            overflow: Some(if matches!(instruction, Inc(_)) {
+                (Tested::Sum, "1")
+            } else {
+                (Tested::Difference, "1")
+            }),
+        },
+        Neg(dst) => Flags::Result {
+            setter: instruction,
+            dst,
+            all: false,
+            overflow: None,
+        },
+        Shl((dst, count)) | Shr((dst, count)) | Sal((dst, count)) | Sar((dst, count))
+            if parse_immediate(count).is_some_and(|count| count != 0) =>
+        {
+            Flags::Result {
+                setter: instruction,
+                dst,
+                all: false,
+                overflow: None,
+            }
+        }
+        Bt((a, bit)) => match parse_immediate(bit) {
+            // x86 takes the bit position modulo the operand size.
+            Some(bit) => carry(
+                Condition::Ne,
+                a,
+                &(1u64 << (bit & 63)).to_string(),
+                Tested::Mask,
+            ),
+            None => carry(Condition::Ne, a, bit, Tested::Bit),
+        },
+        // A rotate leaves the bit it moved last in the carry: the lowest of
+        // the result after `rol`, the sign after `ror`.
+        Rol((dst, count)) if rotated(count) => carry(Condition::Ne, dst, "1", Tested::Mask),
+        Ror((dst, count)) if rotated(count) => carry(Condition::Lt, dst, "0", Tested::Operands),
+        Rcl(_) | Rcr(_) => carry(Condition::Ne, "", "", Tested::Carry),
+        _ => Flags::Opaque(instruction),
+    }
+}
+
+/// The comparison a reader of `flag` tests, given the flags it sees.
+fn read(instruction: &Instruction, flag: Flag, flags: &Flags) -> Result<Comparison, String> {
+    let comparison = |condition, a: &str, b: &str, tested| {
+        if [a, b].iter().any(|operand| is_memory(operand)) {
+            return Err(format!(
+                "`{}` tests a comparison with memory; load it into a register first",
+                instruction
+            ));
+        }
+        if parse_immediate(a).is_some() {
+            return Err(format!(
+                "`{}` tests a comparison whose first operand is not a register",
+                instruction
+            ));
+        }
+        Ok(Comparison {
+            condition,
+            a: a.to_string(),
+            b: b.to_string(),
+            tested,
+        })
+    };
+    let holds = |set, condition: Condition| {
+        if set { condition } else { condition.negate() }
+    };
+    match (flag, flags) {
+        (Flag::Condition(condition), Flags::Compared { a, b, mask, .. }) => {
+            let tested = if *mask {
+                Tested::Mask
+            } else {
+                Tested::Operands
+            };
+            comparison(condition, a, b, tested)
+        }
+        (Flag::Condition(condition), Flags::Result { dst, all, .. })
+            if *all
+                || matches!(
+                    condition,
+                    Condition::Eq | Condition::Ne | Condition::S | Condition::Ns
+                ) =>
+        {
+            comparison(condition, dst, "0", Tested::Operands)
+        }
+        (Flag::Condition(condition), Flags::Carry { setter, carry })
+            if matches!(condition, Condition::B | Condition::Ae)
+                || matches!(setter, Instruction::Bt(_))
+                    && matches!(condition, Condition::S | Condition::Ns) =>
+        {
+            let set = matches!(condition, Condition::B | Condition::S);
+            comparison(
+                holds(set, carry.condition),
+                &carry.a,
+                &carry.b,
+                carry.tested,
+            )
+        }
+        (
+            Flag::Overflow(set),
+            Flags::Result {
+                dst,
+                overflow: Some((tested, src)),
+                ..
+            },
+        ) => comparison(holds(set, Condition::S), dst, src, *tested),
+        (Flag::Parity(set), Flags::Compared { a, b, mask, .. })
+            if *mask || parse_immediate(b) == Some(0) =>
+        {
+            let b = if *mask { b } else { a };
+            comparison(holds(set, Condition::Eq), a, b, Tested::Parity)
+        }
+        (Flag::Parity(set), Flags::Result { dst, .. }) => {
+            comparison(holds(set, Condition::Eq), dst, dst, Tested::Parity)
+        }
+        (
+            _,
+            Flags::Compared { setter, .. }
+            | Flags::Result { setter, .. }
+            | Flags::Carry { setter, .. }
+            | Flags::Opaque(setter),
+        ) => Err(format!(
+            "`{}` tests flags that `{}` does not leave on a target without condition flags; \
+             compare the result with `cmp`",
+            instruction, setter
+        )),
+        (_, Flags::Stale { setter, by }) => Err(format!(
+            "`{}` tests flags from `{}`, but `{}` changes its operands in between",
+            instruction, setter, by
+        )),
+        (_, Flags::Unknown) => Err(format!(
+            "`{}` tests flags that no `cmp` or `test` before it in the same block sets",
+            instruction
+        )),
+    }
+}
+
+/// The flag a reader tests; `None` for every other instruction.
+fn flag(instruction: &Instruction) -> Option<Flag> {
+    use Instruction::*;
+    Some(match instruction {
+        Jo(_) | SetOv(_) | CmovOv(_) => Flag::Overflow(true),
+        Jno(_) | SetNo(_) | CmovNo(_) => Flag::Overflow(false),
+        Jp(_) | SetP(_) | CmovP(_) => Flag::Parity(true),
+        Jnp(_) | SetNp(_) | CmovNp(_) => Flag::Parity(false),
+        _ => Flag::Condition(condition(instruction)?),
+    })
+}
+
+/// The condition a flag reader tests; `None` for every other instruction.
+fn condition(instruction: &Instruction) -> Option<Condition> {
+    use Instruction::*;
+    Some(match instruction {
+        Je(_) | SetEq(_) | CmovEq(_) => Condition::Eq,
+        Jne(_) | SetNe(_) | CmovNe(_) => Condition::Ne,
+        Jl(_) | SetLt(_) | CmovLt(_) => Condition::Lt,
+        Jle(_) | SetLe(_) | CmovLe(_) => Condition::Le,
+        Jg(_) | SetGt(_) | CmovGt(_) => Condition::Gt,
+        Jge(_) | SetGe(_) | CmovGe(_) => Condition::Ge,
+        Jb(_) | SetB(_) | CmovB(_) => Condition::B,
+        Jbe(_) | SetBe(_) | CmovBe(_) => Condition::Be,
+        Ja(_) | SetA(_) | CmovA(_) => Condition::A,
+        Jae(_) | SetAe(_) | CmovAe(_) => Condition::Ae,
+        Js(_) | SetS(_) | CmovS(_) => Condition::S,
+        Jns(_) | SetNs(_) | CmovNs(_) => Condition::Ns,
+        _ => return None,
+    })
+}
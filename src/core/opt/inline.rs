@@ -0,0 +1,372 @@
+//! Inlining of small leaf functions.
+//!
+//! A function is the code reachable from a called label without following
+//! calls. It can be inlined when it is a leaf: it calls nothing, leaves the
+//! stack alone (no `push`/`pop`/`enter`/`leave` and no operand on the stack
+//! or frame pointer), leaves only through `ret`, and no label inside it is
+//! used from outside. Each `call` then becomes a copy of the body with its
+//! labels renamed and every `ret` turned into a jump past the copy.
+//!
+//! Functions of up to eight instructions are inlined everywhere, and larger
+//! ones when they have a single call site and nothing else refers to them, so
+//! `dce` drops the original. Callers that become leaves this way are
+//! considered again. `.inline` before the label inlines a function whatever
+//! its size and `.noinline` keeps it out of line.
+//!
+//! When optimizing for size, a copy must not outlive the original: only
+//! functions that nothing but calls refers to are inlined, those of a single
+//! instruction at every call site and larger ones at their single call site,
+//! and only when `dce` runs to drop the original.
+//!
+//! With register allocation, locals belong to the function that names them,
+//! so every copy gets locals of its own.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use super::cfg::{Cfg, branch_target, operands_mut, referenced_names};
+use super::{OptReport, Registers, is_code, tokens, virtual_index};
+use crate::core::Instruction;
+
+const PASS: &str = "inline";
+
+/// Instructions a function may have to be inlined at every call site.
+const SMALL: usize = 8;
+
+/// Instructions a function with a single call site may have.
+const SINGLE: usize = 64;
+
+/// Rounds of inlining into callers that became leaves.
+const ROUNDS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Hint {
+    Inline,
+    NoInline,
+}
+
+struct Body {
+    range: std::ops::Range<usize>,
+    /// Labels defined in the body, the entry included.
+    labels: HashSet<String>,
+    /// Index of the last code instruction, a `ret`.
+    last: usize,
+}
+
+pub fn run(
+    mut instructions: Vec<Instruction>,
+    regs: &Registers,
+    locals: &HashSet<String>,
+    optimize_size: bool,
+    eliminate_dead: bool,
+    report: &mut OptReport,
+) -> Vec<Instruction> {
+    let small = if optimize_size { 1 } else { SMALL };
+    let hints = hints(&instructions);
+    let mut fresh = FreshNames::new(&instructions, regs);
+    let mut warned = HashSet::new();
+
+    for _ in 0..ROUNDS {
+        let cfg = Cfg::build(&instructions);
+        let mut sites: HashMap<&str, usize> = HashMap::new();
+        let mut referenced: HashSet<&str> = HashSet::new();
+        for instruction in &instructions {
+            match instruction {
+                Instruction::Call(name) => *sites.entry(name.as_str()).or_default() += 1,
+                Instruction::Label(_) => {}
+                _ => referenced.extend(referenced_names(instruction)),
+            }
+        }
+
+        let mut bodies: HashMap<String, Body> = HashMap::new();
+        for (&name, &count) in &sites {
+            let hint = hints.get(name).copied();
+            if hint == Some(Hint::NoInline) {
+                continue;
+            }
+            let body = match body(&instructions, &cfg, name, regs) {
+                Ok(body) => body,
+                Err(reason) => {
+                    if hint == Some(Hint::Inline) && warned.insert(name.to_string()) {
+                        report.note(PASS, format!("`{}`: ignored `.inline`: {}", name, reason));
+                    }
+                    continue;
+                }
+            };
+            let size = instructions[body.range.clone()]
+                .iter()
+                .filter(|i| is_code(i) && !matches!(i, Instruction::Ret))
+                .count();
+            let only_use = count == 1 && !referenced.contains(name);
+            let fits = size <= small || (only_use && size <= SINGLE);
+            // Every call is inlined, so nothing is left to reach the original.
+            let dropped = eliminate_dead && !referenced.contains(name);
+            if hint == Some(Hint::Inline) || (fits && (dropped || !optimize_size)) {
+                bodies.insert(name.to_string(), body);
+            }
+        }
+        if bodies.is_empty() {
+            break;
+        }
+
+        let taken: HashSet<String> = instructions
+            .iter()
+            .filter_map(|i| match i {
+                Instruction::Label(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+        let mut inlined: HashMap<&str, usize> = HashMap::new();
+        let mut out = Vec::with_capacity(instructions.len());
+        for instruction in &instructions {
+            let Instruction::Call(name) = instruction else {
+                out.push(instruction.clone());
+                continue;
+            };
+            let Some(body) = bodies.get(name) else {
+                out.push(instruction.clone());
+                continue;
+            };
+            let suffix = fresh.suffix(&body.labels, &taken);
+            out.extend(copy(&instructions, name, body, &suffix, locals, &mut fresh));
+            *inlined.entry(name.as_str()).or_default() += 1;
+        }
+
+        let mut names: Vec<_> = inlined.into_iter().collect();
+        names.sort();
+        for (name, count) in names {
+            let sites = if count == 1 { "site" } else { "sites" };
+            report.note(
+                PASS,
+                format!("inlined `{}` at {} call {}", name, count, sites),
+            );
+        }
+        instructions = out;
+    }
+
+    instructions
+}
+
+/// `.inline` and `.noinline` hints by the label they precede.
+fn hints(instructions: &[Instruction]) -> HashMap<String, Hint> {
+    let mut hints = HashMap::new();
+    let mut pending = None;
+    for instruction in instructions {
+        match instruction {
+            Instruction::Inline => pending = Some(Hint::Inline),
+            Instruction::NoInline => pending = Some(Hint::NoInline),
+            Instruction::Label(name) => {
+                if let Some(hint) = pending.take() {
+                    hints.insert(name.clone(), hint);
+                }
+            }
+            _ if is_code(instruction) => pending = None,
+            _ => {}
+        }
+    }
+    hints
+}
+
+fn body(
+    instructions: &[Instruction],
+    cfg: &Cfg,
+    name: &str,
+    regs: &Registers,
+) -> Result<Body, &'static str> {
+    let &entry = cfg.labels.get(name).ok_or("it is not a label in code")?;
+    let mut blocks = BTreeSet::new();
+    let mut work = vec![entry];
+    while let Some(b) = work.pop() {
+        if blocks.insert(b) {
+            work.extend(cfg.blocks[b].succs.iter().copied());
+        }
+    }
+    let &end = blocks.last().unwrap_or(&entry);
+    if !blocks.iter().copied().eq(entry..=end) {
+        return Err("its code is not laid out in one run");
+    }
+    let range = cfg.blocks[entry].start..cfg.blocks[end].end;
+
+    let last_code = |b: usize| {
+        let block = &cfg.blocks[b];
+        (block.start..block.end)
+            .rev()
+            .find(|&i| is_code(&instructions[i]))
+    };
+    for &b in &blocks {
+        if cfg.blocks[b].exits
+            && !last_code(b).is_some_and(|i| matches!(instructions[i], Instruction::Ret))
+        {
+            return Err("it leaves other than through `ret`");
+        }
+    }
+    let last = last_code(end).ok_or("it leaves other than through `ret`")?;
+    if !matches!(instructions[last], Instruction::Ret) {
+        return Err("it leaves other than through `ret`");
+    }
+
+    let stack: Vec<&str> = ["sp", "sb"]
+        .iter()
+        .filter_map(|alias| regs.resolve(alias))
+        .collect();
+    for instruction in &instructions[range.clone()] {
+        use Instruction::*;
+        match instruction {
+            Call(_) => return Err("it calls other functions"),
+            Push(_) | Pop(_) | Pusha | Popa | Enter(_) | Leave => return Err("it uses the stack"),
+            Section(_)
+            | Global(_)
+            | Function(_)
+            | Extern(_)
+            | Equ(_, _)
+            | DataByte(_, _)
+            | DataWord(_, _)
+            | DataDword(_, _)
+            | DataQword(_, _)
+            | ReserveByte(_, _)
+            | ReserveWord(_, _)
+            | ReserveDword(_, _)
+            | ReserveQword(_, _) => return Err("it contains data"),
+            _ => {}
+        }
+        let on_stack = referenced_names(instruction)
+            .iter()
+            .any(|n| regs.resolve(n).is_some_and(|r| stack.contains(&r)));
+        if on_stack {
+            return Err("it uses the stack");
+        }
+    }
+
+    let labels: HashSet<String> = instructions[range.clone()]
+        .iter()
+        .filter_map(|i| match i {
+            Instruction::Label(label) => Some(label.clone()),
+            _ => None,
+        })
+        .collect();
+    for (i, instruction) in instructions.iter().enumerate() {
+        let inside = range.contains(&i);
+        if matches!(instruction, Instruction::Label(_))
+            || (inside && branch_target(instruction).is_some())
+        {
+            continue;
+        }
+        let uses_inner = referenced_names(instruction)
+            .iter()
+            .any(|n| *n != name && labels.contains(*n));
+        if uses_inner {
+            return Err("a label inside it is used elsewhere");
+        }
+    }
+
+    Ok(Body {
+        range,
+        labels,
+        last,
+    })
+}
+
+/// The body of `name` as it runs at one call site.
+fn copy(
+    instructions: &[Instruction],
+    name: &str,
+    body: &Body,
+    suffix: &str,
+    locals: &HashSet<String>,
+    fresh: &mut FreshNames,
+) -> Vec<Instruction> {
+    let back = format!("{}__ret{}", name, suffix);
+    let mut renamed: HashMap<String, String> = HashMap::new();
+    let mut code = Vec::new();
+    let mut jumps_back = false;
+
+    for (i, instruction) in instructions[body.range.clone()].iter().enumerate() {
+        let mut instruction = instruction.clone();
+        if let Instruction::Label(label) = &mut instruction {
+            label.push_str(suffix);
+        } else if let Instruction::Ret = instruction {
+            if body.range.start + i == body.last {
+                continue;
+            }
+            jumps_back = true;
+            instruction = Instruction::Jmp(back.clone());
+        } else if branch_target(&instruction).is_some() {
+            for target in operands_mut(&mut instruction) {
+                if body.labels.contains(target.as_str()) {
+                    target.push_str(suffix);
+                }
+            }
+        } else {
+            for operand in operands_mut(&mut instruction) {
+                if !tokens(operand).any(|(t, is_name)| is_name && locals.contains(t)) {
+                    continue;
+                }
+                let mut new = String::with_capacity(operand.len());
+                for (token, is_name) in tokens(operand) {
+                    if is_name && locals.contains(token) {
+                        let local = renamed
+                            .entry(token.to_string())
+                            .or_insert_with(|| fresh.local());
+                        new.push_str(local);
+                    } else {
+                        new.push_str(token);
+                    }
+                }
+                *operand = new;
+            }
+        }
+        code.push(instruction);
+    }
+    if jumps_back {
+        code.push(Instruction::Label(back));
+    }
+    code
+}
+
+/// Label suffixes and local register names no instruction uses yet.
+struct FreshNames {
+    site: usize,
+    register: u32,
+    regs: Registers,
+}
+
+impl FreshNames {
+    fn new(instructions: &[Instruction], regs: &Registers) -> Self {
+        let register = instructions
+            .iter()
+            .flat_map(referenced_names)
+            .filter_map(virtual_index)
+            .chain(regs.virtuals().iter().filter_map(|v| virtual_index(v)))
+            .max()
+            .unwrap_or(0);
+        FreshNames {
+            site: 0,
+            register,
+            regs: regs.clone(),
+        }
+    }
+
+    /// A suffix that renames none of `labels` onto a label in `taken`.
+    fn suffix(&mut self, labels: &HashSet<String>, taken: &HashSet<String>) -> String {
+        loop {
+            self.site += 1;
+            let suffix = format!("__i{}", self.site);
+            let clash = labels.iter().any(|label| {
+                taken.contains(&format!("{}{}", label, suffix))
+                    || taken.contains(&format!("{}__ret{}", label, suffix))
+            });
+            if !clash {
+                return suffix;
+            }
+        }
+    }
+
+    fn local(&mut self) -> String {
+        loop {
+            self.register += 1;
+            let name = format!("r{}", self.register);
+            if self.regs.resolve(&name).is_none() {
+                return name;
+            }
+        }
+    }
+}
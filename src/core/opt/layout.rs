@@ -0,0 +1,300 @@
+//! Branch layout from static predictions.
+//!
+//! Processors without a prediction history guess forward conditional jumps
+//! not taken and backward ones taken, so the hot path should fall through.
+//! A forward jump marked `.likely` is turned around: the code it skips, which
+//! `.likely` says is cold, moves out of line after the next `ret` (or the last
+//! `jmp`) of the section, and the jump, inverted, now leads to it. The moved
+//! code jumps back to the original target unless it already ends in a jump.
+//!
+//! Innermost loop headers are then aligned so the loop body starts on a fetch
+//! boundary, on the targets where that pays off, unless optimizing for size.
+//!
+//! `.likely` and `.unlikely` stay in front of their jumps for the backend,
+//! which turns them into hints on targets that have them (PowerPC `+`/`-`).
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use super::cfg::{Cfg, branch_target};
+use super::{OptReport, Target, is_code, loops};
+use crate::arch::Architecture;
+use crate::core::Instruction;
+
+const PASS: &str = "layout";
+
+/// A cold run of code moved after `anchor`.
+struct Move {
+    /// Index of the `.likely` hint of the jump.
+    hint: usize,
+    jump: usize,
+    cold: Range<usize>,
+    anchor: usize,
+    label: String,
+    /// Original target, where the cold code falls through to.
+    target: String,
+}
+
+pub fn run(
+    instructions: Vec<Instruction>,
+    target: &Target,
+    optimize_size: bool,
+    report: &mut OptReport,
+) -> Vec<Instruction> {
+    let instructions = reorder(instructions, report);
+    if optimize_size {
+        return instructions;
+    }
+    align_loops(instructions, target, report)
+}
+
+/// The conditional jump each hint applies to, with the hint's index.
+fn predictions(instructions: &[Instruction]) -> HashMap<usize, (usize, bool)> {
+    let mut predictions = HashMap::new();
+    let mut pending = None;
+    for (i, instruction) in instructions.iter().enumerate() {
+        match instruction {
+            Instruction::Likely => pending = Some((i, true)),
+            Instruction::Unlikely => pending = Some((i, false)),
+            Instruction::Label(_) => pending = None,
+            _ if is_code(instruction) => {
+                if let Some(hint) = pending.take()
+                    && invert(instruction).is_some()
+                {
+                    predictions.insert(i, hint);
+                }
+            }
+            _ => {}
+        }
+    }
+    predictions
+}
+
+fn reorder(instructions: Vec<Instruction>, report: &mut OptReport) -> Vec<Instruction> {
+    let labels: HashMap<&str, usize> = instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(i, instruction)| match instruction {
+            Instruction::Label(name) => Some((name.as_str(), i)),
+            _ => None,
+        })
+        .collect();
+    let mut taken: HashSet<String> = labels.keys().map(|l| l.to_string()).collect();
+
+    let mut predictions: Vec<_> = predictions(&instructions).into_iter().collect();
+    predictions.sort();
+    let mut moves: Vec<Move> = Vec::new();
+    for (jump, (hint, likely)) in predictions {
+        if !likely {
+            continue;
+        }
+        let Some(target) = branch_target(&instructions[jump]) else {
+            continue;
+        };
+        let Some(&t) = labels.get(target) else {
+            continue;
+        };
+        if t < jump {
+            continue;
+        }
+        let cold = jump + 1..t;
+        let movable = instructions[cold.clone()].iter().all(|i| {
+            !matches!(
+                i,
+                Instruction::Section(_)
+                    | Instruction::Equ(_, _)
+                    | Instruction::DataByte(_, _)
+                    | Instruction::DataWord(_, _)
+                    | Instruction::DataDword(_, _)
+                    | Instruction::DataQword(_, _)
+                    | Instruction::ReserveByte(_, _)
+                    | Instruction::ReserveWord(_, _)
+                    | Instruction::ReserveDword(_, _)
+                    | Instruction::ReserveQword(_, _)
+            )
+        });
+        if !movable || !instructions[cold.clone()].iter().any(is_code) {
+            continue;
+        }
+        let overlaps = moves.iter().any(|m| {
+            m.cold.contains(&jump)
+                || cold.contains(&m.jump)
+                || cold.contains(&m.anchor)
+                || (m.cold.start < cold.end && cold.start < m.cold.end)
+        });
+        if overlaps {
+            continue;
+        }
+        let Some(anchor) = anchor(&instructions, t, &moves) else {
+            report.note(
+                PASS,
+                format!(
+                    "`{}`: no place after the function to move cold code to",
+                    instructions[jump]
+                ),
+            );
+            continue;
+        };
+
+        let mut n = moves.len();
+        let label = loop {
+            let label = format!("{}__cold{}", target, n);
+            if taken.insert(label.clone()) {
+                break label;
+            }
+            n += 1;
+        };
+        moves.push(Move {
+            hint,
+            jump,
+            cold,
+            anchor,
+            label,
+            target: target.to_string(),
+        });
+    }
+
+    if moves.is_empty() {
+        return instructions;
+    }
+    for m in &moves {
+        let size = instructions[m.cold.clone()]
+            .iter()
+            .filter(|i| is_code(i))
+            .count();
+        report.note(
+            PASS,
+            format!(
+                "`{}`: moved {} cold instruction{} out of line",
+                instructions[m.jump],
+                size,
+                if size == 1 { "" } else { "s" }
+            ),
+        );
+    }
+
+    let mut out = Vec::with_capacity(instructions.len() + 2 * moves.len());
+    for (i, instruction) in instructions.iter().enumerate() {
+        if moves.iter().any(|m| m.cold.contains(&i)) {
+            continue;
+        }
+        if moves.iter().any(|m| m.hint == i) {
+            // The inverted jump is now the unlikely one.
+            out.push(Instruction::Unlikely);
+        } else if let Some(m) = moves.iter().find(|m| m.jump == i) {
+            out.push(invert(instruction).unwrap()(m.label.clone()));
+        } else {
+            out.push(instruction.clone());
+        }
+        for m in moves.iter().filter(|m| m.anchor == i) {
+            out.push(Instruction::Label(m.label.clone()));
+            out.extend(instructions[m.cold.clone()].iter().cloned());
+            let falls_through = instructions[m.cold.clone()]
+                .iter()
+                .rev()
+                .find(|i| is_code(i))
+                .is_none_or(|i| !matches!(i, Instruction::Jmp(_) | Instruction::Ret));
+            if falls_through {
+                out.push(Instruction::Jmp(m.target.clone()));
+            }
+        }
+    }
+    out
+}
+
+/// Where code cold for a jump to the label at `t` can go: after the first
+/// `ret` in the section from `t` on, otherwise after its last `jmp`.
+fn anchor(instructions: &[Instruction], t: usize, moves: &[Move]) -> Option<usize> {
+    let end = instructions[t..]
+        .iter()
+        .position(|i| matches!(i, Instruction::Section(_)))
+        .map_or(instructions.len(), |n| t + n);
+    let free = |i: &usize| !moves.iter().any(|m| m.cold.contains(i));
+    (t..end)
+        .filter(free)
+        .find(|&i| matches!(instructions[i], Instruction::Ret))
+        .or_else(|| {
+            (t..end)
+                .filter(free)
+                .rfind(|&i| matches!(instructions[i], Instruction::Jmp(_)))
+        })
+}
+
+/// The conditional jump with the opposite condition, to a new label.
+fn invert(instruction: &Instruction) -> Option<fn(String) -> Instruction> {
+    use Instruction::*;
+    Some(match instruction {
+        Je(_) => Jne,
+        Jne(_) => Je,
+        Jl(_) => Jge,
+        Jge(_) => Jl,
+        Jle(_) => Jg,
+        Jg(_) => Jle,
+        Jo(_) => Jno,
+        Jno(_) => Jo,
+        Js(_) => Jns,
+        Jns(_) => Js,
+        Jp(_) => Jnp,
+        Jnp(_) => Jp,
+        Ja(_) => Jbe,
+        Jbe(_) => Ja,
+        Jae(_) => Jb,
+        Jb(_) => Jae,
+        _ => return None,
+    })
+}
+
+/// Log2 of the alignment for loop headers, where it pays off.
+fn loop_alignment(architecture: Architecture) -> Option<u32> {
+    match architecture {
+        Architecture::AMD64 | Architecture::AMD32 | Architecture::ARM64 => Some(4),
+        Architecture::ARM32 => Some(3),
+        _ => None,
+    }
+}
+
+fn align_loops(
+    instructions: Vec<Instruction>,
+    target: &Target,
+    report: &mut OptReport,
+) -> Vec<Instruction> {
+    let Some(alignment) = loop_alignment(target.architecture) else {
+        return instructions;
+    };
+    let cfg = Cfg::build(&instructions);
+    let loops = loops::find(&instructions, &cfg);
+    let mut headers = HashSet::new();
+    for l in loops.iter().filter(|l| l.is_innermost()) {
+        let block = &cfg.blocks[l.header];
+        if !block.in_text {
+            continue;
+        }
+        let Some(name) = &block.label else {
+            continue;
+        };
+        let aligned = instructions[..block.start]
+            .iter()
+            .rev()
+            .take_while(|i| !is_code(i))
+            .any(|i| matches!(i, Instruction::Align(_)));
+        if !aligned {
+            headers.insert(block.start);
+            report.note(
+                PASS,
+                format!("aligned loop `{}` to {} bytes", name, 1 << alignment),
+            );
+        }
+    }
+    if headers.is_empty() {
+        return instructions;
+    }
+
+    let mut out = Vec::with_capacity(instructions.len() + headers.len());
+    for (i, instruction) in instructions.into_iter().enumerate() {
+        if headers.contains(&i) {
+            out.push(Instruction::Align(alignment.to_string()));
+        }
+        out.push(instruction);
+    }
+    out
+}
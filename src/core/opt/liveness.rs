@@ -0,0 +1,267 @@
+//! Register def/use information and backward liveness over the CFG.
+
+use std::collections::HashSet;
+
+use super::cfg::Cfg;
+use super::{FLAGS, Registers, is_memory, parse_immediate};
+use crate::core::Instruction;
+
+/// Registers an instruction reads and writes.
+#[derive(Debug, Clone, Default)]
+pub struct Effects {
+    pub defs: Vec<String>,
+    pub uses: Vec<String>,
+    /// Reads every register (calls, syscalls, string and port instructions
+    /// whose operands are implicit on some targets).
+    pub barrier: bool,
+    /// Touches memory, the stack, I/O or may trap; never removable.
+    pub side_effect: bool,
+}
+
+impl Effects {
+    fn read(&mut self, regs: &Registers, operand: &str) {
+        self.uses.extend(regs.in_operand(operand));
+    }
+
+    /// `operand` is written. Memory destinations only read their address
+    /// registers and count as a side effect.
+    fn write(&mut self, regs: &Registers, operand: &str) {
+        if is_memory(operand) {
+            self.read(regs, operand);
+            self.side_effect = true;
+        } else if let Some(reg) = regs.as_destination(operand) {
+            self.defs.push(reg);
+        }
+    }
+
+    /// `operand` is both read and written.
+    fn update(&mut self, regs: &Registers, operand: &str) {
+        self.read(regs, operand);
+        self.write(regs, operand);
+    }
+
+    fn flags_in(&mut self) {
+        self.uses.push(FLAGS.to_string());
+    }
+
+    /// Flags follow x86 semantics. Instructions that leave some flags
+    /// untouched (`inc`, rotates, `bt`, shifts by a register count) are
+    /// partial updates, so a flag value produced earlier stays live across
+    /// them. Flags left undefined count as written.
+    fn flags_out(&mut self, complete: bool) {
+        self.defs.push(FLAGS.to_string());
+        if !complete {
+            self.flags_in();
+        }
+    }
+}
+
+pub fn effects(instruction: &Instruction, regs: &Registers) -> Effects {
+    use Instruction::*;
+    let mut e = Effects::default();
+
+    match instruction {
+        Mov((d, s)) | Lea((d, s)) | Load((d, s)) => {
+            e.read(regs, s);
+            e.write(regs, d);
+        }
+        Store((d, s)) => {
+            e.read(regs, d);
+            e.read(regs, s);
+            e.side_effect = true;
+        }
+        CmovEq((d, s)) | CmovNe((d, s)) | CmovLt((d, s)) | CmovLe((d, s)) | CmovGt((d, s))
+        | CmovGe((d, s)) | CmovOv((d, s)) | CmovNo((d, s)) | CmovS((d, s)) | CmovNs((d, s))
+        | CmovP((d, s)) | CmovNp((d, s)) | CmovA((d, s)) | CmovAe((d, s)) | CmovB((d, s))
+        | CmovBe((d, s)) => {
+            e.read(regs, s);
+            e.update(regs, d);
+            e.flags_in();
+        }
+        Push(s) => {
+            e.read(regs, s);
+            e.side_effect = true;
+        }
+        Pop(d) => {
+            e.write(regs, d);
+            e.side_effect = true;
+        }
+        Enter(_) | Leave => e.side_effect = true,
+        Add((d, s)) | Sub((d, s)) | Mul((d, s)) | Imul((d, s)) | Mulh((d, s)) | And((d, s))
+        | Or((d, s)) | Xor((d, s)) | Andn((d, s)) => {
+            e.read(regs, s);
+            e.update(regs, d);
+            e.flags_out(true);
+        }
+        Shl((d, s)) | Shr((d, s)) | Sal((d, s)) | Sar((d, s)) => {
+            // A shift by zero leaves the flags alone.
+            e.read(regs, s);
+            e.update(regs, d);
+            e.flags_out(parse_immediate(s).is_some_and(|count| count != 0));
+        }
+        Rol((d, s)) | Ror((d, s)) | Rcl((d, s)) | Rcr((d, s)) | Btr((d, s)) | Bts((d, s))
+        | Btc((d, s)) => {
+            e.read(regs, s);
+            e.update(regs, d);
+            e.flags_out(false);
+        }
+        Div((d, s)) | Idiv((d, s)) | Mod((d, s)) => {
+            // Division traps on zero and clobbers fixed registers on x86.
+            e.read(regs, s);
+            e.update(regs, d);
+            e.flags_out(true);
+            e.side_effect = true;
+        }
+        Inc(d) | Dec(d) => {
+            // inc/dec preserve the carry flag.
+            e.update(regs, d);
+            e.flags_out(false);
+        }
+        Neg(d) => {
+            e.update(regs, d);
+            e.flags_out(true);
+        }
+        Not(d) => e.update(regs, d),
+        Bsf((d, s)) | Bsr((d, s)) => {
+            e.read(regs, s);
+            e.write(regs, d);
+            e.flags_out(true);
+        }
+        Bextr((d, s, imm)) => {
+            e.read(regs, s);
+            e.read(regs, imm);
+            e.write(regs, d);
+            e.flags_out(true);
+        }
+        Cmp((a, b)) | Test((a, b)) => {
+            e.read(regs, a);
+            e.read(regs, b);
+            e.flags_out(true);
+        }
+        Bt((a, b)) => {
+            e.read(regs, a);
+            e.read(regs, b);
+            e.flags_out(false);
+        }
+        SetEq(d) | SetNe(d) | SetLt(d) | SetLe(d) | SetGt(d) | SetGe(d) | SetOv(d) | SetNo(d)
+        | SetS(d) | SetNs(d) | SetP(d) | SetNp(d) | SetA(d) | SetAe(d) | SetB(d) | SetBe(d) => {
+            // setCC only writes the low byte on x86.
+            e.update(regs, d);
+            e.flags_in();
+        }
+        Je(_) | Jne(_) | Jl(_) | Jle(_) | Jg(_) | Jge(_) | Jo(_) | Jno(_) | Js(_) | Jns(_)
+        | Jp(_) | Jnp(_) | Ja(_) | Jae(_) | Jb(_) | Jbe(_) => e.flags_in(),
+        Jmp(_) => {}
+        Prefetch(a) | Clflush(a) | Clwb(a) => {
+            e.read(regs, a);
+            e.side_effect = true;
+        }
+        Lfence | Sfence | Mfence => e.side_effect = true,
+        Pusha | Popa | Cmps(_) | Scas(_) | Stos(_) | Lods(_) | Movs(_) | Cbw(_) | Cwd(_)
+        | Cdq(_) | Cdqe(_) | Cqo(_) | Cwde(_) | LoopEq(_) | LoopNe(_) | Call(_) | Ret | In(_)
+        | Out(_) | Ins(_) | Outs(_) | Cpuid | Syscall(_) => {
+            e.barrier = true;
+            e.side_effect = true;
+        }
+        Label(_)
+        | Section(_)
+        | Global(_)
+        | Function(_)
+        | Extern(_)
+        | Align(_)
+        | Unroll(_)
+        | Inline
+        | NoInline
+        | Likely
+        | Unlikely
+        | Loc(_)
+        | Equ(_, _)
+        | DataByte(_, _)
+        | DataWord(_, _)
+        | DataDword(_, _)
+        | DataQword(_, _)
+        | ReserveByte(_, _)
+        | ReserveWord(_, _)
+        | ReserveDword(_, _)
+        | ReserveQword(_, _) => {}
+    }
+
+    // The stack and frame pointers are never dead.
+    e.defs.retain(|d| !is_stack_register(regs, d));
+    e
+}
+
+fn is_stack_register(regs: &Registers, name: &str) -> bool {
+    ["sp", "sb"]
+        .iter()
+        .any(|alias| regs.resolve(alias) == Some(name))
+}
+
+/// Applies one instruction to a live set, walking backwards.
+pub fn step(live: &mut HashSet<String>, effects: &Effects, regs: &Registers) {
+    if effects.barrier {
+        live.extend(regs.all().iter().cloned());
+        return;
+    }
+    for d in &effects.defs {
+        live.remove(d);
+    }
+    live.extend(effects.uses.iter().cloned());
+}
+
+/// Live registers at the entry and exit of every block.
+pub struct Liveness {
+    pub live_in: Vec<HashSet<String>>,
+    pub live_out: Vec<HashSet<String>>,
+}
+
+impl Liveness {
+    pub fn analyze(instructions: &[Instruction], cfg: &Cfg, regs: &Registers) -> Liveness {
+        let n = cfg.blocks.len();
+        let block_effects: Vec<Vec<Effects>> = cfg
+            .blocks
+            .iter()
+            .map(|b| {
+                instructions[b.start..b.end]
+                    .iter()
+                    .map(|i| effects(i, regs))
+                    .collect()
+            })
+            .collect();
+
+        let mut live_in = vec![HashSet::new(); n];
+        let mut live_out = vec![HashSet::new(); n];
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in (0..n).rev() {
+                let block = &cfg.blocks[b];
+                if !block.in_text {
+                    continue;
+                }
+                let mut out: HashSet<String> = if block.exits {
+                    regs.all().clone()
+                } else {
+                    HashSet::new()
+                };
+                for &s in &block.succs {
+                    out.extend(live_in[s].iter().cloned());
+                }
+
+                let mut live = out.clone();
+                for e in block_effects[b].iter().rev() {
+                    step(&mut live, e, regs);
+                }
+
+                if live != live_in[b] || out != live_out[b] {
+                    live_in[b] = live;
+                    live_out[b] = out;
+                    changed = true;
+                }
+            }
+        }
+
+        Liveness { live_in, live_out }
+    }
+}
@@ -0,0 +1,208 @@
+//! Dominators and natural loops over the CFG.
+//!
+//! Dominance is computed with the iterative algorithm of Cooper, Harvey and
+//! Kennedy ("A Simple, Fast Dominance Algorithm"), from a virtual root that
+//! enters the program at every entry point and every call target. The
+//! dominator tree is then numbered in depth-first order, so that a block
+//! dominates another exactly when its subtree spans the other's number.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use super::cfg::Cfg;
+use crate::core::Instruction;
+
+pub struct Dominators {
+    reachable: Vec<bool>,
+    /// Number of every block in a depth-first walk of the dominator tree,
+    /// and the last number within its subtree.
+    pre: Vec<usize>,
+    last: Vec<usize>,
+}
+
+impl Dominators {
+    pub fn analyze(instructions: &[Instruction], cfg: &Cfg) -> Dominators {
+        let n = cfg.blocks.len();
+        let root = n;
+
+        let mut roots = cfg.entries(instructions);
+        roots.extend(cfg.blocks.iter().flat_map(|b| b.callees.iter().copied()));
+        roots.sort_unstable();
+        roots.dedup();
+        let succs = |b: usize| -> &[usize] {
+            if b == root {
+                &roots
+            } else {
+                &cfg.blocks[b].succs
+            }
+        };
+
+        // Postorder from the virtual root.
+        let mut order = Vec::with_capacity(n + 1);
+        let mut visited = vec![false; n + 1];
+        let mut stack = vec![(root, 0)];
+        visited[root] = true;
+        while let Some((b, next)) = stack.pop() {
+            if let Some(&s) = succs(b).get(next) {
+                stack.push((b, next + 1));
+                if !visited[s] {
+                    visited[s] = true;
+                    stack.push((s, 0));
+                }
+            } else {
+                order.push(b);
+            }
+        }
+        let mut position = vec![usize::MAX; n + 1];
+        for (i, &b) in order.iter().enumerate() {
+            position[b] = i;
+        }
+
+        let mut preds = vec![Vec::new(); n + 1];
+        for &b in &order {
+            for &s in succs(b) {
+                preds[s].push(b);
+            }
+        }
+
+        let mut idom = vec![None; n + 1];
+        idom[root] = Some(root);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &b in order.iter().rev().filter(|&&b| b != root) {
+                let mut new: Option<usize> = None;
+                for &p in &preds[b] {
+                    if idom[p].is_none() {
+                        continue;
+                    }
+                    new = Some(match new {
+                        None => p,
+                        Some(mut a) => {
+                            let mut p = p;
+                            while a != p {
+                                while position[a] < position[p] {
+                                    a = idom[a].unwrap();
+                                }
+                                while position[p] < position[a] {
+                                    p = idom[p].unwrap();
+                                }
+                            }
+                            a
+                        }
+                    });
+                }
+                if new.is_some() && idom[b] != new {
+                    idom[b] = new;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut children = vec![Vec::new(); n + 1];
+        for &b in order.iter().rev().filter(|&&b| b != root) {
+            if let Some(d) = idom[b] {
+                children[d].push(b);
+            }
+        }
+        let mut pre = vec![usize::MAX; n + 1];
+        let mut last = vec![usize::MAX; n + 1];
+        let mut count = 0;
+        let mut stack = vec![(root, 0)];
+        pre[root] = count;
+        while let Some((b, next)) = stack.pop() {
+            if let Some(&c) = children[b].get(next) {
+                stack.push((b, next + 1));
+                count += 1;
+                pre[c] = count;
+                stack.push((c, 0));
+            } else {
+                last[b] = count;
+            }
+        }
+
+        let reachable = visited[..n].to_vec();
+        pre.truncate(n);
+        last.truncate(n);
+        Dominators {
+            reachable,
+            pre,
+            last,
+        }
+    }
+
+    /// Whether every path from an entry point to `b` goes through `a`.
+    pub fn dominates(&self, a: usize, b: usize) -> bool {
+        self.reachable[a]
+            && self.reachable[b]
+            && self.pre[a] <= self.pre[b]
+            && self.pre[b] <= self.last[a]
+    }
+}
+
+/// A natural loop: every block that reaches a back edge without passing
+/// through the header the edge returns to.
+#[derive(Debug, Clone)]
+pub struct Loop {
+    pub header: usize,
+    /// Blocks of the loop in layout order, header included.
+    pub blocks: Vec<usize>,
+    /// Blocks with a back edge to the header.
+    pub latches: Vec<usize>,
+    innermost: bool,
+}
+
+impl Loop {
+    /// Whether no other loop is nested inside this one.
+    pub fn is_innermost(&self) -> bool {
+        self.innermost
+    }
+}
+
+/// Natural loops of the program, one per header, in layout order.
+pub fn find(instructions: &[Instruction], cfg: &Cfg) -> Vec<Loop> {
+    let dominators = Dominators::analyze(instructions, cfg);
+    let mut preds = vec![Vec::new(); cfg.blocks.len()];
+    let mut latches: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (b, block) in cfg.blocks.iter().enumerate() {
+        if !dominators.reachable[b] {
+            continue;
+        }
+        for &s in &block.succs {
+            preds[s].push(b);
+            if dominators.dominates(s, b) {
+                latches.entry(s).or_default().push(b);
+            }
+        }
+    }
+
+    let mut loops: Vec<Loop> = latches
+        .into_iter()
+        .map(|(header, latches)| {
+            let mut blocks = BTreeSet::from([header]);
+            let mut work = latches.clone();
+            while let Some(b) = work.pop() {
+                if blocks.insert(b) {
+                    work.extend(preds[b].iter().copied());
+                }
+            }
+            Loop {
+                header,
+                blocks: blocks.into_iter().collect(),
+                latches,
+                innermost: true,
+            }
+        })
+        .collect();
+    loops.sort_by_key(|l| l.header);
+
+    // Loops with different headers are either disjoint or nested, so a loop
+    // holding another's header holds all of it.
+    let headers: HashSet<usize> = loops.iter().map(|l| l.header).collect();
+    for lp in &mut loops {
+        lp.innermost = !lp
+            .blocks
+            .iter()
+            .any(|&b| b != lp.header && headers.contains(&b));
+    }
+    loops
+}
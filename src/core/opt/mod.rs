@@ -0,0 +1,259 @@
+//! IR-level passes run by `CodeGenerator` before any native code is emitted.
+//!
+//! Every pass works on the parsed `Instruction` list, so it is shared by all
+//! backends. Target knowledge enters only through `Registers`, built from the
+//! backend's register map.
+
+use std::collections::{HashMap, HashSet};
+
+use super::Instruction;
+use crate::arch::Architecture;
+
+pub mod cfg;
+pub mod cse;
+pub mod dce;
+pub mod flags;
+pub mod inline;
+pub mod layout;
+pub mod liveness;
+pub mod loops;
+pub mod regalloc;
+pub mod schedule;
+pub mod size;
+pub mod strength;
+pub mod unroll;
+pub mod unwind;
+
+#[cfg(all(test, target_arch = "x86_64", target_os = "linux"))]
+mod tests;
+
+/// Pseudo register standing for the condition flags written by `cmp`/`test`
+/// and arithmetic, and read by `jCC`/`setCC`/`cmovCC`.
+pub const FLAGS: &str = "flags";
+
+/// A single line of the optimization report.
+#[derive(Debug, Clone)]
+pub struct OptNote {
+    pub pass: &'static str,
+    pub message: String,
+}
+
+impl std::fmt::Display for OptNote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.pass, self.message)
+    }
+}
+
+/// What the enabled passes changed, in the order they ran.
+#[derive(Debug, Clone, Default)]
+pub struct OptReport {
+    pub notes: Vec<OptNote>,
+}
+
+impl OptReport {
+    pub fn note(&mut self, pass: &'static str, message: String) {
+        self.notes.push(OptNote { pass, message });
+    }
+}
+
+/// Register names known to a target, resolved to the physical register they
+/// end up in. Virtual registers that share a physical register (AMD32 maps
+/// both `r0` and `r6` to `eax`) resolve to the same name, so the analyses see
+/// the aliasing the emitted code will have.
+#[derive(Debug, Clone)]
+pub struct Registers {
+    names: HashMap<String, String>,
+    all: HashSet<String>,
+    /// Virtual registers (`r0`, `r1`, ...) in numeric order.
+    virtuals: Vec<String>,
+    /// Registers without a physical register yet.
+    locals: HashSet<String>,
+}
+
+impl Registers {
+    pub fn new(register_map: &HashMap<String, String>) -> Self {
+        // A physical name that is also a virtual name (`r8` is `r10` on
+        // AMD64) means what the backend emits for it: the virtual one.
+        let mut names = HashMap::with_capacity(register_map.len() * 2);
+        for phys in register_map.values() {
+            names.insert(phys.clone(), phys.clone());
+        }
+        for (virt, phys) in register_map {
+            names.insert(virt.clone(), phys.clone());
+        }
+        let mut all: HashSet<String> = names.values().cloned().collect();
+        all.insert(FLAGS.to_string());
+
+        let mut virtuals: Vec<String> = register_map
+            .keys()
+            .filter(|name| virtual_index(name).is_some())
+            .cloned()
+            .collect();
+        virtuals.sort_by_key(|name| virtual_index(name));
+
+        Registers {
+            names,
+            all,
+            virtuals,
+            locals: HashSet::new(),
+        }
+    }
+
+    /// Like `new`, but `locals` are registers the allocator has yet to place:
+    /// each resolves to itself and none is part of `all()`, so calls and
+    /// returns do not keep them alive.
+    pub fn with_locals(register_map: &HashMap<String, String>, locals: &HashSet<String>) -> Self {
+        let fixed: HashMap<String, String> = register_map
+            .iter()
+            .filter(|(name, _)| !locals.contains(*name))
+            .map(|(name, phys)| (name.clone(), phys.clone()))
+            .collect();
+        let mut regs = Registers::new(&fixed);
+        for local in locals {
+            regs.names.insert(local.clone(), local.clone());
+        }
+        regs.locals = locals.clone();
+        regs
+    }
+
+    pub fn is_local(&self, name: &str) -> bool {
+        self.locals.contains(name)
+    }
+
+    /// Physical register behind `name`, if `name` is a register at all.
+    pub fn resolve(&self, name: &str) -> Option<&str> {
+        self.names.get(name).map(|s| s.as_str())
+    }
+
+    /// Every physical register plus `FLAGS`.
+    pub fn all(&self) -> &HashSet<String> {
+        &self.all
+    }
+
+    /// Virtual register names in numeric order.
+    pub fn virtuals(&self) -> &[String] {
+        &self.virtuals
+    }
+
+    /// Registers read by an operand, e.g. `r3` for `[r3 + 8]`.
+    pub fn in_operand(&self, operand: &str) -> Vec<String> {
+        identifiers(operand)
+            .filter_map(|token| self.resolve(token).map(str::to_string))
+            .collect()
+    }
+
+    /// The register written when `operand` is a destination; `None` for
+    /// memory operands.
+    pub fn as_destination(&self, operand: &str) -> Option<String> {
+        if is_memory(operand) {
+            None
+        } else {
+            self.resolve(operand.trim()).map(str::to_string)
+        }
+    }
+}
+
+pub fn virtual_index(name: &str) -> Option<u32> {
+    name.strip_prefix('r')?.parse().ok()
+}
+
+/// Target facts the passes need beyond register names.
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub architecture: Architecture,
+    /// General-purpose register width in bits.
+    pub width: u32,
+    /// Physical registers no pass may allocate: stack, frame and link
+    /// registers and the backend's own scratch registers.
+    pub reserved: HashSet<String>,
+    pub callee_saved: HashSet<String>,
+    /// Bytes one `push` moves the stack pointer by.
+    pub push_size: u32,
+    /// Whether the target keeps condition flags. Without them, each flag
+    /// reader compares its setter's operands itself (see `flags`).
+    pub flags: bool,
+}
+
+/// Splits an operand into the identifiers it mentions.
+pub fn identifiers(operand: &str) -> impl Iterator<Item = &str> {
+    operand
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$'))
+        .filter(|token| !token.is_empty() && !token.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// Splits an operand into identifiers and the text between them, marking
+/// which is which.
+pub fn tokens(operand: &str) -> impl Iterator<Item = (&str, bool)> {
+    let is_part = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$';
+    let mut rest = operand;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let part = is_part(first);
+        let end = rest
+            .find(|c: char| is_part(c) != part)
+            .unwrap_or(rest.len());
+        let (token, tail) = rest.split_at(end);
+        rest = tail;
+        Some((token, part && !first.is_ascii_digit()))
+    })
+}
+
+pub fn is_memory(operand: &str) -> bool {
+    operand.trim_start().starts_with('[')
+}
+
+/// Parses a decimal, `0x` hexadecimal or `0b` binary immediate.
+pub fn parse_immediate(operand: &str) -> Option<i64> {
+    let operand = operand.trim();
+    let (negative, digits) = match operand.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, operand),
+    };
+    let value = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        u64::from_str_radix(hex, 16).ok()? as i64
+    } else if let Some(bin) = digits
+        .strip_prefix("0b")
+        .or_else(|| digits.strip_prefix("0B"))
+    {
+        u64::from_str_radix(bin, 2).ok()? as i64
+    } else {
+        digits.parse::<i64>().ok()?
+    };
+    Some(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    })
+}
+
+/// Whether the instruction produces machine code, as opposed to labels,
+/// sections, data definitions and symbol directives.
+pub fn is_code(instruction: &Instruction) -> bool {
+    !matches!(
+        instruction,
+        Instruction::Label(_)
+            | Instruction::Section(_)
+            | Instruction::Global(_)
+            | Instruction::Function(_)
+            | Instruction::Extern(_)
+            | Instruction::Align(_)
+            | Instruction::Unroll(_)
+            | Instruction::Inline
+            | Instruction::NoInline
+            | Instruction::Likely
+            | Instruction::Unlikely
+            | Instruction::Loc(_)
+            | Instruction::Equ(_, _)
+            | Instruction::DataByte(_, _)
+            | Instruction::DataWord(_, _)
+            | Instruction::DataDword(_, _)
+            | Instruction::DataQword(_, _)
+            | Instruction::ReserveByte(_, _)
+            | Instruction::ReserveWord(_, _)
+            | Instruction::ReserveDword(_, _)
+            | Instruction::ReserveQword(_, _)
+    )
+}
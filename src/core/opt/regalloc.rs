@@ -0,0 +1,1156 @@
+//! Linear-scan register allocation.
+//!
+//! Names the target maps one-to-one onto an allocatable register (`r0`..`r13`
+//! on AMD64) keep that register: programs use them for arguments, return
+//! values and system calls. Every other `rN` - beyond the map, aliasing a
+//! lower name (AMD32 folds `r6` onto `eax`) or mapped to a reserved register -
+//! is local to the function it appears in and is placed here.
+//!
+//! Within a function, a local only takes a register that
+//! - the function and its callees never name or clobber,
+//! - no caller of the function names, so values callers keep in registers
+//!   across the call survive,
+//! - no `call` in the function clobbers, which leaves the callee-saved
+//!   registers once the function calls anything,
+//! - no instruction inside the local's live range uses implicitly.
+//!
+//! Callee-saved registers a returning function takes are pushed on entry and
+//! popped before each `ret`. Locals that do not fit are spilled to stack slots
+//! in a frame reserved on entry; `sp`-relative operands in the function are
+//! rebased past it. A function whose stack depth cannot be tracked, or that
+//! leaves through a jump while it has a frame, is left alone and reported.
+
+use std::collections::{HashMap, HashSet};
+
+use super::cfg::{Cfg, branch_target, destination, is_terminator, operands_mut, referenced_names};
+use super::liveness::{Effects, Liveness, effects, step};
+use super::{
+    FLAGS, OptReport, Registers, Target, identifiers, is_code, is_memory, parse_immediate,
+    virtual_index,
+};
+use crate::arch::Architecture;
+use crate::core::{Instruction, Section};
+
+const PASS: &str = "regalloc";
+
+/// The register names `run` places: every `rN` in `instructions` that is not
+/// the lowest name of an allocatable register. A name that is also a reserved
+/// register is placed too, so `r13` does not overwrite `sp` on ARM32, and so
+/// is a name missing from the map even where it spells a register: `r14` is
+/// not the register AMD64 keeps `r12` in.
+pub fn locals(
+    instructions: &[Instruction],
+    register_map: &HashMap<String, String>,
+    target: &Target,
+) -> HashSet<String> {
+    let mut home: HashMap<&str, &str> = HashMap::new();
+    for (name, phys) in register_map {
+        if virtual_index(name).is_none() {
+            continue;
+        }
+        match home.get(phys.as_str()) {
+            Some(current) if virtual_index(current) < virtual_index(name) => {}
+            _ => {
+                home.insert(phys, name);
+            }
+        }
+    }
+
+    let mut locals = HashSet::new();
+    for instruction in instructions.iter().filter(|i| is_code(i)) {
+        for name in referenced_names(instruction) {
+            if virtual_index(name).is_none() {
+                continue;
+            }
+            let fixed = match register_map.get(name) {
+                Some(phys) if target.reserved.contains(phys) => false,
+                Some(phys) => phys == name || home.get(phys.as_str()) == Some(&name),
+                None => false,
+            };
+            if !fixed {
+                locals.insert(name.to_string());
+            }
+        }
+    }
+    locals
+}
+
+/// The first `rN` in `instructions` that the target has no register for,
+/// which only `run` can give a place.
+pub fn unmapped<'a>(
+    instructions: &'a [Instruction],
+    register_map: &HashMap<String, String>,
+) -> Option<&'a str> {
+    instructions
+        .iter()
+        .filter(|i| is_code(i))
+        .flat_map(referenced_names)
+        .find(|name| virtual_index(name).is_some() && !register_map.contains_key(*name))
+}
+
+/// The `rN` names code in `instructions` refers to.
+pub fn names(instructions: &[Instruction]) -> HashSet<&str> {
+    instructions
+        .iter()
+        .filter(|i| is_code(i))
+        .flat_map(referenced_names)
+        .filter(|name| virtual_index(name).is_some())
+        .collect()
+}
+
+/// Copies `instructions`, renaming each of their `names` beyond the map that
+/// spells a register the map gives another name (`r14` holds `r12` on
+/// AMD64) to a fresh `rN`, so that the passes do not take the local and the
+/// fixed name for one register.
+pub fn separate(
+    instructions: &[Instruction],
+    names: &HashSet<&str>,
+    register_map: &HashMap<String, String>,
+    report: &mut OptReport,
+) -> Vec<Instruction> {
+    let physical: HashSet<&str> = register_map.values().map(String::as_str).collect();
+    let mut clashing: Vec<String> = names
+        .iter()
+        .filter(|name| !register_map.contains_key(**name) && physical.contains(**name))
+        .map(|name| name.to_string())
+        .collect();
+    if clashing.is_empty() {
+        return instructions.to_vec();
+    }
+    clashing.sort_by_key(|name| virtual_index(name));
+    let next = names
+        .iter()
+        .copied()
+        .chain(register_map.keys().map(String::as_str))
+        .filter_map(virtual_index)
+        .max()
+        .map_or(0, |index| index + 1);
+    let mut instructions = instructions.to_vec();
+    let rename: HashMap<&str, String> = clashing
+        .iter()
+        .zip(next..)
+        .map(|(name, index)| (name.as_str(), format!("r{}", index)))
+        .collect();
+    for name in &clashing {
+        report.note(
+            PASS,
+            format!(
+                "placing `{}` as `{}`: the target keeps another name in {}",
+                name,
+                rename[name.as_str()],
+                name
+            ),
+        );
+    }
+    for instruction in instructions.iter_mut().filter(|i| is_code(i)) {
+        for operand in operands_mut(instruction) {
+            *operand = rename_operand(operand, &rename);
+        }
+    }
+    instructions
+}
+
+/// Places the locals of `regs` (see `Registers::with_locals`). `clobbers`
+/// gives the registers an instruction's expansion overwrites beyond its
+/// operands.
+pub fn run(
+    instructions: Vec<Instruction>,
+    regs: &Registers,
+    target: &Target,
+    clobbers: &dyn Fn(&Instruction) -> Vec<String>,
+    report: &mut OptReport,
+) -> Vec<Instruction> {
+    let Some(sp) = regs.resolve("sp").map(str::to_string) else {
+        return instructions;
+    };
+    let cfg = Cfg::build(&instructions);
+    let functions = functions(&instructions, &cfg);
+    if functions.is_empty() {
+        return instructions;
+    }
+
+    let sb = regs.resolve("sb");
+    let mut home: HashMap<String, String> = HashMap::new();
+    let mut allocatable = Vec::new();
+    for name in regs.virtuals() {
+        let Some(phys) = regs.resolve(name) else {
+            continue;
+        };
+        if target.reserved.contains(phys) || phys == sp || Some(phys) == sb {
+            continue;
+        }
+        if !home.contains_key(phys) {
+            home.insert(phys.to_string(), name.clone());
+            allocatable.push(phys.to_string());
+        }
+    }
+    // Caller-saved registers first: they need no saving.
+    allocatable.sort_by_key(|phys| target.callee_saved.contains(phys));
+
+    let allocator = Allocator {
+        instructions: &instructions,
+        regs,
+        target,
+        sp,
+        home,
+        effects: instructions.iter().map(|i| effects(i, regs)).collect(),
+        implicit: instructions.iter().map(clobbers).collect(),
+        liveness: Liveness::analyze(&instructions, &cfg, regs),
+        cfg,
+        allocatable,
+    };
+
+    let touched: Vec<HashSet<String>> = functions
+        .iter()
+        .map(|f| allocator.touched(f, true))
+        .collect();
+    let named: Vec<HashSet<String>> = functions
+        .iter()
+        .map(|f| allocator.touched(f, false))
+        .collect();
+    let callees = transitive(functions.iter().map(|f| f.callees.clone()).collect());
+
+    let mut rewritten: Vec<Option<Vec<Instruction>>> = vec![None; functions.len()];
+    let mut entries = HashSet::new();
+    for (n, function) in functions.iter().enumerate() {
+        let mut unavailable = named[n].clone();
+        for &callee in &callees[n] {
+            unavailable.extend(touched[callee].iter().cloned());
+        }
+        for (caller, below) in callees.iter().enumerate() {
+            if below.contains(&n) {
+                unavailable.extend(named[caller].iter().cloned());
+            }
+        }
+        for i in function.start..function.end {
+            if matches!(allocator.instructions[i], Instruction::Call(_)) {
+                unavailable.extend(allocator.implicit[i].iter().cloned());
+            }
+        }
+        let pool: Vec<String> = allocator
+            .allocatable
+            .iter()
+            .filter(|phys| !unavailable.contains(*phys))
+            .cloned()
+            .collect();
+
+        match allocator.allocate(function, &pool) {
+            Ok(None) => {}
+            Ok(Some(Allocation {
+                code,
+                message,
+                prologue,
+            })) => {
+                report.note(PASS, message);
+                if prologue
+                    && function.jumped_to
+                    && let Some(name) = &function.name
+                {
+                    entries.insert(name.clone());
+                }
+                rewritten[n] = Some(code);
+            }
+            Err(reason) => report.note(
+                PASS,
+                format!("left {} unallocated: {}", function.describe(), reason),
+            ),
+        }
+    }
+
+    let mut output = Vec::with_capacity(instructions.len());
+    let mut next = 0;
+    for (function, code) in functions.iter().zip(rewritten) {
+        if let Some(code) = code {
+            output.extend_from_slice(&instructions[next..function.start]);
+            output.extend(code);
+            next = function.end;
+        }
+    }
+    output.extend_from_slice(&instructions[next..]);
+
+    // Jumps back to a function's entry must not run its prologue again.
+    if !entries.is_empty() {
+        for instruction in &mut output {
+            if branch_target(instruction).is_some_and(|t| entries.contains(t)) {
+                for operand in operands_mut(instruction) {
+                    *operand = body_label(operand);
+                }
+            }
+        }
+    }
+    output
+}
+
+struct Function {
+    /// The entry label; `None` for code at the start of a section.
+    name: Option<String>,
+    start: usize,
+    end: usize,
+    callees: Vec<usize>,
+    /// Whether a jump targets the entry label.
+    jumped_to: bool,
+}
+
+impl Function {
+    fn describe(&self) -> String {
+        match &self.name {
+            Some(name) => format!("`{}`", name),
+            None => format!("the code at instruction {}", self.start),
+        }
+    }
+}
+
+/// Splits the program into functions: each runs from an entry point (a
+/// `call` target, a global symbol, a label whose address is taken, or the
+/// start of a text section) to the next one.
+fn functions(instructions: &[Instruction], cfg: &Cfg) -> Vec<Function> {
+    let mut entries: HashSet<&str> = HashSet::new();
+    for instruction in instructions {
+        match instruction {
+            Instruction::Call(name) | Instruction::Global(name) | Instruction::Function(name) => {
+                entries.insert(name.as_str());
+            }
+            Instruction::Label(_) => {}
+            _ if branch_target(instruction).is_some() => {}
+            _ => entries.extend(referenced_names(instruction)),
+        }
+    }
+    entries.retain(|name| cfg.labels.contains_key(*name));
+
+    let mut functions = Vec::new();
+    let mut current = Some((None, 0));
+    let mut in_text = true;
+    for (i, instruction) in instructions.iter().enumerate() {
+        let opened = match instruction {
+            Instruction::Section(section) => {
+                in_text = matches!(section, Section::Text);
+                Some(in_text.then_some((None, i)))
+            }
+            Instruction::Label(name) if in_text && entries.contains(name.as_str()) => {
+                Some(Some((Some(name.clone()), i)))
+            }
+            _ => None,
+        };
+        if let Some(opened) = opened {
+            if let Some((name, start)) = current.take() {
+                functions.push(Function {
+                    name,
+                    start,
+                    end: i,
+                    callees: Vec::new(),
+                    jumped_to: false,
+                });
+            }
+            current = opened;
+        }
+    }
+    if let Some((name, start)) = current {
+        functions.push(Function {
+            name,
+            start,
+            end: instructions.len(),
+            callees: Vec::new(),
+            jumped_to: false,
+        });
+    }
+    functions.retain(|f| instructions[f.start..f.end].iter().any(is_code));
+
+    let jumped: HashSet<&str> = instructions.iter().filter_map(branch_target).collect();
+    for function in &mut functions {
+        function.jumped_to = function
+            .name
+            .as_ref()
+            .is_some_and(|name| jumped.contains(name.as_str()));
+    }
+
+    let index: HashMap<String, usize> = functions
+        .iter()
+        .enumerate()
+        .filter_map(|(n, f)| Some((f.name.clone()?, n)))
+        .collect();
+    for function in &mut functions {
+        for instruction in &instructions[function.start..function.end] {
+            if let Instruction::Call(name) = instruction
+                && let Some(&callee) = index.get(name)
+                && !function.callees.contains(&callee)
+            {
+                function.callees.push(callee);
+            }
+        }
+    }
+    functions
+}
+
+/// Everything reachable from each node, following `edges` one or more times.
+fn transitive(edges: Vec<Vec<usize>>) -> Vec<HashSet<usize>> {
+    (0..edges.len())
+        .map(|start| {
+            let mut seen = HashSet::new();
+            let mut work = edges[start].clone();
+            while let Some(n) = work.pop() {
+                if seen.insert(n) {
+                    work.extend(edges[n].iter().copied());
+                }
+            }
+            seen
+        })
+        .collect()
+}
+
+fn body_label(name: &str) -> String {
+    format!("{}__body", name)
+}
+
+/// Bytes pushed since the end of the prologue.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Depth {
+    Unreached,
+    Known(i64),
+    Unknown,
+}
+
+impl Depth {
+    fn meet(self, other: Depth) -> Depth {
+        match (self, other) {
+            (Depth::Unreached, d) | (d, Depth::Unreached) => d,
+            (Depth::Known(a), Depth::Known(b)) if a == b => Depth::Known(a),
+            _ => Depth::Unknown,
+        }
+    }
+}
+
+struct Interval {
+    name: String,
+    start: usize,
+    end: usize,
+    /// Registers instructions inside the range use implicitly.
+    forbidden: HashSet<String>,
+}
+
+/// A function's code after allocation.
+struct Allocation {
+    code: Vec<Instruction>,
+    /// The report line.
+    message: String,
+    /// Whether the code starts with a prologue.
+    prologue: bool,
+}
+
+/// A local and the stack slot it was spilled to.
+struct Spill {
+    name: String,
+    slot: i64,
+}
+
+/// A flag setter whose readers are still ahead, on a target without
+/// condition flags.
+struct Readers {
+    /// The last reader.
+    last: usize,
+    /// Registers the setter reads, which stay put until `last`.
+    held: HashSet<String>,
+    /// Temps pushed before the setter, popped after `last`.
+    saved: Vec<String>,
+    /// Branches before `last`, retargeted to a label restoring `saved`, and
+    /// where they went.
+    exits: Vec<(String, String)>,
+}
+
+struct Allocator<'a> {
+    instructions: &'a [Instruction],
+    regs: &'a Registers,
+    target: &'a Target,
+    sp: String,
+    /// The fixed name of every allocatable register.
+    home: HashMap<String, String>,
+    allocatable: Vec<String>,
+    cfg: Cfg,
+    liveness: Liveness,
+    effects: Vec<Effects>,
+    implicit: Vec<Vec<String>>,
+}
+
+impl Allocator<'_> {
+    /// Allocatable registers the function names through fixed names, plus
+    /// those its instructions clobber when `clobbered`.
+    fn touched(&self, function: &Function, clobbered: bool) -> HashSet<String> {
+        let mut touched = HashSet::new();
+        for i in function.start..function.end {
+            for name in referenced_names(&self.instructions[i]) {
+                if let Some(phys) = self.regs.resolve(name)
+                    && !self.regs.is_local(name)
+                {
+                    touched.insert(phys.to_string());
+                }
+            }
+            if clobbered {
+                touched.extend(self.implicit[i].iter().cloned());
+            }
+        }
+        touched.retain(|phys| self.home.contains_key(phys));
+        touched
+    }
+
+    fn blocks(&self, function: &Function) -> Vec<usize> {
+        (0..self.cfg.blocks.len())
+            .filter(|&b| {
+                let block = &self.cfg.blocks[b];
+                block.in_text && (function.start..function.end).contains(&block.start)
+            })
+            .collect()
+    }
+
+    /// Live range of every local in the function, in instruction indices.
+    fn intervals(&self, function: &Function) -> Vec<Interval> {
+        let mut ranges: HashMap<String, (usize, usize)> = HashMap::new();
+        let mut extend = |name: &str, i: usize| {
+            let range = ranges.entry(name.to_string()).or_insert((i, i));
+            range.0 = range.0.min(i);
+            range.1 = range.1.max(i);
+        };
+        for b in self.blocks(function) {
+            let block = &self.cfg.blocks[b];
+            let mut live = self.liveness.live_out[b].clone();
+            for i in (block.start..block.end).rev() {
+                let e = &self.effects[i];
+                for name in live.iter().chain(&e.defs).chain(&e.uses) {
+                    if self.regs.is_local(name) {
+                        extend(name, i);
+                    }
+                }
+                for name in referenced_names(&self.instructions[i]) {
+                    if self.regs.is_local(name) {
+                        extend(name, i);
+                    }
+                }
+                step(&mut live, e, self.regs);
+            }
+        }
+        for i in function.start..function.end {
+            if let Some(last) = self.last_reader(i, function) {
+                for name in referenced_names(&self.instructions[i]) {
+                    if self.regs.is_local(name) {
+                        extend(name, last);
+                    }
+                }
+            }
+        }
+
+        let mut intervals: Vec<Interval> = ranges
+            .into_iter()
+            .map(|(name, (start, end))| Interval {
+                forbidden: self.implicit[start..=end]
+                    .iter()
+                    .flatten()
+                    .cloned()
+                    .collect(),
+                name,
+                start,
+                end,
+            })
+            .collect();
+        intervals.sort_by(|a, b| (a.start, a.end, &a.name).cmp(&(b.start, b.end, &b.name)));
+        intervals
+    }
+
+    /// On a target without condition flags, the last instruction reading the
+    /// flags `i` sets. Each reader compares what `i` read itself, so the
+    /// registers holding it must not change before then.
+    fn last_reader(&self, i: usize, function: &Function) -> Option<usize> {
+        let sets = |e: &Effects| e.defs.iter().any(|d| d == FLAGS);
+        if self.target.flags || !is_code(&self.instructions[i]) || !sets(&self.effects[i]) {
+            return None;
+        }
+        let mut last = None;
+        for j in i + 1..function.end {
+            let instruction = &self.instructions[j];
+            if matches!(instruction, Instruction::Label(_)) {
+                break;
+            }
+            if !is_code(instruction) {
+                continue;
+            }
+            // Instructions that update only some flags read the rest, but
+            // only rotates through the carry look at them.
+            let e = &self.effects[j];
+            if e.uses.iter().any(|u| u == FLAGS)
+                && (!sets(e) || matches!(instruction, Instruction::Rcl(_) | Instruction::Rcr(_)))
+            {
+                last = Some(j);
+            }
+            if sets(e) || e.barrier || is_terminator(instruction) {
+                break;
+            }
+        }
+        last
+    }
+
+    /// Stack depth before every instruction of the function.
+    fn depths(&self, function: &Function) -> Vec<Depth> {
+        let blocks = self.blocks(function);
+        let mut entry = vec![Depth::Unreached; self.cfg.blocks.len()];
+        let mut work = Vec::new();
+        if let Some(&first) = blocks.first() {
+            entry[first] = Depth::Known(0);
+            work.push(first);
+        }
+        while let Some(b) = work.pop() {
+            let block = &self.cfg.blocks[b];
+            let mut depth = entry[b];
+            for i in block.start..block.end {
+                depth = self.step_depth(&self.instructions[i], depth);
+            }
+            for &s in &block.succs {
+                if !blocks.contains(&s) {
+                    continue;
+                }
+                let met = entry[s].meet(depth);
+                if met != entry[s] {
+                    entry[s] = met;
+                    work.push(s);
+                }
+            }
+        }
+
+        let mut depths = vec![Depth::Unreached; function.end - function.start];
+        for b in blocks {
+            let block = &self.cfg.blocks[b];
+            let mut depth = entry[b];
+            for i in block.start..block.end {
+                depths[i - function.start] = depth;
+                depth = self.step_depth(&self.instructions[i], depth);
+            }
+        }
+        depths
+    }
+
+    fn step_depth(&self, instruction: &Instruction, depth: Depth) -> Depth {
+        use Instruction::*;
+        let Depth::Known(d) = depth else {
+            return depth;
+        };
+        let push = i64::from(self.target.push_size);
+        let is_sp = |operand: &str| self.is_sp(operand);
+        match instruction {
+            Push(_) => Depth::Known(d + push),
+            Pop(op) if !is_sp(op) => Depth::Known(d - push),
+            Sub((op, n)) if is_sp(op) => {
+                parse_immediate(n).map_or(Depth::Unknown, |n| Depth::Known(d + n))
+            }
+            Add((op, n)) if is_sp(op) => {
+                parse_immediate(n).map_or(Depth::Unknown, |n| Depth::Known(d - n))
+            }
+            Lea((op, address)) if is_sp(op) => self
+                .stack_offset(address)
+                .map_or(Depth::Unknown, |n| Depth::Known(d - n)),
+            Enter(_) | Leave | Pusha | Popa => Depth::Unknown,
+            _ if destination(instruction).is_some_and(is_sp) => Depth::Unknown,
+            _ => depth,
+        }
+    }
+
+    fn is_sp(&self, operand: &str) -> bool {
+        self.regs.as_destination(operand).as_deref() == Some(self.sp.as_str())
+    }
+
+    /// `n` for an operand `[sp]`, `[sp + n]` or `[sp - n]`.
+    fn stack_offset(&self, operand: &str) -> Option<i64> {
+        let inner = operand.trim().strip_prefix('[')?.strip_suffix(']')?.trim();
+        let (base, offset) = match inner.find(['+', '-']) {
+            Some(at) => {
+                let n = parse_immediate(&inner[at + 1..])?;
+                (
+                    &inner[..at],
+                    if inner[at..].starts_with('-') { -n } else { n },
+                )
+            }
+            None => (inner, 0),
+        };
+        (self.regs.resolve(base.trim()) == Some(self.sp.as_str())).then_some(offset)
+    }
+
+    fn mentions_sp(&self, operand: &str) -> bool {
+        identifiers(operand).any(|name| self.regs.resolve(name) == Some(self.sp.as_str()))
+    }
+
+    /// Allocates one function; `None` when it has no locals.
+    fn allocate(&self, function: &Function, pool: &[String]) -> Result<Option<Allocation>, String> {
+        let intervals = self.intervals(function);
+        if intervals.is_empty() {
+            return Ok(None);
+        }
+
+        // Linear scan. When every register is taken, the interval that ends
+        // last goes to the stack.
+        let mut assigned: HashMap<usize, String> = HashMap::new();
+        let mut spilled: Vec<usize> = Vec::new();
+        let mut active: Vec<usize> = Vec::new();
+        for (k, interval) in intervals.iter().enumerate() {
+            active.retain(|&a| intervals[a].end >= interval.start);
+            let free = pool.iter().find(|phys| {
+                !interval.forbidden.contains(*phys) && !active.iter().any(|a| &assigned[a] == *phys)
+            });
+            if let Some(phys) = free {
+                assigned.insert(k, phys.clone());
+                active.push(k);
+                continue;
+            }
+            let victim = active
+                .iter()
+                .copied()
+                .filter(|a| {
+                    intervals[*a].end > interval.end && !interval.forbidden.contains(&assigned[a])
+                })
+                .max_by_key(|a| intervals[*a].end);
+            match victim {
+                Some(a) => {
+                    let phys = assigned.remove(&a).unwrap_or_default();
+                    active.retain(|&x| x != a);
+                    spilled.push(a);
+                    assigned.insert(k, phys);
+                    active.push(k);
+                }
+                None => spilled.push(k),
+            }
+        }
+        spilled.sort_unstable();
+
+        let slot_size = i64::from(self.target.width / 8);
+        let push = i64::from(self.target.push_size);
+        let spills: Vec<Spill> = spilled
+            .iter()
+            .enumerate()
+            .map(|(slot, &k)| Spill {
+                name: intervals[k].name.clone(),
+                slot: slot as i64 * slot_size,
+            })
+            .collect();
+
+        let code = &self.instructions[function.start..function.end];
+        let returns = code.iter().any(|i| matches!(i, Instruction::Ret));
+        let mut saved: Vec<String> = Vec::new();
+        if returns {
+            for phys in pool {
+                if self.target.callee_saved.contains(phys) && assigned.values().any(|p| p == phys) {
+                    saved.push(phys.clone());
+                }
+            }
+        }
+        let saved_bytes = saved.len() as i64 * push;
+        let frame = if spills.is_empty() && saved.is_empty() {
+            0
+        } else {
+            (saved_bytes + spills.len() as i64 * slot_size + 15) / 16 * 16 - saved_bytes
+        };
+        let frame_bytes = saved_bytes + frame;
+
+        if frame_bytes > 0
+            && let Some(reason) = self.leaves(function)
+        {
+            return Err(reason);
+        }
+        let depths = self.depths(function);
+
+        let x86 = matches!(
+            self.target.architecture,
+            Architecture::AMD64 | Architecture::AMD32
+        );
+        // Adjusting `sp` through `lea` keeps the flags on x86.
+        let adjust = |bytes: i64| {
+            if x86 {
+                Instruction::Lea(("sp".to_string(), stack_address(bytes)))
+            } else if bytes < 0 {
+                Instruction::Sub(("sp".to_string(), (-bytes).to_string()))
+            } else {
+                Instruction::Add(("sp".to_string(), bytes.to_string()))
+            }
+        };
+        let name_of = |phys: &str| self.home[phys].clone();
+
+        let mut rename: HashMap<&str, String> = HashMap::new();
+        for (k, phys) in &assigned {
+            rename.insert(intervals[*k].name.as_str(), name_of(phys));
+        }
+        let slots: HashMap<&str, i64> = spills.iter().map(|s| (s.name.as_str(), s.slot)).collect();
+
+        let mut output = Vec::with_capacity(code.len() + 8);
+        let mut body = function.start;
+        if matches!(code[0], Instruction::Label(_) | Instruction::Section(_)) {
+            output.push(code[0].clone());
+            body += 1;
+        }
+        if frame_bytes > 0 {
+            for phys in &saved {
+                output.push(Instruction::Push(name_of(phys)));
+            }
+            if frame > 0 {
+                output.push(adjust(-frame));
+            }
+            if let Some(name) = &function.name
+                && function.jumped_to
+            {
+                output.push(Instruction::Label(body_label(name)));
+            }
+        }
+
+        // Without condition flags, a setter's readers compare its operands
+        // themselves, so the registers holding them stay put until the last
+        // reader. Temps saved for the setter are restored after it, and on
+        // the way out of every branch in between.
+        let mut open: Option<Readers> = None;
+        for i in body..function.end {
+            let instruction = &self.instructions[i];
+            if !is_code(instruction) {
+                output.push(instruction.clone());
+                continue;
+            }
+            if matches!(instruction, Instruction::Ret) && frame_bytes > 0 {
+                if frame > 0 {
+                    output.push(adjust(frame));
+                }
+                for phys in saved.iter().rev() {
+                    output.push(Instruction::Pop(name_of(phys)));
+                }
+                output.push(Instruction::Ret);
+                continue;
+            }
+
+            let kept = open.as_ref().map_or(0, |r| r.saved.len() as i64 * push);
+            let held = |phys: &String| open.as_ref().is_some_and(|r| r.held.contains(phys));
+            if kept > 0
+                && (self.step_depth(instruction, Depth::Known(0)) != Depth::Known(0)
+                    || matches!(instruction, Instruction::Call(_)))
+            {
+                return Err(format!("no register to reload into at `{}`", instruction));
+            }
+            let last = self.last_reader(i, function);
+            let closes = open.as_ref().is_some_and(|r| r.last == i);
+
+            let mentions = |j: usize| {
+                let mut mentioned: HashSet<String> = HashSet::new();
+                for name in referenced_names(&self.instructions[j]) {
+                    if let Some(phys) = self.regs.resolve(name) {
+                        mentioned.insert(phys.to_string());
+                    }
+                    if let Some(name) = rename.get(name)
+                        && let Some(phys) = self.regs.resolve(name)
+                    {
+                        mentioned.insert(phys.to_string());
+                    }
+                }
+                mentioned
+            };
+            let mut here: Vec<&str> = Vec::new();
+            for name in referenced_names(instruction) {
+                if slots.contains_key(name) && !here.contains(&name) {
+                    here.push(name);
+                }
+            }
+            let depth = match depths[i - function.start] {
+                Depth::Known(d) => Some(d),
+                _ => None,
+            };
+            let mut temps: Vec<(String, bool)> = Vec::new();
+            if here.is_empty() {
+                let mut rewritten = self.rewrite(instruction, &rename, frame_bytes + kept)?;
+                if kept > 0
+                    && let Some(target) = branch_target(instruction)
+                    && let Some(r) = open.as_mut()
+                {
+                    let exit = format!("__regalloc_exit{}", i);
+                    r.exits.push((exit.clone(), target.to_string()));
+                    if let Some(operand) = operands_mut(&mut rewritten).pop() {
+                        *operand = exit;
+                    }
+                }
+                output.push(rewritten);
+            } else {
+                let Some(depth) = depth else {
+                    return Err(format!("unknown stack depth at `{}`", instruction));
+                };
+                let slot =
+                    |name: &str, pushed: i64| stack_address(depth + kept + pushed + slots[name]);
+
+                // Registers to carry the spilled values through this
+                // instruction, and through its readers if it sets the flags:
+                // free ones from the pool, otherwise any other register,
+                // saved around them.
+                let through = last.unwrap_or(i);
+                let busy: HashSet<&str> = intervals
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, iv)| iv.start <= through && iv.end >= i)
+                    .filter_map(|(k, _)| assigned.get(&k).map(String::as_str))
+                    .collect();
+                let mut mentioned: HashSet<String> = HashSet::new();
+                let mut implicit: HashSet<&String> = HashSet::new();
+                for j in i..=through {
+                    mentioned.extend(mentions(j));
+                    implicit.extend(&self.implicit[j]);
+                }
+                for _ in &here {
+                    let taken = |phys: &String| temps.iter().any(|(t, _)| t == phys);
+                    let usable = |phys: &&String| {
+                        !implicit.contains(*phys)
+                            && !mentioned.contains(*phys)
+                            && !taken(phys)
+                            && !held(phys)
+                    };
+                    let free = pool
+                        .iter()
+                        .filter(usable)
+                        .find(|phys| !busy.contains(phys.as_str()));
+                    let temp = match free {
+                        Some(phys) => (phys.clone(), false),
+                        None => match self.allocatable.iter().find(usable) {
+                            Some(phys) => (phys.clone(), true),
+                            None => {
+                                return Err(format!(
+                                    "no register to reload into at `{}`",
+                                    instruction
+                                ));
+                            }
+                        },
+                    };
+                    temps.push(temp);
+                }
+
+                let (name, (temp, save)) = (here[0], &temps[0]);
+                let temp_name = name_of(temp);
+                match instruction {
+                    Instruction::Push(operand) if operand.trim() == name => {
+                        if *save {
+                            output.push(Instruction::Push(temp_name.clone()));
+                            output.push(Instruction::Push(temp_name.clone()));
+                            output
+                                .push(Instruction::Load((temp_name.clone(), slot(name, 2 * push))));
+                            output
+                                .push(Instruction::Store((stack_address(push), temp_name.clone())));
+                            output.push(Instruction::Pop(temp_name));
+                        } else {
+                            output.push(Instruction::Load((temp_name.clone(), slot(name, 0))));
+                            output.push(Instruction::Push(temp_name));
+                        }
+                        continue;
+                    }
+                    Instruction::Pop(operand) if operand.trim() == name => {
+                        if *save {
+                            output.push(Instruction::Push(temp_name.clone()));
+                            output
+                                .push(Instruction::Load((temp_name.clone(), stack_address(push))));
+                            output.push(Instruction::Store((slot(name, push), temp_name.clone())));
+                            output.push(Instruction::Load((temp_name.clone(), stack_address(0))));
+                            output
+                                .push(Instruction::Store((stack_address(push), temp_name.clone())));
+                            output.push(Instruction::Pop(temp_name.clone()));
+                            output.push(Instruction::Pop(temp_name));
+                        } else {
+                            output.push(Instruction::Pop(temp_name.clone()));
+                            output.push(Instruction::Store((slot(name, -push), temp_name)));
+                        }
+                        continue;
+                    }
+                    _ => {}
+                }
+
+                let pushed = temps.iter().filter(|(_, save)| *save).count() as i64 * push;
+                if pushed > 0
+                    && (destination(instruction).is_some_and(|d| self.is_sp(d))
+                        || closes && kept > 0 && last.is_some())
+                {
+                    return Err(format!("no register to reload into at `{}`", instruction));
+                }
+                let mut local_rename = rename.clone();
+                for (name, (temp, _)) in here.iter().zip(&temps) {
+                    local_rename.insert(name, name_of(temp));
+                }
+                let e = &self.effects[i];
+                let after = match self.step_depth(instruction, Depth::Known(depth)) {
+                    Depth::Known(d) => d - depth,
+                    _ => 0,
+                };
+
+                for (temp, save) in &temps {
+                    if *save {
+                        output.push(Instruction::Push(name_of(temp)));
+                    }
+                }
+                for (name, (temp, _)) in here.iter().zip(&temps) {
+                    let name = name.to_string();
+                    if e.uses.contains(&name) || !e.defs.contains(&name) {
+                        output.push(Instruction::Load((name_of(temp), slot(&name, pushed))));
+                    }
+                }
+                output.push(self.rewrite(
+                    instruction,
+                    &local_rename,
+                    frame_bytes + kept + pushed,
+                )?);
+                for (name, (temp, _)) in here.iter().zip(&temps) {
+                    let name = name.to_string();
+                    if e.defs.contains(&name) {
+                        output.push(Instruction::Store((
+                            slot(&name, pushed + after),
+                            name_of(temp),
+                        )));
+                    }
+                }
+                if last.is_none() {
+                    for (temp, save) in temps.iter().rev() {
+                        if *save {
+                            output.push(Instruction::Pop(name_of(temp)));
+                        }
+                    }
+                }
+            }
+
+            if closes && let Some(readers) = open.take() {
+                let restore = |output: &mut Vec<Instruction>| {
+                    for phys in readers.saved.iter().rev() {
+                        output.push(Instruction::Pop(name_of(phys)));
+                    }
+                };
+                restore(&mut output);
+                if !readers.exits.is_empty() {
+                    let join = format!("__regalloc_join{}", i);
+                    output.push(Instruction::Jmp(join.clone()));
+                    for (exit, target) in readers.exits {
+                        output.push(Instruction::Label(exit));
+                        restore(&mut output);
+                        output.push(Instruction::Jmp(target));
+                    }
+                    output.push(Instruction::Label(join));
+                }
+            }
+            if let Some(last) = last {
+                let mut held = mentions(i);
+                held.extend(temps.iter().map(|(temp, _)| temp.clone()));
+                open = Some(Readers {
+                    last,
+                    held,
+                    saved: temps
+                        .into_iter()
+                        .filter_map(|(temp, save)| save.then_some(temp))
+                        .collect(),
+                    exits: Vec::new(),
+                });
+            }
+        }
+
+        let mut placed: Vec<(&str, &str)> = assigned
+            .iter()
+            .map(|(k, phys)| (intervals[*k].name.as_str(), phys.as_str()))
+            .collect();
+        placed.sort_by_key(|(name, _)| virtual_index(name));
+        let mut parts: Vec<String> = Vec::new();
+        if !placed.is_empty() {
+            let list: Vec<String> = placed
+                .iter()
+                .map(|(name, phys)| format!("`{}` in `{}`", name, phys))
+                .collect();
+            parts.push(list.join(", "));
+        }
+        if !spills.is_empty() {
+            let list: Vec<String> = spills.iter().map(|s| format!("`{}`", s.name)).collect();
+            parts.push(format!("spilled {}", list.join(", ")));
+        }
+        if !saved.is_empty() {
+            let list: Vec<String> = saved.iter().map(|phys| format!("`{}`", phys)).collect();
+            parts.push(format!("saved {}", list.join(", ")));
+        }
+        if frame > 0 {
+            parts.push(format!("{}-byte frame", frame));
+        }
+        let message = format!("{}: {}", function.describe(), parts.join("; "));
+        Ok(Some(Allocation {
+            code: output,
+            message,
+            prologue: frame_bytes > 0,
+        }))
+    }
+
+    /// Renames locals in an instruction and moves its `sp`-relative operands
+    /// past `delta` bytes the allocator pushed.
+    fn rewrite(
+        &self,
+        instruction: &Instruction,
+        rename: &HashMap<&str, String>,
+        delta: i64,
+    ) -> Result<Instruction, String> {
+        let adjusts_sp = matches!(instruction, Instruction::Lea((d, _)) if self.is_sp(d));
+        let mut rewritten = instruction.clone();
+        for operand in operands_mut(&mut rewritten) {
+            let mut renamed = rename_operand(operand, rename);
+            if delta != 0 && !adjusts_sp && is_memory(&renamed) && self.mentions_sp(&renamed) {
+                match self.stack_offset(&renamed) {
+                    Some(offset) => renamed = stack_address(offset + delta),
+                    None => return Err(format!("cannot rebase `{}`", instruction)),
+                }
+            }
+            *operand = renamed;
+        }
+        Ok(rewritten)
+    }
+
+    /// Why control may leave the function other than through `ret`.
+    fn leaves(&self, function: &Function) -> Option<String> {
+        let code = &self.instructions[function.start..function.end];
+        let labels: HashSet<&str> = code
+            .iter()
+            .filter_map(|i| match i {
+                Instruction::Label(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        if let Some(jump) = code
+            .iter()
+            .find(|i| branch_target(i).is_some_and(|t| !labels.contains(t)))
+        {
+            return Some(format!("`{}` leaves the function", jump));
+        }
+        let falls_through = !matches!(
+            code.iter().rev().find(|i| is_code(i)),
+            Some(Instruction::Jmp(_) | Instruction::Ret)
+        );
+        if falls_through
+            && matches!(
+                self.instructions.get(function.end),
+                Some(Instruction::Label(_))
+            )
+        {
+            return Some("it falls through into the next function".to_string());
+        }
+        None
+    }
+}
+
+/// `[sp + offset]`, in the form every backend accepts.
+fn stack_address(offset: i64) -> String {
+    match offset {
+        0 => "[sp]".to_string(),
+        n if n < 0 => format!("[sp - {}]", -n),
+        n => format!("[sp + {}]", n),
+    }
+}
+
+/// Replaces every identifier of `operand` found in `rename`.
+fn rename_operand(operand: &str, rename: &HashMap<&str, String>) -> String {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$';
+    let mut output = String::with_capacity(operand.len());
+    let mut token = String::new();
+    for c in operand.chars().chain(std::iter::once('\0')) {
+        if is_ident(c) {
+            token.push(c);
+            continue;
+        }
+        match rename.get(token.as_str()) {
+            Some(name) => output.push_str(name),
+            None => output.push_str(&token),
+        }
+        token.clear();
+        if c != '\0' {
+            output.push(c);
+        }
+    }
+    output
+}
@@ -0,0 +1,506 @@
+//! List scheduling within basic blocks.
+//!
+//! Straight-line runs of code - between labels, branches and instructions
+//! that read every register (`call`, `syscall`, string instructions) - are
+//! reordered so long-latency results (loads, multiplies, divides) are started
+//! early and independent work fills the wait. Costs come from a small
+//! per-architecture table; the new order is kept only when that model says it
+//! finishes sooner than the original one.
+//!
+//! An instruction stays behind everything it depends on:
+//! - register reads and writes, including the registers the backend's
+//!   expansion uses implicitly (`rax`/`rdx` for x86 division);
+//! - the flags: a flag reader stays after the `cmp`/`test` it reads, and an
+//!   instruction whose flag result is never read may move freely among
+//!   other such instructions but never between a flag write and its reader;
+//! - memory: stores, stack traffic, fences, port I/O and anything touching
+//!   `sp`/`sb` keep their order, and loads never cross them.
+
+use std::collections::HashSet;
+
+use super::cfg::{Cfg, ends_block, operands, referenced_names};
+use super::liveness::{Liveness, effects, step};
+use super::{FLAGS, OptReport, Registers, Target, is_code, is_memory};
+use crate::arch::Architecture;
+use crate::core::Instruction;
+
+const PASS: &str = "schedule";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Alu,
+    Shift,
+    Bit,
+    Mul,
+    Div,
+    Load,
+    Store,
+    Stack,
+}
+
+/// Cycles until a result can be used, and the minimum number of cycles
+/// between two instructions of the class (1 when fully pipelined).
+struct Cost {
+    latency: u32,
+    interval: u32,
+}
+
+struct Model {
+    /// Instructions issued per cycle.
+    width: usize,
+    cost: fn(Class) -> Cost,
+}
+
+fn cost(latency: u32, interval: u32) -> Cost {
+    Cost { latency, interval }
+}
+
+fn model(architecture: Architecture) -> Model {
+    match architecture {
+        Architecture::AMD64 => Model {
+            width: 4,
+            cost: |class| match class {
+                Class::Alu | Class::Shift | Class::Store | Class::Stack => cost(1, 1),
+                Class::Bit | Class::Mul => cost(3, 1),
+                Class::Div => cost(26, 6),
+                Class::Load => cost(4, 1),
+            },
+        },
+        Architecture::AMD32 => Model {
+            width: 2,
+            cost: |class| match class {
+                Class::Alu | Class::Shift | Class::Store | Class::Stack => cost(1, 1),
+                Class::Bit | Class::Mul => cost(3, 1),
+                Class::Div => cost(26, 26),
+                Class::Load => cost(4, 1),
+            },
+        },
+        Architecture::ARM64 => Model {
+            width: 2,
+            cost: |class| match class {
+                Class::Alu | Class::Store | Class::Stack => cost(1, 1),
+                Class::Shift | Class::Bit => cost(2, 1),
+                Class::Mul => cost(3, 1),
+                Class::Div => cost(12, 12),
+                Class::Load => cost(4, 1),
+            },
+        },
+        // Cortex-A7/A53 class in-order cores; division is a library call.
+        Architecture::ARM32 => Model {
+            width: 1,
+            cost: |class| match class {
+                Class::Alu | Class::Store | Class::Stack => cost(1, 1),
+                Class::Shift | Class::Bit => cost(2, 1),
+                Class::Mul => cost(4, 2),
+                Class::Div => cost(40, 40),
+                Class::Load => cost(3, 1),
+            },
+        },
+        // Single-issue in-order pipeline (SiFive U5/U7 class).
+        Architecture::RISCV => Model {
+            width: 1,
+            cost: |class| match class {
+                Class::Alu | Class::Shift | Class::Bit | Class::Store | Class::Stack => cost(1, 1),
+                Class::Mul => cost(3, 1),
+                Class::Div => cost(34, 34),
+                Class::Load => cost(3, 1),
+            },
+        },
+        // 24K class single-issue pipeline; loads have one delay slot.
+        Architecture::MIPS32 => Model {
+            width: 1,
+            cost: |class| match class {
+                Class::Alu | Class::Shift | Class::Bit | Class::Store | Class::Stack => cost(1, 1),
+                Class::Mul => cost(4, 1),
+                Class::Div => cost(35, 35),
+                Class::Load => cost(2, 1),
+            },
+        },
+        // 8-bit core: every 32-bit operation is four byte operations, and
+        // multiplication and division are libgcc calls.
+        Architecture::AVR => Model {
+            width: 1,
+            cost: |class| match class {
+                Class::Alu | Class::Bit => cost(4, 4),
+                Class::Shift => cost(8, 8),
+                Class::Load | Class::Store | Class::Stack => cost(8, 8),
+                Class::Mul => cost(60, 60),
+                Class::Div => cost(700, 700),
+            },
+        },
+        // 16-bit core: every 32-bit operation is two word operations, most
+        // of them a cycle each, and multiplication and division are libgcc
+        // calls.
+        Architecture::MSP430 => Model {
+            width: 1,
+            cost: |class| match class {
+                Class::Alu | Class::Bit => cost(2, 2),
+                Class::Shift => cost(8, 8),
+                Class::Load | Class::Store | Class::Stack => cost(6, 6),
+                Class::Mul => cost(40, 40),
+                Class::Div => cost(400, 400),
+            },
+        },
+        // Registers are memory: every 32-bit operation moves two words
+        // through `hl` and `de`, and multiplication and division are
+        // runtime loops.
+        Architecture::Z80 => Model {
+            width: 1,
+            cost: |class| match class {
+                Class::Alu | Class::Load | Class::Store | Class::Stack => cost(20, 20),
+                Class::Bit => cost(30, 30),
+                Class::Shift => cost(60, 60),
+                Class::Mul => cost(400, 400),
+                Class::Div => cost(1000, 1000),
+            },
+        },
+        Architecture::PowerPC64 | Architecture::PowerPC64LE => Model {
+            width: 2,
+            cost: |class| match class {
+                Class::Alu | Class::Shift | Class::Store | Class::Stack => cost(1, 1),
+                Class::Bit => cost(2, 1),
+                Class::Mul => cost(5, 1),
+                Class::Div => cost(22, 22),
+                Class::Load => cost(3, 1),
+            },
+        },
+        // UltraSPARC III class: two integer pipes, with multiplication and
+        // division holding theirs until done.
+        Architecture::SPARC64 => Model {
+            width: 2,
+            cost: |class| match class {
+                Class::Alu | Class::Shift | Class::Store | Class::Stack => cost(1, 1),
+                Class::Bit => cost(2, 1),
+                Class::Mul => cost(6, 6),
+                Class::Div => cost(70, 70),
+                Class::Load => cost(2, 1),
+            },
+        },
+        // 68020/68030 class: one instruction at a time, a few cycles each,
+        // with `muls.l` and `divs.l` microcoded.
+        Architecture::K68 => Model {
+            width: 1,
+            cost: |class| match class {
+                Class::Alu | Class::Bit | Class::Stack => cost(2, 2),
+                Class::Shift => cost(4, 4),
+                Class::Load | Class::Store => cost(4, 4),
+                Class::Mul => cost(28, 28),
+                Class::Div => cost(56, 56),
+            },
+        },
+        // ESP32 LX6 class: a single-issue five-stage pipeline with a
+        // pipelined 32-bit multiplier and an iterative divider.
+        Architecture::Xtensa => Model {
+            width: 1,
+            cost: |class| match class {
+                Class::Alu | Class::Shift | Class::Bit | Class::Store | Class::Stack => cost(1, 1),
+                Class::Mul => cost(2, 1),
+                Class::Div => cost(34, 34),
+                Class::Load => cost(2, 1),
+            },
+        },
+        // SH-4 class: a dual-issue pipeline, with `mul.l` pipelined and
+        // division a libgcc call.
+        Architecture::SH => Model {
+            width: 2,
+            cost: |class| match class {
+                Class::Alu | Class::Shift | Class::Bit | Class::Store | Class::Stack => cost(1, 1),
+                Class::Mul => cost(4, 1),
+                Class::Div => cost(40, 40),
+                Class::Load => cost(2, 1),
+            },
+        },
+        // Targets without a table of their own: a plain single-issue pipeline.
+        _ => Model {
+            width: 1,
+            cost: |class| match class {
+                Class::Alu | Class::Shift | Class::Bit | Class::Store | Class::Stack => cost(1, 1),
+                Class::Mul => cost(4, 1),
+                Class::Div => cost(32, 32),
+                Class::Load => cost(3, 1),
+            },
+        },
+    }
+}
+
+fn class(instruction: &Instruction) -> Class {
+    use Instruction::*;
+    match instruction {
+        Load(_) => Class::Load,
+        Store(_) => Class::Store,
+        Push(_) | Pop(_) => Class::Stack,
+        Mul(_) | Imul(_) | Mulh(_) => Class::Mul,
+        Div(_) | Idiv(_) | Mod(_) => Class::Div,
+        Shl(_) | Shr(_) | Sal(_) | Sar(_) | Rol(_) | Ror(_) | Rcl(_) | Rcr(_) => Class::Shift,
+        Bsf(_) | Bsr(_) | Bextr(_) | Andn(_) | Bt(_) | Btr(_) | Bts(_) | Btc(_) => Class::Bit,
+        Lea(_) => Class::Alu,
+        _ => match memory_access(instruction) {
+            Some(true) => Class::Store,
+            Some(false) => Class::Load,
+            None => Class::Alu,
+        },
+    }
+}
+
+/// `Some(true)` when the instruction writes memory through an operand,
+/// `Some(false)` when it only reads it, `None` without memory operands.
+fn memory_access(instruction: &Instruction) -> Option<bool> {
+    if matches!(instruction, Instruction::Lea(_)) {
+        return None;
+    }
+    let mut operands = operands(instruction).into_iter();
+    let first = operands.next()?;
+    if is_memory(&first) {
+        // `cmp`/`test` only read their first operand.
+        let reads_only = matches!(
+            instruction,
+            Instruction::Cmp(_) | Instruction::Test(_) | Instruction::Bt(_) | Instruction::Push(_)
+        );
+        return Some(!reads_only);
+    }
+    operands.any(|operand| is_memory(&operand)).then_some(false)
+}
+
+struct Node {
+    uses: HashSet<String>,
+    defs: HashSet<String>,
+    /// Whether the flags this instruction writes are never read.
+    flags_dead: bool,
+    /// Stores and other instructions whose relative order must not change.
+    ordered: bool,
+    loads: bool,
+    class: Class,
+}
+
+pub fn run(
+    instructions: Vec<Instruction>,
+    regs: &Registers,
+    target: &Target,
+    clobbers: &dyn Fn(&Instruction) -> Vec<String>,
+    report: &mut OptReport,
+) -> Vec<Instruction> {
+    let cfg = Cfg::build(&instructions);
+    let liveness = Liveness::analyze(&instructions, &cfg, regs);
+    let model = model(target.architecture);
+    let stack: HashSet<&str> = ["sp", "sb"]
+        .iter()
+        .filter_map(|alias| regs.resolve(alias))
+        .collect();
+
+    let mut order: Vec<usize> = (0..instructions.len()).collect();
+    for (b, block) in cfg.blocks.iter().enumerate() {
+        if !block.in_text {
+            continue;
+        }
+
+        let mut flags_live_after = vec![false; block.end - block.start];
+        let mut live = liveness.live_out[b].clone();
+        for i in (block.start..block.end).rev() {
+            flags_live_after[i - block.start] = live.contains(FLAGS);
+            step(&mut live, &effects(&instructions[i], regs), regs);
+        }
+
+        // Without condition flags, a flag reader compares what its setter
+        // read, so it depends on those registers too.
+        let mut compared: Vec<String> = Vec::new();
+
+        // Each node with the index of the first source line marker in front
+        // of it, which moves along with it.
+        let mut nodes: Vec<(usize, usize, Node)> = Vec::new();
+        let mut marker = None;
+        for i in block.start..=block.end {
+            if i < block.end && matches!(instructions[i], Instruction::Loc(_)) {
+                marker.get_or_insert(i);
+                continue;
+            }
+            let schedulable = i < block.end && {
+                let instruction = &instructions[i];
+                is_code(instruction)
+                    && !ends_block(instruction)
+                    && !effects(instruction, regs).barrier
+            };
+            if schedulable {
+                let instruction = &instructions[i];
+                let e = effects(instruction, regs);
+                let implicit = clobbers(instruction);
+                let touches_stack = referenced_names(instruction)
+                    .into_iter()
+                    .any(|name| regs.resolve(name).is_some_and(|r| stack.contains(r)));
+                let access = memory_access(instruction);
+                let mut node = Node {
+                    uses: e.uses.iter().chain(&implicit).cloned().collect(),
+                    defs: e.defs.iter().chain(&implicit).cloned().collect(),
+                    flags_dead: !flags_live_after[i - block.start],
+                    ordered: e.side_effect || touches_stack || access == Some(true),
+                    loads: access.is_some(),
+                    class: class(instruction),
+                };
+                if !node.defs.contains(FLAGS) {
+                    node.flags_dead = false;
+                }
+                if !target.flags {
+                    if e.uses.iter().any(|u| u == FLAGS) {
+                        node.uses.extend(compared.iter().cloned());
+                    }
+                    if e.defs.iter().any(|d| d == FLAGS) {
+                        compared = e.uses.iter().filter(|u| *u != FLAGS).cloned().collect();
+                    }
+                }
+                nodes.push((marker.take().unwrap_or(i), i, node));
+                continue;
+            }
+            marker = None;
+            if nodes.len() > 1 {
+                let groups: Vec<(usize, usize)> = nodes.iter().map(|(m, i, _)| (*m, *i)).collect();
+                let indices: Vec<usize> = groups.iter().map(|(_, i)| *i).collect();
+                let region: Vec<Node> = nodes.drain(..).map(|(_, _, n)| n).collect();
+                if let Some((schedule, before, after)) = schedule(&region, &model) {
+                    let sequence = schedule.iter().flat_map(|&k| groups[k].0..=groups[k].1);
+                    for (slot, index) in sequence.enumerate() {
+                        order[groups[0].0 + slot] = index;
+                    }
+                    report.note(
+                        PASS,
+                        format!(
+                            "{}: reordered {} instructions, about {} -> {} cycles",
+                            describe(&instructions, block.label.as_deref(), indices[0]),
+                            indices.len(),
+                            before,
+                            after
+                        ),
+                    );
+                }
+            }
+            nodes.clear();
+        }
+    }
+
+    let mut slots: Vec<Option<Instruction>> = instructions.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|i| slots[i].take()).collect()
+}
+
+fn describe(instructions: &[Instruction], label: Option<&str>, first: usize) -> String {
+    match label {
+        Some(label) => format!("`{}`", label),
+        None => format!("block at `{}`", instructions[first]),
+    }
+}
+
+/// The edges into each node: (predecessor, cycles it must wait).
+fn dependencies(nodes: &[Node], model: &Model) -> Vec<Vec<(usize, u32)>> {
+    let mut preds = vec![Vec::new(); nodes.len()];
+    for i in 0..nodes.len() {
+        for j in 0..i {
+            let (a, b) = (&nodes[j], &nodes[i]);
+            let raw = a.defs.iter().any(|d| b.uses.contains(d));
+            let war = a.uses.iter().any(|u| b.defs.contains(u));
+            let waw = a
+                .defs
+                .iter()
+                .any(|d| b.defs.contains(d) && !(d == FLAGS && b.flags_dead));
+            let memory = (a.ordered && (b.ordered || b.loads)) || (a.loads && b.ordered);
+            if raw {
+                preds[i].push((j, (model.cost)(a.class).latency));
+            } else if war || waw || memory {
+                preds[i].push((j, 0));
+            }
+        }
+    }
+    preds
+}
+
+/// Issue cycle of every node when issued in `sequence`, in order, and the
+/// cycle the last result is ready.
+fn simulate(nodes: &[Node], preds: &[Vec<(usize, u32)>], model: &Model, sequence: &[usize]) -> u32 {
+    let mut issued = vec![0u32; nodes.len()];
+    let mut busy: Vec<(Class, u32)> = Vec::new();
+    let (mut cycle, mut slots, mut done) = (0u32, 0usize, 0u32);
+    for &k in sequence {
+        let node = &nodes[k];
+        let mut at = preds[k]
+            .iter()
+            .map(|&(p, wait)| issued[p] + wait)
+            .max()
+            .unwrap_or(0)
+            .max(cycle);
+        if let Some(&(_, free)) = busy.iter().find(|(c, _)| *c == node.class) {
+            at = at.max(free);
+        }
+        if at == cycle && slots == model.width {
+            at += 1;
+        }
+        if at > cycle {
+            cycle = at;
+            slots = 0;
+        }
+        slots += 1;
+        issued[k] = at;
+        let cost = (model.cost)(node.class);
+        busy.retain(|(c, _)| *c != node.class);
+        busy.push((node.class, at + cost.interval));
+        done = done.max(at + cost.latency);
+    }
+    done
+}
+
+/// A better order for `nodes`, with the estimated cycles before and after.
+fn schedule(nodes: &[Node], model: &Model) -> Option<(Vec<usize>, u32, u32)> {
+    let preds = dependencies(nodes, model);
+    let n = nodes.len();
+
+    // Priority: the longest latency path from a node to the end of the region.
+    let mut priority = vec![0u32; n];
+    for i in (0..n).rev() {
+        priority[i] = priority[i].max((model.cost)(nodes[i].class).latency);
+        for &(p, wait) in &preds[i] {
+            priority[p] = priority[p].max(wait + priority[i]);
+        }
+    }
+
+    let mut remaining: Vec<usize> = preds.iter().map(Vec::len).collect();
+    let mut succs = vec![Vec::new(); n];
+    for (i, edges) in preds.iter().enumerate() {
+        for &(p, wait) in edges {
+            succs[p].push((i, wait));
+        }
+    }
+    let mut earliest = vec![0u32; n];
+    let mut scheduled = vec![false; n];
+    let mut busy: Vec<(Class, u32)> = Vec::new();
+    let mut sequence = Vec::with_capacity(n);
+    let mut cycle = 0u32;
+    while sequence.len() < n {
+        let mut issued = 0;
+        while issued < model.width {
+            let pick = (0..n)
+                .filter(|&i| !scheduled[i] && remaining[i] == 0 && earliest[i] <= cycle)
+                .filter(|&i| {
+                    busy.iter()
+                        .all(|&(c, free)| c != nodes[i].class || free <= cycle)
+                })
+                .max_by_key(|&i| (priority[i], std::cmp::Reverse(i)));
+            let Some(i) = pick else {
+                break;
+            };
+            scheduled[i] = true;
+            sequence.push(i);
+            issued += 1;
+            let cost = (model.cost)(nodes[i].class);
+            busy.retain(|(c, _)| *c != nodes[i].class);
+            busy.push((nodes[i].class, cycle + cost.interval));
+            for &(s, wait) in &succs[i] {
+                remaining[s] -= 1;
+                earliest[s] = earliest[s].max(cycle + wait);
+            }
+        }
+        cycle += 1;
+    }
+
+    let original: Vec<usize> = (0..n).collect();
+    if sequence == original {
+        return None;
+    }
+    let before = simulate(nodes, &preds, model, &original);
+    let after = simulate(nodes, &preds, model, &sequence);
+    (after < before).then_some((sequence, before, after))
+}
@@ -0,0 +1,224 @@
+//! Size minimization.
+//!
+//! On x86, where the encodings differ in length:
+//! - `mov r, 0` becomes `xor r, r` when the flags it would clobber are dead;
+//! - `add`/`sub` of 1 becomes `inc`/`dec` when the carry flag they leave
+//!   alone is never read.
+//!
+//! On every target, functions that end in the same instructions share them:
+//! a `ret` block whose last three or more instructions match the end of an
+//! earlier one jumps into that one instead.
+//!
+//! `live_after` gives the emitter the registers each instruction must leave
+//! alone, so backends can drop the saves around their own expansions.
+
+use std::collections::{HashMap, HashSet};
+
+use super::cfg::Cfg;
+use super::liveness::{Liveness, effects, step};
+use super::{FLAGS, OptReport, Registers, Target, is_code, parse_immediate};
+use crate::arch::Architecture;
+use crate::core::Instruction;
+
+const PASS: &str = "size";
+
+/// Instructions two epilogues must share before one jumps into the other.
+const SHARED_TAIL: usize = 3;
+
+pub fn run(
+    instructions: Vec<Instruction>,
+    regs: &Registers,
+    target: &Target,
+    report: &mut OptReport,
+) -> Vec<Instruction> {
+    let instructions = if matches!(
+        target.architecture,
+        Architecture::AMD64 | Architecture::AMD32
+    ) {
+        shorten(instructions, regs, report)
+    } else {
+        instructions
+    };
+    share_epilogues(instructions, report)
+}
+
+/// Registers live after each instruction.
+pub fn live_after(instructions: &[Instruction], regs: &Registers) -> Vec<HashSet<String>> {
+    let cfg = Cfg::build(instructions);
+    let liveness = Liveness::analyze(instructions, &cfg, regs);
+    let mut after = vec![regs.all().clone(); instructions.len()];
+    for (b, block) in cfg.blocks.iter().enumerate() {
+        if !block.in_text {
+            continue;
+        }
+        let mut live = liveness.live_out[b].clone();
+        for i in (block.start..block.end).rev() {
+            after[i] = live.clone();
+            step(&mut live, &effects(&instructions[i], regs), regs);
+        }
+    }
+    after
+}
+
+/// Whether a conditional jump reads only flags `inc` and `dec` set.
+fn ignores_carry(instruction: &Instruction) -> bool {
+    use Instruction::*;
+    matches!(
+        instruction,
+        Je(_) | Jne(_) | Jl(_) | Jle(_) | Jg(_) | Jge(_) | Jo(_) | Jno(_) | Js(_) | Jns(_)
+    )
+}
+
+fn shorten(
+    mut instructions: Vec<Instruction>,
+    regs: &Registers,
+    report: &mut OptReport,
+) -> Vec<Instruction> {
+    let after = live_after(&instructions, regs);
+    let flags_dead = |i: usize| !after[i].contains(FLAGS);
+    let register = |operand: &str| regs.as_destination(operand).is_some();
+
+    let (mut zeroed, mut stepped) = (0, 0);
+    for i in 0..instructions.len() {
+        let replacement = match &instructions[i] {
+            Instruction::Mov((dst, src))
+                if register(dst) && parse_immediate(src) == Some(0) && flags_dead(i) =>
+            {
+                zeroed += 1;
+                Instruction::Xor((dst.clone(), dst.clone()))
+            }
+            Instruction::Add((dst, src)) | Instruction::Sub((dst, src)) if register(dst) => {
+                let sign = if matches!(instructions[i], Instruction::Sub(_)) {
+                    -1
+                } else {
+                    1
+                };
+                let replacement = match parse_immediate(src).map(|n| n * sign) {
+                    Some(1) => Instruction::Inc(dst.clone()),
+                    Some(-1) => Instruction::Dec(dst.clone()),
+                    _ => continue,
+                };
+                // A jump on the zero, sign or overflow flag may follow, as
+                // long as nothing reads the flags after it.
+                let next = (i + 1..instructions.len()).find(|&j| is_code(&instructions[j]));
+                let carry_dead = flags_dead(i)
+                    || next.is_some_and(|j| ignores_carry(&instructions[j]) && flags_dead(j));
+                if !carry_dead {
+                    continue;
+                }
+                stepped += 1;
+                replacement
+            }
+            _ => continue,
+        };
+        instructions[i] = replacement;
+    }
+
+    if zeroed > 0 {
+        report.note(
+            PASS,
+            format!("replaced {} `mov r, 0` with `xor r, r`", zeroed),
+        );
+    }
+    if stepped > 0 {
+        report.note(
+            PASS,
+            format!("replaced {} `add`/`sub` of 1 with `inc`/`dec`", stepped),
+        );
+    }
+    instructions
+}
+
+fn share_epilogues(instructions: Vec<Instruction>, report: &mut OptReport) -> Vec<Instruction> {
+    let cfg = Cfg::build(&instructions);
+    let text: Vec<String> = instructions.iter().map(|i| i.to_string()).collect();
+    let mut taken: HashSet<String> = cfg.labels.keys().cloned().collect();
+
+    // Trailing code of every block that returns, as instruction indices.
+    let mut tails: Vec<(usize, Vec<usize>)> = Vec::new();
+    for (b, block) in cfg.blocks.iter().enumerate() {
+        if !block.in_text {
+            continue;
+        }
+        let tail: Vec<usize> = (block.start..block.end)
+            .rev()
+            .filter(|&i| !matches!(instructions[i], Instruction::Loc(_)))
+            .take_while(|&i| is_code(&instructions[i]))
+            .collect();
+        if tail
+            .first()
+            .is_some_and(|&i| matches!(instructions[i], Instruction::Ret))
+        {
+            tails.push((b, tail));
+        }
+    }
+
+    // Labels to insert before an instruction, and tails replaced by a jump.
+    let mut splits: HashMap<usize, String> = HashMap::new();
+    let mut jumps: HashMap<usize, (usize, String)> = HashMap::new();
+    for (n, (b, tail)) in tails.iter().enumerate() {
+        let best = tails[..n]
+            .iter()
+            .filter(|(earlier, _)| !jumps.contains_key(&cfg.blocks[*earlier].end))
+            .map(|(earlier, other)| {
+                let shared = tail
+                    .iter()
+                    .zip(other)
+                    .take_while(|(a, o)| text[**a] == text[**o])
+                    .count();
+                (shared, *earlier, other)
+            })
+            .max_by_key(|(shared, earlier, _)| (*shared, std::cmp::Reverse(*earlier)));
+        let Some((shared, earlier, other)) = best else {
+            continue;
+        };
+        if shared < SHARED_TAIL {
+            continue;
+        }
+        let start = other[shared - 1];
+        let label = splits
+            .entry(start)
+            .or_insert_with(|| {
+                let base = cfg.blocks[earlier].label.as_deref().unwrap_or("epilogue");
+                let mut k = 0;
+                loop {
+                    let label = format!("{}__tail{}", base, k);
+                    if taken.insert(label.clone()) {
+                        break label;
+                    }
+                    k += 1;
+                }
+            })
+            .clone();
+        let from = tail[shared - 1];
+        jumps.insert(cfg.blocks[*b].end, (from, label.clone()));
+        let name = cfg.blocks[*b].label.as_deref().unwrap_or("a block");
+        report.note(
+            PASS,
+            format!(
+                "`{}`: shares its last {} instructions at `{}`",
+                name, shared, label
+            ),
+        );
+    }
+    if jumps.is_empty() {
+        return instructions;
+    }
+
+    let mut replaced = vec![false; instructions.len()];
+    for (&end, &(from, _)) in &jumps {
+        replaced[from..end].iter_mut().for_each(|r| *r = true);
+    }
+    let mut out = Vec::with_capacity(instructions.len());
+    for (i, instruction) in instructions.into_iter().enumerate() {
+        if let Some(label) = splits.get(&i) {
+            out.push(Instruction::Label(label.clone()));
+        }
+        if let Some((_, label)) = jumps.get(&(i + 1)) {
+            out.push(Instruction::Jmp(label.clone()));
+        } else if !replaced[i] {
+            out.push(instruction);
+        }
+    }
+    out
+}
@@ -0,0 +1,352 @@
+//! Strength reduction.
+//!
+//! Multiplication, division and modulo by an immediate are rewritten into
+//! cheaper sequences:
+//! - powers of two become shifts, with a rounding fix-up for signed division
+//!   and modulo;
+//! - small multipliers become `lea` on x86 and shift-add sequences elsewhere;
+//! - any other divisor becomes a multiply by its "magic" reciprocal through
+//!   `mulh` (Hacker's Delight, chapter 10).
+//!
+//! `div`, `idiv` and `mod` are signed on every backend. Sequences that need a
+//! scratch register take a virtual register that is dead at that point; when
+//! none is free, or the flags of the original instruction are still read, the
+//! instruction is left alone. The second register a modulo needs is pushed
+//! and popped around it when it is not free.
+
+use std::collections::HashSet;
+
+use super::cfg::Cfg;
+use super::liveness::{Liveness, effects, step};
+use super::{FLAGS, OptReport, Registers, Target, parse_immediate};
+use crate::arch::Architecture;
+use crate::core::Instruction;
+
+const PASS: &str = "strength";
+
+pub fn run(
+    instructions: Vec<Instruction>,
+    regs: &Registers,
+    target: &Target,
+    report: &mut OptReport,
+) -> Vec<Instruction> {
+    let cfg = Cfg::build(&instructions);
+    let liveness = Liveness::analyze(&instructions, &cfg, regs);
+    let mut replacements: Vec<Option<Vec<Instruction>>> = vec![None; instructions.len()];
+
+    for (b, block) in cfg.blocks.iter().enumerate() {
+        if !block.in_text {
+            continue;
+        }
+        let mut live = liveness.live_out[b].clone();
+        for i in (block.start..block.end).rev() {
+            if !live.contains(FLAGS)
+                && let Some(sequence) = reduce(&instructions[i], &live, regs, target)
+            {
+                let message = if sequence.is_empty() {
+                    format!("removed `{}`", instructions[i])
+                } else {
+                    let lines: Vec<String> = sequence.iter().map(|s| s.to_string()).collect();
+                    format!("replaced `{}` with `{}`", instructions[i], lines.join("; "))
+                };
+                report.note(PASS, message);
+                replacements[i] = Some(sequence);
+            }
+            step(&mut live, &effects(&instructions[i], regs), regs);
+        }
+    }
+
+    instructions
+        .into_iter()
+        .zip(replacements)
+        .flat_map(|(instruction, replacement)| replacement.unwrap_or_else(|| vec![instruction]))
+        .collect()
+}
+
+fn reduce(
+    instruction: &Instruction,
+    live: &HashSet<String>,
+    regs: &Registers,
+    target: &Target,
+) -> Option<Vec<Instruction>> {
+    let (d, c) = match instruction {
+        Instruction::Mul((d, c))
+        | Instruction::Imul((d, c))
+        | Instruction::Div((d, c))
+        | Instruction::Idiv((d, c))
+        | Instruction::Mod((d, c)) => (d.trim(), c),
+        _ => return None,
+    };
+    let dst = regs.as_destination(d)?;
+    let n = target.width;
+    let c = parse_immediate(c)?;
+    if n < 64 && (c < -(1 << (n - 1)) || c >= 1 << (n - 1)) {
+        return None;
+    }
+
+    let mut scratch = Scratch::new(regs, target, live, &dst);
+    match instruction {
+        Instruction::Mul(_) | Instruction::Imul(_) => multiply(d, c, target, &mut scratch),
+        Instruction::Mod(_) => modulo(d, c, n, &mut scratch),
+        _ => divide(d, c, n, &mut scratch),
+    }
+}
+
+fn multiply(d: &str, c: i64, target: &Target, scratch: &mut Scratch) -> Option<Vec<Instruction>> {
+    use Instruction::*;
+    let x86 = matches!(
+        target.architecture,
+        Architecture::AMD64 | Architecture::AMD32
+    );
+
+    let mut sequence = match c {
+        0 => return Some(vec![Mov(pair(d, "0"))]),
+        1 => return Some(Vec::new()),
+        -1 => return Some(vec![Neg(d.to_string())]),
+        _ => Vec::new(),
+    };
+
+    let m = c.unsigned_abs();
+    let (a, b) = (63 - m.leading_zeros(), m.trailing_zeros());
+    if m.is_power_of_two() {
+        sequence.push(Shl(pair(d, &b.to_string())));
+    } else if x86 && matches!(m >> b, 3 | 5 | 9) {
+        let index = (m >> b) - 1;
+        sequence.push(Lea(pair(d, &format!("[{} + {}*{}]", d, d, index))));
+        if b > 0 {
+            sequence.push(Shl(pair(d, &b.to_string())));
+        }
+    } else if !x86 && (m.count_ones() == 2 || (m + (1 << b)).is_power_of_two()) {
+        // 2^a + 2^b, or 2^a' - 2^b with a' = a + 1
+        let plus = m.count_ones() == 2;
+        let high = if plus { a } else { a + 1 };
+        if high >= target.width {
+            return None;
+        }
+        let t = scratch.take()?;
+        sequence.push(Mov(pair(&t, d)));
+        sequence.push(Shl(pair(d, &high.to_string())));
+        if b > 0 {
+            sequence.push(Shl(pair(&t, &b.to_string())));
+        }
+        sequence.push(if plus {
+            Add(pair(d, &t))
+        } else {
+            Sub(pair(d, &t))
+        });
+    } else {
+        return None;
+    }
+
+    if c < 0 {
+        sequence.push(Neg(d.to_string()));
+    }
+    Some(sequence)
+}
+
+fn divide(d: &str, c: i64, n: u32, scratch: &mut Scratch) -> Option<Vec<Instruction>> {
+    use Instruction::*;
+    let mut sequence = match c {
+        0 => return None,
+        1 => return Some(Vec::new()),
+        -1 => return Some(vec![Neg(d.to_string())]),
+        _ => Vec::new(),
+    };
+
+    let m = c.unsigned_abs();
+    let t = scratch.take()?;
+    if m.is_power_of_two() {
+        // Add 2^k - 1 to negative dividends so the shift rounds toward zero.
+        let k = m.trailing_zeros();
+        sequence.push(Mov(pair(&t, d)));
+        if k == 1 {
+            sequence.push(Shr(pair(&t, &(n - 1).to_string())));
+        } else {
+            sequence.push(Sar(pair(&t, &(n - 1).to_string())));
+            sequence.push(Shr(pair(&t, &(n - k).to_string())));
+        }
+        sequence.push(Add(pair(d, &t)));
+        sequence.push(Sar(pair(d, &k.to_string())));
+        if c < 0 {
+            sequence.push(Neg(d.to_string()));
+        }
+    } else {
+        sequence.extend(quotient(d, c, n, &t));
+        sequence.push(Mov(pair(d, &t)));
+        sequence.push(Shr(pair(&t, &(n - 1).to_string())));
+        sequence.push(Add(pair(d, &t)));
+    }
+    Some(sequence)
+}
+
+fn modulo(d: &str, c: i64, n: u32, scratch: &mut Scratch) -> Option<Vec<Instruction>> {
+    use Instruction::*;
+    let m = c.unsigned_abs();
+    match m {
+        0 => return None,
+        1 => return Some(vec![Mov(pair(d, "0"))]),
+        _ => {}
+    }
+
+    // d - trunc(d / c) * c; the sign of c does not matter.
+    let t = scratch.take()?;
+    let mut sequence = Vec::new();
+    if m.is_power_of_two() {
+        let k = m.trailing_zeros();
+        let k_str = k.to_string();
+        sequence.push(Mov(pair(&t, d)));
+        if k == 1 {
+            sequence.push(Shr(pair(&t, &(n - 1).to_string())));
+        } else {
+            sequence.push(Sar(pair(&t, &(n - 1).to_string())));
+            sequence.push(Shr(pair(&t, &(n - k).to_string())));
+        }
+        sequence.push(Add(pair(&t, d)));
+        sequence.push(Sar(pair(&t, &k_str)));
+        sequence.push(Shl(pair(&t, &k_str)));
+    } else {
+        // Without a second free register, one in use is saved around it.
+        let (u, spilled) = match scratch.take() {
+            Some(u) => (u, false),
+            None => (scratch.spill()?, true),
+        };
+        if spilled {
+            sequence.push(Push(u.clone()));
+        }
+        sequence.extend(quotient(d, c, n, &t));
+        sequence.push(Mov(pair(&u, &t)));
+        sequence.push(Shr(pair(&u, &(n - 1).to_string())));
+        sequence.push(Add(pair(&t, &u)));
+        sequence.push(Mov(pair(&u, &immediate(c, n))));
+        sequence.push(Mul(pair(&t, &u)));
+        if spilled {
+            sequence.push(Pop(u));
+        }
+    }
+    sequence.push(Sub(pair(d, &t)));
+    Some(sequence)
+}
+
+/// Leaves the quotient of `d / c`, before the round-toward-zero correction,
+/// in `t`.
+fn quotient(d: &str, c: i64, n: u32, t: &str) -> Vec<Instruction> {
+    use Instruction::*;
+    let (magic, shift) = magic(c, n);
+    let mut sequence = vec![Mov(pair(t, &immediate(magic, n))), Mulh(pair(t, d))];
+    if c > 0 && magic < 0 {
+        sequence.push(Add(pair(t, d)));
+    } else if c < 0 && magic > 0 {
+        sequence.push(Sub(pair(t, d)));
+    }
+    if shift > 0 {
+        sequence.push(Sar(pair(t, &shift.to_string())));
+    }
+    sequence
+}
+
+/// Signed magic multiplier and shift for dividing `n`-bit values by `d`,
+/// where `2 <= |d|` and `|d|` is not a power of two.
+fn magic(d: i64, n: u32) -> (i64, u32) {
+    let two = 1u128 << (n - 1);
+    let ad = d.unsigned_abs() as u128;
+    let t = two + u128::from(d < 0);
+    let anc = t - 1 - t % ad;
+
+    let mut p = n - 1;
+    let (mut q1, mut r1) = (two / anc, two % anc);
+    let (mut q2, mut r2) = (two / ad, two % ad);
+    loop {
+        p += 1;
+        q1 *= 2;
+        r1 *= 2;
+        if r1 >= anc {
+            q1 += 1;
+            r1 -= anc;
+        }
+        q2 *= 2;
+        r2 *= 2;
+        if r2 >= ad {
+            q2 += 1;
+            r2 -= ad;
+        }
+        let delta = ad - r2;
+        if !(q1 < delta || (q1 == delta && r1 == 0)) {
+            break;
+        }
+    }
+
+    let mask = (1u128 << n) - 1;
+    let mut m = (q2 + 1) & mask;
+    if d < 0 {
+        m = m.wrapping_neg() & mask;
+    }
+    let m = ((m << (128 - n)) as i128 >> (128 - n)) as i64;
+    (m, p - n)
+}
+
+/// Formats an immediate so every backend's `mov` loads it exactly: small
+/// values in decimal, anything else as the `n`-bit pattern in hex.
+fn immediate(value: i64, n: u32) -> String {
+    if (0..=255).contains(&value) {
+        value.to_string()
+    } else {
+        let mask = if n == 64 { u64::MAX } else { (1 << n) - 1 };
+        format!("0x{:x}", value as u64 & mask)
+    }
+}
+
+fn pair(a: &str, b: &str) -> (String, String) {
+    (a.to_string(), b.to_string())
+}
+
+/// Hands out virtual registers that are free at one instruction.
+struct Scratch<'a> {
+    regs: &'a Registers,
+    target: &'a Target,
+    live: &'a HashSet<String>,
+    taken: Vec<String>,
+}
+
+impl<'a> Scratch<'a> {
+    fn new(regs: &'a Registers, target: &'a Target, live: &'a HashSet<String>, dst: &str) -> Self {
+        let mut taken = vec![dst.to_string()];
+        taken.extend(
+            ["sp", "sb"]
+                .iter()
+                .filter_map(|alias| regs.resolve(alias).map(str::to_string)),
+        );
+        Scratch {
+            regs,
+            target,
+            live,
+            taken,
+        }
+    }
+
+    fn take(&mut self) -> Option<String> {
+        self.find(true)
+    }
+
+    /// A virtual register that may be live, for saving on the stack around
+    /// its use.
+    fn spill(&mut self) -> Option<String> {
+        self.find(false)
+    }
+
+    fn find(&mut self, free: bool) -> Option<String> {
+        for name in self.regs.virtuals() {
+            let Some(phys) = self.regs.resolve(name) else {
+                continue;
+            };
+            if self.target.reserved.contains(phys)
+                || (free && self.live.contains(phys))
+                || self.taken.iter().any(|t| t == phys)
+            {
+                continue;
+            }
+            self.taken.push(phys.to_string());
+            return Some(name.clone());
+        }
+        None
+    }
+}
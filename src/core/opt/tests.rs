@@ -0,0 +1,395 @@
+//! Runs programs through each pass on AMD64 Linux: the output is assembled
+//! with `as`, linked with `ld` and executed, and its exit status checked.
+
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{Registers, flags};
+use crate::arch::{Architecture, Tested, create_arch_codegen};
+use crate::core::codegen::{CodeGenConfig, CodeGenerator};
+use crate::core::parser::Parser;
+use crate::core::{Instruction, TargetTriple};
+use crate::platform::Platform;
+
+/// Numbers the build directories of programs run at the same time.
+static BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+/// Compiles `source` for `architecture` with only the passes `enable` turns
+/// on, and checks that `pass` changed it.
+fn compile(
+    source: &str,
+    architecture: Architecture,
+    pass: &str,
+    enable: impl Fn(&mut CodeGenConfig),
+) -> String {
+    let mut config = CodeGenConfig {
+        enable_dead_code_elimination: false,
+        unwind_info: false,
+        ..CodeGenConfig::default()
+    };
+    enable(&mut config);
+    let instructions = Parser::new(source).parse().expect("the program parses");
+    let target = TargetTriple::new(architecture, Platform::Linux);
+    let (assembly, report) = CodeGenerator::with_config(target, config)
+        .generate_with_report(&instructions)
+        .expect("the program compiles");
+    assert!(
+        report.notes.iter().any(|note| note.pass == pass),
+        "{} left the program alone",
+        pass
+    );
+    assembly
+}
+
+/// Compiles `source` for AMD64 as `compile` does, then runs it and returns
+/// its exit status.
+fn run(source: &str, pass: &str, enable: impl Fn(&mut CodeGenConfig)) -> i32 {
+    let assembly = compile(source, Architecture::AMD64, pass, enable);
+    execute(&assembly, pass)
+}
+
+/// Assembles, links and runs AMD64 `assembly`, returning its exit status.
+fn execute(assembly: &str, pass: &str) -> i32 {
+    let dir = std::env::temp_dir().join(format!(
+        "uac-{}-{}-{}",
+        pass,
+        std::process::id(),
+        BUILDS.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).expect("the build directory is created");
+    let (asm, obj, exe) = (dir.join("a.s"), dir.join("a.o"), dir.join("a"));
+    std::fs::write(&asm, assembly).expect("the assembly is written");
+    let assembled = Command::new("as")
+        .arg("--64")
+        .arg(&asm)
+        .arg("-o")
+        .arg(&obj)
+        .status();
+    assert!(
+        assembled.expect("as runs").success(),
+        "as rejected:\n{}",
+        assembly
+    );
+    let linked = Command::new("ld").arg(&obj).arg("-o").arg(&exe).status();
+    assert!(
+        linked.expect("ld runs").success(),
+        "ld rejected:\n{}",
+        assembly
+    );
+    let status = Command::new(&exe).status().expect("the program runs");
+    std::fs::remove_dir_all(&dir).ok();
+    status.code().expect("the program exits")
+}
+
+#[test]
+fn dce_keeps_what_is_read() {
+    let source = "
+section .text
+global _start
+_start:
+    mov r0, 7
+    mov r1, 100
+    jmp done
+    mov r0, 99
+done:
+    add r0, 3
+    syscall exit
+";
+    let status = run(source, "dce", |c| c.enable_dead_code_elimination = true);
+    assert_eq!(status, 10);
+}
+
+#[test]
+fn strength_reduction_matches_signed_division() {
+    // Exits with the number of the first wrong result.
+    let source = "
+section .text
+global _start
+_start:
+    mov r0, -37
+    idiv r0, 4
+    mov r5, 1
+    cmp r0, -9
+    jne fail
+    mov r1, 100
+    mul r1, 9
+    mov r5, 2
+    cmp r1, 900
+    jne fail
+    mov r2, -1000
+    div r2, 7
+    mov r5, 3
+    cmp r2, -142
+    jne fail
+    mov r3, -23
+    mod r3, 8
+    mov r5, 4
+    cmp r3, -7
+    jne fail
+    mov r4, 1000
+    mod r4, 13
+    mov r5, 5
+    cmp r4, 12
+    jne fail
+    mov r5, 0
+fail:
+    mov r0, r5
+    syscall exit
+";
+    let status = run(source, "strength", |c| c.enable_strength_reduction = true);
+    assert_eq!(status, 0);
+}
+
+#[test]
+fn strength_reduction_saves_a_live_register_for_modulo() {
+    // Only r13 is free at the `mod`, which needs two scratch registers.
+    let mut source = String::from("section .text\nglobal _start\n_start:\n");
+    for i in 1..13 {
+        source.push_str(&format!("    mov r{}, {}\n", i, i));
+    }
+    source.push_str(
+        "    mov r0, -1000\n    mod r0, 13\n    mov r13, 1\n    cmp r0, -12\n    jne fail\n",
+    );
+    for i in 1..13 {
+        source.push_str(&format!(
+            "    mov r13, {}\n    cmp r{}, {}\n    jne fail\n",
+            i + 1,
+            i,
+            i
+        ));
+    }
+    source.push_str("    mov r13, 0\nfail:\n    mov r0, r13\n    syscall exit\n");
+    let status = run(&source, "strength", |c| c.enable_strength_reduction = true);
+    assert_eq!(status, 0);
+}
+
+#[test]
+fn regalloc_places_locals_beyond_the_map() {
+    // Twenty locals live across a call, more than AMD64 has registers.
+    let mut source = String::from(
+        "section .text\nglobal _start\nbump:\n    add r0, 5\n    ret\n_start:\n    mov r0, 0\n",
+    );
+    for n in 20..40 {
+        source.push_str(&format!("    mov r{}, {}\n", n, n - 19));
+    }
+    source.push_str("    call bump\n");
+    for n in 20..40 {
+        source.push_str(&format!("    add r0, r{}\n", n));
+    }
+    source.push_str("    sub r0, 200\n    syscall exit\n");
+    let status = run(&source, "regalloc", |c| {
+        c.enable_register_allocation_optimization = true
+    });
+    // 5 + (1 + ... + 20) - 200
+    assert_eq!(status, 15);
+}
+
+#[test]
+fn regalloc_places_locals_named_like_mapped_registers() {
+    // AMD64 keeps `r12` and `r13` in r14 and r15, so `r14` and `r15` must
+    // be placed apart from them, with allocation on or off.
+    let mut source = String::from("section .text\nglobal _start\n_start:\n");
+    for n in 0..20 {
+        source.push_str(&format!("    mov r{}, {}\n", n, n + 1));
+    }
+    for n in 1..20 {
+        source.push_str(&format!("    add r0, r{}\n", n));
+    }
+    source.push_str("    syscall exit\n");
+    for allocate in [false, true] {
+        let status = run(&source, "regalloc", |c| {
+            c.enable_register_allocation_optimization = allocate
+        });
+        assert_eq!(status, 210);
+    }
+}
+
+#[test]
+fn regalloc_places_locals_named_like_reserved_registers() {
+    // ARM32 maps `r13` to itself, and that is the stack pointer.
+    let source = "
+section .text
+global _start
+_start:
+    mov r13, 5
+    add r13, 2
+    mov r0, r13
+    syscall exit
+";
+    let assembly = compile(source, Architecture::ARM32, "regalloc", |c| {
+        c.enable_register_allocation_optimization = true
+    });
+    assert!(!assembly.contains("r13"), "r13 is written:\n{}", assembly);
+}
+
+#[test]
+fn regalloc_keeps_compared_spills_until_the_readers() {
+    // MSP430 has no condition flags, and no register left for `r5` and
+    // `r6`: both are reloaded for the `cmp`, and `jle` and `cmovg` compare
+    // them again after it.
+    let source = "
+section .text
+global _start
+_start:
+    mov r0, 1
+    mov r1, 2
+    mov r2, 3
+    mov r3, 4
+    mov r4, 5
+    mov r5, 7
+    mov r6, 9
+    cmp r5, r6
+    jle small
+    mov r4, r0
+    cmovg r4, r5
+small:
+    add r0, r1
+    add r0, r2
+    add r0, r3
+    add r0, r4
+    syscall exit
+";
+    let assembly = compile(source, Architecture::MSP430, "regalloc", |c| {
+        c.enable_register_allocation_optimization = true
+    });
+    assert!(
+        !assembly.contains(".error"),
+        "an error is emitted:\n{}",
+        assembly
+    );
+}
+
+#[test]
+fn flags_expand_works_out_what_the_flags_hold() {
+    // Overflow and parity, worked out the way a target without flags must,
+    // and a reader after them comparing again: run on AMD64, the expanded
+    // program has to exit as the original does.
+    let source = "
+section .text
+global _start
+_start:
+    mov r0, 0
+    mov r1, 0x7fffffffffffffff
+    mov r2, 1
+    mov r3, 1
+    add r1, r2
+    cmovo r0, r3
+    js negative
+    or r0, 8
+negative:
+    sub r1, r2
+    jno clear
+    or r0, 2
+clear:
+    mov r1, 7
+    test r1, r1
+    jp even
+    or r0, 4
+even:
+    mov r1, 0x8000000000000000
+    mov r3, 16
+    mov r5, 0
+    dec r1
+    cmovo r5, r3
+    or r0, r5
+    syscall exit
+";
+    let instructions = Parser::new(source).parse().expect("the program parses");
+    let regs = Registers::new(&create_arch_codegen(&Architecture::AMD64).get_register_map());
+    let expanded = flags::expand(instructions.clone(), &regs, 64, &|tested| {
+        matches!(tested, Tested::Operands | Tested::Mask)
+    });
+    assert!(
+        !expanded
+            .iter()
+            .any(|i| matches!(i, Instruction::CmovOv(_) | Instruction::Jp(_))),
+        "the overflow and parity readers are left"
+    );
+    let generate = |instructions: &[Instruction]| {
+        let config = CodeGenConfig {
+            enable_dead_code_elimination: false,
+            unwind_info: false,
+            ..CodeGenConfig::default()
+        };
+        let target = TargetTriple::new(Architecture::AMD64, Platform::Linux);
+        CodeGenerator::with_config(target, config)
+            .generate(instructions)
+            .expect("the program compiles")
+    };
+    assert_eq!(execute(&generate(&instructions), "flags"), 23);
+    assert_eq!(execute(&generate(&expanded), "flags"), 23);
+}
+
+#[test]
+fn unroll_runs_the_remainder() {
+    // 23 iterations: five unrolled rounds of four, then three more.
+    let source = "
+section .text
+global _start
+_start:
+    mov r0, 0
+    mov r1, 0
+loop:
+    add r0, r1
+    inc r1
+    cmp r1, 23
+    jl loop
+    syscall exit
+";
+    let status = run(source, "unroll", |c| c.enable_loop_unrolling = true);
+    assert_eq!(status, 253);
+}
+
+#[test]
+fn cse_reuses_values_until_they_change() {
+    let source = "
+section .data
+val dq 0
+
+section .text
+global _start
+_start:
+    mov r1, 6
+    mov r2, 7
+    mov r3, r1
+    mul r3, r2
+    mov r4, r1
+    mul r4, r2
+    store [val], r3
+    load r5, [val]
+    add r1, 1
+    mov r6, r1
+    mul r6, r2
+    mov r0, r3
+    add r0, r4
+    add r0, r5
+    add r0, r6
+    syscall exit
+";
+    let status = run(source, "cse", |c| {
+        c.enable_common_subexpression_elimination = true
+    });
+    // 42 + 42 + 42 + 49
+    assert_eq!(status, 175);
+}
+
+#[test]
+fn inline_copies_the_body_at_each_call() {
+    let source = "
+section .text
+global _start
+triple:
+    mov r1, r0
+    add r0, r1
+    add r0, r1
+    ret
+_start:
+    mov r0, 3
+    call triple
+    call triple
+    add r0, 1
+    syscall exit
+";
+    let status = run(source, "inline", |c| c.enable_function_inlining = true);
+    assert_eq!(status, 28);
+}
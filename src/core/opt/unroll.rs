@@ -0,0 +1,722 @@
+//! Loop unrolling.
+//!
+//! Only counted loops are unrolled: innermost natural loops laid out as one
+//! run of blocks, whose exit test compares an induction register, stepped by
+//! a constant once per iteration, against a bound that does not change inside
+//! the loop. Two shapes are recognized:
+//!
+//! ```asm
+//! loop:                       loop:
+//!     ...                         cmp r1, r2
+//!     inc r0                      jge done
+//!     cmp r0, r1                  ...
+//!     jl loop                     inc r1
+//!                                 jmp loop
+//! ```
+//!
+//! as well as `dec r0` / `jne loop` at the bottom. Unrolling by `n` puts a
+//! guard at the header that checks that at least `n` more iterations will
+//! run, followed by `n` copies of the body without their exit tests. When the
+//! guard fails, the original loop runs the remaining iterations.
+//!
+//! A `.unroll n` directive right before the header label overrides the size
+//! heuristic for that loop.
+
+use std::collections::{HashMap, HashSet};
+
+use super::cfg::{Cfg, branch_target, operands_mut, referenced_names};
+use super::liveness::{Liveness, effects};
+use super::loops::{self, Loop};
+use super::{FLAGS, OptReport, Registers, Target, is_code, is_memory, parse_immediate};
+use crate::core::Instruction;
+
+const PASS: &str = "unroll";
+
+/// Code instructions an unrolled loop may grow to without a `.unroll` hint.
+const SIZE_LIMIT: usize = 64;
+
+pub fn run(
+    instructions: Vec<Instruction>,
+    regs: &Registers,
+    target: &Target,
+    factor: u32,
+    report: &mut OptReport,
+) -> Vec<Instruction> {
+    let cfg = Cfg::build(&instructions);
+    let loops = loops::find(&instructions, &cfg);
+    let liveness = Liveness::analyze(&instructions, &cfg, regs);
+    let labels = Labels::new(&instructions);
+    let mut hints = hints(&instructions, report);
+    let mut edits = Vec::new();
+
+    for lp in &loops {
+        let header = &cfg.blocks[lp.header];
+        let Some(label) = header.label.clone() else {
+            continue;
+        };
+        let hint = hints.remove(&label);
+        let counted = match counted(&instructions, &cfg, lp, &labels, regs, &liveness) {
+            Ok(counted) => counted,
+            Err(reason) => {
+                if let Some(n) = hint {
+                    report.note(
+                        PASS,
+                        format!("`{}`: ignored `.unroll {}`: {}", label, n, reason),
+                    );
+                }
+                continue;
+            }
+        };
+
+        let times = match hint {
+            Some(n) => n,
+            None => factor.min((SIZE_LIMIT / counted.size.max(1)) as u32),
+        };
+        if times < 2 {
+            continue;
+        }
+        match unroll(&instructions, &counted, times, &labels, regs, target) {
+            Ok(code) => {
+                report.note(PASS, format!("`{}`: unrolled {} times", label, times));
+                edits.push((counted.range.clone(), code));
+            }
+            Err(reason) => report.note(PASS, format!("`{}`: left rolled: {}", label, reason)),
+        }
+    }
+
+    for (label, n) in hints {
+        report.note(
+            PASS,
+            format!("ignored `.unroll {}`: `{}` does not start a loop", n, label),
+        );
+    }
+
+    let mut out = Vec::with_capacity(instructions.len());
+    let mut next = 0;
+    for (range, code) in edits {
+        out.extend_from_slice(&instructions[next..range.start]);
+        out.extend(code);
+        next = range.end;
+    }
+    out.extend_from_slice(&instructions[next..]);
+    out
+}
+
+/// Where the program defines and uses its labels, gathered once for all loops.
+struct Labels<'a> {
+    defined: HashSet<&'a str>,
+    /// Instructions naming each label, other than its definition.
+    uses: HashMap<&'a str, Vec<usize>>,
+}
+
+impl<'a> Labels<'a> {
+    fn new(instructions: &'a [Instruction]) -> Self {
+        let mut defined = HashSet::new();
+        let mut uses: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, instruction) in instructions.iter().enumerate() {
+            if let Instruction::Label(name) = instruction {
+                defined.insert(name.as_str());
+                continue;
+            }
+            for name in referenced_names(instruction) {
+                uses.entry(name).or_default().push(i);
+            }
+        }
+        Labels { defined, uses }
+    }
+}
+
+/// `.unroll` hints by the label they precede. A hint that is not directly
+/// followed by a label is reported and dropped.
+fn hints(instructions: &[Instruction], report: &mut OptReport) -> HashMap<String, u32> {
+    let mut hints = HashMap::new();
+    let mut pending: Option<u32> = None;
+    for instruction in instructions {
+        match instruction {
+            Instruction::Unroll(n) => {
+                match parse_immediate(n).and_then(|n| u32::try_from(n).ok()) {
+                    Some(n) => pending = Some(n),
+                    None => report.note(PASS, format!("ignored `.unroll {}`: not a count", n)),
+                }
+            }
+            Instruction::Label(name) => {
+                if let Some(n) = pending.take() {
+                    hints.insert(name.clone(), n);
+                }
+            }
+            _ if is_code(instruction) => {
+                if let Some(n) = pending.take() {
+                    report.note(
+                        PASS,
+                        format!("ignored `.unroll {}`: not followed by a label", n),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+    hints
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cond {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    B,
+    Be,
+    A,
+    Ae,
+}
+
+impl Cond {
+    fn of(instruction: &Instruction) -> Option<Cond> {
+        use Instruction::*;
+        Some(match instruction {
+            Je(_) => Cond::Eq,
+            Jne(_) => Cond::Ne,
+            Jl(_) => Cond::Lt,
+            Jle(_) => Cond::Le,
+            Jg(_) => Cond::Gt,
+            Jge(_) => Cond::Ge,
+            Jb(_) => Cond::B,
+            Jbe(_) => Cond::Be,
+            Ja(_) => Cond::A,
+            Jae(_) => Cond::Ae,
+            _ => return None,
+        })
+    }
+
+    fn negate(self) -> Cond {
+        match self {
+            Cond::Eq => Cond::Ne,
+            Cond::Ne => Cond::Eq,
+            Cond::Lt => Cond::Ge,
+            Cond::Le => Cond::Gt,
+            Cond::Gt => Cond::Le,
+            Cond::Ge => Cond::Lt,
+            Cond::B => Cond::Ae,
+            Cond::Be => Cond::A,
+            Cond::A => Cond::Be,
+            Cond::Ae => Cond::B,
+        }
+    }
+
+    /// The condition with its operands exchanged.
+    fn swap(self) -> Cond {
+        match self {
+            Cond::Lt => Cond::Gt,
+            Cond::Le => Cond::Ge,
+            Cond::Gt => Cond::Lt,
+            Cond::Ge => Cond::Le,
+            Cond::B => Cond::A,
+            Cond::Be => Cond::Ae,
+            Cond::A => Cond::B,
+            Cond::Ae => Cond::Be,
+            cond => cond,
+        }
+    }
+
+    fn is_unsigned(self) -> bool {
+        matches!(self, Cond::B | Cond::Be | Cond::A | Cond::Ae)
+    }
+
+    /// Whether the condition holds for values below the bound; `None` when
+    /// it does not order them.
+    fn holds_below(self) -> Option<bool> {
+        match self {
+            Cond::Lt | Cond::Le | Cond::B | Cond::Be => Some(true),
+            Cond::Gt | Cond::Ge | Cond::A | Cond::Ae => Some(false),
+            Cond::Eq | Cond::Ne => None,
+        }
+    }
+
+    fn branch(self, label: &str) -> Instruction {
+        use Instruction::*;
+        let label = label.to_string();
+        match self {
+            Cond::Eq => Je(label),
+            Cond::Ne => Jne(label),
+            Cond::Lt => Jl(label),
+            Cond::Le => Jle(label),
+            Cond::Gt => Jg(label),
+            Cond::Ge => Jge(label),
+            Cond::B => Jb(label),
+            Cond::Be => Jbe(label),
+            Cond::A => Ja(label),
+            Cond::Ae => Jae(label),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Shape {
+    /// The exit test is the back edge.
+    Bottom,
+    /// The header tests for the exit and the latch jumps back.
+    Top,
+}
+
+/// A loop that runs while `induction cond bound`, stepping `induction` by
+/// `step` once per iteration.
+#[derive(Debug)]
+struct Counted {
+    range: std::ops::Range<usize>,
+    shape: Shape,
+    /// The `cmp` feeding the exit test, unless the step sets the flags.
+    compare: Option<usize>,
+    /// The conditional branch of the exit test.
+    test: usize,
+    /// The `jmp` back to the header of a top-tested loop.
+    back: Option<usize>,
+    induction: String,
+    step: i64,
+    cond: Cond,
+    bound: String,
+    /// Load that refreshes the bound from invariant memory every iteration.
+    reload: Option<Instruction>,
+    /// Code instructions in the loop.
+    size: usize,
+}
+
+fn counted(
+    instructions: &[Instruction],
+    cfg: &Cfg,
+    lp: &Loop,
+    labels: &Labels,
+    regs: &Registers,
+    liveness: &Liveness,
+) -> Result<Counted, &'static str> {
+    if !lp.is_innermost() {
+        return Err("it contains another loop");
+    }
+    let [latch] = lp.latches[..] else {
+        return Err("it has more than one back edge");
+    };
+    if !lp.blocks.iter().copied().eq(lp.header..=latch) {
+        return Err("its blocks are not laid out in one run");
+    }
+    let header = &cfg.blocks[lp.header];
+    let range = header.start..cfg.blocks[latch].end;
+    let body = &instructions[range.clone()];
+    let label = header.label.as_deref().unwrap_or_default();
+
+    for instruction in body {
+        if effects(instruction, regs).barrier
+            || matches!(
+                instruction,
+                Instruction::Enter(_) | Instruction::Leave | Instruction::Section(_)
+            )
+        {
+            return Err("it calls out or leaves the function");
+        }
+    }
+    let inner: HashSet<&str> = body
+        .iter()
+        .filter_map(|i| match i {
+            Instruction::Label(name) if name != label => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+    for name in inner {
+        for &i in labels.uses.get(name).into_iter().flatten() {
+            if !range.contains(&i) || branch_target(&instructions[i]).is_none() {
+                return Err("a label inside it is used elsewhere");
+            }
+        }
+    }
+
+    let last_code = |block: usize| {
+        let block = &cfg.blocks[block];
+        (block.start..block.end)
+            .rev()
+            .find(|&i| is_code(&instructions[i]))
+    };
+    let back_edge = last_code(latch).ok_or("it has no back edge")?;
+    let (shape, test, cond, back) = match &instructions[back_edge] {
+        Instruction::Jmp(target) if target == label && latch != lp.header => {
+            let test = last_code(lp.header).ok_or("its header is empty")?;
+            let cond = Cond::of(&instructions[test]).ok_or("its header does not test for exit")?;
+            let exit = branch_target(&instructions[test]).unwrap_or_default();
+            if cfg.labels.get(exit).is_some_and(|b| lp.blocks.contains(b)) {
+                return Err("its header does not test for exit");
+            }
+            (Shape::Top, test, cond.negate(), Some(back_edge))
+        }
+        instruction if branch_target(instruction) == Some(label) => {
+            let cond = Cond::of(instruction).ok_or("its back edge is not a compare and branch")?;
+            (Shape::Bottom, back_edge, cond, None)
+        }
+        _ => return Err("its back edge is not a compare and branch"),
+    };
+
+    let test_block = if shape == Shape::Top {
+        lp.header
+    } else {
+        latch
+    };
+    let setter = (cfg.blocks[test_block].start..test)
+        .rev()
+        .find(|&i| is_code(&instructions[i]))
+        .ok_or("nothing sets the flags of its exit test")?;
+    let defined_inside = |name: &str| {
+        regs.as_destination(name)
+            .is_some_and(|reg| body.iter().any(|i| effects(i, regs).defs.contains(&reg)))
+    };
+    let (compare, induction, bound, cond) = match &instructions[setter] {
+        Instruction::Cmp((a, b)) if defined_inside(a) => (Some(setter), a, b.as_str(), cond),
+        Instruction::Cmp((a, b)) if defined_inside(b) => (Some(setter), b, a.as_str(), cond.swap()),
+        Instruction::Inc(a)
+        | Instruction::Dec(a)
+        | Instruction::Add((a, _))
+        | Instruction::Sub((a, _))
+            if shape == Shape::Bottom =>
+        {
+            (None, a, "0", cond)
+        }
+        _ => return Err("its exit test does not compare an induction register"),
+    };
+    let induction = induction.trim().to_string();
+    let bound = bound.trim().to_string();
+
+    let reg = regs
+        .as_destination(&induction)
+        .ok_or("its exit test does not compare an induction register")?;
+    if ["sp", "sb"]
+        .iter()
+        .any(|alias| regs.resolve(alias) == Some(&reg))
+    {
+        return Err("its exit test does not compare an induction register");
+    }
+    let updates: Vec<usize> = range
+        .clone()
+        .filter(|&i| effects(&instructions[i], regs).defs.contains(&reg))
+        .collect();
+    let [update] = updates[..] else {
+        return Err("its induction register changes more than once per iteration");
+    };
+    let latch_block = &cfg.blocks[latch];
+    if !(latch_block.start..latch_block.end).contains(&update)
+        || (shape == Shape::Bottom && update > setter)
+        || (compare.is_none() && update != setter)
+    {
+        return Err("its induction register is not stepped once per iteration");
+    }
+    let step = match &instructions[update] {
+        Instruction::Inc(_) => Some(1),
+        Instruction::Dec(_) => Some(-1),
+        Instruction::Add((_, s)) => parse_immediate(s),
+        Instruction::Sub((_, s)) => parse_immediate(s).and_then(i64::checked_neg),
+        _ => None,
+    }
+    .filter(|s| *s != 0 && s.unsigned_abs() < 1 << 16)
+    .ok_or("its induction register is not stepped by a constant")?;
+    match cond.holds_below() {
+        Some(below) if below == (step > 0) => {}
+        None if cond == Cond::Ne => {}
+        _ => return Err("its exit test does not bound the induction register"),
+    }
+
+    let mut reload = None;
+    if is_memory(&bound) {
+        return Err("its bound is read from memory");
+    }
+    for r in regs.in_operand(&bound) {
+        if r == reg {
+            return Err("its bound depends on the induction register");
+        }
+        let defs: Vec<usize> = range
+            .clone()
+            .filter(|&i| effects(&instructions[i], regs).defs.contains(&r))
+            .collect();
+        match defs[..] {
+            [] => {}
+            [def]
+                if compare.is_some_and(|c| def < c)
+                    && block_of(cfg, def) == block_of(cfg, setter) =>
+            {
+                reload = Some(invariant_load(instructions, range.clone(), def, &r, regs)?);
+                if liveness.live_in[lp.header].contains(&r) {
+                    return Err("its bound changes inside the loop");
+                }
+            }
+            _ => return Err("its bound changes inside the loop"),
+        }
+    }
+
+    if flags_read(instructions, cfg, regs, lp.header, header.start) {
+        return Err("the flags are live at its header");
+    }
+    if shape == Shape::Top && flags_read(instructions, cfg, regs, lp.header + 1, 0) {
+        return Err("the flags of its exit test are read");
+    }
+
+    Ok(Counted {
+        size: body.iter().filter(|i| is_code(i)).count(),
+        range,
+        shape,
+        compare,
+        test,
+        back,
+        induction,
+        step,
+        cond,
+        bound,
+        reload,
+    })
+}
+
+fn block_of(cfg: &Cfg, index: usize) -> Option<usize> {
+    let b = cfg
+        .blocks
+        .partition_point(|b| b.start <= index)
+        .checked_sub(1)?;
+    (index < cfg.blocks[b].end).then_some(b)
+}
+
+/// The instruction at `def`, if it loads `reg` from a plain symbol that
+/// nothing in `range` stores to.
+fn invariant_load(
+    instructions: &[Instruction],
+    range: std::ops::Range<usize>,
+    def: usize,
+    reg: &str,
+    regs: &Registers,
+) -> Result<Instruction, &'static str> {
+    let symbol = |operand: &str| {
+        let inner = operand.trim().strip_prefix('[')?.strip_suffix(']')?.trim();
+        let plain = inner
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+        (plain && regs.resolve(inner).is_none()).then(|| inner.to_string())
+    };
+    let source = match &instructions[def] {
+        Instruction::Load((d, s)) if regs.as_destination(d).as_deref() == Some(reg) => symbol(s),
+        _ => None,
+    }
+    .ok_or("its bound changes inside the loop")?;
+
+    for instruction in &instructions[range] {
+        let safe = match instruction {
+            Instruction::Store((d, _)) => symbol(d).is_some_and(|s| s != source),
+            Instruction::Load(_)
+            | Instruction::Lea(_)
+            | Instruction::Cmp(_)
+            | Instruction::Test(_)
+            | Instruction::Push(_)
+            | Instruction::Pop(_)
+            | Instruction::Prefetch(_) => true,
+            _ => !super::cfg::operands(instruction)
+                .iter()
+                .any(|o| is_memory(o)),
+        };
+        if !safe {
+            return Err("its bound is reloaded from memory the loop may write");
+        }
+    }
+    Ok(instructions[def].clone())
+}
+
+/// Whether a flag value present before instruction `from` of block `b` (or its
+/// start, if earlier) may be read before it is overwritten. `inc` and `dec`
+/// leave only the carry flag behind. No calling convention passes values in the
+/// flags, so calls, system calls and leaving the known code end the search.
+fn flags_read(
+    instructions: &[Instruction],
+    cfg: &Cfg,
+    regs: &Registers,
+    b: usize,
+    from: usize,
+) -> bool {
+    use Instruction::*;
+    let mut seen = HashSet::new();
+    // (block, first instruction, whether only the carry flag is still old)
+    let mut work = vec![(b, from, false)];
+    while let Some((b, from, carry_only)) = work.pop() {
+        let Some(block) = cfg.blocks.get(b) else {
+            continue;
+        };
+        let mut carry_only = carry_only;
+        let mut done = false;
+        for instruction in &instructions[from.max(block.start)..block.end] {
+            let e = effects(instruction, regs);
+            let reads = e.uses.iter().any(|u| u == FLAGS);
+            let writes = e.defs.iter().any(|d| d == FLAGS);
+            if e.barrier || (writes && !reads) {
+                done = true;
+                break;
+            }
+            match instruction {
+                Inc(_) | Dec(_) => carry_only = true,
+                Jb(_) | Jae(_) | Ja(_) | Jbe(_) | SetB(_) | SetAe(_) | SetA(_) | SetBe(_)
+                | CmovB(_) | CmovAe(_) | CmovA(_) | CmovBe(_) | Rcl(_) | Rcr(_) => return true,
+                _ if reads && !writes && !carry_only => return true,
+                _ => {}
+            }
+        }
+        if !done {
+            for &s in &block.succs {
+                if seen.insert((s, carry_only)) {
+                    work.push((s, cfg.blocks[s].start, carry_only));
+                }
+            }
+        }
+    }
+    false
+}
+
+fn unroll(
+    instructions: &[Instruction],
+    counted: &Counted,
+    times: u32,
+    labels: &Labels,
+    regs: &Registers,
+    target: &Target,
+) -> Result<Vec<Instruction>, &'static str> {
+    use Instruction::*;
+    let body = &instructions[counted.range.clone()];
+    let Label(label) = &body[0] else {
+        return Err("its header has no label");
+    };
+    let remainder = format!("{}__rem", label);
+    let exit = format!("{}__exit", label);
+    let restore = format!("{}__restore", label);
+    let copy = |name: &str, k: u32| format!("{}__u{}", name, k);
+    let inner: HashSet<&String> = body
+        .iter()
+        .filter_map(|i| match i {
+            Label(name) => Some(name),
+            _ => None,
+        })
+        .collect();
+    let taken = |name: &String| labels.defined.contains(name.as_str());
+    let clash = inner
+        .iter()
+        .any(|name| taken(&format!("{}__rem", name)) || (1..=times).any(|k| taken(&copy(name, k))));
+    if clash || taken(&exit) || taken(&restore) {
+        return Err("the labels it needs are taken");
+    }
+
+    // The guard: at least `times` iterations remain when the test would
+    // still pass with the induction register `times - 1` steps further.
+    let up = counted.step > 0;
+    let cond = match counted.cond {
+        Cond::Ne if up => Cond::Lt,
+        Cond::Ne => Cond::Gt,
+        cond => cond,
+    };
+    let distance = i128::from(counted.step.unsigned_abs()) * i128::from(times - 1);
+    // With line information, the guard and what follows the copies belong
+    // to the header's line rather than to the code before them.
+    let header = body.iter().find(|i| matches!(i, Loc(_))).cloned();
+    let mut code = vec![body[0].clone()];
+    code.extend(header.clone());
+    code.extend(counted.reload.clone());
+    let mut undo = None;
+    if let Some(bound) = parse_immediate(&counted.bound) {
+        let width = target.width.min(64);
+        let (lo, hi, value) = if cond.is_unsigned() {
+            let mask = u64::MAX >> (64 - width);
+            (0, i128::from(mask), i128::from(bound as u64 & mask))
+        } else {
+            let half = 1i128 << (width - 1);
+            (-half, half - 1, i128::from(bound))
+        };
+        let limit = if up {
+            value - distance
+        } else {
+            value + distance
+        };
+        if !(lo..=hi).contains(&limit) {
+            return Err("the guard's bound is out of range");
+        }
+        code.push(Cmp((counted.induction.clone(), limit.to_string())));
+        code.push(cond.negate().branch(&remainder));
+    } else {
+        // Once the test holds, the distance to the bound is exact as an
+        // unsigned value, whatever the signedness of the test. It is computed
+        // in place and taken back on both paths.
+        let (far, near) = if up {
+            (counted.bound.clone(), counted.induction.clone())
+        } else {
+            (counted.induction.clone(), counted.bound.clone())
+        };
+        if regs.as_destination(&far).is_none() {
+            return Err("its bound is not in a register");
+        }
+        let short = if matches!(cond, Cond::Le | Cond::Ge | Cond::Be | Cond::Ae) {
+            Cond::B
+        } else {
+            Cond::Be
+        };
+        let back = Add((far.clone(), near.clone()));
+        code.push(Cmp((counted.induction.clone(), counted.bound.clone())));
+        code.push(cond.negate().branch(&remainder));
+        code.push(Sub((far.clone(), near)));
+        code.push(Cmp((far, distance.to_string())));
+        code.push(short.branch(&restore));
+        code.push(back.clone());
+        undo = Some(back);
+    }
+
+    let start = counted.range.start;
+    for k in 1..=times {
+        let last = k == times;
+        for (offset, instruction) in body.iter().enumerate().skip(1) {
+            let i = start + offset;
+            let is_test = i == counted.test || Some(i) == counted.compare;
+            let keep = match counted.shape {
+                Shape::Bottom => last || !is_test,
+                Shape::Top => !is_test && (last || Some(i) != counted.back),
+            };
+            if !keep {
+                continue;
+            }
+            let mut instruction = instruction.clone();
+            rename(&mut instruction, &|name| {
+                (name != label && inner.contains(&name.to_string())).then(|| copy(name, k))
+            });
+            code.push(instruction);
+        }
+    }
+    code.extend(header);
+    if counted.shape == Shape::Bottom {
+        code.push(Jmp(exit.clone()));
+    }
+    if let Some(back) = undo {
+        code.push(Label(restore));
+        code.push(back);
+    }
+
+    for instruction in body {
+        let mut instruction = instruction.clone();
+        rename(&mut instruction, &|name| {
+            inner
+                .contains(&name.to_string())
+                .then(|| format!("{}__rem", name))
+        });
+        code.push(instruction);
+    }
+    if counted.shape == Shape::Bottom {
+        code.push(Label(exit));
+    }
+    Ok(code)
+}
+
+/// Renames a label definition or branch target through `map`.
+fn rename(instruction: &mut Instruction, map: &dyn Fn(&str) -> Option<String>) {
+    if let Instruction::Label(name) = instruction {
+        if let Some(new) = map(name) {
+            *name = new;
+        }
+    } else if branch_target(instruction).is_some() {
+        for operand in operands_mut(instruction) {
+            if let Some(new) = map(operand) {
+                *operand = new;
+            }
+        }
+    }
+}
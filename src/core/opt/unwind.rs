@@ -0,0 +1,335 @@
+//! Unwind information: where each function's caller frame is.
+//!
+//! Functions are the labels named by `global` or `function`, each running to
+//! the next one or the end of its section. On entry the canonical frame
+//! address (CFA), the stack pointer before the call, is the stack pointer
+//! plus whatever the call pushed. `push`, `pop` and immediate adjustments of
+//! `sp` move the stack pointer away from it; `enter`, or `mov` of `sp` into
+//! the frame pointer, finds the CFA from the frame pointer until `leave` or
+//! the reverse `mov`. Callee-saved registers pushed stay recorded until they
+//! are popped.
+//!
+//! The frame is followed along the control-flow graph, so code after an
+//! epilogue that a jump leads to gets the frame the jump leaves. Anything it
+//! cannot be followed through is an error: other writes to `sp`, writes to
+//! the frame pointer while the CFA is found from it, `pusha` and `popa`, and
+//! labels reached with different frames.
+
+use std::collections::{HashMap, HashSet};
+
+use super::cfg::{Cfg, destination, ends_block};
+use super::{Registers, Target, is_code, is_memory, parse_immediate};
+use crate::arch::FrameLayout;
+use crate::core::{Instruction, Section};
+use crate::platform::Frame;
+
+/// A function unwind information is emitted for.
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    /// Index of the first instruction after the function's label.
+    pub start: usize,
+    /// One past the last instruction.
+    pub end: usize,
+    /// Index of the first instruction after the function's prologue, the
+    /// code that sets up its frame.
+    pub prologue_end: usize,
+    /// The frame on entry.
+    pub entry: Frame,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Unwind {
+    pub functions: Vec<Function>,
+    /// The frame before each instruction that starts a block or follows one
+    /// that touched the frame; `None` elsewhere, outside functions and in
+    /// code no path from a function's entry reaches.
+    pub frames: Vec<Option<Frame>>,
+}
+
+pub fn describe(
+    instructions: &[Instruction],
+    regs: &Registers,
+    target: &Target,
+    layout: &FrameLayout,
+) -> Result<Unwind, String> {
+    let mut unwind = Unwind {
+        functions: Vec::new(),
+        frames: vec![None; instructions.len()],
+    };
+    let Some(sp) = regs.resolve("sp") else {
+        return Ok(unwind);
+    };
+    let tracker = Tracker {
+        regs,
+        layout,
+        sp: sp.to_string(),
+        push: i64::from(target.push_size),
+        saveable: target
+            .callee_saved
+            .iter()
+            .map(String::as_str)
+            .chain(layout.record.iter().copied())
+            .collect(),
+    };
+    let cfg = Cfg::build(instructions);
+
+    for (name, start, end) in functions(instructions) {
+        let Some(&entry) = cfg.labels.get(&name) else {
+            continue;
+        };
+        let entry_frame = Frame {
+            cfa_register: tracker.sp.clone(),
+            cfa_offset: layout.return_address,
+            depth: layout.return_address,
+            saved: Vec::new(),
+        };
+        let mut entries = HashMap::from([(entry, entry_frame.clone())]);
+        let mut work = vec![entry];
+        while let Some(b) = work.pop() {
+            let block = &cfg.blocks[b];
+            let mut frame = entries[&b].clone();
+            unwind.frames[block.start] = Some(frame.clone());
+            for (i, instruction) in instructions
+                .iter()
+                .enumerate()
+                .take(block.end)
+                .skip(block.start)
+            {
+                if tracker.step(&mut frame, instruction)? && i + 1 < block.end {
+                    unwind.frames[i + 1] = Some(frame.clone());
+                }
+            }
+            for &s in &block.succs {
+                let successor = &cfg.blocks[s];
+                if !successor.in_text || !(start..end).contains(&successor.start) {
+                    continue;
+                }
+                match entries.get(&s) {
+                    None => {
+                        entries.insert(s, frame.clone());
+                        work.push(s);
+                    }
+                    Some(known) if *known != frame => {
+                        return Err(format!(
+                            "`{}` in `{}` is reached with different stack frames, \
+                             which unwind information cannot describe",
+                            successor.label.as_deref().unwrap_or(&name),
+                            name
+                        ));
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        // The prologue runs to the last instruction that grows the frame
+        // before the first label, jump or call, or anything shrinking it.
+        let mut prologue_end = start;
+        let mut frame = entry_frame.clone();
+        for (i, instruction) in instructions.iter().enumerate().take(end).skip(start) {
+            if matches!(
+                instruction,
+                Instruction::Label(_) | Instruction::Call(_) | Instruction::Syscall(_)
+            ) || ends_block(instruction)
+            {
+                break;
+            }
+            if !is_code(instruction) {
+                continue;
+            }
+            let (depth, saved) = (frame.depth, frame.saved.len());
+            if !tracker.step(&mut frame, instruction)? {
+                continue;
+            }
+            if frame.depth < depth || frame.saved.len() < saved {
+                break;
+            }
+            prologue_end = i + 1;
+        }
+
+        unwind.functions.push(Function {
+            name,
+            start,
+            end,
+            prologue_end,
+            entry: entry_frame,
+        });
+    }
+    Ok(unwind)
+}
+
+/// Every function with code, as its name and the range after its label.
+pub fn functions(instructions: &[Instruction]) -> Vec<(String, usize, usize)> {
+    let declared: HashSet<&str> = instructions
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::Global(name) | Instruction::Function(name) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    let mut functions = Vec::new();
+    let mut current: Option<(String, usize)> = None;
+    let mut in_text = true;
+    for (i, instruction) in instructions.iter().enumerate() {
+        let (closes, opens) = match instruction {
+            Instruction::Section(section) => {
+                in_text = matches!(section, Section::Text);
+                (true, None)
+            }
+            Instruction::Label(name) if in_text && declared.contains(name.as_str()) => {
+                (true, Some(name))
+            }
+            _ => (false, None),
+        };
+        if closes && let Some((name, start)) = current.take() {
+            functions.push((name, start, i));
+        }
+        if let Some(name) = opens {
+            current = Some((name.clone(), i + 1));
+        }
+    }
+    if let Some((name, start)) = current {
+        functions.push((name, start, instructions.len()));
+    }
+    functions.retain(|(_, start, end)| instructions[*start..*end].iter().any(is_code));
+    functions
+}
+
+struct Tracker<'a> {
+    regs: &'a Registers,
+    layout: &'a FrameLayout,
+    sp: String,
+    push: i64,
+    /// Registers whose saved copies unwinding restores.
+    saveable: HashSet<&'a str>,
+}
+
+impl Tracker<'_> {
+    /// Applies `instruction` to `frame`; whether it touched the frame.
+    fn step(&self, frame: &mut Frame, instruction: &Instruction) -> Result<bool, String> {
+        use Instruction::*;
+        let fp = self.layout.frame_pointer;
+        let framed = frame.cfa_register == fp;
+        let cannot = |what: &str| {
+            Err(format!(
+                "`{}` {}, which unwind information cannot describe",
+                instruction, what
+            ))
+        };
+        let record = self.layout.record.len() as i64 * self.layout.slot;
+
+        match instruction {
+            Push(op) => {
+                frame.depth += self.push;
+                if let Some(register) = self.regs.as_destination(op)
+                    && self.saveable.contains(register.as_str())
+                    && !frame.saved.iter().any(|(saved, _)| *saved == register)
+                {
+                    frame.saved.push((register, frame.depth));
+                }
+            }
+            Pop(op) => {
+                if self.is_sp(op) {
+                    return cannot("pops the stack pointer");
+                }
+                if framed && self.is(op, fp) {
+                    return cannot("pops the frame pointer the frame is found from");
+                }
+                frame.depth -= self.push;
+            }
+            Sub((op, n)) | Add((op, n)) if self.is_sp(op) => {
+                let Some(n) = parse_immediate(n) else {
+                    return cannot("moves the stack pointer by an amount not known in advance");
+                };
+                frame.depth += if matches!(instruction, Sub(_)) { n } else { -n };
+            }
+            Lea((op, address)) if self.is_sp(op) => {
+                let Some(offset) = self.stack_offset(address) else {
+                    return cannot("moves the stack pointer by an amount not known in advance");
+                };
+                frame.depth -= offset;
+            }
+            Mov((dst, src)) if self.is(dst, fp) && self.is_sp(src) => {
+                frame.cfa_register = fp.to_string();
+                frame.cfa_offset = frame.depth;
+            }
+            Mov((dst, src)) if framed && self.is_sp(dst) && self.is(src, fp) => {
+                frame.cfa_register = self.sp.clone();
+                frame.depth = frame.cfa_offset;
+            }
+            Enter((size, nesting)) => {
+                let (Some(size), Some(0)) = (parse_immediate(size), parse_immediate(nesting))
+                else {
+                    return cannot(
+                        "builds a frame of a size or nesting level not known in advance",
+                    );
+                };
+                let top = frame.depth + record;
+                for (k, register) in self.layout.record.iter().enumerate() {
+                    if !frame.saved.iter().any(|(saved, _)| saved == register) {
+                        frame
+                            .saved
+                            .push((register.to_string(), top - k as i64 * self.layout.slot));
+                    }
+                }
+                frame.cfa_register = fp.to_string();
+                frame.cfa_offset = top;
+                frame.depth = top + size;
+            }
+            Leave => {
+                if !framed {
+                    return cannot("tears down a frame no `enter` or `mov` of `sp` set up");
+                }
+                frame.cfa_register = self.sp.clone();
+                frame.depth = frame.cfa_offset - record;
+            }
+            Pusha | Popa => return cannot("moves the stack pointer"),
+            _ => {
+                let Some(written) = destination(instruction) else {
+                    return Ok(false);
+                };
+                if self.is_sp(written) {
+                    return cannot("changes the stack pointer");
+                }
+                if framed && self.is(written, fp) {
+                    return cannot("changes the frame pointer the frame is found from");
+                }
+                return Ok(false);
+            }
+        }
+        if frame.cfa_register == self.sp {
+            frame.cfa_offset = frame.depth;
+        }
+        // Popped slots no longer hold the registers saved in them.
+        let depth = frame.depth;
+        frame.saved.retain(|(_, offset)| *offset <= depth);
+        Ok(true)
+    }
+
+    /// Whether `operand` is the physical register `register`.
+    fn is(&self, operand: &str, register: &str) -> bool {
+        !is_memory(operand) && self.regs.resolve(operand.trim()) == Some(register)
+    }
+
+    fn is_sp(&self, operand: &str) -> bool {
+        self.is(operand, &self.sp)
+    }
+
+    /// `n` for an operand `[sp]`, `[sp + n]` or `[sp - n]`.
+    fn stack_offset(&self, operand: &str) -> Option<i64> {
+        let inner = operand.trim().strip_prefix('[')?.strip_suffix(']')?.trim();
+        let (base, offset) = match inner.find(['+', '-']) {
+            Some(at) => {
+                let n = parse_immediate(&inner[at + 1..])?;
+                (
+                    &inner[..at],
+                    if inner[at..].starts_with('-') { -n } else { n },
+                )
+            }
+            None => (inner, 0),
+        };
+        (self.regs.resolve(base.trim()) == Some(self.sp.as_str())).then_some(offset)
+    }
+}
@@ -2,9 +2,12 @@ use super::*;
 use std::collections::HashMap;
 
 pub struct Parser {
-    lines: Vec<String>,
+    /// Non-empty lines with their line numbers, comments stripped.
+    lines: Vec<(usize, String)>,
     current_section: Section,
     constants: HashMap<String, String>,
+    /// Insert a `.loc` before the code of every line.
+    line_info: bool,
 }
 
 impl Parser {
@@ -12,7 +15,7 @@ impl Parser {
         let estimated_lines = input.len() / 20;
         let mut lines = Vec::with_capacity(estimated_lines);
         
-        for line in input.lines() {
+        for (number, line) in input.lines().enumerate() {
             let line = if let Some(pos) = find_byte(line.as_bytes(), b';') {
                 &line[..pos]
             } else {
@@ -20,7 +23,7 @@ impl Parser {
             };
             let trimmed = line.trim();
             if !trimmed.is_empty() {
-                lines.push(trimmed.to_string());
+                lines.push((number + 1, trimmed.to_string()));
             }
         }
 
@@ -28,14 +31,22 @@ impl Parser {
             lines,
             current_section: Section::Text,
             constants: HashMap::with_capacity(16),
+            line_info: false,
         }
     }
 
+    /// Whether to mark the code of every line with its line number, for
+    /// `CodeGenConfig::debug_source`.
+    pub fn set_line_info(&mut self, enabled: bool) {
+        self.line_info = enabled;
+    }
+
     pub fn parse(&mut self) -> Result<Vec<Instruction>, String> {
         let mut instructions = Vec::with_capacity(self.lines.len());
 
         for i in 0..self.lines.len() {
-            let line = &self.lines[i];
+            let (number, line) = &self.lines[i];
+            let number = *number;
             
             if !line.is_empty() && line.as_bytes()[0] == b's' && line.starts_with("section") {
                 let section = self.parse_section(&line.to_string())?;
@@ -47,6 +58,9 @@ impl Parser {
 
             let instruction = self.parse_instruction(&line.to_string())?;
             if let Some(instr) = instruction {
+                if self.line_info && opt::is_code(&instr) {
+                    instructions.push(Instruction::Loc(number));
+                }
                 instructions.push(instr);
             }
         }
@@ -77,7 +91,15 @@ impl Parser {
                 self.current_section = Section::Rodata;
                 Ok(Some(Instruction::Section(Section::Rodata)))
             }
-            _ => Err(format!("Unknown section: {}", parts[1])),
+            // Anything else is passed through, e.g. interrupt vectors
+            name => match name.strip_prefix('.') {
+                Some(custom) if !custom.is_empty() => {
+                    let section = Section::Custom(custom.to_string());
+                    self.current_section = section.clone();
+                    Ok(Some(Instruction::Section(section)))
+                }
+                _ => Err(format!("Unknown section: {}", parts[1])),
+            },
         }
     }
 
@@ -245,6 +267,7 @@ impl Parser {
             "div" => Ok(Some(Instruction::Div(self.get_two(&parts)?))),
             "idiv" => Ok(Some(Instruction::Idiv(self.get_two(&parts)?))),
             "mod" => Ok(Some(Instruction::Mod(self.get_two(&parts)?))),
+            "mulh" => Ok(Some(Instruction::Mulh(self.get_two(&parts)?))),
             "inc" => Ok(Some(Instruction::Inc(self.get_one(&parts)?))),
             "dec" => Ok(Some(Instruction::Dec(self.get_one(&parts)?))),
             "neg" => Ok(Some(Instruction::Neg(self.get_one(&parts)?))),
@@ -350,8 +373,18 @@ impl Parser {
             
             // Directives
             "global" => Ok(Some(Instruction::Global(self.get_one(&parts)?))),
+            "function" => Ok(Some(Instruction::Function(self.get_one(&parts)?))),
             "extern" => Ok(Some(Instruction::Extern(self.get_one(&parts)?))),
             "align" => Ok(Some(Instruction::Align(self.get_one(&parts)?))),
+            ".unroll" => Ok(Some(Instruction::Unroll(self.get_one(&parts)?))),
+            ".inline" => Ok(Some(Instruction::Inline)),
+            ".noinline" => Ok(Some(Instruction::NoInline)),
+            ".likely" => Ok(Some(Instruction::Likely)),
+            ".unlikely" => Ok(Some(Instruction::Unlikely)),
+            ".loc" => match self.get_one(&parts)?.parse() {
+                Ok(line) => Ok(Some(Instruction::Loc(line))),
+                Err(_) => Err(format!("Invalid line number: {}", parts[1])),
+            },
             
             _ => Err(format!("Unknown instruction: {}", cmd)),
         }
@@ -14,10 +14,23 @@ pub fn compiler_uasm(uasm: String, target: TargetTriple) -> Result<String, Strin
     let mut parser = Parser::new(&uasm);
     let instructions = parser.parse()?;
     let code_generator = CodeGenerator::new(target);
-    let asm_code = code_generator.generate(&instructions);
+    let asm_code = code_generator.generate(&instructions)?;
     Ok(asm_code)
 }
 
+/// Compile UASM into the target, also returning a listing of every source
+/// line beside the assembly it produced
+pub fn compile_uasm_listing(uasm: String, target: TargetTriple) -> Result<(String, String), String> {
+    let mut parser = Parser::new(&uasm);
+    parser.set_line_info(true);
+    let instructions = parser.parse()?;
+    let code_generator = CodeGenerator::new(target);
+    let mut asm_code = Vec::new();
+    let (_, listing) = code_generator.generate_listing_to(&instructions, &uasm, &mut asm_code)?;
+    let asm_code = String::from_utf8(asm_code).expect("backends emit UTF-8");
+    Ok((asm_code, listing))
+}
+
 /// Compile UASM into Linux on target architecture
 pub fn compile_uasm_linux(uasm: String, arch: Architecture) -> Result<String, String> {
     let target = TargetTriple::new(arch, Platform::Linux);
@@ -9,14 +9,17 @@ use std::process;
 use crate::arch::list_target;
 use crate::arch::parse_target;
 use crate::core::TargetTriple;
-use crate::core::codegen::CodeGenerator;
+use crate::core::codegen::{CodeGenConfig, CodeGenerator};
 use crate::core::parser::Parser;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
     if args.len() < 2 {
-        eprintln!("Usage: {} <input.ua> [-o output.s] [-t target] \n", args[0]);
+        eprintln!(
+            "Usage: {} <input.ua> [-o output.s] [-t target] [-O | -Os] [-g] [-fpic] [-mzbb] [-mcpu=name] [-mabi=name] [-mram=address] [--listing file] [--no-unwind] [--unroll n] [--keep-dead] \n",
+            args[0]
+        );
         println!("List of support architectures:");
         list_target(false)
             .iter()
@@ -27,8 +30,10 @@ fn main() {
 
     let input_file = &args[1];
     let mut output_file = "output.s".to_string();
+    let mut listing_file = None;
     let mut architecture = TargetTriple::new(arch::Architecture::AMD64, platform::Platform::Linux);
 
+    let mut config = CodeGenConfig::default();
     let mut is_silent = false;
     let mut i = 2;
     while i < args.len() {
@@ -61,6 +66,81 @@ fn main() {
             }
             "-s" | "--silent" => {
                 is_silent = true;
+                i += 1;
+            }
+            "-O" | "--optimize" => {
+                config.enable_strength_reduction = true;
+                config.enable_register_allocation_optimization = true;
+                config.enable_instruction_scheduling = true;
+                config.enable_loop_unrolling = true;
+                config.enable_common_subexpression_elimination = true;
+                config.enable_function_inlining = true;
+                config.enable_branch_prediction_optimization = true;
+                i += 1;
+            }
+            "-Os" | "--optimize-size" => {
+                config.enable_size_minimization = true;
+                config.enable_strength_reduction = true;
+                config.enable_register_allocation_optimization = true;
+                config.enable_instruction_scheduling = true;
+                config.enable_common_subexpression_elimination = true;
+                config.enable_function_inlining = true;
+                config.enable_branch_prediction_optimization = true;
+                i += 1;
+            }
+            "--unroll" => {
+                match args.get(i + 1).and_then(|n| n.parse().ok()) {
+                    Some(factor) => {
+                        config.enable_loop_unrolling = true;
+                        config.unroll_factor = factor;
+                    }
+                    None => {
+                        eprintln!("Error: --unroll requires a count");
+                        process::exit(1);
+                    }
+                }
+                i += 2;
+            }
+            "-g" | "--debug" => {
+                config.debug_source = Some(input_file.clone());
+                i += 1;
+            }
+            "-fpic" | "-fPIC" => {
+                config.position_independent = true;
+                i += 1;
+            }
+            "-mzbb" => {
+                config.bit_manipulation = true;
+                i += 1;
+            }
+            flag if flag.starts_with("-mcpu=") => {
+                config.cpu = Some(flag["-mcpu=".len()..].to_string());
+                i += 1;
+            }
+            flag if flag.starts_with("-mabi=") => {
+                config.abi = Some(flag["-mabi=".len()..].to_string());
+                i += 1;
+            }
+            flag if flag.starts_with("-mram=") => {
+                config.ram = Some(flag["-mram=".len()..].to_string());
+                i += 1;
+            }
+            "--listing" => {
+                if i + 1 < args.len() {
+                    listing_file = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    eprintln!("Error: --listing requires a filename");
+                    process::exit(1);
+                }
+            }
+            "--no-unwind" => {
+                config.unwind_info = false;
+                i += 1;
+            }
+            "--keep-dead" => {
+                config.enable_dead_code_elimination = false;
+                i += 1;
             }
             _ => {
                 eprintln!("Error: Unknown option {}", args[i]);
@@ -78,6 +158,7 @@ fn main() {
     };
 
     let mut parser = Parser::new(&input_content);
+    parser.set_line_info(config.debug_source.is_some() || listing_file.is_some());
     let instructions = match parser.parse() {
         Ok(instructions) => instructions,
         Err(err) => {
@@ -86,15 +167,52 @@ fn main() {
         }
     };
 
-    let code_generator = CodeGenerator::new(architecture);
-    let asm_code = code_generator.generate(&instructions);
-
-    if let Err(err) = fs::write(&output_file, asm_code) {
-        eprintln!("Error writing output file '{}': {}", output_file, err);
-        process::exit(1);
-    }
+    let code_generator = CodeGenerator::with_config(architecture, config);
+    // The code goes to a file beside the output, which replaces it only once
+    // the program has compiled, so that a failure leaves it as it was.
+    let temporary_file = format!("{}.{}.tmp", output_file, process::id());
+    let mut output = match fs::File::create(&temporary_file) {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("Error writing output file '{}': {}", output_file, err);
+            process::exit(1);
+        }
+    };
+    let generated = match &listing_file {
+        Some(_) => code_generator
+            .generate_listing_to(&instructions, &input_content, &mut output)
+            .map(|(report, listing)| (report, Some(listing))),
+        None => code_generator
+            .generate_to(&instructions, &mut output)
+            .map(|report| (report, None)),
+    };
+    drop(output);
+    let report = match generated {
+        Ok((report, listing)) => {
+            if let Err(err) = fs::rename(&temporary_file, &output_file) {
+                let _ = fs::remove_file(&temporary_file);
+                eprintln!("Error writing output file '{}': {}", output_file, err);
+                process::exit(1);
+            }
+            if let (Some(path), Some(listing)) = (&listing_file, listing)
+                && let Err(err) = fs::write(path, listing)
+            {
+                eprintln!("Error writing listing file '{}': {}", path, err);
+                process::exit(1);
+            }
+            report
+        }
+        Err(err) => {
+            let _ = fs::remove_file(&temporary_file);
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+    };
 
     if !is_silent {
+        for note in &report.notes {
+            println!("{}", note);
+        }
         println!(
             "Successfully compiled '{}' to '{}'",
             input_file, output_file
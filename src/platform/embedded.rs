@@ -0,0 +1,199 @@
+use super::*;
+use crate::{arch::Architecture, core::opt};
+
+/// Bare-metal ELF, as microcontroller toolchains link it: no operating
+/// system and no alignment beyond what the processor needs. Z80 output is a
+/// flat binary instead, in the syntax sjasmplus and z88dk's z80asm share.
+pub struct EmbeddedPlatform {
+    architecture: Architecture,
+}
+
+impl EmbeddedPlatform {
+    pub fn new() -> Self {
+        EmbeddedPlatform {
+            architecture: Architecture::AVR, // default
+        }
+    }
+
+    /// The alignment data of `size` bytes needs, in bytes.
+    fn alignment(&self, size: usize) -> usize {
+        match self.architecture {
+            // 8-bit data buses load any address.
+            Architecture::AVR => 1,
+            Architecture::MSP430 => size.min(2),
+            _ => size.min(4),
+        }
+    }
+
+    /// Whether the output is a flat binary, without sections or symbols.
+    fn flat(&self) -> bool {
+        self.architecture == Architecture::Z80
+    }
+
+    /// `value` as little-endian 16-bit words, `count` of them.
+    fn words(value: &str, count: usize) -> Vec<String> {
+        match opt::parse_immediate(value) {
+            Some(value) => (0..count)
+                .map(|i| ((value >> (16 * i).min(63)) & 0xffff).to_string())
+                .collect(),
+            None => (0..count)
+                .map(|i| match i {
+                    0 => format!("({}) & 0xffff", value),
+                    1 => format!("(({}) >> 16) & 0xffff", value),
+                    _ => "0".to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    fn flat_data(&self, size: DataSize, name: &str, values: &[String]) -> String {
+        // Neither assembler has a 32-bit directive both accept.
+        let (directive, values) = match size {
+            DataSize::Byte => ("db", values.to_vec()),
+            DataSize::Word => ("dw", values.to_vec()),
+            DataSize::Dword => (
+                "dw",
+                values.iter().flat_map(|v| Self::words(v, 2)).collect(),
+            ),
+            DataSize::Qword => (
+                "dw",
+                values.iter().flat_map(|v| Self::words(v, 4)).collect(),
+            ),
+        };
+        format!("{}: {} {}\n", name, directive, values.join(", "))
+    }
+}
+
+impl Default for EmbeddedPlatform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PlatformCodeGen for EmbeddedPlatform {
+    fn get_section_prefix(&self, section: &Section) -> String {
+        match (section, self.architecture) {
+            // Everything follows in order from the origin.
+            (Section::Text, Architecture::Z80) => "; section .text\n".to_string(),
+            (Section::Data, Architecture::Z80) => "; section .data\n".to_string(),
+            (Section::Bss, Architecture::Z80) => "; section .bss\n".to_string(),
+            (Section::Rodata, Architecture::Z80) => "; section .rodata\n".to_string(),
+            (Section::Custom(section), Architecture::Z80) => format!("; section .{}\n", section),
+            // The startup code copies `.data` from flash and clears `.bss`
+            // only for objects that ask for it.
+            (Section::Data, Architecture::AVR) => {
+                ".section .data,\"aw\",@progbits\n.global __do_copy_data\n".to_string()
+            }
+            (Section::Bss, Architecture::AVR) => {
+                ".section .bss,\"aw\",@nobits\n.global __do_clear_bss\n".to_string()
+            }
+            // Constants stay in flash, where `lpm` reads them.
+            (Section::Rodata, Architecture::AVR) => {
+                ".section .progmem.data,\"a\",@progbits\n".to_string()
+            }
+            // msp430-elf crt0 pulls in the copy and clear loops for
+            // objects that reference their symbols.
+            (Section::Data, Architecture::MSP430) => {
+                ".section .data,\"aw\",@progbits\n.global __crt0_movedata\n".to_string()
+            }
+            (Section::Bss, Architecture::MSP430) => {
+                ".section .bss,\"aw\",@nobits\n.global __crt0_init_bss\n".to_string()
+            }
+            // The linker script places `__interrupt_vector_N` in slot N of
+            // the vector table, and `__reset_vector` in the last one.
+            (Section::Custom(section), Architecture::MSP430)
+                if section.starts_with("interrupt_vector_") || section == "reset_vector" =>
+            {
+                format!(".section __{},\"ax\",@progbits\n", section)
+            }
+            (Section::Text, _) => ".section .text,\"ax\",@progbits\n".to_string(),
+            (Section::Data, _) => ".section .data,\"aw\",@progbits\n".to_string(),
+            (Section::Bss, _) => ".section .bss,\"aw\",@nobits\n".to_string(),
+            (Section::Rodata, _) => ".section .rodata,\"a\",@progbits\n".to_string(),
+            (Section::Custom(section), _) => {
+                format!(".section .{},\"a\",@progbits\n", section)
+            }
+        }
+    }
+
+    fn get_global_directive(&self, symbol: &str) -> String {
+        if self.flat() {
+            return format!("; global {}\n", symbol);
+        }
+        format!(".globl {}\n.type {}, @function\n", symbol, symbol)
+    }
+
+    fn get_function_directive(&self, symbol: &str) -> String {
+        if self.flat() {
+            return String::new();
+        }
+        format!(".type {}, @function\n", symbol)
+    }
+
+    fn get_extern_directive(&self, symbol: &str) -> String {
+        if self.flat() {
+            return format!("; extern {}\n", symbol);
+        }
+        format!(".extern {}\n", symbol)
+    }
+
+    fn format_data_directive(&self, size: DataSize, name: &str, values: &[String]) -> String {
+        if self.flat() {
+            return self.flat_data(size, name, values);
+        }
+        let (directive, bytes) = match size {
+            DataSize::Byte => (".byte", 1),
+            DataSize::Word => (".2byte", 2),
+            DataSize::Dword => (".4byte", 4),
+            DataSize::Qword => (".8byte", 8),
+        };
+
+        let mut result = String::new();
+        let alignment = self.alignment(bytes);
+        if alignment > 1 {
+            result.push_str(&format!(".balign {}\n", alignment));
+        }
+        result.push_str(&format!("{}:\n", name));
+        result.push_str(&format!(".type {}, @object\n", name));
+        result.push_str(&format!("    {} {}\n", directive, values.join(", ")));
+        result.push_str(&format!(".size {}, .-{}\n", name, name));
+        result
+    }
+
+    fn format_reserve_directive(&self, name: &str, size: &String) -> String {
+        if self.flat() {
+            return match name {
+                "anonymous" => format!("    ds {}\n", size),
+                name => format!("{}: ds {}\n", name, size),
+            };
+        }
+        let mut result = String::new();
+        if let Ok(size_val) = size.parse::<usize>() {
+            let alignment = self.alignment(size_val);
+            if alignment > 1 {
+                result.push_str(&format!(".balign {}\n", alignment));
+            }
+        }
+
+        if name != "anonymous" {
+            result.push_str(&format!("{}:\n", name));
+            result.push_str(&format!(".type {}, @object\n", name));
+        }
+        result.push_str(&format!("    .space {}\n", size));
+        if name != "anonymous" {
+            result.push_str(&format!(".size {}, {}\n", name, size));
+        }
+        result
+    }
+
+    fn format_equ_directive(&self, name: &str, value: &str) -> String {
+        if self.flat() {
+            return format!("{} equ {}\n", name, value);
+        }
+        format!(".set {}, {}\n", name, value)
+    }
+
+    fn set_architecture(&mut self, arch: Architecture) {
+        self.architecture = arch;
+    }
+}
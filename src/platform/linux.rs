@@ -1,5 +1,5 @@
 use super::*;
-use crate::arch::Architecture;
+use crate::{arch::Architecture, core::opt};
 
 pub struct LinuxPlatform {
     architecture: Architecture,
@@ -11,6 +11,24 @@ impl LinuxPlatform {
             architecture: Architecture::AMD64, // default
         }
     }
+
+    /// Whether 64-bit data goes out as two 32-bit words, the low one first.
+    /// The 32-bit code loads a `dq` variable from its address, which must
+    /// hold the low word whatever the byte order; M68K is big-endian only.
+    fn splits_qwords(&self) -> bool {
+        matches!(self.architecture, Architecture::MIPS32 | Architecture::K68)
+    }
+
+    /// `value` as 32-bit words, the low one first.
+    fn words(value: &str) -> [String; 2] {
+        match opt::parse_immediate(value) {
+            Some(value) => [
+                (value & 0xffff_ffff).to_string(),
+                ((value >> 32) & 0xffff_ffff).to_string(),
+            ],
+            None => [value.to_string(), "0".to_string()],
+        }
+    }
 }
 
 impl PlatformCodeGen for LinuxPlatform {
@@ -43,6 +61,14 @@ impl PlatformCodeGen for LinuxPlatform {
         format!(".globl {}\n.type {}, {}\n", symbol, symbol, function_suffix)
     }
 
+    fn get_function_directive(&self, symbol: &str) -> String {
+        let function_suffix = match self.architecture {
+            Architecture::ARM32 => "%function",
+            _ => "@function",
+        };
+        format!(".type {}, {}\n", symbol, function_suffix)
+    }
+
     fn get_extern_directive(&self, symbol: &str) -> String {
         format!(".extern {}\n", symbol)
     }
@@ -92,7 +118,12 @@ impl PlatformCodeGen for LinuxPlatform {
 
         result.push_str(&format!("{}:\n", name));
         result.push_str(&format!(".type {}, {}\n", name, object_suffix));
-        result.push_str(&format!("    {} {}\n", directive, values.join(", ")));
+        if matches!(size, DataSize::Qword) && self.splits_qwords() {
+            let words: Vec<String> = values.iter().flat_map(|v| Self::words(v)).collect();
+            result.push_str(&format!("    .4byte {}\n", words.join(", ")));
+        } else {
+            result.push_str(&format!("    {} {}\n", directive, values.join(", ")));
+        }
         result.push_str(&format!(".size {}, .-{}\n", name, name));
         result
     }
@@ -1,3 +1,4 @@
+pub mod embedded;
 pub mod linux;
 pub mod macos;
 pub mod windows;
@@ -5,10 +6,13 @@ pub mod windows;
 use crate::{
     arch::Architecture,
     core::{DataSize, Section},
-    platform::{linux::LinuxPlatform, macos::MacOSPlatform, windows::WindowsPlatform},
+    platform::{
+        embedded::EmbeddedPlatform, linux::LinuxPlatform, macos::MacOSPlatform,
+        windows::WindowsPlatform,
+    },
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Format {
     ELF,
     COFF,
@@ -16,6 +20,8 @@ pub enum Format {
     XCOFF,
     A,
     MZ,
+    /// Raw bytes to load at the origin, without headers or symbols.
+    Binary,
     Custom,
 }
 
@@ -33,11 +39,102 @@ pub enum Platform {
 pub trait PlatformCodeGen {
     fn get_section_prefix(&self, section: &Section) -> String;
     fn get_global_directive(&self, symbol: &str) -> String;
+    /// Marks `symbol` as a function, where the object format records it.
+    fn get_function_directive(&self, symbol: &str) -> String {
+        let _ = symbol;
+        String::new()
+    }
     fn get_extern_directive(&self, symbol: &str) -> String;
     fn format_data_directive(&self, size: DataSize, name: &str, values: &[String]) -> String;
     fn format_reserve_directive(&self, name: &str, size: &String) -> String;
     fn format_equ_directive(&self, name: &str, value: &str) -> String;
     fn set_architecture(&mut self, arch: Architecture);
+
+    //
+    // Line Information
+    //
+    /// Directives that open the line table for `path`, the source file.
+    fn get_line_table_start(&self, path: &str) -> String {
+        format!(".file 1 {}\n", quoted(path))
+    }
+    /// Attributes the code that follows to `line` of the source file.
+    fn get_line_directive(&self, line: usize) -> String {
+        format!("    .loc 1 {}\n", line)
+    }
+    /// Directives that close the line table after the last instruction.
+    fn get_line_table_end(&self) -> String {
+        String::new()
+    }
+
+    //
+    // Unwind Information
+    //
+    /// Opens the unwind information of `symbol`'s function, after its label.
+    fn get_unwind_start(&self, symbol: &str) -> String {
+        let _ = symbol;
+        ".cfi_startproc\n".to_string()
+    }
+    /// Describes the frame changing from `from` to `to` at this point, which
+    /// is in the function's prologue when `prologue` is set.
+    fn get_unwind_step(&self, from: &Frame, to: &Frame, prologue: bool) -> String {
+        let _ = prologue;
+        cfi_step(from, to)
+    }
+    /// Marks the end of the function's prologue.
+    fn get_prologue_end(&self) -> String {
+        String::new()
+    }
+    /// Closes the unwind information of the function, after its last
+    /// instruction.
+    fn get_unwind_end(&self) -> String {
+        ".cfi_endproc\n".to_string()
+    }
+}
+
+/// Where the caller's frame is at some point in a function, for unwind
+/// information. The canonical frame address (CFA) is the stack pointer
+/// before the call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// The register the CFA is found from, and the CFA's distance above it.
+    pub cfa_register: String,
+    pub cfa_offset: i64,
+    /// Bytes the stack pointer is below the CFA.
+    pub depth: i64,
+    /// Registers saved on the stack, each with its distance below the CFA,
+    /// in the order they were saved.
+    pub saved: Vec<(String, i64)>,
+}
+
+/// DWARF call frame directives for a change from `from` to `to`.
+pub fn cfi_step(from: &Frame, to: &Frame) -> String {
+    let mut result = String::new();
+    if from.cfa_register != to.cfa_register && from.cfa_offset != to.cfa_offset {
+        result.push_str(&format!(
+            "    .cfi_def_cfa {}, {}\n",
+            to.cfa_register, to.cfa_offset
+        ));
+    } else if from.cfa_register != to.cfa_register {
+        result.push_str(&format!("    .cfi_def_cfa_register {}\n", to.cfa_register));
+    } else if from.cfa_offset != to.cfa_offset {
+        result.push_str(&format!("    .cfi_def_cfa_offset {}\n", to.cfa_offset));
+    }
+    for (register, offset) in &to.saved {
+        if !from.saved.contains(&(register.clone(), *offset)) {
+            result.push_str(&format!("    .cfi_offset {}, {}\n", register, -offset));
+        }
+    }
+    for (register, _) in &from.saved {
+        if !to.saved.iter().any(|(saved, _)| saved == register) {
+            result.push_str(&format!("    .cfi_restore {}\n", register));
+        }
+    }
+    result
+}
+
+/// `text` as an assembler string literal.
+pub fn quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
 }
 
 pub fn create_platform_codegen(
@@ -60,6 +157,11 @@ pub fn create_platform_codegen(
             macos_platform.set_architecture(*arch);
             Box::new(macos_platform)
         }
+        Platform::Embedded => {
+            let mut embedded_platform = EmbeddedPlatform::new();
+            embedded_platform.set_architecture(*arch);
+            Box::new(embedded_platform)
+        }
         _ => {
             eprintln!(
                 "Error: Platform {:?} is not currently implemented",
@@ -84,4 +84,80 @@ impl PlatformCodeGen for WindowsPlatform {
     fn set_architecture(&mut self, arch: Architecture) {
         self.architecture = arch;
     }
+
+    // CodeView keeps one line table per function; the whole text section is
+    // described as one.
+    fn get_line_table_start(&self, path: &str) -> String {
+        format!(
+            ".cv_file 1 {}\n.cv_func_id 0\n{}.Luasm_lines_begin:\n",
+            quoted(path),
+            self.get_section_prefix(&Section::Text)
+        )
+    }
+
+    fn get_line_directive(&self, line: usize) -> String {
+        format!("    .cv_loc 0 1 {} 0\n", line)
+    }
+
+    fn get_line_table_end(&self) -> String {
+        self.get_section_prefix(&Section::Text)
+            + concat!(
+                ".Luasm_lines_end:\n",
+                ".section .debug$S,\"dr\"\n",
+                ".p2align 2\n",
+                ".long 4\n",
+                ".cv_linetable 0, .Luasm_lines_begin, .Luasm_lines_end\n",
+                ".cv_filechecksums\n",
+                ".cv_stringtable\n",
+            )
+    }
+
+    // x64 unwind codes describe the prologue only; the unwinder recognizes
+    // epilogues by their instructions. Other targets use DWARF.
+    fn get_unwind_start(&self, symbol: &str) -> String {
+        match self.architecture {
+            Architecture::AMD64 => format!(".seh_proc {}\n", symbol),
+            _ => ".cfi_startproc\n".to_string(),
+        }
+    }
+
+    fn get_unwind_step(&self, from: &Frame, to: &Frame, prologue: bool) -> String {
+        if self.architecture != Architecture::AMD64 {
+            return cfi_step(from, to);
+        }
+        let mut result = String::new();
+        if !prologue {
+            return result;
+        }
+        let mut depth = from.depth;
+        for (register, _) in to.saved.iter().filter(|saved| !from.saved.contains(saved)) {
+            result.push_str(&format!("    .seh_pushreg {}\n", register));
+            depth += 8;
+        }
+        if to.cfa_register != from.cfa_register {
+            result.push_str(&format!(
+                "    .seh_setframe {}, {}\n",
+                to.cfa_register,
+                depth - to.cfa_offset
+            ));
+        }
+        if to.depth > depth {
+            result.push_str(&format!("    .seh_stackalloc {}\n", to.depth - depth));
+        }
+        result
+    }
+
+    fn get_prologue_end(&self) -> String {
+        match self.architecture {
+            Architecture::AMD64 => "    .seh_endprologue\n".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    fn get_unwind_end(&self) -> String {
+        match self.architecture {
+            Architecture::AMD64 => ".seh_endproc\n".to_string(),
+            _ => ".cfi_endproc\n".to_string(),
+        }
+    }
 }
@@ -16,8 +16,18 @@ TARGETS=(
   "arm32_linux"
   "arm64_macos"
   "arm64_windows"
- # "riscv64_linux"
- # "ppc64_linux"
+  "riscv64_linux"
+  "ppc64_linux"
+  "ppc64le_linux"
+  "mips_linux"
+  "mipsel_linux"
+  "avr_embedded"
+  "z80_embedded"
+  "msp430_embedded"
+  "sparc64_linux"
+  "m68k_linux"
+  "xtensa_linux"
+  "sh4_linux"
 )
 
 # Find all .ua files in examples directory
@@ -99,6 +109,90 @@ for ua_file in "${UA_FILES[@]}"; do
         fi
         ;;
 
+      ppc64le_linux)
+        if command -v powerpc64le-linux-gnu-as >/dev/null 2>&1; then
+          powerpc64le-linux-gnu-as "$asm_file" -o "$obj_file"
+        else
+          llvm-mc --triple=powerpc64le-linux-gnu -arch=ppc64le \
+                  -filetype=obj -o "$obj_file" "$asm_file"
+        fi
+        ;;
+
+      mips_linux)
+        if command -v mips-linux-gnu-as >/dev/null 2>&1; then
+          mips-linux-gnu-as "$asm_file" -o "$obj_file"
+        else
+          llvm-mc --triple=mips-linux-gnu -arch=mips \
+                  -filetype=obj -o "$obj_file" "$asm_file"
+        fi
+        ;;
+
+      mipsel_linux)
+        if command -v mipsel-linux-gnu-as >/dev/null 2>&1; then
+          mipsel-linux-gnu-as "$asm_file" -o "$obj_file"
+        else
+          llvm-mc --triple=mipsel-linux-gnu -arch=mipsel \
+                  -filetype=obj -o "$obj_file" "$asm_file"
+        fi
+        ;;
+
+      avr_embedded)
+        if command -v avr-as >/dev/null 2>&1; then
+          avr-as -mmcu=atmega328p "$asm_file" -o "$obj_file"
+        else
+          llvm-mc --triple=avr -mcpu=atmega328p \
+                  -filetype=obj -o "$obj_file" "$asm_file"
+        fi
+        ;;
+
+      z80_embedded)
+        if command -v sjasmplus >/dev/null 2>&1; then
+          sjasmplus --nologo --raw="$obj_file" "$asm_file"
+        else
+          z88dk-z80asm -b -o"$obj_file" "$asm_file"
+        fi
+        ;;
+
+      msp430_embedded)
+        if command -v msp430-elf-as >/dev/null 2>&1; then
+          msp430-elf-as "$asm_file" -o "$obj_file"
+        else
+          llvm-mc --triple=msp430 -filetype=obj -o "$obj_file" "$asm_file"
+        fi
+        ;;
+
+      sparc64_linux)
+        if command -v sparc64-linux-gnu-as >/dev/null 2>&1; then
+          sparc64-linux-gnu-as "$asm_file" -o "$obj_file"
+        else
+          llvm-mc --triple=sparcv9 -filetype=obj -o "$obj_file" "$asm_file"
+        fi
+        ;;
+
+      m68k_linux)
+        if command -v m68k-linux-gnu-as >/dev/null 2>&1; then
+          m68k-linux-gnu-as "$asm_file" -o "$obj_file"
+        else
+          m68k-elf-as "$asm_file" -o "$obj_file"
+        fi
+        ;;
+
+      xtensa_linux)
+        if command -v xtensa-linux-gnu-as >/dev/null 2>&1; then
+          xtensa-linux-gnu-as --text-section-literals "$asm_file" -o "$obj_file"
+        else
+          xtensa-esp32-elf-as --text-section-literals "$asm_file" -o "$obj_file"
+        fi
+        ;;
+
+      sh4_linux)
+        if command -v sh4-linux-gnu-as >/dev/null 2>&1; then
+          sh4-linux-gnu-as "$asm_file" -o "$obj_file"
+        else
+          sh-elf-as -little "$asm_file" -o "$obj_file"
+        fi
+        ;;
+
       amd64_macos|arm64_macos)
         llvm-mc -arch=$( [[ $target == amd64* ]] && echo x86-64 || echo arm64 ) \
                 -filetype=obj -o "$obj_file" "$asm_file"